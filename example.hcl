@@ -10,6 +10,11 @@ process "test_prod" {
   }
 
   max_memory = "500M"
+
+  labels {
+    team = "payments"
+    env  = "prod"
+  }
 }
 
 process "test" {
@@ -1,11 +1,21 @@
 use super::types::{AgentConfig, AgentInfo, AgentStatus};
 use super::messages::AgentMessage;
+use crate::{config::structs::ClientOptions, process::http};
 use anyhow::{Result, anyhow};
-use std::time::Duration;
+use std::{fs, sync::Arc, time::Duration};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
 use tokio::time::sleep;
-use tokio_tungstenite::{connect_async, tungstenite::Message};
+use tokio_tungstenite::{Connector, client_async_tls_with_config, tungstenite::Message};
 use futures_util::{StreamExt, SinkExt};
 
+use rustls::{
+    ClientConfig, DigitallySignedStruct, RootCertStore, SignatureScheme,
+    client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+    crypto::{self, CryptoProvider},
+    pki_types::{CertificateDer, ServerName, UnixTime, pem::PemObject},
+};
+
 pub struct AgentConnection {
     config: AgentConfig,
     status: AgentStatus,
@@ -44,41 +54,38 @@ impl AgentConnection {
         let server_url = self.config.server_url.trim_end_matches('/');
         
         // Use the same port as HTTP server (WebSocket is now integrated)
-        let ws_url = if server_url.starts_with("https://") {
-            let base = server_url.strip_prefix("https://").unwrap();
-            let (host, port) = if base.contains(':') {
-                let parts: Vec<&str> = base.split(':').collect();
-                let port: u16 = parts.get(1)
-                    .and_then(|p| p.parse().ok())
-                    .unwrap_or(443);
-                (parts[0], port)
-            } else {
-                // No port specified, use default HTTPS port
-                (base, 443)
-            };
-            format!("wss://{}:{}/ws/agent", host, port)
-        } else {
-            let base = server_url.strip_prefix("http://").unwrap_or(server_url);
-            let (host, port) = if base.contains(':') {
-                let parts: Vec<&str> = base.split(':').collect();
-                let port: u16 = parts.get(1)
-                    .and_then(|p| p.parse().ok())
-                    .unwrap_or(80);
-                (parts[0], port)
-            } else {
-                // No port specified, use default HTTP port
-                (base, 80)
-            };
-            format!("ws://{}:{}/ws/agent", host, port)
-        };
+        let ws_url = websocket_url(server_url);
+
+        // Check the server is reachable over HTTP first, so proxy/TLS/DNS
+        // problems are reported clearly instead of as a generic socket error
+        self.preflight(server_url).await?;
 
         println!("[Agent] Connecting to WebSocket: {}", ws_url);
 
-        // Connect to WebSocket server
-        let (ws_stream, _) = connect_async(&ws_url).await
-            .map_err(|e| anyhow!("Failed to connect to WebSocket: {}", e))?;
-        
-        let (mut ws_sender, mut ws_receiver) = ws_stream.split();
+        // Connect to WebSocket server, tunnelling through the proxy if configured.
+        // wss gets the CA and verification settings of the HTTP client
+        let options = &self.config.client;
+        let (host, port) = target(&ws_url)?;
+        let connector = match ws_url.starts_with("wss://") {
+            true => Some(connector(options)?),
+            false => None,
+        };
+
+        let (mut ws_sender, mut ws_receiver) = match &options.proxy {
+            Some(proxy) => {
+                let stream = tunnel(proxy, &host, port).await?;
+                let (ws_stream, _) = client_async_tls_with_config(&ws_url, stream, None, connector).await
+                    .map_err(|e| anyhow!("Failed to connect to WebSocket through proxy: {}", e))?;
+                ws_stream.split()
+            }
+            None => {
+                let stream = TcpStream::connect((host.as_str(), port)).await
+                    .map_err(|e| anyhow!("Failed to connect to WebSocket: {}", e))?;
+                let (ws_stream, _) = client_async_tls_with_config(&ws_url, stream, None, connector).await
+                    .map_err(|e| anyhow!("Failed to connect to WebSocket: {}", e))?;
+                ws_stream.split()
+            }
+        };
 
         // Construct the API endpoint URL
         let api_endpoint = format!("http://{}:{}", self.config.api_address, self.config.api_port);
@@ -197,6 +204,19 @@ impl AgentConnection {
         }
     }
 
+    async fn preflight(&self, server_url: &str) -> Result<()> {
+        let options = &self.config.client;
+        let client = http::build(options)?;
+
+        client
+            .get(format!("{}/health", server_url))
+            .send()
+            .await
+            .map_err(|e| anyhow!("Unable to reach server ({})", http::describe_error(&e, options)))?;
+
+        Ok(())
+    }
+
     pub fn get_info(&self) -> AgentInfo {
         use super::types::ConnectionType;
         let api_endpoint = format!("http://{}:{}", self.config.api_address, self.config.api_port);
@@ -214,3 +234,132 @@ impl AgentConnection {
         }
     }
 }
+
+/// Host and port of the WebSocket server
+fn target(ws_url: &str) -> Result<(String, u16)> {
+    let target = reqwest::Url::parse(ws_url).map_err(|e| anyhow!("Invalid WebSocket URL '{}': {}", ws_url, e))?;
+    let host = target.host_str().ok_or_else(|| anyhow!("WebSocket URL has no host"))?;
+
+    Ok((host.to_string(), target.port_or_known_default().unwrap_or(80)))
+}
+
+/// Open a tunnel to the WebSocket host through an HTTP proxy using CONNECT
+async fn tunnel(proxy: &str, target_host: &str, target_port: u16) -> Result<TcpStream> {
+    let proxy = reqwest::Url::parse(proxy).map_err(|e| anyhow!("Invalid proxy URL '{}': {}", proxy, e))?;
+
+    let proxy_host = proxy.host_str().ok_or_else(|| anyhow!("Proxy URL has no host"))?;
+    let proxy_port = proxy.port_or_known_default().unwrap_or(3128);
+
+    let mut stream = TcpStream::connect((proxy_host, proxy_port))
+        .await
+        .map_err(|e| anyhow!("proxy failure: unable to connect to {}:{}: {}", proxy_host, proxy_port, e))?;
+
+    let request = format!(
+        "CONNECT {target_host}:{target_port} HTTP/1.1\r\nHost: {target_host}:{target_port}\r\n\r\n"
+    );
+    stream.write_all(request.as_bytes()).await?;
+
+    let mut reader = BufReader::new(&mut stream);
+    let mut status = String::new();
+    reader.read_line(&mut status).await?;
+
+    if status.split_whitespace().nth(1) != Some("200") {
+        return Err(anyhow!("proxy failure: CONNECT rejected: {}", status.trim()));
+    }
+
+    // Skip the remaining response headers
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).await? == 0 || line == "\r\n" {
+            break;
+        }
+    }
+
+    Ok(stream)
+}
+
+/// TLS for a wss connection, trusting the same roots as the HTTP client: the bundled ones, `ca_cert`,
+/// or any certificate with `insecure`
+fn connector(options: &ClientOptions) -> Result<Connector> {
+    let provider = Arc::new(crypto::ring::default_provider());
+    let mut roots = RootCertStore { roots: webpki_roots::TLS_SERVER_ROOTS.to_vec() };
+
+    if let Some(path) = &options.ca_cert {
+        let pem = fs::read(path).map_err(|e| anyhow!("unable to read ca certificate '{}': {}", path, e))?;
+        for cert in CertificateDer::pem_slice_iter(&pem) {
+            let cert = cert.map_err(|e| anyhow!("invalid ca certificate '{}': {}", path, e))?;
+            roots.add(cert).map_err(|e| anyhow!("invalid ca certificate '{}': {}", path, e))?;
+        }
+    }
+
+    let mut config = ClientConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()?
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+
+    if options.insecure {
+        log::warn!("TLS certificate verification is disabled for the agent connection");
+        config.dangerous().set_certificate_verifier(Arc::new(AcceptAnyCertificate(provider)));
+    }
+
+    Ok(Connector::Rustls(Arc::new(config)))
+}
+
+/// Verifier of `insecure`, the certificate is accepted while the handshake signatures are still checked
+#[derive(Debug)]
+struct AcceptAnyCertificate(Arc<CryptoProvider>);
+
+impl ServerCertVerifier for AcceptAnyCertificate {
+    fn verify_server_cert(&self, _: &CertificateDer<'_>, _: &[CertificateDer<'_>], _: &ServerName<'_>, _: &[u8], _: UnixTime) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(&self, message: &[u8], cert: &CertificateDer<'_>, dss: &DigitallySignedStruct) -> Result<HandshakeSignatureValid, rustls::Error> {
+        crypto::verify_tls12_signature(message, cert, dss, &self.0.signature_verification_algorithms)
+    }
+
+    fn verify_tls13_signature(&self, message: &[u8], cert: &CertificateDer<'_>, dss: &DigitallySignedStruct) -> Result<HandshakeSignatureValid, rustls::Error> {
+        crypto::verify_tls13_signature(message, cert, dss, &self.0.signature_verification_algorithms)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> { self.0.signature_verification_algorithms.supported_schemes() }
+}
+
+/// Websocket endpoint of a server url like `https://host:port/opm`, keeping the base path it is served under
+fn websocket_url(server_url: &str) -> String {
+    let (scheme, rest, default_port) = match server_url.strip_prefix("https://") {
+        Some(rest) => ("wss", rest, 443),
+        None => ("ws", server_url.strip_prefix("http://").unwrap_or(server_url), 80),
+    };
+
+    let (authority, path) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+    let (host, port) = match authority.split_once(':') {
+        Some((host, port)) => (host, port.parse().unwrap_or(default_port)),
+        None => (authority, default_port),
+    };
+
+    format!("{scheme}://{host}:{port}{}/ws/agent", path.trim_end_matches('/'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_websocket_url() {
+        assert_eq!(websocket_url("http://10.0.0.1:9876"), "ws://10.0.0.1:9876/ws/agent");
+        assert_eq!(websocket_url("https://opm.internal"), "wss://opm.internal:443/ws/agent");
+        assert_eq!(websocket_url("https://proxy.internal/opm/"), "wss://proxy.internal:443/opm/ws/agent");
+        assert_eq!(websocket_url("localhost:9876/opm"), "ws://localhost:9876/opm/ws/agent");
+    }
+
+    #[test]
+    fn test_connector() {
+        let missing = ClientOptions { ca_cert: Some("/nonexistent/opm-ca.pem".to_string()), ..Default::default() };
+        assert!(connector(&missing).err().unwrap().to_string().contains("unable to read ca certificate"));
+
+        let insecure = ClientOptions { insecure: true, ..Default::default() };
+        assert!(matches!(connector(&insecure), Ok(Connector::Rustls(_))));
+        assert_eq!(target("wss://opm.internal:443/opm/ws/agent").unwrap(), (String::from("opm.internal"), 443));
+    }
+}
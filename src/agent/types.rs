@@ -1,3 +1,4 @@
+use crate::config::structs::{ClientOptions, Seen};
 use serde::{Deserialize, Serialize};
 use std::time::SystemTime;
 use uuid::Uuid;
@@ -16,6 +17,10 @@ pub struct AgentConfig {
     pub heartbeat_interval: u64, // seconds
     pub api_address: String, // Address where agent API is listening
     pub api_port: u16,
+    #[serde(flatten)]
+    pub client: ClientOptions, // Proxy and TLS settings for reaching the server
+    #[serde(flatten)]
+    pub seen: Seen, // What the server reported when the connection was checked
 }
 
 impl AgentConfig {
@@ -37,6 +42,8 @@ impl AgentConfig {
             heartbeat_interval: 30, // 30 seconds default
             api_address: "0.0.0.0".to_string(),
             api_port: AGENT_DEFAULT_API_PORT,
+            client: ClientOptions::default(),
+            seen: Seen::default(),
         }
     }
 }
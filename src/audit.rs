@@ -0,0 +1,247 @@
+use chrono::{DateTime, Utc};
+use global_placeholders::global;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    sync::{Mutex, Once},
+};
+
+/// The log moves to `audit.jsonl.1` once it grows past this size, replacing the previous one
+pub const MAX_BYTES: u64 = 5 * 1024 * 1024;
+
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Outcome {
+    Ok,
+    Failed,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Via {
+    Cli,
+    Api,
+    /// The local command socket of the daemon
+    Ipc,
+}
+
+/// One mutating operation, stored as a line of `~/.opm/audit.jsonl`
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, PartialEq)]
+pub struct Entry {
+    #[schema(value_type = String, example = "2000-01-01T01:00:00.000Z")]
+    pub time: DateTime<Utc>,
+    #[schema(example = "stop")]
+    pub action: String,
+    /// Processes as they were named, comma separated
+    #[schema(example = "api,worker")]
+    pub target: String,
+    /// Remote server the operation was sent to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub server: Option<String>,
+    pub outcome: Outcome,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Local user for the cli, `token` or `anonymous` for the api
+    #[schema(example = "alice")]
+    pub actor: String,
+    pub via: Via,
+    /// Address of the api client
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(example = "10.0.0.12")]
+    pub source: Option<String>,
+}
+
+/// Narrows what `opm audit` and `/daemon/audit` return
+#[derive(Debug, Clone, Default)]
+pub struct Filter {
+    pub process: Option<String>,
+    pub action: Option<String>,
+}
+
+impl Entry {
+    pub fn new(action: &str, target: &str, actor: String, via: Via) -> Self {
+        Self {
+            time: Utc::now(),
+            action: action.to_string(),
+            target: target.to_string(),
+            server: None,
+            outcome: Outcome::Ok,
+            error: None,
+            actor,
+            via,
+            source: None,
+        }
+    }
+
+    pub fn failed(mut self, error: Option<String>) -> Self {
+        self.outcome = Outcome::Failed;
+        self.error = error;
+        self
+    }
+}
+
+impl Filter {
+    fn matches(&self, entry: &Entry) -> bool {
+        self.action.as_ref().is_none_or(|action| entry.action == *action)
+            && self.process.as_ref().is_none_or(|process| entry.target.split(',').any(|target| target == process))
+    }
+}
+
+/// Action name for the audit log, api aliases map to the cli command
+pub fn action(method: &str) -> &str {
+    match method {
+        "kill" => "stop",
+        "delete" => "remove",
+        "clean" => "flush",
+        "clear_env" => "reset_env",
+        method => method,
+    }
+}
+
+/// Name of the user running opm, with the invoking user when run through sudo
+pub fn local_actor() -> String {
+    let uid = unsafe { libc::getuid() };
+    let user = crate::process::unix::get_user_name(uid).unwrap_or_else(|| format!("uid {uid}"));
+
+    match std::env::var("SUDO_USER") {
+        Ok(sudo) if sudo != user => format!("{user} (sudo by {sudo})"),
+        _ => user,
+    }
+}
+
+pub fn append(path: &str, entry: &Entry) -> io::Result<()> {
+    let mut line = serde_json::to_string(entry)?;
+    line.push('\n');
+
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    file.write_all(line.as_bytes())?;
+
+    if file.metadata()?.len() > MAX_BYTES {
+        fs::rename(path, format!("{path}.1"))?;
+    }
+
+    Ok(())
+}
+
+/// Last `lines` matching entries, oldest first, the rotated log included
+pub fn read(path: &str, lines: usize, filter: &Filter) -> Vec<Entry> {
+    let mut entries: Vec<Entry> = [format!("{path}.1"), path.to_string()]
+        .iter()
+        .filter_map(|path| fs::read_to_string(path).ok())
+        .flat_map(|contents| contents.lines().filter_map(|line| serde_json::from_str(line).ok()).collect::<Vec<Entry>>())
+        .filter(|entry| filter.matches(entry))
+        .collect();
+
+    entries.drain(..entries.len().saturating_sub(lines));
+    entries
+}
+
+/// Add an entry to the audit log. Failing to write it never fails the operation itself.
+pub fn record(entry: Entry) {
+    if let Err(err) = append(&global!("opm.audit"), &entry) {
+        log::warn!("Cannot write audit entry for {} {}: {err}", entry.action, entry.target);
+    }
+}
+
+pub fn tail(lines: usize, filter: &Filter) -> Vec<Entry> {
+    read(&global!("opm.audit"), lines, filter)
+}
+
+/// Cli operation in flight, with the pid that started it so forked daemons do not record it
+static PENDING: Mutex<Option<(u32, Entry)>> = Mutex::new(None);
+static AT_EXIT: Once = Once::new();
+
+extern "C" fn record_exit() {
+    // Still pending at exit means the command bailed out with an error
+    if let Some(entry) = take_pending() {
+        record(entry.failed(None));
+    }
+}
+
+fn take_pending() -> Option<Entry> {
+    let mut pending = PENDING.lock().ok()?;
+
+    match pending.take() {
+        Some((pid, entry)) if pid == std::process::id() => Some(entry),
+        other => {
+            *pending = other;
+            None
+        }
+    }
+}
+
+/// Start recording a cli operation, it is logged as failed if opm exits before `finish`
+pub fn begin(entry: Entry) {
+    if let Ok(mut pending) = PENDING.lock() {
+        *pending = Some((std::process::id(), entry));
+    }
+
+    AT_EXIT.call_once(|| unsafe {
+        libc::atexit(record_exit);
+    });
+}
+
+/// The cli operation started with `begin` went through
+pub fn finish() {
+    if let Some(entry) = take_pending() {
+        record(entry);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(action: &str, target: &str) -> Entry {
+        Entry::new(action, target, String::from("alice"), Via::Cli)
+    }
+
+    #[test]
+    fn test_append_and_filter() {
+        let path = format!("{}/opm-audit-test-{}.jsonl", std::env::temp_dir().display(), std::process::id());
+
+        append(&path, &entry("start", "api")).unwrap();
+        append(&path, &entry("stop", "api,worker")).unwrap();
+        append(&path, &entry("stop", "web").failed(Some(String::from("Process was not found")))).unwrap();
+
+        let stops = read(&path, 50, &Filter { action: Some(String::from("stop")), ..Filter::default() });
+        assert_eq!(stops.iter().map(|entry| entry.target.as_str()).collect::<Vec<_>>(), vec!["api,worker", "web"]);
+        assert_eq!(stops[1].outcome, Outcome::Failed);
+
+        let worker = read(&path, 50, &Filter { process: Some(String::from("worker")), ..Filter::default() });
+        assert_eq!(worker, vec![stops[0].clone()]);
+
+        let last = read(&path, 1, &Filter::default());
+        assert_eq!(last[0].target, "web");
+
+        fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_rotation() {
+        let path = format!("{}/opm-audit-rotate-{}.jsonl", std::env::temp_dir().display(), std::process::id());
+        let big = entry("adjust", &"x".repeat(MAX_BYTES as usize));
+
+        append(&path, &entry("save", "all")).unwrap();
+        append(&path, &big).unwrap();
+        assert!(fs::metadata(&path).is_err());
+
+        append(&path, &entry("restore", "all")).unwrap();
+        let actions: Vec<String> = read(&path, 50, &Filter::default()).into_iter().map(|entry| entry.action).collect();
+        assert_eq!(actions, vec!["save", "adjust", "restore"]);
+
+        fs::remove_file(&path).ok();
+        fs::remove_file(format!("{path}.1")).ok();
+    }
+
+    #[test]
+    fn test_serialized_line() {
+        let line = serde_json::to_string(&entry("flush", "3")).unwrap();
+
+        assert!(line.contains(r#""outcome":"ok""#) && line.contains(r#""via":"cli""#));
+        assert!(!line.contains("server") && !line.contains("error") && !line.contains("source"));
+    }
+}
@@ -0,0 +1,514 @@
+//! `opm adjust`, one set of changes applied to a process or to many at once, e.g. a new memory limit for
+//! every worker. Several processes are changed in one runner session and saved once.
+
+use super::{Internal, Item, Items, connect, format, internal::COMMAND_PREVIEW_WIDTH};
+use chrono::Utc;
+use colored::Colorize;
+use macros_rs::{crashln, string, ternary, then};
+use std::{collections::BTreeMap, thread};
+
+use opm::{
+    helpers, notice,
+    process::{
+        DEFAULT_WATCH_COOLDOWN, Process, Runner,
+        alerts::Alert,
+        cron::Schedule,
+        environment, health, http, labels,
+        limits::Limits,
+        lograte::MaxLogRate,
+        memlimit,
+        shell::Shell,
+    },
+};
+
+use tabled::{
+    Table, Tabled,
+    settings::{
+        Color,
+        style::{BorderColor, Style},
+    },
+};
+
+/// Processes adjusted at the same time on a remote server, each one is a request of its own
+const CONCURRENCY: usize = 8;
+
+/// What `opm adjust` was asked to change, None or empty fields are left as they are
+#[derive(Default)]
+pub struct Changes {
+    pub command: Option<String>,
+    pub name: Option<String>,
+    pub labels: Vec<(String, String)>,
+    pub unlabel: Vec<String>,
+    pub url: Option<Option<String>>,
+    pub description: Option<Option<String>>,
+    pub group: Option<Option<String>>,
+    pub max_restarts: Option<Option<u64>>,
+    pub watch_cooldown: Option<Option<u64>>,
+    pub limits: Limits,
+    pub collect_cores: Option<bool>,
+    pub supervised: Option<bool>,
+    pub track_children: Option<bool>,
+    pub shell: Option<Shell>,
+    pub alerts: Option<Vec<Alert>>,
+    pub max_log_rate: Option<Option<MaxLogRate>>,
+    pub max_memory_action: Option<memlimit::Action>,
+    pub restart_cron: Option<Option<Schedule>>,
+    pub health: health::Changes,
+    pub env: environment::Changes,
+    /// Restart each adjusted process, so it uses the changes right away
+    pub restart: bool,
+}
+
+/// One field of a process going from one value to another
+#[derive(Debug, PartialEq)]
+pub struct Update {
+    pub field: &'static str,
+    pub from: String,
+    pub to: String,
+    /// Only used once the process is restarted
+    pub next_restart: bool,
+}
+
+impl Update {
+    fn new(field: &'static str, from: String, to: String) -> Self { Update { field, from, to, next_restart: false } }
+
+    fn at_restart(self) -> Self { Update { next_restart: true, ..self } }
+
+    pub fn changed(&self) -> bool { self.from != self.to }
+}
+
+fn enabled(value: bool) -> String { string!(ternary!(value, "enabled", "disabled")) }
+
+impl Changes {
+    pub fn is_empty(&self) -> bool {
+        self.command.is_none()
+            && self.name.is_none()
+            && self.labels.is_empty()
+            && self.unlabel.is_empty()
+            && self.url.is_none()
+            && self.description.is_none()
+            && self.group.is_none()
+            && self.max_restarts.is_none()
+            && self.watch_cooldown.is_none()
+            && self.limits.is_empty()
+            && self.collect_cores.is_none()
+            && self.supervised.is_none()
+            && self.track_children.is_none()
+            && self.shell.is_none()
+            && self.alerts.is_none()
+            && self.max_log_rate.is_none()
+            && self.max_memory_action.is_none()
+            && self.restart_cron.is_none()
+            && self.health.is_empty()
+            && self.env.is_empty()
+    }
+
+    /// Refuse a command that changes nothing or a bad variable, before any process is touched
+    pub fn check(&self) {
+        if let Err(err) = self.env.validate() {
+            crashln!("{} Invalid environment: {err}", *helpers::FAIL);
+        }
+
+        if self.is_empty() {
+            crashln!(
+                "{} At least one of --command, --name, --label, --unlabel, --url, --no-url, --description, --no-description, --group, --no-group, --[no-]max-restarts, --[no-]watch-cooldown, --umask, --ulimit-*, --[no-]collect-cores, --[no-]supervised, --[no-]track-children, --shell, --shell-arg, --no-shell, --alert, --no-alerts, --[no-]max-log-rate, --max-memory-action, --[no-]cron-restart, --health-*, --no-health, --env-add or --env-remove must be provided",
+                *helpers::FAIL
+            );
+        }
+    }
+
+    /// Change the fields kept in the dump, description, group and environment go through the runner
+    /// since a remote daemon stores those itself. Labels that end up invalid change nothing.
+    pub fn apply(&self, process: &mut Process) -> Result<Vec<Update>, String> {
+        let mut updates = vec![];
+
+        // Removals are applied before additions
+        let labels = (!self.labels.is_empty() || !self.unlabel.is_empty()).then(|| {
+            let mut updated = process.labels.clone();
+            self.unlabel.iter().for_each(|key| {
+                updated.remove(key);
+            });
+            updated.extend(self.labels.iter().cloned());
+            updated
+        });
+
+        if let Some(labels) = &labels {
+            labels::validate(labels).map_err(|err| format!("Invalid labels: {err}"))?;
+        }
+
+        // Worked out before anything changes, an interval for a process without a check is refused
+        let health = (!self.health.is_empty()).then(|| self.health.apply(process.health.as_ref())).transpose()?;
+
+        if let Some(command) = &self.command {
+            let preview = |command: &str| helpers::truncate_command(command, COMMAND_PREVIEW_WIDTH).unwrap_or_else(|| command.to_string());
+            updates.push(Update::new("command", preview(&process.script), preview(command)));
+            process.script = command.clone();
+        }
+
+        if let Some(name) = &self.name {
+            updates.push(Update::new("name", process.name.clone(), name.clone()));
+            process.name = name.clone();
+        }
+
+        if let Some(labels) = labels {
+            updates.push(Update::new("labels", labels::describe(&process.labels), labels::describe(&labels)));
+            process.labels = labels;
+        }
+
+        if let Some(url) = &self.url {
+            let describe = |url: &Option<String>| url.clone().unwrap_or_else(|| string!("none"));
+            updates.push(Update::new("url", describe(&process.url), describe(url)));
+            process.url = url.clone();
+        }
+
+        // Crashes already counted stay counted, see `reset_counters`
+        if let Some(max_restarts) = self.max_restarts {
+            let describe = |limit: Option<u64>| limit.map_or_else(|| string!("daemon.restarts"), |limit| limit.to_string());
+            updates.push(Update::new("restart limit", describe(process.max_restarts), describe(max_restarts)));
+            process.max_restarts = max_restarts;
+        }
+
+        if let Some(watch_cooldown) = self.watch_cooldown {
+            let describe = |cooldown: Option<u64>| cooldown.map_or_else(|| format!("default ({DEFAULT_WATCH_COOLDOWN}s)"), |secs| format!("{secs}s"));
+            updates.push(Update::new("watch cooldown", describe(process.watch.cooldown_secs), describe(watch_cooldown)));
+            process.watch.cooldown_secs = watch_cooldown;
+        }
+
+        if !self.limits.is_empty() {
+            let updated = Limits {
+                umask: self.limits.umask.or(process.umask),
+                nofile: self.limits.nofile.or(process.ulimit_nofile),
+                core: self.limits.core.or(process.ulimit_core),
+            };
+
+            updates.push(Update::new("limits", process.limits().describe(), updated.describe()).at_restart());
+            (process.umask, process.ulimit_nofile, process.ulimit_core) = (updated.umask, updated.nofile, updated.core);
+        }
+
+        if let Some(collect_cores) = self.collect_cores {
+            updates.push(Update::new("core collection", enabled(process.collect_cores), enabled(collect_cores)).at_restart());
+            process.collect_cores = collect_cores;
+        }
+
+        if let Some(supervised) = self.supervised {
+            updates.push(Update::new("supervision", enabled(process.supervised), enabled(supervised)).at_restart());
+            process.supervised = supervised;
+        }
+
+        // Only the given parts are replaced, --no-shell clears both
+        if let Some(shell) = &self.shell {
+            let current = process.custom_shell();
+            let updated = match shell.is_empty() {
+                true => Shell::default(),
+                false => Shell {
+                    program: shell.program.clone().or(current.program.clone()),
+                    args: shell.args.clone().or(current.args.clone()),
+                },
+            };
+
+            updates.push(Update::new("shell", current.describe(), updated.describe()).at_restart());
+            (process.shell, process.shell_args) = (updated.program, updated.args);
+        }
+
+        if let Some(alerts) = &self.alerts {
+            let describe = |alerts: &[Alert]| match alerts.is_empty() {
+                true => string!("none"),
+                false => alerts.iter().map(Alert::to_string).collect::<Vec<_>>().join(", "),
+            };
+            updates.push(Update::new("alerts", describe(&process.alerts), describe(alerts)));
+            process.alerts = alerts.clone();
+        }
+
+        if let Some(max_log_rate) = &self.max_log_rate {
+            let describe = |rate: &Option<MaxLogRate>| rate.as_ref().map_or_else(|| string!("none"), MaxLogRate::to_string);
+            updates.push(Update::new("max log rate", describe(&process.max_log_rate), describe(max_log_rate)));
+            process.max_log_rate = max_log_rate.clone();
+        }
+
+        if let Some(action) = self.max_memory_action {
+            updates.push(Update::new("memory action", process.max_memory_action.to_string(), action.to_string()));
+            process.max_memory_action = action;
+        }
+
+        if let Some(restart_cron) = &self.restart_cron {
+            let describe = |schedule: &Option<Schedule>| schedule.as_ref().map_or_else(|| string!("none"), Schedule::to_string);
+            updates.push(Update::new("cron restart", describe(&process.restart_cron), describe(restart_cron)));
+
+            // A new schedule counts from now, like one given to opm start
+            if process.restart_cron != *restart_cron {
+                process.restart_cron_since = restart_cron.as_ref().map(|_| Utc::now());
+                process.restart_cron = restart_cron.clone();
+            }
+        }
+
+        if let Some(health) = health {
+            let describe = |check: &Option<health::Check>| check.as_ref().map_or_else(|| string!("none"), health::Check::describe);
+            updates.push(Update::new("health check", describe(&process.health), describe(&health)));
+
+            // Results of the old check say nothing about the new one
+            if process.health != health {
+                process.health_status = None;
+                process.health = health;
+            }
+        }
+
+        if let Some(track_children) = self.track_children {
+            updates.push(Update::new("child tracking", enabled(process.track_children), enabled(track_children)));
+            process.track_children = track_children;
+            if !track_children {
+                process.children = vec![];
+            }
+        }
+
+        Ok(updates)
+    }
+
+    /// Every update the changes would make to `process`, worked out on a copy of it
+    pub fn preview(&self, process: &Process) -> Result<Vec<Update>, String> {
+        let mut copy = process.clone();
+        let mut updates = self.apply(&mut copy)?;
+        let show = |value: Option<&str>| value.unwrap_or("none").to_string();
+
+        if let Some(description) = &self.description {
+            updates.push(Update::new("description", show(copy.description.as_deref()), show(description.as_deref())));
+        }
+
+        if let Some(group) = &self.group {
+            updates.push(Update::new("group", show(copy.group.as_deref()), show(group.as_deref())));
+        }
+
+        // Each variable that changes is an update of its own
+        for change in self.env.apply(&mut copy.env)? {
+            updates.push(Update::new("environment", String::new(), change.to_string()).at_restart());
+        }
+
+        Ok(updates)
+    }
+}
+
+/// Ids of the processes to adjust, patterns and groups were expanded to them by `cli::expand`
+pub fn targets(runner: &Runner, items: &Items) -> Vec<usize> {
+    if items.is_all() {
+        return runner.list.keys().copied().collect();
+    }
+
+    let mut ids: Vec<usize> = items
+        .items
+        .iter()
+        .map(|item| match item {
+            Item::Id(id) if runner.list.contains_key(id) => *id,
+            Item::Id(id) => crashln!("{} Process ({id}) not found", *helpers::FAIL),
+            Item::Name(name) | Item::Pattern(name) | Item::Group(name) => match runner.list.iter().find(|(_, process)| process.name == *name) {
+                Some((id, _)) => *id,
+                None => crashln!("{} Process ({name}) not found", *helpers::FAIL),
+            },
+        })
+        .collect();
+
+    ids.dedup();
+    ids
+}
+
+#[derive(Tabled)]
+struct Row {
+    id: usize,
+    name: String,
+    changed: String,
+    result: String,
+}
+
+/// Fields that differ, comma separated
+fn changed(updates: &[Update]) -> String {
+    let mut fields: Vec<&str> = updates.iter().filter(|update| update.changed()).map(|update| update.field).collect();
+    fields.dedup();
+    fields.join(", ")
+}
+
+/// Id and name of a process with the updates adjusting it would make
+type Preview = (usize, String, Result<Vec<Update>, String>);
+
+/// Dry run of several processes, each distinct update once with the processes it would be made to
+fn print_diff(previews: &[Preview]) {
+    let mut diff: BTreeMap<(&str, &str, &str), Vec<&str>> = BTreeMap::new();
+
+    for (_, name, updates) in previews {
+        for update in updates.iter().flatten().filter(|update| update.changed()) {
+            diff.entry((update.field, &update.from, &update.to)).or_default().push(name);
+        }
+    }
+
+    if diff.is_empty() {
+        notice!("{} Nothing would change", *helpers::SUCCESS);
+        return;
+    }
+
+    for ((field, from, to), names) in diff {
+        let change = match from.is_empty() {
+            true => to.to_string(),
+            false => format!("'{from}' -> '{to}'"),
+        };
+        notice!("  {} {field}: {change} ({}: {})", "~".yellow(), names.len(), names.join(", "));
+    }
+}
+
+/// Apply the description, group and environment of `changes` on a remote server, `CONCURRENCY` processes at a time
+fn remote_apply(runner: &Runner, ids: &[usize], changes: &Changes) -> BTreeMap<usize, String> {
+    let Some(remote) = &runner.remote else { return BTreeMap::new() };
+
+    let adjust = |id: usize| -> Result<(), String> {
+        if let Some(description) = &changes.description {
+            http::describe(remote, id, description).map_err(|err| format!("description: {err}"))?;
+        }
+        if let Some(group) = &changes.group {
+            http::group(remote, id, group).map_err(|err| format!("group: {err}"))?;
+        }
+        if !changes.env.is_empty() {
+            http::adjust_env(remote, id, &changes.env).map_err(|err| format!("environment: {err}"))?;
+        }
+        Ok(())
+    };
+
+    let adjust = &adjust;
+    ids.chunks(CONCURRENCY)
+        .flat_map(|chunk| {
+            thread::scope(|scope| {
+                let handles: Vec<_> = chunk.iter().map(|id| (*id, scope.spawn(move || adjust(*id)))).collect();
+                handles.into_iter().filter_map(|(id, handle)| handle.join().unwrap_or_else(|_| Err(string!("request panicked"))).err().map(|err| (id, err))).collect::<Vec<_>>()
+            })
+        })
+        .collect()
+}
+
+/// Apply `changes` to a process of the runner, a remote daemon is sent the description, group and environment by `remote_apply`
+fn write(runner: &mut Runner, id: usize, changes: &Changes) -> Result<(), String> {
+    changes.apply(runner.process(id))?;
+
+    if runner.remote.is_some() {
+        return Ok(());
+    }
+
+    if let Some(description) = &changes.description {
+        runner.set_description(id, description.clone())?;
+    }
+    if let Some(group) = &changes.group {
+        runner.set_group(id, group.clone())?;
+    }
+    if !changes.env.is_empty() {
+        runner.adjust_env(id, &changes.env)?;
+    }
+    Ok(())
+}
+
+/// Adjust every process of `ids` with the same changes, or only show what would change with `dry_run`
+pub fn run(ids: &[usize], changes: &Changes, dry_run: bool, server_name: &String) {
+    let mut runner = connect(server_name);
+    let (kind, _) = format(server_name);
+
+    let previews: Vec<Preview> = ids
+        .iter()
+        .filter_map(|id| runner.list.get(id).map(|process| (*id, process.name.clone(), changes.preview(process))))
+        .collect();
+
+    if dry_run {
+        notice!("{} Dry run of adjusting {} {kind}processes, nothing is written", *helpers::SUCCESS, previews.len());
+        print_diff(&previews);
+        return;
+    }
+
+    notice!("{} Adjusting {} {kind}processes", *helpers::SUCCESS, previews.len());
+
+    // Only the processes something changes for are written, a bad label on one leaves the rest to be adjusted
+    let mut results: BTreeMap<usize, Result<String, String>> = BTreeMap::new();
+    let mut adjusted = vec![];
+
+    for (id, _, preview) in &previews {
+        let result = match preview {
+            Err(err) => Err(err.clone()),
+            Ok(updates) if !updates.iter().any(Update::changed) => Ok(String::new()),
+            Ok(updates) => write(&mut runner, *id, changes).map(|_| changed(updates)),
+        };
+
+        then!(matches!(&result, Ok(fields) if !fields.is_empty()), adjusted.push(*id));
+        results.insert(*id, result);
+    }
+
+    runner.save();
+
+    for (id, err) in remote_apply(&runner, &adjusted, changes) {
+        adjusted.retain(|adjusted| *adjusted != id);
+        results.insert(id, Err(err));
+    }
+
+    let rows = previews.iter().map(|(id, name, _)| {
+        let (changed, result) = match &results[id] {
+            Ok(fields) if fields.is_empty() => (string!("-"), "unchanged".bright_black().to_string()),
+            Ok(fields) => (fields.clone(), "adjusted".green().to_string()),
+            Err(err) => (string!("-"), format!("{} {err}", "error:".red())),
+        };
+        Row { id: *id, name: name.clone(), changed, result }
+    });
+
+    let table = Table::new(rows).with(Style::rounded().remove_horizontals()).with(BorderColor::filled(Color::FG_BRIGHT_BLACK)).to_string();
+    notice!("{table}");
+
+    let failed = results.values().filter(|result| result.is_err()).count();
+    if changes.restart {
+        for id in &adjusted {
+            runner = Internal {
+                id: *id,
+                server_name,
+                kind: kind.clone(),
+                runner: runner.clone(),
+            }
+            .restart(&None, &None, false, false, true);
+        }
+    }
+
+    if failed > 0 {
+        crashln!("{} {failed} of {} processes could not be adjusted", *helpers::FAIL, previews.len());
+    }
+    notice!("{} Adjusted {} of {} {kind}processes ✓", *helpers::SUCCESS, adjusted.len(), previews.len());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn process(name: &str) -> Process {
+        Process { name: name.to_string(), script: string!("node index.js"), path: PathBuf::from("/srv"), ..Default::default() }
+    }
+
+    #[test]
+    fn test_preview_leaves_the_process() {
+        let changes = Changes {
+            max_restarts: Some(Some(5)),
+            labels: vec![(string!("team"), string!("payments"))],
+            env: environment::Changes { add: vec![(string!("LOG_LEVEL"), string!("debug"))], remove: vec![] },
+            ..Default::default()
+        };
+        let worker = process("worker-1");
+        let updates = changes.preview(&worker).unwrap();
+
+        assert_eq!(changed(&updates), "labels, restart limit, environment");
+        assert_eq!(updates[1], Update::new("restart limit", string!("daemon.restarts"), string!("5")));
+        assert!(updates[2].next_restart);
+        assert_eq!(worker.max_restarts, None);
+        assert!(worker.labels.is_empty());
+    }
+
+    #[test]
+    fn test_unchanged_and_invalid() {
+        let same = Changes { supervised: Some(false), ..Default::default() };
+        assert_eq!(changed(&same.preview(&process("api")).unwrap()), "");
+
+        let invalid = Changes { labels: vec![(string!(""), string!("x"))], command: Some(string!("node other.js")), ..Default::default() };
+        let mut api = process("api");
+        assert!(invalid.apply(&mut api).unwrap_err().starts_with("Invalid labels"));
+        assert_eq!(api.script, "node index.js");
+
+        let interval = Changes { health: health::Changes { interval: Some(30), ..Default::default() }, command: Some(string!("node other.js")), ..Default::default() };
+        assert!(interval.apply(&mut api).unwrap_err().contains("no health check"));
+        assert_eq!(api.script, "node index.js");
+    }
+}
@@ -1,3 +1,5 @@
+use std::fmt;
+
 pub trait Validatable {
     fn from_id(id: usize) -> Self;
     fn from_string(s: String) -> Self;
@@ -14,6 +16,10 @@ pub enum Args {
 pub enum Item {
     Id(usize),
     Name(String),
+    /// A name with `*` or `?` wildcards, expanded to the processes it matches before the command runs
+    Pattern(String),
+    /// `group:NAME`, expanded to every member of the group before the command runs
+    Group(String),
 }
 
 // Wrapper to support multiple items
@@ -32,7 +38,7 @@ impl Items {
     }
 
     pub fn is_all(&self) -> bool {
-        self.items.len() == 1 && self.items[0].get_string() == Some("all")
+        matches!(self.items.as_slice(), [Item::Name(name)] if name == "all")
     }
 }
 
@@ -57,17 +63,44 @@ impl Validatable for Item {
         Item::Id(id)
     }
     fn from_string(s: String) -> Self {
-        Item::Name(s)
+        name_item(&s)
     }
 
     fn get_string(&self) -> Option<&str> {
         match self {
             Item::Id(_) => None,
-            Item::Name(s) => Some(s),
+            Item::Name(s) | Item::Pattern(s) | Item::Group(s) => Some(s),
+        }
+    }
+}
+
+impl fmt::Display for Args {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Args::Id(id) => write!(f, "{id}"),
+            Args::Script(script) => write!(f, "{script}"),
         }
     }
 }
 
+impl fmt::Display for Item {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Item::Id(id) => write!(f, "{id}"),
+            Item::Name(name) | Item::Pattern(name) => write!(f, "{name}"),
+            Item::Group(group) => write!(f, "group:{group}"),
+        }
+    }
+}
+
+/// Comma separated, the way the items were given
+impl fmt::Display for Items {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let items: Vec<String> = self.items.iter().map(Item::to_string).collect();
+        write!(f, "{}", items.join(","))
+    }
+}
+
 pub fn validate<T: Validatable>(s: &str) -> Result<T, String> {
     if let Ok(id) = s.parse::<usize>() {
         Ok(T::from_id(id))
@@ -99,9 +132,113 @@ pub fn validate_items(s: &str) -> Result<Items, String> {
         if let Ok(id) = part.parse::<usize>() {
             items.push(Item::Id(id));
         } else {
-            items.push(Item::Name(part.to_owned()));
+            items.push(name_item(part));
         }
     }
 
     Ok(Items::multiple(items))
 }
+
+/// A process given by name, a pattern when it has `*` or `?` in it unless written as `name:'lit*eral'`
+fn name_item(s: &str) -> Item {
+    if let Some(group) = s.strip_prefix("group:") {
+        return Item::Group(group.to_owned());
+    }
+
+    if let Some(literal) = s.strip_prefix("name:") {
+        let literal = literal.strip_prefix('\'').and_then(|rest| rest.strip_suffix('\'')).unwrap_or(literal);
+        return Item::Name(literal.to_owned());
+    }
+
+    match s.contains(['*', '?']) {
+        true => Item::Pattern(s.to_owned()),
+        false => Item::Name(s.to_owned()),
+    }
+}
+
+/// Whether `name` matches `pattern`, `*` standing for any run of characters and `?` for exactly one
+pub fn glob_matches(pattern: &str, name: &str) -> bool {
+    let (pattern, name): (Vec<char>, Vec<char>) = (pattern.chars().collect(), name.chars().collect());
+    let (mut p, mut n) = (0, 0);
+    // Last `*` seen and the position in `name` it is tried to stop at, moved on when the rest fails
+    let mut star: Option<(usize, usize)> = None;
+
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((star_p, star_n)) => {
+                    star = Some((star_p, star_n + 1));
+                    (p, n) = (star_p + 1, star_n + 1);
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(items: &Items) -> Vec<String> {
+        items
+            .items
+            .iter()
+            .map(|item| match item {
+                Item::Id(id) => format!("id:{id}"),
+                Item::Name(name) => format!("name:{name}"),
+                Item::Pattern(pattern) => format!("pattern:{pattern}"),
+                Item::Group(group) => format!("group:{group}"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_patterns_in_items() {
+        let items = validate_items("api-*, 3, worker-?,db").unwrap();
+        assert_eq!(kinds(&items), ["pattern:api-*", "id:3", "pattern:worker-?", "name:db"]);
+
+        // Quoted after `name:`, wildcards are part of the name
+        let items = validate_items("name:'lit*eral',name:plain?").unwrap();
+        assert_eq!(kinds(&items), ["name:lit*eral", "name:plain?"]);
+
+        assert!(matches!(validate::<Item>("api-*"), Ok(Item::Pattern(pattern)) if pattern == "api-*"));
+        assert!(matches!(validate::<Item>("name:'api-*'"), Ok(Item::Name(name)) if name == "api-*"));
+        assert!(matches!(validate::<Args>("rm *.tmp"), Ok(Args::Script(script)) if script == "rm *.tmp"));
+        assert!(validate_items("all").unwrap().is_all());
+    }
+
+    #[test]
+    fn test_groups_in_items() {
+        let items = validate_items("group:api, 3,name:group:x").unwrap();
+        assert_eq!(kinds(&items), ["group:api", "id:3", "name:group:x"]);
+        assert_eq!(validate_items("group:api,3").unwrap().to_string(), "group:api,3");
+
+        assert!(matches!(validate::<Item>("group:web"), Ok(Item::Group(group)) if group == "web"));
+        assert!(!validate_items("group:all").unwrap().is_all());
+    }
+
+    #[test]
+    fn test_glob_matches() {
+        assert!(glob_matches("api-*", "api-users"));
+        assert!(glob_matches("api-*", "api-"));
+        assert!(!glob_matches("api-*", "web-api-users"));
+        assert!(glob_matches("*-worker", "mail-worker"));
+        assert!(glob_matches("worker-?", "worker-1"));
+        assert!(!glob_matches("worker-?", "worker-10"));
+        assert!(glob_matches("a*b*c", "axxbyybzc"));
+        assert!(!glob_matches("a*b*c", "axxbyy"));
+        assert!(glob_matches("*", ""));
+        assert!(glob_matches("é*", "éclair"));
+    }
+}
@@ -0,0 +1,78 @@
+use chrono::Local;
+use colored::Colorize;
+use macros_rs::{crashln, string};
+use opm::{
+    audit::{Entry, Outcome, Via},
+    helpers, notice,
+};
+use tabled::{
+    Table, Tabled,
+    settings::{
+        Color, Modify,
+        object::{Rows, Segment},
+        style::{BorderColor, Style},
+    },
+};
+
+#[derive(Tabled)]
+struct Row {
+    time: String,
+    action: String,
+    target: String,
+    outcome: String,
+    actor: String,
+    via: String,
+}
+
+impl From<&Entry> for Row {
+    fn from(entry: &Entry) -> Self {
+        let target = match &entry.server {
+            Some(server) => format!("{} @ {server}", entry.target),
+            None => entry.target.clone(),
+        };
+
+        let outcome = match (entry.outcome, &entry.error) {
+            (Outcome::Ok, _) => string!("ok").green().to_string(),
+            (Outcome::Failed, Some(error)) => format!("failed: {error}").red().to_string(),
+            (Outcome::Failed, None) => string!("failed").red().to_string(),
+        };
+
+        let via = match (entry.via, &entry.source) {
+            (Via::Api, Some(source)) => format!("api ({source})"),
+            (Via::Api, None) => string!("api"),
+            (Via::Cli, _) => string!("cli"),
+            (Via::Ipc, _) => string!("ipc"),
+        };
+
+        Row {
+            time: entry.time.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S").to_string(),
+            action: entry.action.clone(),
+            target,
+            outcome,
+            actor: entry.actor.clone(),
+            via,
+        }
+    }
+}
+
+pub fn print(entries: &[Entry], format: &str) {
+    if format == "json" {
+        match serde_json::to_string(entries) {
+            Ok(json) => println!("{json}"),
+            Err(err) => crashln!("{} Cannot encode audit log.\n{}", *helpers::FAIL, string!(err).white()),
+        }
+        return;
+    }
+
+    if entries.is_empty() {
+        return notice!("{} No audit entries found", *helpers::INFO);
+    }
+
+    let table = Table::new(entries.iter().map(Row::from))
+        .with(Style::rounded().remove_verticals())
+        .with(Modify::new(Segment::all()).with(BorderColor::filled(Color::new("\x1b[38;2;45;55;72m", "\x1b[39m"))))
+        .with(Modify::new(Rows::first()).with(Color::FG_BRIGHT_CYAN))
+        .to_string();
+
+    notice!("{table}");
+}
@@ -0,0 +1,187 @@
+use opm::config;
+use std::{
+    borrow::Cow,
+    io::{self, IsTerminal},
+};
+
+/// Columns of `opm list`, in the order they are shown by default
+pub const DEFAULT: [&str; 9] = ["id", "name", "pid", "uptime", "restarts", "status", "cpu", "mem", "watch"];
+
+/// Every column `--columns` and `cli.list_columns` accept
+pub const AVAILABLE: [&str; 16] = [
+    "id",
+    "name",
+    "pid",
+    "uptime",
+    "restarts",
+    "status",
+    "cpu",
+    "mem",
+    "watch",
+    "url",
+    "group",
+    "ports",
+    "restarts_remaining",
+    "labels",
+    "fds",
+    "threads",
+];
+
+/// Shown by `--format minimal` whatever is picked, one tab separated line per process for scripts
+const MINIMAL: [&str; 4] = ["id", "name", "status", "pid"];
+
+/// Appended by `--format wide` when not picked explicitly
+const WIDE: [&str; 3] = ["fds", "threads", "url"];
+
+/// Columns read from a sample of the running process, the slow part of listing it
+const SAMPLED: [&str; 4] = ["cpu", "mem", "fds", "threads"];
+
+/// Clap parser for one entry of `--columns id,name,status`
+pub fn parse(value: &str) -> Result<String, String> {
+    let column = value.trim();
+
+    match AVAILABLE.contains(&column) {
+        true => Ok(column.to_string()),
+        false => Err(format!("unknown column '{column}', available: {}", AVAILABLE.join(","))),
+    }
+}
+
+/// Columns to show: `--columns`, then `cli.list_columns` of config.toml, then the defaults
+///
+/// `--format wide` appends the fds, threads and url columns that were not picked explicitly, `--format minimal`
+/// always has the id, name, status and pid.
+pub fn selected(columns: &[String], format: &str) -> Vec<String> {
+    if format == "minimal" {
+        return MINIMAL.map(String::from).to_vec();
+    }
+
+    let mut selected: Vec<String> = match columns.is_empty() {
+        false => columns.to_vec(),
+        true => configured().unwrap_or_else(|| DEFAULT.map(String::from).to_vec()),
+    };
+
+    if format == "wide" {
+        for column in WIDE {
+            if !selected.iter().any(|picked| picked == column) {
+                selected.push(String::from(column));
+            }
+        }
+    }
+
+    selected
+}
+
+/// Whether the columns come from `--columns` or `cli.list_columns` rather than the defaults
+pub fn picked(columns: &[String]) -> bool { !columns.is_empty() || configured().is_some() }
+
+/// `selected` with the group column after the name, shown by default once a listed process has a group
+pub fn with_group(selected: &[String]) -> Vec<String> {
+    let mut columns = selected.to_vec();
+
+    if !columns.iter().any(|column| column == "group") {
+        let at = columns.iter().position(|column| column == "name").map_or(columns.len(), |name| name + 1);
+        columns.insert(at, String::from("group"));
+    }
+
+    columns
+}
+
+/// Whether the processes have to be sampled, raw and json show every field whatever the columns
+pub fn sampled(columns: &[String], format: &str) -> bool {
+    matches!(format, "raw" | "json") || columns.iter().any(|column| SAMPLED.contains(&column.as_str()))
+}
+
+/// `cli.list_columns`, unknown entries are skipped so an old config never breaks `opm list`
+fn configured() -> Option<Vec<String>> {
+    let columns: Vec<String> = config::read().cli?.list_columns?.iter().filter_map(|column| parse(column).ok()).collect();
+    (!columns.is_empty()).then_some(columns)
+}
+
+pub fn header(column: &str) -> &str {
+    match column {
+        "restarts" => "↺",
+        "watch" => "watching",
+        "restarts_remaining" => "restarts left",
+        column => column,
+    }
+}
+
+/// Width the table is truncated to: `--max-width`, or the terminal width when printing to one
+pub fn max_width(flag: Option<usize>) -> Option<usize> {
+    flag.or_else(|| {
+        let stdout = io::stdout();
+        stdout.is_terminal().then(|| crossterm::terminal::size().ok()).flatten().map(|(width, _)| width as usize)
+    })
+}
+
+/// RFC 4180 CSV, fields with a separator, quote or line break are quoted
+pub fn csv(columns: &[String], rows: &[Vec<String>]) -> String {
+    let line = |fields: &mut dyn Iterator<Item = &str>| fields.map(quote).collect::<Vec<_>>().join(",");
+    let mut out = line(&mut columns.iter().map(String::as_str));
+
+    for row in rows {
+        out.push_str("\r\n");
+        out.push_str(&line(&mut row.iter().map(String::as_str)));
+    }
+
+    out
+}
+
+fn quote(field: &str) -> Cow<'_, str> {
+    match field.contains([',', '"', '\n', '\r']) {
+        true => Cow::Owned(format!("\"{}\"", field.replace('"', "\"\""))),
+        false => Cow::Borrowed(field),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_columns() {
+        assert_eq!(parse(" status ").unwrap(), "status");
+        assert_eq!(parse("restarts_remaining").unwrap(), "restarts_remaining");
+        assert!(parse("memory").unwrap_err().starts_with("unknown column 'memory'"));
+    }
+
+    #[test]
+    fn test_selected_columns() {
+        let columns = vec![String::from("name"), String::from("id")];
+
+        assert_eq!(selected(&columns, "default"), columns);
+        assert_eq!(selected(&columns, "wide")[2..], [String::from("fds"), String::from("threads"), String::from("url")]);
+        assert_eq!(selected(&[String::from("url"), String::from("fds")], "wide"), ["url", "fds", "threads"].map(String::from));
+        assert_eq!(selected(&columns, "minimal"), ["id", "name", "status", "pid"].map(String::from));
+        assert!(!sampled(&selected(&columns, "minimal"), "minimal"));
+    }
+
+    #[test]
+    fn test_with_group() {
+        let columns = DEFAULT.map(String::from);
+        assert_eq!(with_group(&columns)[..3], ["id", "name", "group"].map(String::from));
+        assert_eq!(with_group(&with_group(&columns)).len(), DEFAULT.len() + 1);
+        assert_eq!(with_group(&[String::from("pid")]), ["pid", "group"].map(String::from));
+    }
+
+    #[test]
+    fn test_sampled_columns() {
+        let narrow = vec![String::from("id"), String::from("name"), String::from("status")];
+
+        assert!(!sampled(&narrow, "default") && !sampled(&narrow, "csv"));
+        assert!(sampled(&narrow, "json"));
+        assert!(sampled(&selected(&narrow, "wide"), "wide"));
+        assert!(sampled(&DEFAULT.map(String::from), "default"));
+    }
+
+    #[test]
+    fn test_csv_quoting() {
+        let columns = vec![String::from("name"), String::from("labels")];
+        let rows = vec![
+            vec![String::from("api"), String::from("team=core,tier=web")],
+            vec![String::from("say \"hi\""), String::from("none")],
+        ];
+
+        assert_eq!(csv(&columns, &rows), "name,labels\r\napi,\"team=core,tier=web\"\r\n\"say \"\"hi\"\"\",none");
+    }
+}
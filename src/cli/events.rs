@@ -0,0 +1,189 @@
+use crate::daemon::pid;
+use chrono::{DateTime, Local, Utc};
+use colored::Colorize;
+use global_placeholders::global;
+use macros_rs::{crashln, string};
+use opm::{
+    config,
+    events::{self, Cursor, Event, Filter, Kind},
+    helpers, notice,
+    process::{Runner, http},
+};
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    thread::sleep,
+    time::Duration,
+};
+
+/// How often the event file and the daemon pid are checked while following
+const POLL: Duration = Duration::from_millis(500);
+/// Wait between attempts to reach an unavailable event source
+const RETRY: Duration = Duration::from_secs(1);
+
+pub struct Options<'o> {
+    pub format: &'o str,
+    pub filter: &'o Filter,
+    /// Recorded events to print first, 20 without `--follow` and none with it unless given
+    pub lines: Option<usize>,
+    /// Wait for an unavailable source instead of exiting
+    pub wait: bool,
+}
+
+pub fn run(follow: bool, options: &Options, server_name: &String) {
+    if follow && options.format == "json" {
+        crashln!("{} --format json prints one array and cannot follow, use --format jsonl", *helpers::FAIL);
+    }
+
+    if !follow {
+        return history(options, server_name);
+    }
+
+    // Ctrl-C is how a follow ends, every printed line is already flushed
+    extern "C" fn interrupted(_: libc::c_int) { unsafe { libc::_exit(0) } }
+    unsafe { libc::signal(libc::SIGINT, interrupted as *const () as libc::sighandler_t) };
+
+    match super::LOCAL_SERVER_NAMES.contains(&server_name.as_str()) {
+        true => follow_file(options),
+        false => follow_remote(options, server_name),
+    }
+}
+
+fn history(options: &Options, server_name: &str) {
+    let runner = super::connect(server_name);
+    let events = match runner.events(options.lines.unwrap_or(20), options.filter) {
+        Ok(events) => events,
+        Err(err) => crashln!("{} Failed to read process events: {err}", *helpers::FAIL),
+    };
+
+    if options.format == "json" {
+        match serde_json::to_string(&events) {
+            Ok(json) => println!("{json}"),
+            Err(err) => crashln!("{} Cannot encode process events.\n{}", *helpers::FAIL, string!(err).white()),
+        }
+        return;
+    }
+
+    if events.is_empty() && options.format != "jsonl" {
+        return notice!("{} No process events found", *helpers::INFO);
+    }
+
+    events.iter().for_each(|event| emit(event, options.format));
+}
+
+/// Print one event and flush it, a closed pipe ends the command like it would for `tail -f`
+fn emit(event: &Event, format: &str) {
+    let line = match format {
+        "jsonl" => serde_json::to_string(event).unwrap_or_default(),
+        _ => format!("{} {:<12} {}", event.time.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S"), paint(event.kind), event.message),
+    };
+
+    let mut stdout = io::stdout().lock();
+    if writeln!(stdout, "{line}").and_then(|_| stdout.flush()).is_err() {
+        std::process::exit(0);
+    }
+}
+
+fn paint(kind: Kind) -> String {
+    let name = kind.to_string();
+
+    match kind {
+        Kind::Crash | Kind::GaveUp | Kind::ImportFailed => name.red().to_string(),
+        Kind::MemoryLimit | Kind::Alert | Kind::LogRate | Kind::LogsFull | Kind::Gap => name.yellow().to_string(),
+        Kind::Restart | Kind::WatchReload => name.cyan().to_string(),
+        Kind::WatchHeld => name.bright_black().to_string(),
+        Kind::ImportStart => name.green().to_string(),
+    }
+}
+
+fn daemon_pid() -> Option<i32> {
+    let pid = pid::read().ok()?.get::<i32>();
+    pid::running(pid).then_some(pid)
+}
+
+/// Tail the daemon's event file, marking a gap whenever the daemon went away or was replaced
+fn follow_file(options: &Options) {
+    let path = global!("opm.events");
+    let mut last_pid = daemon_pid();
+    let mut down_since: Option<DateTime<Utc>> = None;
+
+    if last_pid.is_none() && !options.wait {
+        crashln!("{} The daemon is not running, start it with `opm daemon start` or pass --wait", *helpers::FAIL);
+    }
+
+    let mut cursor = Cursor::end(&path);
+    events::read(&path, options.lines.unwrap_or(0), options.filter).iter().for_each(|event| emit(event, options.format));
+
+    loop {
+        match (daemon_pid(), last_pid) {
+            (Some(pid), Some(last)) if pid != last => {
+                emit(&Event::gap(down_since.take().unwrap_or_else(Utc::now)), options.format);
+                last_pid = Some(pid);
+            }
+            (Some(pid), _) => {
+                down_since = None;
+                last_pid = Some(pid);
+            }
+            (None, Some(_)) => {
+                down_since.get_or_insert_with(Utc::now);
+            }
+            (None, None) => {}
+        }
+
+        for event in cursor.poll().iter().filter(|event| options.filter.matches(event)) {
+            emit(event, options.format);
+        }
+
+        sleep(POLL);
+    }
+}
+
+/// Follow the event stream of a remote daemon's api, reconnecting with a gap marker when it drops
+fn follow_remote(options: &Options, server_name: &String) {
+    let Some(server) = config::servers().servers.and_then(|servers| servers.get(server_name).map(|server| server.get())) else {
+        crashln!("{} Server '{server_name}' does not exist", *helpers::FAIL)
+    };
+
+    let (mut connected, mut lost) = (false, None::<DateTime<Utc>>);
+
+    loop {
+        let runner = Runner::connect(server_name.clone(), server.clone(), false);
+        let stream = match runner.as_ref().and_then(|runner| runner.remote.as_ref()) {
+            Some(remote) => http::event_stream(remote, options.filter).map_err(|err| err.to_string()),
+            None => Err(format!("failed to connect (address={})", server.address)),
+        };
+
+        let response = match stream {
+            Ok(response) => response,
+            Err(err) if !connected && !options.wait => crashln!("{} Event stream of {server_name} is unavailable: {err}", *helpers::FAIL),
+            Err(_) => {
+                if connected {
+                    lost.get_or_insert_with(Utc::now);
+                }
+                sleep(RETRY);
+                continue;
+            }
+        };
+
+        match lost.take() {
+            Some(since) => emit(&Event::gap(since), options.format),
+            None if !connected => {
+                let recorded = runner.map(|runner| runner.events(options.lines.unwrap_or(0), options.filter));
+                recorded.and_then(Result::ok).unwrap_or_default().iter().for_each(|event| emit(event, options.format));
+            }
+            None => {}
+        }
+
+        connected = true;
+
+        for line in BufReader::new(response).lines() {
+            let Ok(line) = line else { break };
+            let event = line.strip_prefix("data:").and_then(|data| serde_json::from_str::<Event>(data.trim()).ok());
+            if let Some(event) = event.filter(|event| options.filter.matches(event)) {
+                emit(&event, options.format);
+            }
+        }
+
+        lost = Some(Utc::now());
+        sleep(RETRY);
+    }
+}
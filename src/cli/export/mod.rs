@@ -0,0 +1,3 @@
+//! Processes written out for other tools, see `opm get-command --format`
+
+pub(crate) mod translate;
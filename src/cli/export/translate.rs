@@ -0,0 +1,316 @@
+//! A managed process as a systemd unit, a Dockerfile or a compose service, to move it out of opm
+
+use macros_rs::ternary;
+use opm::process::{Env, Process, limits::UNLIMITED};
+use std::collections::{BTreeMap, HashMap};
+
+/// Env keys containing one of these hold a secret, their values are only written with `--show-secrets`
+const SECRET_KEYS: [&str; 8] = ["SECRET", "TOKEN", "PASSWORD", "PASSWD", "API_KEY", "PRIVATE", "CREDENTIAL", "AUTH"];
+pub const REDACTED: &str = "<redacted>";
+
+/// Base image of the Dockerfile and compose formats, the script decides what it really needs
+const IMAGE: &str = "debian:stable-slim";
+
+/// Everything the formats are generated from
+#[derive(Debug, Clone, PartialEq)]
+pub struct Service {
+    pub name: String,
+    /// Shell, its arguments and the script, the way the daemon runs it
+    pub command: Vec<String>,
+    pub cwd: String,
+    pub env: BTreeMap<String, String>,
+    /// Whether values of `env` were replaced with `REDACTED`
+    pub redacted: bool,
+    pub max_memory: u64,
+    pub nofile: Option<u64>,
+    pub core: Option<u64>,
+    pub umask: Option<u32>,
+}
+
+impl Service {
+    /// `current` is the environment of the calling shell, variables it has as well were inherited and are left out
+    pub fn new(process: &Process, shell: &str, args: &[String], current: &HashMap<String, String>, show_secrets: bool) -> Service {
+        let (env, redacted) = env(&process.env, current, show_secrets);
+
+        Service {
+            name: process.name.clone(),
+            command: [shell.to_string()].into_iter().chain(args.iter().cloned()).chain([process.script.clone()]).collect(),
+            cwd: process.path.display().to_string(),
+            env,
+            redacted,
+            max_memory: process.max_memory,
+            nofile: process.ulimit_nofile,
+            core: process.ulimit_core,
+            umask: process.umask,
+        }
+    }
+
+    fn header(&self, format: &str) -> String {
+        let mut header = format!("# {}, generated by opm get-command --format {format}\n", self.name);
+        if self.redacted {
+            header.push_str(&format!("# Secret values are {REDACTED}, add --show-secrets to write them\n"));
+        }
+        header
+    }
+}
+
+/// The variables set for the process itself, secrets redacted unless `show_secrets`
+pub fn env(stored: &Env, current: &HashMap<String, String>, show_secrets: bool) -> (BTreeMap<String, String>, bool) {
+    let mut redacted = false;
+
+    let env = stored
+        .iter()
+        .filter(|(key, value)| current.get(*key) != Some(value))
+        .map(|(key, value)| match !show_secrets && is_secret(key) {
+            true => {
+                redacted = true;
+                (key.clone(), REDACTED.to_string())
+            }
+            false => (key.clone(), value.clone()),
+        })
+        .collect();
+
+    (env, redacted)
+}
+
+pub fn is_secret(key: &str) -> bool {
+    let key = key.to_uppercase();
+    SECRET_KEYS.iter().any(|secret| key.contains(secret))
+}
+
+/// A unit for `/etc/systemd/system/<name>.service`
+pub fn systemd(service: &Service) -> String {
+    let command: Vec<String> = service.command.iter().map(|arg| systemd_quote(&arg.replace('$', "$$"))).collect();
+    let mut unit = service.header("systemd");
+
+    unit.push_str(&format!("[Unit]\nDescription={}\nAfter=network.target\n\n", escape_specifiers(&service.name)));
+    unit.push_str("[Service]\nType=simple\n");
+    unit.push_str(&format!("WorkingDirectory={}\n", escape_specifiers(&service.cwd)));
+    unit.push_str(&format!("ExecStart={}\n", command.join(" ")));
+
+    for (key, value) in &service.env {
+        unit.push_str(&format!("Environment={}\n", systemd_quote(&format!("{key}={value}"))));
+    }
+
+    if service.max_memory > 0 {
+        unit.push_str(&format!("MemoryMax={}\n", service.max_memory));
+    }
+    if let Some(nofile) = service.nofile {
+        unit.push_str(&format!("LimitNOFILE={}\n", systemd_limit(nofile)));
+    }
+    if let Some(core) = service.core {
+        unit.push_str(&format!("LimitCORE={}\n", systemd_limit(core)));
+    }
+    if let Some(umask) = service.umask {
+        unit.push_str(&format!("UMask={umask:04o}\n"));
+    }
+
+    // The daemon restarts a process whenever it exits while it should be running
+    unit.push_str("Restart=always\nRestartSec=1\n\n[Install]\nWantedBy=multi-user.target\n");
+    unit
+}
+
+/// A Dockerfile built from the working directory, limits become `docker run` options
+pub fn dockerfile(service: &Service) -> String {
+    let mut file = service.header("dockerfile");
+
+    let options = run_options(service);
+    if !options.is_empty() {
+        file.push_str(&format!("# Run with: docker run {}\n", options.join(" ")));
+    }
+    if let Some(umask) = service.umask {
+        file.push_str(&format!("# umask {umask:04o} is not applied in the container\n"));
+    }
+
+    file.push_str(&format!("FROM {IMAGE}\nWORKDIR {}\nCOPY . .\n", service.cwd));
+
+    let env: Vec<String> = service.env.iter().map(|(key, value)| format!("{key}={}", json(value).replace('$', "\\$"))).collect();
+    if !env.is_empty() {
+        file.push_str(&format!("ENV {}\n", env.join(" \\\n    ")));
+    }
+
+    file.push_str(&format!("CMD {}\n", json(&service.command)));
+    file
+}
+
+/// A `services:` entry for compose.yaml, the working directory is mounted where the process ran
+pub fn compose(service: &Service) -> String {
+    // Compose interpolates `$` in every value
+    let quote = |value: &str| json(value).replace('$', "$$");
+    let command: Vec<String> = service.command.iter().map(|arg| quote(arg)).collect();
+
+    let mut file = service.header("compose");
+    file.push_str(&format!("services:\n  {}:\n    image: {IMAGE}\n", compose_name(&service.name)));
+    file.push_str(&format!("    working_dir: {}\n", quote(&service.cwd)));
+    file.push_str(&format!("    volumes:\n      - {}\n", quote(&format!("{0}:{0}", service.cwd))));
+    file.push_str(&format!("    command: [{}]\n", command.join(", ")));
+
+    if !service.env.is_empty() {
+        file.push_str("    environment:\n");
+        for (key, value) in &service.env {
+            file.push_str(&format!("      {key}: {}\n", quote(value)));
+        }
+    }
+
+    if service.max_memory > 0 {
+        file.push_str(&format!("    mem_limit: {}\n", service.max_memory));
+    }
+
+    let ulimits: Vec<(&str, u64)> = [("nofile", service.nofile), ("core", service.core)].into_iter().filter_map(|(name, limit)| Some((name, limit?))).collect();
+    if !ulimits.is_empty() {
+        file.push_str("    ulimits:\n");
+        for (name, limit) in ulimits {
+            file.push_str(&format!("      {name}: {}\n", compose_limit(limit)));
+        }
+    }
+    if let Some(umask) = service.umask {
+        file.push_str(&format!("    # umask {umask:04o} is not applied in the container\n"));
+    }
+
+    file.push_str("    restart: always\n");
+    file
+}
+
+/// `docker run` options for the limits a Dockerfile cannot hold
+fn run_options(service: &Service) -> Vec<String> {
+    let mut options = vec![];
+
+    if service.max_memory > 0 {
+        options.push(format!("--memory {}", service.max_memory));
+    }
+    for (name, limit) in [("nofile", service.nofile), ("core", service.core)] {
+        if let Some(limit) = limit {
+            options.push(format!("--ulimit {name}={}", compose_limit(limit)));
+        }
+    }
+
+    options
+}
+
+fn json<T: serde::Serialize + ?Sized>(value: &T) -> String { serde_json::to_string(value).unwrap_or_default() }
+
+/// `%` starts a specifier in most unit settings
+fn escape_specifiers(value: &str) -> String { value.replace('%', "%%") }
+
+/// An argument as systemd splits command lines, quoted with C escapes unless it is a plain word
+fn systemd_quote(value: &str) -> String {
+    let value = escape_specifiers(value);
+    if !value.is_empty() && value.chars().all(|char| char.is_ascii_alphanumeric() || "/._-=:,+@$%".contains(char)) {
+        return value;
+    }
+
+    let mut quoted = String::from("\"");
+    for char in value.chars() {
+        match char {
+            '\\' => quoted.push_str("\\\\"),
+            '"' => quoted.push_str("\\\""),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            char => quoted.push(char),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn systemd_limit(limit: u64) -> String {
+    match limit {
+        UNLIMITED => String::from("infinity"),
+        limit => limit.to_string(),
+    }
+}
+
+fn compose_limit(limit: u64) -> String {
+    match limit {
+        UNLIMITED => String::from("-1"),
+        limit => limit.to_string(),
+    }
+}
+
+/// Compose service names are lowercase letters, digits, `-` and `_`
+fn compose_name(name: &str) -> String {
+    name.to_lowercase().chars().map(|char| ternary!(char.is_ascii_alphanumeric() || char == '_', char, '-')).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn service() -> Service {
+        Service {
+            name: String::from("api"),
+            command: vec![String::from("/bin/sh"), String::from("-c"), String::from("node index.js --greeting \"hi $USER\" --cpu 50%")],
+            cwd: String::from("/srv/api"),
+            env: BTreeMap::from([
+                (String::from("API_TOKEN"), String::from(REDACTED)),
+                (String::from("NODE_ENV"), String::from("production")),
+                (String::from("PORT"), String::from("3000")),
+            ]),
+            redacted: true,
+            max_memory: 512 * 1024 * 1024,
+            nofile: Some(4096),
+            core: Some(UNLIMITED),
+            umask: Some(0o022),
+        }
+    }
+
+    #[test]
+    fn test_systemd() {
+        assert_eq!(systemd(&service()), include_str!("golden/api.service"));
+    }
+
+    #[test]
+    fn test_dockerfile() {
+        assert_eq!(dockerfile(&service()), include_str!("golden/api.Dockerfile"));
+    }
+
+    #[test]
+    fn test_compose() {
+        assert_eq!(compose(&service()), include_str!("golden/api.compose.yaml"));
+    }
+
+    #[test]
+    fn test_plain_service() {
+        let service = Service {
+            name: String::from("Worker 2"),
+            command: vec![String::from("/bin/sh"), String::from("-c"), String::from("./worker")],
+            cwd: String::from("/srv/worker"),
+            env: BTreeMap::new(),
+            redacted: false,
+            max_memory: 0,
+            nofile: None,
+            core: None,
+            umask: None,
+        };
+
+        assert_eq!(systemd(&service), include_str!("golden/worker.service"));
+        assert!(compose(&service).contains("services:\n  worker-2:\n"));
+        assert!(!dockerfile(&service).contains("ENV") && !dockerfile(&service).contains("docker run"));
+    }
+
+    #[test]
+    fn test_env_leaves_out_inherited_and_secrets() {
+        let stored = Env::from([
+            (String::from("HOME"), String::from("/root")),
+            (String::from("PATH"), String::from("/opt/node/bin")),
+            (String::from("DB_PASSWORD"), String::from("hunter2")),
+            (String::from("NODE_ENV"), String::from("production")),
+        ]);
+        let current = HashMap::from([(String::from("HOME"), String::from("/root")), (String::from("PATH"), String::from("/usr/bin"))]);
+
+        let (env, redacted) = super::env(&stored, &current, false);
+        assert!(redacted);
+        assert_eq!(env.keys().collect::<Vec<_>>(), vec!["DB_PASSWORD", "NODE_ENV", "PATH"]);
+        assert_eq!(env["DB_PASSWORD"], REDACTED);
+
+        let (env, redacted) = super::env(&stored, &current, true);
+        assert!(!redacted);
+        assert_eq!(env["DB_PASSWORD"], "hunter2");
+    }
+
+    #[test]
+    fn test_is_secret() {
+        assert!(is_secret("GITHUB_TOKEN") && is_secret("db_password") && is_secret("STRIPE_API_KEY") && is_secret("AUTH_HEADER"));
+        assert!(!is_secret("NODE_ENV") && !is_secret("PORT") && !is_secret("KEYBOARD_LAYOUT"));
+    }
+}
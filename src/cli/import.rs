@@ -1,7 +1,15 @@
 use super::{Item, Items};
+use chrono::{Local, Utc};
 use colored::Colorize;
-use macros_rs::{crashln, string};
+use macros_rs::{crashln, string, then};
 use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use hcl::{
+    Block, Body, Expression, Identifier,
+    eval::{Context, Evaluate},
+    expr::{Heredoc, TemplateExpr},
+};
 
 use std::{
     collections::HashMap,
@@ -10,9 +18,31 @@ use std::{
 };
 
 use opm::{
-    file::Exists,
-    helpers,
-    process::{Env, Runner},
+    alert, config,
+    file::{self, Exists},
+    helpers, notice,
+    process::{
+        Env, Labels, Runner, StartOptions,
+        alerts::Alert,
+        cron::Schedule,
+        health::{self, Probe},
+        validate_monitor_interval,
+        labels,
+        limits::{self, Limits},
+        lograte::MaxLogRate,
+        memlimit,
+        pending::{self, Import},
+        shell::{self, Shell},
+    },
+};
+
+use tabled::{
+    Table, Tabled,
+    settings::{
+        Color, Modify,
+        object::{Rows, Segment},
+        style::{BorderColor, Style},
+    },
 };
 
 #[derive(Deserialize, Debug)]
@@ -29,23 +59,221 @@ struct Process {
     #[serde(default)]
     env: Env,
     max_memory: Option<String>,
+    /// `"stop"` or `"restart"`, see `opm::process::memlimit`
+    max_memory_action: Option<memlimit::Action>,
+    #[serde(default)]
+    labels: Labels,
+    #[serde(default)]
+    depends_on: Vec<String>,
+    startup_grace: Option<u64>,
+    /// Milliseconds between the daemon's checks, `daemon.interval` when unset
+    monitor_interval_ms: Option<u64>,
+    /// Crashes the daemon restarts the process after, `daemon.restarts` when unset
+    max_restarts: Option<u64>,
+    url: Option<String>,
+    /// Free text shown by `opm details`, at most 1 KB
+    description: Option<String>,
+    /// Group the process is managed with, see `opm::process::group`
+    group: Option<String>,
+    #[serde(default)]
+    expand_env_templates: Option<bool>,
+    /// Octal, written as a string so leading zeros survive
+    umask: Option<String>,
+    ulimit_nofile: Option<Limit>,
+    ulimit_core: Option<Limit>,
+    collect_cores: Option<bool>,
+    supervised: Option<bool>,
+    /// False for processes that fork many short-lived children
+    track_children: Option<bool>,
+    /// Absolute path of the shell, `runner.shell` when unset
+    shell: Option<String>,
+    /// Arguments given to the shell before the script, `runner.args` when unset
+    shell_args: Option<Vec<String>>,
+    #[serde(default)]
+    alerts: Vec<Alert>,
+    /// e.g. `"100M/min restart"`, see `opm::process::lograte`
+    max_log_rate: Option<MaxLogRate>,
+    /// e.g. `"0 3 * * *"`, see `opm::process::cron`
+    restart_cron: Option<Schedule>,
+    health: Option<Health>,
+}
+
+/// A resource limit, a number or `"unlimited"`
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(untagged)]
+enum Limit {
+    Number(u64),
+    Text(String),
+}
+
+impl Limit {
+    fn new(limit: u64) -> Self {
+        match limit {
+            limits::UNLIMITED => Limit::Text(limits::format_limit(limit)),
+            limit => Limit::Number(limit),
+        }
+    }
+
+    fn parse(&self, parse: fn(&str) -> Result<u64, String>) -> Result<u64, String> {
+        match self {
+            Limit::Number(limit) => parse(&limit.to_string()),
+            Limit::Text(limit) => parse(limit),
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 struct Watch {
     path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    cooldown_secs: Option<u64>,
+}
+
+/// `health = { url = "http://127.0.0.1:8080/health" }` or `{ cmd = "pg_isready", retries = 5 }`
+#[derive(Serialize, Deserialize, Debug)]
+struct Health {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    cmd: Option<String>,
+    interval: Option<u64>,
+    retries: Option<u32>,
+    timeout: Option<u64>,
+}
+
+impl Health {
+    fn new(check: &health::Check) -> Self {
+        let (url, cmd) = match &check.probe {
+            Probe::Url(url) => (Some(url.clone()), None),
+            Probe::Command(command) => (None, Some(command.clone())),
+        };
+        Health { url, cmd, interval: Some(check.interval), retries: Some(check.retries), timeout: Some(check.timeout) }
+    }
 }
 
 impl Process {
     fn get_watch_path(&self) -> Option<String> {
         self.watch.as_ref().and_then(|w| Some(w.path.clone()))
     }
+
+    fn get_watch_cooldown(&self) -> Option<u64> { self.watch.as_ref().and_then(|w| w.cooldown_secs) }
+
+    fn get_limits(&self) -> Result<Limits, String> {
+        Ok(Limits {
+            umask: self.umask.as_deref().map(limits::parse_umask).transpose()?,
+            nofile: self.ulimit_nofile.as_ref().map(|limit| limit.parse(limits::parse_nofile)).transpose()?,
+            core: self.ulimit_core.as_ref().map(|limit| limit.parse(limits::parse_core)).transpose()?,
+        })
+    }
+
+    fn get_health(&self) -> Result<Option<health::Check>, String> {
+        let Some(health) = &self.health else { return Ok(None) };
+        let probe = match (&health.url, &health.cmd) {
+            (Some(url), None) => Probe::Url(helpers::parse_url(url)?),
+            (None, Some(cmd)) => Probe::Command(health::parse_command(cmd)?),
+            _ => return Err(string!("expected either url or cmd")),
+        };
+
+        health::Changes { probe: Some(Some(probe)), interval: health.interval, retries: health.retries, timeout: health.timeout }.apply(None)
+    }
+
+    fn get_shell(&self) -> Result<Shell, String> {
+        Ok(Shell {
+            program: self.shell.as_deref().map(shell::parse).transpose()?,
+            args: self.shell_args.clone(),
+        })
+    }
 }
 
-pub fn read_hcl(path: &String) {
-    let mut servers: Vec<String> = vec![];
+/// An imported process whose settings were all checked
+struct Validated {
+    name: String,
+    item: Process,
+    options: StartOptions,
+}
+
+/// Check every imported process before anything is created, so a bad entry never leaves a partial import
+fn validate(list: HashMap<String, Process>) -> Vec<Validated> {
+    let mut validated: Vec<Validated> = list
+        .into_iter()
+        .map(|(name, item)| {
+            if let Err(err) = labels::validate(&item.labels) {
+                crashln!("{} Invalid labels for {name}: {err}", *helpers::FAIL);
+            }
 
-    println!("{} Applying action importProcess", *helpers::SUCCESS);
+            let max_memory = match item.max_memory.as_deref().map(helpers::parse_memory).transpose() {
+                Ok(max_memory) => max_memory.unwrap_or_default(),
+                Err(err) => crashln!("{} {err} for {name}", *helpers::FAIL),
+            };
+
+            let url = match item.url.as_deref().map(helpers::parse_url).transpose() {
+                Ok(url) => url,
+                Err(err) => crashln!("{} {err} for {name}", *helpers::FAIL),
+            };
+
+            if let Some(Err(err)) = item.description.as_deref().map(opm::process::description::parse) {
+                crashln!("{} Invalid description for {name}: {err}", *helpers::FAIL);
+            }
+
+            if let Some(Err(err)) = item.group.as_deref().map(opm::process::group::parse) {
+                crashln!("{} Invalid group for {name}: {err}", *helpers::FAIL);
+            }
+
+            let limits = match item.get_limits() {
+                Ok(limits) => limits,
+                Err(err) => crashln!("{} Invalid limits for {name}: {err}", *helpers::FAIL),
+            };
+
+            let shell = match item.get_shell() {
+                Ok(shell) => shell,
+                Err(err) => crashln!("{} Invalid shell for {name}: {err}", *helpers::FAIL),
+            };
+
+            let health = match item.get_health() {
+                Ok(health) => health,
+                Err(err) => crashln!("{} Invalid health check for {name}: {err}", *helpers::FAIL),
+            };
+
+            if let Some(Err(err)) = item.monitor_interval_ms.map(validate_monitor_interval) {
+                crashln!("{} {err} for {name}", *helpers::FAIL);
+            }
+
+            let options = StartOptions {
+                watch: item.get_watch_path(),
+                max_memory,
+                max_memory_action: item.max_memory_action,
+                limits,
+                shell,
+                collect_cores: item.collect_cores.unwrap_or_default(),
+                supervised: item.supervised.unwrap_or_default(),
+                labels: item.labels.clone(),
+                env: Env::new(),
+                startup_grace: item.startup_grace,
+                monitor_interval_ms: item.monitor_interval_ms,
+                max_restarts: item.max_restarts,
+                watch_cooldown: item.get_watch_cooldown(),
+                url,
+                description: item.description.clone(),
+                group: item.group.clone(),
+                alerts: item.alerts.clone(),
+                max_log_rate: item.max_log_rate.clone(),
+                restart_cron: item.restart_cron.clone(),
+                health,
+                pidfile: None,
+                expand_env: item.expand_env_templates.unwrap_or_default(),
+                force_update: false,
+            };
+
+            Validated { name, item, options }
+        })
+        .collect();
+
+    validated.sort_by(|a, b| a.name.cmp(&b.name));
+    validated
+}
+
+pub fn read_hcl(path: &String, background: bool) {
+    notice!("{} Applying action importProcess", *helpers::SUCCESS);
 
     let contents = match fs::read_to_string(path) {
         Ok(contents) => contents,
@@ -56,7 +284,7 @@ pub fn read_hcl(path: &String) {
         ),
     };
 
-    let hcl_parsed: ProcessWrapper = match hcl::from_str(&contents) {
+    let hcl_parsed: ProcessWrapper = match hcl::parse(&contents).and_then(|body| hcl::from_body(read_heredocs(body))) {
         Ok(hcl) => hcl,
         Err(err) => crashln!(
             "{} Cannot parse imported file.\n{}",
@@ -65,7 +293,18 @@ pub fn read_hcl(path: &String) {
         ),
     };
 
-    for (name, item) in hcl_parsed.list {
+    let validated = validate(hcl_parsed.list);
+    match background {
+        true => register(path, validated),
+        false => start(validated),
+    }
+}
+
+/// Create and start the processes one after the other
+fn start(validated: Vec<Validated>) {
+    let mut servers: Vec<String> = vec![];
+
+    for Validated { name, item, options } in validated {
         let mut runner = Runner::new();
         let server_name = &item.server.clone().unwrap_or("local".into());
         let (kind, list_name) = super::format(server_name);
@@ -76,22 +315,19 @@ pub fn read_hcl(path: &String) {
             kind: kind.clone(),
             runner: runner.clone(),
         }
-        .create(
-            &item.script,
-            &Some(name.clone()),
-            &item.get_watch_path(),
-            &item.max_memory,
-            true,
-        );
+        .create(&item.script, &Some(name.clone()), &options, true);
 
-        println!("{} Imported {kind}process {name}", *helpers::SUCCESS);
+        notice!("{} Imported {kind}process {name}", *helpers::SUCCESS);
 
         match runner.find(&name, server_name) {
             Some(id) => {
+                runner.set_track_children(id, item.track_children.unwrap_or(true));
                 let mut p = runner.get(id);
-                p.stop();
+                p.stop(None);
                 p.set_env(item.env);
+                p.set_depends_on(item.depends_on);
                 p.restart(false);
+                p.get_runner().save();
             }
             None => crashln!("{} Failed to write to ({name})", *helpers::FAIL),
         }
@@ -104,14 +340,109 @@ pub fn read_hcl(path: &String) {
     servers
         .iter()
         .for_each(|server| super::Internal::list(&string!("default"), &server));
-    println!(
+    notice!(
         "{} Applied startProcess to imported items",
         *helpers::SUCCESS
     );
 }
 
+/// Store the processes as pending and leave starting them to the daemon
+fn register(path: &str, validated: Vec<Validated>) {
+    if let Some(remote) = validated.iter().find(|v| v.item.server.as_deref().is_some_and(|server| !matches!(server, "local" | "internal"))) {
+        crashln!("{} --background only imports local processes, {} is on server {}", *helpers::FAIL, remote.name, remote.item.server.as_deref().unwrap_or_default());
+    }
+
+    let node = config::read().runner.node;
+    let mut runner = Runner::new();
+    let mut ids = vec![];
+
+    for Validated { name, item, options } in validated {
+        let command = super::internal::script_command(&item.script, &node);
+
+        let id = runner.register(&name, &command, file::cwd(), &options);
+        runner.set_env(id, item.env).set_depends_on(id, item.depends_on).set_track_children(id, item.track_children.unwrap_or(true));
+        ids.push(id);
+
+        notice!("{} Registered process {name} ({id}) as pending", *helpers::SUCCESS);
+    }
+
+    runner.save();
+
+    let count = ids.len();
+    if let Err(err) = (Import { file: path.to_string(), time: Utc::now(), ids }).write() {
+        alert!("{} Cannot record the import for import-status: {err}", *helpers::WARN);
+    }
+
+    super::Internal::list(&string!("default"), &string!("local"));
+    notice!("{} The daemon starts the {count} imported processes in the background, see `opm import-status`", *helpers::SUCCESS);
+}
+
+#[derive(Tabled, Serialize)]
+struct StatusRow {
+    id: usize,
+    name: String,
+    state: String,
+}
+
+/// Progress of the last `opm import --background`
+pub fn status(format: &str) {
+    let import = match Import::read() {
+        Ok(Some(import)) => import,
+        Ok(None) => crashln!("{} No background import was made yet", *helpers::FAIL),
+        Err(err) => crashln!("{} Cannot read the import status.\n{}", *helpers::FAIL, string!(err).white()),
+    };
+
+    let runner = Runner::new();
+    let rows: Vec<StatusRow> = import
+        .ids
+        .iter()
+        .map(|id| {
+            let process = runner.info(*id);
+            StatusRow {
+                id: *id,
+                name: process.map(|process| process.name.clone()).unwrap_or_default(),
+                state: string!(pending::state(process)),
+            }
+        })
+        .collect();
+
+    if format == "json" {
+        let status = json!({ "file": import.file, "time": import.time, "processes": rows });
+        return println!("{status}");
+    }
+
+    let count = |state: &str| rows.iter().filter(|row| row.state == state).count();
+    notice!(
+        "{} Import of {} at {}: {} started, {} pending, {} failed",
+        *helpers::SUCCESS,
+        import.file,
+        import.time.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S"),
+        count("started"),
+        count("pending"),
+        count("failed")
+    );
+
+    let table = Table::new(rows.iter().map(|row| StatusRow {
+        state: match row.state.as_str() {
+            "started" => row.state.green().to_string(),
+            "pending" => row.state.yellow().to_string(),
+            "failed" => row.state.red().to_string(),
+            _ => row.state.clone(),
+        },
+        id: row.id,
+        name: row.name.clone(),
+    }))
+    .with(Style::rounded().remove_verticals())
+    .with(Modify::new(Segment::all()).with(BorderColor::filled(Color::new("\x1b[38;2;45;55;72m", "\x1b[39m"))))
+    .with(Modify::new(Rows::first()).with(Color::FG_BRIGHT_CYAN))
+    .to_string();
+
+    notice!("{table}");
+    then!(count("failed") > 0, notice!("Failed processes left their reason in their error log, see `opm logs <name>`"));
+}
+
 pub fn export_hcl(items: &Items, path: &Option<String>) {
-    println!("{} Applying action exportProcess", *helpers::SUCCESS);
+    notice!("{} Applying action exportProcess", *helpers::SUCCESS);
 
     let runner = Runner::new();
     let mut process_ids = Vec::new();
@@ -131,7 +462,7 @@ pub fn export_hcl(items: &Items, path: &Option<String>) {
         for item in &items.items {
             match item {
                 Item::Id(id) => process_ids.push(*id),
-                Item::Name(name) => match runner.find(&name, &string!("internal")) {
+                Item::Name(name) | Item::Pattern(name) | Item::Group(name) => match runner.find(name, &string!("internal")) {
                     Some(id) => process_ids.push(id),
                     None => crashln!("{} Process ({name}) not found", *helpers::FAIL),
                 },
@@ -176,6 +507,7 @@ pub fn export_hcl(items: &Items, path: &Option<String>) {
         if process.watch.enabled {
             watch_parsed = Some(Watch {
                 path: process.watch.path.clone(),
+                cooldown_secs: process.watch.cooldown_secs,
             })
         }
 
@@ -202,10 +534,32 @@ pub fn export_hcl(items: &Items, path: &Option<String>) {
                 watch = (watch_parsed)
                 env = (env_parsed)
                 max_memory = (max_memory_str)
+                max_memory_action = ((process.max_memory_action == memlimit::Action::Restart).then(|| process.max_memory_action.to_string()))
+                labels = (process.labels.clone())
+                depends_on = (process.depends_on.clone())
+                startup_grace = (process.startup_grace)
+                monitor_interval_ms = (process.monitor_interval_ms)
+                max_restarts = (process.max_restarts)
+                url = (process.url.clone())
+                description = (process.description.clone())
+                group = (process.group.clone())
+                expand_env_templates = (process.expand_env.then_some(true))
+                umask = (process.umask.map(limits::format_umask))
+                ulimit_nofile = (process.ulimit_nofile.map(Limit::new))
+                ulimit_core = (process.ulimit_core.map(Limit::new))
+                collect_cores = (process.collect_cores.then_some(true))
+                supervised = (process.supervised.then_some(true))
+                track_children = ((!process.track_children).then_some(false))
+                shell = (process.shell.clone())
+                shell_args = (process.shell_args.clone())
+                alerts = ((!process.alerts.is_empty()).then(|| process.alerts.iter().map(Alert::to_string).collect::<Vec<_>>()))
+                max_log_rate = (process.max_log_rate.as_ref().map(MaxLogRate::to_string))
+                restart_cron = (process.restart_cron.as_ref().map(Schedule::to_string))
+                health = (process.health.as_ref().map(Health::new))
             }
         };
 
-        let serialized = hcl::to_string(&data).unwrap();
+        let serialized = hcl::to_string(&write_heredocs(data)).unwrap();
 
         // Append to file
         let mut file = match OpenOptions::new()
@@ -227,10 +581,236 @@ pub fn export_hcl(items: &Items, path: &Option<String>) {
         }
     }
 
-    println!(
+    notice!(
         "{} Exported {} process(es) to {}",
         *helpers::SUCCESS,
         count,
         output_path
     );
 }
+
+/// Write multi-line attributes as heredocs instead of quoted strings full of `\n` escapes
+fn write_heredocs(mut block: Block) -> Block {
+    for attr in block.body.attributes_mut() {
+        if let Expression::String(value) = &attr.expr
+            && value.contains('\n')
+        {
+            attr.expr = heredoc(value);
+        }
+    }
+
+    block
+}
+
+fn heredoc(value: &str) -> Expression {
+    // The delimiter can't appear as a line of its own inside the value
+    let delimiter = (0..)
+        .map(|n| match n {
+            0 => string!("EOT"),
+            n => format!("EOT{n}"),
+        })
+        .find(|delimiter| value.lines().all(|line| line.trim() != delimiter))
+        .unwrap_or_default();
+
+    let template = value.replace("${", "$${").replace("%{", "%%{");
+    let heredoc = Heredoc::new(Identifier::new(delimiter).unwrap(), format!("{template}\n"));
+    Expression::from(TemplateExpr::Heredoc(heredoc))
+}
+
+/// Value of a heredoc attribute, `None` for any other expression
+fn heredoc_value(expr: &Expression) -> Option<String> {
+    let Expression::TemplateExpr(template) = expr else { return None };
+    let TemplateExpr::Heredoc(heredoc) = template.as_ref() else { return None };
+
+    // Interpolations can't be resolved here, those heredocs are taken as written
+    let value = match template.evaluate(&Context::new()) {
+        Ok(hcl::Value::String(value)) => value,
+        _ => heredoc.template.clone(),
+    };
+
+    // The line break before the closing delimiter isn't part of the value
+    Some(value.strip_suffix('\n').map(str::to_string).unwrap_or(value))
+}
+
+/// Replace heredoc attributes of every block with their plain string value
+fn read_heredocs(mut body: Body) -> Body {
+    for block in body.blocks_mut() {
+        for attr in block.body.attributes_mut() {
+            if let Some(value) = heredoc_value(&attr.expr) {
+                attr.expr = Expression::String(value);
+            }
+        }
+    }
+
+    body
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(script: &str) -> (String, String) {
+        let data = hcl::block! {
+            process ("app") {
+                script = (script)
+            }
+        };
+
+        let serialized = hcl::to_string(&write_heredocs(data)).unwrap();
+        let parsed: ProcessWrapper = hcl::parse(&serialized).and_then(|body| hcl::from_body(read_heredocs(body))).unwrap();
+        (serialized, parsed.list["app"].script.clone())
+    }
+
+    #[test]
+    fn test_single_line_script_stays_quoted() {
+        let script = r#"node server.js --name "api" --home $HOME --tpl ${value} 'quoted'"#;
+        let (serialized, read) = round_trip(script);
+
+        assert!(!serialized.contains("<<"));
+        assert_eq!(read, script);
+    }
+
+    #[test]
+    fn test_multi_line_script_uses_heredoc() {
+        let scripts = [
+            "cd /srv/app\nexport NAME=\"o'brien\"\necho \"$NAME ${NAME} %{x} $$\"\nexec node index.js",
+            "printf '%s\\n' done\n  indented\n\ttabbed\n",
+            "echo start\nEOT\necho end",
+        ];
+
+        for script in scripts {
+            let (serialized, read) = round_trip(script);
+            assert!(serialized.contains("<<EOT"), "{serialized}");
+            assert_eq!(read, script);
+        }
+    }
+
+    #[test]
+    fn test_import_handwritten_heredocs() {
+        let contents = "process \"app\" {\n  script = <<-EOT\n    echo one\n      echo two\n    EOT\n}\nprocess \"env\" {\n  script = <<EOT\necho ${HOME}\nEOT\n}\n";
+        let parsed: ProcessWrapper = hcl::parse(contents).and_then(|body| hcl::from_body(read_heredocs(body))).unwrap();
+
+        assert_eq!(parsed.list["app"].script, "echo one\n  echo two");
+        assert_eq!(parsed.list["env"].script, "echo ${HOME}");
+    }
+
+    #[test]
+    fn test_url_attribute_is_optional() {
+        let contents = "process \"app\" {\n  script = \"node app.js\"\n  url = \"https://app.internal/health\"\n}\nprocess \"worker\" {\n  script = \"node worker.js\"\n}\n";
+        let parsed: ProcessWrapper = hcl::parse(contents).and_then(|body| hcl::from_body(read_heredocs(body))).unwrap();
+
+        assert_eq!(parsed.list["app"].url.as_deref(), Some("https://app.internal/health"));
+        assert_eq!(parsed.list["worker"].url, None);
+    }
+
+    #[test]
+    fn test_description_attribute() {
+        let description = "Legacy billing worker, owned by @dana\ndecommission Q3";
+        let data = hcl::block! {
+            process ("billing") {
+                script = "node worker.js"
+                description = (description)
+            }
+        };
+
+        let serialized = hcl::to_string(&write_heredocs(data)).unwrap();
+        let parsed: ProcessWrapper = hcl::parse(&serialized).and_then(|body| hcl::from_body(read_heredocs(body))).unwrap();
+        assert_eq!(parsed.list["billing"].description.as_deref(), Some(description));
+    }
+
+    #[test]
+    fn test_group_attribute() {
+        let contents = "process \"api-1\" {\n  script = \"node api.js\"\n  group = \"api\"\n}\nprocess \"cron\" {\n  script = \"./cron\"\n}\n";
+        let parsed: ProcessWrapper = hcl::parse(contents).and_then(|body| hcl::from_body(read_heredocs(body))).unwrap();
+
+        assert_eq!(parsed.list["api-1"].group.as_deref(), Some("api"));
+        assert_eq!(parsed.list["cron"].group, None);
+    }
+
+    #[test]
+    fn test_max_log_rate_attribute() {
+        let contents = "process \"api\" {\n  script = \"node api.js\"\n  max_log_rate = \"100M/min restart\"\n}\nprocess \"cron\" {\n  script = \"./cron\"\n}\n";
+        let parsed: ProcessWrapper = hcl::parse(contents).and_then(|body| hcl::from_body(read_heredocs(body))).unwrap();
+
+        assert_eq!(parsed.list["api"].max_log_rate.as_ref().map(MaxLogRate::to_string).as_deref(), Some("100M/min restart"));
+        assert_eq!(parsed.list["cron"].max_log_rate, None);
+
+        let invalid = "process \"api\" {\n  script = \"node api.js\"\n  max_log_rate = \"100M/day\"\n}\n";
+        let invalid: Result<ProcessWrapper, _> = hcl::parse(invalid).and_then(|body| hcl::from_body(read_heredocs(body)));
+        assert!(invalid.is_err());
+    }
+
+    #[test]
+    fn test_restart_cron_attribute() {
+        let contents = "process \"api\" {\n  script = \"node api.js\"\n  restart_cron = \"0 3 * * *\"\n}\n";
+        let parsed: ProcessWrapper = hcl::parse(contents).and_then(|body| hcl::from_body(read_heredocs(body))).unwrap();
+        assert_eq!(parsed.list["api"].restart_cron.as_ref().map(Schedule::to_string).as_deref(), Some("0 3 * * *"));
+
+        let invalid = "process \"api\" {\n  script = \"node api.js\"\n  restart_cron = \"0 25 * * *\"\n}\n";
+        let invalid: Result<ProcessWrapper, _> = hcl::parse(invalid).and_then(|body| hcl::from_body(read_heredocs(body)));
+        assert!(invalid.is_err());
+    }
+
+    #[test]
+    fn test_max_memory_action_attribute() {
+        let contents = "process \"api\" {\n  script = \"node api.js\"\n  max_memory = \"512M\"\n  max_memory_action = \"restart\"\n}\nprocess \"worker\" {\n  script = \"node worker.js\"\n}\n";
+        let parsed: ProcessWrapper = hcl::parse(contents).and_then(|body| hcl::from_body(read_heredocs(body))).unwrap();
+
+        assert_eq!(parsed.list["api"].max_memory_action, Some(memlimit::Action::Restart));
+        assert_eq!(parsed.list["worker"].max_memory_action, None);
+    }
+
+    #[test]
+    fn test_health_attribute() {
+        let contents = "process \"api\" {\n  script = \"node api.js\"\n  health = {\n    url = \"http://127.0.0.1:8080/health\"\n    retries = 5\n  }\n}\nprocess \"db\" {\n  script = \"./db\"\n  health = { cmd = \"pg_isready\" }\n}\n";
+        let parsed: ProcessWrapper = hcl::parse(contents).and_then(|body| hcl::from_body(read_heredocs(body))).unwrap();
+
+        let api = parsed.list["api"].get_health().unwrap().unwrap();
+        assert_eq!((api.probe.to_string(), api.interval, api.retries), (string!("GET http://127.0.0.1:8080/health"), 10, 5));
+        assert_eq!(parsed.list["db"].get_health().unwrap().map(|check| check.probe), Some(Probe::Command(string!("pg_isready"))));
+
+        let both = "process \"api\" {\n  script = \"node api.js\"\n  health = {\n    url = \"http://127.0.0.1:8080/health\"\n    cmd = \"true\"\n  }\n}\n";
+        let both: ProcessWrapper = hcl::parse(both).and_then(|body| hcl::from_body(read_heredocs(body))).unwrap();
+        assert!(both.list["api"].get_health().is_err());
+    }
+
+    #[test]
+    fn test_monitor_interval_attribute() {
+        let contents = "process \"api\" {\n  script = \"node api.js\"\n  monitor_interval_ms = 250\n}\nprocess \"worker\" {\n  script = \"node worker.js\"\n}\n";
+        let parsed: ProcessWrapper = hcl::parse(contents).and_then(|body| hcl::from_body(read_heredocs(body))).unwrap();
+
+        assert_eq!(parsed.list["api"].monitor_interval_ms, Some(250));
+        assert_eq!(parsed.list["worker"].monitor_interval_ms, None);
+    }
+
+    #[test]
+    fn test_track_children_attribute() {
+        let contents = "process \"ci\" {\n  script = \"./runner.sh\"\n  track_children = false\n}\nprocess \"api\" {\n  script = \"node api.js\"\n}\n";
+        let parsed: ProcessWrapper = hcl::parse(contents).and_then(|body| hcl::from_body(read_heredocs(body))).unwrap();
+
+        assert_eq!(parsed.list["ci"].track_children, Some(false));
+        assert_eq!(parsed.list["api"].track_children, None);
+    }
+
+    #[test]
+    fn test_shell_attributes() {
+        let contents = "process \"login\" {\n  script = \"deploy\"\n  shell = \"/bin/sh\"\n  shell_args = [\"-l\", \"-c\"]\n}\nprocess \"api\" {\n  script = \"node api.js\"\n}\nprocess \"bad\" {\n  script = \"true\"\n  shell = \"fish\"\n}\n";
+        let parsed: ProcessWrapper = hcl::parse(contents).and_then(|body| hcl::from_body(read_heredocs(body))).unwrap();
+
+        let shell = parsed.list["login"].get_shell().unwrap();
+        assert_eq!(shell.program.as_deref(), Some("/bin/sh"));
+        assert_eq!(shell.args, Some(vec![string!("-l"), string!("-c")]));
+        assert!(parsed.list["api"].get_shell().unwrap().is_empty());
+        assert!(parsed.list["bad"].get_shell().is_err());
+    }
+
+    #[test]
+    fn test_max_restarts_attribute() {
+        let contents = "process \"db\" {\n  script = \"postgres\"\n  max_restarts = 0\n}\nprocess \"legacy\" {\n  script = \"./legacy\"\n  max_restarts = 50\n}\nprocess \"api\" {\n  script = \"node api.js\"\n}\n";
+        let parsed: ProcessWrapper = hcl::parse(contents).and_then(|body| hcl::from_body(read_heredocs(body))).unwrap();
+
+        assert_eq!(parsed.list["db"].max_restarts, Some(0));
+        assert_eq!(parsed.list["legacy"].max_restarts, Some(50));
+        assert_eq!(parsed.list["api"].max_restarts, None);
+    }
+}
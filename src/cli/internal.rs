@@ -1,3 +1,5 @@
+use super::{adjust::{Changes, Update}, columns, export::translate};
+use chrono::{DateTime, SecondsFormat, Utc};
 use colored::Colorize;
 use lazy_static::lazy_static;
 use macros_rs::{crashln, string, ternary, then};
@@ -6,7 +8,16 @@ use opm::process::{MemoryInfo, unix::NativeProcess as Process};
 use regex::Regex;
 use serde::Serialize;
 use serde_json::json;
-use std::fs;
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    fs,
+    sync::mpsc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use nix::sys::signal::Signal;
 
 #[cfg(not(target_os = "linux"))]
 use nix::{
@@ -16,20 +27,29 @@ use nix::{
 };
 
 use opm::{
-    config, file,
+    alert, config,
+    file::{self, LogOptions},
     helpers::{self, ColoredString},
-    log,
+    log, notice, output,
     process::{
-        ItemSingle, Runner, get_process_cpu_usage_with_children_from_process,
-        get_process_memory_with_children, http, is_pid_alive,
+        Handles, ItemSingle, Labels, Runner, StartOptions, Watch, adopt,
+        alerts::Alert,
+        cores,
+        cron::{self, Schedule},
+        health, http, is_pid_alive, is_privileged, labels,
+        limits::{self, Limits},
+        lograte::{self, MaxLogRate},
+        memlimit, max_processes, process_find_zombies, template,
     },
 };
 
 use tabled::{
     Table, Tabled,
+    builder::Builder,
     settings::{
-        Color, Modify, Rotate, Width,
+        Color, Modify, Padding, Rotate, Width,
         object::{Columns, Rows, Segment},
+        peaker::PriorityMax,
         style::{BorderColor, Style},
         themes::Colorization,
     },
@@ -44,6 +64,211 @@ lazy_static! {
 // Constants for real-time statistics display timing
 pub(crate) const STATS_PRE_LIST_DELAY_MS: u64 = 100;
 
+/// How long `opm list --all-servers` waits for each server, all of them are asked at once
+const SERVER_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Commands longer than this are cut in the info table
+pub(super) const COMMAND_PREVIEW_WIDTH: usize = 120;
+
+/// Table cell for a command, long or multi-line commands point at `get-command --raw` for the full text
+fn command_column(command: &str, id: usize, server_name: &str) -> String {
+    let Some(preview) = helpers::truncate_command(command, COMMAND_PREVIEW_WIDTH) else {
+        return command.to_string();
+    };
+
+    match matches!(server_name, "internal" | "local") {
+        true => format!("{preview} (see `opm get-command {id} --raw`)"),
+        false => format!("{preview} (see `opm get-command {id} --raw -s {server_name}`)"),
+    }
+}
+
+/// Command a script is run with, known script files get their interpreter in front
+pub(super) fn script_command(script: &str, node: &str) -> String {
+    // Check if script is a file path with an extension
+    // Multi-line scripts always go to the shell verbatim
+    if script.contains('\n') {
+        script.to_string()
+    } else if let Some(ext_start) = script.rfind('.') {
+        let ext = &script[ext_start..];
+
+        if SCRIPT_EXTENSION_PATTERN.is_match(script) {
+            // It's a script file with extension - determine the interpreter
+            let interpreter = match ext {
+                ".js" | ".ts" | ".mjs" | ".cjs" => node.to_string(),
+                ".py" | ".py3" | ".pyw" => "python3".to_string(),
+                ".sh" | ".bash" | ".zsh" => "bash".to_string(),
+                ".rb" => "ruby".to_string(),
+                ".pl" => "perl".to_string(),
+                ".php" => "php".to_string(),
+                ".lua" => "lua".to_string(),
+                ".r" | ".R" => "Rscript".to_string(),
+                ".go" => "go run".to_string(),
+                ".java" => "java".to_string(),
+                ".kt" | ".kts" => "kotlin".to_string(),
+                ".scala" => "scala".to_string(),
+                ".groovy" => "groovy".to_string(),
+                ".swift" => "swift".to_string(),
+                _ => "".to_string(),
+            };
+
+            if !interpreter.is_empty() {
+                format!("{} {}", interpreter, script)
+            } else {
+                script.to_string()
+            }
+        } else {
+            script.to_string()
+        }
+    } else {
+        // No extension, check old pattern for js/ts
+        if SIMPLE_PATH_PATTERN.is_match(script) {
+            format!("{node} {script}")
+        } else {
+            script.to_string()
+        }
+    }
+}
+
+/// Colored and padded status for the info and list tables
+fn status_column(status: &str) -> colored::ColoredString {
+    match status {
+        "online" => "online   ".green().bold(),
+        "starting" => "starting ".cyan().bold(),
+        "pending" => "pending  ".yellow().bold(),
+        "unhealthy" => "unhealthy".magenta().bold(),
+        status => format!("{status}   ").red().bold(),
+    }
+}
+
+/// Pidfile of a forking service, the pid column then shows the adopted pid
+fn pidfile_column(item: &opm::process::Process) -> String {
+    match &item.pidfile {
+        Some(pidfile) => pidfile.path.display().to_string(),
+        None => string!("none"),
+    }
+}
+
+/// Core files with their sizes, one per line
+fn cores_column(cores: &[cores::Core]) -> String {
+    match cores.is_empty() {
+        true => string!("none  "),
+        false => cores.iter().map(|core| format!("{}  ", core.describe())).collect::<Vec<_>>().join("\n"),
+    }
+}
+
+/// One alert per line, as they were given to `--alert`
+fn alerts_column(alerts: &[Alert]) -> String {
+    match alerts.is_empty() {
+        true => string!("none  "),
+        false => alerts.iter().map(|alert| format!("{alert}  ")).collect::<Vec<_>>().join("\n"),
+    }
+}
+
+/// Current log growth, with the limit it is held to
+fn log_rate_column(rate: Option<u64>, limit: &Option<MaxLogRate>) -> String {
+    let rate = rate.map_or_else(|| string!("n/a"), lograte::format);
+    match limit {
+        Some(limit) => format!("{rate} (max {limit})  "),
+        None => format!("{rate}  "),
+    }
+}
+
+/// The restart schedule with its next time
+fn cron_restart_column(schedule: &Option<Schedule>) -> String {
+    match schedule {
+        Some(schedule) => format!("{}  ", cron::describe(schedule, Utc::now())),
+        None => string!("none  "),
+    }
+}
+
+fn handles_column(count: Option<u64>) -> String { count.map_or_else(|| string!("n/a"), |count| count.to_string()) }
+
+/// What became of the command that launched a forking service
+fn launcher_column(item: &opm::process::Process) -> String {
+    match item.pidfile.as_ref().map(|pidfile| pidfile.launcher) {
+        Some(Some(launcher)) => launcher.to_string(),
+        Some(None) => string!("not adopted"),
+        None => string!("none"),
+    }
+}
+
+/// How often the daemon checks the process, `default` being `daemon.interval` when it is known
+fn monitor_column(item: &opm::process::Process, default: Option<u64>) -> String {
+    match (item.monitor_interval_ms, default) {
+        (Some(ms), _) => format!("{ms}ms"),
+        (None, Some(ms)) => format!("{ms}ms (daemon.interval)"),
+        (None, None) => string!("daemon.interval"),
+    }
+}
+
+/// Crashes counted against the restart limit, `default` being `daemon.restarts` when it is known
+fn crash_budget_column(item: &opm::process::Process, default: Option<u64>) -> String {
+    match (item.max_restarts, default) {
+        (Some(limit), _) => format!("{}/{limit}", item.crash.value),
+        (None, Some(limit)) => format!("{}/{limit} (daemon.restarts)", item.crash.value),
+        (None, None) => format!("{} (daemon.restarts)", item.crash.value),
+    }
+}
+
+/// When a crashed process that is backing off is restarted, see `daemon.restart_delay_base_ms`
+fn next_restart_column(item: &opm::process::Process, now: DateTime<Utc>) -> String {
+    match item.crash.next_restart {
+        Some(at) if item.running && at > now => format!("in {} ({})", helpers::format_seconds((at - now).num_seconds().max(1)), at.to_rfc3339_opts(SecondsFormat::Secs, true)),
+        _ => string!("none"),
+    }
+}
+
+/// The cooldown between watch reloads and when the last one happened, with the change held back if any
+fn watch_reload_column(item: &opm::process::Process) -> String {
+    if !item.watch.enabled {
+        return string!("disabled");
+    }
+
+    let last = item.watch.last_reload.map_or_else(|| string!("never"), |at| format!("{} ago", helpers::format_duration(at)));
+    match &item.watch.pending {
+        Some(hash) => format!("{}s cooldown, last {last}, held {}", item.watch.cooldown(), &hash[..hash.len().min(12)]),
+        None => format!("{}s cooldown, last {last}", item.watch.cooldown()),
+    }
+}
+
+/// The health check and how the last one of this run went
+fn health_column(item: &opm::process::Process) -> String {
+    let Some(check) = &item.health else { return string!("none  ") };
+
+    match item.health_status() {
+        Some(status) => format!("{}\n{} {} ago  ", check.describe(), status.describe(), helpers::format_duration(status.at)),
+        None => format!("{}\nnot checked yet  ", check.describe()),
+    }
+}
+
+/// Actions the daemon queued behind the one still running on the process, it alone knows them
+fn queued(id: usize) -> Vec<String> {
+    crate::daemon::ipc::connect()
+        .and_then(|mut client| client.request(&crate::daemon::ipc::Request::Queue { id }).ok())
+        .and_then(|data| serde_json::from_value(data).ok())
+        .unwrap_or_default()
+}
+
+fn queued_column(actions: &[String]) -> String {
+    match actions.is_empty() {
+        true => string!("none"),
+        false => actions.join(", "),
+    }
+}
+
+/// Whether a supervisor runs the process, and what it last recorded
+fn supervised_column(item: &opm::process::Process) -> String {
+    match (item.supervised, item.supervisor()) {
+        (false, _) => string!("no"),
+        (true, Some(state)) if state.alive() => format!("yes (supervisor {})", state.supervisor),
+        (true, Some(state)) => match state.exit_code {
+            Some(code) => format!("yes (exited with {code})"),
+            None => string!("yes (supervisor gone)"),
+        },
+        (true, None) => string!("yes, from the next restart"),
+    }
+}
+
 pub struct Internal<'i> {
     pub id: usize,
     pub runner: Runner,
@@ -52,75 +277,116 @@ pub struct Internal<'i> {
 }
 
 impl<'i> Internal<'i> {
-    pub fn create(
-        mut self,
-        script: &String,
-        name: &Option<String>,
-        watch: &Option<String>,
-        max_memory: &Option<String>,
-        silent: bool,
-    ) -> Runner {
+    pub fn create(mut self, script: &String, name: &Option<String>, options: &StartOptions, silent: bool) -> Runner {
         let config = config::read();
         let name = match name {
             Some(name) => string!(name),
             None => string!(script.split_whitespace().next().unwrap_or_default()),
         };
 
-        // Parse max_memory if provided
-        let max_memory_bytes = match max_memory {
-            Some(mem_str) => match helpers::parse_memory(mem_str) {
-                Ok(bytes) => bytes,
-                Err(err) => crashln!("{} {}", *helpers::FAIL, err),
-            },
-            None => 0,
-        };
-
         if matches!(self.server_name, "internal" | "local") {
-            // Check if script is a file path with an extension
-            let script_to_run = if let Some(ext_start) = script.rfind('.') {
-                let ext = &script[ext_start..];
-
-                if SCRIPT_EXTENSION_PATTERN.is_match(script) {
-                    // It's a script file with extension - determine the interpreter
-                    let interpreter = match ext {
-                        ".js" | ".ts" | ".mjs" | ".cjs" => config.runner.node.clone(),
-                        ".py" | ".py3" | ".pyw" => "python3".to_string(),
-                        ".sh" | ".bash" | ".zsh" => "bash".to_string(),
-                        ".rb" => "ruby".to_string(),
-                        ".pl" => "perl".to_string(),
-                        ".php" => "php".to_string(),
-                        ".lua" => "lua".to_string(),
-                        ".r" | ".R" => "Rscript".to_string(),
-                        ".go" => "go run".to_string(),
-                        ".java" => "java".to_string(),
-                        ".kt" | ".kts" => "kotlin".to_string(),
-                        ".scala" => "scala".to_string(),
-                        ".groovy" => "groovy".to_string(),
-                        ".swift" => "swift".to_string(),
-                        _ => "".to_string(),
-                    };
+            let script_to_run = script_command(script, &config.runner.node);
 
-                    if !interpreter.is_empty() {
-                        format!("{} {}", interpreter, script)
-                    } else {
-                        script.clone()
-                    }
-                } else {
-                    script.clone()
-                }
-            } else {
-                // No extension, check old pattern for js/ts
-                if SIMPLE_PATH_PATTERN.is_match(script) {
-                    format!("{} {}", config.runner.node, script)
-                } else {
-                    script.clone()
+            if let Err(err) = self.runner.check_capacity(1, max_processes(&config.runner)) {
+                crashln!("{} {err}", *helpers::FAIL);
+            }
+
+            // A pidfile left by an instance that is gone would be adopted right away
+            let pidfile = options.pidfile.as_ref().map(|path| file::cwd().join(path));
+            if let Some(path) = &pidfile
+                && let Err(err) = adopt::clear_stale(path)
+            {
+                crashln!("{} {err}", *helpers::FAIL);
+            }
+
+            self.runner.start(&name, &script_to_run, file::cwd(), options);
+
+            // Names are not unique, the newest process with the name is the one just created
+            if let Some(path) = pidfile
+                && let Some(id) = self.runner.list.iter().filter(|(_, p)| p.name == name).map(|(id, _)| *id).max()
+            {
+                match self.runner.adopt(id, path) {
+                    Ok(pid) => then!(!silent, notice!("{} Adopted pid {pid} from the pidfile", *helpers::SUCCESS)),
+                    Err(err) => alert!("{} Failed to adopt ({name}): {err}", *helpers::FAIL),
                 }
-            };
+            }
 
-            self.runner
-                .start(&name, &script_to_run, file::cwd(), watch, max_memory_bytes)
-                .save();
+            self.runner.save();
         } else {
+            let StartOptions { env, startup_grace, monitor_interval_ms, max_restarts, watch_cooldown, url, description, group, alerts, max_log_rate, restart_cron, health, max_memory_action, pidfile, limits, shell, collect_cores, supervised, .. } = options;
+            then!(
+                startup_grace.is_some(),
+                alert!("{} --startup-grace is only applied to local processes", *helpers::WARN)
+            );
+            then!(
+                monitor_interval_ms.is_some(),
+                alert!("{} --monitor-interval is only applied to local processes", *helpers::WARN)
+            );
+            then!(
+                max_restarts.is_some(),
+                alert!("{} --max-restarts is only applied to local processes", *helpers::WARN)
+            );
+            then!(
+                watch_cooldown.is_some(),
+                alert!("{} --watch-cooldown is only applied to local processes", *helpers::WARN)
+            );
+            then!(
+                url.is_some(),
+                alert!("{} --url is only applied to local processes", *helpers::WARN)
+            );
+            then!(
+                description.is_some(),
+                alert!("{} --description is only applied to local processes, set it with opm adjust --description", *helpers::WARN)
+            );
+            then!(
+                group.is_some(),
+                alert!("{} --group is only applied to local processes, set it with opm adjust --group", *helpers::WARN)
+            );
+            then!(
+                !alerts.is_empty(),
+                alert!("{} --alert is only applied to local processes", *helpers::WARN)
+            );
+            then!(
+                max_log_rate.is_some(),
+                alert!("{} --max-log-rate is only applied to local processes", *helpers::WARN)
+            );
+            then!(
+                restart_cron.is_some(),
+                alert!("{} --cron-restart is only applied to local processes", *helpers::WARN)
+            );
+            then!(
+                max_memory_action.is_some(),
+                alert!("{} --max-memory-action is only applied to local processes", *helpers::WARN)
+            );
+            then!(
+                health.is_some(),
+                alert!("{} --health-url and --health-cmd are only applied to local processes", *helpers::WARN)
+            );
+            then!(
+                pidfile.is_some(),
+                alert!("{} --pidfile is only applied to local processes", *helpers::WARN)
+            );
+            then!(
+                !limits.is_empty(),
+                alert!("{} --umask and --ulimit-* are only applied to local processes", *helpers::WARN)
+            );
+            then!(
+                *collect_cores,
+                alert!("{} --collect-cores is only applied to local processes", *helpers::WARN)
+            );
+            then!(
+                *supervised,
+                alert!("{} --supervised is only applied to local processes", *helpers::WARN)
+            );
+            then!(
+                !shell.is_empty(),
+                alert!("{} --shell and --shell-arg are only applied to local processes", *helpers::WARN)
+            );
+            then!(
+                !env.is_empty(),
+                alert!("{} --env is only applied to local processes, set it with opm adjust --env-add", *helpers::WARN)
+            );
+
             let Some(servers) = config::servers().servers else {
                 crashln!("{} Failed to read servers", *helpers::FAIL)
             };
@@ -128,7 +394,7 @@ impl<'i> Internal<'i> {
             if let Some(server) = servers.get(self.server_name) {
                 match Runner::connect(self.server_name.into(), server.get(), false) {
                     Some(mut remote) => {
-                        remote.start(&name, script, file::cwd(), watch, max_memory_bytes)
+                        remote.start(&name, script, file::cwd(), options)
                     }
                     None => crashln!(
                         "{} Failed to connect (name={}, address={})",
@@ -148,7 +414,7 @@ impl<'i> Internal<'i> {
 
         then!(
             !silent,
-            println!(
+            notice!(
                 "{} Creating {}process with ({name})",
                 *helpers::SUCCESS,
                 self.kind
@@ -156,7 +422,7 @@ impl<'i> Internal<'i> {
         );
         then!(
             !silent,
-            println!("{} {}Created ({name}) ✓", *helpers::SUCCESS, self.kind)
+            notice!("{} {}Created ({name}) ✓", *helpers::SUCCESS, self.kind)
         );
 
         return self.runner;
@@ -172,7 +438,7 @@ impl<'i> Internal<'i> {
     ) -> Runner {
         then!(
             !silent,
-            println!(
+            notice!(
                 "{} Applying {}action restartProcess on ({})",
                 *helpers::SUCCESS,
                 self.kind,
@@ -181,6 +447,10 @@ impl<'i> Internal<'i> {
         );
 
         if matches!(self.server_name, "internal" | "local") {
+            if let Err(err) = self.runner.signal_permission(self.id) {
+                crashln!("{} Cannot restart: {err}", *helpers::FAIL);
+            }
+
             let mut item = self.runner.get(self.id);
 
             match watch {
@@ -229,7 +499,7 @@ impl<'i> Internal<'i> {
         }
 
         if !silent {
-            println!(
+            notice!(
                 "{} Restarted {}({}) ✓",
                 *helpers::SUCCESS,
                 self.kind,
@@ -244,7 +514,7 @@ impl<'i> Internal<'i> {
     pub fn reload(mut self, silent: bool) -> Runner {
         then!(
             !silent,
-            println!(
+            notice!(
                 "{} Applying {}action reloadProcess on ({})",
                 *helpers::SUCCESS,
                 self.kind,
@@ -253,6 +523,10 @@ impl<'i> Internal<'i> {
         );
 
         if matches!(self.server_name, "internal" | "local") {
+            if let Err(err) = self.runner.signal_permission(self.id) {
+                crashln!("{} Cannot reload: {err}", *helpers::FAIL);
+            }
+
             let mut item = self.runner.get(self.id);
             item.reload(true);  // Reload command should increment counter
             self.runner = item.get_runner().clone();
@@ -285,7 +559,7 @@ impl<'i> Internal<'i> {
         }
 
         if !silent {
-            println!(
+            notice!(
                 "{} Reloaded {}({}) ✓",
                 *helpers::SUCCESS,
                 self.kind,
@@ -297,10 +571,10 @@ impl<'i> Internal<'i> {
         return self.runner;
     }
 
-    pub fn stop(mut self, silent: bool) -> Runner {
+    pub fn stop(mut self, silent: bool, signal: Option<Signal>) -> Runner {
         then!(
             !silent,
-            println!(
+            notice!(
                 "{} Applying {}action stopProcess on ({})",
                 *helpers::SUCCESS,
                 self.kind,
@@ -332,21 +606,25 @@ impl<'i> Internal<'i> {
             };
         }
 
+        if let Err(err) = self.runner.signal_permission(self.id) {
+            crashln!("{} Cannot stop: {err}", *helpers::FAIL);
+        }
+
         let mut item = self.runner.get(self.id);
-        item.stop();
+        item.stop(signal);
         self.runner = item.get_runner().clone();
         self.runner.save();
 
         if !silent {
-            println!("{} Stopped {}({}) ✓", *helpers::SUCCESS, self.kind, self.id);
+            notice!("{} Stopped {}({}) ✓", *helpers::SUCCESS, self.kind, self.id);
             log!("process stopped {}(id={})", self.kind, self.id);
         }
 
         return self.runner;
     }
 
-    pub fn remove(mut self) {
-        println!(
+    pub fn remove(mut self, purge_logs: bool) {
+        notice!(
             "{} Applying {}action removeProcess on ({})",
             *helpers::SUCCESS,
             self.kind,
@@ -377,13 +655,28 @@ impl<'i> Internal<'i> {
             };
         }
 
-        self.runner.remove(self.id);
-        println!("{} Removed {}({}) ✓", *helpers::SUCCESS, self.kind, self.id);
+        let purged = self.runner.remove(self.id, purge_logs);
+        notice!("{} Removed {}({}) ✓", *helpers::SUCCESS, self.kind, self.id);
         log!("process removed (id={})", self.id);
+
+        if let Some(purged) = purged {
+            for log in &purged.removed {
+                notice!("  {} Deleted {} ({})", *helpers::SUCCESS, log.path, helpers::format_memory(log.bytes));
+            }
+            for log in &purged.shared {
+                alert!("  {} Kept {log}, another process writes to it", *helpers::WARN);
+            }
+            for err in &purged.failed {
+                alert!("  {} Cannot delete {err}", *helpers::FAIL);
+            }
+            notice!("{} Deleted {} log files, {} reclaimed", *helpers::SUCCESS, purged.removed.len(), helpers::format_memory(purged.bytes()));
+        } else if purge_logs {
+            alert!("{} The server did not report deleting any logs", *helpers::WARN);
+        }
     }
 
     pub fn flush(&mut self) {
-        println!(
+        notice!(
             "{} Applying {}action flushLogs on ({})",
             *helpers::SUCCESS,
             self.kind,
@@ -415,7 +708,7 @@ impl<'i> Internal<'i> {
         }
 
         self.runner.flush(self.id);
-        println!(
+        notice!(
             "{} Flushed Logs {}({}) ✓",
             *helpers::SUCCESS,
             self.kind,
@@ -437,17 +730,93 @@ impl<'i> Internal<'i> {
             memory_usage: String,
             #[tabled(rename = "memory limit")]
             memory_limit: String,
+            #[tabled(rename = "memory action")]
+            memory_action: memlimit::Action,
+            #[tabled(rename = "memory restarts")]
+            memory_restarts: u64,
+            #[tabled(rename = "spawn limits")]
+            spawn_limits: String,
+            #[tabled(skip)]
+            limit_values: Limits,
+            cores: String,
+            #[tabled(skip)]
+            core_files: Vec<cores::Core>,
             #[tabled(rename = "path hash")]
             hash: String,
             #[tabled(rename = "watching")]
             watch: String,
+            #[tabled(rename = "watch reloads")]
+            watch_reloads: String,
+            #[tabled(skip)]
+            watch_state: Watch,
+            #[tabled(rename = "monitor interval")]
+            monitor: String,
+            #[tabled(skip)]
+            monitor_interval_ms: Option<u64>,
+            #[tabled(rename = "queued actions")]
+            queued: String,
+            #[tabled(skip)]
+            queued_actions: Vec<String>,
+            labels: String,
+            #[tabled(skip)]
+            label_map: Labels,
+            zombies: usize,
+            #[tabled(rename = "open fds")]
+            fds: String,
+            threads: String,
+            #[tabled(skip)]
+            handles: Handles,
+            alerts: String,
+            #[tabled(skip)]
+            alert_rules: Vec<Alert>,
+            #[tabled(rename = "log rate")]
+            log_rate: String,
+            #[tabled(skip)]
+            log_rate_bytes: Option<u64>,
+            #[tabled(skip)]
+            max_log_rate: Option<MaxLogRate>,
+            #[tabled(rename = "cron restart")]
+            cron_restart: String,
+            #[tabled(skip)]
+            restart_cron: Option<Schedule>,
+            #[tabled(skip)]
+            next_cron_restart: Option<DateTime<Utc>>,
+            health: String,
+            #[tabled(skip)]
+            health_check: Option<health::Check>,
+            #[tabled(skip)]
+            health_status: Option<health::Status>,
             children: String,
             #[tabled(rename = "exec cwd")]
             path: String,
             #[tabled(rename = "script command ")]
             command: String,
+            #[tabled(skip)]
+            full_command: String,
             #[tabled(rename = "script id")]
             id: String,
+            #[tabled(rename = "restart reason")]
+            restart_reason: String,
+            fingerprint: String,
+            url: String,
+            description: String,
+            #[tabled(skip)]
+            description_text: Option<String>,
+            pidfile: String,
+            launcher: String,
+            supervised: String,
+            #[tabled(skip)]
+            is_supervised: bool,
+            #[tabled(rename = "crash budget")]
+            crash_budget: String,
+            #[tabled(rename = "next restart")]
+            next_restart: String,
+            #[tabled(skip)]
+            next_restart_at: Option<DateTime<Utc>>,
+            #[tabled(skip)]
+            crashes: u64,
+            #[tabled(skip)]
+            max_restarts: Option<u64>,
             restarts: u64,
             uptime: String,
             pid: String,
@@ -463,16 +832,48 @@ impl<'i> Internal<'i> {
                      "name": &self.name.trim(),
                      "path": &self.path.trim(),
                      "restarts": &self.restarts,
+                     "crashes": &self.crashes,
+                     "max_restarts": &self.max_restarts,
+                     "next_restart": &self.next_restart_at,
+                     "restart_reason": &self.restart_reason.trim(),
+                     "fingerprint": &self.fingerprint,
+                     "url": &self.url,
+                     "description": &self.description_text,
+                     "pidfile": &self.pidfile,
+                     "launcher": &self.launcher,
+                     "supervised": &self.is_supervised,
                      "hash": &self.hash.trim(),
                      "watch": &self.watch.trim(),
+                     "watch_cooldown": self.watch_state.enabled.then(|| self.watch_state.cooldown()),
+                     "last_watch_reload": &self.watch_state.last_reload,
+                     "watch_pending": &self.watch_state.pending,
+                     "monitor_interval_ms": &self.monitor_interval_ms,
+                     "queued": &self.queued_actions,
                      "children": &self.children,
+                     "zombies": &self.zombies,
+                     "fds": &self.handles.fds,
+                     "threads": &self.handles.threads,
+                     "alerts": &self.alert_rules,
+                     "log_rate": &self.log_rate_bytes,
+                     "max_log_rate": &self.max_log_rate,
+                     "restart_cron": &self.restart_cron,
+                     "next_cron_restart": &self.next_cron_restart,
+                     "health": &self.health_check,
+                     "health_status": &self.health_status,
                      "uptime": &self.uptime.trim(),
                      "status": &self.status.0.trim(),
                      "log_out": &self.log_out.trim(),
                      "cpu": &self.cpu_percent.trim(),
-                     "command": &self.command.trim(),
+                     "command": &self.full_command,
                      "mem": &self.memory_usage.trim(),
                      "mem_limit": &self.memory_limit.trim(),
+                     "max_memory_action": &self.memory_action,
+                     "memory_restarts": &self.memory_restarts,
+                     "umask": self.limit_values.umask.map(limits::format_umask),
+                     "ulimit_nofile": self.limit_values.nofile.map(limits::format_limit),
+                     "ulimit_core": self.limit_values.core.map(limits::format_limit),
+                     "cores": &self.core_files,
+                     "labels": &self.label_map,
                      "log_error": &self.log_error.trim(),
                 });
 
@@ -493,18 +894,18 @@ impl<'i> Internal<'i> {
                     "raw" => println!("{:?}", data[0]),
                     "json" => println!("{json}"),
                     _ => {
-                        println!(
+                        notice!(
                             "{}\n{table}\n",
                             format!("Describing {}process with id ({})", self.kind, self.id)
                                 .on_bright_white()
                                 .black()
                         );
-                        println!(
+                        notice!(
                             " {}",
                             format!("Use `opm logs {} [--lines <num>]` to display logs", self.id)
                                 .white()
                         );
-                        println!(
+                        notice!(
                             " {}",
                             format!(
                                 "Use `opm env {}`  to display environment variables",
@@ -529,11 +930,16 @@ impl<'i> Internal<'i> {
                 
                 let mut memory_usage: Option<MemoryInfo> = None;
                 let mut cpu_percent: Option<f64> = None;
+                let mut log_rate: Option<u64> = None;
+                let mut zombies = 0;
+                let mut handles = Handles::default();
 
                 let path = file::make_relative(&item.path, &home)
                     .to_string_lossy()
                     .into_owned();
-                let children = if item.children.is_empty() {
+                let children = if !item.track_children {
+                    "not tracked".to_string()
+                } else if item.children.is_empty() {
                     "none".to_string()
                 } else {
                     format!("{:?}", item.children)
@@ -545,13 +951,14 @@ impl<'i> Internal<'i> {
                     // For shell scripts, use shell_pid to capture the entire process tree
                     let pid_for_monitoring = item.shell_pid.unwrap_or(item.pid);
 
-                    if let Ok(process) = Process::new(pid_for_monitoring as u32) {
-                        memory_usage = get_process_memory_with_children(pid_for_monitoring);
-                        cpu_percent = Some(get_process_cpu_usage_with_children_from_process(
-                            &process,
-                            pid_for_monitoring,
-                        ));
+                    if Process::new_fast(pid_for_monitoring as u32).is_ok() {
+                        memory_usage = item.memory(pid_for_monitoring);
+                        let (cpu, rate) = lograte::during(item, || item.cpu_timed(pid_for_monitoring));
+                        (cpu_percent, log_rate) = (Some(cpu), rate);
                     }
+
+                    zombies = process_find_zombies(pid_for_monitoring).len();
+                    handles = item.handles(pid_for_monitoring);
                 }
 
                 let cpu_percent = match cpu_percent {
@@ -564,19 +971,8 @@ impl<'i> Internal<'i> {
                     None => string!("0b"),
                 };
 
-                let status = if process_actually_running {
-                    "online   ".green().bold()
-                } else if item.running {
-                    // Process is marked as running but PID doesn't exist - it crashed
-                    "crashed   ".red().bold()
-                } else {
-                    match item.crash.crashed {
-                        true => "crashed   ",
-                        false => "stopped   ",
-                    }
-                    .red()
-                    .bold()
-                };
+                // Marked as running but with a dead PID shows as crashed
+                let status = status_column(item.status(process_actually_running, Utc::now()));
 
                 let memory_limit = if item.max_memory > 0 {
                     format!("{}  ", helpers::format_memory(item.max_memory))
@@ -587,30 +983,66 @@ impl<'i> Internal<'i> {
                 // Only count uptime when the process is actually running
                 // Crashed or stopped processes should show "none" uptime
                 let uptime = if process_actually_running {
-                    format!("{}", helpers::format_duration(item.started))
+                    format!("{}", item.uptime(Utc::now()))
                 } else {
                     string!("none")
                 };
 
+                let full_command = item.command_line(&config.shell, &config.args);
+                let core_files = cores::available(item);
+                let daemon_config = config::read().daemon;
+                let (default_interval, default_restarts) = (daemon_config.interval, daemon_config.restarts);
+                let queued_actions = queued(self.id);
                 let data = vec![Info {
                     children,
+                    zombies,
+                    fds: handles_column(handles.fds),
+                    threads: handles_column(handles.threads),
+                    handles,
+                    alerts: alerts_column(&item.alerts),
+                    alert_rules: item.alerts.clone(),
+                    log_rate: log_rate_column(log_rate, &item.max_log_rate),
+                    log_rate_bytes: log_rate,
+                    max_log_rate: item.max_log_rate.clone(),
+                    cron_restart: cron_restart_column(&item.restart_cron),
+                    restart_cron: item.restart_cron.clone(),
+                    next_cron_restart: item.restart_cron.as_ref().and_then(|schedule| schedule.next(Utc::now())),
+                    health: health_column(item),
+                    health_check: item.health.clone(),
+                    health_status: item.health_status().cloned(),
                     cpu_percent,
                     memory_usage,
                     memory_limit,
+                    memory_action: item.max_memory_action,
+                    memory_restarts: item.memory_restarts,
+                    spawn_limits: format!("{}  ", item.limits().describe()),
+                    limit_values: item.limits(),
+                    cores: cores_column(&core_files),
+                    core_files,
                     id: string!(self.id),
                     restarts: item.restarts,
+                    crash_budget: crash_budget_column(item, Some(default_restarts)),
+                    next_restart: next_restart_column(item, Utc::now()),
+                    next_restart_at: item.crash.next_restart.filter(|_| item.running),
+                    crashes: item.crash.value,
+                    max_restarts: Some(item.restart_limit(default_restarts)),
+                    restart_reason: item.last_restart_reason.clone().unwrap_or_else(|| string!("none")),
+                    fingerprint: item.fingerprint.clone().unwrap_or_else(|| string!("none")),
+                    url: item.url.clone().unwrap_or_else(|| string!("none")),
+                    description: item.description.clone().unwrap_or_else(|| string!("none")),
+                    description_text: item.description.clone(),
+                    pidfile: pidfile_column(item),
+                    launcher: launcher_column(item),
+                    supervised: supervised_column(item),
+                    is_supervised: item.supervised,
                     name: item.name.clone(),
                     log_out: item.logs().out,
                     path: format!("{} ", path),
                     log_error: item.logs().error,
                     status: ColoredString(status),
                     pid: ternary!(process_actually_running, format!("{}", item.pid), string!("n/a")),
-                    command: format!(
-                        "{} {} '{}'",
-                        config.shell,
-                        config.args.join(" "),
-                        item.script
-                    ),
+                    command: command_column(&full_command, self.id, self.server_name),
+                    full_command,
                     hash: ternary!(
                         item.watch.enabled,
                         format!("{}  ", item.watch.hash),
@@ -621,6 +1053,18 @@ impl<'i> Internal<'i> {
                         format!("{path}/{}  ", item.watch.path),
                         string!("disabled  ")
                     ),
+                    watch_reloads: format!("{}  ", watch_reload_column(item)),
+                    watch_state: item.watch.clone(),
+                    labels: ternary!(
+                        item.labels.is_empty(),
+                        string!("none  "),
+                        format!("{}  ", labels::describe(&item.labels))
+                    ),
+                    label_map: item.labels.clone(),
+                    monitor: monitor_column(item, Some(default_interval)),
+                    monitor_interval_ms: Some(item.monitor_interval(default_interval)),
+                    queued: queued_column(&queued_actions),
+                    queued_actions,
                     uptime,
                 }];
 
@@ -657,28 +1101,22 @@ impl<'i> Internal<'i> {
             let info = http::info(&remote, self.id);
             let path = item.path.to_string_lossy().into_owned();
 
-            let status = if item.running {
-                "online   ".green().bold()
-            } else {
-                match item.crash.crashed {
-                    true => "crashed   ",
-                    false => "stopped   ",
-                }
-                .red()
-                .bold()
-            };
+            // The PID lives on the remote machine, so trust its running flag
+            let status = status_column(item.status(item.running, Utc::now()));
 
             // Only count uptime when the process is actually running (not crashed or stopped)
             // For remote processes, we can't check is_pid_alive() since the PID is on a different machine,
             // so we trust the server's running and crashed flags instead of checking the PID directly
             let uptime_value = if item.running && !item.crash.crashed {
-                format!("{}", helpers::format_duration(item.started))
+                format!("{}", item.uptime(Utc::now()))
             } else {
                 string!("none")
             };
 
             if let Ok(info) = info {
-                let stats = info.json::<ItemSingle>().unwrap().stats;
+                let single = info.json::<ItemSingle>().unwrap();
+                let (stats, zombies) = (single.stats, single.info.zombies);
+                let handles = Handles { fds: stats.fds, threads: stats.threads };
                 let children = if item.children.is_empty() {
                     "none".to_string()
                 } else {
@@ -701,15 +1139,51 @@ impl<'i> Internal<'i> {
                     string!("none  ")
                 };
 
+                let full_command = item.command_line(&remote.config.shell, &remote.config.args);
                 let data = vec![Info {
                     children,
+                    zombies,
+                    fds: handles_column(handles.fds),
+                    threads: handles_column(handles.threads),
+                    handles,
+                    alerts: alerts_column(&item.alerts),
+                    alert_rules: item.alerts.clone(),
+                    log_rate: log_rate_column(stats.log_rate, &item.max_log_rate),
+                    log_rate_bytes: stats.log_rate,
+                    max_log_rate: item.max_log_rate.clone(),
+                    cron_restart: cron_restart_column(&item.restart_cron),
+                    restart_cron: item.restart_cron.clone(),
+                    next_cron_restart: item.restart_cron.as_ref().and_then(|schedule| schedule.next(Utc::now())),
+                    health: health_column(&item),
+                    health_check: item.health.clone(),
+                    health_status: item.health_status().cloned(),
                     cpu_percent,
                     memory_usage,
                     memory_limit,
+                    memory_action: item.max_memory_action,
+                    memory_restarts: item.memory_restarts,
+                    spawn_limits: format!("{}  ", item.limits().describe()),
+                    limit_values: item.limits(),
+                    cores: cores_column(&item.crash.cores),
+                    core_files: item.crash.cores.clone(),
                     id: string!(self.id),
                     path: path.clone(),
                     status: status.into(),
                     restarts: item.restarts,
+                    crash_budget: crash_budget_column(&item, None),
+                    next_restart: next_restart_column(&item, Utc::now()),
+                    next_restart_at: item.crash.next_restart.filter(|_| item.running),
+                    crashes: item.crash.value,
+                    max_restarts: item.max_restarts,
+                    restart_reason: item.last_restart_reason.clone().unwrap_or_else(|| string!("none")),
+                    fingerprint: item.fingerprint.clone().unwrap_or_else(|| string!("none")),
+                    url: item.url.clone().unwrap_or_else(|| string!("none")),
+                    description: item.description.clone().unwrap_or_else(|| string!("none")),
+                    description_text: item.description.clone(),
+                    pidfile: pidfile_column(&item),
+                    launcher: launcher_column(&item),
+                    supervised: string!(ternary!(item.supervised, "yes", "no")),
+                    is_supervised: item.supervised,
                     name: item.name.clone(),
                     pid: ternary!(
                         item.running && !item.crash.crashed,
@@ -723,17 +1197,25 @@ impl<'i> Internal<'i> {
                         format!("{}  ", item.watch.hash),
                         string!("none  ")
                     ),
-                    command: format!(
-                        "{} {} '{}'",
-                        remote.config.shell,
-                        remote.config.args.join(" "),
-                        item.script
-                    ),
+                    command: command_column(&full_command, self.id, self.server_name),
+                    full_command,
                     watch: ternary!(
                         item.watch.enabled,
                         format!("{path}/{}  ", item.watch.path),
                         string!("disabled  ")
                     ),
+                    watch_reloads: format!("{}  ", watch_reload_column(&item)),
+                    watch_state: item.watch.clone(),
+                    labels: ternary!(
+                        item.labels.is_empty(),
+                        string!("none  "),
+                        format!("{}  ", labels::describe(&item.labels))
+                    ),
+                    label_map: item.labels.clone(),
+                    monitor: monitor_column(&item, None),
+                    monitor_interval_ms: item.monitor_interval_ms,
+                    queued: queued_column(&[]),
+                    queued_actions: vec![],
                     uptime: uptime_value,
                 }];
 
@@ -742,14 +1224,13 @@ impl<'i> Internal<'i> {
         }
     }
 
-    pub fn logs(
-        mut self,
-        lines: &usize,
-        follow: bool,
-        filter: Option<&str>,
-        errors_only: bool,
-        stats: bool,
-    ) {
+    pub fn logs(mut self, lines: &usize, follow: bool, errors_only: bool, options: &LogOptions) {
+        let window = &options.window;
+        let kinds = match errors_only {
+            true => vec!["error"],
+            false => vec!["error", "out"],
+        };
+
         if !matches!(self.server_name, "internal" | "local") {
             let Some(servers) = config::servers().servers else {
                 crashln!("{} Failed to read servers", *helpers::FAIL)
@@ -777,21 +1258,38 @@ impl<'i> Internal<'i> {
                 .runner
                 .info(self.id)
                 .unwrap_or_else(|| crashln!("{} Process ({}) not found", *helpers::FAIL, self.id));
-            println!(
+
+            if options.rotated {
+                alert!("{} --rotated only reads the logs of local processes", *helpers::WARN)
+            }
+
+            if !window.is_empty() {
+                notice!("{}", format!("Showing logs {} for {}process [{}]", window.describe(), self.kind, self.id).yellow());
+
+                for kind in kinds {
+                    match http::logs_window(self.runner.remote.as_ref().unwrap(), self.id, kind, window) {
+                        Ok(log) => {
+                            file::window_header(log.path, window, log.approximate);
+                            let start = log.lines.len().saturating_sub(*lines);
+                            file::print_lines(&log.lines[start..], self.id, kind, &item.name, options);
+                        }
+                        Err(err) => alert!("{} Failed to fetch {kind} logs: {err}", *helpers::FAIL),
+                    }
+                }
+                return;
+            }
+
+            notice!(
                 "{}",
                 format!("Showing last {lines} lines for {}process [{}] (change the value with --lines option)", self.kind, self.id).yellow()
             );
 
-            for kind in vec!["error", "out"] {
-                if errors_only && kind == "out" {
-                    continue;
-                }
-
+            for kind in kinds {
                 let logs = http::logs(&self.runner.remote.as_ref().unwrap(), self.id, kind);
 
                 if let Ok(log) = logs {
                     if log.lines.is_empty() {
-                        println!(
+                        notice!(
                             "{}",
                             format!("[OPM] No logs found for {}/{kind}", item.name).bright_black()
                         );
@@ -799,7 +1297,7 @@ impl<'i> Internal<'i> {
                     }
 
                     file::logs_internal_with_options(
-                        log.lines, *lines, log.path, self.id, kind, &item.name, filter, stats,
+                        log.lines, *lines, log.path, self.id, kind, &item.name, options,
                     )
                 }
             }
@@ -809,8 +1307,17 @@ impl<'i> Internal<'i> {
                 .info(self.id)
                 .unwrap_or_else(|| crashln!("{} Process ({}) not found", *helpers::FAIL, self.id));
 
+            if !window.is_empty() {
+                notice!("{}", format!("Showing logs {} for {}process [{}]", window.describe(), self.kind, self.id).yellow());
+
+                for kind in kinds {
+                    file::logs_window(item, *lines, kind, options);
+                }
+                return;
+            }
+
             if follow {
-                println!(
+                notice!(
                     "{}",
                     format!(
                         "Following logs for {}process [{}] (press Ctrl+C to exit)",
@@ -819,32 +1326,32 @@ impl<'i> Internal<'i> {
                     .yellow()
                 );
             } else {
-                println!(
+                notice!(
                     "{}",
                     format!("Showing last {lines} lines for {}process [{}] (change the value with --lines option)", self.kind, self.id).yellow()
                 );
             }
 
             if errors_only {
-                file::logs_with_options(item, *lines, "error", follow, filter, stats);
+                file::logs_with_options(item, *lines, "error", follow, options);
             } else {
                 // When follow mode is enabled, we can't follow both logs simultaneously
                 // So we'll only display initial content for both, then follow stdout
                 if follow {
-                    println!("{}", "\n--- Error Logs (last lines) ---".bright_red());
-                    file::logs_with_options(item, *lines, "error", false, filter, false);
-                    println!("{}", "\n--- Standard Output (following) ---".bright_green());
-                    file::logs_with_options(item, *lines, "out", true, filter, stats);
+                    notice!("{}", "\n--- Error Logs (last lines) ---".bright_red());
+                    file::logs_with_options(item, *lines, "error", false, &LogOptions { stats: false, stats_field: None, ..*options });
+                    notice!("{}", "\n--- Standard Output (following) ---".bright_green());
+                    file::logs_with_options(item, *lines, "out", true, options);
                 } else {
-                    file::logs_with_options(item, *lines, "error", false, filter, stats);
-                    file::logs_with_options(item, *lines, "out", false, filter, stats);
+                    file::logs_with_options(item, *lines, "error", false, options);
+                    file::logs_with_options(item, *lines, "out", false, options);
                 }
             }
         }
     }
 
     pub fn env(mut self) {
-        println!(
+        notice!(
             "{}",
             format!("Showing env for {}process {}:\n", self.kind, self.id).bright_yellow()
         );
@@ -873,20 +1380,32 @@ impl<'i> Internal<'i> {
             };
         }
 
+        let local = self.runner.remote.is_none();
         let item = self.runner.process(self.id);
-        item.env
-            .iter()
-            .for_each(|(key, value)| println!("{}: {}", key, value.green()));
+        let context = template::Context { id: item.id, name: &item.name, port: item.env.get("PORT").map(String::as_str) };
+
+        for (key, value) in &item.env {
+            // Templates are expanded where the process runs, the host of a remote server is not known here
+            match template::expand(value, &context).0 {
+                Cow::Owned(expanded) if item.expand_env && local && expanded != *value => {
+                    println!("{}: {} -> {}", key, value.green(), expanded.green())
+                }
+                _ => println!("{}: {}", key, value.green()),
+            }
+        }
     }
 
-    pub fn get_command(mut self) {
-        println!(
-            "{}",
-            format!(
-                "Showing startup command for {}process {}:\n",
-                self.kind, self.id
+    pub fn get_command(mut self, raw: bool, format: &str, show_secrets: bool) {
+        then!(
+            !raw && format == "shell",
+            notice!(
+                "{}",
+                format!(
+                    "Showing startup command for {}process {}:\n",
+                    self.kind, self.id
+                )
+                .bright_yellow()
             )
-            .bright_yellow()
         );
 
         if !matches!(self.server_name, "internal" | "local") {
@@ -914,24 +1433,44 @@ impl<'i> Internal<'i> {
         }
 
         let item = self.runner.process(self.id);
+
+        // The script exactly as stored, for piping into files or other tools
+        if raw {
+            print!("{}", item.script);
+            then!(!item.script.ends_with('\n'), println!());
+            return;
+        }
+
         let config = config::read().runner;
-        let command = format!(
-            "{} {} '{}'",
-            config.shell,
-            config.args.join(" "),
-            item.script
-        );
+
+        // Complete files for other runtimes, printed alone so they can be redirected
+        if format != "shell" {
+            let current: HashMap<String, String> = std::env::vars().collect();
+            let (shell, args) = item.invocation(&config.shell, &config.args);
+            let service = translate::Service::new(item, &shell, &args, &current, show_secrets);
+
+            match format {
+                "systemd" => print!("{}", translate::systemd(&service)),
+                "dockerfile" => print!("{}", translate::dockerfile(&service)),
+                _ => print!("{}", translate::compose(&service)),
+            }
+            return;
+        }
+        let command = item.command_line(&config.shell, &config.args);
 
         println!("{}", command.green().bold());
-        println!(
+        notice!(
             "\n{}",
             "You can use this command to start the process manually:".dimmed()
         );
-        println!("{}", command.white());
+        notice!("{}", command.white());
     }
 
-    pub fn adjust(mut self, command: &Option<String>, name: &Option<String>) {
-        println!(
+    pub fn adjust(mut self, changes: &Changes) {
+        // Checked before anything is changed, so a bad variable changes nothing
+        changes.check();
+
+        notice!(
             "{} Adjusting {}process ({})",
             *helpers::SUCCESS,
             self.kind,
@@ -962,47 +1501,80 @@ impl<'i> Internal<'i> {
             };
         }
 
-        // Check if at least one parameter is provided
-        if command.is_none() && name.is_none() {
-            crashln!(
-                "{} At least one of --command or --name must be provided",
-                *helpers::FAIL
-            );
-        }
+        let updates = match changes.apply(self.runner.process(self.id)) {
+            Ok(updates) => updates,
+            Err(err) => crashln!("{} {err}", *helpers::FAIL),
+        };
 
-        let process = self.runner.process(self.id);
+        for Update { field, from, to, next_restart } in updates {
+            notice!("  {} Updating {field} from '{from}' to '{to}'{}", *helpers::SUCCESS, ternary!(next_restart, ", applied at the next restart", ""));
+        }
 
-        // Update command if provided
-        if let Some(new_command) = command {
-            println!(
-                "  {} Updating command from '{}' to '{}'",
+        // A remote daemon stores it itself, like the environment
+        if let Some(description) = &changes.description {
+            let describe = |description: &Option<String>| description.clone().unwrap_or_else(|| string!("none"));
+            notice!(
+                "  {} Updating description from '{}' to '{}'",
                 *helpers::SUCCESS,
-                process.script,
-                new_command
+                describe(&self.runner.process(self.id).description),
+                describe(description)
             );
-            process.script = new_command.clone();
+
+            if let Err(err) = self.runner.set_description(self.id, description.clone()) {
+                crashln!("{} Failed to update the description: {err}", *helpers::FAIL);
+            }
         }
 
-        // Update name if provided
-        if let Some(new_name) = name {
-            println!(
-                "  {} Updating name from '{}' to '{}'",
+        if let Some(group) = &changes.group {
+            let show = |group: Option<&str>| group.unwrap_or("none").to_string();
+            notice!(
+                "  {} Updating group from '{}' to '{}'",
                 *helpers::SUCCESS,
-                process.name,
-                new_name
+                show(self.runner.process(self.id).group.as_deref()),
+                show(group.as_deref())
             );
-            process.name = new_name.clone();
+
+            if let Err(err) = self.runner.set_group(self.id, group.clone()) {
+                crashln!("{} Failed to update the group: {err}", *helpers::FAIL);
+            }
+        }
+
+        // A remote daemon applies and saves these itself
+        let mut env_changed = false;
+        if !changes.env.is_empty() {
+            let applied = match self.runner.adjust_env(self.id, &changes.env) {
+                Ok(applied) => applied,
+                Err(err) => crashln!("{} Failed to update the environment: {err}", *helpers::FAIL),
+            };
+
+            match applied.is_empty() {
+                true => notice!("  {} Environment already up to date", *helpers::SUCCESS),
+                false => notice!(
+                    "  {} Updating environment{}\n{}",
+                    *helpers::SUCCESS,
+                    ternary!(changes.restart, "", ", applied at the next restart"),
+                    applied.iter().map(|change| format!("      {change}")).collect::<Vec<_>>().join("\n")
+                ),
+            }
+            env_changed = !applied.is_empty();
         }
 
         self.runner.save();
 
-        println!(
+        notice!(
             "{} Adjusted {}({}) ✓",
             *helpers::SUCCESS,
             self.kind,
             self.id
         );
         log!("process adjusted (id={})", self.id);
+
+        let running = self.runner.info(self.id).is_some_and(|process| process.running);
+        if changes.restart {
+            self.restart(&None, &None, false, false, true);
+        } else if env_changed && running {
+            notice!("{} Restart ({}) or pass --restart for the running process to use the new environment", *helpers::INFO, self.id);
+        }
     }
 
     pub fn save(server_name: &String) {
@@ -1010,8 +1582,14 @@ impl<'i> Internal<'i> {
             crashln!("{} Cannot force save on remote servers", *helpers::FAIL)
         }
 
-        println!("{} Saved current processes to dumpfile", *helpers::SUCCESS);
-        Runner::new().save();
+        // The daemon saves its own view of the processes when it answers on its socket
+        match crate::daemon::ipc::connect().map(|mut client| client.request(&crate::daemon::ipc::Request::Save)) {
+            Some(Err(err)) => crashln!("{} Failed to save through the daemon: {err}", *helpers::FAIL),
+            Some(Ok(_)) => {}
+            None => Runner::new().save(),
+        }
+
+        notice!("{} Saved current processes to dumpfile", *helpers::SUCCESS);
     }
 
     pub fn restore(server_name: &String) {
@@ -1022,7 +1600,7 @@ impl<'i> Internal<'i> {
             crashln!("{} Cannot restore on remote servers", *helpers::FAIL)
         }
 
-        println!("{} Starting restore process...", *helpers::SUCCESS);
+        notice!("{} Starting restore process...", *helpers::SUCCESS);
 
         // Clear log folder before restoring processes
         let config = config::read();
@@ -1075,7 +1653,7 @@ impl<'i> Internal<'i> {
             .collect();
 
         if processes_to_restore.is_empty() {
-            println!("{} No processes to restore", *helpers::SUCCESS);
+            notice!("{} No processes to restore", *helpers::SUCCESS);
             Internal::list(&string!("default"), &list_name);
             return;
         }
@@ -1115,7 +1693,7 @@ impl<'i> Internal<'i> {
                     restored_ids.push(*id);
                 } else {
                     failed_ids.push((*id, name.clone()));
-                    println!(
+                    alert!(
                         "{} Failed to restore process '{}' (id={}) - process is not running",
                         *helpers::FAIL,
                         name,
@@ -1129,7 +1707,7 @@ impl<'i> Internal<'i> {
                 }
             } else {
                 failed_ids.push((*id, name.clone()));
-                println!(
+                alert!(
                     "{} Failed to restore process '{}' (id={}) - process not found",
                     *helpers::FAIL,
                     name,
@@ -1146,170 +1724,77 @@ impl<'i> Internal<'i> {
         Internal::list(&string!("default"), &list_name);
     }
 
-    pub fn list(format: &String, server_name: &String) {
+    pub fn list(format: &str, server_name: &String) {
+        Internal::list_matching(format, server_name, &[], &[], None)
+    }
+
+    /// List processes carrying every one of the given labels, showing the given columns
+    pub fn list_matching(format: &str, server_name: &String, filters: &[(String, String)], columns: &[String], max_width: Option<usize>) {
         // Check permissions for remote operations
         super::check_remote_permission(server_name);
-        
+
+        // The table is decoration in quiet mode, skip reading a dump another invocation may be writing
+        if output::quiet() && !matches!(format, "raw" | "json" | "csv" | "minimal") {
+            return;
+        }
+
+        let defaulted = !columns::picked(columns);
+        let columns = columns::selected(columns, format);
+        let sampled = columns::sampled(&columns, format);
+        let max_width = columns::max_width(max_width);
+        let max_restarts = config::read().daemon.restarts;
+
         let render_list = |runner: &mut Runner, internal: bool| {
-            let mut processes: Vec<ProcessItem> = Vec::new();
-
-            #[derive(Tabled, Debug)]
-            struct ProcessItem {
-                id: ColoredString,
-                name: String,
-                pid: String,
-                uptime: String,
-                #[tabled(rename = "↺")]
-                restarts: String,
-                status: ColoredString,
-                cpu: String,
-                mem: String,
-                #[tabled(rename = "watching")]
-                watch: String,
+            // Full items for scripts, an empty table is an empty array rather than a notice
+            if matches!(format, "raw" | "json") {
+                let mut processes = match runner.processes() {
+                    Ok(processes) => processes,
+                    Err(err) => {
+                        alert!("{} Failed to list processes: {err}", *helpers::FAIL);
+                        return;
+                    }
+                };
+                processes.retain(|process| labels::matches(&process.labels, filters));
+
+                match format {
+                    "raw" => println!("{:?}", processes),
+                    _ => println!("{}", json!(processes)),
+                }
+                return;
             }
 
-            impl serde::Serialize for ProcessItem {
-                fn serialize<S: serde::Serializer>(
-                    &self,
-                    serializer: S,
-                ) -> Result<S::Ok, S::Error> {
-                    let trimmed_json = json!({
-                        "cpu": &self.cpu.trim(),
-                        "mem": &self.mem.trim(),
-                        "id": &self.id.0.trim(),
-                        "pid": &self.pid.trim(),
-                        "name": &self.name.trim(),
-                        "watch": &self.watch.trim(),
-                        "uptime": &self.uptime.trim(),
-                        "status": &self.status.0.trim(),
-                        "restarts": &self.restarts.trim(),
-                    });
-                    trimmed_json.serialize(serializer)
+            if format == "minimal" {
+                for process in list_rows(runner, internal, filters, false, max_restarts) {
+                    println!("{}", columns.iter().map(|column| process.text(column)).collect::<Vec<_>>().join("\t"));
                 }
+                return;
             }
 
             if runner.is_empty() {
-                println!("{} Process table empty", *helpers::SUCCESS);
-            } else {
-                for (id, item) in runner.items() {
-                    // Check if process actually exists before reporting as online
-                    // A process marked as running but with a non-existent PID should be shown as crashed
-                    let process_actually_running = item.running && is_pid_alive(item.pid);
-                    
-                    let mut cpu_percent: String = string!("0.00%");
-                    let mut memory_usage: String = string!("0b");
-
-                    // Only fetch CPU and memory stats if process is actually running
-                    // Stopped or crashed processes should always show 0% CPU and 0b memory
-                    if process_actually_running {
-                        if internal {
-                            let mut usage_internals: (Option<f64>, Option<MemoryInfo>) = (None, None);
-
-                            // For shell scripts, use shell_pid to capture the entire process tree
-                            let pid_for_monitoring = item.shell_pid.unwrap_or(item.pid);
-
-                            if let Ok(process) = Process::new(pid_for_monitoring as u32) {
-                                usage_internals = (
-                                    Some(get_process_cpu_usage_with_children_from_process(
-                                        &process,
-                                        pid_for_monitoring,
-                                    )),
-                                    get_process_memory_with_children(pid_for_monitoring),
-                                );
-                            }
-
-                            cpu_percent = match usage_internals.0 {
-                                Some(percent) => format!("{:.2}%", percent),
-                                None => string!("0.00%"),
-                            };
-
-                            memory_usage = match usage_internals.1 {
-                                Some(usage) => helpers::format_memory(usage.rss),
-                                None => string!("0b"),
-                            };
-                        } else {
-                            let info = http::info(&runner.remote.as_ref().unwrap(), id);
-
-                            if let Ok(info) = info {
-                                let stats = info.json::<ItemSingle>().unwrap().stats;
-
-                                cpu_percent = match stats.cpu_percent {
-                                    Some(percent) => format!("{:.2}%", percent),
-                                    None => string!("0.00%"),
-                                };
-
-                                memory_usage = match stats.memory_usage {
-                                    Some(usage) => helpers::format_memory(usage.rss),
-                                    None => string!("0b"),
-                                };
-                            }
-                        }
-                    }
+                notice!("{} Process table empty", *helpers::SUCCESS);
+                return;
+            }
 
-                    let status = if process_actually_running {
-                        "online   ".green().bold()
-                    } else if item.running {
-                        // Process is marked as running but PID doesn't exist - it crashed
-                        "crashed   ".red().bold()
-                    } else {
-                        match item.crash.crashed {
-                            true => "crashed   ",
-                            false => "stopped   ",
-                        }
-                        .red()
-                        .bold()
-                    };
+            let processes = list_rows(runner, internal, filters, sampled, max_restarts);
+            let columns = ternary!(defaulted && processes.iter().any(ListRow::grouped), columns::with_group(&columns), columns.clone());
+            let table = list_table(&columns, processes.iter().map(|process| process.cells(&columns)), max_width);
 
-                    // Only count uptime when the process is actually running
-                    // Crashed or stopped processes should show "none" uptime
-                    let uptime = if process_actually_running {
-                        format!("{}  ", helpers::format_duration(item.started))
-                    } else {
-                        string!("none  ")
-                    };
-
-                    processes.push(ProcessItem {
-                        status: status.into(),
-                        cpu: format!("{cpu_percent}   "),
-                        mem: format!("{memory_usage}   "),
-                        id: id.to_string().cyan().bold().into(),
-                        restarts: format!("{}  ", item.restarts),
-                        name: format!("{}   ", item.name.clone()),
-                        pid: ternary!(process_actually_running, format!("{}  ", item.pid), string!("n/a  ")),
-                        watch: ternary!(
-                            item.watch.enabled,
-                            format!("{}  ", item.watch.path),
-                            string!("disabled  ")
-                        ),
-                        uptime,
-                    });
+            match format {
+                "csv" => {
+                    let rows: Vec<Vec<String>> = processes.iter().map(|process| columns.iter().map(|column| process.text(column)).collect()).collect();
+                    println!("{}", columns::csv(&columns, &rows));
                 }
-
-                let table = Table::new(&processes)
-                    .with(Style::rounded().remove_verticals())
-                    .with(Modify::new(Segment::all()).with(BorderColor::filled(Color::new("\x1b[38;2;45;55;72m", "\x1b[39m"))))
-                    .with(Colorization::exact([Color::FG_BRIGHT_CYAN], Rows::first()))
-                    .with(Modify::new(Columns::single(1)).with(Width::truncate(40).suffix("... ")))
-                    .to_string();
-
-                if let Ok(json) = serde_json::to_string(&processes) {
-                    match format.as_str() {
-                        "raw" => println!("{:?}", processes),
-                        "json" => println!("{json}"),
-                        "default" => println!("{table}"),
-                        _ => {}
-                    };
-                };
-            }
+                "default" | "wide" => notice!("{table}"),
+                _ => {}
+            };
         };
-
         if let Some(servers) = config::servers().servers {
             let mut failed: Vec<(String, String)> = vec![];
 
             if let Some(server) = servers.get(server_name) {
                 match Runner::connect(server_name.clone(), server.get(), true) {
                     Some(mut remote) => render_list(&mut remote, false),
-                    None => println!(
+                    None => alert!(
                         "{} Failed to fetch (name={server_name}, address={})",
                         *helpers::FAIL,
                         server.address
@@ -1317,8 +1802,8 @@ impl<'i> Internal<'i> {
                 }
             } else {
                 if matches!(&**server_name, "internal" | "all" | "global" | "local") {
-                    if *server_name == "all" || *server_name == "global" {
-                        println!("{} Internal daemon", *helpers::SUCCESS);
+                    if (*server_name == "all" || *server_name == "global") && matches!(format, "default" | "wide") {
+                        notice!("{} Internal daemon", *helpers::SUCCESS);
                     }
                     render_list(&mut Runner::new(), true);
                 } else {
@@ -1336,9 +1821,9 @@ impl<'i> Internal<'i> {
             }
 
             if !failed.is_empty() {
-                println!("{} Failed servers:", *helpers::FAIL);
+                alert!("{} Failed servers:", *helpers::FAIL);
                 failed.iter().for_each(|server| {
-                    println!(
+                    alert!(
                         " {} {} {}",
                         "-".yellow(),
                         format!("{}", server.0),
@@ -1350,4 +1835,343 @@ impl<'i> Internal<'i> {
             render_list(&mut Runner::new(), true);
         }
     }
+
+    /// List the local processes and those of every server of servers.toml, the servers fetched side by side
+    ///
+    /// A server that fails or does not answer within `SERVER_TIMEOUT` gets an error line instead of aborting the list.
+    pub fn list_servers(format: &str, filters: &[(String, String)], columns: &[String], max_width: Option<usize>, merged: bool) {
+        super::check_remote_permission(&string!("all"));
+
+        if output::quiet() && !matches!(format, "raw" | "json" | "csv" | "minimal") {
+            return;
+        }
+
+        let defaulted = !columns::picked(columns);
+        let columns = columns::selected(columns, format);
+        let sampled = columns::sampled(&columns, format);
+        let max_width = columns::max_width(max_width);
+        let max_restarts = config::read().daemon.restarts;
+        let servers = config::servers().servers.unwrap_or_default();
+        let deadline = Instant::now() + SERVER_TIMEOUT;
+
+        let (sender, receiver) = mpsc::channel();
+        for (name, server) in &servers {
+            let (sender, name, server, filters) = (sender.clone(), name.clone(), server.get(), filters.to_vec());
+
+            thread::spawn(move || {
+                let address = server.address.clone();
+                let rows = match Runner::connect(name.clone(), server, false) {
+                    Some(remote) => Ok(list_rows(&remote, false, &filters, sampled, max_restarts)),
+                    None => Err(format!("unreachable [{address}]")),
+                };
+                let _ = sender.send((name, rows));
+            });
+        }
+        drop(sender);
+
+        // The local processes are read while the servers answer
+        let mut listed = vec![(string!("local"), Ok(list_rows(&Runner::new(), true, filters, sampled, max_restarts)))];
+        let mut fetched: HashMap<String, Result<Vec<ListRow>, String>> = HashMap::new();
+
+        while fetched.len() < servers.len() {
+            match receiver.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                Ok((name, rows)) => {
+                    fetched.insert(name, rows);
+                }
+                Err(_) => break,
+            }
+        }
+
+        listed.extend(servers.iter().map(|(name, server)| {
+            let timeout = || Err(format!("no answer within {}s [{}]", SERVER_TIMEOUT.as_secs(), server.address));
+            (name.clone(), fetched.remove(name).unwrap_or_else(timeout))
+        }));
+
+        let grouped = listed.iter().any(|(_, rows)| rows.as_ref().is_ok_and(|rows| rows.iter().any(ListRow::grouped)));
+        let columns = ternary!(defaulted && grouped && format != "minimal", columns::with_group(&columns), columns);
+
+        match format {
+            "raw" => println!("{:?}", listed),
+            "json" => {
+                let servers: Vec<serde_json::Value> = listed
+                    .iter()
+                    .map(|(server, rows)| match rows {
+                        Ok(rows) => json!({ "server": server, "processes": rows }),
+                        Err(err) => json!({ "server": server, "processes": [], "error": err }),
+                    })
+                    .collect();
+                println!("{}", json!(servers));
+            }
+            "csv" => {
+                let header: Vec<String> = std::iter::once(string!("server")).chain(columns.iter().cloned()).collect();
+                let mut rows: Vec<Vec<String>> = vec![];
+
+                for (server, listed) in &listed {
+                    match listed {
+                        Ok(processes) => rows.extend(processes.iter().map(|process| std::iter::once(server.clone()).chain(columns.iter().map(|column| process.text(column))).collect())),
+                        Err(err) => alert!("{} {server}: {err}", *helpers::FAIL),
+                    }
+                }
+
+                println!("{}", columns::csv(&header, &rows));
+            }
+            "minimal" => {
+                for (server, listed) in &listed {
+                    match listed {
+                        Ok(processes) => processes.iter().for_each(|process| println!("{server}\t{}", columns.iter().map(|column| process.text(column)).collect::<Vec<_>>().join("\t"))),
+                        Err(err) => alert!("{} {server}: {err}", *helpers::FAIL),
+                    }
+                }
+            }
+            "default" | "wide" if merged => {
+                let header: Vec<String> = std::iter::once(string!("server")).chain(columns.iter().cloned()).collect();
+                let rows = listed.iter().flat_map(|(server, listed)| -> Vec<Vec<String>> {
+                    match listed {
+                        Ok(processes) => processes.iter().map(|process| std::iter::once(server.clone()).chain(process.cells(&columns)).collect()).collect(),
+                        // One row per unreachable server, the error in the first column after its name
+                        Err(err) => vec![std::iter::once(server.clone()).chain(std::iter::once(err.red().to_string())).chain(columns.iter().skip(1).map(|_| String::new())).collect()],
+                    }
+                });
+
+                notice!("{}", list_table(&header, rows, max_width));
+            }
+            "default" | "wide" => {
+                for (server, listed) in &listed {
+                    match listed {
+                        Ok(processes) if processes.is_empty() => notice!("{} {}: process table empty", *helpers::SUCCESS, server.bold()),
+                        Ok(processes) => {
+                            notice!("{} {}", *helpers::SUCCESS, server.bold());
+                            notice!("{}", list_table(&columns, processes.iter().map(|process| process.cells(&columns)), max_width));
+                        }
+                        Err(err) => alert!("{} {}: {err}", *helpers::FAIL, server.bold()),
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// One process of `opm list`, its columns formatted for the table
+#[derive(Debug)]
+struct ListRow {
+    id: ColoredString,
+    name: String,
+    pid: String,
+    uptime: String,
+    restarts: String,
+    status: ColoredString,
+    cpu: String,
+    mem: String,
+    watch: String,
+    labels: Labels,
+    url: String,
+    group: String,
+    ports: String,
+    restarts_remaining: String,
+    fds: String,
+    threads: String,
+}
+
+impl ListRow {
+    fn grouped(&self) -> bool { self.group != "none" }
+
+    /// Column text without padding or color, as written to csv
+    fn text(&self, column: &str) -> String {
+        let text: &str = match column {
+            "id" => &self.id.0,
+            "status" => &self.status.0,
+            "name" => &self.name,
+            "pid" => &self.pid,
+            "uptime" => &self.uptime,
+            "restarts" => &self.restarts,
+            "cpu" => &self.cpu,
+            "mem" => &self.mem,
+            "watch" => &self.watch,
+            "url" => &self.url,
+            "group" => &self.group,
+            "ports" => &self.ports,
+            "restarts_remaining" => &self.restarts_remaining,
+            "fds" => &self.fds,
+            "threads" => &self.threads,
+            "labels" if self.labels.is_empty() => "none",
+            "labels" => return self.labels.iter().map(|(key, value)| format!("{key}={value}")).collect::<Vec<_>>().join(","),
+            _ => "",
+        };
+        text.trim().to_string()
+    }
+
+    /// Table cells of `columns`, in order
+    fn cells(&self, columns: &[String]) -> Vec<String> { columns.iter().map(|column| self.cell(column)).collect() }
+
+    /// Table cell, id and status keep their color
+    fn cell(&self, column: &str) -> String {
+        match column {
+            "id" | "status" => {
+                let mut colored = ternary!(column == "id", self.id.0.clone(), self.status.0.clone());
+                colored.input = self.text(column);
+                colored.to_string()
+            }
+            column => self.text(column),
+        }
+    }
+}
+
+impl serde::Serialize for ListRow {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let trimmed_json = json!({
+            "cpu": &self.cpu.trim(),
+            "mem": &self.mem.trim(),
+            "id": &self.id.0.trim(),
+            "pid": &self.pid.trim(),
+            "name": &self.name.trim(),
+            "watch": &self.watch.trim(),
+            "uptime": &self.uptime.trim(),
+            "status": &self.status.0.trim(),
+            "restarts": &self.restarts.trim(),
+            "labels": &self.labels,
+            "url": ternary!(self.url.trim() == "none", None, Some(self.url.trim())),
+            "group": ternary!(self.group == "none", None, Some(&self.group)),
+            "ports": ternary!(self.ports == "none", None, Some(&self.ports)),
+            "restarts_remaining": self.restarts_remaining.parse::<u64>().ok(),
+            "fds": self.fds.parse::<u64>().ok(),
+            "threads": self.threads.parse::<u64>().ok(),
+        });
+        trimmed_json.serialize(serializer)
+    }
+}
+
+/// Rows of the processes of `runner` carrying every one of `filters`, cpu and memory are read when `sampled`
+fn list_rows(runner: &Runner, internal: bool, filters: &[(String, String)], sampled: bool, max_restarts: u64) -> Vec<ListRow> {
+    let mut processes: Vec<ListRow> = Vec::new();
+
+    for (id, item) in runner.items() {
+        if !labels::matches(&item.labels, filters) {
+            continue;
+        }
+
+        // Check if process actually exists before reporting as online
+        // A process marked as running but with a non-existent PID should be shown as crashed
+        let process_actually_running = item.running && is_pid_alive(item.pid);
+
+        let mut cpu_percent: String = string!("0.00%");
+        let mut memory_usage: String = string!("0b");
+        let mut handles = Handles::default();
+
+        // Only fetch CPU and memory stats if process is actually running and they are shown
+        // Stopped or crashed processes should always show 0% CPU and 0b memory
+        if process_actually_running && sampled {
+            if internal {
+                let mut usage_internals: (Option<f64>, Option<MemoryInfo>) = (None, None);
+
+                // For shell scripts, use shell_pid to capture the entire process tree
+                let pid_for_monitoring = item.shell_pid.unwrap_or(item.pid);
+
+                if let Ok(process) = Process::new_fast(pid_for_monitoring as u32) {
+                    usage_internals = (Some(item.cpu(&process, pid_for_monitoring)), item.memory(pid_for_monitoring));
+                }
+
+                handles = item.handles(pid_for_monitoring);
+
+                cpu_percent = match usage_internals.0 {
+                    Some(percent) => format!("{:.2}%", percent),
+                    None => string!("0.00%"),
+                };
+
+                memory_usage = match usage_internals.1 {
+                    Some(usage) => helpers::format_memory(usage.rss),
+                    None => string!("0b"),
+                };
+            } else {
+                let info = http::info(runner.remote.as_ref().unwrap(), id);
+
+                if let Ok(info) = info {
+                    let stats = info.json::<ItemSingle>().unwrap().stats;
+                    handles = Handles { fds: stats.fds, threads: stats.threads };
+
+                    cpu_percent = match stats.cpu_percent {
+                        Some(percent) => format!("{:.2}%", percent),
+                        None => string!("0.00%"),
+                    };
+
+                    memory_usage = match stats.memory_usage {
+                        Some(usage) => helpers::format_memory(usage.rss),
+                        None => string!("0b"),
+                    };
+                }
+            }
+        }
+
+        // Marked as running but with a dead PID shows as crashed
+        let status = status_column(item.status(process_actually_running, Utc::now()));
+
+        // Only count uptime when the process is actually running
+        // Crashed or stopped processes should show "none" uptime
+        let uptime = if process_actually_running {
+            format!("{}  ", item.uptime(Utc::now()))
+        } else {
+            string!("none  ")
+        };
+
+        processes.push(ListRow {
+            status: status.into(),
+            cpu: format!("{cpu_percent}   "),
+            mem: format!("{memory_usage}   "),
+            id: id.to_string().cyan().bold().into(),
+            restarts: format!("{}  ", item.restarts),
+            name: ternary!(
+                internal && process_actually_running && is_privileged(item.pid),
+                format!("{} (privileged)   ", item.name),
+                format!("{}   ", item.name)
+            ),
+            pid: ternary!(process_actually_running, format!("{}  ", item.pid), string!("n/a  ")),
+            watch: ternary!(
+                item.watch.enabled,
+                format!("{}  ", item.watch.path),
+                string!("disabled  ")
+            ),
+            labels: item.labels.clone(),
+            url: format!("{}  ", item.url.as_deref().unwrap_or("none")),
+            group: item.group().unwrap_or("none").to_string(),
+            ports: item.env.get("PORT").cloned().unwrap_or_else(|| string!("none")),
+            // The limit of a remote daemon is its own config, which is not known here
+            restarts_remaining: ternary!(internal, item.restart_limit(max_restarts).saturating_sub(item.crash.value).to_string(), string!("n/a")),
+            fds: handles_column(handles.fds),
+            threads: handles_column(handles.threads),
+            uptime,
+        });
+    }
+
+    processes
+}
+
+/// The `opm list` table of `rows`, one cell per entry of `columns`
+fn list_table(columns: &[String], rows: impl IntoIterator<Item = Vec<String>>, max_width: Option<usize>) -> Table {
+    let mut builder = Builder::default();
+    builder.push_record(columns.iter().map(|column| columns::header(column)));
+    rows.into_iter().for_each(|row| builder.push_record(row));
+
+    let mut table = builder.build();
+    table
+        .with(Style::rounded().remove_verticals())
+        .with(Modify::new(Segment::all()).with(BorderColor::filled(Color::new("\x1b[38;2;45;55;72m", "\x1b[39m"))))
+        .with(Colorization::exact([Color::FG_BRIGHT_CYAN], Rows::first()))
+        .with(Modify::new(Segment::all()).with(Padding::new(1, 2, 0, 0)));
+
+    for (index, column) in columns.iter().enumerate() {
+        then!(
+            matches!(column.as_str(), "name" | "watch"),
+            table.with(Modify::new(Columns::single(index)).with(Width::truncate(40).suffix("...")))
+        );
+    }
+
+    // Narrow terminals cut the widest columns first instead of wrapping every row
+    if let Some(width) = max_width {
+        table.with(Width::truncate(width).priority(PriorityMax::right()).suffix("…"));
+    }
+
+    table
 }
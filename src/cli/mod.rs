@@ -1,13 +1,48 @@
 mod args;
 pub use args::*;
 
+pub(crate) mod adjust;
+pub(crate) mod audit;
+pub(crate) mod columns;
+pub(crate) mod events;
+pub(crate) mod export;
 pub(crate) mod import;
 pub(crate) mod internal;
+pub(crate) mod notifications;
+pub(crate) mod pm2;
+pub(crate) mod report;
+pub(crate) mod rolling;
+pub(crate) mod servers;
+pub(crate) mod snapshot;
+pub(crate) mod snapshots;
+pub(crate) mod stats;
+pub(crate) mod tail;
+pub(crate) mod ui;
+pub(crate) mod wait;
 
 use internal::{Internal, STATS_PRE_LIST_DELAY_MS};
-use macros_rs::{crashln, string, ternary};
-use opm::{config, helpers, process::Runner};
+use macros_rs::{crashln, string, ternary, then};
+use anyhow::anyhow;
+use inquire::InquireError;
+use nix::sys::signal::Signal;
+use opm::{
+    alert,
+    audit::Filter,
+    config,
+    file::LogOptions,
+    helpers, notice, output,
+    process::{
+        Process, Runner, StartOptions, archive,
+        collision::{self, Collision},
+        cores, fingerprint, group, http, is_pid_alive, labels, logdir, shutdown,
+        stats::By,
+    },
+};
+use std::collections::BTreeMap;
 use std::env;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
 use std::thread;
 use std::time::Duration;
 
@@ -24,6 +59,116 @@ pub(crate) fn format(server_name: &String) -> (String, String) {
     return (kind, server_name.to_string());
 }
 
+/// Runner of the local daemon, or of a server of servers.toml
+fn connect(server_name: &str) -> Runner {
+    if LOCAL_SERVER_NAMES.contains(&server_name) {
+        return Runner::new();
+    }
+
+    let Some(servers) = config::servers().servers else {
+        crashln!("{} Failed to read servers", *helpers::FAIL)
+    };
+
+    let Some(server) = servers.get(server_name) else {
+        crashln!("{} Server '{server_name}' does not exist", *helpers::FAIL)
+    };
+
+    match Runner::connect(server_name.to_string(), server.get(), false) {
+        Some(remote) => remote,
+        None => crashln!(
+            "{} Failed to connect (name={server_name}, address={})",
+            *helpers::FAIL,
+            server.address
+        ),
+    }
+}
+
+/// `items` with each pattern or group replaced by the processes it stands for on `server_name`, in id order
+pub fn expand(items: &Items, server_name: &str) -> Items {
+    if !items.items.iter().any(|item| matches!(item, Item::Pattern(_) | Item::Group(_))) {
+        return items.clone();
+    }
+
+    let runner = connect(server_name);
+    let items = items
+        .items
+        .iter()
+        .flat_map(|item| match matching(&runner, item) {
+            Some(ids) => ids.into_iter().map(Item::Id).collect(),
+            None => vec![item.clone()],
+        })
+        .collect();
+
+    Items::multiple(items)
+}
+
+/// The process `item` names, a pattern or group has to stand for exactly one and lists them otherwise
+pub fn resolve(item: &Item, server_name: &str) -> Item {
+    if !matches!(item, Item::Pattern(_) | Item::Group(_)) {
+        return item.clone();
+    }
+
+    let runner = connect(server_name);
+
+    match matching(&runner, item).unwrap_or_default()[..] {
+        [id] => Item::Id(id),
+        ref ids => {
+            let names: Vec<String> = ids.iter().map(|id| format!("{} ({id})", runner.list[id].name)).collect();
+            crashln!(
+                "{} {} '{}' matches {} processes, give one of them: {}",
+                *helpers::FAIL,
+                ternary!(matches!(item, Item::Group(_)), "Group", "Pattern"),
+                item.get_string().unwrap_or_default(),
+                ids.len(),
+                names.join(", ")
+            )
+        }
+    }
+}
+
+/// Ids of the processes a pattern matches or a group holds, there has to be at least one. None for other items.
+fn matching(runner: &Runner, item: &Item) -> Option<Vec<usize>> {
+    let (ids, what): (Vec<usize>, String) = match item {
+        Item::Pattern(pattern) => (
+            runner.list.iter().filter(|(_, process)| glob_matches(pattern, &process.name)).map(|(id, _)| *id).collect(),
+            format!("match pattern '{pattern}'"),
+        ),
+        Item::Group(group) => (
+            runner.list.iter().filter(|(_, process)| process.group() == Some(group.as_str())).map(|(id, _)| *id).collect(),
+            format!("are in group '{group}'"),
+        ),
+        Item::Id(_) | Item::Name(_) => return None,
+    };
+
+    if ids.is_empty() {
+        crashln!("{} No processes {what}", *helpers::FAIL);
+    }
+
+    Some(ids)
+}
+
+/// Resolve the processes targeted by `--label` filters (every process without any), narrowed to `items` when given
+fn label_targets(items: Option<&Items>, filters: &[(String, String)], server_name: &str) -> Vec<usize> {
+    let runner = connect(server_name);
+    let matching = runner.find_by_labels(filters);
+
+    match items {
+        Some(items) if !items.is_all() => items
+            .items
+            .iter()
+            .map(|item| match item {
+                Item::Id(id) => *id,
+                Item::Name(name) | Item::Pattern(name) | Item::Group(name) => match runner.list.iter().find(|(_, p)| p.name == *name) {
+                    Some((id, _)) => *id,
+                    None => crashln!("{} Process ({name}) not found", *helpers::FAIL),
+                },
+            })
+            .filter(|id| matching.contains(id))
+            .collect(),
+        _ => matching,
+    }
+}
+
 /// Check if the current role allows remote operations
 pub(crate) fn check_remote_permission(server_name: &String) {
     let config = config::read();
@@ -37,6 +182,43 @@ pub(crate) fn check_remote_permission(server_name: &String) {
     }
 }
 
+/// `--if-changed` compares with the `.env` and watched files of this machine
+fn check_if_changed(if_changed: bool, server_name: &str) {
+    if if_changed && !LOCAL_SERVER_NAMES.contains(&server_name) {
+        crashln!("{} --if-changed is only available for local processes", *helpers::FAIL);
+    }
+}
+
+/// With `--if-changed`, true for a running process whose fingerprint still matches, reported as unchanged
+fn unchanged(runner: &Runner, id: usize, if_changed: bool) -> bool {
+    let Some(process) = runner.info(id).filter(|_| if_changed) else {
+        return false;
+    };
+
+    // A stopped or crashed process is always restarted, whatever it would run with
+    let unchanged = process.running && is_pid_alive(process.pid) && fingerprint::unchanged(process);
+    then!(unchanged, println!("{} unchanged", process.name));
+
+    unchanged
+}
+
+/// The local process `--name` belongs to, when `opm start` was also given a command
+fn taken(runner: &Runner, name: &Option<String>, script: &str, server_name: &str) -> Option<Collision> {
+    let name = name.as_deref().filter(|name| *name != script && LOCAL_SERVER_NAMES.contains(&server_name))?;
+    collision::check(&runner.list, name, &internal::script_command(script, &config::read().runner.node))
+}
+
+/// Read a command from a file for `--command-file`, newlines and quotes are kept as written
+pub fn read_command_file(path: &PathBuf) -> String {
+    let command = match fs::read_to_string(path) {
+        Ok(contents) => contents.trim_end_matches(['\r', '\n']).to_string(),
+        Err(err) => crashln!("{} Cannot read command file {}: {err}", *helpers::FAIL, path.display()),
+    };
+
+    then!(command.trim().is_empty(), crashln!("{} Command file {} is empty", *helpers::FAIL, path.display()));
+    command
+}
+
 pub fn get_version(short: bool) -> String {
     return match short {
         true => format!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION")),
@@ -60,8 +242,7 @@ pub fn get_version(short: bool) -> String {
 pub fn start(
     name: &Option<String>,
     args: &Args,
-    watch: &Option<String>,
-    max_memory: &Option<String>,
+    options: &StartOptions,
     reset_env: &bool,
     server_name: &String,
     workers: &Option<usize>,
@@ -73,6 +254,96 @@ pub fn start(
     let mut runner = Runner::new();
     let (kind, list_name) = format(server_name);
 
+    then!(
+        options.force_update && !LOCAL_SERVER_NAMES.contains(&server_name.as_str()),
+        alert!("{} --force-update is only applied to local processes", *helpers::WARN)
+    );
+
+    if let Err(err) = labels::validate(&options.labels) {
+        crashln!("{} Invalid labels: {err}", *helpers::FAIL);
+    }
+
+    let StartOptions { watch, labels, env, startup_grace, monitor_interval_ms, max_restarts, watch_cooldown, url, description, group, alerts, max_log_rate, restart_cron, health, max_memory_action, pidfile, expand_env, force_update, limits, shell, collect_cores, supervised, .. } = options;
+
+    // Labels of existing processes are changed with `opm adjust`
+    let warn_existing = || {
+        then!(
+            !labels.is_empty(),
+            alert!(
+                "{} Labels are only applied to new processes, use `opm adjust --label` to change them",
+                *helpers::WARN
+            )
+        );
+        then!(
+            startup_grace.is_some(),
+            alert!("{} --startup-grace is only applied to new processes", *helpers::WARN)
+        );
+        then!(
+            monitor_interval_ms.is_some(),
+            alert!("{} --monitor-interval is only applied to new processes", *helpers::WARN)
+        );
+        then!(
+            max_restarts.is_some(),
+            alert!("{} --max-restarts is only applied to new processes, use `opm adjust --max-restarts` to change it", *helpers::WARN)
+        );
+        then!(
+            watch_cooldown.is_some(),
+            alert!("{} --watch-cooldown is only applied to new processes, use `opm adjust --watch-cooldown` to change it", *helpers::WARN)
+        );
+        then!(
+            url.is_some(),
+            alert!("{} --url is only applied to new processes, use `opm adjust --url` to change it", *helpers::WARN)
+        );
+        then!(
+            description.is_some(),
+            alert!("{} --description is only applied to new processes, use `opm adjust --description` to change it", *helpers::WARN)
+        );
+        then!(
+            group.is_some(),
+            alert!("{} --group is only applied to new processes, use `opm adjust --group` to change it", *helpers::WARN)
+        );
+        then!(
+            !alerts.is_empty(),
+            alert!("{} --alert is only applied to new processes, use `opm adjust --alert` to change them", *helpers::WARN)
+        );
+        then!(
+            max_log_rate.is_some(),
+            alert!("{} --max-log-rate is only applied to new processes, use `opm adjust --max-log-rate` to change it", *helpers::WARN)
+        );
+        then!(
+            restart_cron.is_some(),
+            alert!("{} --cron-restart is only applied to new processes, use `opm adjust --cron-restart` to change it", *helpers::WARN)
+        );
+        then!(
+            health.is_some(),
+            alert!("{} --health-url and --health-cmd are only applied to new processes, use `opm adjust --health-url` to change it", *helpers::WARN)
+        );
+        then!(
+            max_memory_action.is_some(),
+            alert!("{} --max-memory-action is only applied to new processes, use `opm adjust --max-memory-action` to change it", *helpers::WARN)
+        );
+        then!(
+            pidfile.is_some(),
+            alert!("{} --pidfile is only applied to new processes", *helpers::WARN)
+        );
+        then!(
+            *expand_env,
+            alert!("{} --expand-env-templates is only applied to new processes", *helpers::WARN)
+        );
+        then!(
+            !limits.is_empty() || *collect_cores || *supervised,
+            alert!("{} --umask, --ulimit-*, --collect-cores and --supervised are only applied to new processes, use `opm adjust` to change them", *helpers::WARN)
+        );
+        then!(
+            !shell.is_empty(),
+            alert!("{} --shell and --shell-arg are only applied to new processes, use `opm adjust --shell` to change them", *helpers::WARN)
+        );
+        then!(
+            !env.is_empty(),
+            alert!("{} --env is only applied to new processes, use `opm adjust --env-add` to change it", *helpers::WARN)
+        );
+    };
+
     let arg = match args.get_string() {
         Some(arg) => arg,
         None => "",
@@ -104,8 +375,18 @@ pub fn start(
             );
         }
 
+        // Workers are managed together as `group:<name>`, a remote daemon is given no group unless asked for
+        let group = match (group, LOCAL_SERVER_NAMES.contains(&server_name.as_str())) {
+            (Some(group), _) => Some(group.clone()),
+            (None, true) => group::parse(name.as_deref().unwrap_or("worker")).ok(),
+            (None, false) => None,
+        };
+
+        // Workers are not adopted from a pidfile and run without a memory limit
+        let worker_options = StartOptions { max_memory: 0, max_memory_action: None, pidfile: None, group, ..options.clone() };
+
         // Start multiple worker instances
-        println!(
+        notice!(
             "{} Starting {} worker instances for load balancing",
             *helpers::SUCCESS,
             worker_count
@@ -127,7 +408,7 @@ pub fn start(
                 String::new()
             };
 
-            println!(
+            notice!(
                 "  {} Starting worker {} of {}{}",
                 *helpers::SUCCESS,
                 i + 1,
@@ -142,31 +423,31 @@ pub fn start(
                 kind: kind.clone(),
                 runner: runner.clone(),
             }
-            .create(&arg.to_string(), &worker_name, watch, &None, true);
+            .create(&arg.to_string(), &worker_name, &worker_options, true);
         }
 
-        println!(
+        notice!(
             "{} All {} workers started successfully",
             *helpers::SUCCESS,
             worker_count
         );
 
-        // Allow CPU stats to accumulate before displaying the list
-        thread::sleep(Duration::from_millis(STATS_PRE_LIST_DELAY_MS));
+        wait_for_stats();
         Internal::list(&string!("default"), &list_name);
         return;
     }
 
     if arg == "all" {
-        println!(
+        notice!(
             "{} Applying {kind}action startAllProcess",
             *helpers::SUCCESS
         );
+        warn_existing();
 
         let process_ids: Vec<usize> = runner.items().keys().copied().collect();
         
         if process_ids.is_empty() {
-            println!("{} Cannot start all, no processes found", *helpers::FAIL);
+            alert!("{} Cannot start all, no processes found", *helpers::FAIL);
         } else {
             for id in process_ids {
                 runner = Internal {
@@ -181,6 +462,7 @@ pub fn start(
     } else {
         match args {
             Args::Id(id) => {
+                warn_existing();
                 Internal {
                     id: *id,
                     runner,
@@ -189,8 +471,40 @@ pub fn start(
                 }
                 .restart(name, watch, *reset_env, false, false);  // start by id - don't increment
             }
+            Args::Script(script) if let Some(collision) = taken(&runner, name, script, server_name) => {
+                let id = match collision {
+                    Collision::Same(id) => id,
+                    Collision::Changed { id, stored } => {
+                        let command = internal::script_command(script, &config::read().runner.node);
+                        if !*force_update {
+                            crashln!(
+                                "{} Process {} ({id}) runs another command, nothing was started:\n{}\n{} Use `opm adjust {id} --command` to change it, or start again with --force-update",
+                                *helpers::FAIL,
+                                name.as_deref().unwrap_or_default(),
+                                collision::diff(&stored, &command),
+                                *helpers::INFO
+                            );
+                        }
+
+                        notice!("{} Updating command of ({id}):\n{}", *helpers::SUCCESS, collision::diff(&stored, &command));
+                        runner.process(id).script = command;
+                        runner.save();
+                        id
+                    }
+                };
+
+                warn_existing();
+                Internal {
+                    id,
+                    runner,
+                    server_name,
+                    kind,
+                }
+                .restart(name, watch, *reset_env, false, false);
+            }
             Args::Script(script) => match runner.find(&script, server_name) {
                 Some(id) => {
+                    warn_existing();
                     Internal {
                         id,
                         runner,
@@ -206,17 +520,21 @@ pub fn start(
                         server_name,
                         kind,
                     }
-                    .create(script, name, watch, max_memory, false);
+                    .create(script, name, options, false);
                 }
             },
         }
     }
 
-    // Allow CPU stats to accumulate before displaying the list
-    thread::sleep(Duration::from_millis(STATS_PRE_LIST_DELAY_MS));
+    wait_for_stats();
     Internal::list(&string!("default"), &list_name);
 }
 
+/// Allow CPU stats to accumulate before displaying the list, quiet mode shows none
+fn wait_for_stats() {
+    then!(!output::quiet(), thread::sleep(Duration::from_millis(STATS_PRE_LIST_DELAY_MS)));
+}
+
 fn parse_port_range(port_str: &str) -> Vec<u16> {
     if port_str.contains('-') {
         // Parse range like "3000-3010"
@@ -249,20 +567,65 @@ fn parse_port_range(port_str: &str) -> Vec<u16> {
     }
 }
 
-pub fn stop(items: &Items, server_name: &String) {
+pub fn stop(items: &Option<Items>, filters: &[(String, String)], deadline: &Option<u64>, signal: Option<Signal>, server_name: &String) {
     // Check permissions for remote operations
     check_remote_permission(server_name);
     
     let mut runner: Runner = Runner::new();
     let (kind, list_name) = format(server_name);
 
+    if !filters.is_empty() {
+        let process_ids = label_targets(items.as_ref(), filters, server_name);
+        then!(
+            process_ids.is_empty(),
+            alert!("{} No processes match the given labels", *helpers::FAIL)
+        );
+
+        for id in process_ids {
+            runner = Internal {
+                id,
+                server_name,
+                kind: kind.clone(),
+                runner: runner.clone(),
+            }
+            .stop(false, signal);
+        }
+
+        Internal::list(&string!("default"), &list_name);
+        return;
+    }
+
+    let Some(items) = items else {
+        crashln!("{} No processes given to stop", *helpers::FAIL)
+    };
+
     if items.is_all() {
-        println!("{} Applying {kind}action stopAllProcess", *helpers::SUCCESS);
+        notice!("{} Applying {kind}action stopAllProcess", *helpers::SUCCESS);
 
         let process_ids: Vec<usize> = runner.items().keys().copied().collect();
         
         if process_ids.is_empty() {
-            println!("{} Cannot stop all, no processes found", *helpers::FAIL);
+            alert!("{} Cannot stop all, no processes found", *helpers::FAIL);
+        } else if LOCAL_SERVER_NAMES.contains(&server_name.as_str()) {
+            shutdown::run(&mut runner, &process_ids, deadline.map(Duration::from_secs), shutdown::kill_timeout(&config::read().daemon), signal.unwrap_or(Signal::SIGTERM), |summary| {
+                let mut line = format!(
+                    "{} Level {}/{} done in {:.2}s",
+                    *helpers::SUCCESS,
+                    summary.level,
+                    summary.levels,
+                    summary.elapsed.as_secs_f64()
+                );
+
+                if !summary.stopped.is_empty() {
+                    line.push_str(&format!(", stopped {}", summary.stopped.join(", ")));
+                }
+                if !summary.killed.is_empty() {
+                    line.push_str(&format!(", killed {}", summary.killed.join(", ")));
+                }
+
+                notice!("{line}");
+            });
+            runner.save();
         } else {
             for id in process_ids {
                 runner = Internal {
@@ -271,7 +634,7 @@ pub fn stop(items: &Items, server_name: &String) {
                     kind: kind.clone(),
                     runner: runner.clone(),
                 }
-                .stop(true);
+                .stop(true, signal);
             }
         }
     } else {
@@ -284,9 +647,9 @@ pub fn stop(items: &Items, server_name: &String) {
                         kind: kind.clone(),
                         runner: runner.clone(),
                     }
-                    .stop(false);
+                    .stop(false, signal);
                 }
-                Item::Name(name) => match runner.find(&name, server_name) {
+                Item::Name(name) | Item::Pattern(name) | Item::Group(name) => match runner.find(name, server_name) {
                     Some(id) => {
                         runner = Internal {
                             id,
@@ -294,7 +657,7 @@ pub fn stop(items: &Items, server_name: &String) {
                             kind: kind.clone(),
                             runner: runner.clone(),
                         }
-                        .stop(false);
+                        .stop(false, signal);
                     }
                     None => crashln!("{} Process ({name}) not found", *helpers::FAIL),
                 },
@@ -305,7 +668,24 @@ pub fn stop(items: &Items, server_name: &String) {
     Internal::list(&string!("default"), &list_name);
 }
 
-pub fn remove(items: &Items, server_name: &String) {
+/// Whether `opm remove` deletes logs: `--keep-logs`/`--purge-logs`, then `runner.remove_logs`, then a prompt
+fn purge_logs(flag: Option<bool>, count: usize) -> bool {
+    if let Some(purge) = flag.or(config::read().runner.remove_logs) {
+        return purge;
+    }
+
+    let prompt = format!("Also delete the logs of the {count} removed process(es)?");
+    match inquire::Confirm::new(&prompt).with_default(false).prompt() {
+        Ok(purge) => purge,
+        Err(InquireError::OperationCanceled | InquireError::OperationInterrupted) => crashln!("{} Aborted", *helpers::FAIL),
+        Err(_) => {
+            notice!("{} Keeping the logs, pass --purge-logs to delete them", *helpers::SUCCESS);
+            false
+        }
+    }
+}
+
+pub fn remove(items: &Items, purge: Option<bool>, server_name: &String) {
     // Check permissions for remote operations
     check_remote_permission(server_name);
     
@@ -313,13 +693,14 @@ pub fn remove(items: &Items, server_name: &String) {
     let (kind, _) = format(server_name);
 
     if items.is_all() {
-        println!("{} Applying {kind}action removeAllProcess", *helpers::SUCCESS);
+        notice!("{} Applying {kind}action removeAllProcess", *helpers::SUCCESS);
 
         let process_ids: Vec<usize> = runner.items().keys().copied().collect();
         
         if process_ids.is_empty() {
-            println!("{} Cannot remove all, no processes found", *helpers::FAIL);
+            alert!("{} Cannot remove all, no processes found", *helpers::FAIL);
         } else {
+            let purge_logs = purge_logs(purge, process_ids.len());
             for id in process_ids {
                 Internal {
                     id,
@@ -327,10 +708,11 @@ pub fn remove(items: &Items, server_name: &String) {
                     server_name,
                     kind: kind.clone(),
                 }
-                .remove();
+                .remove(purge_logs);
             }
         }
     } else {
+        let purge_logs = purge_logs(purge, items.items.len());
         for item in &items.items {
             match item {
                 Item::Id(id) => Internal {
@@ -339,15 +721,15 @@ pub fn remove(items: &Items, server_name: &String) {
                     server_name,
                     kind: kind.clone(),
                 }
-                .remove(),
-                Item::Name(name) => match runner.find(&name, server_name) {
+                .remove(purge_logs),
+                Item::Name(name) | Item::Pattern(name) | Item::Group(name) => match runner.find(name, server_name) {
                     Some(id) => Internal {
                         id,
                         runner: runner.clone(),
                         server_name,
                         kind: kind.clone(),
                     }
-                    .remove(),
+                    .remove(purge_logs),
                     None => crashln!("{} Process ({name}) not found", *helpers::FAIL),
                 },
             }
@@ -372,7 +754,7 @@ pub fn info(item: &Item, format: &String, server_name: &String) {
             kind,
         }
         .info(format),
-        Item::Name(name) => match runner.find(&name, server_name) {
+        Item::Name(name) | Item::Pattern(name) | Item::Group(name) => match runner.find(name, server_name) {
             Some(id) => Internal {
                 id,
                 runner,
@@ -385,40 +767,175 @@ pub fn info(item: &Item, format: &String, server_name: &String) {
     }
 }
 
+pub fn snapshot(item: &Item, args: &[String], format: &str, server_name: &String) {
+    // Check permissions for remote operations
+    check_remote_permission(server_name);
+
+    let runner: Runner = Runner::new();
+
+    match item {
+        Item::Id(id) => snapshot::run(*id, runner, args, format, server_name),
+        Item::Name(name) | Item::Pattern(name) | Item::Group(name) => match runner.find(name, server_name) {
+            Some(id) => snapshot::run(id, runner, args, format, server_name),
+            None => crashln!("{} Process ({name}) not found", *helpers::FAIL),
+        },
+    }
+}
+
+pub fn stats_summary(by: &By, format: &str, server_name: &String) {
+    // Check permissions for remote operations
+    check_remote_permission(server_name);
+
+    let runner = snapshot::connect(Runner::new(), server_name);
+    match runner.summary(by) {
+        Ok(summary) => stats::print(&summary, format),
+        Err(err) => crashln!("{} Failed to summarize processes: {err}", *helpers::FAIL),
+    }
+}
+
+pub fn audit_log(lines: usize, filter: &Filter, format: &str, server_name: &String) {
+    // Check permissions for remote operations
+    check_remote_permission(server_name);
+
+    let runner = snapshot::connect(Runner::new(), server_name);
+    match runner.audit(lines, filter) {
+        Ok(entries) => audit::print(&entries, format),
+        Err(err) => crashln!("{} Failed to read the audit log: {err}", *helpers::FAIL),
+    }
+}
+
+pub fn notification_history(limit: usize, format: &str, server_name: &String) {
+    // Check permissions for remote operations
+    check_remote_permission(server_name);
+
+    let runner = snapshot::connect(Runner::new(), server_name);
+    match runner.notification_history(limit) {
+        Ok(history) => notifications::print(&history, format),
+        Err(err) => crashln!("{} Failed to read the notification history: {err}", *helpers::FAIL),
+    }
+}
+
 pub fn logs(
-    item: &Item,
+    items: &Option<Items>,
     lines: &usize,
     server_name: &String,
     follow: bool,
-    filter: Option<&str>,
     errors_only: bool,
-    stats: bool,
+    filters: &[(String, String)],
+    options: &LogOptions,
 ) {
     // Check permissions for remote operations
     check_remote_permission(server_name);
-    
+
     let runner: Runner = Runner::new();
     let (kind, _) = format(server_name);
+    let process_ids = label_targets(items.as_ref(), filters, server_name);
 
-    match item {
-        Item::Id(id) => Internal {
-            id: *id,
-            runner,
+    if process_ids.is_empty() {
+        match filters.is_empty() {
+            true => crashln!("{} No processes found", *helpers::FAIL),
+            false => crashln!("{} No processes match the given labels", *helpers::FAIL),
+        }
+    }
+
+    // Several processes are followed together once the last lines of each were printed
+    let multiplex = follow && process_ids.len() > 1;
+    if multiplex && !LOCAL_SERVER_NAMES.contains(&server_name.as_str()) {
+        crashln!("{} --follow of several processes only reads local logs, {} were selected on {server_name}", *helpers::FAIL, process_ids.len());
+    }
+
+    let tail = multiplex.then(|| tail::Tail::new(&runner, &process_ids, errors_only));
+    for id in process_ids {
+        Internal {
+            id,
+            runner: runner.clone(),
             server_name,
-            kind,
+            kind: kind.clone(),
         }
-        .logs(lines, follow, filter, errors_only, stats),
-        Item::Name(name) => match runner.find(&name, server_name) {
-            Some(id) => Internal {
-                id,
-                runner,
-                server_name,
-                kind,
+        .logs(lines, follow && !multiplex, errors_only, options);
+    }
+
+    if let Some(tail) = tail {
+        tail.run(options.filter, options.timestamps);
+    }
+}
+
+/// Write the logs of the selected processes into a gzipped tarball
+pub fn export_logs(
+    items: &Option<Items>,
+    lines: Option<usize>,
+    hours: Option<u64>,
+    output: &PathBuf,
+    filters: &[(String, String)],
+    server_name: &String,
+) {
+    // Check permissions for remote operations
+    check_remote_permission(server_name);
+
+    let process_ids = label_targets(items.as_ref(), filters, server_name);
+
+    if process_ids.is_empty() {
+        match filters.is_empty() {
+            true => crashln!("{} No processes found", *helpers::FAIL),
+            false => crashln!("{} No processes match the given labels", *helpers::FAIL),
+        }
+    }
+
+    let mut file = match File::create(output) {
+        Ok(file) => BufWriter::new(file),
+        Err(err) => crashln!("{} Failed to create {}: {err}", *helpers::FAIL, output.display()),
+    };
+
+    if LOCAL_SERVER_NAMES.contains(&server_name.as_str()) {
+        let runner = Runner::new();
+        let processes: Vec<Process> = process_ids.iter().filter_map(|id| runner.info(*id).cloned()).collect();
+
+        if let Err(err) = archive::write(&mut file, &processes, archive::Selection::new(lines, hours)) {
+            crashln!("{} Failed to write log archive: {err}", *helpers::FAIL);
+        }
+    } else {
+        // The daemon builds one archive per process, so remote exports are limited to one
+        if process_ids.len() > 1 {
+            crashln!(
+                "{} Exporting logs from a remote server needs a single process, {} were selected",
+                *helpers::FAIL,
+                process_ids.len()
+            );
+        }
+
+        let Some(server) = config::servers().servers.and_then(|servers| servers.get(server_name).cloned()) else {
+            crashln!("{} Server '{server_name}' does not exist", *helpers::FAIL)
+        };
+
+        let Some(runner) = Runner::connect(server_name.clone(), server.get(), false) else {
+            crashln!("{} Failed to connect (name={server_name}, address={})", *helpers::FAIL, server.address)
+        };
+
+        let remote = runner.remote.as_ref().unwrap();
+        let download = http::logs_archive(remote, process_ids[0], lines, hours).and_then(|mut response| {
+            if !response.status().is_success() {
+                return Err(anyhow!("server responded with {}", response.status()));
             }
-            .logs(lines, follow, filter, errors_only, stats),
-            None => crashln!("{} Process ({name}) not found", *helpers::FAIL),
-        },
+            Ok(response.copy_to(&mut file)?)
+        });
+
+        if let Err(err) = download {
+            crashln!("{} Failed to download log archive: {err}", *helpers::FAIL);
+        }
     }
+
+    if let Err(err) = file.flush() {
+        crashln!("{} Failed to write log archive: {err}", *helpers::FAIL);
+    }
+
+    let size = fs::metadata(output).map(|metadata| metadata.len()).unwrap_or(0);
+    notice!(
+        "{} Exported logs of {} process(es) to {} ({})",
+        *helpers::SUCCESS,
+        process_ids.len(),
+        output.display(),
+        helpers::format_memory(size)
+    );
 }
 
 // combine into a single function that handles multiple
@@ -437,7 +954,7 @@ pub fn env(item: &Item, server_name: &String) {
             kind,
         }
         .env(),
-        Item::Name(name) => match runner.find(&name, server_name) {
+        Item::Name(name) | Item::Pattern(name) | Item::Group(name) => match runner.find(name, server_name) {
             Some(id) => Internal {
                 id,
                 runner,
@@ -450,10 +967,14 @@ pub fn env(item: &Item, server_name: &String) {
     }
 }
 
-pub fn flush(item: &Item, server_name: &String) {
+pub fn flush(item: &Item, yes: bool, server_name: &String) {
     // Check permissions for remote operations
     check_remote_permission(server_name);
-    
+
+    if item.get_string() == Some("all") {
+        return bulk_all("flush", "Flush the logs of", yes, server_name);
+    }
+
     let runner: Runner = Runner::new();
     let (kind, _) = format(server_name);
 
@@ -465,7 +986,7 @@ pub fn flush(item: &Item, server_name: &String) {
             kind,
         }
         .flush(),
-        Item::Name(name) => match runner.find(&name, server_name) {
+        Item::Name(name) | Item::Pattern(name) | Item::Group(name) => match runner.find(name, server_name) {
             Some(id) => Internal {
                 id,
                 runner,
@@ -478,15 +999,200 @@ pub fn flush(item: &Item, server_name: &String) {
     }
 }
 
-pub fn restart(items: &Items, server_name: &String) {
+/// List the core files of a local process, or delete them with `clean`
+pub fn cores(item: &Item, clean: bool) {
+    let mut runner = Runner::new();
+    let id = match item {
+        Item::Id(id) if runner.exists(*id) => *id,
+        Item::Id(id) => crashln!("{} Process ({id}) not found", *helpers::FAIL),
+        Item::Name(name) | Item::Pattern(name) | Item::Group(name) => match runner.find(name, &string!("internal")) {
+            Some(id) => id,
+            None => crashln!("{} Process ({name}) not found", *helpers::FAIL),
+        },
+    };
+
+    let process = runner.process(id);
+    if !clean {
+        let found = cores::available(process);
+        then!(found.is_empty(), notice!("{} No core files for {}", *helpers::SUCCESS, process.name));
+        then!(found.is_empty() && !process.collect_cores, notice!("Enable it with `opm adjust {} --collect-cores`", process.name));
+
+        for core in found {
+            println!("{}  {}", core.time.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M:%S"), core.describe());
+        }
+        return;
+    }
+
+    match cores::clean(process) {
+        Ok(removed) => {
+            let bytes = removed.iter().map(|core| core.bytes).sum();
+            notice!("{} Removed {} core files ({}) of {}", *helpers::SUCCESS, removed.len(), helpers::format_memory(bytes), process.name);
+            process.crash.cores.clear();
+            runner.save();
+        }
+        Err(err) => crashln!("{} Cannot remove the cores of {}: {err}", *helpers::FAIL, process.name),
+    }
+}
+
+/// Carry the logs of local processes started with another `runner.log_path` over to the current one
+pub fn migrate_logs(mode: logdir::Mode) {
+    let mut runner = Runner::new();
+    let current = config::read().runner.log_path;
+    let stale: Vec<(usize, String)> = logdir::stale(&runner, &current).into_iter().map(|(id, dir)| (id, dir.to_string())).collect();
+
+    if stale.is_empty() {
+        notice!("{} Every process logs to {current}", *helpers::SUCCESS);
+        return;
+    }
+
+    let mut running = vec![];
+    for (id, dir) in stale {
+        let process = runner.process(id);
+        match logdir::migrate(process, &current, mode) {
+            Ok(files) => {
+                let verb = ternary!(mode == logdir::Mode::Symlink, "Linked", "Moved");
+                notice!("{} {verb} {} log files of {} ({id}) from {dir}", *helpers::SUCCESS, files.len(), process.name);
+                then!(process.running, running.push(process.name.clone()));
+                process.log_path = Some(current.clone());
+            }
+            Err(err) => alert!("{} Left the logs of {} ({id}) in {dir}: {err}", *helpers::FAIL, process.name),
+        }
+    }
+
+    runner.save();
+    then!(
+        !running.is_empty(),
+        alert!("{} {} keep writing to the files they have open until their next restart", *helpers::WARN, running.join(", "))
+    );
+}
+
+/// Clear the stored environment of a process, or of every process with `all`
+pub fn env_clear(item: &Item, yes: bool, server_name: &String) {
+    // Check permissions for remote operations
+    check_remote_permission(server_name);
+
+    if item.get_string() == Some("all") {
+        return bulk_all("clear_env", "Clear the environment of", yes, server_name);
+    }
+
+    let (kind, _) = format(server_name);
+    let mut runner = connect(server_name);
+
+    let id = match item {
+        Item::Id(id) if runner.exists(*id) => *id,
+        Item::Id(id) => crashln!("{} Process ({id}) not found", *helpers::FAIL),
+        Item::Name(name) | Item::Pattern(name) | Item::Group(name) => match runner.find(name, server_name) {
+            Some(id) => id,
+            None => crashln!("{} Process ({name}) not found", *helpers::FAIL),
+        },
+    };
+
+    runner.clear_env(id).save();
+    notice!("{} Cleared environment of {kind}({id}) ✓", *helpers::SUCCESS);
+}
+
+/// Run a bulk action on every process once confirmed, reporting the outcome per process
+fn bulk_all(method: &str, action: &str, yes: bool, server_name: &String) {
+    let (kind, _) = format(server_name);
+    let mut runner = connect(server_name);
+    let names: BTreeMap<usize, String> = runner.items().into_iter().map(|(id, process)| (id, process.name)).collect();
+
+    if names.is_empty() {
+        return alert!("{} No processes found", *helpers::FAIL);
+    }
+
+    if !yes {
+        let prompt = format!("{action} all {} {kind}processes?", names.len());
+        match inquire::Confirm::new(&prompt).with_default(false).prompt() {
+            Ok(true) => {}
+            Ok(false) | Err(InquireError::OperationCanceled | InquireError::OperationInterrupted) => crashln!("{} Aborted", *helpers::FAIL),
+            Err(err) => crashln!("{} Cannot prompt ({err}), re-run with --yes", *helpers::FAIL),
+        }
+    }
+
+    let (success, failed): (Vec<usize>, Vec<usize>) = match &runner.remote {
+        Some(remote) => match http::bulk_all(remote, method) {
+            Ok(result) => (result.success, result.failed),
+            Err(err) => crashln!("{} Failed to run {method} on {server_name}: {err}", *helpers::FAIL),
+        },
+        None => {
+            let mut failed = vec![];
+            for &id in names.keys() {
+                match method {
+                    "flush" => {
+                        if let Err(err) = runner.process(id).logs().truncate() {
+                            alert!("{} {} ({id}): {err}", *helpers::FAIL, names[&id]);
+                            failed.push(id);
+                        }
+                    }
+                    _ => {
+                        runner.clear_env(id);
+                    }
+                }
+            }
+            runner.save();
+            (names.keys().copied().filter(|id| !failed.contains(id)).collect(), failed)
+        }
+    };
+
+    for id in &success {
+        notice!("{} {} ({id}) ✓", *helpers::SUCCESS, names.get(id).map_or("?", String::as_str));
+    }
+
+    if !failed.is_empty() {
+        let failed: Vec<String> = failed.iter().map(|id| names.get(id).cloned().unwrap_or_else(|| id.to_string())).collect();
+        crashln!("{} {method} failed on {} of {} processes: {}", *helpers::FAIL, failed.len(), names.len(), failed.join(", "));
+    }
+
+    notice!("{} Applied {kind}action {method} on {} processes", *helpers::SUCCESS, success.len());
+}
+
+pub fn restart(items: &Option<Items>, filters: &[(String, String)], if_changed: bool, server_name: &String) {
     // Check permissions for remote operations
     check_remote_permission(server_name);
+    check_if_changed(if_changed, server_name);
     
     let mut runner: Runner = Runner::new();
     let (kind, list_name) = format(server_name);
+    let mut restarted = 0;
+
+    if !filters.is_empty() {
+        let process_ids = label_targets(items.as_ref(), filters, server_name);
+        then!(
+            process_ids.is_empty(),
+            alert!("{} No processes match the given labels", *helpers::FAIL)
+        );
+
+        for id in process_ids {
+            if unchanged(&runner, id, if_changed) {
+                continue;
+            }
+
+            restarted += 1;
+            runner = Internal {
+                id,
+                server_name,
+                kind: kind.clone(),
+                runner: runner.clone(),
+            }
+            .restart(&None, &None, false, false, true);
+        }
+
+        if restarted == 0 && if_changed {
+            return;
+        }
+
+        wait_for_stats();
+        Internal::list(&string!("default"), &list_name);
+        return;
+    }
+
+    let Some(items) = items else {
+        crashln!("{} No processes given to restart", *helpers::FAIL)
+    };
 
     if items.is_all() {
-        println!(
+        notice!(
             "{} Applying {kind}action restartAllProcess",
             *helpers::SUCCESS
         );
@@ -494,9 +1200,14 @@ pub fn restart(items: &Items, server_name: &String) {
         let process_ids: Vec<usize> = runner.items().keys().copied().collect();
         
         if process_ids.is_empty() {
-            println!("{} Cannot restart all, no processes found", *helpers::FAIL);
+            alert!("{} Cannot restart all, no processes found", *helpers::FAIL);
         } else {
             for id in process_ids {
+                if unchanged(&runner, id, if_changed) {
+                    continue;
+                }
+
+                restarted += 1;
                 runner = Internal {
                     id,
                     server_name,
@@ -510,6 +1221,11 @@ pub fn restart(items: &Items, server_name: &String) {
         for item in &items.items {
             match item {
                 Item::Id(id) => {
+                    if unchanged(&runner, *id, if_changed) {
+                        continue;
+                    }
+
+                    restarted += 1;
                     runner = Internal {
                         id: *id,
                         server_name,
@@ -518,8 +1234,13 @@ pub fn restart(items: &Items, server_name: &String) {
                     }
                     .restart(&None, &None, false, false, true);  // restart by id - increment counter
                 }
-                Item::Name(name) => match runner.find(&name, server_name) {
+                Item::Name(name) | Item::Pattern(name) | Item::Group(name) => match runner.find(name, server_name) {
                     Some(id) => {
+                        if unchanged(&runner, id, if_changed) {
+                            continue;
+                        }
+
+                        restarted += 1;
                         runner = Internal {
                             id,
                             server_name,
@@ -534,20 +1255,25 @@ pub fn restart(items: &Items, server_name: &String) {
         }
     }
 
-    // Allow CPU stats to accumulate before displaying the list
-    thread::sleep(Duration::from_millis(STATS_PRE_LIST_DELAY_MS));
+    if restarted == 0 && if_changed {
+        return;
+    }
+
+    wait_for_stats();
     Internal::list(&string!("default"), &list_name);
 }
 
-pub fn reload(items: &Items, server_name: &String) {
+pub fn reload(items: &Items, if_changed: bool, server_name: &String) {
     // Check permissions for remote operations
     check_remote_permission(server_name);
+    check_if_changed(if_changed, server_name);
     
     let mut runner: Runner = Runner::new();
     let (kind, list_name) = format(server_name);
+    let mut reloaded = 0;
 
     if items.is_all() {
-        println!(
+        notice!(
             "{} Applying {kind}action reloadAllProcess",
             *helpers::SUCCESS
         );
@@ -555,9 +1281,14 @@ pub fn reload(items: &Items, server_name: &String) {
         let process_ids: Vec<usize> = runner.items().keys().copied().collect();
         
         if process_ids.is_empty() {
-            println!("{} Cannot reload all, no processes found", *helpers::FAIL);
+            alert!("{} Cannot reload all, no processes found", *helpers::FAIL);
         } else {
             for id in process_ids {
+                if unchanged(&runner, id, if_changed) {
+                    continue;
+                }
+
+                reloaded += 1;
                 runner = Internal {
                     id,
                     server_name,
@@ -571,6 +1302,11 @@ pub fn reload(items: &Items, server_name: &String) {
         for item in &items.items {
             match item {
                 Item::Id(id) => {
+                    if unchanged(&runner, *id, if_changed) {
+                        continue;
+                    }
+
+                    reloaded += 1;
                     runner = Internal {
                         id: *id,
                         server_name,
@@ -579,8 +1315,13 @@ pub fn reload(items: &Items, server_name: &String) {
                     }
                     .reload(false);
                 }
-                Item::Name(name) => match runner.find(&name, server_name) {
+                Item::Name(name) | Item::Pattern(name) | Item::Group(name) => match runner.find(name, server_name) {
                     Some(id) => {
+                        if unchanged(&runner, id, if_changed) {
+                            continue;
+                        }
+
+                        reloaded += 1;
                         runner = Internal {
                             id,
                             server_name,
@@ -595,12 +1336,42 @@ pub fn reload(items: &Items, server_name: &String) {
         }
     }
 
-    // Allow CPU stats to accumulate before displaying the list
-    thread::sleep(Duration::from_millis(STATS_PRE_LIST_DELAY_MS));
+    if reloaded == 0 && if_changed {
+        return;
+    }
+
+    wait_for_stats();
     Internal::list(&string!("default"), &list_name);
 }
 
-pub fn get_command(item: &Item, server_name: &String) {
+pub fn trigger_reload(item: &Item, reason: &String, server_name: &String) {
+    // Check permissions for remote operations
+    check_remote_permission(server_name);
+
+    let (kind, _) = format(server_name);
+    let mut runner = connect(server_name);
+
+    let id = match item {
+        Item::Id(id) if runner.exists(*id) => *id,
+        Item::Id(id) => crashln!("{} Process ({id}) not found", *helpers::FAIL),
+        Item::Name(name) | Item::Pattern(name) | Item::Group(name) => match runner.find(name, server_name) {
+            Some(id) => id,
+            None => crashln!("{} Process ({name}) not found", *helpers::FAIL),
+        },
+    };
+
+    notice!("{} Applying {kind}action triggerReload on ({id})", *helpers::SUCCESS);
+
+    let result = runner.trigger_reload(id, reason);
+    runner.save();
+
+    match result {
+        Ok(pid) => notice!("{} Reloaded {kind}({id}) with reason '{reason}', new pid {pid} ✓", *helpers::SUCCESS),
+        Err(err) => crashln!("{} Failed to reload {kind}({id}): {err}", *helpers::FAIL),
+    }
+}
+
+pub fn get_command(item: &Item, raw: bool, target: &str, show_secrets: bool, server_name: &String) {
     // Check permissions for remote operations
     check_remote_permission(server_name);
     
@@ -614,49 +1385,41 @@ pub fn get_command(item: &Item, server_name: &String) {
             server_name,
             kind,
         }
-        .get_command(),
-        Item::Name(name) => match runner.find(&name, server_name) {
+        .get_command(raw, target, show_secrets),
+        Item::Name(name) | Item::Pattern(name) | Item::Group(name) => match runner.find(name, server_name) {
             Some(id) => Internal {
                 id,
                 runner,
                 server_name,
                 kind,
             }
-            .get_command(),
+            .get_command(raw, target, show_secrets),
             None => crashln!("{} Process ({name}) not found", *helpers::FAIL),
         },
     }
 }
 
-pub fn adjust(
-    item: &Item,
-    command: &Option<String>,
-    name: &Option<String>,
-    server_name: &String,
-) {
+pub fn adjust(items: &Items, changes: &adjust::Changes, dry_run: bool, server_name: &String) {
     // Check permissions for remote operations
     check_remote_permission(server_name);
-    
-    let runner: Runner = Runner::new();
-    let (kind, _) = format(server_name);
+    changes.check();
 
-    match item {
-        Item::Id(id) => Internal {
-            id: *id,
-            runner,
-            server_name,
-            kind,
-        }
-        .adjust(command, name),
-        Item::Name(item_name) => match runner.find(&item_name, server_name) {
-            Some(id) => Internal {
-                id,
-                runner,
+    let ids = adjust::targets(&connect(server_name), items);
+    if changes.name.is_some() && ids.len() > 1 {
+        crashln!("{} --name cannot be given for several processes, they would all share it", *helpers::FAIL);
+    }
+
+    match ids.as_slice() {
+        [id] if !dry_run => {
+            let (kind, _) = format(server_name);
+            Internal {
+                id: *id,
+                runner: Runner::new(),
                 server_name,
                 kind,
             }
-            .adjust(command, name),
-            None => crashln!("{} Process ({item_name}) not found", *helpers::FAIL),
-        },
+            .adjust(changes)
+        }
+        ids => adjust::run(ids, changes, dry_run, server_name),
     }
 }
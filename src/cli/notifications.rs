@@ -0,0 +1,90 @@
+use chrono::Local;
+use colored::Colorize;
+use macros_rs::{crashln, string};
+use opm::{
+    helpers, notice,
+    notifications::history::{ChannelStatus, Delivery, NotificationHistory},
+};
+use tabled::{
+    Table, Tabled,
+    settings::{
+        Color, Modify,
+        object::{Rows, Segment},
+        style::{BorderColor, Style},
+    },
+};
+
+#[derive(Tabled)]
+struct Row {
+    time: String,
+    event: String,
+    process: String,
+    channel: String,
+    status: String,
+}
+
+fn status(delivery: &Delivery) -> String {
+    match (delivery.success, &delivery.error) {
+        (true, _) => string!("delivered").green().to_string(),
+        (false, Some(error)) => format!("failed: {error}").red().to_string(),
+        (false, None) => string!("failed").red().to_string(),
+    }
+}
+
+impl From<&Delivery> for Row {
+    fn from(delivery: &Delivery) -> Self {
+        Row {
+            time: delivery.time.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S").to_string(),
+            event: delivery.event.clone(),
+            process: delivery.process.clone().unwrap_or_else(|| string!("-")),
+            channel: delivery.channel.clone(),
+            status: status(delivery),
+        }
+    }
+}
+
+#[derive(Tabled)]
+struct ChannelRow {
+    channel: String,
+    #[tabled(rename = "last delivery")]
+    last: String,
+    status: String,
+}
+
+impl From<&ChannelStatus> for ChannelRow {
+    fn from(channel: &ChannelStatus) -> Self {
+        ChannelRow {
+            channel: channel.channel.clone(),
+            last: channel.last.as_ref().map_or_else(|| string!("never"), |last| last.time.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S").to_string()),
+            status: channel.last.as_ref().map_or_else(|| string!("-"), status),
+        }
+    }
+}
+
+fn table<T: Tabled>(rows: impl IntoIterator<Item = T>) -> String {
+    Table::new(rows)
+        .with(Style::rounded().remove_verticals())
+        .with(Modify::new(Segment::all()).with(BorderColor::filled(Color::new("\x1b[38;2;45;55;72m", "\x1b[39m"))))
+        .with(Modify::new(Rows::first()).with(Color::FG_BRIGHT_CYAN))
+        .to_string()
+}
+
+pub fn print(history: &NotificationHistory, format: &str) {
+    if format == "json" {
+        match serde_json::to_string(history) {
+            Ok(json) => println!("{json}"),
+            Err(err) => crashln!("{} Cannot encode notification history.\n{}", *helpers::FAIL, string!(err).white()),
+        }
+        return;
+    }
+
+    if !history.channels.is_empty() {
+        notice!("{}\n{}\n", "Channels".on_bright_white().black(), table(history.channels.iter().map(ChannelRow::from)));
+    }
+
+    if history.deliveries.is_empty() {
+        return notice!("{} No notification deliveries found", *helpers::INFO);
+    }
+
+    notice!("{}", table(history.deliveries.iter().map(Row::from)));
+}
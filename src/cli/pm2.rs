@@ -0,0 +1,419 @@
+//! `opm import --from-pm2`, taking over the processes of a pm2 installation.
+//!
+//! The processes come from `pm2 jlist` while pm2 runs, and from the `dump.pm2` it saved otherwise. Translating
+//! them is kept apart from reading and registering, so it can be checked against captured pm2 output.
+
+use macros_rs::{crashln, string};
+use serde_json::Value;
+
+use std::{
+    borrow::Cow,
+    collections::BTreeMap,
+    env, fs,
+    path::{Path, PathBuf},
+    process::Command,
+    thread,
+    time::Duration,
+};
+
+use opm::{
+    alert, helpers, notice,
+    process::{Env, Runner, StartOptions, is_pid_alive, memlimit},
+};
+
+/// How long a started copy has to keep running before its pm2 counterpart is stopped
+const STARTUP_CHECK: Duration = Duration::from_secs(2);
+
+/// Restarts pm2 allows when none are set, left to `daemon.restarts` rather than copied
+const PM2_MAX_RESTARTS: u64 = 16;
+
+/// pm2 options without an opm counterpart, with the value pm2 uses when they are not set
+const PM2_ONLY: [(&str, Option<&str>); 14] = [
+    ("cron_restart", None),
+    ("autorestart", Some("true")),
+    ("restart_delay", Some("0")),
+    ("exp_backoff_restart_delay", Some("0")),
+    ("min_uptime", Some("1000")),
+    ("kill_timeout", Some("1600")),
+    ("wait_ready", Some("false")),
+    ("listen_timeout", Some("3000")),
+    ("shutdown_with_message", Some("false")),
+    ("treekill", Some("true")),
+    ("stop_exit_codes", None),
+    ("log_date_format", None),
+    ("source_map_support", Some("true")),
+    ("namespace", Some("\"default\"")),
+];
+
+/// Keys pm2 adds to the environment of its processes for its own bookkeeping
+const PM2_ENV: [&str; 6] = ["unique_id", "km_link", "vizion_running", "windowsHide", "status", "NODE_APP_INSTANCE"];
+
+/// A pm2 process as it is registered in opm
+#[derive(Debug, Clone, PartialEq)]
+pub struct Translated {
+    pub name: String,
+    /// Id of the pm2 process, to stop it once the copy runs
+    pub pm_id: Option<u64>,
+    pub command: String,
+    pub path: PathBuf,
+    pub env: Env,
+    pub max_memory: u64,
+    pub max_restarts: Option<u64>,
+    pub watch: Option<String>,
+    /// pm2 settings that were left behind, one sentence each
+    pub untranslated: Vec<String>,
+}
+
+/// Map the entries of `pm2 jlist` or `dump.pm2` to opm processes, in pm2 id order.
+///
+/// The instances of a cluster become workers named `<name>-worker-<n>` like `opm start --workers` names them,
+/// each with the instance number pm2 would have given it.
+pub fn translate(entries: &[Value]) -> Vec<Translated> {
+    let mut entries: Vec<(&Value, Option<u64>)> = entries
+        .iter()
+        .map(|entry| {
+            // `pm2 jlist` nests the settings under pm2_env, `dump.pm2` has them at the top
+            let settings = entry.get("pm2_env").unwrap_or(entry);
+            (settings, entry.get("pm_id").or_else(|| settings.get("pm_id")).and_then(Value::as_u64))
+        })
+        .collect();
+    entries.sort_by_key(|(_, pm_id)| *pm_id);
+
+    let mut instances: BTreeMap<String, usize> = BTreeMap::new();
+    for (settings, _) in &entries {
+        *instances.entry(text(settings, "name").unwrap_or_default()).or_default() += 1;
+    }
+
+    let mut seen: BTreeMap<String, usize> = BTreeMap::new();
+    entries
+        .into_iter()
+        .map(|(settings, pm_id)| {
+            let name = text(settings, "name").unwrap_or_default();
+            let index = seen.entry(name.clone()).or_default();
+            let translated = translate_one(settings, pm_id, &name, (instances[&name] > 1).then_some(*index));
+            *index += 1;
+            translated
+        })
+        .collect()
+}
+
+/// One pm2 process, `worker` being its instance number within a cluster
+fn translate_one(settings: &Value, pm_id: Option<u64>, name: &str, worker: Option<usize>) -> Translated {
+    let mut untranslated = vec![];
+    let path = PathBuf::from(text(settings, "pm_cwd").or_else(|| text(settings, "cwd")).unwrap_or_else(|| string!(".")));
+
+    let mut env: Env = match settings.get("env") {
+        Some(Value::Object(vars)) => vars
+            .iter()
+            .filter(|(key, _)| *key != name && !PM2_ENV.contains(&key.as_str()) && !key.starts_with("PM2_") && !key.starts_with("pm_") && !key.starts_with("axm_"))
+            .filter_map(|(key, value)| scalar(value).map(|value| (key.clone(), value)))
+            .collect(),
+        _ => Env::new(),
+    };
+
+    if let Some(index) = worker {
+        let var = text(settings, "instance_var").unwrap_or_else(|| string!("NODE_APP_INSTANCE"));
+        env.insert(var, index.to_string());
+    }
+
+    let max_memory = match settings.get("max_memory_restart") {
+        Some(Value::Number(bytes)) => bytes.as_u64().unwrap_or_default(),
+        Some(Value::String(size)) => helpers::parse_memory(size).unwrap_or_else(|_| {
+            untranslated.push(format!("max_memory_restart {size} is not a size opm reads"));
+            0
+        }),
+        _ => 0,
+    };
+
+    let max_restarts = settings.get("max_restarts").and_then(Value::as_u64).filter(|restarts| *restarts != PM2_MAX_RESTARTS);
+    let watch = watch(settings, &path, &mut untranslated);
+
+    if worker == Some(0) {
+        untranslated.push(string!("cluster mode runs the instances as separate workers, they do not share a port unless they bind it with SO_REUSEPORT"));
+    }
+
+    for (key, default) in PM2_ONLY {
+        let Some(value) = settings.get(key).filter(|value| !value.is_null() && **value != Value::String(String::new())) else { continue };
+        if default.is_none_or(|default| serde_json::from_str::<Value>(default).ok().as_ref() != Some(value)) {
+            untranslated.push(format!("{key} {value} has no opm counterpart"));
+        }
+    }
+
+    Translated {
+        name: match worker {
+            Some(index) => format!("{name}-worker-{}", index + 1),
+            None => name.to_string(),
+        },
+        pm_id,
+        command: command(settings),
+        path,
+        env,
+        max_memory,
+        max_restarts,
+        watch,
+        untranslated,
+    }
+}
+
+/// Interpreter, its arguments, the script and its arguments, quoted for the shell
+fn command(settings: &Value) -> String {
+    let script = text(settings, "pm_exec_path").or_else(|| text(settings, "script")).unwrap_or_default();
+    let interpreter = text(settings, "exec_interpreter").unwrap_or_else(|| string!("node"));
+
+    let mut words: Vec<String> = vec![];
+    if interpreter != "none" {
+        words.push(interpreter);
+        words.extend(list(settings, "node_args").or_else(|| list(settings, "interpreter_args")).unwrap_or_default());
+    }
+    words.push(script);
+    words.extend(list(settings, "args").unwrap_or_default());
+
+    words.iter().map(|word| quote(word)).collect::<Vec<_>>().join(" ")
+}
+
+/// The path to watch, pm2 watches the working directory when `watch` is just true
+fn watch(settings: &Value, cwd: &Path, untranslated: &mut Vec<String>) -> Option<String> {
+    if let Some(ignored) = list(settings, "ignore_watch").filter(|ignored| !ignored.is_empty()) {
+        untranslated.push(format!("ignore_watch {} has no opm counterpart, changes there restart the process too", ignored.join(",")));
+    }
+
+    let paths = match settings.get("watch")? {
+        Value::Bool(true) => return Some(cwd.display().to_string()),
+        Value::String(path) => vec![path.clone()],
+        Value::Array(_) => list(settings, "watch")?,
+        _ => return None,
+    };
+
+    let (first, rest) = paths.split_first()?;
+    if !rest.is_empty() {
+        untranslated.push(format!("watch only covers {first}, opm watches a single path and not {}", rest.join(",")));
+    }
+    Some(cwd.join(first).display().to_string())
+}
+
+fn text(settings: &Value, key: &str) -> Option<String> { settings.get(key)?.as_str().filter(|text| !text.is_empty()).map(str::to_string) }
+
+/// A list of strings, pm2 also keeps a single string where one is expected
+fn list(settings: &Value, key: &str) -> Option<Vec<String>> {
+    match settings.get(key)? {
+        Value::Array(values) => Some(values.iter().filter_map(scalar).collect()),
+        Value::String(value) if !value.is_empty() => Some(value.split_whitespace().map(str::to_string).collect()),
+        _ => None,
+    }
+}
+
+fn scalar(value: &Value) -> Option<String> {
+    match value {
+        Value::String(value) => Some(value.clone()),
+        Value::Number(_) | Value::Bool(_) => Some(value.to_string()),
+        _ => None,
+    }
+}
+
+/// Single quotes around a word the shell would split or expand
+fn quote(word: &str) -> Cow<'_, str> {
+    match !word.is_empty() && word.chars().all(|char| char.is_ascii_alphanumeric() || "-_./=:,@%+".contains(char)) {
+        true => Cow::Borrowed(word),
+        false => Cow::Owned(format!("'{}'", word.replace('\'', r"'\''"))),
+    }
+}
+
+/// `$PM2_HOME`, `~/.pm2` when unset
+fn pm2_home() -> PathBuf {
+    match env::var_os("PM2_HOME") {
+        Some(home) => PathBuf::from(home),
+        None => home::home_dir().unwrap_or_default().join(".pm2"),
+    }
+}
+
+/// Whether the pm2 daemon runs, `pm2 jlist` would otherwise start one just to answer
+fn pm2_running(home: &Path) -> bool {
+    fs::read_to_string(home.join("pm2.pid")).ok().and_then(|pid| pid.trim().parse::<i64>().ok()).is_some_and(is_pid_alive)
+}
+
+/// The processes pm2 knows about and where they were read from
+fn read() -> Result<(Vec<Value>, String), String> {
+    let home = pm2_home();
+
+    if pm2_running(&home) {
+        let output = Command::new("pm2").arg("jlist").output().map_err(|err| format!("Cannot run pm2 jlist: {err}"))?;
+        if !output.status.success() {
+            return Err(format!("pm2 jlist failed: {}", String::from_utf8_lossy(&output.stderr).trim()));
+        }
+        return parse_jlist(&String::from_utf8_lossy(&output.stdout)).map(|entries| (entries, string!("pm2 jlist")));
+    }
+
+    let dump = home.join("dump.pm2");
+    let contents = fs::read_to_string(&dump).map_err(|err| format!("pm2 is not running and {} cannot be read: {err}", dump.display()))?;
+    let entries = serde_json::from_str(&contents).map_err(|err| format!("Cannot parse {}: {err}", dump.display()))?;
+    Ok((entries, dump.display().to_string()))
+}
+
+/// The array of `pm2 jlist`, skipping the notices pm2 may print before it
+fn parse_jlist(stdout: &str) -> Result<Vec<Value>, String> {
+    let json: String = stdout.lines().skip_while(|line| !line.trim_start().starts_with('[')).collect::<Vec<_>>().join("\n");
+    serde_json::from_str(&json).map_err(|err| format!("Cannot parse the output of pm2 jlist: {err}"))
+}
+
+/// Register the pm2 processes as stopped local processes, or start them with `start`.
+///
+/// With `stop_pm2`, a pm2 process is stopped once its copy is still running after `STARTUP_CHECK`.
+pub fn import(start: bool, stop_pm2: bool) {
+    notice!("{} Applying action importPm2", *helpers::SUCCESS);
+
+    let (entries, source) = match read() {
+        Ok(read) => read,
+        Err(err) => crashln!("{} {err}", *helpers::FAIL),
+    };
+
+    let mut runner = Runner::new();
+    let mut imported: Vec<(usize, Translated)> = vec![];
+
+    for process in translate(&entries) {
+        if runner.find(&process.name, &string!("local")).is_some() {
+            alert!("{} Skipped {}, a process with that name exists", *helpers::WARN, process.name);
+            continue;
+        }
+
+        let options = StartOptions {
+            watch: process.watch.clone(),
+            max_memory: process.max_memory,
+            // pm2 restarts a process over max_memory_restart where opm stops it by default
+            max_memory_action: (process.max_memory > 0).then_some(memlimit::Action::Restart),
+            max_restarts: process.max_restarts,
+            ..Default::default()
+        };
+
+        let id = runner.register(&process.name, &process.command, process.path.clone(), &options);
+        runner.set_env(id, process.env.clone()).set_pending(id, false);
+
+        notice!("{} Imported pm2 process {} ({id})", *helpers::SUCCESS, process.name);
+        for note in &process.untranslated {
+            alert!("{}   {}: {note}", *helpers::WARN, process.name);
+        }
+        imported.push((id, process));
+    }
+
+    runner.save();
+    notice!("{} Imported {} processes from {source}", *helpers::SUCCESS, imported.len());
+
+    if !start {
+        super::Internal::list(&string!("default"), &string!("local"));
+        notice!("{} The processes are stopped, review them and run `opm start <name>`", *helpers::SUCCESS);
+        return;
+    }
+
+    for (id, _) in &imported {
+        runner.restart(*id, false, false);
+    }
+    runner.save();
+
+    thread::sleep(STARTUP_CHECK);
+    let runner = Runner::new();
+
+    for (id, process) in &imported {
+        let running = runner.info(*id).is_some_and(|item| item.running && is_pid_alive(item.pid));
+        if !running {
+            alert!("{} {} did not keep running, its pm2 process is left alone", *helpers::FAIL, process.name);
+            continue;
+        }
+
+        match (stop_pm2, process.pm_id) {
+            (true, Some(pm_id)) => match Command::new("pm2").args(["stop", &pm_id.to_string()]).output() {
+                Ok(output) if output.status.success() => notice!("{} Stopped pm2 process {pm_id} for {}", *helpers::SUCCESS, process.name),
+                Ok(output) => alert!("{} pm2 stop {pm_id} failed: {}", *helpers::FAIL, String::from_utf8_lossy(&output.stderr).trim()),
+                Err(err) => alert!("{} Cannot run pm2 stop {pm_id}: {err}", *helpers::FAIL),
+            },
+            (true, None) => alert!("{} No pm2 id for {}, stop it in pm2 yourself", *helpers::WARN, process.name),
+            (false, _) => {}
+        }
+    }
+
+    super::Internal::list(&string!("default"), &string!("local"));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `pm2 jlist` of a fork process and a cluster of two, trimmed of the fields nothing reads
+    const JLIST: &str = r#"[
+        {"pid": 4100, "name": "api", "pm_id": 0, "monit": {"memory": 52000000, "cpu": 0},
+         "pm2_env": {"name": "api", "pm_id": 0, "pm_cwd": "/srv/api", "pm_exec_path": "/srv/api/server.js",
+            "exec_interpreter": "node", "node_args": ["--max-old-space-size=512"], "args": ["--port", "8080"],
+            "exec_mode": "fork_mode", "instances": 1, "max_memory_restart": 314572800, "max_restarts": 16,
+            "autorestart": true, "watch": true, "ignore_watch": ["node_modules"], "kill_timeout": 1600,
+            "env": {"NODE_ENV": "production", "PORT": 8080, "api": "{}", "unique_id": "5d0c", "PM2_HOME": "/root/.pm2", "pm_id": 0}}},
+        {"pid": 4200, "name": "web", "pm_id": 2,
+         "pm2_env": {"name": "web", "pm_id": 2, "pm_cwd": "/srv/web", "pm_exec_path": "/srv/web/index.js",
+            "exec_interpreter": "node", "exec_mode": "cluster_mode", "instances": 2, "max_restarts": 5,
+            "cron_restart": "0 3 * * *", "env": {"NODE_APP_INSTANCE": 1}}},
+        {"pid": 4199, "name": "web", "pm_id": 1,
+         "pm2_env": {"name": "web", "pm_id": 1, "pm_cwd": "/srv/web", "pm_exec_path": "/srv/web/index.js",
+            "exec_interpreter": "node", "exec_mode": "cluster_mode", "instances": 2, "max_restarts": 5,
+            "cron_restart": "0 3 * * *", "env": {"NODE_APP_INSTANCE": 0}}}
+    ]"#;
+
+    /// `dump.pm2` of a binary run with its arguments, which keeps the settings at the top level
+    const DUMP: &str = r#"[
+        {"name": "worker", "pm_id": 3, "pm_cwd": "/opt/worker", "pm_exec_path": "/opt/worker/bin/run",
+         "exec_interpreter": "none", "args": "--queue emails --label it's", "max_memory_restart": "1G",
+         "watch": ["config", "lib"], "autorestart": false, "env": {"LOG_LEVEL": "debug"}}
+    ]"#;
+
+    fn fixture(json: &str) -> Vec<Translated> { translate(&serde_json::from_str::<Vec<Value>>(json).unwrap()) }
+
+    #[test]
+    fn test_translate_fork_process() {
+        let api = &fixture(JLIST)[0];
+
+        assert_eq!(api.name, "api");
+        assert_eq!(api.pm_id, Some(0));
+        assert_eq!(api.command, "node --max-old-space-size=512 /srv/api/server.js --port 8080");
+        assert_eq!(api.path, PathBuf::from("/srv/api"));
+        assert_eq!(api.max_memory, 314572800);
+        assert_eq!(api.max_restarts, None);
+        assert_eq!(api.watch.as_deref(), Some("/srv/api"));
+
+        // pm2 bookkeeping is dropped, numbers are kept as text
+        assert_eq!(api.env, Env::from([(string!("NODE_ENV"), string!("production")), (string!("PORT"), string!("8080"))]));
+        // Defaults such as autorestart and kill_timeout are not reported
+        assert_eq!(api.untranslated.len(), 1, "{:?}", api.untranslated);
+        assert!(api.untranslated[0].starts_with("ignore_watch node_modules"));
+    }
+
+    #[test]
+    fn test_translate_cluster() {
+        let processes = fixture(JLIST);
+        let workers: Vec<_> = processes.iter().filter(|process| process.name.starts_with("web")).collect();
+
+        assert_eq!(workers.iter().map(|worker| worker.name.as_str()).collect::<Vec<_>>(), ["web-worker-1", "web-worker-2"]);
+        assert_eq!(workers.iter().map(|worker| worker.pm_id).collect::<Vec<_>>(), [Some(1), Some(2)]);
+        assert_eq!(workers[1].env.get("NODE_APP_INSTANCE").map(String::as_str), Some("1"));
+        assert_eq!(workers[0].max_restarts, Some(5));
+
+        assert!(workers[0].untranslated.iter().any(|note| note.starts_with("cluster mode")));
+        assert!(!workers[1].untranslated.iter().any(|note| note.starts_with("cluster mode")));
+        assert!(workers[1].untranslated.contains(&string!(r#"cron_restart "0 3 * * *" has no opm counterpart"#)));
+    }
+
+    #[test]
+    fn test_translate_dump() {
+        let worker = &fixture(DUMP)[0];
+
+        assert_eq!(worker.command, r"/opt/worker/bin/run --queue emails --label 'it'\''s'");
+        assert_eq!(worker.max_memory, 1024 * 1024 * 1024);
+        assert_eq!(worker.watch.as_deref(), Some("/opt/worker/config"));
+        assert_eq!(worker.untranslated, [
+            "watch only covers config, opm watches a single path and not lib",
+            "autorestart false has no opm counterpart",
+        ]);
+    }
+
+    #[test]
+    fn test_parse_jlist_after_notices() {
+        let stdout = ">>>> In-memory PM2 is out-of-date, do:\n>>>> $ pm2 update\n[{\"name\":\"api\"}]\n";
+        assert_eq!(parse_jlist(stdout).unwrap().len(), 1);
+        assert!(parse_jlist("no processes").is_err());
+    }
+}
@@ -0,0 +1,89 @@
+use chrono::{DateTime, Local, NaiveDate, TimeDelta, Utc};
+use colored::Colorize;
+use macros_rs::{crashln, string};
+use opm::{
+    config, helpers, notice,
+    process::{
+        checkpoint,
+        stats::{self, Report, Rollup, Row},
+    },
+};
+use std::{fs, path::Path};
+
+pub struct Options<'o> {
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    /// csv or ndjson
+    pub format: &'o str,
+    /// Printed when not given
+    pub output: Option<&'o Path>,
+    pub rollup: Rollup,
+}
+
+/// Parse `--to`, a plain date includes that whole day
+pub fn parse_until(value: &str) -> Result<DateTime<Utc>, String> {
+    let until = checkpoint::parse_time(value)?;
+
+    match NaiveDate::parse_from_str(value.trim(), "%Y-%m-%d") {
+        Ok(_) => Ok(until + TimeDelta::days(1)),
+        Err(_) => Ok(until),
+    }
+}
+
+fn local(time: DateTime<Utc>) -> String { time.with_timezone(&Local).format("%Y-%m-%d %H:%M").to_string() }
+
+fn render(report: &Report, format: &str) -> String {
+    match format {
+        "ndjson" => report.rows.iter().filter_map(|row| serde_json::to_string(row).ok()).map(|line| line + "\n").collect(),
+        _ => {
+            let columns: Vec<String> = Row::COLUMNS.iter().map(|column| column.to_string()).collect();
+            let rows: Vec<Vec<String>> = report.rows.iter().map(Row::fields).collect();
+            super::columns::csv(&columns, &rows) + "\r\n"
+        }
+    }
+}
+
+/// Tell when the samples start later or end earlier than asked for, on stderr since stdout may hold the report
+fn check_coverage(report: &Report, options: &Options) {
+    let Some((first, last)) = report.covered else {
+        return eprintln!("{} No samples between {} and {}, see daemon.metrics_interval", *helpers::WARN, local(options.from), local(options.to));
+    };
+
+    // A sample is taken every interval, so the edges of a fully covered range are at most that far off
+    let interval = config::read().daemon.metrics_interval.max(60) as i64;
+    let slack = TimeDelta::seconds(interval * 2);
+
+    if first - options.from > slack || options.to.min(Utc::now()) - last > slack {
+        eprintln!(
+            "{} Samples only cover {} to {} of the requested {} to {}",
+            *helpers::WARN,
+            local(first),
+            local(last),
+            local(options.from),
+            local(options.to)
+        );
+    }
+}
+
+pub fn run(options: &Options) {
+    if options.from >= options.to {
+        crashln!("{} --from must be before --to", *helpers::FAIL);
+    }
+
+    let report = stats::report(stats::history(options.from), options.from, options.to, options.rollup);
+    let contents = render(&report, options.format);
+    check_coverage(&report, options);
+
+    let Some(path) = options.output else {
+        return print!("{contents}");
+    };
+
+    if let Err(err) = fs::write(path, contents) {
+        crashln!("{} Cannot write {}.\n{}", *helpers::FAIL, path.display(), string!(err).white());
+    }
+
+    match report.covered {
+        Some((first, last)) => notice!("{} Wrote {} rows to {}, covering {} to {}", *helpers::SUCCESS, report.rows.len(), path.display(), local(first), local(last)),
+        None => notice!("{} Wrote an empty report to {}", *helpers::SUCCESS, path.display()),
+    }
+}
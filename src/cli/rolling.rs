@@ -0,0 +1,152 @@
+//! `opm restart --rolling`, restarts processes one at a time so the rest of a worker group keeps serving
+
+use super::{Internal, Item, Items, check_if_changed, check_remote_permission, connect, format, label_targets, unchanged, wait, wait_for_stats};
+use macros_rs::{crashln, string};
+use opm::{helpers, notice, process::Runner};
+
+use std::{
+    collections::HashMap,
+    thread::sleep,
+    time::{Duration, Instant},
+};
+
+/// How long a restarted process has to come online before the rollout is aborted
+const ONLINE_TIMEOUT: Duration = Duration::from_secs(60);
+
+const POLL: Duration = Duration::from_millis(250);
+
+/// Base name and index of a process started with `--workers`, named `<base>-worker-<N>`
+fn worker(name: &str) -> Option<(&str, usize)> {
+    let (base, index) = name.rsplit_once("-worker-")?;
+    Some((base, index.parse().ok()?))
+}
+
+/// Restart order of `(id, name)` pairs: a worker group in index order, groups and other processes by their lowest id
+fn order(processes: &[(usize, String)]) -> Vec<usize> {
+    let group = |id: usize, name: &str| worker(name).map_or_else(|| format!("\0{id}"), |(base, _)| base.to_string());
+    let mut first: HashMap<String, usize> = HashMap::new();
+
+    for (id, name) in processes {
+        first.entry(group(*id, name)).and_modify(|first| *first = (*first).min(*id)).or_insert(*id);
+    }
+
+    let mut ordered: Vec<(usize, usize, usize)> = processes
+        .iter()
+        .map(|(id, name)| (first[&group(*id, name)], worker(name).map_or(0, |(_, index)| index), *id))
+        .collect();
+
+    ordered.sort();
+    ordered.dedup();
+    ordered.into_iter().map(|(_, _, id)| id).collect()
+}
+
+fn targets(runner: &Runner, items: Option<&Items>, filters: &[(String, String)], server_name: &String) -> Vec<usize> {
+    if !filters.is_empty() {
+        return label_targets(items, filters, server_name);
+    }
+
+    match items {
+        None => crashln!("{} No processes given to restart", *helpers::FAIL),
+        Some(items) if items.is_all() => runner.list.keys().copied().collect(),
+        Some(items) => items
+            .items
+            .iter()
+            .map(|item| match item {
+                Item::Id(id) if runner.list.contains_key(id) => *id,
+                Item::Id(id) => crashln!("{} Process ({id}) not found", *helpers::FAIL),
+                Item::Name(name) | Item::Pattern(name) | Item::Group(name) => match runner.find(name, server_name) {
+                    Some(id) => id,
+                    None => crashln!("{} Process ({name}) not found", *helpers::FAIL),
+                },
+            })
+            .collect(),
+    }
+}
+
+/// Wait for a restarted process to be online, which it only is once its startup grace passed
+fn settle(id: usize, server_name: &str) -> Result<(), String> {
+    let deadline = Instant::now() + ONLINE_TIMEOUT;
+
+    loop {
+        let runner = connect(server_name);
+        let Some(process) = runner.list.get(&id) else {
+            return Err(string!("it was removed"));
+        };
+
+        match wait::status(&runner, process) {
+            "online" => return Ok(()),
+            status @ ("stopped" | "crashed" | "errored") => return Err(format!("it is {status}")),
+            _ if Instant::now() >= deadline => return Err(format!("it was not online after {}s", ONLINE_TIMEOUT.as_secs())),
+            _ => sleep(POLL),
+        }
+    }
+}
+
+/// Restart the processes one after the other, waiting `delay` once each is online. Exits with 1 at the first that is not.
+pub fn run(items: &Option<Items>, filters: &[(String, String)], delay: Duration, if_changed: bool, server_name: &String) {
+    check_remote_permission(server_name);
+    check_if_changed(if_changed, server_name);
+
+    let mut runner = connect(server_name);
+    let (kind, list_name) = format(server_name);
+
+    let ids: Vec<usize> = targets(&runner, items.as_ref(), filters, server_name).into_iter().filter(|id| !unchanged(&runner, *id, if_changed)).collect();
+    let processes: Vec<(usize, String)> = ids.iter().filter_map(|id| runner.list.get(id).map(|process| (*id, process.name.clone()))).collect();
+    let ordered = order(&processes);
+
+    if ordered.is_empty() {
+        notice!("{} Nothing to restart", *helpers::SUCCESS);
+        return;
+    }
+
+    notice!("{} Rolling restart of {} {kind}processes, {}ms apart", *helpers::SUCCESS, ordered.len(), delay.as_millis());
+
+    for (done, id) in ordered.iter().enumerate() {
+        let name = runner.list.get(id).map(|process| process.name.clone()).unwrap_or_default();
+
+        Internal {
+            id: *id,
+            server_name,
+            kind: kind.clone(),
+            runner: runner.clone(),
+        }
+        .restart(&None, &None, false, true, true);
+
+        if let Err(err) = settle(*id, server_name) {
+            crashln!("{} Rolling restart aborted at {name} ({id}), {err}. {} processes were not restarted", *helpers::FAIL, ordered.len() - done - 1);
+        }
+
+        notice!("{} Restarted {kind}{name} ({id}), online [{}/{}]", *helpers::SUCCESS, done + 1, ordered.len());
+        if done + 1 < ordered.len() {
+            sleep(delay);
+        }
+        runner = connect(server_name);
+    }
+
+    wait_for_stats();
+    Internal::list(&string!("default"), &list_name);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn processes(names: &[(usize, &str)]) -> Vec<(usize, String)> { names.iter().map(|(id, name)| (*id, name.to_string())).collect() }
+
+    #[test]
+    fn test_worker_names() {
+        assert_eq!(worker("api-worker-3"), Some(("api", 3)));
+        assert_eq!(worker("my-worker-pool-worker-12"), Some(("my-worker-pool", 12)));
+        assert_eq!(worker("api-worker-x"), None);
+        assert_eq!(worker("api"), None);
+    }
+
+    #[test]
+    fn test_groups_restart_in_index_order() {
+        let list = processes(&[(7, "api-worker-10"), (2, "cron"), (3, "api-worker-2"), (4, "web-worker-1"), (5, "api-worker-1"), (1, "web-worker-2")]);
+
+        // web starts at id 1, cron is 2, api starts at 3
+        assert_eq!(order(&list), vec![4, 1, 2, 5, 3, 7]);
+        assert!(order(&[]).is_empty());
+    }
+}
@@ -0,0 +1,125 @@
+use chrono::Utc;
+use macros_rs::string;
+use opm::{
+    config::{self, structs::{ClientOptions, Seen}, token},
+    helpers, notice,
+    process::{RemoteConfig, http},
+};
+use tabled::{
+    Table, Tabled,
+    settings::{
+        Color, Modify,
+        object::{Rows, Segment},
+        style::{BorderColor, Style},
+    },
+};
+
+#[derive(Tabled)]
+struct Row {
+    name: String,
+    address: String,
+    /// The `env:`/`file:` reference, a literal token is never printed
+    token: String,
+    proxy: String,
+    version: String,
+    role: String,
+    #[tabled(rename = "last seen")]
+    last_seen: String,
+}
+
+/// Remote servers of servers.toml, as used by `--server`
+pub fn print() {
+    let Some(servers) = config::servers().servers.filter(|servers| !servers.is_empty()) else {
+        return notice!("No remote servers in servers.toml");
+    };
+
+    let rows: Vec<Row> = servers
+        .into_iter()
+        .map(|(name, server)| Row {
+            name,
+            address: server.address,
+            token: token::display(&server.token),
+            proxy: server.client.proxy.unwrap_or_else(|| String::from("none")),
+            version: server.seen.version.unwrap_or_else(|| String::from("unknown")),
+            role: server.seen.role.unwrap_or_else(|| String::from("unknown")),
+            last_seen: server.seen.last_seen.map_or(String::from("never"), |time| format!("{} ago", helpers::format_duration(time))),
+        })
+        .collect();
+
+    let table = Table::new(rows)
+        .with(Style::rounded().remove_verticals())
+        .with(Modify::new(Segment::all()).with(BorderColor::filled(Color::new("\x1b[38;2;45;55;72m", "\x1b[39m"))))
+        .with(Modify::new(Rows::first()).with(Color::FG_BRIGHT_CYAN))
+        .to_string();
+
+    notice!("Remote servers\n{table}");
+}
+
+/// Reach the daemon at `address` with `token`, what it reports about itself or why it cannot be used
+pub fn verify(address: &str, token: &Option<String>, client: &ClientOptions) -> Result<Seen, String> {
+    let (agent, headers) = http::sync::client(token, client).map_err(|err| err.to_string())?;
+    let response = agent
+        .get(format!("{}/daemon/config", address.trim_end_matches('/')))
+        .headers(headers)
+        .send()
+        .map_err(|err| format!("the server is unreachable, {}", http::describe_error(&err, client)))?;
+
+    if let Some(err) = refusal(response.status().as_u16()) {
+        return Err(err);
+    }
+
+    let remote = response.json::<RemoteConfig>().map_err(|_| string!("the address does not answer as an opm daemon"))?;
+    Ok(Seen {
+        version: remote.version,
+        role: remote.role,
+        last_seen: Some(Utc::now()),
+    })
+}
+
+/// Why an answer with `status` means the server cannot be used
+fn refusal(status: u16) -> Option<String> {
+    match status {
+        401 | 403 => Some(string!("the token was rejected")),
+        404 => Some(string!("the address does not answer as an opm daemon, is the API enabled?")),
+        status if status >= 400 => Some(format!("the server answered with status {status}")),
+        _ => None,
+    }
+}
+
+/// `v2.3.0, server` for what a server reported, older daemons leave both out
+pub fn describe(seen: &Seen) -> String {
+    let version = seen.version.as_ref().map_or(string!("unknown version"), |version| format!("v{version}"));
+    format!("{version}, {}", seen.role.as_deref().unwrap_or("unknown role"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn test_refusal() {
+        assert_eq!(refusal(200), None);
+        assert_eq!(refusal(401).as_deref(), Some("the token was rejected"));
+        assert_eq!(refusal(403).as_deref(), Some("the token was rejected"));
+        assert!(refusal(404).unwrap().contains("API enabled"));
+        assert_eq!(refusal(502).as_deref(), Some("the server answered with status 502"));
+    }
+
+    #[test]
+    fn test_seen_outdated() {
+        let now = Utc::now();
+        let seen = Seen { version: Some(string!("2.3.0")), role: Some(string!("server")), last_seen: Some(now) };
+        let interval = Duration::minutes(1);
+
+        assert!(Seen::default().outdated(&seen, interval));
+        assert!(!seen.outdated(&Seen { last_seen: Some(now + Duration::seconds(30)), ..seen.clone() }, interval));
+        assert!(seen.outdated(&Seen { last_seen: Some(now + Duration::minutes(2)), ..seen.clone() }, interval));
+
+        // An upgrade or role change is recorded right away
+        let upgraded = Seen { version: Some(string!("2.4.0")), last_seen: Some(now + Duration::seconds(1)), ..seen.clone() };
+        assert!(seen.outdated(&upgraded, interval));
+        assert_eq!(describe(&upgraded), "v2.4.0, server");
+        assert_eq!(describe(&Seen::default()), "unknown version, unknown role");
+    }
+}
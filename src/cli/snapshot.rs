@@ -0,0 +1,413 @@
+use chrono::{DateTime, Utc};
+use colored::Colorize;
+use global_placeholders::global;
+use macros_rs::{crashln, string};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use opm::{
+    config, helpers, notice,
+    process::{ItemSingle, Runner, http, labels},
+};
+
+use tabled::{
+    Table, Tabled,
+    settings::{
+        Color, Modify,
+        object::{Rows, Segment},
+        style::{BorderColor, Style},
+    },
+};
+
+/// Point-in-time copy of `opm details` used to compare a process against later
+#[derive(Serialize, Deserialize)]
+pub struct Snapshot {
+    pub name: String,
+    pub server: String,
+    pub taken_at: DateTime<Utc>,
+    pub item: ItemSingle,
+    pub env: BTreeMap<String, String>,
+}
+
+#[derive(Clone, Debug, Tabled, Serialize)]
+struct DiffRow {
+    field: String,
+    snapshot: String,
+    current: String,
+    change: String,
+}
+
+pub fn run(id: usize, runner: Runner, args: &[String], format: &str, server_name: &String) {
+    let action = args.first().map(String::as_str).unwrap_or("list");
+    let name = args.get(1);
+
+    let mut runner = connect(runner, server_name);
+    let process_name = runner.process(id).name.clone();
+    let dir = directory(server_name, &process_name);
+
+    let require_name = || match name {
+        Some(name) if valid_name(name) => name.clone(),
+        Some(name) => crashln!(
+            "{} Invalid snapshot name '{name}', use letters, digits, '.', '_' or '-'",
+            *helpers::FAIL
+        ),
+        None => crashln!("{} Snapshot '{action}' requires a name", *helpers::FAIL),
+    };
+
+    match action {
+        "save" => {
+            let name = require_name();
+            let snapshot = capture(&mut runner, id, &name, server_name);
+            let encoded = match serde_json::to_string_pretty(&snapshot) {
+                Ok(encoded) => encoded,
+                Err(err) => crashln!("{} Cannot encode snapshot.\n{}", *helpers::FAIL, string!(err).white()),
+            };
+
+            if let Err(err) = fs::create_dir_all(&dir).and_then(|_| fs::write(dir.join(format!("{name}.json")), encoded)) {
+                crashln!("{} Cannot write snapshot.\n{}", *helpers::FAIL, string!(err).white());
+            }
+
+            notice!("{} Saved snapshot '{name}' for {process_name} ({id})", *helpers::SUCCESS);
+        }
+        "diff" => {
+            let name = require_name();
+            let saved = read(&dir, &name);
+            let current = capture(&mut runner, id, &name, server_name);
+            let rows = diff(&saved, &current);
+
+            match format {
+                "json" => match serde_json::to_string(&rows) {
+                    Ok(json) => println!("{json}"),
+                    Err(err) => crashln!("{} Cannot encode diff.\n{}", *helpers::FAIL, string!(err).white()),
+                },
+                _ => {
+                    let table = Table::new(rows)
+                        .with(Style::rounded().remove_verticals())
+                        .with(Modify::new(Segment::all()).with(BorderColor::filled(Color::new("\x1b[38;2;45;55;72m", "\x1b[39m"))))
+                        .with(Modify::new(Rows::first()).with(Color::FG_BRIGHT_CYAN))
+                        .to_string();
+
+                    notice!(
+                        "{}\n{table}\n",
+                        format!("Comparing {process_name} ({id}) with snapshot '{name}' taken {} ago", helpers::format_duration(saved.taken_at))
+                            .on_bright_white()
+                            .black()
+                    );
+                }
+            }
+        }
+        "list" => {
+            let mut names = list(&dir);
+            names.sort();
+
+            if names.is_empty() {
+                notice!("{} No snapshots saved for {process_name} ({id})", *helpers::INFO);
+            } else {
+                notice!("{} Snapshots for {process_name} ({id}):", *helpers::INFO);
+                for name in names {
+                    let taken = read(&dir, &name).taken_at;
+                    notice!("   {name} {}", format!("({} ago)", helpers::format_duration(taken)).bright_black());
+                }
+            }
+        }
+        "delete" | "remove" => {
+            let name = require_name();
+            match fs::remove_file(dir.join(format!("{name}.json"))) {
+                Ok(_) => notice!("{} Deleted snapshot '{name}'", *helpers::SUCCESS),
+                Err(_) => crashln!("{} Snapshot '{name}' not found", *helpers::FAIL),
+            }
+        }
+        _ => crashln!("{} Unknown snapshot action '{action}', use save, diff, list or delete", *helpers::FAIL),
+    }
+}
+
+/// Runner for the given server, the local one unless a remote is named
+pub(super) fn connect(runner: Runner, server_name: &String) -> Runner {
+    if matches!(&**server_name, "internal" | "local") {
+        return runner;
+    }
+
+    let Some(servers) = config::servers().servers else {
+        crashln!("{} Failed to read servers", *helpers::FAIL)
+    };
+
+    match servers.get(server_name) {
+        Some(server) => match Runner::connect(server_name.clone(), server.get(), false) {
+            Some(remote) => remote,
+            None => crashln!("{} Failed to connect (name={server_name}, address={})", *helpers::FAIL, server.address),
+        },
+        None => crashln!("{} Server '{server_name}' does not exist", *helpers::FAIL),
+    }
+}
+
+fn capture(runner: &mut Runner, id: usize, name: &str, server_name: &String) -> Snapshot {
+    let env = runner.process(id).env.clone();
+
+    // Remote runners only hold the dump, stats come from the info route
+    let item = match &runner.remote {
+        Some(remote) => match http::info(remote, id).and_then(|response| Ok(response.json::<ItemSingle>()?)) {
+            Ok(item) => item,
+            Err(err) => crashln!("{} Failed to fetch process info.\n{}", *helpers::FAIL, string!(err).white()),
+        },
+        None => runner.clone().get(id).fetch(),
+    };
+
+    Snapshot {
+        item,
+        env,
+        name: name.to_string(),
+        server: server_name.to_string(),
+        taken_at: Utc::now(),
+    }
+}
+
+fn directory(server_name: &str, process_name: &str) -> PathBuf {
+    let sanitize = |value: &str| value.replace(['/', ' '], "_");
+    PathBuf::from(global!("opm.base"))
+        .join("snapshots")
+        .join(sanitize(server_name))
+        .join(sanitize(process_name))
+}
+
+fn valid_name(name: &str) -> bool {
+    !name.is_empty() && !name.starts_with('.') && name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'))
+}
+
+fn read(dir: &Path, name: &str) -> Snapshot {
+    let path = dir.join(format!("{name}.json"));
+    let contents = match fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(_) => crashln!("{} Snapshot '{name}' not found", *helpers::FAIL),
+    };
+
+    match serde_json::from_str(&contents) {
+        Ok(snapshot) => snapshot,
+        Err(err) => crashln!("{} Cannot parse snapshot '{name}'.\n{}", *helpers::FAIL, string!(err).white()),
+    }
+}
+
+fn list(dir: &Path) -> Vec<String> {
+    fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .flatten()
+                .filter_map(|entry| entry.file_name().to_str()?.strip_suffix(".json").map(String::from))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn diff(saved: &Snapshot, current: &Snapshot) -> Vec<DiffRow> {
+    let (then, now) = (&saved.item, &current.item);
+    let mut rows = vec![];
+
+    let mut row = |field: &str, snapshot: String, current: String, change: String| {
+        rows.push(DiffRow {
+            field: field.to_string(),
+            snapshot,
+            current,
+            change,
+        })
+    };
+
+    let rss = |item: &ItemSingle| item.stats.memory_usage.as_ref().map_or(0, |m| m.rss);
+    let vms = |item: &ItemSingle| item.stats.memory_usage.as_ref().map_or(0, |m| m.vms);
+    let cpu = |item: &ItemSingle| item.stats.cpu_percent.unwrap_or(0.0);
+
+    row("status", then.info.status.clone(), now.info.status.clone(), changed(then.info.status != now.info.status));
+    row("pid", then.info.pid.to_string(), now.info.pid.to_string(), changed(then.info.pid != now.info.pid));
+    row(
+        "memory",
+        helpers::format_memory(rss(then)),
+        helpers::format_memory(rss(now)),
+        memory_delta(rss(then), rss(now)),
+    );
+    row(
+        "virtual memory",
+        helpers::format_memory(vms(then)),
+        helpers::format_memory(vms(now)),
+        memory_delta(vms(then), vms(now)),
+    );
+    row(
+        "cpu",
+        format!("{:.2}%", cpu(then)),
+        format!("{:.2}%", cpu(now)),
+        format!("{:+.2}%", cpu(now) - cpu(then)),
+    );
+    row(
+        "restarts",
+        then.stats.restarts.to_string(),
+        now.stats.restarts.to_string(),
+        count_delta(then.stats.restarts, now.stats.restarts),
+    );
+    row(
+        "crashes",
+        then.raw.crashes.to_string(),
+        now.raw.crashes.to_string(),
+        count_delta(then.raw.crashes, now.raw.crashes),
+    );
+    row(
+        "uptime",
+        then.info.uptime.clone(),
+        now.info.uptime.clone(),
+        label(then.stats.start_time != now.stats.start_time, "restarted since snapshot", "-"),
+    );
+    row(
+        "children",
+        then.info.children.len().to_string(),
+        now.info.children.len().to_string(),
+        count_delta(then.info.children.len() as u64, now.info.children.len() as u64),
+    );
+    row(
+        "labels",
+        labels::describe(&then.info.labels),
+        labels::describe(&now.info.labels),
+        changed(then.info.labels != now.info.labels),
+    );
+
+    let keys = saved.env.keys().chain(current.env.keys()).collect::<std::collections::BTreeSet<_>>();
+    for key in keys {
+        let (before, after) = (saved.env.get(key), current.env.get(key));
+        if before == after {
+            continue;
+        }
+
+        let change = match (before, after) {
+            (None, Some(_)) => "added",
+            (Some(_), None) => "removed",
+            _ => "changed",
+        };
+
+        row(
+            &format!("env {key}"),
+            before.cloned().unwrap_or_else(|| string!("-")),
+            after.cloned().unwrap_or_else(|| string!("-")),
+            change.to_string(),
+        );
+    }
+
+    rows
+}
+
+fn label(condition: bool, yes: &str, no: &str) -> String {
+    if condition { yes } else { no }.to_string()
+}
+
+fn changed(condition: bool) -> String {
+    label(condition, "changed", "-")
+}
+
+fn count_delta(before: u64, after: u64) -> String {
+    match after as i128 - before as i128 {
+        0 => string!("-"),
+        delta => format!("{delta:+}"),
+    }
+}
+
+fn memory_delta(before: u64, after: u64) -> String {
+    match after.cmp(&before) {
+        std::cmp::Ordering::Equal => string!("-"),
+        std::cmp::Ordering::Greater => format!("+{}", helpers::format_memory(after - before)),
+        std::cmp::Ordering::Less => format!("-{}", helpers::format_memory(before - after)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opm::process::{Info, Log, MemoryInfo, Raw, Stats, Watch};
+
+    fn snapshot(rss: u64, restarts: u64, env: &[(&str, &str)]) -> Snapshot {
+        Snapshot {
+            name: string!("test"),
+            server: string!("local"),
+            taken_at: Utc::now(),
+            env: env.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+            item: ItemSingle {
+                info: Info {
+                    id: 0,
+                    pid: 100,
+                    name: string!("app"),
+                    status: string!("online"),
+                    path: PathBuf::from("/tmp"),
+                    uptime: string!("1m"),
+                    command: string!("sh -c 'app'"),
+                    labels: Default::default(),
+                    last_restart_reason: None,
+                    url: None,
+                    max_restarts: None,
+                    health: None,
+                    description: None,
+                    children: vec![],
+                    zombies: 0,
+                    fingerprint: None,
+                },
+                stats: Stats {
+                    restarts,
+                    start_time: 0,
+                    cpu_percent: Some(1.0),
+                    memory_usage: Some(MemoryInfo { rss, vms: rss }),
+                    fds: None,
+                    threads: None,
+                    cpu_sample: None,
+                    log_rate: None,
+                },
+                watch: Watch {
+                    enabled: false,
+                    path: String::new(),
+                    hash: String::new(),
+                    cooldown_secs: None,
+                    last_reload: None,
+                    pending: None,
+                },
+                log: Log {
+                    out: String::new(),
+                    error: String::new(),
+                },
+                raw: Raw {
+                    running: true,
+                    crashed: false,
+                    crashes: 0,
+                },
+            },
+        }
+    }
+
+    fn change<'r>(rows: &'r [DiffRow], field: &str) -> &'r str {
+        &rows.iter().find(|row| row.field == field).unwrap().change
+    }
+
+    #[test]
+    fn test_diff_reports_memory_and_restart_deltas() {
+        let saved = snapshot(1024 * 1024, 1, &[]);
+        let current = snapshot(313 * 1024 * 1024, 3, &[]);
+        let rows = diff(&saved, &current);
+
+        assert_eq!(change(&rows, "memory"), "+312mb");
+        assert_eq!(change(&rows, "restarts"), "+2");
+        assert_eq!(change(&rows, "pid"), "-");
+    }
+
+    #[test]
+    fn test_diff_reports_env_changes() {
+        let saved = snapshot(0, 0, &[("KEEP", "1"), ("OLD", "x"), ("MODE", "a")]);
+        let current = snapshot(0, 0, &[("KEEP", "1"), ("NEW", "y"), ("MODE", "b")]);
+        let rows = diff(&saved, &current);
+
+        assert_eq!(change(&rows, "env NEW"), "added");
+        assert_eq!(change(&rows, "env OLD"), "removed");
+        assert_eq!(change(&rows, "env MODE"), "changed");
+        assert!(!rows.iter().any(|row| row.field == "env KEEP"));
+    }
+
+    #[test]
+    fn test_snapshot_names_are_validated() {
+        assert!(valid_name("before-deploy_1.2"));
+        assert!(!valid_name("../escape"));
+        assert!(!valid_name(""));
+    }
+}
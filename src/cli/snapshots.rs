@@ -0,0 +1,130 @@
+use chrono::{Local, Utc};
+use colored::Colorize;
+use global_placeholders::global;
+use macros_rs::{crashln, string};
+use opm::{
+    helpers, notice,
+    process::{
+        Runner,
+        snapshots::{self, Change, Kind, Snapshot},
+    },
+};
+use tabled::{
+    Table, Tabled,
+    settings::{
+        Color, Modify,
+        object::{Rows, Segment},
+        style::{BorderColor, Style},
+    },
+};
+
+#[derive(Tabled)]
+struct SnapshotRow {
+    snapshot: String,
+    taken: String,
+    processes: String,
+    size: String,
+}
+
+impl From<&Snapshot> for SnapshotRow {
+    fn from(snapshot: &Snapshot) -> Self {
+        SnapshotRow {
+            snapshot: snapshot.stamp.clone(),
+            taken: snapshot.time.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S").to_string(),
+            processes: snapshot.load().map_or_else(|_| string!("unreadable"), |runner| runner.list.len().to_string()),
+            size: helpers::format_memory(snapshot.bytes),
+        }
+    }
+}
+
+#[derive(Tabled)]
+struct ChangeRow {
+    change: String,
+    id: usize,
+    name: String,
+    fields: String,
+}
+
+impl From<&Change> for ChangeRow {
+    fn from(change: &Change) -> Self {
+        let kind = change.kind.to_string();
+        let kind = match change.kind {
+            Kind::Created => kind.green(),
+            Kind::Removed => kind.red(),
+            Kind::Changed => kind.yellow(),
+        };
+
+        ChangeRow {
+            change: kind.to_string(),
+            id: change.id,
+            name: change.name.clone(),
+            fields: change.fields.join(", "),
+        }
+    }
+}
+
+fn table<T: Tabled>(rows: impl IntoIterator<Item = T>) -> String {
+    Table::new(rows)
+        .with(Style::rounded().remove_verticals())
+        .with(Modify::new(Segment::all()).with(BorderColor::filled(Color::new("\x1b[38;2;45;55;72m", "\x1b[39m"))))
+        .with(Modify::new(Rows::first()).with(Color::FG_BRIGHT_CYAN))
+        .to_string()
+}
+
+pub fn list(format: &str) {
+    let found = snapshots::list(&snapshots::dir());
+
+    if format == "json" {
+        match serde_json::to_string(&found) {
+            Ok(json) => println!("{json}"),
+            Err(err) => crashln!("{} Cannot encode snapshots.\n{}", *helpers::FAIL, string!(err).white()),
+        }
+        return;
+    }
+
+    if found.is_empty() {
+        return notice!("{} No dump snapshots yet, the daemon takes one every daemon.snapshot_interval", *helpers::INFO);
+    }
+
+    notice!("{}", table(found.iter().rev().map(SnapshotRow::from)));
+}
+
+pub fn restore(stamp: &str, dry_run: bool) {
+    let dir = snapshots::dir();
+    let snapshot = snapshots::find(&dir, stamp).unwrap_or_else(|err| crashln!("{} {err}", *helpers::FAIL));
+    let target = snapshot.load().unwrap_or_else(|err| crashln!("{} {err}", *helpers::FAIL));
+
+    let mut runner = Runner::new();
+    let changes = snapshots::diff(&runner, &target);
+
+    if changes.is_empty() {
+        return notice!("{} Processes already match snapshot {}", *helpers::SUCCESS, snapshot.stamp);
+    }
+
+    notice!("{}", table(changes.iter().map(ChangeRow::from)));
+
+    if dry_run {
+        return notice!("{} Dry run, nothing was changed", *helpers::INFO);
+    }
+
+    let blocked = snapshots::blocked(&changes);
+    if !blocked.is_empty() {
+        let names: Vec<String> = blocked.iter().map(|change| format!("{} ({})", change.name, change.id)).collect();
+        crashln!("{} Restoring never stops a running process, stop these first: {}", *helpers::FAIL, names.join(", "));
+    }
+
+    // The list being replaced becomes a snapshot of its own, so the restore can be undone
+    match std::fs::read(global!("opm.dump")).map(|dump| snapshots::take(&dir, &dump, Utc::now(), usize::MAX)) {
+        Ok(Ok(Some(previous))) => notice!("{} Saved the current processes as snapshot {}", *helpers::SUCCESS, previous.stamp),
+        Ok(Ok(None)) => {}
+        Ok(Err(err)) | Err(err) => crashln!("{} Cannot snapshot the current processes, nothing was changed.\n{}", *helpers::FAIL, string!(err).white()),
+    }
+
+    snapshots::apply(&mut runner, target, &changes);
+    runner.save();
+
+    notice!("{} Restored {} process(es) from snapshot {}", *helpers::SUCCESS, changes.len(), snapshot.stamp);
+    if changes.iter().any(|change| change.kind != Kind::Removed) {
+        notice!("{} Restored processes are stopped, start them with `opm start <name>`", *helpers::INFO);
+    }
+}
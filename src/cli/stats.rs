@@ -0,0 +1,61 @@
+use colored::Colorize;
+use macros_rs::{crashln, string};
+use opm::{
+    helpers, notice,
+    process::stats::{Bucket, Summary},
+};
+use tabled::{
+    Table, Tabled,
+    settings::{
+        Color, Modify,
+        object::{Rows, Segment},
+        style::{BorderColor, Style},
+    },
+};
+
+#[derive(Tabled)]
+struct Row {
+    bucket: String,
+    processes: usize,
+    running: usize,
+    cpu: String,
+    mem: String,
+    #[tabled(rename = "↺")]
+    restarts: u64,
+}
+
+impl From<&Bucket> for Row {
+    fn from(bucket: &Bucket) -> Self {
+        Row {
+            bucket: bucket.name.clone(),
+            processes: bucket.processes,
+            running: bucket.running,
+            cpu: format!("{:.2}%", bucket.cpu),
+            mem: helpers::format_memory(bucket.memory),
+            restarts: bucket.restarts,
+        }
+    }
+}
+
+pub fn print(summary: &Summary, format: &str) {
+    if format == "json" {
+        match serde_json::to_string(summary) {
+            Ok(json) => println!("{json}"),
+            Err(err) => crashln!("{} Cannot encode summary.\n{}", *helpers::FAIL, string!(err).white()),
+        }
+        return;
+    }
+
+    let rows: Vec<Row> = summary.buckets.iter().chain([&summary.total]).map(Row::from).collect();
+    let table = Table::new(rows)
+        .with(Style::rounded().remove_verticals())
+        .with(Modify::new(Segment::all()).with(BorderColor::filled(Color::new("\x1b[38;2;45;55;72m", "\x1b[39m"))))
+        .with(Modify::new(Rows::first()).with(Color::FG_BRIGHT_CYAN))
+        .with(Modify::new(Rows::last()).with(Color::BOLD))
+        .to_string();
+
+    notice!(
+        "{}\n{table}\n",
+        format!("Resource usage by {}", summary.by).on_bright_white().black()
+    );
+}
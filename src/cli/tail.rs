@@ -0,0 +1,113 @@
+//! `opm logs --follow` of several processes, their lines interleaved and tagged with a colored `[name]` like docker compose does
+
+use colored::{Color, Colorize};
+use macros_rs::ternary;
+use opm::{
+    file::{self, FollowEvent, LogFollower},
+    notice,
+    process::Runner,
+};
+
+use std::{
+    io::{self, Write},
+    sync::mpsc,
+    thread::{self, sleep},
+    time::Duration,
+};
+
+const POLL: Duration = Duration::from_millis(500);
+
+/// Tag colors by position, so processes next to each other differ
+const COLORS: [Color; 6] = [Color::Cyan, Color::Yellow, Color::Magenta, Color::Blue, Color::Green, Color::BrightRed];
+
+/// The logs of one process, read on a thread of its own
+struct Stream {
+    tag: String,
+    logs: Vec<(&'static str, LogFollower)>,
+}
+
+/// Logs of several processes followed together
+pub struct Tail {
+    streams: Vec<Stream>,
+}
+
+/// `[name]` padded to the longest name, so the lines of every process start in one column
+fn tag(name: &str, width: usize) -> String { format!("{:<width$}", format!("[{name}]"), width = width + 2) }
+
+impl Tail {
+    /// Start following the logs of `ids` where they end now, so nothing written while their last lines are printed is missed
+    pub fn new(runner: &Runner, ids: &[usize], errors_only: bool) -> Self {
+        let kinds: &[&'static str] = ternary!(errors_only, &["error"], &["out", "error"]);
+        let width = ids.iter().filter_map(|id| runner.list.get(id)).map(|process| process.name.chars().count()).max().unwrap_or(0);
+
+        let streams = ids
+            .iter()
+            .filter_map(|id| runner.list.get(id))
+            .enumerate()
+            .map(|(index, process)| {
+                let logs = process.logs();
+                Stream {
+                    tag: tag(&process.name, width).color(COLORS[index % COLORS.len()]).to_string(),
+                    logs: kinds.iter().map(|kind| (*kind, LogFollower::new(ternary!(*kind == "out", &logs.out, &logs.error)))).collect(),
+                }
+            })
+            .collect();
+
+        Tail { streams }
+    }
+
+    /// Print the lines of every process as they are written, until Ctrl-C. Each thread sends whole
+    /// lines and only this one prints them, so lines of different processes never tear each other.
+    pub fn run(self, filter: Option<&str>, timestamps: bool) {
+        // Ctrl-C is how a follow ends, every printed line is already flushed
+        extern "C" fn interrupted(_: libc::c_int) { unsafe { libc::_exit(0) } }
+        unsafe { libc::signal(libc::SIGINT, interrupted as *const () as libc::sighandler_t) };
+        notice!("{}", format!("\nFollowing the logs of {} processes (press Ctrl+C to exit)", self.streams.len()).yellow());
+
+        let (sender, receiver) = mpsc::channel::<String>();
+        for Stream { tag, mut logs } in self.streams {
+            let (sender, filter) = (sender.clone(), filter.map(str::to_string));
+
+            thread::spawn(move || {
+                loop {
+                    for (kind, follower) in &mut logs {
+                        for event in follower.poll() {
+                            let line = match event {
+                                FollowEvent::Line(line) => file::follow_line(&line, kind, filter.as_deref(), timestamps),
+                                FollowEvent::Rotated => Some(format!("-- {kind} log rotated --").bright_black().to_string()),
+                            };
+
+                            if let Some(line) = line
+                                && sender.send(format!("{tag} {line}")).is_err()
+                            {
+                                return;
+                            }
+                        }
+                    }
+                    sleep(POLL);
+                }
+            });
+        }
+        drop(sender);
+
+        // A closed pipe ends the command like it would for `tail -f`
+        let mut stdout = io::stdout().lock();
+        for line in receiver {
+            if writeln!(stdout, "{line}").and_then(|_| stdout.flush()).is_err() {
+                std::process::exit(0);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tags_line_up() {
+        assert_eq!(tag("api", 8), "[api]     ");
+        assert_eq!(tag("worker-1", 8), "[worker-1]");
+        assert_eq!(tag("api", 8).len(), tag("worker-1", 8).len());
+    }
+}
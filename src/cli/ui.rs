@@ -0,0 +1,579 @@
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use macros_rs::{crashln, string};
+use opm::{
+    audit, config, helpers,
+    process::{ProcessItem, Runner, http, is_pid_alive},
+};
+
+use ratatui::{
+    DefaultTerminal, Frame,
+    layout::{Constraint, Layout, Rect},
+    style::{Color, Modifier, Style, Stylize},
+    text::{Line, Span},
+    widgets::{Block, Paragraph, Row, Table, TableState, Wrap},
+};
+
+use std::{
+    fs::File,
+    io::{self, IsTerminal, Read, Seek, SeekFrom},
+    time::{Duration, Instant},
+};
+
+/// Seconds between process list refreshes, sampling every process is not free
+const LIST_REFRESH: Duration = Duration::from_secs(2);
+/// Log refresh while following the selected process
+const FOLLOW_REFRESH: Duration = Duration::from_millis(500);
+const TICK: Duration = Duration::from_millis(100);
+
+/// Below this the panes cannot be laid out, a notice is drawn instead
+const MIN_WIDTH: u16 = 60;
+const MIN_HEIGHT: u16 = 12;
+
+const LOG_LINES: usize = 500;
+/// Only the end of a log is read for the tail
+const LOG_TAIL_BYTES: u64 = 256 * 1024;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Action {
+    Restart,
+    Stop,
+}
+
+#[derive(Debug, PartialEq)]
+enum Mode {
+    Browse,
+    Filter,
+    Confirm { action: Action, id: usize, name: String },
+}
+
+struct App {
+    /// `local` first, then the servers of servers.toml in order
+    servers: Vec<String>,
+    server: usize,
+    runner: Result<Runner, String>,
+    processes: Vec<ProcessItem>,
+    /// Id of the selected process, kept across refreshes while the list reorders
+    selected: Option<usize>,
+    filter: String,
+    mode: Mode,
+    follow: bool,
+    errors: bool,
+    log: Vec<String>,
+    /// Outcome of the last action or refresh, `true` when it failed
+    status: Option<(bool, String)>,
+    listed: Option<Instant>,
+    logged: Option<Instant>,
+}
+
+impl Action {
+    fn name(&self) -> &'static str {
+        match self {
+            Action::Restart => "restart",
+            Action::Stop => "stop",
+        }
+    }
+}
+
+fn connect(server: &str) -> Result<Runner, String> {
+    if matches!(server, "internal" | "local") {
+        return Ok(Runner::new());
+    }
+
+    let servers = config::servers().servers.unwrap_or_default();
+    let Some(config) = servers.get(server) else {
+        return Err(format!("Server '{server}' does not exist"));
+    };
+
+    Runner::connect(server.to_string(), config.get(), false).ok_or_else(|| format!("Failed to connect (name={server}, address={})", config.address))
+}
+
+/// Last `lines` lines of a log file, reading only its end
+fn tail(path: &str, lines: usize) -> Vec<String> {
+    let Ok(mut file) = File::open(path) else { return vec![] };
+    let start = file.metadata().map_or(0, |metadata| metadata.len().saturating_sub(LOG_TAIL_BYTES));
+    let mut bytes = vec![];
+
+    if file.seek(SeekFrom::Start(start)).and_then(|_| file.read_to_end(&mut bytes)).is_err() {
+        return vec![];
+    }
+
+    let text = String::from_utf8_lossy(&bytes);
+    // Reading from the middle of the file starts on a partial line
+    let all: Vec<&str> = text.lines().skip(usize::from(start > 0)).collect();
+
+    all[all.len().saturating_sub(lines)..].iter().map(|line| line.to_string()).collect()
+}
+
+fn status_color(status: &str) -> Color {
+    match status {
+        "online" => Color::Green,
+        "starting" => Color::Yellow,
+        "pending" => Color::Blue,
+        "unhealthy" => Color::Magenta,
+        _ => Color::Red,
+    }
+}
+
+impl App {
+    fn new(server: &str) -> Self {
+        let mut servers = vec![string!("local")];
+        servers.extend(config::servers().servers.unwrap_or_default().into_keys());
+
+        let server = servers.iter().position(|name| name == server).unwrap_or(0);
+
+        Self {
+            runner: connect(&servers[server]),
+            servers,
+            server,
+            processes: vec![],
+            selected: None,
+            filter: string!(""),
+            mode: Mode::Browse,
+            follow: false,
+            errors: false,
+            log: vec![],
+            status: None,
+            listed: None,
+            logged: None,
+        }
+    }
+
+    fn is_local(&self) -> bool { self.server == 0 }
+
+    fn visible(&self) -> Vec<&ProcessItem> {
+        let filter = self.filter.to_lowercase();
+        self.processes.iter().filter(|process| process.name.to_lowercase().contains(&filter)).collect()
+    }
+
+    fn selected_process(&self) -> Option<&ProcessItem> {
+        let visible = self.visible();
+        self.selected.and_then(|id| visible.iter().find(|process| process.id == id).copied()).or(visible.first().copied())
+    }
+
+    fn select(&mut self, offset: isize) {
+        let visible = self.visible();
+        if visible.is_empty() {
+            return;
+        }
+
+        let current = self.selected.and_then(|id| visible.iter().position(|process| process.id == id)).unwrap_or(0);
+        let next = current.saturating_add_signed(offset).min(visible.len() - 1);
+
+        self.selected = Some(visible[next].id);
+        self.logged = None;
+    }
+
+    fn refresh(&mut self) {
+        // The local dump is written by the daemon and other invocations, read it again every time
+        if self.is_local() {
+            self.runner = Ok(Runner::new());
+        }
+
+        match self.runner.as_ref().map_err(String::clone).and_then(Runner::processes) {
+            Ok(processes) => self.processes = processes,
+            Err(err) => {
+                self.processes.clear();
+                self.status = Some((true, err));
+            }
+        }
+
+        self.listed = Some(Instant::now());
+        self.load_log();
+    }
+
+    fn load_log(&mut self) {
+        let kind = if self.errors { "error" } else { "out" };
+        let Some(id) = self.selected_process().map(|process| process.id) else {
+            self.log.clear();
+            return;
+        };
+
+        self.log = match &self.runner {
+            Ok(runner) => match &runner.remote {
+                Some(remote) => match http::logs(remote, id, kind) {
+                    Ok(log) => log.lines[log.lines.len().saturating_sub(LOG_LINES)..].to_vec(),
+                    Err(err) => vec![format!("Cannot fetch logs: {err}")],
+                },
+                None => match runner.info(id).map(|process| process.logs()) {
+                    Some(logs) => tail(if self.errors { &logs.error } else { &logs.out }, LOG_LINES),
+                    None => vec![],
+                },
+            },
+            Err(_) => vec![],
+        };
+
+        self.logged = Some(Instant::now());
+    }
+
+    fn switch_server(&mut self, offset: isize) {
+        let count = self.servers.len() as isize;
+        self.server = (self.server as isize + offset).rem_euclid(count) as usize;
+        self.runner = connect(&self.servers[self.server]);
+        self.selected = None;
+        self.status = None;
+        self.refresh();
+    }
+
+    fn act(&mut self, action: Action, id: usize, name: &str) {
+        let server = &self.servers[self.server];
+        let result = match self.runner.as_mut() {
+            Err(err) => Err(err.clone()),
+            Ok(runner) => match runner.remote.as_ref() {
+                Some(remote) => match action {
+                    Action::Restart => http::restart(remote, id).map(|_| ()),
+                    Action::Stop => http::stop(remote, id, None).map(|_| ()),
+                }
+                .map_err(|err| err.to_string()),
+                None => {
+                    let mut runner = Runner::new();
+                    let alive = runner.info(id).is_some_and(|process| is_pid_alive(process.pid));
+
+                    // Checked first, the runner would only print the refusal over the ui
+                    match alive.then(|| runner.signal_permission(id)).transpose() {
+                        Err(err) => Err(err),
+                        Ok(_) if !runner.exists(id) => Err(format!("Process {id} was not found")),
+                        Ok(_) => {
+                            match action {
+                                Action::Restart => runner.restart(id, !alive, true).save(),
+                                Action::Stop => runner.stop(id, None).save(),
+                            }
+                            Ok(())
+                        }
+                    }
+                }
+            },
+        };
+
+        let mut entry = audit::Entry::new(action.name(), name, audit::local_actor(), audit::Via::Cli);
+        entry.server = (!self.is_local()).then(|| server.clone());
+
+        self.status = Some(match result {
+            Ok(()) => {
+                audit::record(entry);
+                (false, format!("{} {name} ({id}) ✓", if action == Action::Restart { "Restarted" } else { "Stopped" }))
+            }
+            Err(err) => {
+                audit::record(entry.failed(Some(err.clone())));
+                (true, format!("Cannot {} {name}: {err}", action.name()))
+            }
+        });
+
+        self.refresh();
+    }
+
+    /// Handle a key press, false once the ui should close
+    fn key(&mut self, key: KeyEvent) -> bool {
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            return false;
+        }
+
+        match std::mem::replace(&mut self.mode, Mode::Browse) {
+            Mode::Filter => match key.code {
+                KeyCode::Enter => {}
+                KeyCode::Esc => self.filter.clear(),
+                KeyCode::Backspace => {
+                    self.filter.pop();
+                    self.mode = Mode::Filter;
+                }
+                KeyCode::Char(c) => {
+                    self.filter.push(c);
+                    self.mode = Mode::Filter;
+                }
+                _ => self.mode = Mode::Filter,
+            },
+            Mode::Confirm { action, id, name } => {
+                match key.code {
+                    KeyCode::Char('y') | KeyCode::Char('Y') => self.act(action, id, &name),
+                    _ => self.status = Some((false, format!("Cancelled {} of {name}", action.name()))),
+                }
+            }
+            Mode::Browse => match key.code {
+                KeyCode::Char('q') => return false,
+                KeyCode::Esc if self.filter.is_empty() => return false,
+                KeyCode::Esc => self.filter.clear(),
+                KeyCode::Up | KeyCode::Char('k') => self.select(-1),
+                KeyCode::Down | KeyCode::Char('j') => self.select(1),
+                KeyCode::PageUp => self.select(-10),
+                KeyCode::PageDown => self.select(10),
+                KeyCode::Char('/') => self.mode = Mode::Filter,
+                KeyCode::Char('l') => {
+                    self.follow = !self.follow;
+                    self.logged = None;
+                }
+                KeyCode::Char('e') => {
+                    self.errors = !self.errors;
+                    self.logged = None;
+                }
+                KeyCode::Tab => self.switch_server(1),
+                KeyCode::BackTab => self.switch_server(-1),
+                KeyCode::Char(c @ ('r' | 's')) => {
+                    if let Some(process) = self.selected_process() {
+                        let action = if c == 'r' { Action::Restart } else { Action::Stop };
+                        self.mode = Mode::Confirm { action, id: process.id, name: process.name.clone() };
+                    }
+                }
+                _ => {}
+            },
+        }
+
+        true
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        let area = frame.area();
+
+        if area.width < MIN_WIDTH || area.height < MIN_HEIGHT {
+            let notice = format!("Terminal too small ({}x{}), resize to at least {MIN_WIDTH}x{MIN_HEIGHT} or press q", area.width, area.height);
+            frame.render_widget(Paragraph::new(notice).wrap(Wrap { trim: true }), area);
+            return;
+        }
+
+        let [header, body, footer] = Layout::vertical([Constraint::Length(1), Constraint::Min(0), Constraint::Length(1)]).areas(area);
+        let [list, side] = Layout::horizontal([Constraint::Percentage(45), Constraint::Percentage(55)]).areas(body);
+
+        self.draw_header(frame, header);
+        self.draw_list(frame, list);
+
+        match self.follow {
+            true => self.draw_log(frame, side),
+            false => {
+                let [details, log] = Layout::vertical([Constraint::Length(11), Constraint::Min(0)]).areas(side);
+                self.draw_details(frame, details);
+                self.draw_log(frame, log);
+            }
+        }
+
+        self.draw_footer(frame, footer);
+    }
+
+    fn draw_header(&self, frame: &mut Frame, area: Rect) {
+        let mut spans = vec![
+            Span::raw(" opm ").bold().black().on_cyan(),
+            Span::raw(format!("  server: {} ({}/{})", self.servers[self.server], self.server + 1, self.servers.len())),
+        ];
+
+        if !self.filter.is_empty() || self.mode == Mode::Filter {
+            spans.push(Span::raw(format!("  filter: {}", self.filter)).yellow());
+        }
+
+        frame.render_widget(Line::from(spans), area);
+    }
+
+    fn draw_list(&self, frame: &mut Frame, area: Rect) {
+        let visible = self.visible();
+        let selected = self.selected_process().and_then(|selected| visible.iter().position(|process| process.id == selected.id));
+
+        let rows = visible.iter().map(|process| {
+            Row::new(vec![
+                Span::raw(process.id.to_string()).cyan(),
+                Span::raw(process.name.clone()),
+                Span::raw(process.status.clone()).fg(status_color(&process.status)),
+                Span::raw(process.cpu.clone()),
+                Span::raw(process.mem.clone()),
+            ])
+        });
+
+        let widths = [Constraint::Length(4), Constraint::Min(10), Constraint::Length(9), Constraint::Length(8), Constraint::Length(9)];
+        let table = Table::new(rows, widths)
+            .header(Row::new(["id", "name", "status", "cpu", "mem"]).bold().light_cyan())
+            .block(Block::bordered().title(format!(" processes ({}) ", visible.len())))
+            .row_highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+
+        frame.render_stateful_widget(table, area, &mut TableState::new().with_selected(selected));
+    }
+
+    fn draw_details(&self, frame: &mut Frame, area: Rect) {
+        let block = Block::bordered().title(" details ");
+        let Some(process) = self.selected_process() else {
+            frame.render_widget(Paragraph::new("No process selected").block(block), area);
+            return;
+        };
+
+        let field = |key: &str, value: String| Line::from(vec![Span::raw(format!("{key:<10}")).cyan(), Span::raw(value)]);
+        let labels = process.labels.iter().map(|(key, value)| format!("{key}={value}")).collect::<Vec<_>>().join(", ");
+
+        let lines = vec![
+            field("name", process.name.clone()),
+            field("status", process.status.clone()),
+            field("pid", if process.status == "online" { process.pid.to_string() } else { string!("n/a") }),
+            field("uptime", process.uptime.clone()),
+            field("restarts", process.restarts.to_string()),
+            field("cpu / mem", format!("{} / {}", process.cpu, process.mem)),
+            field("watching", if process.watch_path.is_empty() { string!("disabled") } else { process.watch_path.clone() }),
+            field("labels", if labels.is_empty() { string!("none") } else { labels }),
+            field("url", process.url.clone().unwrap_or_else(|| string!("none"))),
+        ];
+
+        frame.render_widget(Paragraph::new(lines).block(block), area);
+    }
+
+    fn draw_log(&self, frame: &mut Frame, area: Rect) {
+        let kind = if self.errors { "error" } else { "out" };
+        let title = format!(" {kind} log{} ", if self.follow { ", following" } else { "" });
+        let height = area.height.saturating_sub(2) as usize;
+
+        let lines: Vec<Line> = self.log[self.log.len().saturating_sub(height)..].iter().map(|line| Line::raw(line.as_str())).collect();
+        frame.render_widget(Paragraph::new(lines).block(Block::bordered().title(title)), area);
+    }
+
+    fn draw_footer(&self, frame: &mut Frame, area: Rect) {
+        let line = match &self.mode {
+            Mode::Filter => Line::from(format!("/{}▏  enter to apply, esc to clear", self.filter)),
+            Mode::Confirm { action, id, name } => Line::from(format!("{} {name} ({id})? y/n", if *action == Action::Restart { "Restart" } else { "Stop" })).bold().yellow(),
+            Mode::Browse => match &self.status {
+                Some((true, message)) => Line::from(message.as_str()).red(),
+                Some((false, message)) => Line::from(message.as_str()).green(),
+                None => Line::from("↑↓ select  r restart  s stop  l follow logs  e error log  / filter  tab server  q quit").dark_gray(),
+            },
+        };
+
+        frame.render_widget(line, area);
+    }
+
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> io::Result<()> {
+        loop {
+            let log_refresh = if self.follow { FOLLOW_REFRESH } else { LIST_REFRESH };
+
+            if self.listed.is_none_or(|at| at.elapsed() >= LIST_REFRESH) {
+                self.refresh();
+            } else if self.logged.is_none_or(|at| at.elapsed() >= log_refresh) {
+                self.load_log();
+            }
+
+            terminal.draw(|frame| self.draw(frame))?;
+
+            if !event::poll(TICK)? {
+                continue;
+            }
+
+            if let Event::Key(key) = event::read()?
+                && key.kind == KeyEventKind::Press
+            {
+                let acted = matches!(self.mode, Mode::Confirm { .. });
+
+                if !self.key(key) {
+                    return Ok(());
+                }
+
+                // Restarting runs the process here, anything it printed is drawn over
+                if acted {
+                    terminal.clear()?;
+                }
+            }
+        }
+    }
+}
+
+extern "C" fn restore() { ratatui::restore() }
+
+pub fn run(server: &str) {
+    if !io::stdout().is_terminal() {
+        crashln!("{} opm ui needs an interactive terminal", *helpers::FAIL);
+    }
+
+    let mut app = App::new(server);
+    // Panics are restored by the hook ratatui installs, exits through crashln! by this one
+    let mut terminal = ratatui::init();
+    unsafe { libc::atexit(restore) };
+
+    let result = app.run(&mut terminal);
+    ratatui::restore();
+
+    if let Err(err) = result {
+        crashln!("{} Terminal error: {err}", *helpers::FAIL);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use std::io::Write;
+
+    fn process(id: usize, name: &str) -> ProcessItem {
+        ProcessItem {
+            pid: 0,
+            id,
+            cpu: string!("0.00%"),
+            mem: string!("0b"),
+            name: string!(name),
+            restarts: 0,
+            status: string!("online"),
+            uptime: string!("1s"),
+            watch_path: string!(""),
+            start_time: Utc::now(),
+            agent_id: None,
+            agent_name: None,
+            labels: Default::default(),
+            group: None,
+            url: None,
+        }
+    }
+
+    fn app(processes: Vec<ProcessItem>) -> App {
+        App {
+            servers: vec![string!("local")],
+            server: 0,
+            runner: Err(string!("offline")),
+            processes,
+            selected: None,
+            filter: string!(""),
+            mode: Mode::Browse,
+            follow: false,
+            errors: false,
+            log: vec![],
+            status: None,
+            listed: Some(Instant::now()),
+            logged: Some(Instant::now()),
+        }
+    }
+
+    fn key(code: KeyCode) -> KeyEvent { KeyEvent::new(code, KeyModifiers::NONE) }
+
+    #[test]
+    fn test_filter_and_selection() {
+        let mut app = app(vec![process(0, "api"), process(3, "worker"), process(7, "api-admin")]);
+
+        assert_eq!(app.selected_process().map(|process| process.id), Some(0));
+        app.select(1);
+        app.select(5);
+        assert_eq!(app.selected, Some(7));
+
+        for code in [KeyCode::Char('/'), KeyCode::Char('W'), KeyCode::Enter] {
+            assert!(app.key(key(code)));
+        }
+        assert_eq!(app.visible().iter().map(|process| process.id).collect::<Vec<_>>(), vec![3]);
+        // The selection is filtered out, the first match stands in for it
+        assert_eq!(app.selected_process().map(|process| process.id), Some(3));
+
+        assert!(app.key(key(KeyCode::Esc)));
+        assert_eq!(app.visible().len(), 3);
+        assert!(!app.key(key(KeyCode::Esc)));
+    }
+
+    #[test]
+    fn test_actions_need_confirmation() {
+        let mut app = app(vec![process(2, "api")]);
+
+        app.key(key(KeyCode::Char('s')));
+        assert_eq!(app.mode, Mode::Confirm { action: Action::Stop, id: 2, name: string!("api") });
+
+        app.key(key(KeyCode::Char('n')));
+        assert_eq!(app.mode, Mode::Browse);
+        assert_eq!(app.status, Some((false, string!("Cancelled stop of api"))));
+    }
+
+    #[test]
+    fn test_tail() {
+        let path = std::env::temp_dir().join(format!("opm-ui-tail-{}.log", std::process::id()));
+        let mut file = File::create(&path).unwrap();
+        (1..=2000).for_each(|line| writeln!(file, "line {line} {}", "x".repeat(200)).unwrap());
+
+        let lines = tail(path.to_str().unwrap(), 3);
+        assert_eq!(lines.len(), 3);
+        assert!(lines[2].starts_with("line 2000 "));
+        assert!(tail("/nonexistent/opm.log", 3).is_empty());
+
+        std::fs::remove_file(&path).ok();
+    }
+}
@@ -0,0 +1,212 @@
+//! `opm wait`, blocks a deploy script until processes are online, stopped or crashed
+
+use super::{Item, Items, connect};
+use chrono::Utc;
+use colored::Colorize;
+use macros_rs::{crashln, then};
+use opm::{
+    helpers,
+    process::{Process, Runner, is_pid_alive},
+};
+
+use std::{
+    collections::BTreeMap,
+    fmt,
+    str::FromStr,
+    thread::sleep,
+    time::{Duration, Instant},
+};
+
+/// How often the processes are read again
+const POLL: Duration = Duration::from_millis(250);
+
+/// Exit codes of `opm wait`
+pub const REACHED: i32 = 0;
+pub const TIMED_OUT: i32 = 1;
+pub const CONTRARY: i32 = 2;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum State {
+    Online,
+    Stopped,
+    Crashed,
+}
+
+impl FromStr for State {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim() {
+            "online" => Ok(State::Online),
+            "stopped" => Ok(State::Stopped),
+            "crashed" => Ok(State::Crashed),
+            "healthy" => Err(String::from("online already waits for the health checks of a process to pass, use online")),
+            other => Err(format!("unknown state '{other}', available: online,stopped,crashed")),
+        }
+    }
+}
+
+impl fmt::Display for State {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            State::Online => write!(f, "online"),
+            State::Stopped => write!(f, "stopped"),
+            State::Crashed => write!(f, "crashed"),
+        }
+    }
+}
+
+/// Where a process stands against the state waited for
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Outcome {
+    Waiting,
+    Reached,
+    /// In a state nothing moves it out of without a command, e.g. stopped while waiting for online
+    Contrary,
+}
+
+/// `status` is what `opm list` shows for `process`
+fn outcome(target: State, process: &Process, status: &str) -> Outcome {
+    // Exited for good: stopped by hand, a completed command, or crashed past its restarts
+    let settled = !process.running;
+
+    match (target, status) {
+        (State::Online, "online") | (State::Stopped, "stopped") | (State::Crashed, "crashed") => Outcome::Reached,
+        (State::Online, "stopped") | (State::Crashed, "stopped") => Outcome::Contrary,
+        (State::Online | State::Stopped, "crashed") if settled => Outcome::Contrary,
+        _ => Outcome::Waiting,
+    }
+}
+
+/// Exit code once the outcomes decide one, `any` is done with the first process that got there
+fn decide(outcomes: &[Outcome], any: bool) -> Option<i32> {
+    let count = |wanted: Outcome| outcomes.iter().filter(|outcome| **outcome == wanted).count();
+
+    match any {
+        false if count(Outcome::Reached) == outcomes.len() => Some(REACHED),
+        false if count(Outcome::Contrary) > 0 => Some(CONTRARY),
+        true if count(Outcome::Reached) > 0 => Some(REACHED),
+        true if count(Outcome::Contrary) == outcomes.len() => Some(CONTRARY),
+        _ => None,
+    }
+}
+
+/// Ids of the processes waited for, every one of them for `all`
+fn targets(runner: &Runner, items: &Items) -> Vec<usize> {
+    if items.is_all() {
+        return runner.list.keys().copied().collect();
+    }
+
+    items
+        .items
+        .iter()
+        .map(|item| match item {
+            Item::Id(id) if runner.list.contains_key(id) => *id,
+            Item::Id(id) => crashln!("{} Process ({id}) not found", *helpers::FAIL),
+            Item::Name(name) | Item::Pattern(name) | Item::Group(name) => match runner.list.iter().find(|(_, process)| process.name == *name) {
+                Some((id, _)) => *id,
+                None => crashln!("{} Process ({name}) not found", *helpers::FAIL),
+            },
+        })
+        .collect()
+}
+
+/// Status of a process as its daemon sees it, a remote one keeps `running` up to date for its pids
+pub(super) fn status(runner: &Runner, process: &Process) -> &'static str {
+    let alive = match runner.remote {
+        Some(_) => process.running,
+        None => is_pid_alive(process.pid),
+    };
+
+    process.status(alive, Utc::now())
+}
+
+/// Poll `items` until they reach `target`, returning the exit code of `opm wait`
+pub fn run(items: &Items, target: State, timeout: u64, any: bool, verbose: bool, server_name: &str) -> i32 {
+    let deadline = Instant::now() + Duration::from_secs(timeout);
+    let ids = targets(&connect(server_name), items);
+    let mut seen: BTreeMap<usize, &str> = BTreeMap::new();
+
+    if ids.is_empty() {
+        crashln!("{} No processes to wait for", *helpers::FAIL);
+    }
+
+    loop {
+        let runner = connect(server_name);
+        let outcomes: Vec<Outcome> = ids
+            .iter()
+            .map(|id| {
+                let Some(process) = runner.list.get(id) else {
+                    then!(verbose && seen.insert(*id, "removed") != Some("removed"), println!("{} ({id}) {}", *helpers::WARN, "removed".red()));
+                    return Outcome::Contrary;
+                };
+
+                let status = status(&runner, process);
+                then!(verbose && seen.insert(*id, status) != Some(status), println!("{} {} ({id}) {status}", *helpers::INFO, process.name));
+                outcome(target, process, status)
+            })
+            .collect();
+
+        if let Some(code) = decide(&outcomes, any) {
+            then!(verbose && code == CONTRARY, println!("{} Gave up waiting, nothing brings the processes {target} without a command", *helpers::FAIL));
+            return code;
+        }
+
+        if Instant::now() >= deadline {
+            then!(verbose, println!("{} Timed out after {timeout}s waiting for {target}", *helpers::FAIL));
+            return TIMED_OUT;
+        }
+
+        sleep(POLL);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use macros_rs::string;
+    use opm::process::Crash;
+    use std::path::PathBuf;
+
+    fn process(running: bool, crashed: bool) -> Process {
+        let crash = Crash { crashed, ..Default::default() };
+        Process { name: string!("api"), script: string!("node index.js"), path: PathBuf::from("/srv"), running, crash, ..Default::default() }
+    }
+
+    #[test]
+    fn test_states() {
+        assert_eq!("online".parse::<State>(), Ok(State::Online));
+        assert!("healthy".parse::<State>().unwrap_err().contains("health checks"));
+        assert!("up".parse::<State>().is_err());
+    }
+
+    #[test]
+    fn test_outcome() {
+        let (up, stopped, restarting, gave_up) = (process(true, false), process(false, false), process(true, true), process(false, true));
+
+        assert_eq!(outcome(State::Online, &up, "starting"), Outcome::Waiting);
+        assert_eq!(outcome(State::Online, &up, "online"), Outcome::Reached);
+        assert_eq!(outcome(State::Online, &stopped, "stopped"), Outcome::Contrary);
+        // The daemon still restarts it, until it gives up
+        assert_eq!(outcome(State::Online, &restarting, "crashed"), Outcome::Waiting);
+        assert_eq!(outcome(State::Online, &gave_up, "crashed"), Outcome::Contrary);
+
+        assert_eq!(outcome(State::Stopped, &up, "online"), Outcome::Waiting);
+        assert_eq!(outcome(State::Stopped, &gave_up, "crashed"), Outcome::Contrary);
+        assert_eq!(outcome(State::Crashed, &restarting, "crashed"), Outcome::Reached);
+        assert_eq!(outcome(State::Crashed, &stopped, "stopped"), Outcome::Contrary);
+    }
+
+    #[test]
+    fn test_all_and_any() {
+        use Outcome::*;
+
+        assert_eq!(decide(&[Reached, Reached], false), Some(REACHED));
+        assert_eq!(decide(&[Reached, Waiting], false), None);
+        assert_eq!(decide(&[Contrary, Waiting], false), Some(CONTRARY));
+
+        assert_eq!(decide(&[Reached, Contrary], true), Some(REACHED));
+        assert_eq!(decide(&[Waiting, Contrary], true), None);
+        assert_eq!(decide(&[Contrary, Contrary], true), Some(CONTRARY));
+    }
+}
@@ -1,29 +1,22 @@
 pub mod structs;
+pub mod token;
 
 use crate::{
     file::{self, Exists},
     helpers,
-    process::RemoteConfig,
+    process::{RemoteConfig, http},
 };
 
+use chrono::Utc;
 use colored::Colorize;
 use macros_rs::{crashln, fmtstr, string};
-use reqwest::blocking::Client;
-use reqwest::header::{HeaderMap, HeaderValue};
 use structs::prelude::*;
 
-use std::{fs::write, path::Path};
+use std::{collections::BTreeMap, fs::write, path::Path};
 
-pub fn from(address: &str, token: Option<&str>) -> Result<RemoteConfig, anyhow::Error> {
-    let client = Client::new();
-    let mut headers = HeaderMap::new();
-
-    if let Some(token) = token {
-        headers.insert(
-            "token",
-            HeaderValue::from_static(Box::leak(Box::from(token))),
-        );
-    }
+pub fn from(address: &str, token: Option<&str>, options: &ClientOptions) -> Result<RemoteConfig, anyhow::Error> {
+    let client = http::sync::build(options)?;
+    let headers = http::headers(token)?;
 
     let response = client
         .get(fmtstr!("{address}/daemon/config"))
@@ -52,11 +45,23 @@ pub fn read() -> Config {
                         args: vec![string!("-c")],
                         node: string!("node"),
                         log_path: format!("{path}/.opm/logs"),
+                        dump_path: None,
+                        dump_format: DumpFormat::Ron,
+                        remove_logs: None,
+                        logs_on_enospc: Default::default(),
+                        cpu_sample_ms: None,
+                        max_processes: None,
+                        log_timestamps: false,
+                        log_max_size: None,
+                        log_keep: None,
                     },
                     daemon: Daemon {
                         restarts: 10,
                         interval: 1000,
+                        restart_delay_base_ms: 500,
+                        restart_delay_max_ms: 60_000,
                         kind: string!("default"),
+                        autostart: true,
                         web: structs::Web {
                             ui: false,
                             api: false,
@@ -65,12 +70,26 @@ pub fn read() -> Config {
                             secure: Some(structs::Secure {
                                 enabled: false,
                                 token: secure_token,
+                                tokens: vec![],
                             }),
                             path: None,
+                            create_policy: None,
+                            session_ttl: None,
                         },
                         notifications: None,
+                        log_checkpoint: 30,
+                        metrics_interval: 60,
+                        snapshot_interval: 24 * 60 * 60,
+                        snapshot_keep: 7,
+                        watch_hash_threads: 2,
+                        retention: None,
+                        cores: structs::Cores::default(),
+                        self_limits: None,
+                        kill_timeout: None,
+                        env: BTreeMap::new(),
                     },
                     role: structs::Role::Standalone,
+                    cli: None,
                 };
 
                 let contents = match toml::to_string(&config) {
@@ -102,6 +121,7 @@ pub fn read() -> Config {
                 config.daemon.web.secure = Some(structs::Secure {
                     enabled: false,
                     token: secure_token,
+                    tokens: vec![],
                 });
                 needs_save = true;
                 log::info!("added secure API token to existing config");
@@ -118,25 +138,76 @@ pub fn read() -> Config {
     }
 }
 
-pub fn servers() -> Servers {
+/// Read an existing config without creating or patching it, errors are returned instead of exiting
+pub fn try_read() -> Result<Config, String> {
+    let Some(path) = home::home_dir() else {
+        return Err(string!("Impossible to get your home directory"));
+    };
+
+    let config_path = format!("{}/.opm/config.toml", path.display());
+    let contents = std::fs::read_to_string(&config_path).map_err(|err| format!("Cannot read {config_path}: {err}"))?;
+
+    toml::from_str(&contents).map_err(|err| format!("Cannot parse {config_path}: {err}"))
+}
+
+/// Where the remote servers used by `--server` and the `/remote` routes are kept
+pub fn servers_path() -> String {
     match home::home_dir() {
-        Some(path) => {
-            let path = path.display();
-            let config_path = format!("{path}/.opm/servers.toml");
+        Some(path) => format!("{}/.opm/servers.toml", path.display()),
+        None => crashln!("{} Impossible to get your home directory", *helpers::FAIL),
+    }
+}
 
-            if !Exists::check(&config_path).file() {
-                if let Err(err) = write(&config_path, "") {
-                    crashln!(
-                        "{} Error writing servers.\n{}",
-                        *helpers::FAIL,
-                        string!(err).white()
-                    )
-                }
-            }
+pub fn servers() -> Servers {
+    let config_path = servers_path();
+
+    if !Exists::check(&config_path).file()
+        && let Err(err) = write(&config_path, "")
+    {
+        crashln!(
+            "{} Error writing servers.\n{}",
+            *helpers::FAIL,
+            string!(err).white()
+        )
+    }
+
+    file::read(config_path)
+}
+
+/// Contact with a server is recorded at most this often
+pub const SEEN_INTERVAL: chrono::Duration = chrono::Duration::minutes(1);
+
+/// Record in servers.toml that a command reached `name` and what it reported about itself
+pub fn seen(name: &str, remote: &RemoteConfig) {
+    let mut servers = servers();
+    let Some(server) = servers.servers.as_mut().and_then(|servers| servers.get_mut(name)) else {
+        return;
+    };
 
-            file::read(config_path)
+    let next = Seen {
+        version: remote.version.clone(),
+        role: remote.role.clone(),
+        last_seen: Some(Utc::now()),
+    };
+
+    if server.seen.outdated(&next, SEEN_INTERVAL) {
+        server.seen = next;
+        if let Err(err) = servers.save() {
+            log::warn!("Cannot record contact with server {name}: {err}");
         }
-        None => crashln!("{} Impossible to get your home directory", *helpers::FAIL),
+    }
+}
+
+impl Servers {
+    pub fn save(&self) -> Result<(), String> {
+        let Some(path) = home::home_dir() else {
+            return Err(string!("Impossible to get your home directory"));
+        };
+
+        let config_path = format!("{}/.opm/servers.toml", path.display());
+        let contents = toml::to_string(self).map_err(|err| format!("Cannot encode servers: {err}"))?;
+
+        write(&config_path, contents).map_err(|err| format!("Cannot write {config_path}: {err}"))
     }
 }
 
@@ -177,8 +248,12 @@ impl Config {
         self
     }
 
+    /// Base path the api and web ui are served under, `/opm` for `opm/` and empty at the root
     pub fn get_path(&self) -> String {
-        self.daemon.web.path.clone().unwrap_or_else(|| string!("/"))
+        match self.daemon.web.path.as_deref().map(|path| path.trim_matches('/')) {
+            Some(path) if !path.is_empty() => format!("/{path}"),
+            _ => string!(""),
+        }
     }
 
     pub fn get_address(&self) -> rocket::Config {
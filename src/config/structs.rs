@@ -1,8 +1,9 @@
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 
 pub mod prelude {
-    pub use super::{Config, Daemon, Runner, Server, Servers, Secure, Web, Notifications, Role};
+    pub use super::{Cli, ClientOptions, Config, Daemon, DumpFormat, Limits, Retention, Runner, Seen, Server, Servers, Secure, Web, Notifications, Role};
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
@@ -28,6 +29,16 @@ pub struct Config {
     pub daemon: Daemon,
     #[serde(default)]
     pub role: Role,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cli: Option<Cli>,
+}
+
+/// Defaults for CLI output
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct Cli {
+    /// Columns of `opm list` when `--columns` is not given, e.g. `["id", "name", "status"]`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub list_columns: Option<Vec<String>>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -36,17 +47,170 @@ pub struct Runner {
     pub args: Vec<String>,
     pub node: String,
     pub log_path: String,
+    /// Location of the process dump (defaults to ~/.opm/process.dump)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dump_path: Option<String>,
+    #[serde(default)]
+    pub dump_format: DumpFormat,
+    /// Whether `opm remove` deletes the logs of a process without asking, unset prompts
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub remove_logs: Option<bool>,
+    /// What a start does when the log filesystem is full
+    #[serde(default)]
+    pub logs_on_enospc: LogsOnEnospc,
+    /// Window of a timed cpu sample in milliseconds, 20 to 1000 (defaults to 100)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cpu_sample_ms: Option<u64>,
+    /// Processes kept at most, starts past it are refused (defaults to 500)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_processes: Option<usize>,
+    /// Start new local processes under a supervisor that stamps every log line with the time it was written
+    #[serde(default)]
+    pub log_timestamps: bool,
+    /// Size process logs are rotated past, e.g. `10M`, never when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub log_max_size: Option<String>,
+    /// Rotated archives kept per log (defaults to 5)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub log_keep: Option<usize>,
+}
+
+/// Whether processes start without their logs while the log filesystem is full
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogsOnEnospc {
+    /// The start fails and the process is errored until there is space again
+    #[default]
+    Fail,
+    /// Output goes to /dev/null, for services that have to run while the disk is fixed
+    Devnull,
+}
+
+/// Format the process dump is written in, existing dumps are read in either format
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum DumpFormat {
+    /// Compact RON, the fastest to encode and parse
+    #[default]
+    Ron,
+    /// Pretty-printed JSON, larger and slower to save but readable and diffable
+    Json,
+}
+
+impl DumpFormat {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DumpFormat::Ron => "ron",
+            DumpFormat::Json => "json",
+        }
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct Daemon {
     pub restarts: u64,
     pub interval: u64,
+    /// Milliseconds before restarting a crashed process, doubled with every crash in a row, 0 restarts it right away
+    #[serde(default = "default_restart_delay_base_ms")]
+    pub restart_delay_base_ms: u64,
+    /// Longest wait in milliseconds between the restarts of a crashing process
+    #[serde(default = "default_restart_delay_max_ms")]
+    pub restart_delay_max_ms: u64,
     pub kind: String,
+    /// Start the daemon after ordinary commands when it is not running, `--no-daemon` skips it once
+    #[serde(default = "default_autostart")]
+    pub autostart: bool,
     #[serde(default = "default_web")]
     pub web: Web,
     #[serde(default)]
     pub notifications: Option<Notifications>,
+    /// Seconds between log offset checkpoints used by `opm logs --since/--until`, 0 disables them
+    #[serde(default = "default_log_checkpoint")]
+    pub log_checkpoint: u64,
+    /// Seconds between the per-process samples `opm report` is built from, 0 disables them
+    #[serde(default = "default_metrics_interval")]
+    pub metrics_interval: u64,
+    /// Seconds between the copies of the dump kept in `~/.opm/snapshots`, 0 disables them
+    #[serde(default = "default_snapshot_interval")]
+    pub snapshot_interval: u64,
+    /// Most recent dump snapshots kept, older ones are deleted after each new one
+    #[serde(default = "default_snapshot_keep")]
+    pub snapshot_keep: usize,
+    /// Watched paths hashed at the same time in the background, 0 hashes them in the daemon loop
+    #[serde(default = "default_watch_hash_threads")]
+    pub watch_hash_threads: usize,
+    /// Limits for the history files the daemon keeps, enforced by an hourly housekeeping pass
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retention: Option<Retention>,
+    /// Caps for the core files collected from processes with `collect_cores`
+    #[serde(default)]
+    pub cores: Cores,
+    /// What the daemon itself may use before it trims its caches
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub self_limits: Option<SelfLimits>,
+    /// Milliseconds a stopped process gets to exit before it and its children are killed (defaults to 5000)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kill_timeout: Option<u64>,
+    /// Variables set in the environment of the daemon when it starts, every process it spawns inherits them
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq)]
+pub struct SelfLimits {
+    /// Resident memory of the daemon alone, without the processes it manages, e.g. `200M`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_rss: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_cpu_percent: Option<f64>,
+    /// Restart the daemon when trimming did not bring it back under `max_rss`, processes keep running
+    #[serde(default)]
+    pub reexec: bool,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct Cores {
+    /// Most recent cores kept per process
+    #[serde(default = "default_core_count")]
+    pub max_count: usize,
+    /// Size all collected cores are kept under by deleting the oldest first, e.g. `2G`
+    #[serde(default = "default_core_size", skip_serializing_if = "Option::is_none")]
+    pub max_size: Option<String>,
+}
+
+impl Default for Cores {
+    fn default() -> Self {
+        Cores {
+            max_count: default_core_count(),
+            max_size: default_core_size(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq)]
+pub struct Retention {
+    #[serde(default)]
+    pub events: Limits,
+    #[serde(default)]
+    pub metrics: Limits,
+    /// Rotated process logs, the logs processes are writing to are never touched
+    #[serde(default)]
+    pub logs: Limits,
+    #[serde(default)]
+    pub audit: Limits,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq)]
+pub struct Limits {
+    /// Archived files older than this are deleted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_days: Option<u64>,
+    /// Size the whole class is kept under by deleting archived files oldest first, e.g. `500M`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_size: Option<String>,
+    /// Gzip archived files that are kept
+    #[serde(default)]
+    pub compress: bool,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -61,12 +225,62 @@ pub struct Web {
     pub port: u64,
     pub secure: Option<Secure>,
     pub path: Option<String>,
+    /// What `/process/create` may start, anything when not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub create_policy: Option<CreatePolicy>,
+    /// Seconds a WebUI login lasts (defaults to 12 hours)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_ttl: Option<u64>,
+}
+
+/// Limits on the processes API clients create, the CLI is not held to them
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct CreatePolicy {
+    /// Whether `/process/create` starts anything at all
+    #[serde(default = "default_allow_create")]
+    pub allow_create: bool,
+    /// Directories the working directory of a new process must be in, any when empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub paths: Vec<String>,
+    /// Regexes of which one must match the whole script, any script when empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub commands: Vec<String>,
+}
+
+impl Default for CreatePolicy {
+    fn default() -> Self {
+        CreatePolicy {
+            allow_create: true,
+            paths: vec![],
+            commands: vec![],
+        }
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct Secure {
     pub enabled: bool,
     pub token: String,
+    /// Further tokens of `opm daemon token add`, each limited to its scope
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tokens: Vec<ApiToken>,
+}
+
+/// Named API token, only the hash of the token itself is stored
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+pub struct ApiToken {
+    pub name: String,
+    /// blake3 of the token, see `token::hash`
+    pub hash: String,
+    /// Actions it may run (restart, stop, logs, ...), any when empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub actions: Vec<String>,
+    /// Name patterns like `app-*` and `group:NAME` of the processes it reaches, every process when empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub processes: Vec<String>,
+    /// Only read processes and their logs
+    #[serde(default)]
+    pub read_only: bool,
 }
 
 pub fn default_web() -> Web {
@@ -77,9 +291,55 @@ pub fn default_web() -> Web {
         port: 9876,
         secure: None,
         path: None,
+        create_policy: None,
+        session_ttl: None,
     }
 }
 
+fn default_autostart() -> bool {
+    true
+}
+
+fn default_allow_create() -> bool {
+    true
+}
+
+fn default_restart_delay_base_ms() -> u64 {
+    500
+}
+
+fn default_restart_delay_max_ms() -> u64 {
+    60_000
+}
+
+fn default_log_checkpoint() -> u64 {
+    30
+}
+
+fn default_metrics_interval() -> u64 {
+    60
+}
+
+fn default_snapshot_interval() -> u64 {
+    24 * 60 * 60
+}
+
+fn default_snapshot_keep() -> usize {
+    7
+}
+
+fn default_watch_hash_threads() -> usize {
+    2
+}
+
+fn default_core_count() -> usize {
+    3
+}
+
+fn default_core_size() -> Option<String> {
+    Some("2G".to_string())
+}
+
 fn default_address() -> String {
     "127.0.0.1".to_string()
 }
@@ -88,7 +348,7 @@ fn default_port() -> u64 {
     9876
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Servers {
     pub servers: Option<BTreeMap<String, Server>>,
 }
@@ -97,17 +357,59 @@ pub struct Servers {
 pub struct Server {
     pub address: String,
     pub token: Option<String>,
+    #[serde(flatten)]
+    pub client: ClientOptions,
+    #[serde(flatten)]
+    pub seen: Seen,
 }
 
 impl Server {
     pub fn get(&self) -> Self {
         Self {
-            token: self.token.clone(),
             address: self.address.trim_end_matches('/').to_string(),
+            ..self.clone()
         }
     }
 }
 
+/// What a server reported about itself the last time a command reached it
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub struct Seen {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub role: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_seen: Option<DateTime<Utc>>,
+}
+
+impl Seen {
+    /// Whether `next` is worth writing over this, a successful command is only recorded
+    /// once `interval` passed so remote commands do not rewrite servers.toml every time
+    pub fn outdated(&self, next: &Seen, interval: chrono::Duration) -> bool {
+        match (self.last_seen, next.last_seen) {
+            _ if (&self.version, &self.role) != (&next.version, &next.role) => true,
+            (Some(last), Some(now)) => now - last >= interval,
+            (None, Some(_)) => true,
+            _ => false,
+        }
+    }
+}
+
+/// HTTP client settings for reaching a remote server through a proxy or private CA
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct ClientOptions {
+    /// Explicit proxy URL (e.g. http://proxy.corp:3128)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<String>,
+    /// Additional root certificate(s) in PEM format
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ca_cert: Option<String>,
+    /// Skip TLS certificate verification entirely
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub insecure: bool,
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Notifications {
     #[serde(default)]
@@ -130,4 +432,6 @@ pub struct NotificationEvents {
     pub process_crash: bool,
     #[serde(default)]
     pub process_restart: bool,
+    #[serde(default)]
+    pub process_alert: bool,
 }
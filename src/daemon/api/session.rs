@@ -0,0 +1,111 @@
+//! WebUI logins, the API token exchanged for a random id in an HttpOnly cookie so the browser never keeps the token.
+//! Sessions only live in memory, restarting the daemon logs everyone out.
+
+use opm::config::structs::Web;
+use std::{
+    collections::HashMap,
+    sync::{Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+use uuid::Uuid;
+
+pub const COOKIE: &str = "opm_session";
+
+/// Sessions kept at once, a login past it ends the one closest to expiring
+pub const MAX_SESSIONS: usize = 256;
+
+/// How long a login lasts without `daemon.web.session_ttl`
+pub const DEFAULT_TTL: Duration = Duration::from_secs(12 * 60 * 60);
+
+/// How long a refused login waits before it is answered, so tokens cannot be guessed quickly
+pub const REFUSED_DELAY: Duration = Duration::from_millis(500);
+
+/// Ids of the logged in browsers and when each one expires
+#[derive(Default)]
+pub struct Sessions(Mutex<HashMap<String, Instant>>);
+
+impl Sessions {
+    /// Start a session lasting `ttl` and return its id
+    pub fn create(&self, ttl: Duration, now: Instant) -> String {
+        let id = format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple());
+        let mut sessions = self.lock();
+        sessions.retain(|_, expires| *expires > now);
+
+        if sessions.len() >= MAX_SESSIONS
+            && let Some(first) = sessions.iter().min_by_key(|(_, expires)| **expires).map(|(id, _)| id.clone())
+        {
+            sessions.remove(&first);
+        }
+
+        sessions.insert(id.clone(), now + ttl);
+        id
+    }
+
+    /// Whether `id` is a session that has not expired, an expired one is dropped
+    pub fn valid(&self, id: &str, now: Instant) -> bool {
+        let mut sessions = self.lock();
+        match sessions.get(id) {
+            Some(expires) if *expires > now => true,
+            Some(_) => {
+                sessions.remove(id);
+                false
+            }
+            None => false,
+        }
+    }
+
+    /// End the session, false when there was none
+    pub fn remove(&self, id: &str) -> bool { self.lock().remove(id).is_some() }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, Instant>> { self.0.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) }
+}
+
+pub fn ttl(web: &Web) -> Duration { web.session_ttl.map_or(DEFAULT_TTL, Duration::from_secs) }
+
+/// Whether `given` is `token`, comparing their hashes in constant time so the answer takes as long however much of it matched
+pub fn token_matches(given: &str, token: &str) -> bool { blake3::hash(given.as_bytes()) == blake3::hash(token.as_bytes()) }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sessions_expire() {
+        let sessions = Sessions::default();
+        let now = Instant::now();
+        let id = sessions.create(Duration::from_secs(60), now);
+
+        assert_eq!(id.len(), 64);
+        assert!(sessions.valid(&id, now + Duration::from_secs(59)));
+        assert!(!sessions.valid(&id, now + Duration::from_secs(60)));
+        // Dropped once found expired
+        assert!(!sessions.remove(&id));
+        assert!(!sessions.valid("unknown", now));
+    }
+
+    #[test]
+    fn test_token_matches() {
+        assert!(token_matches("secret", "secret"));
+        assert!(!token_matches("secreT", "secret"));
+        assert!(!token_matches("secret2", "secret"));
+        assert!(!token_matches("", "secret"));
+    }
+
+    #[test]
+    fn test_sessions_are_bounded() {
+        let sessions = Sessions::default();
+        let now = Instant::now();
+
+        let first = sessions.create(Duration::from_secs(10), now);
+        let ids: Vec<String> = (1..MAX_SESSIONS).map(|_| sessions.create(Duration::from_secs(60), now)).collect();
+        assert!(sessions.valid(&first, now));
+
+        let last = sessions.create(Duration::from_secs(60), now);
+        assert_eq!(sessions.lock().len(), MAX_SESSIONS);
+        assert!(!sessions.valid(&first, now), "The session closest to expiring makes room");
+        assert!(sessions.valid(&last, now) && ids.iter().all(|id| sessions.valid(id, now)));
+
+        assert!(sessions.remove(&last));
+        assert!(!sessions.valid(&last, now));
+    }
+}
@@ -0,0 +1,85 @@
+//! The environment the daemon runs with, `daemon.env` and `opm daemon restore --daemon-env` set on
+//! top of what it inherited. Applied before it forks, so every process it spawns starts from it.
+
+use opm::process::environment;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, env};
+use utoipa::ToSchema;
+
+/// Differ between any two shells, left out of the hash so it only changes with what processes see
+const VOLATILE: [&str; 4] = ["_", "PWD", "OLDPWD", "SHLVL"];
+
+/// What a process spawned by this environment starts from, to compare the daemon with a shell
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, PartialEq)]
+pub struct Summary {
+    #[schema(example = "/usr/local/bin:/usr/bin:/bin")]
+    pub path: Option<String>,
+    /// Blake3 of the sorted variables, without `_`, `PWD`, `OLDPWD` and `SHLVL`
+    #[schema(example = "5d3b0e4c1a9f")]
+    pub hash: String,
+    #[schema(example = 24)]
+    pub vars: usize,
+}
+
+impl Summary {
+    pub fn current() -> Summary { Summary::of(env::vars().collect()) }
+
+    fn of(vars: BTreeMap<String, String>) -> Summary {
+        let kept: Vec<String> = vars.iter().filter(|(key, _)| !VOLATILE.contains(&key.as_str())).map(|(key, value)| format!("{key}={value}")).collect();
+
+        Summary {
+            path: vars.get("PATH").cloned(),
+            hash: blake3::hash(kept.join("\0").as_bytes()).to_hex()[..12].to_string(),
+            vars: kept.len(),
+        }
+    }
+}
+
+/// `configured` with `overrides` on top, every name checked before any is set
+pub fn merge(configured: &BTreeMap<String, String>, overrides: &[(String, String)]) -> Result<BTreeMap<String, String>, String> {
+    let mut merged = configured.clone();
+    merged.extend(overrides.iter().cloned());
+
+    for (key, value) in &merged {
+        environment::parse(&format!("{key}={value}")).map_err(|err| format!("daemon.env: {err}"))?;
+    }
+
+    Ok(merged)
+}
+
+/// Set the variables in the environment of this process, returning their names
+pub fn apply(vars: &BTreeMap<String, String>) -> Vec<String> {
+    for (key, value) in vars {
+        // SAFETY: called by `daemon::start` before it forks or spawns any thread of the daemon
+        unsafe { env::set_var(key, value) };
+    }
+
+    vars.keys().cloned().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use macros_rs::string;
+
+    #[test]
+    fn test_merge_and_summary() {
+        let configured = BTreeMap::from([(string!("HTTPS_PROXY"), string!("http://proxy:3128")), (string!("LANG"), string!("C.UTF-8"))]);
+        let merged = merge(&configured, &[(string!("LANG"), string!("en_US.UTF-8"))]).unwrap();
+        assert_eq!(merged["LANG"], "en_US.UTF-8");
+        assert_eq!(merged["HTTPS_PROXY"], "http://proxy:3128");
+
+        let err = merge(&BTreeMap::from([(string!("1BAD"), string!("x"))]), &[]).unwrap_err();
+        assert!(err.starts_with("daemon.env:"), "{err}");
+
+        // Volatile variables do not change the hash, anything else does
+        let mut vars = BTreeMap::from([(string!("PATH"), string!("/usr/bin")), (string!("PWD"), string!("/root"))]);
+        let summary = Summary::of(vars.clone());
+        assert_eq!((summary.path.as_deref(), summary.vars, summary.hash.len()), (Some("/usr/bin"), 1, 12));
+
+        vars.insert(string!("PWD"), string!("/tmp"));
+        assert_eq!(Summary::of(vars.clone()), summary);
+        vars.insert(string!("PATH"), string!("/opt/bin:/usr/bin"));
+        assert_ne!(Summary::of(vars).hash, summary.hash);
+    }
+}
@@ -0,0 +1,181 @@
+//! The daemon's own memory and cpu held against `daemon.self_limits`.
+//! Over `max_rss` it trims what it only keeps for speed or display, and with `reexec` set restarts
+//! once trimming did not help, the processes it manages carry on and are picked up again from the dump.
+
+use opm::{config::structs::SelfLimits, helpers};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use std::{
+    sync::{
+        Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+/// How long the daemon stays over `max_rss` after a trim before it trims again, or restarts with `reexec`
+pub const TRIM_EVERY: Duration = Duration::from_secs(60);
+
+static RSS: AtomicU64 = AtomicU64::new(0);
+static CPU: AtomicU64 = AtomicU64::new(0);
+static GUARD: Mutex<Guard> = Mutex::new(Guard { memory: false, cpu: false, trimmed: None });
+
+/// What the daemon uses against what it may use, `None` where no limit is set
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, Default, PartialEq)]
+pub struct SelfUsage {
+    /// Resident memory of the daemon alone, without the processes it manages
+    #[schema(example = 18874368)]
+    pub rss: u64,
+    #[schema(example = 0.4)]
+    pub cpu_percent: f64,
+    #[schema(example = 209715200)]
+    pub max_rss: Option<u64>,
+    #[schema(example = 20.0)]
+    pub max_cpu_percent: Option<f64>,
+}
+
+impl SelfUsage {
+    /// A broken `max_rss` counts as none, `opm daemon health` shows it the way it was written
+    pub fn new(rss: u64, cpu_percent: f64, limits: &SelfLimits) -> SelfUsage {
+        SelfUsage {
+            rss,
+            cpu_percent,
+            max_rss: limits.max_rss.as_deref().map(helpers::parse_memory).and_then(Result::ok),
+            max_cpu_percent: limits.max_cpu_percent,
+        }
+    }
+
+    pub fn memory_over(&self) -> bool { self.max_rss.is_some_and(|max| self.rss > max) }
+
+    pub fn cpu_over(&self) -> bool { self.max_cpu_percent.is_some_and(|max| self.cpu_percent > max) }
+
+    /// `18mb of 200mb, 0.40% of 20%` for the health table
+    pub fn describe(&self) -> String {
+        let memory = match self.max_rss {
+            Some(max) => format!("{} of {}", helpers::format_memory(self.rss), helpers::format_memory(max)),
+            None => helpers::format_memory(self.rss),
+        };
+
+        match self.max_cpu_percent {
+            Some(max) => format!("{memory}, {:.2}% of {max}%", self.cpu_percent),
+            None => format!("{memory}, {:.2}%", self.cpu_percent),
+        }
+    }
+}
+
+/// What the monitoring loop does about one sample
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Step {
+    /// Passed `max_rss`, logged once until it is back under
+    MemoryOver,
+    /// Passed `max_cpu_percent`, logged once until it is back under
+    CpuOver,
+    Trim,
+    Reexec,
+}
+
+/// Whether the last sample was over each limit and when the caches were last trimmed
+#[derive(Debug, Default)]
+pub struct Guard {
+    memory: bool,
+    cpu: bool,
+    trimmed: Option<Instant>,
+}
+
+impl Guard {
+    pub fn check(&mut self, usage: &SelfUsage, reexec: bool, now: Instant) -> Vec<Step> {
+        let mut steps = vec![];
+
+        if usage.cpu_over() && !self.cpu {
+            steps.push(Step::CpuOver);
+        }
+        self.cpu = usage.cpu_over();
+
+        if !usage.memory_over() {
+            self.memory = false;
+            self.trimmed = None;
+            return steps;
+        }
+
+        if !self.memory {
+            self.memory = true;
+            steps.push(Step::MemoryOver);
+        }
+
+        match self.trimmed {
+            Some(trimmed) if now.duration_since(trimmed) < TRIM_EVERY => {}
+            Some(_) if reexec => steps.push(Step::Reexec),
+            _ => {
+                self.trimmed = Some(now);
+                steps.push(Step::Trim);
+            }
+        }
+
+        steps
+    }
+}
+
+/// Keep `usage` for the API and the socket, then check it against the limits
+pub fn record(usage: &SelfUsage, reexec: bool) -> Vec<Step> {
+    RSS.store(usage.rss, Ordering::Release);
+    CPU.store(usage.cpu_percent.to_bits(), Ordering::Release);
+
+    GUARD.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).check(usage, reexec, Instant::now())
+}
+
+/// The last sample of the monitoring loop against the limits in effect now
+pub fn last(limits: &SelfLimits) -> SelfUsage { SelfUsage::new(RSS.load(Ordering::Acquire), f64::from_bits(CPU.load(Ordering::Acquire)), limits) }
+
+/// Whether the monitoring loop has to sample the daemon at all
+pub fn enabled(limits: &SelfLimits) -> bool { limits.max_rss.is_some() || limits.max_cpu_percent.is_some() }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tiny(reexec: bool) -> SelfLimits {
+        SelfLimits {
+            max_rss: Some(String::from("1K")),
+            max_cpu_percent: Some(0.01),
+            reexec,
+        }
+    }
+
+    #[test]
+    fn test_usage() {
+        let usage = SelfUsage::new(20 * 1024 * 1024, 0.5, &SelfLimits { max_rss: Some(String::from("200M")), max_cpu_percent: Some(20.0), reexec: false });
+        assert_eq!(usage.max_rss, Some(200 * 1024 * 1024));
+        assert!(!usage.memory_over() && !usage.cpu_over());
+        assert_eq!(usage.describe(), "20mb of 200mb, 0.50% of 20%");
+
+        let usage = SelfUsage::new(2048, 30.0, &SelfLimits { max_rss: Some(String::from("lots")), ..SelfLimits::default() });
+        assert_eq!(usage.max_rss, None);
+        assert!(!usage.memory_over() && !usage.cpu_over());
+        assert_eq!(usage.describe(), "2kb, 30.00%");
+    }
+
+    #[test]
+    fn test_tiny_limits_trim_then_wait() {
+        let usage = SelfUsage::new(4096, 1.0, &tiny(false));
+        let (mut guard, start) = (Guard::default(), Instant::now());
+
+        assert_eq!(guard.check(&usage, false, start), vec![Step::CpuOver, Step::MemoryOver, Step::Trim]);
+        assert!(guard.check(&usage, false, start + Duration::from_secs(1)).is_empty());
+        assert_eq!(guard.check(&usage, false, start + TRIM_EVERY), vec![Step::Trim]);
+
+        // Back under both, the next breach is logged again
+        assert!(guard.check(&SelfUsage::new(512, 0.0, &tiny(false)), false, start + TRIM_EVERY * 2).is_empty());
+        assert_eq!(guard.check(&usage, false, start + TRIM_EVERY * 3), vec![Step::CpuOver, Step::MemoryOver, Step::Trim]);
+    }
+
+    #[test]
+    fn test_reexec_only_after_a_trim_did_not_help() {
+        let usage = SelfUsage::new(4096, 0.0, &tiny(true));
+        let (mut guard, start) = (Guard::default(), Instant::now());
+
+        assert_eq!(guard.check(&usage, true, start), vec![Step::MemoryOver, Step::Trim]);
+        assert!(guard.check(&usage, true, start + Duration::from_secs(30)).is_empty());
+        assert_eq!(guard.check(&usage, true, start + TRIM_EVERY), vec![Step::Reexec]);
+    }
+}
@@ -0,0 +1,343 @@
+//! Line-delimited JSON commands on `~/.opm/daemon.sock`, so local tooling can reach the
+//! running daemon without enabling the HTTP API. Access is limited by the file mode of the socket.
+
+use super::api::{self, Failure};
+use chrono::Utc;
+use global_placeholders::global;
+use macros_rs::ternary;
+use opm::{
+    audit,
+    process::{Runner, counters, labels},
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+use std::{
+    fs,
+    io::{self, BufRead, BufReader, Write},
+    os::unix::{
+        fs::PermissionsExt,
+        net::{UnixListener, UnixStream},
+    },
+    path::{Path, PathBuf},
+    process, thread,
+    time::Duration,
+};
+
+/// Protocol version, sent by clients in their `hello` and checked by the daemon
+pub const VERSION: u32 = 1;
+
+/// How long a client waits for an answer, an action such as restart can take a few seconds
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// One line sent to the daemon, `hello` has to come first on every connection
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Request {
+    Hello {
+        version: u32,
+    },
+    /// Processes as `GET /list` returns them, filtered by `key=value` labels
+    List {
+        #[serde(default)]
+        labels: Vec<String>,
+    },
+    /// A process as `GET /process/{id}/info` returns it
+    Info {
+        id: usize,
+    },
+    /// The methods of `POST /process/{id}/action`
+    Action {
+        id: usize,
+        method: String,
+        #[serde(default)]
+        purge_logs: bool,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        signal: Option<String>,
+    },
+    Save,
+    /// The daemon part of `GET /daemon/metrics`
+    Health,
+    /// Actions waiting for the one still running on a process
+    Queue {
+        id: usize,
+    },
+    /// Internal counters of the daemon, started over from 0 after answering when `reset`
+    Stats {
+        #[serde(default)]
+        reset: bool,
+    },
+}
+
+/// One line answered by the daemon, with `data` when `ok` and `error` otherwise
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Response {
+    pub ok: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl Response {
+    fn ok(data: Value) -> Self { Response { ok: true, data: Some(data), error: None } }
+
+    fn error(error: impl Into<String>) -> Self { Response { ok: false, data: None, error: Some(error.into()) } }
+}
+
+pub fn path() -> PathBuf { PathBuf::from(global!("opm.socket")) }
+
+/// Listen on `path` in the background, replacing a socket a previous daemon left behind
+pub fn listen(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+        _ => {}
+    }
+
+    let listener = UnixListener::bind(path)?;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            thread::spawn(move || serve(stream));
+        }
+    });
+
+    Ok(())
+}
+
+/// Answer the requests of one connection until the client hangs up or fails the handshake
+fn serve(stream: UnixStream) {
+    let Ok(reader) = stream.try_clone() else { return };
+    let mut writer = stream;
+    let mut greeted = false;
+
+    for line in BufReader::new(reader).lines() {
+        let Ok(line) = line else { break };
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let (response, close) = match serde_json::from_str::<Request>(&line) {
+            Err(err) => (Response::error(format!("Invalid request: {err}")), false),
+            Ok(request) if greeted => (handle(request), false),
+            Ok(Request::Hello { version }) if version == VERSION => {
+                greeted = true;
+                (Response::ok(json!({ "version": VERSION, "pid": process::id() })), false)
+            }
+            Ok(Request::Hello { version }) => (Response::error(format!("Unsupported protocol version {version}, the daemon speaks {VERSION}")), true),
+            Ok(_) => (Response::error("Send hello with the protocol version first"), true),
+        };
+
+        let Ok(line) = serde_json::to_string(&response) else { break };
+        if writeln!(writer, "{line}").is_err() || close {
+            break;
+        }
+    }
+}
+
+fn handle(request: Request) -> Response {
+    match request {
+        Request::Hello { .. } => Response::error("Already greeted"),
+        Request::List { labels } => {
+            let filters = match labels.iter().map(|pair| labels::parse(pair)).collect::<Result<Vec<_>, _>>() {
+                Ok(filters) => filters,
+                Err(err) => return Response::error(err),
+            };
+
+            let mut processes = Runner::new().fetch();
+            processes.retain(|process| labels::matches(&process.labels, &filters));
+            reply(&processes)
+        }
+        Request::Info { id } => {
+            let runner = Runner::new();
+            match runner.exists(id) {
+                true => reply(&runner.get(id).fetch()),
+                false => Response::error("Process was not found"),
+            }
+        }
+        Request::Action { id, method, purge_logs, signal } => {
+            let signal = match signal.as_deref().map(opm::process::signal::parse).transpose() {
+                Ok(signal) => signal,
+                Err(err) => return Response::error(err),
+            };
+
+            let runner = Runner::new();
+            let Some(target) = runner.info(id).map(|process| process.name.clone()) else {
+                return Response::error("Process was not found");
+            };
+
+            let result = api::submit_action(runner, id, &method, purge_logs, signal).map_err(|err| err.message());
+            let entry = audit::Entry::new(audit::action(&method), &target, audit::local_actor(), audit::Via::Ipc);
+            audit::record(match &result {
+                Ok(_) => entry,
+                Err(err) => entry.failed(Some(err.clone())),
+            });
+
+            match result {
+                Ok(response) => reply(&response),
+                Err(err) => Response::error(err),
+            }
+        }
+        Request::Save => {
+            Runner::new().save();
+            Response::ok(json!({ "done": true, "action": "save" }))
+        }
+        Request::Health => reply(&api::daemon_metrics().daemon),
+        Request::Queue { id } => reply(&super::queue::lock().pending(id)),
+        Request::Stats { reset } => {
+            let started = super::pid::uptime().unwrap_or_else(|_| Utc::now());
+            reply(&ternary!(reset, counters::reset(started), counters::snapshot(started)))
+        }
+    }
+}
+
+fn reply(data: &impl Serialize) -> Response {
+    match serde_json::to_value(data) {
+        Ok(data) => Response::ok(data),
+        Err(err) => Response::error(format!("Cannot encode the answer: {err}")),
+    }
+}
+
+/// A connection to the daemon that passed the handshake
+pub struct Client {
+    reader: BufReader<UnixStream>,
+    writer: UnixStream,
+}
+
+impl Client {
+    /// Connect and greet the daemon listening on `path`, an error when there is none or it speaks another version
+    pub fn connect(path: &Path) -> Result<Client, String> {
+        let stream = UnixStream::connect(path).map_err(|err| format!("Cannot connect to {}: {err}", path.display()))?;
+        stream.set_read_timeout(Some(TIMEOUT)).map_err(|err| err.to_string())?;
+
+        let reader = BufReader::new(stream.try_clone().map_err(|err| err.to_string())?);
+        let mut client = Client { reader, writer: stream };
+
+        client.request(&Request::Hello { version: VERSION })?;
+        Ok(client)
+    }
+
+    /// Send one request and wait for its answer
+    pub fn request(&mut self, request: &Request) -> Result<Value, String> {
+        let line = serde_json::to_string(request).map_err(|err| err.to_string())?;
+        writeln!(self.writer, "{line}").map_err(|err| format!("Cannot send to the daemon: {err}"))?;
+
+        let mut answer = String::new();
+        match self.reader.read_line(&mut answer) {
+            Ok(0) => return Err(String::from("The daemon closed the connection")),
+            Err(err) => return Err(format!("No answer from the daemon: {err}")),
+            Ok(_) => {}
+        }
+
+        let response: Response = serde_json::from_str(&answer).map_err(|err| format!("Invalid answer from the daemon: {err}"))?;
+        match response.ok {
+            true => Ok(response.data.unwrap_or(Value::Null)),
+            false => Err(response.error.unwrap_or_else(|| String::from("Request failed"))),
+        }
+    }
+}
+
+/// The running daemon, when it answers on its socket
+pub fn connect() -> Option<Client> {
+    match super::pid::alive() {
+        true => Client::connect(&path()).ok(),
+        false => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn socket(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("opm-ipc-{name}-{}.sock", process::id()));
+        listen(&path).unwrap();
+        path
+    }
+
+    /// Send raw lines and read one answer per line
+    fn exchange(path: &Path, lines: &[&str]) -> Vec<Response> {
+        let stream = UnixStream::connect(path).unwrap();
+        stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut writer = stream;
+
+        let mut answers = vec![];
+        for line in lines {
+            // A closed connection shows up as a failed write or read, depending on timing
+            let mut answer = String::new();
+            if writeln!(writer, "{line}").is_err() || reader.read_line(&mut answer).unwrap_or(0) == 0 {
+                break;
+            }
+            answers.push(serde_json::from_str(&answer).unwrap());
+        }
+        answers
+    }
+
+    #[test]
+    fn test_handshake() {
+        let path = socket("handshake");
+
+        let answers = exchange(&path, &[r#"{"type":"hello","version":1}"#, "not json", r#"{"type":"bogus"}"#]);
+        assert_eq!(answers[0].data.as_ref().unwrap()["version"], VERSION);
+        assert_eq!(answers[0].data.as_ref().unwrap()["pid"], process::id());
+        // Bad lines are answered with an error and leave the connection open
+        assert!(answers[1].error.as_ref().unwrap().starts_with("Invalid request"));
+        assert!(!answers[2].ok);
+
+        fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_handshake_required() {
+        let path = socket("greeting");
+
+        // Requests before the hello and a version the daemon does not speak close the connection
+        let answers = exchange(&path, &[r#"{"type":"save"}"#, r#"{"type":"hello","version":1}"#]);
+        assert_eq!(answers.len(), 1);
+        assert_eq!(answers[0].error.as_deref(), Some("Send hello with the protocol version first"));
+
+        let answers = exchange(&path, &[r#"{"type":"hello","version":99}"#, r#"{"type":"hello","version":1}"#]);
+        assert_eq!(answers.len(), 1);
+        assert_eq!(answers[0].error.as_deref(), Some("Unsupported protocol version 99, the daemon speaks 1"));
+
+        fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_client() {
+        let path = socket("client");
+        let mut client = Client::connect(&path).unwrap();
+
+        let err = client.request(&Request::List { labels: vec![String::from("no-equals-sign")] }).unwrap_err();
+        assert!(err.contains("no-equals-sign"), "{err}");
+        assert_eq!(client.request(&Request::Hello { version: VERSION }), Err(String::from("Already greeted")));
+
+        fs::remove_file(&path).ok();
+        assert!(Client::connect(&path).is_err());
+    }
+
+    #[test]
+    fn test_listen_replaces_stale_socket() {
+        let path = socket("stale");
+        let first = fs::metadata(&path).unwrap();
+        assert_eq!(first.permissions().mode() & 0o777, 0o600);
+
+        listen(&path).unwrap();
+        assert!(Client::connect(&path).is_ok());
+
+        fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_request_format() {
+        let request = Request::Action { id: 3, method: String::from("restart"), purge_logs: false, signal: None };
+        assert_eq!(serde_json::to_string(&request).unwrap(), r#"{"type":"action","id":3,"method":"restart","purge_logs":false}"#);
+        let request = Request::Action { id: 3, method: String::from("stop"), purge_logs: false, signal: Some(String::from("SIGINT")) };
+        assert_eq!(serde_json::to_string(&request).unwrap(), r#"{"type":"action","id":3,"method":"stop","purge_logs":false,"signal":"SIGINT"}"#);
+        assert_eq!(serde_json::from_str::<Request>(r#"{"type":"list"}"#).unwrap(), Request::List { labels: vec![] });
+        assert_eq!(serde_json::from_str::<Request>(r#"{"type":"stats"}"#).unwrap(), Request::Stats { reset: false });
+    }
+}
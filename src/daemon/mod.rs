@@ -1,26 +1,50 @@
 #[macro_use]
 mod log;
 mod api;
+pub mod env;
 mod fork;
-
-use api::{DAEMON_CPU_PERCENTAGE, DAEMON_MEM_USAGE, DAEMON_START_TIME};
+mod guard;
+pub mod ipc;
+mod pid1;
+mod queue;
+mod reload;
+mod schedule;
+mod service;
+pub mod token;
+mod watch;
+
+use crate::webui::check;
+use api::{DAEMON_CPU_PERCENTAGE, DAEMON_MEM_USAGE, DAEMON_SELF_CPU, DAEMON_SELF_MAX_CPU, DAEMON_SELF_MAX_RSS, DAEMON_SELF_RSS, DAEMON_START_TIME};
 use chrono::{DateTime, Utc};
 use colored::Colorize;
 use fork::{Fork, daemon};
+use schedule::Schedule;
 use global_placeholders::global;
-use macros_rs::{crashln, str, string, ternary};
+use macros_rs::{crashln, str, string, ternary, then};
 #[cfg(any(target_os = "linux", target_os = "macos"))]
-use opm::process::{MemoryInfo, unix::NativeProcess as Process};
+use opm::process::{MemoryInfo, unix::{self, NativeProcess as Process}};
 use serde::Serialize;
 use serde_json::json;
 use std::panic;
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::{process, thread::sleep, time::Duration};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Mutex, atomic::{AtomicBool, Ordering}};
+use std::{
+    path::Path,
+    process,
+    thread::{self, sleep},
+    time::{Duration, Instant},
+};
 
+use once_cell::sync::Lazy;
 use opm::{
-    config,
+    alert,
+    config::{self, structs::{Cores, SelfLimits}},
+    events::{self, Event, Kind},
     helpers::{self, ColoredString},
-    process::{Runner, get_process_cpu_usage_with_children_from_process, hash, id::Id},
+    notice,
+    notifications::{self, NotificationEvent, NotificationManager},
+    process::{Runner, WatchChange, alerts, checkpoint, cores, counters, dump, health, logdir, lograte, logspace, memlimit, get_process_cpu_usage_timed, id::Id, labels, max_processes, near_max_processes, pending, rotate, snapshots, stats},
+    storage,
 };
 
 use tabled::{
@@ -33,16 +57,66 @@ use tabled::{
     },
 };
 
-// Grace period in seconds to wait after process start before checking for crashes
-// This prevents false crash detection when shell processes haven't spawned children yet
-// Reduced to 1 second to allow faster detection of immediately-crashing processes
-const STARTUP_GRACE_PERIOD_SECS: i64 = 1;
+const HOUSEKEEPING_INTERVAL: Duration = Duration::from_secs(60 * 60);
+/// How often a disabled `daemon.metrics_interval` is checked again
+const METRICS_IDLE: Duration = Duration::from_secs(60);
+/// How often the daemon checks whether a dump snapshot is due
+const SNAPSHOT_CHECK: Duration = Duration::from_secs(60);
+/// How often process logs are checked against `runner.log_max_size`
+const LOG_ROTATION_CHECK: Duration = Duration::from_secs(10);
+/// More zombie children than this means a managed process does not wait for its children
+const ZOMBIE_WARNING: usize = 5;
 
 static ENABLE_API: AtomicBool = AtomicBool::new(false);
 static ENABLE_WEBUI: AtomicBool = AtomicBool::new(false);
+/// Run the daemon in the process that started it instead of forking, see `opm daemon start --foreground`
+static FOREGROUND: AtomicBool = AtomicBool::new(false);
+/// `--daemon-env` of `opm daemon restore`, set on top of `daemon.env` when the daemon starts
+static ENV_OVERRIDES: Lazy<Mutex<Vec<(String, String)>>> = Lazy::new(|| Mutex::new(vec![]));
+/// Set while another host saved the dump last, the monitoring loop leaves the processes alone meanwhile
+static FOREIGN_DUMP: AtomicBool = AtomicBool::new(false);
+/// Whether the log filesystem was full at the last check, see `check_log_space`
+static LOGS_FULL: AtomicBool = AtomicBool::new(false);
+/// Whether `runner.log_max_size` could not be parsed at the last check, see `rotate_logs`
+static LOG_ROTATION_INVALID: AtomicBool = AtomicBool::new(false);
+/// Whether the process count was past `MAX_PROCESSES_WARN_PERCENT` of the limit at the last check
+static NEAR_MAX_PROCESSES: AtomicBool = AtomicBool::new(false);
+
+/// Pids of managed processes, reaped by the daemon once they exit when it started them itself
+static SPAWNED: Lazy<Mutex<HashSet<i64>>> = Lazy::new(|| Mutex::new(HashSet::new()));
+/// Zombie children last seen per process, to warn once when the count passes the threshold
+static ZOMBIES: Lazy<Mutex<HashMap<usize, usize>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+/// Cycles since the children of each process were last saved, see `Process::children_due`
+static CHILDREN_SAVED: Lazy<Mutex<HashMap<usize, u32>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+/// How long the `--alert` thresholds of each process have been breached
+static ALERTS: Lazy<Mutex<alerts::Tracker>> = Lazy::new(|| Mutex::new(alerts::Tracker::default()));
+/// Log sizes of each process with a `--max-log-rate` at its last check
+static LOG_RATES: Lazy<Mutex<lograte::Tracker>> = Lazy::new(|| Mutex::new(lograte::Tracker::default()));
+/// A health check in flight, with the start of the run of the process it checks
+type HealthCheck = (DateTime<Utc>, thread::JoinHandle<Result<String, String>>);
+/// Health checks in flight per process
+static HEALTH_CHECKS: Lazy<Mutex<HashMap<usize, HealthCheck>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+// Shared by the monitoring loop and the API, failed sends are kept in notify-queue.jsonl
+// and every delivery attempt in notify-history.jsonl
+pub(crate) static NOTIFICATIONS: Lazy<NotificationManager> = Lazy::new(|| {
+    NotificationManager::with_queue_file(
+        config::read().daemon.notifications,
+        format!("{}notify-queue.jsonl", global!("opm.base")).into(),
+    )
+    .with_history_file(notifications::history::path())
+});
+
+/// Send a notification without blocking the monitoring loop
+fn notify(event: NotificationEvent, process: Option<String>, title: String, message: String) {
+    if let Ok(handle) = tokio::runtime::Handle::try_current() {
+        handle.spawn(async move { NOTIFICATIONS.send(event, process.as_deref(), &title, &message).await });
+    }
+}
 
 extern "C" fn handle_termination_signal(_: libc::c_int) {
     pid::remove();
+    let _ = std::fs::remove_file(ipc::path());
     log!("[daemon] killed", "pid" => process::id());
     unsafe { libc::_exit(0) }
 }
@@ -52,17 +126,483 @@ extern "C" fn handle_sigpipe(_: libc::c_int) {
     // This can happen when the daemon tries to use println!() after being daemonized
 }
 
-fn restart_process() {
-    // Load daemon config once at the start to avoid repeated I/O operations
-    let daemon_config = config::read().daemon;
-    
-    // Use a single Runner instance to avoid state synchronization issues
+/// Apply config.toml to the running daemon, on SIGHUP or through the API
+pub(crate) fn reload_settings() -> reload::Report {
+    let report = match config::try_read() {
+        Ok(config) => {
+            let report = reload::apply(&config);
+            if let Ok(handle) = tokio::runtime::Handle::try_current() {
+                handle.spawn(async move { NOTIFICATIONS.update_config(config.daemon.notifications).await });
+            }
+            report
+        }
+        Err(err) => reload::Report::failed(err),
+    };
+
+    match &report.error {
+        Some(err) => log!("[daemon] config reload failed", "error" => err),
+        None => log!(
+            "[daemon] config reloaded",
+            "applied" => reload::Report::keys(&report.applied),
+            "restart_required" => reload::Report::keys(&report.restart_required)
+        ),
+    }
+
+    report
+}
+
+/// Apply `daemon.retention` and delete unreferenced dump blobs, every file pruned is recorded in the daemon log
+fn housekeep() {
+    match dump::sweep_blobs() {
+        Ok(swept) => {
+            for blob in swept {
+                log!("[daemon] housekeeping", "class" => "blobs", "action" => "deleted", "path" => blob.path.display(), "freed" => helpers::format_memory(blob.bytes), "reason" => "unreferenced");
+            }
+        }
+        Err(err) => log!("[daemon] housekeeping failed", "class" => "blobs", "error" => err),
+    }
+
+    let Some(retention) = reload::retention() else { return };
+    let (pruned, errors) = storage::housekeep(&retention);
+
+    for file in pruned {
+        log!(
+            "[daemon] housekeeping",
+            "class" => file.class,
+            "action" => file.action,
+            "path" => file.path.display(),
+            "freed" => helpers::format_memory(file.bytes),
+            "reason" => file.reason
+        );
+    }
+
+    for err in errors {
+        log!("[daemon] housekeeping failed", "error" => err);
+    }
+}
+
+/// Copy the dump into `~/.opm/snapshots` once the newest copy is `daemon.snapshot_interval` old
+fn snapshot_dump() {
+    let (interval, dir, now) = (reload::snapshot_interval(), snapshots::dir(), Utc::now());
+    if interval == 0 || !snapshots::due(&dir, interval, now) {
+        return;
+    }
+
+    let dump = match std::fs::read(global!("opm.dump")) {
+        Ok(dump) => dump,
+        Err(err) => {
+            log!("[daemon] cannot read the dump for a snapshot", "error" => err);
+            return;
+        }
+    };
+
+    match snapshots::take(&dir, &dump, now, reload::snapshot_keep()) {
+        Ok(Some(snapshot)) => log!("[daemon] saved dump snapshot", "stamp" => snapshot.stamp, "size" => helpers::format_memory(snapshot.bytes)),
+        // Unchanged since the last snapshot, the next change is copied as soon as it is written
+        Ok(None) => {}
+        Err(err) => log!("[daemon] cannot save dump snapshot", "error" => err),
+    }
+}
+
+/// Rotate the logs of local processes past `runner.log_max_size`. A running unsupervised process
+/// keeps its log open, so it is copied and emptied in place. Supervisors are told to reopen theirs.
+fn rotate_logs() {
+    let policy = match reload::log_rotation() {
+        Ok(Some(policy)) => policy,
+        Ok(None) => return,
+        Err(err) => {
+            then!(!LOG_ROTATION_INVALID.swap(true, Ordering::AcqRel), log!("[daemon] log rotation disabled", "error" => err));
+            return;
+        }
+    };
+    LOG_ROTATION_INVALID.store(false, Ordering::Release);
+
+    for item in Runner::new().items().values() {
+        let supervisor = item.supervisor().filter(|state| state.alive());
+        let mode = ternary!(item.running && !item.supervised, rotate::Mode::Copy, rotate::Mode::Rename);
+        let logs = item.logs();
+
+        for log in [&logs.out, &logs.error] {
+            match rotate::rotate(Path::new(log), policy, mode) {
+                Ok(true) => {
+                    log!("[daemon] rotated log", "name" => item.name, "path" => log, "keep" => policy.keep);
+                    if let Some(state) = &supervisor {
+                        let _ = unsafe { libc::kill(state.supervisor as i32, libc::SIGHUP) };
+                    }
+                }
+                Ok(false) => {}
+                Err(err) => log!("[daemon] cannot rotate log", "name" => item.name, "path" => log, "error" => err),
+            }
+        }
+    }
+}
+
+/// Keep the lock of this daemon fresh, unless a daemon on another host took it over meanwhile
+fn refresh_lock() {
+    match pid::foreign() {
+        Some(lock) => log!("[daemon] another host holds the daemon lock of this opm home", "host" => lock.host, "pid" => lock.pid, "base" => global!("opm.base")),
+        None => pid::claim(process::id()),
+    }
+}
+
+/// Record once that the log filesystem filled up, processes errored by it are only retried once it has space again
+fn check_log_space() {
+    let log_path = config::read().runner.log_path;
+    let full = logspace::full(&log_path);
+
+    match (full, LOGS_FULL.swap(full, Ordering::AcqRel)) {
+        (true, false) => {
+            let message = format!("No space left for process logs in {log_path}, processes that cannot start are errored until there is");
+            log!("[daemon] log filesystem full", "path" => log_path);
+            events::record(Event::daemon(Kind::LogsFull, message.clone()));
+            notify(NotificationEvent::ProcessAlert, None, string!("Log filesystem full"), message);
+        }
+        (false, true) => log!("[daemon] log filesystem has space again", "path" => log_path),
+        _ => {}
+    }
+}
+
+/// Warn once when the managed processes come close to `runner.max_processes`, runaway scripts calling `opm start` in a loop slow every cycle down
+fn check_process_count(count: usize) {
+    let limit = max_processes(&config::read().runner);
+    let near = near_max_processes(count, limit);
+
+    match (near, NEAR_MAX_PROCESSES.swap(near, Ordering::AcqRel)) {
+        (true, false) => log!("[daemon] process count close to runner.max_processes", "count" => count, "limit" => limit),
+        (false, true) => log!("[daemon] process count back under the warning threshold", "count" => count, "limit" => limit),
+        _ => {}
+    }
+}
+
+/// Whether the monitoring loop may change processes, logging and notifying once another host wrote the dump.
+/// Anything saved on this host again, an `opm restart` for example, hands the dump back to this daemon.
+fn owns_dump(runner: &Runner) -> bool {
+    match runner.foreign_host() {
+        Some(host) => {
+            if !FOREIGN_DUMP.swap(true, Ordering::AcqRel) {
+                let message = format!("The dump in {} was saved by {host}, this daemon stopped changing processes until this host saves it again", global!("opm.base"));
+                log!("[daemon] dump saved by another host, pausing", "host" => host, "dump" => global!("opm.dump"));
+                notify(NotificationEvent::ProcessAlert, None, format!("OPM home shared with {host}"), message);
+            }
+            false
+        }
+        None => {
+            if FOREIGN_DUMP.swap(false, Ordering::AcqRel) {
+                log!("[daemon] dump saved by this host again, resuming", "host" => opm::process::host::current());
+            }
+            true
+        }
+    }
+}
+
+/// Signs of another host using this opm home, for `opm daemon health`
+fn shared_home(runner: &Runner) -> Vec<String> {
+    let mut signs = vec![];
+
+    if let Some(lock) = pid::lock().filter(pid::Lock::foreign) {
+        let state = ternary!(lock.live(Utc::now()), "is held", "was left");
+        signs.push(format!("the daemon lock {state} by {} (pid {}), refreshed {}", lock.host, lock.pid, lock.refreshed.to_rfc3339()));
+    }
+
+    if let Some(host) = runner.foreign_host() {
+        signs.push(format!("the dump was last saved by {host}"));
+    }
+
+    signs
+}
+
+/// Append one sample of every process to metrics.jsonl for `opm report`
+fn sample_metrics() {
     let runner = Runner::new();
-    // Collect IDs first to avoid borrowing issues during iteration
-    // Use process_ids() instead of items().keys() to avoid cloning all processes
-    let process_ids: Vec<usize> = runner.process_ids().collect();
-    
-    for id in process_ids {
+    let time = Utc::now();
+    let records: Vec<stats::Record> = runner.items().values().map(|item| stats::Record::new(time, item, &Runner::sample(item))).collect();
+
+    if !records.is_empty() {
+        stats::record(&records);
+    }
+}
+
+/// Save the children of a process, at most every `CHILDREN_SAVE_CYCLES` cycles once it has some
+fn track_children(runner: &mut Runner, item: &opm::process::Process, live: &[i64]) {
+    let Ok(mut saved) = CHILDREN_SAVED.lock() else { return };
+    let cycles = saved.entry(item.id).or_default();
+    *cycles = cycles.saturating_add(1);
+
+    if item.children_due(live, *cycles) {
+        log!("[daemon] added", "children" => format!("{live:?}"));
+        runner.set_children(item.id, live.to_vec()).save();
+        *cycles = 0;
+    }
+}
+
+/// Warn once a managed process keeps more than `ZOMBIE_WARNING` defunct children around
+fn check_zombies(id: usize, name: &str, zombies: &[i64]) {
+    let Ok(mut seen) = ZOMBIES.lock() else { return };
+    let previous = seen.insert(id, zombies.len()).unwrap_or(0);
+
+    if zombies.len() > ZOMBIE_WARNING && previous <= ZOMBIE_WARNING {
+        log!("[daemon] process is not reaping its children", "name" => name, "id" => id, "zombies" => zombies.len(), "pids" => format!("{zombies:?}"));
+    }
+}
+
+/// Hold the daemon to `daemon.self_limits`, see `guard` for when it trims and restarts
+fn keep_within(usage: &guard::SelfUsage, limits: &SelfLimits) {
+    for step in guard::record(usage, limits.reexec) {
+        match step {
+            guard::Step::CpuOver => log!("[daemon] over its cpu limit", "cpu_percent" => format!("{:.2}", usage.cpu_percent), "max_cpu_percent" => usage.max_cpu_percent.unwrap_or_default()),
+            guard::Step::MemoryOver => log!("[daemon] over its memory limit", "rss" => helpers::format_memory(usage.rss), "max_rss" => helpers::format_memory(usage.max_rss.unwrap_or_default())),
+            guard::Step::Trim => {
+                trim();
+                log!("[daemon] trimmed its caches", "rss" => helpers::format_memory(usage.rss));
+            }
+            guard::Step::Reexec => reexec(),
+        }
+    }
+}
+
+/// Drop what the daemon only keeps for speed or display
+fn trim() {
+    // Counts at or under the threshold warn the same as no count at all
+    if let Ok(mut seen) = ZOMBIES.lock() {
+        seen.retain(|_, count| *count > ZOMBIE_WARNING);
+        seen.shrink_to_fit();
+    }
+    if let Ok(mut spawned) = SPAWNED.lock() {
+        spawned.shrink_to_fit();
+    }
+
+    // One latency histogram per route the API answered, scrapes start them over
+    api::HTTP_REQ_HISTOGRAM.reset();
+
+    // Freed memory stays with the daemon until the allocator hands it back
+    #[cfg(all(target_os = "linux", target_env = "gnu"))]
+    unsafe {
+        libc::malloc_trim(0);
+    }
+}
+
+/// Replace the daemon with a fresh one, the processes keep running and are picked up again from the dump
+fn reexec() {
+    use std::os::unix::process::CommandExt;
+
+    let exe = match std::env::current_exe() {
+        Ok(exe) => exe,
+        Err(err) => return log!("[daemon] cannot restart itself", "error" => err),
+    };
+
+    let mut args = vec!["--quiet", "daemon", "restore"];
+    if ENABLE_WEBUI.load(Ordering::Acquire) {
+        args.push("--webui");
+    } else if ENABLE_API.load(Ordering::Acquire) {
+        args.push("--api");
+    }
+
+    log!("[daemon] restarting itself over its memory limit", "pid" => process::id());
+
+    // The new daemon refuses to start while the pid file names a running daemon, which is still this one
+    pid::remove();
+    let _ = std::fs::remove_file(ipc::path());
+
+    let err = process::Command::new(exe).args(args).exec();
+
+    log!("[daemon] cannot restart itself", "error" => err);
+    pid::write(process::id());
+    if let Err(err) = ipc::listen(&ipc::path()) {
+        log!("[daemon] cannot listen for commands", "socket" => ipc::path().display(), "error" => err);
+    }
+}
+
+/// Fire the alerts of a running process whose metric stayed above its threshold long enough
+fn check_alerts(id: usize, item: &opm::process::Process, starting: bool) {
+    let Ok(mut tracker) = ALERTS.lock() else { return };
+
+    if !item.running || starting || item.alerts.is_empty() {
+        return tracker.forget(id);
+    }
+
+    let sample = Runner::sample(item);
+    for alert in &item.alerts {
+        match tracker.check(id, alert, alert.metric.value(&sample), Utc::now()) {
+            Some(alerts::Transition::Fired(value)) => {
+                let message = format!("Process {} ({id}) {alert}: {}", item.name, alert.metric.format(value));
+                log!("[daemon] alert fired", "name" => item.name, "id" => id, "alert" => alert, "value" => value);
+                events::record(Event::process(Kind::Alert, id, &item.name, message.clone()));
+                notify(NotificationEvent::ProcessAlert, Some(item.name.clone()), format!("Process alert: {}", item.name), message);
+            }
+            Some(alerts::Transition::Resolved) => log!("[daemon] alert resolved", "name" => item.name, "id" => id, "alert" => alert),
+            None => {}
+        }
+    }
+}
+
+/// Act on a running process whose logs grew faster than its `--max-log-rate` for `lograte::BREACHES` checks in a row.
+/// True when it was restarted or stopped, the rest of its checks wait for the next interval.
+fn check_log_rate(runner: &mut Runner, id: usize, item: &opm::process::Process, starting: bool) -> bool {
+    let Ok(mut tracker) = LOG_RATES.lock() else { return false };
+    let Some(limit) = item.max_log_rate.as_ref().filter(|_| item.running && !starting) else {
+        tracker.forget(id);
+        return false;
+    };
+
+    let Some(rate) = tracker.check(id, limit, lograte::sizes(item), Utc::now()) else { return false };
+    let message = format!(
+        "Process {} ({id}) logged {} for {} checks in a row, over its max log rate of {}",
+        item.name,
+        lograte::format(rate),
+        lograte::BREACHES,
+        lograte::format(limit.per_minute())
+    );
+
+    log!("[daemon] log rate exceeded", "name" => item.name, "id" => id, "rate" => rate, "limit" => limit, "action" => limit.action);
+    events::record(Event::process(Kind::LogRate, id, &item.name, format!("{message}, action: {}", limit.action)));
+    notify(NotificationEvent::ProcessAlert, Some(item.name.clone()), format!("Process logging too fast: {}", item.name), message);
+
+    match limit.action {
+        lograte::Action::Warn => return false,
+        lograte::Action::Restart => {
+            if let Err(err) = runner.trigger_reload(id, "log_rate") {
+                log!("[daemon] log rate restart failed", "name" => item.name, "id" => id, "error" => err);
+            }
+        }
+        lograte::Action::Stop => {
+            runner.stop(id, None);
+        }
+    }
+
+    // A restarted process that keeps flooding is acted on again after as many checks
+    tracker.forget(id);
+    runner.save();
+    true
+}
+
+/// Run the health check of an online process when it is due, on a thread of its own so a slow one holds up no other
+/// process. Its outcome is recorded at a later check. Some(reason) once it failed `retries` checks in a row.
+fn check_health(runner: &mut Runner, id: usize, item: &opm::process::Process, starting: bool) -> Option<String> {
+    let Ok(mut checks) = HEALTH_CHECKS.lock() else { return None };
+    let Some(check) = item.health.as_ref().filter(|_| item.running && !starting) else {
+        checks.remove(&id);
+        return None;
+    };
+
+    if checks.get(&id).is_some_and(|(_, handle)| handle.is_finished()) {
+        let (started, handle) = checks.remove(&id)?;
+        // The process was restarted while it was checked
+        if started != item.started {
+            return None;
+        }
+
+        let result = handle.join().unwrap_or_else(|_| Err(string!("the check panicked")));
+        let last = item.health_status();
+        let status = health::Status::after(last, result, Utc::now());
+
+        if last.is_none_or(|last| last.healthy != status.healthy) {
+            log!("[daemon] health changed", "name" => item.name, "id" => id, "health" => status.describe());
+        }
+
+        let failures = status.failures;
+        let reason = format!("failed {failures} health checks in a row ({})", status.detail);
+        runner.process(id).health_status = Some(status);
+        runner.save();
+
+        return (failures >= check.retries.max(1)).then_some(reason);
+    }
+
+    if !checks.contains_key(&id) && check.due(item.health_status(), Utc::now()) {
+        let (check, path) = (check.clone(), item.path.clone());
+        checks.insert(id, (item.started, thread::spawn(move || check.run(&path))));
+    }
+
+    None
+}
+
+/// Restart a running process once a time of its `--cron-restart` passed. True when it was restarted.
+fn check_restart_cron(runner: &mut Runner, id: usize, item: &opm::process::Process) -> bool {
+    let Some(schedule) = &item.restart_cron else { return false };
+    let now = Utc::now();
+
+    // Set without a time to count from, by an HCL import for one
+    let Some(since) = item.restart_cron_since else {
+        runner.process(id).restart_cron_since = Some(now);
+        runner.save();
+        return false;
+    };
+
+    let Some(due) = schedule.due(since, now) else { return false };
+    runner.process(id).restart_cron_since = Some(now);
+
+    // A stopped process stays stopped, one started since the time is already fresh
+    if !item.running || item.started >= due {
+        runner.save();
+        return false;
+    }
+
+    log!("[daemon] scheduled restart", "name" => item.name, "id" => id, "schedule" => schedule, "due" => due);
+    events::record(Event::process(Kind::Restart, id, &item.name, format!("Process {} ({id}) restarted on its schedule ({schedule})", item.name)));
+
+    if let Err(err) = runner.trigger_reload(id, "cron") {
+        log!("[daemon] scheduled restart failed", "name" => item.name, "id" => id, "error" => err);
+    }
+
+    runner.save();
+    true
+}
+
+/// Wait for the managed processes the daemon is the parent of, so they do not stay defunct
+fn reap_spawned() {
+    let Ok(mut spawned) = SPAWNED.lock() else { return };
+
+    spawned.retain(|&pid| match opm::process::reap(pid) {
+        opm::process::Reap::Running => true,
+        opm::process::Reap::Reaped => {
+            log!("[daemon] reaped exited process", "pid" => pid);
+            false
+        }
+        opm::process::Reap::NotChild => false,
+    });
+}
+
+/// The settings `check_processes` holds processes to
+pub(crate) struct Supervision {
+    pub restarts: u64,
+    /// Base and longest delay of the crash restart backoff in milliseconds
+    pub restart_delay: (u64, u64),
+    pub log_checkpoint: u64,
+    pub cores: Cores,
+    /// Watched paths hashed at the same time off the loop, see `watch`
+    pub watch_hash_threads: usize,
+}
+
+impl Supervision {
+    /// Restart limits, checkpoints and core collection of the applied config, see `opm daemon reload-config`
+    fn applied() -> Self {
+        Supervision {
+            restarts: reload::restarts(),
+            restart_delay: reload::restart_delay(),
+            log_checkpoint: reload::log_checkpoint(),
+            cores: reload::cores(),
+            watch_hash_threads: reload::watch_hash_threads(),
+        }
+    }
+
+    /// When a process that crashed `crashes` times in a row is restarted, None for right away
+    fn next_restart(&self, crashes: u64, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let (base_ms, max_ms) = self.restart_delay;
+        let delay = opm::process::restart_backoff(crashes, base_ms, max_ms);
+        ternary!(delay > chrono::Duration::zero(), Some(now + delay), None)
+    }
+}
+
+/// Enough of a watch hash to tell changes apart in events
+fn short_hash(hash: &str) -> &str { &hash[..hash.len().min(12)] }
+
+/// Check the processes in `due`, the ones whose own monitoring interval has passed
+fn restart_process(due: &[usize]) { check_processes(due, &Supervision::applied(), Runner::new) }
+
+/// Crash detection, restarts, memory limits and watch reloads of `due`, with the processes `load` reads
+pub(crate) fn check_processes(due: &[usize], supervision: &Supervision, load: impl Fn() -> Runner) {
+    let log_checkpoint = supervision.log_checkpoint;
+
+    for &id in due {
         // Note: We reload runner at the start of each iteration to ensure we see
         // changes made by previous iterations (e.g., when a previous process was
         // restarted and the state was saved to disk). This is necessary because
@@ -84,7 +624,7 @@ fn restart_process() {
         // TODO: Consider implementing Runner::reload() method for future optimization
         // that only updates changed state rather than full reconstruction from disk.
         // This would be more efficient but adds complexity.
-        let mut runner = Runner::new();
+        let mut runner = load();
         
         // Clone item to avoid borrowing issues when we mutate runner later.
         // This is required by Rust's borrow checker - we can't hold an immutable
@@ -98,62 +638,155 @@ fn restart_process() {
             Some(item) => item.clone(),
             None => continue, // Process was removed, skip it
         };
+
+        // A limit of its own replaces `daemon.restarts`
+        let max_restarts = item.restart_limit(supervision.restarts);
+        let limit = item.restart_limit_source();
         
-        let children = opm::process::process_find_children(item.pid);
+        // Processes that fork all the time are not walked, their children are looked up when they are stopped
+        if item.track_children {
+            let children = opm::process::process_classify_children(item.pid);
+            track_children(&mut runner, &item, &children.live);
+            check_zombies(id, &item.name, &children.zombies);
+        }
 
-        if !children.is_empty() && children != item.children {
-            log!("[daemon] added", "children" => format!("{children:?}"));
-            runner.set_children(id, children.clone()).save();
+        if let Ok(mut spawned) = SPAWNED.lock() {
+            spawned.extend([item.pid, item.shell_pid.unwrap_or(0)].into_iter().filter(|&pid| pid > 0));
         }
 
+        // Offset checkpoints let `opm logs --since/--until` seek into large logs
+        if log_checkpoint > 0 {
+            let logs = item.logs();
+            for log in [&logs.out, &logs.error] {
+                if let Err(err) = checkpoint::record(log, log_checkpoint, Utc::now()) {
+                    log!("[daemon] failed to record log checkpoint", "path" => log, "error" => err);
+                }
+            }
+        }
+
+        // Memory spikes while a process boots are expected, limits apply once it is online
+        let starting = item.is_starting(Utc::now());
+
         // Check memory limit if configured
-        if item.running && item.max_memory > 0 {
+        if item.running && item.max_memory > 0 && !starting {
             let pid_for_monitoring = item.shell_pid.unwrap_or(item.pid);
-            if let Some(memory_info) =
-                opm::process::get_process_memory_with_children(pid_for_monitoring)
+            if let Some(memory_info) = item.memory(pid_for_monitoring)
+                && memory_info.rss > item.max_memory
             {
-                if memory_info.rss > item.max_memory {
-                    log!("[daemon] memory limit exceeded", "name" => item.name, "id" => id, 
-                         "memory" => memory_info.rss, "limit" => item.max_memory);
-                    println!(
-                        "{} Process ({}) exceeded memory limit: {} > {} - stopping process",
-                        *helpers::FAIL,
-                        item.name,
-                        helpers::format_memory(memory_info.rss),
-                        helpers::format_memory(item.max_memory)
-                    );
-                    runner.stop(id);
+                let action = item.max_memory_action;
+                log!("[daemon] memory limit exceeded", "name" => item.name, "id" => id, 
+                     "memory" => memory_info.rss, "limit" => item.max_memory, "action" => action);
+                println!(
+                    "{} Process ({}) exceeded memory limit: {} > {} - {}",
+                    *helpers::FAIL,
+                    item.name,
+                    helpers::format_memory(memory_info.rss),
+                    helpers::format_memory(item.max_memory),
+                    ternary!(action == memlimit::Action::Restart, "restarting process", "stopping process")
+                );
+                let usage = format!("{} > {}", helpers::format_memory(memory_info.rss), helpers::format_memory(item.max_memory));
+                let message = format!("Process {} ({id}) exceeded its memory limit: {usage}", item.name);
+                events::record(Event::process(Kind::MemoryLimit, id, &item.name, format!("{message}, action: {action}")));
+
+                match action {
+                    // Reloaded like a watch change, the crash counter is left alone
+                    memlimit::Action::Restart => match runner.trigger_reload(id, "memory") {
+                        Ok(_) => {
+                            runner.process(id).memory_restarts += 1;
+                            notify(NotificationEvent::ProcessRestart, Some(item.name.clone()), format!("Process restarted: {}", item.name), message);
+                        }
+                        // Left stopped like any restart that failed, reported as a crash
+                        Err(err) => {
+                            log!("[daemon] memory limit restart failed", "name" => item.name, "id" => id, "error" => err);
+                            let failed = format!("{message}, restarting it failed: {err}");
+                            events::record(Event::process(Kind::Crash, id, &item.name, failed.clone()));
+                            notify(NotificationEvent::ProcessCrash, Some(item.name.clone()), format!("Process crashed: {}", item.name), failed);
+                        }
+                    },
                     // Don't mark as crashed since this is intentional enforcement
-                    runner.save();
-                    continue;
+                    memlimit::Action::Stop => {
+                        runner.stop(id, None);
+                    }
                 }
+
+                runner.save();
+                continue;
             }
         }
 
-        if item.running && item.watch.enabled {
-            let path = item.path.join(item.watch.path.clone());
-            let hash = hash::create(path);
+        check_alerts(id, &item, starting);
+
+        if check_log_rate(&mut runner, id, &item, starting) {
+            continue;
+        }
+
+        if check_restart_cron(&mut runner, id, &item) {
+            continue;
+        }
+
+        if !item.running || !item.watch.enabled {
+            watch::forget(id);
+        } else if let Some(hash) = watch::poll(id, item.path.join(&item.watch.path), &item.watch.hash, supervision.watch_hash_threads) {
+            let now = Utc::now();
+            let mut watch = item.watch.clone();
+            let change = format!("{} -> {}", short_hash(&item.watch.hash), short_hash(&hash));
+
+            match watch.check(&hash, now) {
+                WatchChange::Reload => {
+                    log!("[daemon] watch triggered reload", "name" => item.name, "id" => id, "hash" => change);
+                    events::record(Event::process(Kind::WatchReload, id, &item.name, format!("Process {} ({id}) reloaded, its watched path changed ({change})", item.name)));
+                    // Watch reload should increment counter and store the hash it was reloaded for
+                    if let Err(err) = runner.watch_reload(id, hash) {
+                        log!("[daemon] watch reload failed", "name" => item.name, "id" => id, "error" => err);
+                    }
+                    watch.reloaded(now);
+                    let process = runner.process(id);
+                    (process.watch.last_reload, process.watch.pending) = (watch.last_reload, watch.pending);
+                    runner.save();
+                    log!("[daemon] watch reload complete", "name" => item.name, "id" => id);
+                    continue;
+                }
+                WatchChange::Held => {
+                    // Each change is recorded once, not at every check it stays held
+                    if watch.pending != item.watch.pending {
+                        log!("[daemon] watch reload held back", "name" => item.name, "id" => id, "hash" => change);
+                        events::record(Event::process(
+                            Kind::WatchHeld,
+                            id,
+                            &item.name,
+                            format!("Process {} ({id}) watched path changed ({change}), held back by its {}s watch cooldown", item.name, watch.cooldown()),
+                        ));
+                    }
+                }
+                WatchChange::Unchanged => {}
+            }
 
-            if hash != item.watch.hash {
-                log!("[daemon] watch triggered reload", "name" => item.name, "id" => id);
-                runner.restart(id, false, true);  // Watch reload should increment counter
+            if watch.pending != item.watch.pending {
+                runner.process(id).watch.pending = watch.pending;
                 runner.save();
-                log!("[daemon] watch reload complete", "name" => item.name, "id" => id);
-                continue;
             }
         }
 
         // Check if process is alive based on PID
         // is_pid_alive() handles all PID validation (including PID <= 0)
-        let process_alive = opm::process::is_pid_alive(item.pid);
+        // An adopted pid reused by an unrelated process counts as dead
+        // A supervised process is dead once its supervisor recorded how it exited
+        // One failing its health check is ended and handled below as if it had crashed
+        let unhealthy = check_health(&mut runner, id, &item, starting);
+        if let Some(reason) = &unhealthy {
+            log!("[daemon] restarting unhealthy process", "name" => item.name, "id" => id, "reason" => reason);
+            runner.kill_unhealthy(id);
+        }
+
+        let supervisor = item.supervisor();
+        let process_alive = unhealthy.is_none() && supervisor.as_ref().is_none_or(|state| state.alive()) && opm::process::is_pid_alive(item.pid) && item.owns_pid();
         
         // If process is alive and has been running successfully, keep monitoring
         // Note: We no longer auto-reset crash counter here - it persists to show
         // crash history over time. Only explicit reset (via reset_counters()) will clear it.
         if process_alive && item.running && item.crash.value > 0 {
             // Check if process has been running for at least the grace period
-            let uptime_secs = (Utc::now() - item.started).num_seconds();
-            if uptime_secs >= STARTUP_GRACE_PERIOD_SECS {
+            if !starting {
                 // Process has been stable - clear crashed flag but keep crash count
                 if runner.exists(id) {
                     let process = runner.process(id);
@@ -178,9 +811,23 @@ fn restart_process() {
                 // Check if this is a newly detected crash (not already marked as crashed)
                 // If already crashed, we've already incremented the counter and are waiting for restart
                 if !item.crash.crashed {
+                    // Moved out of the working directory before a restart could overwrite them
+                    let cores = match item.collect_cores {
+                        true => cores::collect(&item, &supervision.cores),
+                        false => vec![],
+                    };
+
+                    if !cores.is_empty() {
+                        log!("[daemon] found core files", "name" => item.name, "id" => id, "cores" => cores.len());
+                    }
+
                     // Get crash count before modifying
                     let crash_count = {
                         let process = runner.process(id);
+                        process.crash.cores = cores.clone();
+                        if let Some(code) = supervisor.as_ref().and_then(|state| state.exit_code) {
+                            process.exit_code = Some(code);
+                        }
                         // Increment consecutive crash counter
                         process.crash.value += 1;
                         process.crash.crashed = true;
@@ -194,28 +841,74 @@ fn restart_process() {
                     // - crash_count=10 with max_restarts=10: allow restart (10th restart attempt)
                     // - crash_count=11 with max_restarts=10: give up (exceeded 10 restarts)
                     // This means "restarts: 10" allows exactly 10 restart attempts
-                    if crash_count > daemon_config.restarts {
+                    if crash_count > max_restarts {
                         // Exceeded max restarts - give up and set running=false
                         let process = runner.process(id);
                         process.running = false;
                         log!("[daemon] process exceeded max crash limit", 
-                             "name" => item.name, "id" => id, "crash_count" => crash_count, "max_restarts" => daemon_config.restarts);
+                             "name" => item.name, "id" => id, "crash_count" => crash_count, "max_restarts" => max_restarts, "limit" => limit);
                         runner.save();
                     } else {
                         // Still within crash limit - mark as crashed and save
-                        // Next daemon cycle will restart it
+                        // A later daemon cycle restarts it once its backoff has passed
+                        let next_restart = supervision.next_restart(crash_count, Utc::now());
+                        runner.process(id).crash.next_restart = next_restart;
                         log!("[daemon] process crashed", 
-                             "name" => item.name, "id" => id, "crash_count" => crash_count, "max_restarts" => daemon_config.restarts);
+                             "name" => item.name, "id" => id, "crash_count" => crash_count, "max_restarts" => max_restarts,
+                             "next_restart" => next_restart.map_or(string!("now"), |at| at.to_rfc3339()));
                         runner.save();
                     }
+
+                    let mut message = format!("Process {} ({id}) crashed ({crash_count}/{max_restarts} restarts, {limit})", item.name);
+                    if let Some(reason) = &unhealthy {
+                        message.push_str(&format!(", {reason}"));
+                    }
+                    if let Some(code) = supervisor.as_ref().and_then(|state| state.exit_code) {
+                        message.push_str(&format!(", exit code {code}"));
+                    }
+                    if !item.labels.is_empty() {
+                        message.push_str(&format!(" [{}]", labels::describe(&item.labels)));
+                    }
+                    if !cores.is_empty() {
+                        let cores: Vec<String> = cores.iter().map(cores::Core::describe).collect();
+                        message.push_str(&format!(", core dumped: {}", cores.join(", ")));
+                    }
+
+                    events::record(Event::process(Kind::Crash, id, &item.name, message.clone()));
+                    if crash_count > max_restarts {
+                        let gave_up = format!("Process {} ({id}) crashed {crash_count} times, no longer restarting it ({limit} is {max_restarts})", item.name);
+                        events::record(Event::process(Kind::GaveUp, id, &item.name, gave_up));
+                        // The notification says the process is given up on and which limit it ran out of
+                        message.push_str(&format!(", no longer restarting it ({limit} is {max_restarts})"));
+                    }
+                    notify(NotificationEvent::ProcessCrash, Some(item.name.clone()), format!("Process crashed: {}", item.name), message);
+                } else if item.errored.as_deref() == Some(logspace::FULL) && LOGS_FULL.load(Ordering::Acquire) {
+                    // Waits for space without using up its restarts, see `check_log_space`
+                } else if item.crash.next_restart.is_some_and(|at| at > Utc::now()) {
+                    // Backing off, see `daemon.restart_delay_base_ms`
                 } else {
                     // Process is already marked as crashed - attempt restart now
                     log!("[daemon] restarting crashed process", 
-                         "name" => item.name, "id" => id, "crash_count" => item.crash.value, "max_restarts" => daemon_config.restarts);
+                         "name" => item.name, "id" => id, "crash_count" => item.crash.value, "max_restarts" => max_restarts);
                     runner.restart(id, true, true);
                     runner.save();
-                    log!("[daemon] restart complete", 
-                         "name" => item.name, "id" => id, "new_pid" => runner.info(id).map(|p| p.pid).unwrap_or(0));
+
+                    if let Some(reason) = runner.info(id).and_then(|p| p.errored.clone()) {
+                        log!("[daemon] restart failed, process errored", "name" => item.name, "id" => id, "reason" => reason);
+                        continue;
+                    }
+
+                    // A restart that failed counted as a crash, the next attempt backs off too
+                    if let Some(crashes) = runner.info(id).filter(|p| p.running && p.crash.crashed).map(|p| p.crash.value) {
+                        runner.process(id).crash.next_restart = supervision.next_restart(crashes, Utc::now());
+                        runner.save();
+                        log!("[daemon] restart failed", "name" => item.name, "id" => id, "crash_count" => crashes);
+                        continue;
+                    }
+
+                    let pid = runner.info(id).map(|p| p.pid).unwrap_or(0);
+                    log!("[daemon] restart complete", "name" => item.name, "id" => id, "new_pid" => pid);
+                    events::record(Event::process(Kind::Restart, id, &item.name, format!("Process {} ({id}) restarted after a crash, pid {pid}", item.name)));
                 }
             } else {
                 // Process was already stopped (running=false), just update PID
@@ -229,53 +922,124 @@ fn restart_process() {
     }
 }
 
-pub fn health(format: &String) {
+/// Start processes registered by `opm import --background`, see `pending::ready` for the order
+fn start_pending() {
+    let mut runner = Runner::new();
+
+    for id in pending::ready(&runner.list, Utc::now()) {
+        runner.restart(id, false, false);
+        runner.save();
+
+        let Some(item) = runner.info(id) else { continue };
+
+        if !item.crash.crashed {
+            log!("[daemon] started pending process", "name" => item.name, "id" => id, "pid" => item.pid);
+            events::record(Event::process(Kind::ImportStart, id, &item.name, format!("Process {} ({id}) started from a background import, pid {}", item.name, item.pid)));
+        } else {
+            log!("[daemon] pending process failed to start", "name" => item.name, "id" => id);
+            events::record(Event::process(Kind::ImportFailed, id, &item.name, format!("Process {} ({id}) from a background import failed to start, see its error log", item.name)));
+        }
+    }
+}
+
+pub fn health(format: &str, check_webui: bool) {
     let mut pid: Option<i32> = None;
     let mut cpu_percent: Option<f64> = None;
     let mut uptime: Option<DateTime<Utc>> = None;
     let mut memory_usage: Option<MemoryInfo> = None;
+    let mut own_cpu: Option<f64> = None;
     let mut runner = Runner::new();
     let mut daemon_running = false;
 
+    // Looked at before a stale pid file and its lock are cleaned up below
+    let shared = shared_home(&runner);
+
+    // A daemon answering on its socket reports on itself, the pid file is only read without one
+    let answer = ipc::connect().and_then(|mut client| client.request(&ipc::Request::Health).ok());
+
     #[derive(Clone, Debug, Tabled)]
     struct Info {
         #[tabled(rename = "pid file")]
         pid_file: String,
+        #[tabled(rename = "command socket")]
+        socket: String,
         #[tabled(rename = "fork path")]
         path: String,
         #[tabled(rename = "cpu percent")]
         cpu_percent: String,
         #[tabled(rename = "memory usage")]
         memory_usage: String,
+        #[tabled(rename = "self limits")]
+        self_limits: String,
+        #[tabled(skip)]
+        limits: guard::SelfUsage,
+        #[tabled(rename = "history on disk")]
+        storage_used: String,
+        #[tabled(skip)]
+        storage: Vec<storage::Usage>,
+        #[tabled(rename = "log space")]
+        log_space_free: String,
+        #[tabled(skip)]
+        log_space: Option<logspace::Space>,
         #[tabled(rename = "daemon type")]
         external: String,
+        #[tabled(rename = "daemon PATH")]
+        env_path: String,
+        environment: String,
+        #[tabled(skip)]
+        env: Option<env::Summary>,
+        #[tabled(skip)]
+        shell_env: env::Summary,
         #[tabled(rename = "process count")]
         process_count: usize,
+        #[tabled(rename = "process limit")]
+        max_processes: usize,
         role: String,
+        host: String,
+        #[tabled(skip)]
+        shared: Vec<String>,
+        #[tabled(skip)]
+        stale_logs: Vec<String>,
         uptime: String,
         pid: String,
         status: ColoredString,
+        #[tabled(skip)]
+        webui: Option<check::Report>,
     }
 
     impl Serialize for Info {
         fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
-            let trimmed_json = json!({
+            let mut trimmed_json = json!({
              "pid_file": &self.pid_file.trim(),
+             "socket": &self.socket.trim(),
              "path": &self.path.trim(),
              "cpu": &self.cpu_percent.trim(),
              "mem": &self.memory_usage.trim(),
+             "limits": &self.limits,
              "process_count": &self.process_count.to_string(),
+             "max_processes": &self.max_processes,
              "role": &self.role,
+             "host": &self.host,
+             "shared": &self.shared,
+             "stale_logs": &self.stale_logs,
              "uptime": &self.uptime.trim(),
              "pid": &self.pid.trim(),
              "status": &self.status.0.trim(),
+             "storage": &self.storage,
+             "log_space": &self.log_space,
+             "env": &self.env,
+             "shell_env": &self.shell_env,
             });
 
+            if let Some(report) = &self.webui {
+                trimmed_json["webui"] = json!(report);
+            }
+
             trimmed_json.serialize(serializer)
         }
     }
 
-    if pid::exists() {
+    if answer.is_none() && pid::exists() {
         match pid::read() {
             Ok(process_id) => {
                 // Check if the process is actually running before trying to get its information
@@ -288,12 +1052,11 @@ pub fn health(format: &String) {
                     // Try to get process stats (may fail for detached processes)
                     #[cfg(any(target_os = "linux", target_os = "macos"))]
                     {
-                        if let Ok(process) = Process::new(process_id.get::<u32>()) {
+                        if let Ok(process) = Process::new_fast(process_id.get::<u32>()) {
                             memory_usage = process.memory_info().ok().map(MemoryInfo::from);
-                            cpu_percent = Some(get_process_cpu_usage_with_children_from_process(
-                                &process,
-                                process_id.get::<i64>(),
-                            ));
+                            let (own, with_children) = get_process_cpu_usage_timed(process_id.get::<i64>());
+                            own_cpu = Some(own);
+                            cpu_percent = Some(with_children);
                         }
                     }
                 } else {
@@ -309,6 +1072,9 @@ pub fn health(format: &String) {
         }
     }
 
+    let self_limits = config::read().daemon.self_limits.unwrap_or_default();
+    let mut limits = guard::SelfUsage::new(memory_usage.as_ref().map_or(0, |usage| usage.rss), own_cpu.unwrap_or_default(), &self_limits);
+
     let cpu_percent = match cpu_percent {
         Some(percent) => format!("{:.2}%", percent),
         None => string!("0.00%"),
@@ -324,26 +1090,65 @@ pub fn health(format: &String) {
         None => string!("none"),
     };
 
-    let pid = match pid {
+    let mut pid = match pid {
         Some(pid) => string!(pid),
         None => string!("n/a"),
     };
 
+    let (mut cpu_percent, mut memory_usage, mut uptime, mut process_count) = (cpu_percent, memory_usage, uptime, runner.count());
+    if let Some(daemon) = &answer {
+        let text = |value: &serde_json::Value, default: &String| value.as_str().map_or_else(|| default.clone(), str::to_string);
+
+        daemon_running = true;
+        pid = daemon["pid"].as_i64().map_or(pid, |pid| pid.to_string());
+        uptime = text(&daemon["uptime"], &uptime);
+        cpu_percent = text(&daemon["stats"]["cpu_percent"], &cpu_percent);
+        memory_usage = text(&daemon["stats"]["memory_usage"], &memory_usage);
+        process_count = daemon["process_count"].as_u64().map_or(process_count, |count| count as usize);
+        limits = serde_json::from_value(daemon["limits"].clone()).unwrap_or(limits);
+    }
+
+    // Only a daemon answering on its socket can tell what it runs with
+    let daemon_env: Option<env::Summary> = answer.as_ref().and_then(|daemon| serde_json::from_value(daemon["env"].clone()).ok());
+    let shell_env = env::Summary::current();
+
+    let storage = storage::usage();
+    let log_space = logspace::Space::new(&config::read().runner.log_path);
+    let stale_logs = logdir::stale(&runner, &config::read().runner.log_path).into_iter().map(|(id, dir)| format!("{} ({id}) in {dir}", runner.list[&id].name)).collect();
     let data = vec![Info {
         pid: pid,
         cpu_percent,
         memory_usage,
+        self_limits: ternary!(guard::enabled(&self_limits), limits.describe(), string!("none")),
+        limits: limits.clone(),
+        storage_used: helpers::format_memory(storage.iter().map(|usage| usage.bytes).sum()),
+        storage,
+        log_space_free: log_space.as_ref().map_or_else(|| string!("unknown"), logspace::Space::describe),
+        log_space: log_space.clone(),
         uptime: uptime,
         path: global!("opm.base"),
         external: global!("opm.daemon.kind"),
-        process_count: runner.count(),
+        env_path: daemon_env.as_ref().and_then(|env| env.path.clone()).unwrap_or_else(|| string!("unknown")),
+        environment: match &daemon_env {
+            Some(env) => format!("{} ({} vars, {} this shell)", env.hash, env.vars, ternary!(env.hash == shell_env.hash, "same as", "differs from")),
+            None => string!("unknown"),
+        },
+        env: daemon_env.clone(),
+        shell_env,
+        process_count,
+        max_processes: max_processes(&config::read().runner),
         role: config::read().get_role_name().to_string(),
+        host: opm::process::host::current().to_string(),
+        shared,
+        stale_logs,
         pid_file: format!("{}  ", global!("opm.pid")),
+        socket: format!("{}{}  ", global!("opm.socket"), ternary!(answer.is_some(), " (answering)", "")),
         status: ColoredString(ternary!(
             daemon_running,
             "online".green().bold(),
             "stopped".red().bold()
         )),
+        webui: ternary!(check_webui, Some(check::check(&crate::webui::templates())), None),
     }];
 
     let table = Table::new(data.clone())
@@ -354,19 +1159,44 @@ pub fn health(format: &String) {
         .to_string();
 
     if let Ok(json) = serde_json::to_string(&data[0]) {
-        match format.as_str() {
+        match format {
             "raw" => println!("{:?}", data[0]),
             "json" => println!("{json}"),
             "default" => {
-                println!(
+                notice!(
                     "{}\n{table}\n",
                     format!("OPM daemon information").on_bright_white().black()
                 );
-                println!(
+                if limits.memory_over() || limits.cpu_over() {
+                    alert!("{} The daemon is over daemon.self_limits: {}", *helpers::WARN, limits.describe());
+                }
+                if near_max_processes(data[0].process_count, data[0].max_processes) {
+                    alert!("{} {} of at most {} processes are managed, see runner.max_processes", *helpers::WARN, data[0].process_count, data[0].max_processes);
+                }
+                if let Some(space) = log_space.as_ref().filter(|space| space.low()) {
+                    alert!("{} Only {} left for process logs in {}, below {}%", *helpers::WARN, helpers::format_memory(space.free), space.path, logspace::WARN_PERCENT);
+                }
+                for sign in &data[0].shared {
+                    alert!("{} {} looks shared with another host: {sign}", *helpers::WARN, global!("opm.base"));
+                }
+                if !data[0].stale_logs.is_empty() {
+                    alert!(
+                        "{} Logs outside of runner.log_path, see `opm migrate-logs`: {}",
+                        *helpers::WARN,
+                        data[0].stale_logs.join(", ")
+                    );
+                }
+                if let Some(report) = &data[0].webui {
+                    match report.ok {
+                        true => notice!("{} WebUI self-check passed, {} page(s) and {} asset(s)\n", *helpers::SUCCESS, report.templates, report.assets),
+                        false => report.lines().iter().for_each(|line| alert!("{} WebUI self-check: {line}", *helpers::FAIL)),
+                    }
+                }
+                notice!(
                     " {}",
                     format!("Use `opm daemon restart` to restart the daemon").white()
                 );
-                println!(
+                notice!(
                     " {}",
                     format!("Use `opm daemon reset` to clean process id values").white()
                 );
@@ -376,9 +1206,53 @@ pub fn health(format: &String) {
     };
 }
 
+pub fn stats(format: &str, reset: bool) {
+    let Some(mut client) = ipc::connect() else {
+        crashln!("{} The daemon is not running, or does not answer on {}", *helpers::FAIL, global!("opm.socket"));
+    };
+
+    let answer = client.request(&ipc::Request::Stats { reset }).and_then(|data| serde_json::from_value::<counters::Snapshot>(data).map_err(|err| err.to_string()));
+    let snapshot = match answer {
+        Ok(snapshot) => snapshot,
+        Err(err) => crashln!("{} Cannot read the daemon counters: {err}", *helpers::FAIL),
+    };
+
+    #[derive(Tabled)]
+    struct Row {
+        counter: &'static str,
+        count: u64,
+        #[tabled(rename = "per minute")]
+        rate: String,
+    }
+
+    // Under a second of counting says nothing about a rate
+    let minutes = (Utc::now() - snapshot.since).num_milliseconds().max(1000) as f64 / 60_000.0;
+    let rows = counters::Counter::ALL.iter().map(|&counter| Row {
+        counter: counter.name(),
+        count: snapshot.get(counter),
+        rate: format!("{:.2}", snapshot.get(counter) as f64 / minutes),
+    });
+
+    match format {
+        "json" => println!("{}", json!(snapshot)),
+        "default" => {
+            let table = Table::new(rows).with(Style::rounded().remove_horizontals()).with(BorderColor::filled(Color::FG_BRIGHT_BLACK)).to_string();
+            notice!("{}\n{table}\n", format!("OPM daemon counters for the last {}", helpers::format_duration(snapshot.since)).on_bright_white().black());
+            if reset {
+                notice!("{} Counters reset to 0", *helpers::SUCCESS);
+            }
+        }
+        _ => {}
+    }
+}
+
 pub fn stop() {
+    if let Some(lock) = pid::foreign() {
+        crashln!("{} The daemon runs on {} (pid {}), stop it there", *helpers::FAIL, lock.host, lock.pid);
+    }
+
     if pid::exists() {
-        println!("{} Stopping OPM daemon", *helpers::SUCCESS);
+        notice!("{} Stopping OPM daemon", *helpers::SUCCESS);
 
         match pid::read() {
             Ok(pid) => {
@@ -387,14 +1261,14 @@ pub fn stop() {
                 }
                 pid::remove();
                 log!("[daemon] stopped", "pid" => pid);
-                println!("{} OPM daemon stopped", *helpers::SUCCESS);
+                notice!("{} OPM daemon stopped", *helpers::SUCCESS);
             }
             Err(err) => {
                 // PID file exists but can't be read (corrupted or invalid)
                 log!("[daemon] removing corrupted PID file", "error" => err);
-                println!("{} PID file is corrupted, removing it", *helpers::SUCCESS);
+                notice!("{} PID file is corrupted, removing it", *helpers::SUCCESS);
                 pid::remove();
-                println!("{} OPM daemon stopped", *helpers::SUCCESS);
+                notice!("{} OPM daemon stopped", *helpers::SUCCESS);
             }
         }
     } else {
@@ -402,15 +1276,86 @@ pub fn stop() {
     }
 }
 
+pub fn reload_config() {
+    let pid = match pid::read() {
+        Ok(pid) if pid::running(pid.get()) => pid,
+        _ => crashln!("{} The daemon is not running", *helpers::FAIL),
+    };
+
+    // The daemon would keep its current settings, but the mistake is clearer here
+    if let Err(err) = config::try_read() {
+        crashln!("{} {err}", *helpers::FAIL);
+    }
+
+    let report_path = reload::report_path();
+    let _ = std::fs::remove_file(&report_path);
+
+    if unsafe { libc::kill(pid.get(), libc::SIGHUP) } != 0 {
+        crashln!("{} Failed to signal the daemon (pid={pid})", *helpers::FAIL);
+    }
+
+    // The daemon applies the reload between monitoring cycles, which wake up on the signal
+    let mut report = None;
+    for _ in 0..100 {
+        if let Ok(contents) = std::fs::read(&report_path) {
+            report = serde_json::from_slice::<reload::Report>(&contents).ok();
+            break;
+        }
+        sleep(Duration::from_millis(50));
+    }
+
+    let Some(report) = report else {
+        crashln!("{} The daemon did not confirm the reload, see {}", *helpers::FAIL, global!("opm.daemon.log"));
+    };
+
+    print_reload(&report);
+}
+
+fn print_reload(report: &reload::Report) {
+    if let Some(err) = &report.error {
+        crashln!("{} Daemon kept its current config: {err}", *helpers::FAIL);
+    }
+
+    if report.applied.is_empty() && report.restart_required.is_empty() {
+        notice!("{} Daemon config is up to date", *helpers::SUCCESS);
+        return;
+    }
+
+    if !report.applied.is_empty() {
+        notice!("{} Reloaded daemon config", *helpers::SUCCESS);
+        for change in &report.applied {
+            notice!("   {} {}: {} -> {}", "+".green(), change.key, change.from, change.to);
+        }
+    }
+
+    if !report.restart_required.is_empty() {
+        alert!("{} Restart the daemon to apply (opm daemon restore)", *helpers::WARN);
+        for change in &report.restart_required {
+            alert!("   {} {}: {} -> {}", "!".yellow(), change.key, change.from, change.to);
+        }
+    }
+}
+
 pub fn start(verbose: bool) {
     if verbose {
-        println!(
+        notice!(
             "{} Spawning OPM daemon (opm_base={})",
             *helpers::SUCCESS,
             global!("opm.base")
         );
     }
 
+    // Its pid means nothing here, so a stale pid file check would start a second daemon fighting over the dump
+    if let Some(lock) = pid::foreign() {
+        crashln!(
+            "{} The daemon of {} runs on {} (pid {}).\n Stop it there, or give each host its own home.",
+            *helpers::FAIL,
+            global!("opm.base"),
+            lock.host,
+            lock.pid
+        );
+    }
+
     if pid::exists() {
         match pid::read() {
             Ok(pid) => {
@@ -426,7 +1371,7 @@ pub fn start(verbose: bool) {
             Err(err) => {
                 // PID file exists but can't be read (corrupted or invalid)
                 log!("[daemon] removing corrupted PID file", "error" => err);
-                println!("{} Removing corrupted PID file", *helpers::SUCCESS);
+                notice!("{} Removing corrupted PID file", *helpers::SUCCESS);
                 pid::remove();
             }
         }
@@ -437,7 +1382,6 @@ pub fn start(verbose: bool) {
     async extern "C" fn init() {
         pid::name("OPM Restart Handler Daemon");
 
-        let config = config::read().daemon;
         let api_enabled = ENABLE_API.load(Ordering::Acquire);
         let ui_enabled = ENABLE_WEBUI.load(Ordering::Acquire);
 
@@ -446,11 +1390,74 @@ pub fn start(verbose: bool) {
             libc::signal(libc::SIGPIPE, handle_sigpipe as usize);
         };
 
+        if pid1::wanted() {
+            pid1::install();
+            log!("[daemon] acting as init, reaping orphans and stopping processes on SIGTERM or SIGINT", "pid" => process::id());
+        }
+
+        reload::install(&config::read());
+
+        // Whatever the daemon inherited from the shell that started it stays out of managed processes
+        unix::set_cloexec_from(3, unix::fd_limit());
+
         DAEMON_START_TIME.set(Utc::now().timestamp_millis() as f64);
 
         pid::write(process::id());
         log!("[daemon] new fork", "pid" => process::id());
 
+        let environment = env::Summary::current();
+        log!("[daemon] environment", "hash" => environment.hash, "vars" => environment.vars, "path" => environment.path.unwrap_or_default());
+
+        // Local tooling talks to the daemon on its socket, with or without the API
+        match ipc::listen(&ipc::path()) {
+            Ok(()) => log!("[daemon] listening for commands", "socket" => ipc::path().display()),
+            Err(err) => log!("[daemon] cannot listen for commands", "socket" => ipc::path().display(), "error" => err),
+        }
+
+        NOTIFICATIONS.spawn_retry_task();
+
+        // Compressing old history can take a while, it stays out of the monitoring loop
+        thread::spawn(|| {
+            loop {
+                housekeep();
+                sleep(HOUSEKEEPING_INTERVAL);
+            }
+        });
+
+        thread::spawn(|| {
+            loop {
+                snapshot_dump();
+                sleep(SNAPSHOT_CHECK);
+            }
+        });
+
+        thread::spawn(|| {
+            loop {
+                rotate_logs();
+                sleep(LOG_ROTATION_CHECK);
+            }
+        });
+
+        thread::spawn(|| {
+            loop {
+                sleep(pid::LOCK_REFRESH);
+                refresh_lock();
+            }
+        });
+
+        // Sampling measures every process, so it keeps its own pace apart from the monitoring loop
+        thread::spawn(|| {
+            loop {
+                match reload::metrics_interval() {
+                    0 => sleep(METRICS_IDLE),
+                    interval => {
+                        sample_metrics();
+                        sleep(Duration::from_secs(interval));
+                    }
+                }
+            }
+        });
+
         if api_enabled {
             log!(
                 "[daemon] Starting API server",
@@ -505,36 +1512,63 @@ pub fn start(verbose: bool) {
             }
         }
 
+        let mut schedule = Schedule::default();
+
         loop {
-            if api_enabled {
+            if let Some(signal) = pid1::stop_requested() {
+                pid1::shutdown(signal);
+            }
+
+            let limits = reload::self_limits();
+
+            if api_enabled || guard::enabled(&limits) {
                 #[cfg(any(target_os = "linux", target_os = "macos"))]
                 {
-                    if let Ok(process_info) = Process::new(process::id()) {
-                        let cpu_usage = get_process_cpu_usage_with_children_from_process(
-                            &process_info,
-                            process::id() as i64,
-                        );
-                        DAEMON_CPU_PERCENTAGE.observe(cpu_usage);
-                        
-                        if let Ok(mem_info) = process_info.memory_info() {
-                            DAEMON_MEM_USAGE.observe(mem_info.rss() as f64);
+                    if let Ok(process_info) = Process::new_fast(process::id()) {
+                        let rss = process_info.memory_info().map(|info| info.rss()).unwrap_or(0);
+                        let (own_cpu, cpu_usage) = get_process_cpu_usage_timed(process::id() as i64);
+                        let usage = guard::SelfUsage::new(rss, own_cpu, &limits);
+
+                        if api_enabled {
+                            DAEMON_CPU_PERCENTAGE.observe(cpu_usage);
+
+                            if let Ok(mem_info) = process_info.memory_info() {
+                                DAEMON_MEM_USAGE.observe(mem_info.rss() as f64);
+                            }
+
+                            DAEMON_SELF_RSS.set(usage.rss as f64);
+                            DAEMON_SELF_MAX_RSS.set(usage.max_rss.unwrap_or_default() as f64);
+                            DAEMON_SELF_CPU.set(usage.cpu_percent);
+                            DAEMON_SELF_MAX_CPU.set(usage.max_cpu_percent.unwrap_or_default());
                         }
+
+                        keep_within(&usage, &limits);
                     }
                 }
             }
 
-            // Wrap restart_process in catch_unwind to prevent daemon crashes
-            // This is a last-resort safety net - restart_process() has internal error handling,
-            // but catch_unwind ensures that even unexpected panics won't crash the daemon.
-            // This is placed in the hot loop because:
-            // 1. restart_process() doesn't return Result, so we can't use traditional error handling
-            // 2. The performance impact is negligible (catch_unwind is lightweight when no panic occurs)
-            // 3. Daemon stability is critical - it manages all processes and must not crash
-            // If a process monitoring operation fails, we log it and continue
-            // This ensures the daemon remains stable even when individual processes fail
-            if !Runner::new().is_empty() {
+            // Every process is checked at its own interval, new ones right away
+            let runner = Runner::new();
+            let default_interval = reload::interval();
+            let intervals: Vec<(usize, u64)> = runner.list.iter().map(|(id, item)| (*id, item.monitor_interval(default_interval))).collect();
+            let due = schedule.due(&intervals, Instant::now());
+
+            if !runner.is_empty() && owns_dump(&runner) {
+                check_log_space();
+                check_process_count(runner.list.len());
+
+                // Wrap restart_process in catch_unwind to prevent daemon crashes
+                // This is a last-resort safety net - restart_process() has internal error handling,
+                // but catch_unwind ensures that even unexpected panics won't crash the daemon.
+                // This is placed in the hot loop because:
+                // 1. restart_process() doesn't return Result, so we can't use traditional error handling
+                // 2. The performance impact is negligible (catch_unwind is lightweight when no panic occurs)
+                // 3. Daemon stability is critical - it manages all processes and must not crash
+                // If a process monitoring operation fails, we log it and continue
+                // This ensures the daemon remains stable even when individual processes fail
                 let result = panic::catch_unwind(|| {
-                    restart_process();
+                    restart_process(&due);
+                    start_pending();
                 });
                 
                 if let Err(err) = result {
@@ -543,13 +1577,38 @@ pub fn start(verbose: bool) {
                     eprintln!("[daemon] Warning: process monitoring encountered an error but daemon continues running");
                 }
             }
-            
-            sleep(Duration::from_millis(config.interval));
+
+            reap_spawned();
+
+            // Leave a report for `opm daemon reload-config`, which sent the SIGHUP
+            if reload::requested() && let Err(err) = reload::write_report(&reload_settings()) {
+                log!("[daemon] failed to write reload report", "error" => err);
+            }
+
+            // Sleep until the next process is due, `daemon.interval` at most
+            reload::sleep(schedule.wait(Instant::now(), Duration::from_millis(reload::interval())));
+        }
+    }
+
+    // Set before forking, the daemon and every process it spawns start from it
+    let overrides = ENV_OVERRIDES.lock().map(|overrides| overrides.clone()).unwrap_or_default();
+    match env::merge(&config::read().daemon.env, &overrides) {
+        Ok(vars) => {
+            let keys = env::apply(&vars);
+            if verbose && !keys.is_empty() {
+                notice!("{} Daemon environment sets {}", *helpers::SUCCESS, keys.join(", "));
+            }
         }
+        Err(err) => crashln!("{} {err}", *helpers::FAIL),
+    }
+
+    // The lifetime of a container is that of its pid 1, which must not fork away
+    if FOREGROUND.load(Ordering::Acquire) || process::id() == 1 {
+        return init();
     }
 
     if verbose {
-        println!(
+        notice!(
             "{} OPM Successfully daemonized (type={})",
             *helpers::SUCCESS,
             global!("opm.daemon.kind")
@@ -594,6 +1653,19 @@ pub fn start(verbose: bool) {
     }
 }
 
+/// Variables `restart` sets in the environment of the daemon, over the ones of `daemon.env`
+pub fn set_env(vars: Vec<(String, String)>) {
+    if let Ok(mut overrides) = ENV_OVERRIDES.lock() {
+        *overrides = vars;
+    }
+}
+
+/// Make `restart` run the daemon in this process, `init` also makes it reap orphans and stop the processes when signalled
+pub fn foreground(init: bool) {
+    FOREGROUND.store(true, Ordering::Release);
+    then!(init, pid1::request());
+}
+
 pub fn restart(api: &bool, webui: &bool, verbose: bool) {
     if pid::exists() {
         stop();
@@ -631,7 +1703,7 @@ pub fn reset() {
             // Save the changes
             runner.save();
 
-            println!("{} Rearranged ID 1 to ID 0", *helpers::SUCCESS);
+            notice!("{} Rearranged ID 1 to ID 0", *helpers::SUCCESS);
             log!("[daemon] rearranged ID 1 to ID 0", "id" => "0");
         }
     }
@@ -643,166 +1715,345 @@ pub fn reset() {
         None => runner.set_id(Id::new(0)),
     }
 
-    println!(
+    notice!(
         "{} Successfully reset (index={})",
         *helpers::SUCCESS,
         runner.id
     );
 }
 
-pub fn setup() {
-    use std::env;
-    use std::fs;
-    use std::path::Path;
+pub fn setup(uninstall: bool, dry_run: bool, yes: bool) {
+    match uninstall {
+        true => service::uninstall(dry_run),
+        false => service::install(dry_run, yes),
+    }
+}
 
-    println!("{} Setting up OPM systemd service...", *helpers::SUCCESS);
+pub mod pid;
 
-    // Get the current user's home directory
-    let home_dir = match home::home_dir() {
-        Some(dir) => dir,
-        None => crashln!("{} Unable to determine home directory", *helpers::FAIL),
-    };
+/// `check_processes` against real short-lived processes, in an opm home of its own.
+/// They spawn processes and wait on them for seconds, run them with `cargo test -- --ignored`.
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+    use opm::process::{StartOptions, backend::{DumpFile, RunnerBackend}};
+    use std::{fs, path::PathBuf, sync::{Arc, Once}};
 
-    // Get the path to the opm binary
-    let opm_binary = match env::current_exe() {
-        Ok(path) => path,
-        Err(err) => crashln!("{} Unable to determine opm binary path: {}", *helpers::FAIL, err),
-    };
+    static HOME: Once = Once::new();
 
-    let opm_binary_str = opm_binary.to_string_lossy();
+    /// A cycle of the daemon, far shorter than the default `daemon.interval`
+    const INTERVAL: Duration = Duration::from_millis(100);
 
-    // Determine systemd service directory
-    // For user services: ~/.config/systemd/user/
-    // For system services: /etc/systemd/system/ (requires root)
-    let is_root = unsafe { libc::geteuid() == 0 };
+    /// Points `HOME` at a temporary directory before the globals are set up, so config, logs and events stay out of `~/.opm`
+    fn home() -> PathBuf {
+        let path = std::env::temp_dir().join(format!("opm-daemon-{}", process::id()));
 
-    let (service_dir_path, install_target) = if is_root {
-        (
-            Path::new("/etc/systemd/system").to_path_buf(),
-            "multi-user.target",
-        )
-    } else {
-        (
-            home_dir.join(".config/systemd/user"),
-            "default.target",
-        )
-    };
+        HOME.call_once(|| {
+            let _ = fs::remove_dir_all(&path);
+            fs::create_dir_all(&path).unwrap();
+            unsafe { std::env::set_var("HOME", &path) };
+            crate::globals::init();
+        });
 
-    let service_dir = service_dir_path.as_path();
+        path
+    }
 
-    // Create service directory if it doesn't exist
-    if !service_dir.exists() {
-        if let Err(err) = fs::create_dir_all(service_dir) {
-            crashln!(
-                "{} Failed to create service directory {:?}: {}",
-                *helpers::FAIL,
-                service_dir,
-                err
-            );
+    /// Processes kept in a dump of their own, stopped again when the test ends
+    struct Dump(Arc<dyn RunnerBackend>);
+
+    impl Dump {
+        fn new(name: &str) -> Dump {
+            let path = home().join(format!("{name}.dump"));
+            let _ = fs::remove_file(&path);
+            Dump(Arc::new(DumpFile(path)))
+        }
+
+        fn runner(&self) -> Runner { Runner::with_backend(self.0.clone()) }
+
+        fn start(&self, name: &str, script: &str, watch: Option<String>, max_memory: u64) -> usize {
+            let mut runner = self.runner();
+            runner.start(&string!(name), &string!(script), home(), &StartOptions { watch, max_memory, ..Default::default() });
+            runner.save();
+            runner.find(name, &string!("internal")).unwrap()
+        }
+
+        fn process(&self, id: usize) -> opm::process::Process { self.runner().info(id).unwrap().clone() }
+
+        /// Runs daemon cycles over `due` until `done` holds for process `id`
+        fn cycle_until(&self, due: &[usize], supervision: &Supervision, id: usize, what: &str, mut done: impl FnMut(&opm::process::Process) -> bool) {
+            let deadline = Instant::now() + Duration::from_secs(15);
+
+            while !done(&self.process(id)) {
+                assert!(Instant::now() < deadline, "timed out waiting for {what}");
+                self.cycle(due, supervision);
+            }
+        }
+
+        /// One cycle, how long `check_processes` took
+        fn cycle(&self, due: &[usize], supervision: &Supervision) -> Duration {
+            let started = Instant::now();
+            check_processes(due, supervision, || self.runner());
+            let took = started.elapsed();
+
+            reap_spawned();
+            sleep(INTERVAL);
+            took
         }
     }
 
-    let service_file_path = service_dir.join("opm.service");
-    let opm_dir = global!("opm.base");
-    let pid_file = global!("opm.pid");
-
-    // Generate service file content
-    let service_content = if is_root {
-        format!(
-            r#"# OPM Daemon systemd service file (system-wide)
-
-[Unit]
-Description=OPM Process Manager Daemon
-After=network.target
-
-[Service]
-Type=forking
-WorkingDirectory={}
-PIDFile={}
-ExecStart={} daemon start
-ExecStop={} daemon stop
-Restart=on-failure
-RestartSec=5s
-LimitNOFILE=infinity
-LimitNPROC=infinity
-LimitCORE=infinity
-
-[Install]
-WantedBy={}
-"#,
-            opm_dir,
-            pid_file,
-            opm_binary_str,
-            opm_binary_str,
-            install_target
-        )
-    } else {
-        format!(
-            r#"# OPM Daemon systemd service file (user service)
-
-[Unit]
-Description=OPM Process Manager Daemon
-After=network.target
-
-[Service]
-Type=forking
-WorkingDirectory={}
-PIDFile={}
-ExecStart={} daemon start
-ExecStop={} daemon stop
-Restart=on-failure
-RestartSec=5s
-
-[Install]
-WantedBy={}
-"#,
-            opm_dir,
-            pid_file,
-            opm_binary_str,
-            opm_binary_str,
-            install_target
-        )
-    };
+    impl Drop for Dump {
+        fn drop(&mut self) {
+            let mut runner = self.runner();
+            let alive: Vec<usize> = runner.list.iter().filter(|(_, item)| opm::process::is_pid_alive(item.pid)).map(|(id, _)| *id).collect();
 
-    // Write service file
-    if let Err(err) = fs::write(&service_file_path, service_content) {
-        crashln!(
-            "{} Failed to write service file to {:?}: {}",
-            *helpers::FAIL,
-            service_file_path,
-            err
-        );
+            for id in alive {
+                runner.stop(id, None);
+            }
+            runner.save();
+        }
     }
 
-    println!(
-        "{} Service file created at: {}",
-        *helpers::SUCCESS,
-        service_file_path.display()
-    );
+    fn supervision(restarts: u64) -> Supervision { Supervision { restarts, restart_delay: (0, 0), log_checkpoint: 0, cores: Cores::default(), watch_hash_threads: 2 } }
+
+    #[test]
+    #[ignore = "it runs real processes for several seconds"]
+    fn test_crashing_process_gives_up_after_its_restarts() {
+        let dump = Dump::new("crash");
+        let supervision = supervision(2);
+        let crashing = dump.start("crashing", "sleep 0.3; exit 1", None, 0);
+        let stable = dump.start("stable", "sleep 60", None, 0);
+        let stable_pid = dump.process(stable).pid;
+        let due = [crashing, stable];
+
+        // Every crash is counted once, then the next cycle restarts it
+        let mut counted = vec![];
+        dump.cycle_until(&due, &supervision, crashing, "the crashing process to give up", |item| {
+            if counted.last() != Some(&item.crash.value) {
+                counted.push(item.crash.value);
+            }
+            !item.running
+        });
+
+        // "restarts: 2" allows two restarts, the third crash is over the budget
+        assert_eq!(counted, [0, 1, 2, 3]);
+        let item = dump.process(crashing);
+        assert_eq!(item.crash.value, 3);
+        assert_eq!(item.restarts, 2);
+        assert!(item.crash.crashed);
+        assert_eq!(item.pid, 0);
+
+        // Given up for good, further cycles leave it alone
+        for _ in 0..5 {
+            dump.cycle(&due, &supervision);
+        }
+        let item = dump.process(crashing);
+        assert!(!item.running);
+        assert_eq!((item.crash.value, item.restarts), (3, 2));
 
-    // Provide instructions for enabling the service
-    if is_root {
-        println!("\n{} To enable and start the OPM daemon:", *helpers::SUCCESS);
-        println!("  sudo systemctl daemon-reload");
-        println!("  sudo systemctl enable opm.service");
-        println!("  sudo systemctl start opm.service");
-        println!("\n{} To check daemon status:", *helpers::SUCCESS);
-        println!("  sudo systemctl status opm.service");
-    } else {
-        println!("\n{} To enable and start the OPM daemon:", *helpers::SUCCESS);
-        println!("  systemctl --user daemon-reload");
-        println!("  systemctl --user enable opm.service");
-        println!("  systemctl --user start opm.service");
-        println!("\n{} To enable lingering (start daemon at boot):", *helpers::SUCCESS);
-        println!("  loginctl enable-linger $USER");
-        println!("\n{} To check daemon status:", *helpers::SUCCESS);
-        println!("  systemctl --user status opm.service");
-    }
-
-    println!(
-        "\n{} Setup complete! The OPM daemon will now start automatically with the system.",
-        *helpers::SUCCESS
-    );
-}
+        let item = dump.process(stable);
+        assert!(item.running);
+        assert_eq!((item.pid, item.crash.value, item.restarts), (stable_pid, 0, 0));
+    }
 
-pub mod pid;
+    #[test]
+    #[ignore = "it runs real processes for several seconds"]
+    fn test_crash_counter_progression() {
+        let dump = Dump::new("progression");
+        let supervision = supervision(10);
+        let id = dump.start("flaky", "sleep 0.3; exit 1", None, 0);
+
+        for crashes in 1..=3 {
+            // Counted once while it waits for the next cycle to restart it
+            dump.cycle_until(&[id], &supervision, id, "the crash to be counted", |item| item.crash.crashed);
+            assert_eq!(dump.process(id).crash.value, crashes);
+
+            dump.cycle_until(&[id], &supervision, id, "the restart", |item| !item.crash.crashed);
+            let item = dump.process(id);
+            assert!(item.running && item.pid > 0);
+            assert_eq!((item.crash.value, item.restarts), (crashes, crashes));
+        }
+    }
+
+    #[test]
+    #[ignore = "it runs real processes for several seconds"]
+    fn test_crash_restarts_back_off() {
+        let dump = Dump::new("backoff");
+        let supervision = Supervision { restart_delay: (400, 1000), ..supervision(10) };
+        let id = dump.start("failing", "sleep 0.2; exit 1", None, 0);
+
+        dump.cycle_until(&[id], &supervision, id, "the first crash", |item| item.crash.crashed);
+        let next_restart = dump.process(id).crash.next_restart.expect("a crash schedules its restart");
+        // 400ms doubled for the first crash
+        assert!(next_restart > Utc::now() + chrono::Duration::milliseconds(500));
+
+        // Cycles before then leave it alone
+        dump.cycle(&[id], &supervision);
+        let item = dump.process(id);
+        assert!(item.crash.crashed && item.running);
+        assert_eq!(item.restarts, 0);
+
+        dump.cycle_until(&[id], &supervision, id, "the restart", |item| !item.crash.crashed);
+        assert!(Utc::now() >= next_restart);
+        let item = dump.process(id);
+        assert_eq!((item.restarts, item.crash.next_restart), (1, None));
+    }
+
+    #[test]
+    #[ignore = "it runs real processes for several seconds"]
+    fn test_watched_path_change_reloads() {
+        let dump = Dump::new("watch");
+        let watched = home().join("watched");
+        fs::create_dir_all(&watched).unwrap();
+        fs::write(watched.join("config.txt"), "first").unwrap();
+
+        let id = dump.start("watched", "sleep 60", Some(watched.display().to_string()), 0);
+        let pid = dump.process(id).pid;
+
+        // Nothing changed, nothing reloads
+        for _ in 0..3 {
+            dump.cycle(&[id], &supervision(10));
+        }
+        assert_eq!(dump.process(id).pid, pid);
+
+        fs::write(watched.join("config.txt"), "second").unwrap();
+        dump.cycle_until(&[id], &supervision(10), id, "the watch reload", |item| item.pid != pid);
+
+        let item = dump.process(id);
+        assert!(item.running);
+        assert_eq!(item.restarts, 1);
+        assert_eq!(item.crash.value, 0);
+        assert_eq!(item.last_restart_reason.as_deref(), Some("watch"));
+        assert!(item.watch.last_reload.is_some());
+        assert!(!opm::process::is_pid_alive(pid));
+    }
+
+    #[test]
+    #[ignore = "it runs real processes for several seconds"]
+    fn test_watch_hashing_leaves_the_loop() {
+        // Deep and wide enough to take longer than a cycle to hash
+        let mut deep = home().join("deep");
+        for level in 0..60 {
+            deep.push(format!("level-{level}"));
+            fs::create_dir_all(&deep).unwrap();
+            for file in 0..500 {
+                fs::write(deep.join(format!("{file}.js")), vec![level as u8; 4096]).unwrap();
+            }
+        }
+
+        let watched = home().join("deep").display().to_string();
+        let hashing = Instant::now();
+        opm::process::hash::create(home().join("deep"));
+        assert!(hashing.elapsed() > INTERVAL, "the tree hashed in {:?}, too fast to hold up a cycle", hashing.elapsed());
+
+        let dump = Dump::new("deep");
+        let supervision = supervision(10);
+        let watcher = dump.start("watcher", "sleep 60", Some(watched), 0);
+        let crashing = dump.start("crashing", "sleep 0.5; exit 1", None, 0);
+        let watcher_pid = dump.process(watcher).pid;
+        let due = [watcher, crashing];
+
+        // Every cycle hands the hash out or finds it in flight, the crash is seen within one interval of the exit
+        let mut slowest = Duration::ZERO;
+        let deadline = Instant::now() + Duration::from_secs(15);
+        while dump.process(crashing).crash.value == 0 {
+            assert!(Instant::now() < deadline, "timed out waiting for the crash");
+            slowest = slowest.max(dump.cycle(&due, &supervision));
+        }
+
+        assert!(slowest < INTERVAL, "a cycle took {slowest:?} while the watched tree was hashed");
+        assert_eq!(dump.process(watcher).pid, watcher_pid, "an unchanged tree does not reload");
+    }
+
+    #[test]
+    #[ignore = "it runs real processes for several seconds"]
+    fn test_memory_limit_stops_after_grace_period() {
+        let dump = Dump::new("memory");
+        let id = dump.start("hog", r#"awk 'BEGIN { s = "x"; while (length(s) < 64000000) s = s s; system("sleep 60") }'"#, None, 32 * 1024 * 1024);
+
+        let mut runner = dump.runner();
+        runner.process(id).startup_grace = Some(1);
+        runner.save();
+        let started = Instant::now();
+
+        dump.cycle_until(&[id], &supervision(10), id, "the memory limit to stop it", |item| !item.running);
+
+        // Limits only apply once the process is online, it is stopped and not counted as a crash
+        assert!(started.elapsed() >= Duration::from_secs(1));
+        let item = dump.process(id);
+        assert!(!item.crash.crashed);
+        assert_eq!((item.crash.value, item.restarts), (0, 0));
+        assert!(!opm::process::is_pid_alive(item.pid));
+    }
+
+    #[test]
+    #[ignore = "it runs real processes for several seconds"]
+    fn test_memory_limit_restarts() {
+        let dump = Dump::new("memory-restart");
+        let id = dump.start("hog", r#"awk 'BEGIN { s = "x"; while (length(s) < 64000000) s = s s; system("sleep 60") }'"#, None, 32 * 1024 * 1024);
+        let pid = dump.process(id).pid;
+
+        let mut runner = dump.runner();
+        runner.process(id).startup_grace = Some(1);
+        runner.set_max_memory_action(id, memlimit::Action::Restart).save();
+
+        dump.cycle_until(&[id], &supervision(10), id, "the memory limit to restart it", |item| item.pid != pid);
+
+        // Reloaded and counted apart from crashes
+        let item = dump.process(id);
+        assert!(item.running);
+        assert_eq!((item.memory_restarts, item.crash.value), (1, 0));
+        assert_eq!(item.last_restart_reason.as_deref(), Some("memory"));
+        assert!(!opm::process::is_pid_alive(pid));
+    }
+
+    #[test]
+    #[ignore = "it runs real processes for several seconds"]
+    fn test_memory_limit_restart_failure_is_not_counted() {
+        let dump = Dump::new("memory-restart-failure");
+        let id = dump.start("hog", r#"awk 'BEGIN { s = "x"; while (length(s) < 64000000) s = s s; system("sleep 60") }'"#, None, 32 * 1024 * 1024);
+
+        // A working directory that is gone cannot be restarted in
+        let mut runner = dump.runner();
+        let process = runner.process(id);
+        (process.startup_grace, process.path) = (Some(1), home().join("removed"));
+        runner.set_max_memory_action(id, memlimit::Action::Restart).save();
+
+        dump.cycle_until(&[id], &supervision(10), id, "the memory limit restart to fail", |item| !item.running);
+
+        let item = dump.process(id);
+        assert_eq!(item.memory_restarts, 0);
+        assert!(item.crash.crashed);
+    }
+
+    #[test]
+    #[ignore = "it checks a full runner of processes for several seconds"]
+    fn test_cycle_at_max_processes() {
+        let dump = Dump::new("capacity");
+        // Outlives the cycle, which reads the dump of every entry again and takes minutes in a debug build
+        let mut child = std::process::Command::new("sleep").arg("3600").spawn().unwrap();
+        let pid = child.id() as i64;
+        let limit = opm::process::DEFAULT_MAX_PROCESSES;
+
+        // Entries that are never spawned, all of them watching the same live pid
+        let mut runner = dump.runner();
+        for n in 0..limit {
+            let id = runner.register(&format!("dummy-{n}"), "sleep 60", home(), &StartOptions::default());
+            let process = runner.process(id);
+            (process.pid, process.running, process.pending, process.startup_grace) = (pid, true, false, Some(0));
+        }
+        runner.save();
+        assert!(runner.check_capacity(1, limit).is_err());
+
+        let items = dump.runner().fetch();
+        assert_eq!(items.len(), limit);
+        assert!(items.iter().all(|item| item.pid == pid && item.status == "online"));
+
+        let due: Vec<usize> = runner.list.keys().copied().collect();
+        dump.cycle(&due, &supervision(10));
+        assert!(dump.runner().list.values().all(|item| item.running && item.pid == pid && item.crash.value == 0));
+
+        // Gone before the dump is dropped, nothing is left for it to stop
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+}
@@ -1,11 +1,16 @@
 use anyhow::{Result, anyhow};
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, TimeDelta, Utc};
 use core::fmt;
 use global_placeholders::global;
 use macros_rs::crashln;
-use opm::{file::Exists, helpers};
+use opm::{file::Exists, helpers, process::host};
 use serde::{Deserialize, Serialize};
-use std::{convert::TryFrom, fs, io};
+use std::{convert::TryFrom, fs, io, time::Duration};
+
+/// How often the running daemon refreshes its lock
+pub const LOCK_REFRESH: Duration = Duration::from_secs(15);
+/// A lock of another host not refreshed for this long is left over from a daemon that is gone
+const LOCK_STALE_SECS: i64 = 60;
 
 #[derive(Copy, Clone, Serialize, Deserialize)]
 pub struct Pid(i32);
@@ -26,6 +31,42 @@ impl fmt::Display for Pid {
     }
 }
 
+/// The host and pid of the daemon using this opm home, kept beside the pid file.
+/// A pid only means something on the machine that wrote it, so a second host sharing `~/.opm` reads the lock instead.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Lock {
+    pub host: String,
+    pub pid: i32,
+    pub refreshed: DateTime<Utc>,
+}
+
+impl Lock {
+    pub fn foreign(&self) -> bool { host::is_foreign(Some(&self.host)) }
+
+    /// Whether the daemon holding it still refreshed it lately, the only sign of life a lock of another host gives
+    pub fn live(&self, now: DateTime<Utc>) -> bool { now - self.refreshed < TimeDelta::seconds(LOCK_STALE_SECS) }
+}
+
+pub fn lock() -> Option<Lock> {
+    let contents = fs::read_to_string(global!("opm.lock")).ok()?;
+    serde_json::from_str(&contents).ok()
+}
+
+/// The lock of a daemon running on another host, which this host must neither start beside nor stop
+pub fn foreign() -> Option<Lock> {
+    lock().filter(|lock| lock.foreign() && lock.live(Utc::now()))
+}
+
+/// Record this daemon in the lock, the daemon does it again every `LOCK_REFRESH`
+pub fn claim(pid: u32) {
+    let lock = Lock { host: host::current().to_string(), pid: pid as i32, refreshed: Utc::now() };
+
+    let contents = serde_json::to_string(&lock).unwrap_or_default();
+    if let Err(err) = fs::write(global!("opm.lock"), contents) {
+        log::error!("Failed to write lock file: {}", err);
+    }
+}
+
 pub fn exists() -> bool {
     fs::metadata(global!("opm.pid")).is_ok()
 }
@@ -33,6 +74,11 @@ pub fn running(pid: i32) -> bool {
     unsafe { libc::kill(pid, 0) == 0 }
 }
 
+/// Whether the pid file names a daemon that is still running
+pub fn alive() -> bool {
+    read().is_ok_and(|pid| running(pid.get()))
+}
+
 pub fn uptime() -> io::Result<DateTime<Utc>> {
     let metadata = fs::metadata(global!("opm.pid"))?;
     let creation_time = metadata.created()?;
@@ -54,9 +100,20 @@ pub fn write(pid: u32) {
     if let Err(err) = fs::write(global!("opm.pid"), pid.to_string()) {
         crashln!("{} Failed to write PID to file: {}", *helpers::FAIL, err);
     }
+    claim(pid);
 }
 
 pub fn remove() {
+    // The pid of a daemon on another host is never running here, yet its files are not stale
+    if let Some(lock) = foreign() {
+        log::warn!("Keeping the PID file of the daemon on {} (pid {}).", lock.host, lock.pid);
+        return;
+    }
+
+    if lock().is_some() && let Err(err) = fs::remove_file(global!("opm.lock")) {
+        log::error!("Failed to remove lock file: {}", err);
+    }
+
     if Exists::check(&global!("opm.pid")).file() {
         log::warn!("Stale PID file detected. Removing the PID file.");
         if let Err(err) = fs::remove_file(global!("opm.pid")) {
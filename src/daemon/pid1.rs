@@ -0,0 +1,98 @@
+//! The daemon as the init of a container, where nothing else reaps orphans or stops the processes
+
+use super::{ipc, pid};
+use nix::sys::signal::Signal;
+use opm::{
+    config,
+    process::{Runner, reap_all, shutdown},
+};
+
+use std::{
+    process,
+    sync::atomic::{AtomicBool, AtomicI32, Ordering},
+    thread,
+    time::Duration,
+};
+
+/// How often the reaper looks for a SIGCHLD, reaping itself is left to a thread that may wait on spawns
+const REAP_POLL: Duration = Duration::from_millis(50);
+
+static WANTED: AtomicBool = AtomicBool::new(false);
+static CHILD_EXITED: AtomicBool = AtomicBool::new(false);
+/// Signal that asked the daemon to stop, 0 while none did
+static STOP: AtomicI32 = AtomicI32::new(0);
+
+/// Act as init whatever the pid, for `opm daemon start --pid1`
+pub fn request() { WANTED.store(true, Ordering::Release) }
+
+/// Whether the daemon acts as init: asked to, or running as pid 1 of a pid namespace
+pub fn wanted() -> bool { WANTED.load(Ordering::Acquire) || process::id() == 1 }
+
+extern "C" fn handle_sigchld(_: libc::c_int) {
+    // Only flag it, reaping waits for spawns reading the status of their own child
+    CHILD_EXITED.store(true, Ordering::Release);
+}
+
+extern "C" fn handle_stop(signal: libc::c_int) {
+    // Stopping the processes takes seconds, the monitoring loop does it between cycles
+    STOP.store(signal, Ordering::Release);
+}
+
+/// Reap exited children on SIGCHLD and stop the processes on SIGTERM or SIGINT, replacing the handler that just exits
+pub fn install() {
+    unsafe {
+        libc::signal(libc::SIGCHLD, handle_sigchld as *const () as usize);
+        libc::signal(libc::SIGTERM, handle_stop as *const () as usize);
+        libc::signal(libc::SIGINT, handle_stop as *const () as usize);
+    }
+
+    thread::spawn(|| {
+        loop {
+            if CHILD_EXITED.swap(false, Ordering::AcqRel) {
+                reap();
+            }
+            thread::sleep(REAP_POLL);
+        }
+    });
+}
+
+fn reap() {
+    for pid in reap_all() {
+        log!("[daemon] reaped exited child", "pid" => pid);
+    }
+}
+
+/// The signal that asked the daemon to stop, if one did
+pub fn stop_requested() -> Option<i32> {
+    match STOP.load(Ordering::Acquire) {
+        0 => None,
+        signal => Some(signal),
+    }
+}
+
+/// Stop every running process and exit, once pid 1 is gone the kernel would kill them without a chance to clean up.
+///
+/// Processes go down level by level, before the ones they depend on and newest first otherwise. Each one gets
+/// SIGTERM and `daemon.kill_timeout` to exit before SIGKILL, its children stopped along with it. The dump keeps
+/// them marked as running, so they are restarted when the container starts again.
+pub fn shutdown(signal: i32) -> ! {
+    let mut runner = Runner::new();
+    let running: Vec<usize> = runner.list.iter().filter(|(_, item)| item.running).map(|(id, _)| *id).collect();
+    log!("[daemon] stopping processes before exiting", "signal" => signal, "processes" => running.len());
+
+    shutdown::run(&mut runner, &running, None, shutdown::kill_timeout(&config::read().daemon), Signal::SIGTERM, |summary| {
+        log!(
+            "[daemon] shutdown level done",
+            "level" => format!("{}/{}", summary.level, summary.levels),
+            "stopped" => summary.stopped.join(","),
+            "killed" => summary.killed.join(","),
+            "elapsed" => format!("{:.2}s", summary.elapsed.as_secs_f64())
+        );
+    });
+
+    reap();
+    pid::remove();
+    let _ = std::fs::remove_file(ipc::path());
+    log!("[daemon] killed", "pid" => process::id());
+    process::exit(0)
+}
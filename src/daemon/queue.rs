@@ -0,0 +1,118 @@
+//! Actions asked for while another one still runs on the same process, run in order once it is done.
+//! Only API and socket requests go through here, the CLI without a daemon acts right away.
+
+use once_cell::sync::Lazy;
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    sync::{Mutex, MutexGuard},
+    thread,
+};
+
+/// Methods that wait for the running one, anything else runs right away
+pub const QUEUED: [&str; 5] = ["start", "stop", "kill", "restart", "reload"];
+
+static QUEUE: Lazy<Mutex<Queue>> = Lazy::new(|| Mutex::new(Queue::default()));
+
+/// Which processes have an action running and what waits behind it
+#[derive(Default, Debug)]
+pub struct Queue {
+    busy: HashSet<usize>,
+    pending: HashMap<usize, VecDeque<String>>,
+}
+
+impl Queue {
+    /// Claim the process for `method`, or queue it behind the running action and return its position.
+    /// The same method twice in a row is queued once, both callers get the same position.
+    pub fn submit(&mut self, id: usize, method: &str) -> Option<usize> {
+        if self.busy.insert(id) {
+            return None;
+        }
+
+        let pending = self.pending.entry(id).or_default();
+        if pending.back().map(String::as_str) != Some(method) {
+            pending.push_back(method.to_string());
+        }
+
+        Some(pending.len())
+    }
+
+    /// The next action once the running one is done, the process stays claimed until nothing is left
+    pub fn next(&mut self, id: usize) -> Option<String> {
+        let next = self.pending.get_mut(&id).and_then(VecDeque::pop_front);
+
+        if next.is_none() {
+            self.pending.remove(&id);
+            self.busy.remove(&id);
+        }
+
+        next
+    }
+
+    pub fn pending(&self, id: usize) -> Vec<String> { self.pending.get(&id).map(|pending| pending.iter().cloned().collect()).unwrap_or_default() }
+
+    /// Drop the queued actions, the running one still completes
+    pub fn cancel(&mut self, id: usize) -> Vec<String> { self.pending.remove(&id).map(Vec::from).unwrap_or_default() }
+}
+
+pub fn lock() -> MutexGuard<'static, Queue> { QUEUE.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) }
+
+/// Hand the process back after its action, running whatever was queued meanwhile on a thread of its own
+pub fn done(id: usize, run: fn(usize, &str)) {
+    let Some(first) = lock().next(id) else { return };
+
+    thread::spawn(move || {
+        let mut next = Some(first);
+        while let Some(method) = next {
+            run(id, &method);
+            next = lock().next(id);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_actions_wait_for_the_running_one() {
+        let mut queue = Queue::default();
+
+        assert_eq!(queue.submit(0, "stop"), None);
+        assert_eq!(queue.submit(0, "restart"), Some(1));
+        assert_eq!(queue.submit(0, "reload"), Some(2));
+        // Other processes are not held up
+        assert_eq!(queue.submit(1, "restart"), None);
+
+        assert_eq!(queue.next(0).as_deref(), Some("restart"));
+        assert_eq!(queue.pending(0), vec!["reload"]);
+        assert_eq!(queue.next(0).as_deref(), Some("reload"));
+        assert_eq!(queue.next(0), None);
+
+        // Released once nothing is left
+        assert_eq!(queue.submit(0, "start"), None);
+    }
+
+    #[test]
+    fn test_consecutive_duplicates_are_coalesced() {
+        let mut queue = Queue::default();
+        queue.submit(0, "stop");
+
+        assert_eq!(queue.submit(0, "restart"), Some(1));
+        assert_eq!(queue.submit(0, "restart"), Some(1));
+        assert_eq!(queue.submit(0, "stop"), Some(2));
+        assert_eq!(queue.submit(0, "restart"), Some(3));
+        assert_eq!(queue.pending(0), vec!["restart", "stop", "restart"]);
+    }
+
+    #[test]
+    fn test_cancel_keeps_the_running_action() {
+        let mut queue = Queue::default();
+        queue.submit(0, "stop");
+        queue.submit(0, "restart");
+
+        assert_eq!(queue.cancel(0), vec!["restart"]);
+        assert!(queue.pending(0).is_empty());
+        assert_eq!(queue.submit(0, "start"), Some(1));
+        assert_eq!(queue.cancel(2), Vec::<String>::new());
+    }
+}
@@ -0,0 +1,376 @@
+use global_placeholders::global;
+use macros_rs::{string, ternary};
+use once_cell::sync::Lazy;
+use opm::{
+    config::structs::{Config, CreatePolicy, Cores, Retention, SelfLimits},
+    process::rotate,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use std::{
+    fs,
+    sync::{
+        Mutex,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+static REQUESTED: AtomicBool = AtomicBool::new(false);
+static INTERVAL: AtomicU64 = AtomicU64::new(1000);
+static RESTARTS: AtomicU64 = AtomicU64::new(10);
+static RESTART_DELAY_BASE_MS: AtomicU64 = AtomicU64::new(500);
+static RESTART_DELAY_MAX_MS: AtomicU64 = AtomicU64::new(60_000);
+static LOG_CHECKPOINT: AtomicU64 = AtomicU64::new(30);
+static METRICS_INTERVAL: AtomicU64 = AtomicU64::new(60);
+static SNAPSHOT_INTERVAL: AtomicU64 = AtomicU64::new(24 * 60 * 60);
+static SNAPSHOT_KEEP: AtomicU64 = AtomicU64::new(7);
+static WATCH_HASH_THREADS: AtomicU64 = AtomicU64::new(2);
+
+static RETENTION: Lazy<Mutex<Option<Retention>>> = Lazy::new(|| Mutex::new(None));
+static CORES: Lazy<Mutex<Cores>> = Lazy::new(|| Mutex::new(Cores::default()));
+static SELF_LIMITS: Lazy<Mutex<SelfLimits>> = Lazy::new(|| Mutex::new(SelfLimits::default()));
+static CREATE_POLICY: Lazy<Mutex<Option<CreatePolicy>>> = Lazy::new(|| Mutex::new(None));
+static LOG_ROTATION: Lazy<Mutex<Result<Option<rotate::Policy>, String>>> = Lazy::new(|| Mutex::new(Ok(None)));
+
+// Settings the daemon currently runs with, compared against config.toml on every reload
+static APPLIED: Lazy<Mutex<Vec<Setting>>> = Lazy::new(|| Mutex::new(vec![]));
+
+struct Setting {
+    key: &'static str,
+    /// Whether the running daemon can pick up a new value
+    hot: bool,
+    value: String,
+    /// What is shown in reports, notification channels may hold webhook secrets
+    shown: String,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, PartialEq)]
+pub struct Change {
+    #[schema(example = "daemon.interval")]
+    pub key: String,
+    #[schema(example = "1000")]
+    pub from: String,
+    #[schema(example = "500")]
+    pub to: String,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Debug, Default)]
+pub struct Report {
+    /// Changes now in effect
+    pub applied: Vec<Change>,
+    /// Changes that only take effect after `opm daemon restore`
+    pub restart_required: Vec<Change>,
+    /// Set when config.toml could not be read, nothing was applied
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl Report {
+    pub fn failed(error: String) -> Self {
+        Self { error: Some(error), ..Self::default() }
+    }
+
+    /// Comma separated keys for daemon log lines
+    pub fn keys(changes: &[Change]) -> String {
+        changes.iter().map(|change| change.key.as_str()).collect::<Vec<_>>().join(",")
+    }
+}
+
+extern "C" fn handle_sighup(_: libc::c_int) {
+    // Only flag the request, the monitoring loop applies it between cycles
+    REQUESTED.store(true, Ordering::Release);
+}
+
+fn setting(key: &'static str, hot: bool, value: impl ToString) -> Setting {
+    let value = value.to_string();
+    Setting { key, hot, shown: value.clone(), value }
+}
+
+fn settings(config: &Config) -> Vec<Setting> {
+    let daemon = &config.daemon;
+    let notifications = match &daemon.notifications {
+        Some(notifications) => format!(
+            "{} channel(s), {}",
+            notifications.channels.as_ref().map_or(0, Vec::len),
+            ternary!(notifications.enabled, "enabled", "disabled")
+        ),
+        None => String::from("none"),
+    };
+
+    vec![
+        setting("daemon.interval", true, daemon.interval),
+        setting("daemon.restarts", true, daemon.restarts),
+        setting("daemon.restart_delay_base_ms", true, daemon.restart_delay_base_ms),
+        setting("daemon.restart_delay_max_ms", true, daemon.restart_delay_max_ms),
+        setting("daemon.log_checkpoint", true, daemon.log_checkpoint),
+        setting("daemon.metrics_interval", true, daemon.metrics_interval),
+        setting("daemon.snapshot_interval", true, daemon.snapshot_interval),
+        setting("daemon.snapshot_keep", true, daemon.snapshot_keep),
+        setting("daemon.watch_hash_threads", true, daemon.watch_hash_threads),
+        Setting {
+            key: "daemon.notifications",
+            hot: true,
+            value: serde_json::to_string(&daemon.notifications).unwrap_or_default(),
+            shown: notifications,
+        },
+        setting("daemon.retention", true, serde_json::to_string(&daemon.retention).unwrap_or_default()),
+        setting("daemon.cores", true, serde_json::to_string(&daemon.cores).unwrap_or_default()),
+        setting("daemon.self_limits", true, serde_json::to_string(&daemon.self_limits).unwrap_or_default()),
+        // Values may hold proxy credentials, reports only name the variables
+        Setting {
+            key: "daemon.env",
+            hot: false,
+            value: serde_json::to_string(&daemon.env).unwrap_or_default(),
+            shown: ternary!(daemon.env.is_empty(), String::from("none"), daemon.env.keys().cloned().collect::<Vec<_>>().join(", ")),
+        },
+        setting("daemon.kill_timeout", true, daemon.kill_timeout.map_or(string!("default"), |ms| ms.to_string())),
+        setting("daemon.kind", false, &daemon.kind),
+        setting("daemon.web.address", false, &daemon.web.address),
+        setting("daemon.web.port", false, daemon.web.port),
+        setting("daemon.web.api", false, daemon.web.api),
+        setting("daemon.web.ui", false, daemon.web.ui),
+        setting("daemon.web.path", false, daemon.web.path.as_deref().unwrap_or("/")),
+        setting("daemon.web.create_policy", true, serde_json::to_string(&daemon.web.create_policy).unwrap_or_default()),
+        setting("daemon.web.session_ttl", true, daemon.web.session_ttl.map_or(string!("default"), |ttl| ttl.to_string())),
+        // The daemon resolves log file paths once at startup
+        setting("runner.log_path", false, &config.runner.log_path),
+        setting("runner.dump_path", false, config.runner.dump_path.as_deref().unwrap_or("default")),
+        setting("runner.dump_format", false, config.runner.dump_format.as_str()),
+        setting("runner.cpu_sample_ms", true, config.runner.cpu_sample_ms.map_or(string!("default"), |ms| ms.to_string())),
+        setting("runner.max_processes", true, opm::process::max_processes(&config.runner)),
+        setting("runner.log_timestamps", true, config.runner.log_timestamps),
+        setting("runner.log_max_size", true, config.runner.log_max_size.as_deref().unwrap_or("none")),
+        setting("runner.log_keep", true, config.runner.log_keep.unwrap_or(rotate::DEFAULT_KEEP)),
+    ]
+}
+
+fn store(config: &Config) {
+    INTERVAL.store(config.daemon.interval, Ordering::Release);
+    RESTARTS.store(config.daemon.restarts, Ordering::Release);
+    RESTART_DELAY_BASE_MS.store(config.daemon.restart_delay_base_ms, Ordering::Release);
+    RESTART_DELAY_MAX_MS.store(config.daemon.restart_delay_max_ms, Ordering::Release);
+    LOG_CHECKPOINT.store(config.daemon.log_checkpoint, Ordering::Release);
+    METRICS_INTERVAL.store(config.daemon.metrics_interval, Ordering::Release);
+    SNAPSHOT_INTERVAL.store(config.daemon.snapshot_interval, Ordering::Release);
+    SNAPSHOT_KEEP.store(config.daemon.snapshot_keep as u64, Ordering::Release);
+    WATCH_HASH_THREADS.store(config.daemon.watch_hash_threads as u64, Ordering::Release);
+    *RETENTION.lock().unwrap() = config.daemon.retention.clone();
+    *CORES.lock().unwrap() = config.daemon.cores.clone();
+    *SELF_LIMITS.lock().unwrap() = config.daemon.self_limits.clone().unwrap_or_default();
+    *CREATE_POLICY.lock().unwrap() = config.daemon.web.create_policy.clone();
+    *LOG_ROTATION.lock().unwrap() = rotate::Policy::new(&config.runner);
+    opm::process::unix::cpu::set_sample_window(config.runner.cpu_sample_ms);
+}
+
+/// Record the settings the daemon starts with and reload on SIGHUP
+pub fn install(config: &Config) {
+    store(config);
+    *APPLIED.lock().unwrap() = settings(config);
+
+    unsafe { libc::signal(libc::SIGHUP, handle_sighup as *const () as usize) };
+}
+
+/// Take a pending SIGHUP reload request
+pub fn requested() -> bool {
+    REQUESTED.swap(false, Ordering::AcqRel)
+}
+
+pub fn interval() -> u64 {
+    INTERVAL.load(Ordering::Acquire)
+}
+
+pub fn restarts() -> u64 {
+    RESTARTS.load(Ordering::Acquire)
+}
+
+/// `daemon.restart_delay_base_ms` and `daemon.restart_delay_max_ms`
+pub fn restart_delay() -> (u64, u64) {
+    (RESTART_DELAY_BASE_MS.load(Ordering::Acquire), RESTART_DELAY_MAX_MS.load(Ordering::Acquire))
+}
+
+pub fn log_checkpoint() -> u64 {
+    LOG_CHECKPOINT.load(Ordering::Acquire)
+}
+
+pub fn metrics_interval() -> u64 {
+    METRICS_INTERVAL.load(Ordering::Acquire)
+}
+
+pub fn snapshot_interval() -> u64 {
+    SNAPSHOT_INTERVAL.load(Ordering::Acquire)
+}
+
+pub fn snapshot_keep() -> usize {
+    SNAPSHOT_KEEP.load(Ordering::Acquire) as usize
+}
+
+pub fn watch_hash_threads() -> usize {
+    WATCH_HASH_THREADS.load(Ordering::Acquire) as usize
+}
+
+pub fn retention() -> Option<Retention> {
+    RETENTION.lock().unwrap().clone()
+}
+
+pub fn cores() -> Cores {
+    CORES.lock().unwrap().clone()
+}
+
+pub fn self_limits() -> SelfLimits {
+    SELF_LIMITS.lock().unwrap().clone()
+}
+
+pub fn create_policy() -> Option<CreatePolicy> {
+    CREATE_POLICY.lock().unwrap().clone()
+}
+
+/// `runner.log_max_size` and `runner.log_keep`, an error when the size cannot be parsed
+pub fn log_rotation() -> Result<Option<rotate::Policy>, String> {
+    LOG_ROTATION.lock().unwrap().clone()
+}
+
+/// Sleep for `duration`, waking up early when a reload is requested
+pub fn sleep(duration: Duration) {
+    let deadline = Instant::now() + duration;
+
+    while !REQUESTED.load(Ordering::Acquire) {
+        let now = Instant::now();
+        if now >= deadline {
+            break;
+        }
+        thread::sleep((deadline - now).min(Duration::from_millis(50)));
+    }
+}
+
+/// Apply a freshly read config to the running daemon, hot settings all change under one lock.
+/// Settings that need a restart keep their running value and are reported until then.
+/// Notifications are only compared here, callers hand them to the notification manager.
+pub fn apply(config: &Config) -> Report {
+    let mut applied = APPLIED.lock().unwrap();
+    let mut report = Report::default();
+
+    for (current, next) in applied.iter_mut().zip(settings(config)) {
+        if current.value == next.value {
+            continue;
+        }
+
+        let change = Change {
+            key: next.key.to_string(),
+            from: current.shown.clone(),
+            to: next.shown.clone(),
+        };
+
+        match next.hot {
+            true => {
+                report.applied.push(change);
+                *current = next;
+            }
+            false => report.restart_required.push(change),
+        }
+    }
+
+    store(config);
+    report
+}
+
+pub fn report_path() -> String {
+    format!("{}daemon-reload.json", global!("opm.base"))
+}
+
+/// Leave the outcome of a SIGHUP reload for `opm daemon reload-config` to pick up
+pub fn write_report(report: &Report) -> std::io::Result<()> {
+    let path = report_path();
+    let temp = format!("{path}.tmp");
+
+    // Written aside and renamed so the CLI never reads a partial report
+    fs::write(&temp, serde_json::to_vec(report)?)?;
+    fs::rename(temp, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(interval: u64, port: u64, channels: &str) -> Config {
+        config_with_logs(interval, port, channels, "/tmp/opm-logs", 30)
+    }
+
+    fn config_with_logs(interval: u64, port: u64, channels: &str, log_path: &str, log_checkpoint: u64) -> Config {
+        toml::from_str(&format!(
+            r#"
+            default = "local"
+
+            [runner]
+            shell = "/bin/sh"
+            args = ["-c"]
+            node = "node"
+            log_path = "{log_path}"
+
+            [daemon]
+            restarts = 10
+            interval = {interval}
+            kind = "default"
+            log_checkpoint = {log_checkpoint}
+
+            [daemon.web]
+            port = {port}
+
+            [daemon.notifications]
+            enabled = true
+            channels = [{channels}]
+            "#
+        ))
+        .unwrap()
+    }
+
+    fn keys(changes: &[Change]) -> Vec<&str> {
+        changes.iter().map(|change| change.key.as_str()).collect()
+    }
+
+    #[test]
+    fn test_sighup_applies_new_interval() {
+        install(&config(1000, 9876, r#""discord://token@id""#));
+        assert_eq!(interval(), 1000);
+
+        unsafe { libc::raise(libc::SIGHUP) };
+        assert!(requested());
+        assert!(!requested());
+
+        let report = apply(&config(250, 9000, r#""discord://other@id""#));
+        assert_eq!(interval(), 250);
+        assert_eq!(keys(&report.applied), vec!["daemon.interval", "daemon.notifications"]);
+        assert_eq!(report.applied[0], Change { key: "daemon.interval".into(), from: "1000".into(), to: "250".into() });
+        assert!(!serde_json::to_string(&report).unwrap().contains("token"));
+
+        // The API keeps listening on the old port, so the change is reported until a restart
+        assert_eq!(keys(&report.restart_required), vec!["daemon.web.port"]);
+        let report = apply(&config(250, 9000, r#""discord://other@id""#));
+        assert!(report.applied.is_empty());
+        assert_eq!(keys(&report.restart_required), vec!["daemon.web.port"]);
+
+        // A reload request cuts the current sleep short
+        apply(&config(60_000, 9876, r#""discord://other@id""#));
+        let started = Instant::now();
+        thread::spawn(|| {
+            thread::sleep(Duration::from_millis(100));
+            unsafe { libc::raise(libc::SIGHUP) };
+        });
+        sleep(Duration::from_millis(interval()));
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert!(requested());
+
+        apply(&config(200, 9876, r#""discord://other@id""#));
+        let started = Instant::now();
+        sleep(Duration::from_millis(interval()));
+        assert!(started.elapsed() >= Duration::from_millis(200));
+
+        // Log checkpoints change in place, log files stay where the daemon found them at startup
+        let report = apply(&config_with_logs(200, 9876, r#""discord://other@id""#, "/tmp/opm-logs-new", 10));
+        assert_eq!(log_checkpoint(), 10);
+        assert_eq!(keys(&report.applied), vec!["daemon.log_checkpoint"]);
+        assert_eq!(keys(&report.restart_required), vec!["runner.log_path"]);
+    }
+}
@@ -0,0 +1,84 @@
+//! Which processes a monitoring cycle checks, each one at its own `monitor_interval_ms`
+
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+/// When each process is next due for a check
+#[derive(Default, Debug)]
+pub struct Schedule {
+    next: HashMap<usize, Instant>,
+}
+
+impl Schedule {
+    /// Ids of the processes due at `now` out of `intervals`, pairs of id and milliseconds, booking their next check.
+    /// New processes are due right away and removed ones are forgotten.
+    pub fn due(&mut self, intervals: &[(usize, u64)], now: Instant) -> Vec<usize> {
+        self.next.retain(|id, _| intervals.iter().any(|(known, _)| known == id));
+
+        let mut due = vec![];
+        for &(id, ms) in intervals {
+            let interval = Duration::from_millis(ms);
+            let next = self.next.entry(id).or_insert(now);
+
+            if *next <= now {
+                *next = now + interval;
+                due.push(id);
+            } else if *next > now + interval {
+                // The interval was shortened since the last check, e.g. by a config reload
+                *next = now + interval;
+            }
+        }
+
+        due
+    }
+
+    /// How long until the next check is due, at most `limit` so the rest of the loop keeps its pace
+    pub fn wait(&self, now: Instant, limit: Duration) -> Duration {
+        self.next.values().map(|next| next.saturating_duration_since(now)).min().map_or(limit, |wait| wait.min(limit))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MS: Duration = Duration::from_millis(1);
+
+    #[test]
+    fn test_processes_are_checked_at_their_own_interval() {
+        let (mut schedule, start) = (Schedule::default(), Instant::now());
+        let intervals = [(0, 1000), (1, 250), (2, 1000)];
+
+        assert_eq!(schedule.due(&intervals, start), vec![0, 1, 2]);
+        assert_eq!(schedule.wait(start, 1000 * MS), 250 * MS);
+
+        assert_eq!(schedule.due(&intervals, start + 250 * MS), vec![1]);
+        assert_eq!(schedule.due(&intervals, start + 400 * MS), Vec::<usize>::new());
+        assert_eq!(schedule.wait(start + 400 * MS, 1000 * MS), 100 * MS);
+        assert_eq!(schedule.due(&intervals, start + 500 * MS), vec![1]);
+        assert_eq!(schedule.due(&intervals, start + 1000 * MS), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn test_schedule_follows_the_process_list() {
+        let (mut schedule, start) = (Schedule::default(), Instant::now());
+        schedule.due(&[(0, 60_000), (1, 60_000)], start);
+
+        // A new process is checked right away, a removed one no longer holds the loop up
+        assert_eq!(schedule.due(&[(0, 60_000), (3, 60_000)], start + MS), vec![3]);
+        assert_eq!(schedule.due(&[], start + 2 * MS), Vec::<usize>::new());
+        assert_eq!(schedule.wait(start + 2 * MS, 1000 * MS), 1000 * MS);
+    }
+
+    #[test]
+    fn test_shortened_interval_applies_before_the_old_one_ends() {
+        let (mut schedule, start) = (Schedule::default(), Instant::now());
+        schedule.due(&[(0, 60_000)], start);
+
+        assert_eq!(schedule.due(&[(0, 200)], start + 100 * MS), Vec::<usize>::new());
+        assert_eq!(schedule.wait(start + 100 * MS, 1000 * MS), 200 * MS);
+        assert_eq!(schedule.due(&[(0, 200)], start + 300 * MS), vec![0]);
+    }
+}
@@ -0,0 +1,291 @@
+use global_placeholders::global;
+use macros_rs::crashln;
+use opm::helpers;
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+const UNIT_NAME: &str = "opm.service";
+const SYSTEM_DIR: &str = "/etc/systemd/system";
+const USER_DIR: &str = ".config/systemd/user";
+
+/// Where the unit lives, system-wide when running as root and a user service otherwise
+pub struct Unit {
+    pub path: PathBuf,
+    pub system: bool,
+}
+
+impl Unit {
+    pub fn locate(system: bool, home: &Path) -> Self {
+        let dir = match system {
+            true => PathBuf::from(SYSTEM_DIR),
+            false => home.join(USER_DIR),
+        };
+
+        Self { path: dir.join(UNIT_NAME), system }
+    }
+
+    fn install_target(&self) -> &'static str {
+        match self.system {
+            true => "multi-user.target",
+            false => "default.target",
+        }
+    }
+
+    /// systemctl invocation for this kind of unit, e.g. `sudo systemctl stop opm.service`
+    pub fn systemctl(&self, args: &[&str]) -> String {
+        match self.system {
+            true => format!("sudo systemctl {}", args.join(" ")),
+            false => format!("systemctl --user {}", args.join(" ")),
+        }
+    }
+
+    pub fn render(&self, binary: &str, opm_dir: &str, pid_file: &str) -> String {
+        let (kind, limits) = match self.system {
+            true => ("system-wide", "LimitNOFILE=infinity\nLimitNPROC=infinity\nLimitCORE=infinity\n"),
+            false => ("user service", ""),
+        };
+
+        format!(
+            r#"# OPM Daemon systemd service file ({kind})
+
+[Unit]
+Description=OPM Process Manager Daemon
+After=network.target
+
+[Service]
+Type=forking
+WorkingDirectory={opm_dir}
+PIDFile={pid_file}
+ExecStart={binary} daemon start
+ExecStop={binary} daemon stop
+Restart=on-failure
+RestartSec=5s
+{limits}
+[Install]
+WantedBy={}
+"#,
+            self.install_target()
+        )
+    }
+}
+
+/// Binary launched by an existing unit file
+pub fn exec_binary(content: &str) -> Option<&str> {
+    content
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("ExecStart="))
+        .map(|exec| exec.trim().trim_end_matches("daemon start").trim_end())
+}
+
+fn current_unit() -> Unit {
+    let Some(home) = home::home_dir() else {
+        crashln!("{} Unable to determine home directory", *helpers::FAIL)
+    };
+
+    let is_root = unsafe { libc::geteuid() == 0 };
+    Unit::locate(is_root, &home)
+}
+
+pub fn install(dry_run: bool, yes: bool) {
+    let unit = current_unit();
+    let binary = match env::current_exe() {
+        Ok(path) => path.to_string_lossy().into_owned(),
+        Err(err) => crashln!("{} Unable to determine opm binary path: {}", *helpers::FAIL, err),
+    };
+
+    let content = unit.render(&binary, &global!("opm.base"), &global!("opm.pid"));
+
+    if dry_run {
+        println!("{} Would write {}:\n", *helpers::SUCCESS, unit.path.display());
+        print!("{content}");
+        return;
+    }
+
+    let existing = fs::read_to_string(&unit.path).ok();
+    let updating = existing.is_some();
+
+    if existing.as_deref() == Some(content.as_str()) {
+        println!("{} Service file at {} is already up to date", *helpers::SUCCESS, unit.path.display());
+        return;
+    }
+
+    if let Some(stale) = existing.as_deref().and_then(exec_binary).filter(|stale| *stale != binary) {
+        println!(
+            "{} Existing {} launches {stale}, but this opm is {binary}",
+            *helpers::WARN,
+            unit.path.display()
+        );
+
+        if !yes && !confirm("Update the service to use this binary?") {
+            println!("{} Left the existing service file unchanged", *helpers::WARN);
+            return;
+        }
+    }
+
+    println!("{} Setting up OPM systemd service...", *helpers::SUCCESS);
+
+    if let Some(dir) = unit.path.parent()
+        && let Err(err) = fs::create_dir_all(dir)
+    {
+        crashln!("{} Failed to create service directory {:?}: {}", *helpers::FAIL, dir, err);
+    }
+
+    if let Err(err) = fs::write(&unit.path, content) {
+        crashln!("{} Failed to write service file to {:?}: {}", *helpers::FAIL, unit.path, err);
+    }
+
+    println!("{} Service file created at: {}", *helpers::SUCCESS, unit.path.display());
+
+    if updating {
+        println!("\n{} To load the updated service:", *helpers::SUCCESS);
+        println!("  {}", unit.systemctl(&["daemon-reload"]));
+        println!("  {}", unit.systemctl(&["restart", UNIT_NAME]));
+        return;
+    }
+
+    println!("\n{} To enable and start the OPM daemon:", *helpers::SUCCESS);
+    println!("  {}", unit.systemctl(&["daemon-reload"]));
+    println!("  {}", unit.systemctl(&["enable", UNIT_NAME]));
+    println!("  {}", unit.systemctl(&["start", UNIT_NAME]));
+
+    if !unit.system {
+        println!("\n{} To enable lingering (start daemon at boot):", *helpers::SUCCESS);
+        println!("  loginctl enable-linger $USER");
+    }
+
+    println!("\n{} To check daemon status:", *helpers::SUCCESS);
+    println!("  {}", unit.systemctl(&["status", UNIT_NAME]));
+
+    println!(
+        "\n{} Setup complete! The OPM daemon will now start automatically with the system.",
+        *helpers::SUCCESS
+    );
+}
+
+pub fn uninstall(dry_run: bool) {
+    let unit = current_unit();
+
+    // A system unit can only be removed by root, point at it from a user shell
+    let system = Unit::locate(true, Path::new("/"));
+    if !unit.system && system.path.exists() {
+        println!("{} A system-wide service exists at {}, remove it as root with:", *helpers::WARN, system.path.display());
+        for command in removal_commands(&system) {
+            println!("  {command}");
+        }
+    }
+
+    if !unit.path.exists() {
+        println!("{} No service file found at {}", *helpers::WARN, unit.path.display());
+        return;
+    }
+
+    if dry_run {
+        println!("{} Would run:", *helpers::SUCCESS);
+        for command in removal_commands(&unit) {
+            println!("  {command}");
+        }
+        return;
+    }
+
+    let mut failed = false;
+    for args in [["stop", UNIT_NAME], ["disable", UNIT_NAME]] {
+        failed |= !systemctl(&unit, &args);
+    }
+
+    if let Err(err) = fs::remove_file(&unit.path) {
+        crashln!("{} Failed to remove service file {:?}: {}", *helpers::FAIL, unit.path, err);
+    }
+
+    failed |= !systemctl(&unit, &["daemon-reload"]);
+    println!("{} Removed service file {}", *helpers::SUCCESS, unit.path.display());
+
+    if failed {
+        println!("{} Some systemctl commands failed, finish the removal with:", *helpers::WARN);
+        println!("  {}", unit.systemctl(&["stop", UNIT_NAME]));
+        println!("  {}", unit.systemctl(&["disable", UNIT_NAME]));
+        println!("  {}", unit.systemctl(&["daemon-reload"]));
+    }
+}
+
+fn removal_commands(unit: &Unit) -> Vec<String> {
+    let remove = match unit.system {
+        true => format!("sudo rm {}", unit.path.display()),
+        false => format!("rm {}", unit.path.display()),
+    };
+
+    vec![
+        unit.systemctl(&["stop", UNIT_NAME]),
+        unit.systemctl(&["disable", UNIT_NAME]),
+        remove,
+        unit.systemctl(&["daemon-reload"]),
+    ]
+}
+
+fn systemctl(unit: &Unit, args: &[&str]) -> bool {
+    let mut command = Command::new("systemctl");
+    if !unit.system {
+        command.arg("--user");
+    }
+
+    match command.args(args).output() {
+        Ok(output) if output.status.success() => true,
+        Ok(output) => {
+            log::warn!("systemctl {} failed: {}", args.join(" "), String::from_utf8_lossy(&output.stderr).trim());
+            false
+        }
+        Err(err) => {
+            log::warn!("Unable to run systemctl: {err}");
+            false
+        }
+    }
+}
+
+fn confirm(message: &str) -> bool {
+    match inquire::Confirm::new(message).with_default(true).prompt() {
+        Ok(answer) => answer,
+        Err(err) => {
+            println!("{} Cannot prompt ({err}), re-run with --yes to update the service", *helpers::FAIL);
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unit_locations() {
+        let home = Path::new("/home/opm");
+
+        assert_eq!(Unit::locate(true, home).path, PathBuf::from("/etc/systemd/system/opm.service"));
+        assert_eq!(Unit::locate(false, home).path, PathBuf::from("/home/opm/.config/systemd/user/opm.service"));
+        assert_eq!(Unit::locate(true, home).systemctl(&["stop", UNIT_NAME]), "sudo systemctl stop opm.service");
+        assert_eq!(Unit::locate(false, home).systemctl(&["daemon-reload"]), "systemctl --user daemon-reload");
+    }
+
+    #[test]
+    fn test_render_and_detect_binary() {
+        let home = Path::new("/home/opm");
+
+        for system in [true, false] {
+            let unit = Unit::locate(system, home);
+            let content = unit.render("/usr/local/bin/opm", "/home/opm/.opm/", "/home/opm/.opm/daemon.pid");
+
+            assert_eq!(exec_binary(&content), Some("/usr/local/bin/opm"));
+            assert!(content.contains("ExecStop=/usr/local/bin/opm daemon stop"));
+            assert_eq!(content.contains("LimitNOFILE=infinity"), system);
+            assert!(content.contains(match system {
+                true => "WantedBy=multi-user.target",
+                false => "WantedBy=default.target",
+            }));
+        }
+
+        assert_eq!(exec_binary("[Service]\nExecStart=/old/opm daemon start\n"), Some("/old/opm"));
+        assert_eq!(exec_binary("[Service]\nType=forking\n"), None);
+    }
+}
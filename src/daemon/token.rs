@@ -0,0 +1,173 @@
+//! `opm daemon token`, named API tokens limited to some actions and processes. Only their hashes are
+//! kept in config.toml, the daemon reads them on every request so a change needs no restart.
+
+use super::api::scope;
+use colored::Colorize;
+use macros_rs::crashln;
+use serde_json::json;
+
+use opm::{
+    alert,
+    config::{self, structs::ApiToken, token},
+    helpers, notice,
+    process::group,
+};
+
+use tabled::{
+    Table, Tabled,
+    settings::{
+        Color,
+        style::{BorderColor, Style},
+    },
+};
+
+/// Actors the audit log already uses for callers without a named token
+const RESERVED: [&str; 3] = ["token", "session", "anonymous"];
+
+/// Check a token name, it is what the audit log records as the actor of its requests
+fn check_name(name: &str) -> Result<(), String> {
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-')) {
+        return Err(format!("token name '{name}' may only contain [A-Za-z0-9_.-]"));
+    }
+
+    match RESERVED.contains(&name) {
+        true => Err(format!("'{name}' is reserved, choose another token name")),
+        false => Ok(()),
+    }
+}
+
+/// Actions as the scope compares them, e.g. `kill` is stored as `stop`
+fn check_actions(actions: &[String]) -> Result<Vec<String>, String> {
+    actions
+        .iter()
+        .map(|action| match scope::canonical(action.trim()) {
+            action if scope::ACTIONS.contains(&action) => Ok(action.to_string()),
+            action => Err(format!("unknown action '{action}', available: {}", scope::ACTIONS.join(","))),
+        })
+        .collect()
+}
+
+fn check_processes(processes: &[String]) -> Result<Vec<String>, String> {
+    processes
+        .iter()
+        .map(|pattern| match pattern.trim().strip_prefix("group:") {
+            Some(name) => group::parse(name).map(|name| format!("group:{name}")),
+            None if pattern.trim().is_empty() => Err(String::from("a process pattern cannot be empty")),
+            None => Ok(pattern.trim().to_string()),
+        })
+        .collect()
+}
+
+/// Store a new token limited to `actions` on `processes` (any of either when empty) and print it, the only time it is shown
+pub fn add(name: &str, actions: &[String], processes: &[String], read_only: bool) {
+    let checked = check_name(name).and_then(|_| Ok((check_actions(actions)?, check_processes(processes)?)));
+    let (actions, processes) = match checked {
+        Ok(checked) => checked,
+        Err(err) => crashln!("{} Cannot add token: {err}", *helpers::FAIL),
+    };
+
+    let mut config = config::read();
+    let Some(secure) = config.daemon.web.secure.as_mut() else {
+        crashln!("{} daemon.web.secure is missing from config.toml", *helpers::FAIL);
+    };
+
+    if secure.tokens.iter().any(|token| token.name == name) {
+        crashln!("{} A token named {name} already exists, remove it first", *helpers::FAIL);
+    }
+
+    let secret = uuid::Uuid::new_v4().to_string();
+    secure.tokens.push(ApiToken {
+        name: name.to_string(),
+        hash: token::hash(&secret),
+        actions,
+        processes,
+        read_only,
+    });
+
+    let enabled = secure.enabled;
+    config.save();
+
+    if !enabled {
+        alert!("{} daemon.web.secure.enabled is false, every request is let in until it is enabled", *helpers::WARN);
+    }
+
+    notice!("{} Added token {name}, copy it now, it is not shown again:", *helpers::SUCCESS);
+    println!("{secret}");
+}
+
+pub fn remove(name: &str) {
+    let mut config = config::read();
+    let Some(secure) = config.daemon.web.secure.as_mut() else {
+        crashln!("{} Token {name} was not found", *helpers::FAIL);
+    };
+
+    let before = secure.tokens.len();
+    secure.tokens.retain(|token| token.name != name);
+
+    if secure.tokens.len() == before {
+        crashln!("{} Token {name} was not found", *helpers::FAIL);
+    }
+
+    config.save();
+    notice!("{} Removed token {name}, requests sending it are refused from now on", *helpers::SUCCESS);
+}
+
+#[derive(Tabled)]
+struct Row {
+    name: String,
+    actions: String,
+    processes: String,
+    #[tabled(rename = "read only")]
+    read_only: bool,
+}
+
+impl From<&ApiToken> for Row {
+    fn from(token: &ApiToken) -> Self {
+        let list = |values: &[String], every: &str| match values.is_empty() {
+            true => every.to_string(),
+            false => values.join(","),
+        };
+
+        Row {
+            name: token.name.clone(),
+            actions: list(&token.actions, "any"),
+            processes: list(&token.processes, "all"),
+            read_only: token.read_only,
+        }
+    }
+}
+
+/// The named tokens with their scopes, never the tokens themselves
+pub fn list(format: &str) {
+    let tokens = config::read().daemon.web.secure.map(|secure| secure.tokens).unwrap_or_default();
+
+    match format {
+        "json" => println!("{}", json!(tokens.iter().map(|token| json!({"name": token.name, "actions": token.actions, "processes": token.processes, "read_only": token.read_only})).collect::<Vec<_>>())),
+        "default" if tokens.is_empty() => notice!("{} No named tokens, add one with opm daemon token add", *helpers::SUCCESS),
+        "default" => {
+            let table = Table::new(tokens.iter().map(Row::from)).with(Style::rounded().remove_horizontals()).with(BorderColor::filled(Color::FG_BRIGHT_BLACK)).to_string();
+            notice!("{}\n{table}\n", "Named API tokens".on_bright_white().black());
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(values: &[&str]) -> Vec<String> { values.iter().map(|value| value.to_string()).collect() }
+
+    #[test]
+    fn test_checks() {
+        assert!(check_name("ci-deploy").is_ok());
+        assert!(check_name("token").unwrap_err().contains("reserved"));
+        assert!(check_name("ci deploy").is_err());
+
+        assert_eq!(check_actions(&strings(&["restart", "kill", "clean"])).unwrap(), strings(&["restart", "stop", "flush"]));
+        assert!(check_actions(&strings(&["deploy"])).unwrap_err().contains("unknown action 'deploy'"));
+
+        assert_eq!(check_processes(&strings(&["app-*", " group:workers"])).unwrap(), strings(&["app-*", "group:workers"]));
+        assert!(check_processes(&strings(&["group:a b"])).is_err());
+    }
+}
@@ -0,0 +1,54 @@
+//! Watched paths hashed off the daemon loop, so a `node_modules`-sized tree does not hold up the crash
+//! checks of every other process. A cycle hands the hash of a process to a worker and takes the result
+//! at a later one, at most `daemon.watch_hash_threads` hashes run at the same time.
+
+use once_cell::sync::Lazy;
+use opm::process::hash;
+
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::Mutex,
+    thread::{self, JoinHandle},
+};
+
+/// A hash in flight, with the stored hash of the process when it was handed out
+struct Job {
+    stored: String,
+    worker: JoinHandle<String>,
+}
+
+static JOBS: Lazy<Mutex<HashMap<usize, Job>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// The hash of the watched `path` of process `id` once one finished, `stored` is the hash the process holds.
+/// A result is dropped when the stored hash changed meanwhile, a reload already stored a newer one. A
+/// process hands out its next hash a cycle after taking a result, so the others get their turn at a
+/// worker. With no `threads` the hash is computed right here.
+pub fn poll(id: usize, path: PathBuf, stored: &str, threads: usize) -> Option<String> {
+    if threads == 0 {
+        return Some(hash::create(path));
+    }
+
+    let Ok(mut jobs) = JOBS.lock() else { return None };
+    if let Some(job) = jobs.remove(&id) {
+        if !job.worker.is_finished() {
+            jobs.insert(id, job);
+            return None;
+        }
+        return job.worker.join().ok().filter(|_| job.stored == stored);
+    }
+
+    if jobs.len() < threads {
+        let worker = thread::spawn(move || hash::create(path));
+        jobs.insert(id, Job { stored: stored.to_string(), worker });
+    }
+
+    None
+}
+
+/// Forget the hash in flight for a process that is not watched anymore
+pub fn forget(id: usize) {
+    if let Ok(mut jobs) = JOBS.lock() {
+        jobs.remove(&id);
+    }
+}
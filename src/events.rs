@@ -0,0 +1,327 @@
+use chrono::{DateTime, Utc};
+use global_placeholders::global;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use std::{
+    fmt,
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Seek, SeekFrom, Write},
+    str::FromStr,
+};
+
+/// The file moves to `events.jsonl.1` once it grows past this size, replacing the previous one
+pub const MAX_BYTES: u64 = 5 * 1024 * 1024;
+
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Kind {
+    /// The daemon found a process dead that was supposed to run
+    Crash,
+    /// The daemon restarted a crashed process
+    Restart,
+    /// A process crashed more often than `runner.restarts` allows and is no longer restarted
+    GaveUp,
+    /// A process went over its memory limit and was stopped
+    MemoryLimit,
+    /// The watched path of a process changed and it was reloaded
+    WatchReload,
+    /// The watched path changed within the watch cooldown, the reload waits for it to settle
+    WatchHeld,
+    /// The daemon started a process registered by `opm import --background`
+    ImportStart,
+    /// A process registered by `opm import --background` failed to start
+    ImportFailed,
+    /// A metric of a process stayed above an `--alert` threshold
+    Alert,
+    /// The logs of a process grew faster than its `--max-log-rate` for several checks in a row
+    LogRate,
+    /// The log filesystem has no space left, processes that cannot start are errored until it has
+    LogsFull,
+    /// Not written by the daemon, marks a stretch where events may have been missed
+    Gap,
+}
+
+impl Kind {
+    pub const ALL: [Kind; 12] = [
+        Kind::Crash,
+        Kind::Restart,
+        Kind::GaveUp,
+        Kind::MemoryLimit,
+        Kind::WatchReload,
+        Kind::WatchHeld,
+        Kind::ImportStart,
+        Kind::ImportFailed,
+        Kind::Alert,
+        Kind::LogRate,
+        Kind::LogsFull,
+        Kind::Gap,
+    ];
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Kind::Crash => write!(f, "crash"),
+            Kind::Restart => write!(f, "restart"),
+            Kind::GaveUp => write!(f, "gave_up"),
+            Kind::MemoryLimit => write!(f, "memory_limit"),
+            Kind::WatchReload => write!(f, "watch_reload"),
+            Kind::WatchHeld => write!(f, "watch_held"),
+            Kind::ImportStart => write!(f, "import_start"),
+            Kind::ImportFailed => write!(f, "import_failed"),
+            Kind::Alert => write!(f, "alert"),
+            Kind::LogRate => write!(f, "log_rate"),
+            Kind::LogsFull => write!(f, "logs_full"),
+            Kind::Gap => write!(f, "gap"),
+        }
+    }
+}
+
+impl FromStr for Kind {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let value = value.trim();
+
+        match Kind::ALL.into_iter().find(|kind| kind.to_string() == value) {
+            Some(kind) => Ok(kind),
+            None => Err(format!(
+                "unknown event type '{value}', available: {}",
+                Kind::ALL.map(|kind| kind.to_string()).join(",")
+            )),
+        }
+    }
+}
+
+/// Something the daemon noticed about a process, stored as a line of `~/.opm/events.jsonl`
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, PartialEq)]
+pub struct Event {
+    #[schema(value_type = String, example = "2000-01-01T01:00:00.000Z")]
+    pub time: DateTime<Utc>,
+    #[serde(rename = "type")]
+    pub kind: Kind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(example = 0)]
+    pub id: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(example = "api")]
+    pub name: Option<String>,
+    #[schema(example = "Process api (0) crashed (1/10 restarts)")]
+    pub message: String,
+}
+
+impl Event {
+    pub fn process(kind: Kind, id: usize, name: &str, message: String) -> Self {
+        Self {
+            time: Utc::now(),
+            kind,
+            id: Some(id),
+            name: Some(name.to_string()),
+            message,
+        }
+    }
+
+    /// About the daemon as a whole rather than one process
+    pub fn daemon(kind: Kind, message: String) -> Self {
+        Self {
+            time: Utc::now(),
+            kind,
+            id: None,
+            name: None,
+            message,
+        }
+    }
+
+    /// Marker for a reader that lost its source, events between `since` and now may be missing
+    pub fn gap(since: DateTime<Utc>) -> Self {
+        Self {
+            time: Utc::now(),
+            kind: Kind::Gap,
+            id: None,
+            name: None,
+            message: format!("Event source was unavailable since {}, events in between may be missing", since.to_rfc3339()),
+        }
+    }
+}
+
+/// Narrows what `opm events` and `/daemon/events` return, gap markers always pass
+#[derive(Debug, Clone, Default)]
+pub struct Filter {
+    /// Every type when empty
+    pub kinds: Vec<Kind>,
+    /// Process name or id
+    pub process: Option<String>,
+}
+
+impl Filter {
+    /// Filter of the api query, unknown types are ignored
+    pub fn from_query(types: Option<&str>, process: Option<String>) -> Self {
+        let kinds = types.map(|types| types.split(',').filter_map(|kind| kind.parse().ok()).collect()).unwrap_or_default();
+        Filter { kinds, process }
+    }
+
+    pub fn matches(&self, event: &Event) -> bool {
+        if event.kind == Kind::Gap {
+            return true;
+        }
+
+        let kind = self.kinds.is_empty() || self.kinds.contains(&event.kind);
+        let process = match &self.process {
+            Some(process) => event.name.as_deref() == Some(process) || event.id.is_some_and(|id| id.to_string() == *process),
+            None => true,
+        };
+
+        kind && process
+    }
+}
+
+pub fn append(path: &str, event: &Event) -> io::Result<()> {
+    let mut line = serde_json::to_string(event)?;
+    line.push('\n');
+
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    file.write_all(line.as_bytes())?;
+
+    if file.metadata()?.len() > MAX_BYTES {
+        fs::rename(path, format!("{path}.1"))?;
+    }
+
+    Ok(())
+}
+
+fn parse(contents: &str) -> impl Iterator<Item = Event> + '_ { contents.lines().filter_map(|line| serde_json::from_str(line).ok()) }
+
+/// Last `lines` matching events, oldest first, the rotated file included
+pub fn read(path: &str, lines: usize, filter: &Filter) -> Vec<Event> {
+    let mut events: Vec<Event> = [format!("{path}.1"), path.to_string()]
+        .iter()
+        .filter_map(|path| fs::read_to_string(path).ok())
+        .flat_map(|contents| parse(&contents).filter(|event| filter.matches(event)).collect::<Vec<Event>>())
+        .collect();
+
+    events.drain(..events.len().saturating_sub(lines));
+    events
+}
+
+/// Add an event to the event file. Failing to write it never fails the daemon.
+pub fn record(event: Event) {
+    if let Err(err) = append(&global!("opm.events"), &event) {
+        log::warn!("Cannot write {} event: {err}", event.kind);
+    }
+}
+
+pub fn tail(lines: usize, filter: &Filter) -> Vec<Event> { read(&global!("opm.events"), lines, filter) }
+
+/// Reads the events appended to a file since the last poll, following it across rotations
+pub struct Cursor {
+    path: String,
+    offset: u64,
+}
+
+impl Cursor {
+    /// Start at the current end of the file, only events written from now on are returned
+    pub fn end(path: &str) -> Self {
+        let offset = fs::metadata(path).map(|meta| meta.len()).unwrap_or(0);
+        Cursor { path: path.to_string(), offset }
+    }
+
+    pub fn poll(&mut self) -> Vec<Event> {
+        let mut contents = String::new();
+        let len = fs::metadata(&self.path).map(|meta| meta.len()).unwrap_or(0);
+
+        // Shorter than what was read means it was rotated, the rest of the old file is now `.1`
+        if len < self.offset {
+            complete_lines(&format!("{}.1", self.path), self.offset, &mut contents);
+            self.offset = 0;
+        }
+
+        self.offset += complete_lines(&self.path, self.offset, &mut contents);
+        parse(&contents).collect()
+    }
+}
+
+/// Append the complete lines of a file from `offset` on, returning how many bytes they took
+fn complete_lines(path: &str, offset: u64, out: &mut String) -> u64 {
+    let mut buf = String::new();
+    let read = File::open(path).and_then(|mut file| {
+        file.seek(SeekFrom::Start(offset))?;
+        file.read_to_string(&mut buf)
+    });
+
+    // A line still being written is left for the next poll
+    match read.ok().and(buf.rfind('\n')) {
+        Some(end) => {
+            out.push_str(&buf[..=end]);
+            end as u64 + 1
+        }
+        None => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(kind: Kind, id: usize, name: &str) -> Event { Event::process(kind, id, name, format!("{kind} of {name}")) }
+
+    #[test]
+    fn test_filter() {
+        let filter = Filter {
+            kinds: vec![Kind::Crash, Kind::MemoryLimit],
+            process: Some(String::from("api")),
+        };
+
+        assert!(filter.matches(&event(Kind::Crash, 0, "api")));
+        assert!(!filter.matches(&event(Kind::Restart, 0, "api")));
+        assert!(!filter.matches(&event(Kind::Crash, 1, "worker")));
+        assert!(filter.matches(&Event::gap(Utc::now())));
+
+        let by_id = Filter { process: Some(String::from("1")), ..Filter::default() };
+        assert!(by_id.matches(&event(Kind::WatchReload, 1, "worker")));
+
+        assert_eq!(Filter::from_query(Some("crash,nope,memory_limit"), None).kinds, vec![Kind::Crash, Kind::MemoryLimit]);
+        assert_eq!("gave_up".parse::<Kind>(), Ok(Kind::GaveUp));
+        assert!("oom".parse::<Kind>().unwrap_err().starts_with("unknown event type 'oom'"));
+    }
+
+    #[test]
+    fn test_serialized_line() {
+        let line = serde_json::to_string(&event(Kind::MemoryLimit, 2, "api")).unwrap();
+        assert!(line.contains(r#""type":"memory_limit""#) && line.contains(r#""id":2"#));
+
+        let gap = serde_json::to_string(&Event::gap(Utc::now())).unwrap();
+        assert!(gap.contains(r#""type":"gap""#) && !gap.contains("name"));
+    }
+
+    #[test]
+    fn test_cursor_follows_appends_and_rotation() {
+        let path = format!("{}/opm-events-test-{}.jsonl", std::env::temp_dir().display(), std::process::id());
+        append(&path, &event(Kind::Crash, 0, "old")).unwrap();
+
+        let mut cursor = Cursor::end(&path);
+        assert!(cursor.poll().is_empty());
+
+        // Half a line is held back until it is complete
+        let line = format!("{}\n", serde_json::to_string(&event(Kind::WatchReload, 1, "worker")).unwrap());
+        append(&path, &event(Kind::Crash, 0, "api")).unwrap();
+        OpenOptions::new().append(true).open(&path).unwrap().write_all(&line.as_bytes()[..8]).unwrap();
+        let names: Vec<Option<String>> = cursor.poll().into_iter().map(|event| event.name).collect();
+        assert_eq!(names, vec![Some(String::from("api"))]);
+
+        OpenOptions::new().append(true).open(&path).unwrap().write_all(&line.as_bytes()[8..]).unwrap();
+        assert_eq!(cursor.poll()[0].kind, Kind::WatchReload);
+
+        // The rest of the rotated file comes before the new one
+        append(&path, &event(Kind::Restart, 0, "api")).unwrap();
+        fs::rename(&path, format!("{path}.1")).unwrap();
+        append(&path, &event(Kind::GaveUp, 0, "api")).unwrap();
+
+        let kinds: Vec<Kind> = cursor.poll().into_iter().map(|event| event.kind).collect();
+        assert_eq!(kinds, vec![Kind::Restart, Kind::GaveUp]);
+        assert_eq!(read(&path, 2, &Filter::default()).len(), 2);
+
+        fs::remove_file(&path).ok();
+        fs::remove_file(format!("{path}.1")).ok();
+    }
+}
@@ -1,42 +1,66 @@
-use crate::{helpers, log, process::Process};
+use crate::{
+    helpers, log,
+    process::{
+        Process,
+        checkpoint::{self, Window},
+        rotate, supervise,
+    },
+};
+use chrono::Local;
 use colored::Colorize;
 use macros_rs::{crashln, string, ternary};
 
 use std::{
     env,
     fs::{self, File},
-    io::{BufRead, BufReader},
+    io::{self, BufRead, BufReader, Read, Seek, SeekFrom},
     path::{Path, PathBuf},
     thread::sleep,
     time::Duration,
 };
 
+/// How `opm logs` picks and prints the lines of a log
+#[derive(Clone, Copy, Default)]
+pub struct LogOptions<'a> {
+    /// Only lines containing this pattern, ignoring case
+    pub filter: Option<&'a str>,
+    /// Print a summary of the log levels after the lines
+    pub stats: bool,
+    /// Summarize the JSON lines with this numeric field, for `--stats-json`
+    pub stats_field: Option<&'a str>,
+    /// Only lines logged inside this `--since/--until` range
+    pub window: Window,
+    /// Keep the supervisor's timestamp prefix of each line
+    pub timestamps: bool,
+    /// Read on into the rotated archives when the log is shorter than the tail
+    pub rotated: bool,
+}
+
 pub fn logs(item: &Process, lines_to_tail: usize, kind: &str) {
-    logs_with_options(item, lines_to_tail, kind, false, None, false);
+    logs_with_options(item, lines_to_tail, kind, false, &LogOptions::default());
 }
 
-pub fn logs_with_options(
-    item: &Process,
-    lines_to_tail: usize,
-    kind: &str,
-    follow: bool,
-    filter: Option<&str>,
-    stats: bool,
-) {
+pub fn logs_with_options(item: &Process, lines_to_tail: usize, kind: &str, follow: bool, options: &LogOptions) {
     let log_file = match kind {
         "out" => item.logs().out,
         "error" => item.logs().error,
         _ => item.logs().out,
     };
 
-    if !Exists::check(&log_file).empty() {
-        let file = File::open(&log_file).unwrap();
-        let reader = BufReader::new(file);
-        let lines: Vec<String> = reader
+    let mut lines: Vec<String> = match Exists::check(&log_file).empty() {
+        true => vec![],
+        false => BufReader::new(File::open(&log_file).unwrap())
             .lines()
             .map(|line| line.unwrap_or_else(|err| format!("error reading line: {err}")))
-            .collect();
+            .collect(),
+    };
+
+    // Older lines from the rotated archives, when the log alone is too short for the tail
+    if options.rotated && lines.len() < lines_to_tail {
+        lines.splice(0..0, rotate::tail(Path::new(&log_file), lines_to_tail - lines.len()));
+    }
 
+    if !lines.is_empty() {
         logs_internal_with_options(
             lines,
             lines_to_tail,
@@ -44,51 +68,27 @@ pub fn logs_with_options(
             item.id,
             kind,
             &item.name,
-            filter,
-            stats,
+            options,
         );
 
         if follow {
             // Follow mode: continuously watch for new lines
-            use std::io::{Seek, SeekFrom};
-            let mut file = File::open(&log_file).unwrap();
-
-            // Start from the current end of file
-            let mut last_pos = file.seek(SeekFrom::End(0)).unwrap();
+            let mut follower = LogFollower::new(&log_file);
+            let color = ternary!(kind == "out", "green", "red");
 
             loop {
-                // Check current file size
-                let current_size = file.metadata().unwrap().len();
-
-                if current_size > last_pos {
-                    // New content available - read from last position
-                    file.seek(SeekFrom::Start(last_pos)).unwrap();
-                    let reader = BufReader::new(&file);
-
-                    for line in reader.lines() {
-                        if let Ok(line) = line {
-                            if let Some(pattern) = filter {
-                                if !line.to_lowercase().contains(&pattern.to_lowercase()) {
-                                    continue;
-                                }
-                            }
-
-                            let (level_indicator, line_color) = detect_log_level(&line, kind);
-                            let color = ternary!(kind == "out", "green", "red");
-                            println!(
-                                "{} {} {}",
-                                format!("{}|{}", item.id, item.name).color(color),
-                                level_indicator,
-                                line.color(line_color)
-                            );
+                for event in follower.poll() {
+                    let line = match event {
+                        FollowEvent::Line(line) => line,
+                        FollowEvent::Rotated => {
+                            println!("{}", "-- log rotated --".bright_black());
+                            continue;
                         }
-                    }
+                    };
 
-                    last_pos = current_size;
-                } else if current_size < last_pos {
-                    // File was truncated or rotated - reset position
-                    last_pos = 0;
-                    file.seek(SeekFrom::Start(0)).unwrap();
+                    if let Some(line) = follow_line(&line, kind, options.filter, options.timestamps) {
+                        println!("{} {line}", format!("{}|{}", item.id, item.name).color(color));
+                    }
                 }
 
                 // Poll interval - using a simple polling mechanism
@@ -104,6 +104,117 @@ pub fn logs_with_options(
     }
 }
 
+/// A followed line as it is printed after the name of its process, None when `filter` leaves it out
+pub fn follow_line(line: &str, kind: &str, filter: Option<&str>, timestamps: bool) -> Option<String> {
+    if filter.is_some_and(|pattern| !line.to_lowercase().contains(&pattern.to_lowercase())) {
+        return None;
+    }
+
+    let (stamp, line) = stamped(line, timestamps);
+    let (level_indicator, line_color) = detect_log_level(line, kind);
+    Some(format!("{level_indicator} {stamp}{}", line.color(line_color)))
+}
+
+/// Event produced while following a log file
+#[derive(Debug, PartialEq)]
+pub enum FollowEvent {
+    Line(String),
+    Rotated,
+}
+
+/// Tails a log file, surviving truncation (`opm flush`) and replacement
+/// of the file (rotation, recreation) by reopening it when needed.
+pub struct LogFollower {
+    path: PathBuf,
+    file: Option<File>,
+    inode: Option<(u64, u64)>,
+    position: u64,
+    partial: String,
+}
+
+impl LogFollower {
+    /// Start following `path` from its current end
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        let mut follower = Self {
+            path: path.into(),
+            file: None,
+            inode: None,
+            position: 0,
+            partial: String::new(),
+        };
+
+        if let Ok(file) = File::open(&follower.path) {
+            follower.inode = file.metadata().ok().map(|m| inode_of(&m));
+            follower.position = file.metadata().map(|m| m.len()).unwrap_or(0);
+            follower.file = Some(file);
+        }
+
+        follower
+    }
+
+    /// Read any complete lines written since the last poll
+    pub fn poll(&mut self) -> Vec<FollowEvent> {
+        let mut events = Vec::new();
+
+        // The path may point to a different file now (rotated or recreated)
+        let current_inode = fs::metadata(&self.path).ok().map(|m| inode_of(&m));
+        if current_inode.is_some() && current_inode != self.inode {
+            if self.file.is_some() {
+                // Drain whatever was written to the old file before it was replaced
+                self.read_new_lines(&mut events);
+                events.push(FollowEvent::Rotated);
+            }
+
+            self.file = File::open(&self.path).ok();
+            self.inode = current_inode;
+            self.position = 0;
+            self.partial.clear();
+        }
+
+        let size = match &self.file {
+            Some(file) => file.metadata().map(|m| m.len()).unwrap_or(0),
+            None => return events,
+        };
+
+        if size < self.position {
+            // File was truncated in place
+            events.push(FollowEvent::Rotated);
+            self.position = 0;
+            self.partial.clear();
+        }
+
+        self.read_new_lines(&mut events);
+        events
+    }
+
+    fn read_new_lines(&mut self, events: &mut Vec<FollowEvent>) {
+        use std::io::{Read, Seek, SeekFrom};
+
+        let Some(file) = self.file.as_mut() else { return };
+        if file.seek(SeekFrom::Start(self.position)).is_err() {
+            return;
+        }
+
+        let mut buffer = Vec::new();
+        if let Ok(read) = file.read_to_end(&mut buffer) {
+            self.position += read as u64;
+        }
+
+        self.partial.push_str(&String::from_utf8_lossy(&buffer));
+
+        // Keep an unterminated trailing line until the rest of it arrives
+        while let Some(index) = self.partial.find('\n') {
+            let line: String = self.partial.drain(..=index).collect();
+            events.push(FollowEvent::Line(line.trim_end_matches(['\n', '\r']).to_string()));
+        }
+    }
+}
+
+fn inode_of(metadata: &fs::Metadata) -> (u64, u64) {
+    use std::os::unix::fs::MetadataExt;
+    (metadata.dev(), metadata.ino())
+}
+
 pub fn logs_internal(
     lines: Vec<String>,
     lines_to_tail: usize,
@@ -119,8 +230,7 @@ pub fn logs_internal(
         id,
         log_type,
         item_name,
-        None,
-        false,
+        &LogOptions::default(),
     );
 }
 
@@ -131,21 +241,57 @@ pub fn logs_internal_with_options(
     id: usize,
     log_type: &str,
     item_name: &str,
-    filter: Option<&str>,
-    stats: bool,
+    options: &LogOptions,
 ) {
     println!(
         "{}",
         format!("\n{log_file} last {lines_to_tail} lines:").bright_black()
     );
 
-    let color = ternary!(log_type == "out", "green", "red");
     let start_index = if lines.len() > lines_to_tail {
         lines.len() - lines_to_tail
     } else {
         0
     };
 
+    print_lines(&lines[start_index..], id, log_type, item_name, options);
+}
+
+/// Print the last `lines_to_tail` lines of a log that fall inside a `--since/--until` window
+pub fn logs_window(item: &Process, lines_to_tail: usize, kind: &str, options: &LogOptions) {
+    let log_file = match kind {
+        "error" => item.logs().error,
+        _ => item.logs().out,
+    };
+
+    let slice = match checkpoint::select(&log_file, options.window) {
+        Ok(slice) => slice,
+        Err(_) => {
+            println!("{}", format!("[OPM] No logs found in {log_file}").bright_black());
+            return;
+        }
+    };
+
+    window_header(&log_file, &options.window, slice.approximate);
+    let start_index = slice.lines.len().saturating_sub(lines_to_tail);
+    print_lines(&slice.lines[start_index..], item.id, kind, &item.name, options);
+}
+
+/// Header for the lines of a log inside a window, noting when the range is only approximate
+pub fn window_header(log_file: &str, window: &Window, approximate: bool) {
+    println!("{}", format!("\n{log_file} {}:", window.describe()).bright_black());
+
+    if approximate {
+        println!(
+            "{}",
+            "(approximate, this log has no timestamps so the range is rounded out to the nearest checkpoints)".bright_black()
+        );
+    }
+}
+
+pub fn print_lines(lines: &[String], id: usize, log_type: &str, item_name: &str, options: &LogOptions) {
+    let color = ternary!(log_type == "out", "green", "red");
+
     // Statistics counters
     let mut error_count = 0;
     let mut warn_count = 0;
@@ -153,16 +299,16 @@ pub fn logs_internal_with_options(
     let mut debug_count = 0;
     let mut filtered_lines = Vec::new();
 
-    for (_, line) in lines.iter().skip(start_index).enumerate() {
+    for line in lines {
         // Apply filter if provided
-        if let Some(pattern) = filter {
+        if let Some(pattern) = options.filter {
             if !line.to_lowercase().contains(&pattern.to_lowercase()) {
                 continue;
             }
         }
 
         // Detect log level in the line content for better identification
-        let (level_indicator, line_color) = detect_log_level(&line, log_type);
+        let (level_indicator, line_color) = detect_log_level(stamped(line, false).1, log_type);
 
         // Count log levels for statistics
         if level_indicator.contains("ERR") {
@@ -179,7 +325,7 @@ pub fn logs_internal_with_options(
     }
 
     // Display statistics if requested
-    if stats {
+    if options.stats {
         println!("{}", "".bright_black());
         println!("{}", "Log Statistics:".bright_yellow().bold());
         println!(
@@ -210,10 +356,16 @@ pub fn logs_internal_with_options(
         println!("{}", "".bright_black());
     }
 
+    if let Some(field) = options.stats_field {
+        let summary = log::stats::summarize(filtered_lines.iter().map(|(_, _, line)| line.as_str()), field);
+        print_json_stats(&summary);
+    }
+
     // Display the filtered logs
     for (level_indicator, line_color, line) in filtered_lines {
+        let (stamp, line) = stamped(line, options.timestamps);
         println!(
-            "{} {} {}",
+            "{} {} {stamp}{}",
             format!("{}|{}", id, item_name).color(color),
             level_indicator,
             line.color(line_color)
@@ -221,6 +373,47 @@ pub fn logs_internal_with_options(
     }
 }
 
+/// Summary of the JSON lines of a log, for `opm logs --stats-json`
+pub fn print_json_stats(summary: &log::stats::Summary) {
+    println!("{}", "".bright_black());
+    println!("{}", "JSON Log Statistics:".bright_yellow().bold());
+    println!("  Lines:    {} ({} unparsed)", summary.lines, summary.unparsed.to_string().bright_black());
+
+    if !summary.levels.is_empty() {
+        let levels: Vec<String> = summary.levels.iter().map(|(level, count)| format!("{level} {count}")).collect();
+        println!("  Levels:   {}", levels.join(", "));
+    }
+
+    if let Some(rate) = summary.error_rate() {
+        let classes: Vec<String> = summary.status.iter().map(|(class, count)| format!("{class} {count}")).collect();
+        let share = format!("{:.1}% 5xx", rate * 100.0);
+        println!("  Status:   {} ({})", classes.join(", "), ternary!(rate == 0.0, share.green(), share.red()));
+    }
+
+    match &summary.field {
+        Some(field) => println!(
+            "  {}: p50 {} p95 {} max {} ({} lines)",
+            field.name,
+            field.p50,
+            field.p95,
+            field.max,
+            field.count
+        ),
+        None => println!("  {}", "No numeric field to summarize, see --stats-field".bright_black()),
+    }
+    println!("{}", "".bright_black());
+}
+
+/// A line split from the time its supervisor stamped it with, the time shown dimmed in local
+/// time with `timestamps` and left out otherwise. Lines without a stamp are kept as they are.
+fn stamped(line: &str, timestamps: bool) -> (String, &str) {
+    match supervise::split_stamp(line) {
+        Some((time, rest)) if timestamps => (format!("{} ", time.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S%.3f")).bright_black().to_string(), rest),
+        Some((_, rest)) => (string!(), rest),
+        None => (string!(), line),
+    }
+}
+
 /// Detect log level from line content and return appropriate indicator and color
 fn detect_log_level(line: &str, log_type: &str) -> (String, &'static str) {
     let line_lower = line.to_lowercase();
@@ -254,6 +447,61 @@ fn detect_log_level(line: &str, log_type: &str) -> (String, &'static str) {
     ("[OUT ]".to_string(), "white")
 }
 
+/// Bytes read at a time when a file is read from its end
+const CHUNK_SIZE: u64 = 8192;
+
+/// Offset where the last `lines` lines of the first `end` bytes start, reading backwards in chunks
+pub fn tail_offset(file: &mut File, end: u64, lines: usize) -> io::Result<u64> {
+    if lines == 0 {
+        return Ok(end);
+    }
+
+    let mut buf = vec![0; CHUNK_SIZE as usize];
+    let mut pos = end;
+    let mut seen = 0;
+
+    while pos > 0 {
+        let start = pos.saturating_sub(CHUNK_SIZE);
+        let chunk = &mut buf[..(pos - start) as usize];
+        file.seek(SeekFrom::Start(start))?;
+        file.read_exact(chunk)?;
+
+        for (index, byte) in chunk.iter().enumerate().rev() {
+            let at = start + index as u64;
+            // A trailing newline ends the last line rather than starting a new one
+            if *byte != b'\n' || at + 1 == end {
+                continue;
+            }
+
+            seen += 1;
+            if seen == lines {
+                return Ok(at + 1);
+            }
+        }
+
+        pos = start;
+    }
+
+    Ok(0)
+}
+
+/// Last `lines` lines of a file, reading only its end
+pub fn tail(path: &str, lines: usize) -> Vec<String> {
+    let Ok(mut file) = File::open(path) else { return vec![] };
+    let end = file.metadata().map_or(0, |metadata| metadata.len());
+    let mut bytes = vec![];
+
+    let read = tail_offset(&mut file, end, lines).and_then(|offset| {
+        file.seek(SeekFrom::Start(offset))?;
+        file.take(end - offset).read_to_end(&mut bytes)
+    });
+
+    match read {
+        Ok(_) => String::from_utf8_lossy(&bytes).lines().map(str::to_string).collect(),
+        Err(_) => vec![],
+    }
+}
+
 pub fn cwd() -> PathBuf {
     match env::current_dir() {
         Ok(path) => path,
@@ -330,7 +578,7 @@ pub fn from_object<T: serde::de::DeserializeOwned>(bytes: &[u8]) -> T {
     }
 }
 
-fn read_file_with_retry(path: &str) -> Result<Vec<u8>, String> {
+pub(crate) fn read_file_with_retry(path: &str) -> Result<Vec<u8>, String> {
     let mut retry_count = 0;
     let max_retries = 5;
 
@@ -393,3 +641,135 @@ pub fn read_object<T: serde::de::DeserializeOwned>(path: String) -> T {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn temp_log(name: &str) -> PathBuf {
+        let path = env::temp_dir().join(format!("opm-follow-{}-{name}.log", std::process::id()));
+        fs::write(&path, "existing line\n").unwrap();
+        path
+    }
+
+    fn append(path: &Path, contents: &str) {
+        let mut file = fs::OpenOptions::new().append(true).open(path).unwrap();
+        file.write_all(contents.as_bytes()).unwrap();
+    }
+
+    fn line(text: &str) -> FollowEvent {
+        FollowEvent::Line(text.to_string())
+    }
+
+    fn temp_file(name: &str, contents: &str) -> (PathBuf, File) {
+        let path = env::temp_dir().join(format!("opm-file-{}-{name}", std::process::id()));
+        fs::write(&path, contents).unwrap();
+        let file = File::open(&path).unwrap();
+        (path, file)
+    }
+
+    #[test]
+    fn test_tail_offset() {
+        let contents = "one\ntwo\nthree\n";
+        let (path, mut file) = temp_file("tail", contents);
+        let end = contents.len() as u64;
+
+        assert_eq!(&contents[tail_offset(&mut file, end, 2).unwrap() as usize..], "two\nthree\n");
+        assert_eq!(tail_offset(&mut file, end, 3).unwrap(), 0);
+        assert_eq!(tail_offset(&mut file, end, 10).unwrap(), 0);
+        assert_eq!(tail_offset(&mut file, end, 0).unwrap(), end);
+
+        fs::remove_file(path).ok();
+    }
+
+    #[test]
+    fn test_tail_offset_across_chunks() {
+        let contents = format!("{}\nlast\n", "x".repeat(CHUNK_SIZE as usize * 2));
+        let (path, mut file) = temp_file("chunks", &contents);
+        let end = contents.len() as u64;
+
+        assert_eq!(&contents[tail_offset(&mut file, end, 1).unwrap() as usize..], "last\n");
+        assert_eq!(tail_offset(&mut file, end, 2).unwrap(), 0);
+
+        fs::remove_file(path).ok();
+    }
+
+    #[test]
+    fn test_tail() {
+        let (path, _) = temp_file("tail-lines", "one\ntwo\nthree");
+        let path = path.to_str().unwrap();
+
+        assert_eq!(tail(path, 2), ["two", "three"]);
+        assert_eq!(tail(path, 10), ["one", "two", "three"]);
+        assert!(tail(path, 0).is_empty());
+        assert!(tail("/nonexistent/opm.log", 5).is_empty());
+
+        fs::remove_file(path).ok();
+    }
+
+    #[test]
+    fn test_follow_reads_appended_lines() {
+        let path = temp_log("append");
+        let mut follower = LogFollower::new(&path);
+        assert!(follower.poll().is_empty());
+
+        append(&path, "first\nsecond\npart");
+        assert_eq!(follower.poll(), vec![line("first"), line("second")]);
+
+        append(&path, "ial\n");
+        assert_eq!(follower.poll(), vec![line("partial")]);
+
+        fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_follow_survives_truncation() {
+        let path = temp_log("truncate");
+        let mut follower = LogFollower::new(&path);
+
+        append(&path, "before flush\n");
+        assert_eq!(follower.poll(), vec![line("before flush")]);
+
+        File::create(&path).unwrap();
+        append(&path, "after\n");
+        assert_eq!(follower.poll(), vec![FollowEvent::Rotated, line("after")]);
+
+        fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_follow_survives_replacement() {
+        let path = temp_log("replace");
+        let rotated = path.with_extension("log.1");
+        let mut follower = LogFollower::new(&path);
+
+        append(&path, "old file\n");
+        fs::rename(&path, &rotated).unwrap();
+
+        // Nothing at the path yet, keep reading the old handle
+        assert_eq!(follower.poll(), vec![line("old file")]);
+
+        fs::write(&path, "new file\n").unwrap();
+        assert_eq!(follower.poll(), vec![FollowEvent::Rotated, line("new file")]);
+
+        append(&path, "more\n");
+        assert_eq!(follower.poll(), vec![line("more")]);
+
+        fs::remove_file(&path).ok();
+        fs::remove_file(&rotated).ok();
+    }
+    #[test]
+    fn test_stamped_lines() {
+        let line = "2026-10-16T18:32:29.300Z listening on :8080";
+        assert_eq!(stamped(line, false), (string!(), "listening on :8080"));
+
+        let (stamp, rest) = stamped(line, true);
+        assert_eq!(rest, "listening on :8080");
+        assert!(stamp.contains("2026-10-1") && stamp.contains(":29.300"), "{stamp}");
+
+        // Written without a supervisor, nothing to take off
+        assert_eq!(stamped("listening on :8080", true), (string!(), "listening on :8080"));
+        assert_eq!(stamped("2026-10-16 plain text", false), (string!(), "2026-10-16 plain text"));
+    }
+}
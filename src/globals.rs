@@ -3,7 +3,7 @@ use macros_rs::{crashln, then};
 use once_cell::sync::OnceCell;
 use opm::{config, file::Exists, helpers};
 use serde::{Deserialize, Serialize};
-use std::fs;
+use std::{fs, path::Path};
 use utoipa::ToSchema;
 
 #[derive(Clone, Serialize, Deserialize, ToSchema)]
@@ -56,7 +56,21 @@ pub(crate) fn init() {
             init!("opm.base", format!("{path}/.opm/"));
             init!("opm.log", format!("{path}/.opm/opm.log"));
             init!("opm.pid", format!("{path}/.opm/daemon.pid"));
-            init!("opm.dump", format!("{path}/.opm/process.dump"));
+            init!("opm.lock", format!("{path}/.opm/daemon.lock"));
+            init!("opm.socket", format!("{path}/.opm/daemon.sock"));
+            init!("opm.audit", format!("{path}/.opm/audit.jsonl"));
+            init!("opm.events", format!("{path}/.opm/events.jsonl"));
+            init!("opm.metrics", format!("{path}/.opm/metrics.jsonl"));
+            let dump_path = config.runner.dump_path.clone().unwrap_or(format!("{path}/.opm/process.dump"));
+            if let Some(parent) = Path::new(&dump_path).parent()
+                && !Exists::check(&parent.to_string_lossy()).folder()
+            {
+                fs::create_dir_all(parent).unwrap();
+                log::info!("created opm dump dir");
+            }
+
+            init!("opm.dump", dump_path);
+            init!("opm.dump.format", config.runner.dump_format.as_str());
 
             init!("opm.daemon.kind", config.daemon.kind);
             init!("opm.daemon.log", format!("{path}/.opm/daemon.log"));
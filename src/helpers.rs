@@ -34,11 +34,17 @@ impl fmt::Display for ColoredString {
     }
 }
 
+/// Time since `datetime`, which the wall clock may have gone back past since
 pub fn format_duration(datetime: DateTime<Utc>) -> String {
-    let current_time = Utc::now();
-    let duration = current_time.signed_duration_since(datetime);
+    match Utc::now().signed_duration_since(datetime).num_seconds() {
+        s if s < 0 => "0s (clock skew detected)".to_string(),
+        s => format_seconds(s),
+    }
+}
 
-    match duration.num_seconds() {
+/// `3d`, `5h`, `2m` or `9s`, in the largest whole unit
+pub fn format_seconds(seconds: i64) -> String {
+    match seconds {
         s if s >= 86400 => format!("{}d", s / 86400),
         s if s >= 3600 => format!("{}h", s / 3600),
         s if s >= 60 => format!("{}m", s / 60),
@@ -65,6 +71,38 @@ pub fn format_memory(bytes: u64) -> String {
     [result, SUFFIX[base.floor() as usize]].join("")
 }
 
+/// Quote a value for a POSIX shell, embedded single quotes are closed, escaped and reopened
+pub fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+/// Full invocation the runner uses for a script, e.g. `/bin/sh -c 'node index.js'`
+pub fn shell_command(shell: &str, args: &[String], script: &str) -> String {
+    format!("{shell} {} {}", args.join(" "), shell_quote(script))
+}
+
+/// Single line preview of a command cut to `max` characters, `None` when it already fits
+pub fn truncate_command(command: &str, max: usize) -> Option<String> {
+    if !command.contains('\n') && command.chars().count() <= max {
+        return None;
+    }
+
+    let first = command.lines().next().unwrap_or_default();
+    Some(format!("{}…", first.chars().take(max).collect::<String>()))
+}
+
+/// Check a process status link is an absolute http(s) URL, returned trimmed as written
+pub fn parse_url(url: &str) -> Result<String, String> {
+    let url = url.trim();
+    let parsed = reqwest::Url::parse(url).map_err(|err| format!("Invalid URL '{url}': {err}"))?;
+
+    match parsed.scheme() {
+        "http" | "https" if parsed.host_str().is_some_and(|host| !host.is_empty()) => Ok(url.to_string()),
+        "http" | "https" => Err(format!("Invalid URL '{url}': missing host")),
+        scheme => Err(format!("Invalid URL '{url}': expected http or https, got {scheme}")),
+    }
+}
+
 /// Parse memory string like "100M", "1G", "500K" to bytes
 pub fn parse_memory(mem_str: &str) -> Result<u64, String> {
     let mem_str = mem_str.trim().to_uppercase();
@@ -101,3 +139,37 @@ pub fn parse_memory(mem_str: &str) -> Result<u64, String> {
         )),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shell_quote_is_verbatim() {
+        let script = "echo \"it's $HOME ${USER}\" `date` \\n\nsecond 'line' $((1 + 1))";
+        let output = std::process::Command::new("sh")
+            .arg("-c")
+            .arg(format!("printf %s {}", shell_quote(script)))
+            .output()
+            .unwrap();
+
+        assert_eq!(String::from_utf8_lossy(&output.stdout), script);
+    }
+
+    #[test]
+    fn test_truncate_command() {
+        assert_eq!(truncate_command("node index.js", 20), None);
+        assert_eq!(truncate_command("node index.js", 4).as_deref(), Some("node…"));
+        assert_eq!(truncate_command("cd /srv\nnode index.js", 20).as_deref(), Some("cd /srv…"));
+    }
+
+    #[test]
+    fn test_parse_url() {
+        assert_eq!(parse_url(" https://app.internal/health ").as_deref(), Ok("https://app.internal/health"));
+        assert_eq!(parse_url("http://10.0.0.5:8080").as_deref(), Ok("http://10.0.0.5:8080"));
+        assert!(parse_url("app.internal/health").is_err());
+        assert!(parse_url("/health").is_err());
+        assert!(parse_url("ftp://app.internal").unwrap_err().contains("expected http or https"));
+        assert!(parse_url("javascript:alert(1)").is_err());
+    }
+}
@@ -5,6 +5,10 @@ pub mod log;
 pub mod process;
 pub mod agent;
 pub mod notifications;
+pub mod output;
+pub mod audit;
+pub mod events;
+pub mod storage;
 
 // Deprecated
 // #[cxx::bridge]
@@ -29,6 +33,6 @@ pub mod notifications;
 pub use process::{
     get_process_cpu_usage_percentage, get_process_cpu_usage_percentage_fast,
     get_process_cpu_usage_with_children, get_process_cpu_usage_with_children_fast,
-    get_process_cpu_usage_with_children_from_process, get_process_memory_with_children,
-    process_find_children, process_run, process_stop,
+    get_process_cpu_usage_timed, get_process_cpu_usage_with_children_from_process, get_process_memory_with_children,
+    process_find_children, process_find_zombies, process_run, process_stop, process_stop_with_signal,
 };
@@ -3,6 +3,8 @@ use global_placeholders::global;
 use std::fs::{File, OpenOptions};
 use std::io::{self, Write};
 
+pub mod stats;
+
 pub struct Logger {
     file: File,
 }
@@ -0,0 +1,202 @@
+use crate::process::archive::line_time;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use utoipa::ToSchema;
+
+use std::collections::BTreeMap;
+
+/// Numeric field summarized when none is given
+pub const DEFAULT_FIELD: &str = "duration_ms";
+
+/// Distribution of a numeric field over the lines that have it
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, PartialEq)]
+#[schema(as = LogStatsField)]
+pub struct Field {
+    #[schema(example = "duration_ms")]
+    pub name: String,
+    /// Lines the field was found in as a number
+    #[schema(example = 1200)]
+    pub count: usize,
+    #[schema(example = 12.0)]
+    pub p50: f64,
+    #[schema(example = 87.5)]
+    pub p95: f64,
+    #[schema(example = 1503.0)]
+    pub max: f64,
+}
+
+/// What the JSON lines of a log say, for `opm logs --stats-json` and the logs route
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, Default, PartialEq)]
+#[schema(as = LogStats)]
+pub struct Summary {
+    /// Lines examined, parsed or not
+    #[schema(example = 1250)]
+    pub lines: usize,
+    /// Lines that are not a JSON object
+    #[schema(example = 3)]
+    pub unparsed: usize,
+    /// Lines per `level`, lowercased, `(none)` when an object has no level
+    pub levels: BTreeMap<String, usize>,
+    /// Lines per HTTP status class (`2xx`, `5xx`, ...), empty when no line has a `status`
+    pub status: BTreeMap<String, usize>,
+    /// Unset when no line has the field as a number
+    pub field: Option<Field>,
+}
+
+impl Summary {
+    /// Share of the lines with a status that are 5xx, when any line has one
+    pub fn error_rate(&self) -> Option<f64> {
+        let total: usize = self.status.values().sum();
+        (total > 0).then(|| *self.status.get("5xx").unwrap_or(&0) as f64 / total as f64)
+    }
+}
+
+/// The JSON object a line holds, also after a timestamp such as the one supervised logs are written with
+fn object(line: &str) -> Option<serde_json::Map<String, Value>> {
+    let line = line.trim();
+    let json = match line.starts_with('{') {
+        true => line,
+        false if line_time(line).is_some() => &line[line.find('{')?..],
+        false => return None,
+    };
+
+    match serde_json::from_str(json) {
+        Ok(Value::Object(object)) => Some(object),
+        _ => None,
+    }
+}
+
+/// Value at `name`, where dots reach into nested objects (`http.status`)
+fn lookup<'v>(object: &'v serde_json::Map<String, Value>, name: &str) -> Option<&'v Value> {
+    if let Some(value) = object.get(name) {
+        return Some(value);
+    }
+
+    let (first, rest) = name.split_once('.')?;
+    match object.get(first)? {
+        Value::Object(inner) => lookup(inner, rest),
+        _ => None,
+    }
+}
+
+/// Numbers and numeric strings, as loggers write both
+fn number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(number) => number.as_f64(),
+        Value::String(text) => text.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+fn level(value: Option<&Value>) -> String {
+    match value {
+        Some(Value::String(level)) => level.to_lowercase(),
+        Some(Value::Number(level)) => level.to_string(),
+        _ => String::from("(none)"),
+    }
+}
+
+/// Nearest-rank percentile of sorted values
+fn percentile(sorted: &[f64], percent: f64) -> f64 {
+    let rank = ((percent / 100.0) * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+/// Summarize the JSON lines among `lines`, reporting the distribution of `field`
+pub fn summarize<'l>(lines: impl IntoIterator<Item = &'l str>, field: &str) -> Summary {
+    let mut summary = Summary::default();
+    let mut values = Vec::new();
+
+    for line in lines {
+        summary.lines += 1;
+
+        let Some(object) = object(line) else {
+            summary.unparsed += 1;
+            continue;
+        };
+
+        *summary.levels.entry(level(object.get("level"))).or_default() += 1;
+
+        if let Some(status) = object.get("status").and_then(number).filter(|status| (100.0..1000.0).contains(status)) {
+            *summary.status.entry(format!("{}xx", status as u64 / 100)).or_default() += 1;
+        }
+
+        if let Some(value) = lookup(&object, field).and_then(number).filter(|value| value.is_finite()) {
+            values.push(value);
+        }
+    }
+
+    if !values.is_empty() {
+        values.sort_by(f64::total_cmp);
+        summary.field = Some(Field {
+            name: field.to_string(),
+            count: values.len(),
+            p50: percentile(&values, 50.0),
+            p95: percentile(&values, 95.0),
+            max: values[values.len() - 1],
+        });
+    }
+
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ACCESS_LOG: &str = r#"{"level":"info","status":200,"duration_ms":12,"path":"/"}
+{"level":"info","status":201,"duration_ms":30,"path":"/orders"}
+{"level":"WARN","status":404,"duration_ms":3,"path":"/missing"}
+{"level":"error","status":503,"duration_ms":"1500","path":"/orders"}
+listening on :8080
+{"level":"info","status":200,"duration_ms":8,"path":"/"}
+{"level":"debug","message":"cache warm"}
+{"level":"info","status":500,"duration_ms":95.5,"path":"/pay"}
+"#;
+
+    const SUPERVISED_LOG: &str = r#"2026-10-16T18:32:29.112Z {"level":30,"http":{"status":200,"latency":4}}
+2026-10-16T18:32:29.200Z {"level":50,"http":{"status":502,"latency":40}}
+2026-10-16T18:32:29.300Z plain text after a timestamp
+"#;
+
+    #[test]
+    fn test_summarize_access_log() {
+        let summary = summarize(ACCESS_LOG.lines(), DEFAULT_FIELD);
+
+        assert_eq!(summary.lines, 8);
+        assert_eq!(summary.unparsed, 1);
+        assert_eq!(summary.levels, BTreeMap::from([(String::from("debug"), 1), (String::from("error"), 1), (String::from("info"), 4), (String::from("warn"), 1)]));
+        assert_eq!(summary.status, BTreeMap::from([(String::from("2xx"), 3), (String::from("4xx"), 1), (String::from("5xx"), 2)]));
+        assert_eq!(summary.error_rate(), Some(2.0 / 6.0));
+
+        let field = summary.field.unwrap();
+        assert_eq!((field.count, field.p50, field.p95, field.max), (6, 12.0, 1500.0, 1500.0));
+    }
+
+    #[test]
+    fn test_summarize_nested_field_after_timestamp() {
+        let summary = summarize(SUPERVISED_LOG.lines(), "http.latency");
+
+        assert_eq!((summary.lines, summary.unparsed), (3, 1));
+        assert_eq!(summary.levels, BTreeMap::from([(String::from("30"), 1), (String::from("50"), 1)]));
+        // `status` is only read at the top level
+        assert!(summary.status.is_empty());
+        assert_eq!(summary.error_rate(), None);
+        assert_eq!(summary.field.unwrap().max, 40.0);
+    }
+
+    #[test]
+    fn test_summarize_without_json() {
+        let summary = summarize(["starting", "", "[1, 2]"], DEFAULT_FIELD);
+
+        assert_eq!(summary, Summary { lines: 3, unparsed: 3, ..Summary::default() });
+    }
+
+    #[test]
+    fn test_percentile() {
+        let values: Vec<f64> = (1..=100).map(f64::from).collect();
+        assert_eq!((percentile(&values, 50.0), percentile(&values, 95.0)), (50.0, 95.0));
+        assert_eq!(percentile(&[7.0], 95.0), 7.0);
+    }
+}
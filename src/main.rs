@@ -1,11 +1,33 @@
+#![recursion_limit = "256"]
+
 mod cli;
 mod daemon;
 mod globals;
 mod webui;
 
+use chrono::{DateTime, TimeDelta, Utc};
 use clap::{Parser, Subcommand};
 use clap_verbosity_flag::{LogLevel, Verbosity};
-use macros_rs::{str, string};
+use macros_rs::{crashln, str, string, ternary};
+use nix::sys::signal::Signal;
+use opm::{
+    alert,
+    file::LogOptions,
+    notice,
+    process::{
+        StartOptions,
+        alerts::Alert,
+        checkpoint::Window,
+        cron::Schedule,
+        health::{self, Probe},
+        limits::Limits,
+        logdir,
+        lograte::MaxLogRate,
+        memlimit,
+        shell::Shell,
+    },
+};
+use std::io::{IsTerminal, stdout};
 use update_informer::{Check, registry};
 
 use crate::{
@@ -19,6 +41,10 @@ impl LogLevel for NoneLevel {
     fn default() -> Option<log::Level> {
         None
     }
+
+    fn quiet_help() -> Option<&'static str> {
+        Some("Only print errors (to stderr) and the output requested with --format")
+    }
 }
 
 #[derive(Parser)]
@@ -28,6 +54,21 @@ struct Cli {
     command: Commands,
     #[clap(flatten)]
     verbose: Verbosity<NoneLevel>,
+    /// Do not start the daemon when it is not running, like `daemon.autostart = false`
+    #[arg(long, global = true)]
+    no_daemon: bool,
+}
+
+impl Cli {
+    /// `--quiet` comes from the verbosity flags, logging is already off by default
+    fn quiet(&self) -> bool {
+        self.verbose.is_present() && self.verbose.is_silent()
+    }
+
+    /// Whether commands may start the daemon, the config is only read without `--no-daemon`
+    fn autostart(&self) -> bool {
+        !self.no_daemon && opm::config::read().daemon.autostart
+    }
 }
 
 #[derive(Subcommand)]
@@ -47,17 +88,56 @@ enum Daemon {
         /// WebUI using api
         #[arg(long)]
         webui: bool,
+        /// Run the daemon in this process instead of forking, as the entrypoint of a container
+        #[arg(long)]
+        foreground: bool,
+        /// Act as init: reap orphans and stop the processes on SIGTERM or SIGINT, implied when running as pid 1
+        #[arg(long)]
+        pid1: bool,
+        /// Set a variable in the environment of the daemon, over `daemon.env` (repeatable)
+        #[arg(long = "daemon-env", value_name = "KEY=VALUE", value_parser = opm::process::environment::parse)]
+        daemon_env: Vec<(String, String)>,
     },
+    /// Apply config.toml changes to the running daemon without restarting it
+    #[command(name = "reload-config")]
+    ReloadConfig,
     /// Check daemon health
     #[command(visible_alias = "info", visible_alias = "status")]
     Health {
         /// Format output
         #[arg(long, default_value_t = string!("default"))]
         format: String,
+        /// Also render the WebUI pages and look up the assets they link to
+        #[arg(long)]
+        check_webui: bool,
+    },
+    /// Show how often the daemon read and wrote the dump, scanned processes, sampled cpu and restarted
+    Stats {
+        /// Format output
+        #[arg(long, default_value_t = string!("default"))]
+        format: String,
+        /// Start the counters over from 0 after showing them
+        #[arg(long)]
+        reset: bool,
+    },
+    /// Manage named API tokens, each limited to some actions and processes
+    Token {
+        #[command(subcommand)]
+        command: TokenCommand,
     },
     /// Setup systemd service to start OPM daemon automatically
     #[command(visible_alias = "install")]
-    Setup,
+    Setup {
+        /// Stop, disable and remove the service instead
+        #[arg(long)]
+        uninstall: bool,
+        /// Show what would be written or run without changing anything
+        #[arg(long)]
+        dry_run: bool,
+        /// Update a service pointing at another opm binary without asking
+        #[arg(short, long, conflicts_with = "uninstall")]
+        yes: bool,
+    },
 }
 
 // add opm restore command
@@ -67,7 +147,27 @@ enum Commands {
     #[command(visible_alias = "add")]
     Import {
         /// Path of file to import
-        path: String,
+        #[arg(required_unless_present = "from_pm2")]
+        path: Option<String>,
+        /// Register the processes as pending and let the daemon start them, see `opm import-status`
+        #[arg(long, visible_alias = "async")]
+        background: bool,
+        /// Take over the processes of pm2, registered stopped unless --start is given
+        #[arg(long, group = "pm2", conflicts_with_all = ["path", "background"])]
+        from_pm2: bool,
+        /// With --from-pm2, start the imported processes right away
+        // Through a group, clap drops a requirement on an argument that conflicts with a given one
+        #[arg(long, requires = "pm2")]
+        start: bool,
+        /// With --start, stop each pm2 process once its copy keeps running
+        #[arg(long, requires = "start")]
+        stop_pm2: bool,
+    },
+    /// Progress of the last `opm import --background`
+    ImportStatus {
+        /// Format output (default|json)
+        #[arg(long, default_value_t = string!("default"))]
+        format: String,
     },
     /// Export environment file from process
     #[command(visible_alias = "get")]
@@ -82,14 +182,44 @@ enum Commands {
         /// Process name
         #[arg(long)]
         name: Option<String>,
-        #[clap(value_parser = cli::validate::<Args>)]
-        args: Args,
+        #[clap(value_parser = cli::validate::<Args>, required_unless_present = "command_file")]
+        args: Option<Args>,
+        /// Read the command from a file, keeping newlines and quotes as written
+        #[arg(long, value_name = "PATH", conflicts_with = "args")]
+        command_file: Option<std::path::PathBuf>,
         /// Watch to reload path
         #[arg(long)]
         watch: Option<String>,
         /// Maximum memory limit (e.g., 100M, 1G)
+        #[arg(long, value_parser = opm::helpers::parse_memory)]
+        max_memory: Option<u64>,
+        /// What the daemon does once the process goes over --max-memory: stop (default) or restart
+        #[arg(long, value_name = "ACTION", requires = "max_memory", value_parser = opm::process::memlimit::parse)]
+        max_memory_action: Option<memlimit::Action>,
+        /// File mode mask for the process, in octal (e.g. 0002)
+        #[arg(long, value_name = "MASK", value_parser = opm::process::limits::parse_umask)]
+        umask: Option<u32>,
+        /// Soft limit on open files for the process (number or unlimited)
+        #[arg(long, value_name = "LIMIT", value_parser = opm::process::limits::parse_nofile)]
+        ulimit_nofile: Option<u64>,
+        /// Soft limit on core file size in bytes for the process, 0 disables core dumps (number or unlimited)
+        #[arg(long, value_name = "LIMIT", value_parser = opm::process::limits::parse_core)]
+        ulimit_core: Option<u64>,
+        /// Enable core dumps and collect the cores crashes leave into ~/.opm/cores/<name>
+        #[arg(long)]
+        collect_cores: bool,
+        /// Run the process under a supervisor that timestamps and rotates its logs and records its exit code
         #[arg(long)]
-        max_memory: Option<String>,
+        supervised: bool,
+        /// Prefix every log line with the time it was written, runs the process supervised (see `runner.log_timestamps`)
+        #[arg(long)]
+        log_timestamps: bool,
+        /// Shell to run the process in instead of runner.shell, an absolute path (e.g. /usr/bin/fish)
+        #[arg(long, value_name = "PATH", value_parser = opm::process::shell::parse)]
+        shell: Option<String>,
+        /// Argument given to the shell before the script instead of runner.args (repeatable, e.g. --shell-arg -l --shell-arg -c)
+        #[arg(long = "shell-arg", value_name = "ARG", allow_hyphen_values = true)]
+        shell_args: Vec<String>,
         /// Agent connection (use with agent-enabled server)
         #[arg(short, long)]
         server: Option<String>,
@@ -102,33 +232,110 @@ enum Commands {
         /// Port range for workers (e.g., "3000-3010" or just "3000" for SO_REUSEPORT)
         #[arg(short = 'p', long)]
         port_range: Option<String>,
+        /// Label to attach to the new process (key=value, repeatable)
+        #[arg(long = "label", value_name = "KEY=VALUE", value_parser = opm::process::labels::parse)]
+        labels: Vec<(String, String)>,
+        /// Environment variable of the new process, over the shell's and under .env, kept across restarts (KEY=VALUE, repeatable)
+        #[arg(long = "env", value_name = "KEY=VALUE", value_parser = opm::process::environment::parse)]
+        env: Vec<(String, String)>,
+        /// Seconds the new process is reported as starting before it counts as online [default: 1]
+        #[arg(long, value_name = "SECS")]
+        startup_grace: Option<u64>,
+        /// Milliseconds between the daemon's checks of the new process, at least 100 [default: daemon.interval]
+        #[arg(long, value_name = "MS", value_parser = opm::process::parse_monitor_interval)]
+        monitor_interval: Option<u64>,
+        /// Crashes the daemon restarts the new process after, 0 never restarts it [default: daemon.restarts]
+        #[arg(long, value_name = "COUNT")]
+        max_restarts: Option<u64>,
+        /// Seconds after a watch reload in which further changes are coalesced into one reload [default: 5]
+        #[arg(long, value_name = "SECS", requires = "watch")]
+        watch_cooldown: Option<u64>,
+        /// Status page of the new process, shown in `opm list --format wide` and the web UI
+        #[arg(long, value_parser = opm::helpers::parse_url)]
+        url: Option<String>,
+        /// Free text kept with the new process, shown by `opm details` and the web UI (at most 1 KB)
+        #[arg(long, value_name = "TEXT", value_parser = opm::process::description::parse)]
+        description: Option<String>,
+        /// Group to manage the new process with, e.g. `opm restart group:api` [default: the name with --workers]
+        #[arg(long, value_name = "NAME", value_parser = opm::process::group::parse)]
+        group: Option<String>,
+        /// Alert when a metric stays above a threshold, e.g. "fds above 900 for 1m" (repeatable)
+        #[arg(long = "alert", value_name = "RULE", value_parser = opm::process::alerts::parse)]
+        alerts: Vec<Alert>,
+        /// Act when the logs grow faster than this for several checks, e.g. "100M/min" or "1G/h restart" (warn, restart or stop)
+        #[arg(long, value_name = "RATE", value_parser = opm::process::lograte::parse)]
+        max_log_rate: Option<MaxLogRate>,
+        /// Restart the process at the times of a cron expression, e.g. "0 3 * * *" for every night at 3
+        #[arg(long, value_name = "CRON", value_parser = opm::process::cron::parse)]
+        cron_restart: Option<Schedule>,
+        /// Check the process is healthy with a GET to this url, restarting it after --health-retries failures
+        #[arg(long, value_name = "URL", group = "health", value_parser = opm::helpers::parse_url)]
+        health_url: Option<String>,
+        /// Check the process is healthy by running this command in its directory, exit code 0 passes
+        #[arg(long, value_name = "COMMAND", group = "health", value_parser = opm::process::health::parse_command)]
+        health_cmd: Option<String>,
+        /// Seconds between health checks [default: 10]
+        #[arg(long, value_name = "SECS", requires = "health", value_parser = clap::value_parser!(u64).range(1..))]
+        health_interval: Option<u64>,
+        /// Failed health checks in a row before the process is restarted [default: 3]
+        #[arg(long, value_name = "COUNT", requires = "health", value_parser = clap::value_parser!(u32).range(1..))]
+        health_retries: Option<u32>,
+        /// Seconds a health check may take before it fails [default: 5]
+        #[arg(long, value_name = "SECS", requires = "health", value_parser = clap::value_parser!(u64).range(1..))]
+        health_timeout: Option<u64>,
+        /// For services that fork into the background: monitor the pid they write to this file
+        #[arg(long, value_name = "PATH", conflicts_with = "workers")]
+        pidfile: Option<std::path::PathBuf>,
+        /// Expand {{hostname}}, {{port}}, {{opm.process_name}}, {{opm.process_id}} and {{opm.worker_index}} in env values at every spawn
+        #[arg(long)]
+        expand_env_templates: bool,
+        /// Replace the command of the process already named --name instead of refusing to start it
+        #[arg(long, requires = "name")]
+        force_update: bool,
     },
     /// Stop/Kill a process
     #[command(visible_alias = "kill")]
     Stop {
-        #[clap(value_parser = cli::validate_items)]
-        items: Items,
+        #[clap(value_parser = cli::validate_items, required_unless_present = "labels")]
+        items: Option<Items>,
         /// Agent connection (use with agent-enabled server)
         #[arg(short, long)]
         server: Option<String>,
+        /// Kill whatever is still running after this many seconds (with `stop all`)
+        #[arg(long, value_name = "SECS")]
+        deadline: Option<u64>,
+        /// Signal to stop with instead of SIGTERM (e.g. SIGINT, quit)
+        #[arg(long, value_parser = opm::process::signal::parse)]
+        signal: Option<Signal>,
+        /// Label to filter processes by (key=value, repeat to match all)
+        #[arg(long = "label", value_name = "KEY=VALUE", value_parser = opm::process::labels::parse)]
+        labels: Vec<(String, String)>,
     },
     /// Stop then remove a process
     #[command(visible_alias = "rm", visible_alias = "delete")]
     Remove {
         #[clap(value_parser = cli::validate_items)]
         items: Items,
+        /// Keep the logs of the removed processes without asking
+        #[arg(long)]
+        keep_logs: bool,
+        /// Delete the logs of the removed processes, with their rotations, without asking
+        #[arg(long, conflicts_with = "keep_logs")]
+        purge_logs: bool,
         /// Agent connection (use with agent-enabled server)
         #[arg(short, long)]
         server: Option<String>,
     },
     /// Get env of a process
-    #[command(visible_alias = "cmdline")]
+    #[command(visible_alias = "cmdline", args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
     Env {
-        #[clap(value_parser = cli::validate::<Item>)]
-        item: Item,
+        #[clap(required = true, value_parser = cli::validate::<Item>)]
+        item: Option<Item>,
         /// Agent connection (use with agent-enabled server)
         #[arg(short, long)]
         server: Option<String>,
+        #[command(subcommand)]
+        command: Option<EnvCommand>,
     },
     /// Get information of a process
     #[command(visible_alias = "info")]
@@ -141,10 +348,51 @@ enum Commands {
         /// Agent connection (use with agent-enabled server)
         #[arg(short, long)]
         server: Option<String>,
+        /// Save, diff, list or delete snapshots (e.g. --snapshot save before-deploy)
+        #[arg(long, num_args = 1..=2, value_names = ["ACTION", "NAME"])]
+        snapshot: Option<Vec<String>>,
     },
     /// List all processes
     #[command(visible_alias = "ls")]
     List {
+        /// Format output (raw, json, csv, minimal, default or wide, which adds the url column)
+        #[arg(long, default_value_t = string!("default"))]
+        format: String,
+        /// Agent connection (use with agent-enabled server)
+        #[arg(short, long)]
+        server: Option<String>,
+        /// Label to filter processes by (key=value, repeat to match all)
+        #[arg(long = "label", value_name = "KEY=VALUE", value_parser = opm::process::labels::parse)]
+        labels: Vec<(String, String)>,
+        /// Columns to show, in order (id,name,pid,uptime,restarts,status,cpu,mem,watch,url,group,ports,restarts_remaining,labels)
+        #[arg(long, value_delimiter = ',', value_parser = cli::columns::parse)]
+        columns: Vec<String>,
+        /// Truncate the table to this many characters, defaults to the terminal width
+        #[arg(long)]
+        max_width: Option<usize>,
+        /// List the local processes and those of every server, fetched concurrently
+        #[arg(long, conflicts_with = "server")]
+        all_servers: bool,
+        /// With --all-servers, one table with a server column instead of a table per server
+        #[arg(long, requires = "all_servers")]
+        merged: bool,
+    },
+    /// Resource usage statistics
+    Stats {
+        #[command(subcommand)]
+        command: StatsCommand,
+    },
+    /// Show who started, stopped or changed processes, from the CLI or the API
+    Audit {
+        /// Number of latest entries to show
+        #[arg(long, default_value_t = 50)]
+        lines: usize,
+        /// Only operations on this process
+        #[arg(long)]
+        process: Option<String>,
+        /// Only this action (start, stop, restart, remove, adjust, flush, save, restore...)
+        #[arg(long)]
+        action: Option<String>,
         /// Format output
         #[arg(long, default_value_t = string!("default"))]
         format: String,
@@ -152,6 +400,79 @@ enum Commands {
         #[arg(short, long)]
         server: Option<String>,
     },
+    /// Notifications the daemon sent, or failed to send, to its channels
+    Notifications {
+        #[command(subcommand)]
+        command: NotificationsCommand,
+    },
+    /// Block until processes reach a state, exits 1 on timeout and 2 once they cannot reach it without a command
+    Wait {
+        #[clap(value_parser = cli::validate_items)]
+        items: Items,
+        /// State to wait for (online, stopped or crashed)
+        #[arg(long = "for", value_name = "STATE", default_value = "online")]
+        target: cli::wait::State,
+        /// Seconds to wait before giving up
+        #[arg(long, value_name = "SECS", default_value_t = 60)]
+        timeout: u64,
+        /// Done once every process reached the state (default)
+        #[arg(long, conflicts_with = "any")]
+        all: bool,
+        /// Done once one of the processes reached the state
+        #[arg(long)]
+        any: bool,
+        /// Agent connection (use with agent-enabled server)
+        #[arg(short, long)]
+        server: Option<String>,
+    },
+    /// Process events the daemon recorded (crashes, restarts, memory limits...), optionally followed live
+    Events {
+        /// Keep printing events as they happen, reconnecting when the daemon restarts
+        #[arg(short, long)]
+        follow: bool,
+        /// Format output (default|json|jsonl), json cannot be followed
+        #[arg(long, default_value_t = string!("default"))]
+        format: String,
+        /// Only these event types (crash,restart,gave_up,memory_limit,watch_reload,watch_held,import_start,import_failed,alert,log_rate,logs_full)
+        #[arg(long, value_delimiter = ',')]
+        event_types: Vec<opm::events::Kind>,
+        /// Only events of this process, by name or id
+        #[arg(long)]
+        process: Option<String>,
+        /// Number of recorded events to print first, 20 by default and none when following
+        #[arg(long)]
+        lines: Option<usize>,
+        /// Wait for the daemon instead of failing when it is not reachable
+        #[arg(long)]
+        wait: bool,
+        /// Agent connection (use with agent-enabled server)
+        #[arg(short, long)]
+        server: Option<String>,
+    },
+    /// Hourly cpu, memory, restart and uptime figures per process from the daemon's samples
+    Report {
+        /// Start of the report (YYYY-MM-DD [HH:MM[:SS]], RFC 3339, or ago like 7d) [default: 7d]
+        #[arg(long, value_name = "TIME", value_parser = opm::process::checkpoint::parse_time)]
+        from: Option<DateTime<Utc>>,
+        /// End of the report, a plain date includes that day [default: now]
+        #[arg(long, value_name = "TIME", value_parser = cli::report::parse_until)]
+        to: Option<DateTime<Utc>>,
+        /// Format output (csv or ndjson)
+        #[arg(long, default_value_t = string!("csv"), value_parser = ["csv", "ndjson"])]
+        format: String,
+        /// Write the report to a file instead of printing it
+        #[arg(short, long, value_name = "PATH")]
+        output: Option<std::path::PathBuf>,
+        /// One row per hour and process, or per hour and group label (process or group)
+        #[arg(long, default_value = "process")]
+        by: opm::process::stats::Rollup,
+    },
+    /// Interactive terminal UI to browse, follow and act on processes
+    Ui {
+        /// Agent connection to open first, tab switches between servers
+        #[arg(short, long)]
+        server: Option<String>,
+    },
     /// Restore all processes
     #[command(visible_alias = "resurrect")]
     Restore {
@@ -168,14 +489,11 @@ enum Commands {
     },
     /// Get logs from a process
     Logs {
-        #[clap(value_parser = cli::validate::<Item>)]
-        item: Item,
-        #[arg(
-            long,
-            default_value_t = 15,
-            help = "Number of lines to display from the end of the log file"
-        )]
-        lines: usize,
+        #[clap(value_parser = cli::validate_items, required_unless_present = "labels")]
+        items: Option<Items>,
+        /// Number of lines from the end of each log file [default: 15, every matching line with --output, --since or --until]
+        #[arg(long)]
+        lines: Option<usize>,
         /// Agent connection (use with agent-enabled server)
         #[arg(short, long)]
         server: Option<String>,
@@ -191,15 +509,69 @@ enum Commands {
         /// Show log statistics
         #[arg(long)]
         stats: bool,
+        /// Parse JSON log lines and show counts per level, HTTP status classes and percentiles of --stats-field
+        #[arg(long)]
+        stats_json: bool,
+        /// Numeric JSON field summarized by --stats-json, dots reach into nested objects
+        #[arg(long, value_name = "FIELD", default_value = opm::log::stats::DEFAULT_FIELD, requires = "stats_json")]
+        stats_field: String,
+        /// Label to filter processes by (key=value, repeat to match all)
+        #[arg(long = "label", value_name = "KEY=VALUE", value_parser = opm::process::labels::parse)]
+        labels: Vec<(String, String)>,
+        /// Write the logs into a .tar.gz archive instead of printing them
+        #[arg(short, long, value_name = "PATH", conflicts_with = "follow")]
+        output: Option<std::path::PathBuf>,
+        /// Only export lines logged in the last N hours
+        #[arg(long, value_name = "HOURS", requires = "output")]
+        hours: Option<u64>,
+        /// Only show lines logged at or after TIME (HH:MM[:SS], YYYY-MM-DD [HH:MM[:SS]], RFC 3339, or ago like 30m)
+        #[arg(long, value_name = "TIME", value_parser = opm::process::checkpoint::parse_time, conflicts_with_all = ["follow", "output"])]
+        since: Option<DateTime<Utc>>,
+        /// Only show lines logged at or before TIME, same formats as --since
+        #[arg(long, value_name = "TIME", value_parser = opm::process::checkpoint::parse_time, conflicts_with_all = ["follow", "output"])]
+        until: Option<DateTime<Utc>>,
+        /// Show the time each line was written, kept in the logs of supervised processes
+        #[arg(long)]
+        timestamps: bool,
+        /// Read on into the rotated logs when the current one has fewer than --lines
+        #[arg(long, conflicts_with_all = ["since", "until"])]
+        rotated: bool,
     },
     /// Flush a process log
     #[command(visible_alias = "clean", visible_alias = "log_rotate")]
     Flush {
+        /// Process id or name, `all` flushes every process
         #[clap(value_parser = cli::validate::<Item>)]
         item: Item,
         /// Agent connection (use with agent-enabled server)
         #[arg(short, long)]
         server: Option<String>,
+        /// Skip the confirmation of `flush all`
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// List the core files crashes of a process with `collect_cores` left behind
+    Cores {
+        #[clap(value_parser = cli::validate::<Item>)]
+        item: Item,
+        /// Delete the listed core files
+        #[arg(long)]
+        clean: bool,
+    },
+    /// Carry the logs left in the old directory by a change of runner.log_path over to the current one
+    #[command(name = "migrate-logs")]
+    MigrateLogs {
+        /// Move the files, the default
+        #[arg(long = "move", conflicts_with = "symlink")]
+        moved: bool,
+        /// Link the files from the current directory instead of moving them
+        #[arg(long)]
+        symlink: bool,
+    },
+    /// Copies of the dump the daemon takes every daemon.snapshot_interval
+    Snapshots {
+        #[command(subcommand)]
+        command: SnapshotsCommand,
     },
     /// Daemon management
     #[command(visible_alias = "bgd")]
@@ -210,11 +582,23 @@ enum Commands {
 
     /// Restart a process
     Restart {
-        #[clap(value_parser = cli::validate_items)]
-        items: Items,
+        #[clap(value_parser = cli::validate_items, required_unless_present = "labels")]
+        items: Option<Items>,
         /// Agent connection (use with agent-enabled server)
         #[arg(short, long)]
         server: Option<String>,
+        /// Label to filter processes by (key=value, repeat to match all)
+        #[arg(long = "label", value_name = "KEY=VALUE", value_parser = opm::process::labels::parse)]
+        labels: Vec<(String, String)>,
+        /// Only restart when the command, environment, .env, memory limit or watched files changed since the last start
+        #[arg(long)]
+        if_changed: bool,
+        /// Restart one process at a time, each once the previous is online, workers of a group in index order
+        #[arg(long)]
+        rolling: bool,
+        /// Milliseconds to wait after a process of a rolling restart is online
+        #[arg(long, value_name = "MS", default_value_t = 2000, requires = "rolling")]
+        delay_ms: u64,
     },
 
     /// Reload a process (same as restart - stops and starts the process)
@@ -224,6 +608,22 @@ enum Commands {
         /// Agent connection (use with agent-enabled server)
         #[arg(short, long)]
         server: Option<String>,
+        /// Only reload when the command, environment, .env, memory limit or watched files changed since the last start
+        #[arg(long)]
+        if_changed: bool,
+    },
+
+    /// Reload a process exactly like a watch change would, for deploy scripts and CI
+    #[command(visible_alias = "trigger")]
+    TriggerReload {
+        #[clap(value_parser = cli::validate::<Item>)]
+        item: Item,
+        /// Why the reload was requested, stored as the last restart reason
+        #[arg(long, default_value_t = string!("trigger"))]
+        reason: String,
+        /// Agent connection (use with agent-enabled server)
+        #[arg(short, long)]
+        server: Option<String>,
     },
 
     /// Get startup command for a process
@@ -231,22 +631,157 @@ enum Commands {
     GetCommand {
         #[clap(value_parser = cli::validate::<Item>)]
         item: Item,
+        /// Print only the stored script, exactly as it is run
+        #[arg(long, conflicts_with = "format")]
+        raw: bool,
+        /// Print the command for a shell, or the process as a systemd unit, a Dockerfile or a compose service
+        #[arg(long, default_value = "shell", value_parser = ["shell", "systemd", "dockerfile", "compose"])]
+        format: String,
+        /// Write env values that look like secrets (tokens, passwords, keys) instead of redacting them
+        #[arg(long)]
+        show_secrets: bool,
         /// Agent connection (use with agent-enabled server)
         #[arg(short, long)]
         server: Option<String>,
     },
 
-    /// Adjust process command and/or name
+    /// Adjust the command, name or settings of one or more processes
     #[command(visible_alias = "update", visible_alias = "modify")]
     Adjust {
-        #[clap(value_parser = cli::validate::<Item>)]
-        item: Item,
+        #[clap(value_parser = cli::validate_items)]
+        items: Items,
         /// New execution command/script
         #[arg(long)]
         command: Option<String>,
+        /// Read the new command from a file, keeping newlines and quotes as written
+        #[arg(long, value_name = "PATH", conflicts_with = "command")]
+        command_file: Option<std::path::PathBuf>,
         /// New process name
         #[arg(long)]
         name: Option<String>,
+        /// Label to add or replace (key=value, repeatable)
+        #[arg(long = "label", value_name = "KEY=VALUE", value_parser = opm::process::labels::parse)]
+        labels: Vec<(String, String)>,
+        /// Label key to remove (repeatable)
+        #[arg(long, value_name = "KEY")]
+        unlabel: Vec<String>,
+        /// New status page link
+        #[arg(long, value_parser = opm::helpers::parse_url)]
+        url: Option<String>,
+        /// Remove the status page link
+        #[arg(long, conflicts_with = "url")]
+        no_url: bool,
+        /// New description, shown by `opm details` and the web UI (at most 1 KB)
+        #[arg(long, value_name = "TEXT", value_parser = opm::process::description::parse)]
+        description: Option<String>,
+        /// Remove the description
+        #[arg(long, conflicts_with = "description")]
+        no_description: bool,
+        /// Move the process into a group, managed together as `group:NAME`
+        #[arg(long, value_name = "NAME", value_parser = opm::process::group::parse)]
+        group: Option<String>,
+        /// Take the process out of its group
+        #[arg(long, conflicts_with = "group")]
+        no_group: bool,
+        /// Crashes the daemon restarts the process after, 0 never restarts it
+        #[arg(long, value_name = "COUNT")]
+        max_restarts: Option<u64>,
+        /// Go back to daemon.restarts for the restart limit
+        #[arg(long, conflicts_with = "max_restarts")]
+        no_max_restarts: bool,
+        /// Seconds after a watch reload in which further changes are coalesced into one reload
+        #[arg(long, value_name = "SECS")]
+        watch_cooldown: Option<u64>,
+        /// Go back to the default watch cooldown of 5 seconds
+        #[arg(long, conflicts_with = "watch_cooldown")]
+        no_watch_cooldown: bool,
+        /// New file mode mask in octal, used from the next restart (e.g. 0002)
+        #[arg(long, value_name = "MASK", value_parser = opm::process::limits::parse_umask)]
+        umask: Option<u32>,
+        /// New soft limit on open files, used from the next restart (number or unlimited)
+        #[arg(long, value_name = "LIMIT", value_parser = opm::process::limits::parse_nofile)]
+        ulimit_nofile: Option<u64>,
+        /// New soft limit on core file size in bytes, used from the next restart (number or unlimited)
+        #[arg(long, value_name = "LIMIT", value_parser = opm::process::limits::parse_core)]
+        ulimit_core: Option<u64>,
+        /// Enable core dumps and collect crash cores, from the next restart
+        #[arg(long)]
+        collect_cores: bool,
+        /// Stop collecting crash cores, from the next restart
+        #[arg(long, conflicts_with = "collect_cores")]
+        no_collect_cores: bool,
+        /// Run the process under a supervisor, from the next restart
+        #[arg(long)]
+        supervised: bool,
+        /// Run the process directly again, from the next restart
+        #[arg(long, conflicts_with = "supervised")]
+        no_supervised: bool,
+        /// Track the children of the process and count them in its cpu and memory
+        #[arg(long)]
+        track_children: bool,
+        /// Stop tracking children, for processes that fork many short-lived ones (build servers, cron-like parents)
+        #[arg(long, conflicts_with = "track_children")]
+        no_track_children: bool,
+        /// New shell to run the process in, from the next restart (an absolute path)
+        #[arg(long, value_name = "PATH", value_parser = opm::process::shell::parse)]
+        shell: Option<String>,
+        /// Replace the arguments given to the shell before the script, from the next restart (repeatable)
+        #[arg(long = "shell-arg", value_name = "ARG", allow_hyphen_values = true)]
+        shell_args: Vec<String>,
+        /// Go back to runner.shell and runner.args, from the next restart
+        #[arg(long, conflicts_with_all = ["shell", "shell_args"])]
+        no_shell: bool,
+        /// Replace the alerts with these, e.g. "threads above 200 for 5m" (repeatable)
+        #[arg(long = "alert", value_name = "RULE", value_parser = opm::process::alerts::parse)]
+        alerts: Vec<Alert>,
+        /// Remove all alerts
+        #[arg(long, conflicts_with = "alerts")]
+        no_alerts: bool,
+        /// New log growth limit, e.g. "100M/min stop"
+        #[arg(long, value_name = "RATE", value_parser = opm::process::lograte::parse)]
+        max_log_rate: Option<MaxLogRate>,
+        /// Remove the log growth limit
+        #[arg(long, conflicts_with = "max_log_rate")]
+        no_max_log_rate: bool,
+        /// What the daemon does once the process goes over its memory limit: stop or restart
+        #[arg(long, value_name = "ACTION", value_parser = opm::process::memlimit::parse)]
+        max_memory_action: Option<memlimit::Action>,
+        /// New restart schedule, a cron expression like "0 3 * * *"
+        #[arg(long, value_name = "CRON", value_parser = opm::process::cron::parse)]
+        cron_restart: Option<Schedule>,
+        /// Remove the restart schedule
+        #[arg(long, conflicts_with = "cron_restart")]
+        no_cron_restart: bool,
+        /// New health check, a GET to this url
+        #[arg(long, value_name = "URL", conflicts_with = "health_cmd", value_parser = opm::helpers::parse_url)]
+        health_url: Option<String>,
+        /// New health check, a command run in the directory of the process
+        #[arg(long, value_name = "COMMAND", value_parser = opm::process::health::parse_command)]
+        health_cmd: Option<String>,
+        /// New seconds between health checks
+        #[arg(long, value_name = "SECS", value_parser = clap::value_parser!(u64).range(1..))]
+        health_interval: Option<u64>,
+        /// New number of failed health checks in a row before a restart
+        #[arg(long, value_name = "COUNT", value_parser = clap::value_parser!(u32).range(1..))]
+        health_retries: Option<u32>,
+        /// New seconds a health check may take
+        #[arg(long, value_name = "SECS", value_parser = clap::value_parser!(u64).range(1..))]
+        health_timeout: Option<u64>,
+        /// Remove the health check
+        #[arg(long, conflicts_with_all = ["health_url", "health_cmd", "health_interval", "health_retries", "health_timeout"])]
+        no_health: bool,
+        /// Environment variable to set, used from the next restart and kept over the restarting shell's (KEY=VALUE, repeatable)
+        #[arg(long, visible_alias = "env", value_name = "KEY=VALUE", value_parser = opm::process::environment::parse)]
+        env_add: Vec<(String, String)>,
+        /// Environment variable to remove, used from the next restart (repeatable)
+        #[arg(long, value_name = "KEY")]
+        env_remove: Vec<String>,
+        /// Restart the process once adjusted, so it uses the changes right away
+        #[arg(long)]
+        restart: bool,
+        /// Show what would change for each process without writing anything
+        #[arg(long, conflicts_with = "restart")]
+        dry_run: bool,
         /// Agent connection (use with agent-enabled server)
         #[arg(short, long)]
         server: Option<String>,
@@ -258,6 +793,217 @@ enum Commands {
         #[command(subcommand)]
         command: AgentCommand,
     },
+
+    /// Supervisor of a `--supervised` process, started by opm itself
+    #[command(name = "__supervise", hide = true)]
+    Supervise {
+        /// State file the supervisor records the process and its exit in
+        #[arg(long)]
+        state: std::path::PathBuf,
+        /// Log file for stdout
+        #[arg(long)]
+        out: std::path::PathBuf,
+        /// Log file for stderr
+        #[arg(long)]
+        error: std::path::PathBuf,
+        #[arg(last = true, required = true)]
+        command: Vec<String>,
+    },
+}
+
+impl Commands {
+    /// Commands run for their effect, their tables and markers are decoration
+    fn is_action(&self) -> bool {
+        matches!(
+            self,
+            Commands::Import { .. }
+                | Commands::Export { .. }
+                | Commands::Start { .. }
+                | Commands::Stop { .. }
+                | Commands::Remove { .. }
+                | Commands::Restore { .. }
+                | Commands::Save { .. }
+                | Commands::Flush { .. }
+                | Commands::Env { command: Some(EnvCommand::Clear { .. }), .. }
+                | Commands::Restart { .. }
+                | Commands::Reload { .. }
+                | Commands::TriggerReload { .. }
+                | Commands::Adjust { dry_run: false, .. }
+                | Commands::Cores { clean: true, .. }
+                | Commands::MigrateLogs { .. }
+                | Commands::Snapshots { command: SnapshotsCommand::Restore { dry_run: false, .. } }
+        )
+    }
+
+    /// Replace name patterns like `api-*` with the processes they match on the server the command goes to
+    fn expand_patterns(&mut self) {
+        match self {
+            Commands::Stop { items: Some(items), server, .. } | Commands::Restart { items: Some(items), server, .. } | Commands::Logs { items: Some(items), server, .. } => {
+                *items = cli::expand(items, &defaults(server))
+            }
+            Commands::Remove { items, server, .. }
+            | Commands::Reload { items, server, .. }
+            | Commands::Wait { items, server, .. }
+            | Commands::Adjust { items, server, .. } => {
+                *items = cli::expand(items, &defaults(server))
+            }
+            Commands::Export { items, .. } => *items = cli::expand(items, "internal"),
+            Commands::Env { command: Some(EnvCommand::Clear { item, server, .. }), .. }
+            | Commands::Env { item: Some(item), server, .. }
+            | Commands::Details { item, server, .. }
+            | Commands::Flush { item, server, .. }
+            | Commands::TriggerReload { item, server, .. }
+            | Commands::GetCommand { item, server, .. } => *item = cli::resolve(item, &defaults(server)),
+            Commands::Cores { item, .. } => *item = cli::resolve(item, "internal"),
+            _ => {}
+        }
+    }
+
+    /// Commands that rely on a local daemon to do what they were asked
+    fn needs_daemon(&self) -> bool {
+        let local = |server: &Option<String>| matches!(defaults(server).as_str(), "internal" | "local");
+
+        match self {
+            Commands::Restore { server } => local(server),
+            Commands::Start { watch: Some(_), server, .. } => local(server),
+            Commands::Import { background, .. } => *background,
+            _ => false,
+        }
+    }
+
+    /// How a mutating command is written to the audit log, read-only commands are not
+    fn audit_entry(&self) -> Option<opm::audit::Entry> {
+        let labels = |items: &Option<Items>, labels: &[(String, String)]| match items {
+            Some(items) => items.to_string(),
+            None => labels.iter().map(|(key, value)| format!("label:{key}={value}")).collect::<Vec<_>>().join(","),
+        };
+
+        let (action, target, server) = match self {
+            Commands::Import { path, .. } => ("import", path.clone().unwrap_or_else(|| string!("pm2")), &None),
+            Commands::Start { name, args, command_file, server, .. } => {
+                let file = command_file.as_ref().and_then(|path| path.file_stem()).map(|stem| stem.to_string_lossy().into_owned());
+                let target = name.clone().or(file).or_else(|| args.as_ref().map(Args::to_string));
+                ("start", target.unwrap_or_default(), server)
+            }
+            Commands::Stop { items, labels: filters, server, .. } => ("stop", labels(items, filters), server),
+            Commands::Restart { items, labels: filters, server, .. } => ("restart", labels(items, filters), server),
+            Commands::Remove { items, server, .. } => ("remove", items.to_string(), server),
+            Commands::Reload { items, server, .. } => ("reload", items.to_string(), server),
+            Commands::Restore { server } => ("restore", string!("all"), server),
+            Commands::Save { server } => ("save", string!("all"), server),
+            Commands::Flush { item, server, .. } => ("flush", item.to_string(), server),
+            Commands::Env { command: Some(EnvCommand::Clear { item, server, .. }), .. } => ("reset_env", item.to_string(), server),
+            Commands::TriggerReload { item, server, .. } => ("trigger", item.to_string(), server),
+            Commands::Adjust { items, server, dry_run: false, .. } => ("adjust", items.to_string(), server),
+            Commands::Cores { item, clean: true } => ("clean_cores", item.to_string(), &None),
+            Commands::MigrateLogs { .. } => ("migrate_logs", string!("all"), &None),
+            Commands::Snapshots { command: SnapshotsCommand::Restore { stamp, dry_run: false } } => ("snapshot_restore", stamp.clone(), &None),
+            Commands::Daemon { command: Daemon::ReloadConfig } => ("reload_config", string!("config.toml"), &None),
+            Commands::Daemon { command: Daemon::Token { command: TokenCommand::Add { name, .. } } } => ("token_add", name.clone(), &None),
+            Commands::Daemon { command: Daemon::Token { command: TokenCommand::Remove { name } } } => ("token_remove", name.clone(), &None),
+            _ => return None,
+        };
+
+        let mut entry = opm::audit::Entry::new(action, &target, opm::audit::local_actor(), opm::audit::Via::Cli);
+        let server = defaults(server);
+        entry.server = ternary!(matches!(&*server, "internal" | "local"), None, Some(server));
+
+        Some(entry)
+    }
+}
+
+#[derive(Subcommand)]
+enum EnvCommand {
+    /// Clear the stored environment of a process, `all` clears every process
+    Clear {
+        #[clap(value_parser = cli::validate::<Item>)]
+        item: Item,
+        /// Agent connection (use with agent-enabled server)
+        #[arg(short, long)]
+        server: Option<String>,
+        /// Skip the confirmation of `env clear all`
+        #[arg(short, long)]
+        yes: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum TokenCommand {
+    /// Create a token and print it, only its hash is stored
+    Add {
+        /// Name the token is listed and audited under
+        name: String,
+        /// Actions it may run, any when not given: info,logs,create,start,restart,reload,stop,remove,flush,reset_env
+        #[arg(long, value_delimiter = ',')]
+        actions: Vec<String>,
+        /// Processes it reaches by name pattern or group:NAME, e.g. 'app-*,group:workers', all when not given
+        #[arg(long, value_delimiter = ',')]
+        processes: Vec<String>,
+        /// Only read processes and their logs
+        #[arg(long)]
+        read_only: bool,
+    },
+    /// Remove a token, requests sending it are refused right away
+    #[command(visible_alias = "rm")]
+    Remove { name: String },
+    /// List the tokens with their scopes
+    #[command(visible_alias = "ls")]
+    List {
+        /// Format output (default or json)
+        #[arg(long, default_value_t = string!("default"))]
+        format: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum SnapshotsCommand {
+    /// List the dump snapshots, newest first
+    #[command(visible_alias = "ls")]
+    List {
+        /// Format output (default or json)
+        #[arg(long, default_value_t = string!("default"))]
+        format: String,
+    },
+    /// Bring back the processes of a snapshot, stopped
+    Restore {
+        /// Timestamp of the snapshot, or enough of it to match only one (e.g. 20261015T09)
+        stamp: String,
+        /// Show what would be created, removed or changed without applying it
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum StatsCommand {
+    /// Memory, cpu, process and restart totals per group, label value or name prefix
+    Summary {
+        /// Bucket processes by: group, label:<key> or name-prefix
+        #[arg(long)]
+        by: opm::process::stats::By,
+        /// Format output (default or json)
+        #[arg(long, default_value_t = string!("default"))]
+        format: String,
+        /// Agent connection (use with agent-enabled server)
+        #[arg(short, long)]
+        server: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum NotificationsCommand {
+    /// Recent delivery attempts and the last delivery of each channel, channel tokens redacted
+    History {
+        /// Number of latest deliveries to show
+        #[arg(long, default_value_t = 50)]
+        limit: usize,
+        /// Format output (default or json)
+        #[arg(long, default_value_t = string!("default"))]
+        format: String,
+        /// Agent connection (use with agent-enabled server)
+        #[arg(short, long)]
+        server: Option<String>,
+    },
 }
 
 #[derive(Subcommand)]
@@ -270,8 +1016,29 @@ enum AgentCommand {
         #[arg(long)]
         name: Option<String>,
         /// Authentication token (optional)
-        #[arg(long)]
+        #[arg(long, conflicts_with_all = ["token_env", "token_file"])]
         token: Option<String>,
+        /// Read the token from this environment variable when it is used, instead of storing it
+        #[arg(long, value_name = "NAME", conflicts_with = "token_file")]
+        token_env: Option<String>,
+        /// Read the token from this file when it is used, instead of storing it
+        #[arg(long, value_name = "PATH")]
+        token_file: Option<String>,
+        /// Proxy URL used to reach the server (e.g., http://proxy.corp:3128)
+        #[arg(long)]
+        proxy: Option<String>,
+        /// Additional CA certificate(s) in PEM format
+        #[arg(long)]
+        ca_cert: Option<String>,
+        /// Disable TLS certificate verification (not recommended)
+        #[arg(long)]
+        insecure: bool,
+        /// Save the connection without checking that the server answers and accepts the token
+        #[arg(long)]
+        no_test: bool,
+        /// Save the connection even when the check fails
+        #[arg(long, conflicts_with = "no_test")]
+        force: bool,
     },
     /// List connected agents (view via API/Web UI)
     #[command(visible_alias = "ls")]
@@ -285,37 +1052,74 @@ enum AgentCommand {
 fn agent_list() {
     use opm::helpers;
     
-    println!("{} Connected Agents", *helpers::INFO);
-    println!();
-    println!("To view connected agents, use one of the following methods:");
-    println!();
-    println!("  1. Web UI:");
-    println!("     • Start the daemon with Web UI enabled:");
-    println!("       opm daemon restore --webui");
-    println!("     • Open your browser to: http://localhost:9876");
-    println!("     • Navigate to the 'Agents' page");
-    println!();
-    println!("  2. API Endpoint:");
-    println!("     • Start the daemon with API enabled:");
-    println!("       opm daemon restore --api");
-    println!("     • Query: curl http://localhost:9876/daemon/agents/list");
-    println!();
-    println!("  3. Connect an agent:");
-    println!("     • On a remote machine: opm agent connect <server-url>");
-    println!("     • Example: opm agent connect http://192.168.1.100:9876");
+    cli::servers::print();
+    notice!();
+    notice!("{} Connected Agents", *helpers::INFO);
+    notice!();
+    notice!("To view connected agents, use one of the following methods:");
+    notice!();
+    notice!("  1. Web UI:");
+    notice!("     • Start the daemon with Web UI enabled:");
+    notice!("       opm daemon restore --webui");
+    notice!("     • Open your browser to: http://localhost:9876");
+    notice!("     • Navigate to the 'Agents' page");
+    notice!();
+    notice!("  2. API Endpoint:");
+    notice!("     • Start the daemon with API enabled:");
+    notice!("       opm daemon restore --api");
+    notice!("     • Query: curl http://localhost:9876/daemon/agents/list");
+    notice!();
+    notice!("  3. Connect an agent:");
+    notice!("     • On a remote machine: opm agent connect <server-url>");
+    notice!("     • Example: opm agent connect http://192.168.1.100:9876");
 }
 
-fn agent_connect(server_url: String, name: Option<String>, token: Option<String>) {
+fn agent_connect(server_url: String, name: Option<String>, token: Option<String>, client: opm::config::structs::ClientOptions, test: bool, force: bool) {
     use opm::helpers;
     use opm::agent::types::AgentConfig;
     
-    println!("{} Starting OPM Agent...", *helpers::SUCCESS);
+    notice!("{} Starting OPM Agent...", *helpers::SUCCESS);
+    
+    if client.insecure {
+        alert!("{} TLS certificate verification is DISABLED for this agent.", *helpers::WARN);
+        alert!("{} Any certificate presented by the server (or an attacker) will be accepted.", *helpers::WARN);
+    }
+    
+    // Only the reference is saved, the secret may be provided later
+    if let Some(Err(err)) = token.as_deref().map(opm::config::token::resolve) {
+        alert!("{} {err}, it has to be available when the token is used", *helpers::WARN);
+    }
+
+    // Fail early on a bad proxy url or unreadable certificate
+    if let Err(e) = opm::process::http::sync::build(&client) {
+        eprintln!("{} Invalid connection options: {}", *helpers::FAIL, e);
+        return;
+    }
+
+    // A typo in the address or token would otherwise only show once the agent fails to register
+    let mut seen = opm::config::structs::Seen::default();
+    if test {
+        match cli::servers::verify(&server_url, &token, &client) {
+            Ok(reported) => {
+                notice!("{} Reached {server_url} ({})", *helpers::SUCCESS, cli::servers::describe(&reported));
+                seen = reported;
+            }
+            Err(err) if force => alert!("{} Checking {server_url} failed, {err}. Saving it anyway (--force)", *helpers::WARN),
+            Err(err) => {
+                eprintln!("{} Checking {server_url} failed, {err}", *helpers::FAIL);
+                eprintln!("   Fix the address or token, or save it anyway with --force");
+                return;
+            }
+        }
+    }
     
-    let config = AgentConfig::new(server_url, name, token);
+    let mut config = AgentConfig::new(server_url, name, token);
+    config.client = client;
+    config.seen = seen;
     
     // Save agent config
     match save_agent_config(&config) {
-        Ok(_) => println!("{} Agent configuration saved", *helpers::SUCCESS),
+        Ok(_) => notice!("{} Agent configuration saved", *helpers::SUCCESS),
         Err(e) => {
             eprintln!("{} Failed to save agent config: {}", *helpers::FAIL, e);
             return;
@@ -330,10 +1134,10 @@ fn agent_connect(server_url: String, name: Option<String>, token: Option<String>
     opm_config.daemon.web.port = config.api_port as u64;
     opm_config.save();
     
-    println!("{} Agent ID: {}", *helpers::SUCCESS, config.id);
-    println!("{} Agent Name: {}", *helpers::SUCCESS, config.name);
-    println!("{} Server URL: {}", *helpers::SUCCESS, config.server_url);
-    println!("{} Agent API: http://{}:{}", *helpers::SUCCESS, config.api_address, config.api_port);
+    notice!("{} Agent ID: {}", *helpers::SUCCESS, config.id);
+    notice!("{} Agent Name: {}", *helpers::SUCCESS, config.name);
+    notice!("{} Server URL: {}", *helpers::SUCCESS, config.server_url);
+    notice!("{} Agent API: http://{}:{}", *helpers::SUCCESS, config.api_address, config.api_port);
     
     // Start agent in background
     start_agent_daemon();
@@ -344,7 +1148,7 @@ fn agent_disconnect() {
     
     match load_agent_config() {
         Ok(config) => {
-            println!("{} Disconnecting agent '{}'...", *helpers::SUCCESS, config.name);
+            notice!("{} Disconnecting agent '{}'...", *helpers::SUCCESS, config.name);
             
             // Restore role to standalone
             let mut opm_config = opm::config::read();
@@ -355,8 +1159,8 @@ fn agent_disconnect() {
             if let Err(e) = remove_agent_config() {
                 eprintln!("{} Failed to remove agent config: {}", *helpers::FAIL, e);
             } else {
-                println!("{} Agent disconnected successfully", *helpers::SUCCESS);
-                println!("{} Role restored to standalone", *helpers::SUCCESS);
+                notice!("{} Agent disconnected successfully", *helpers::SUCCESS);
+                notice!("{} Role restored to standalone", *helpers::SUCCESS);
             }
         }
         Err(_) => {
@@ -370,14 +1174,27 @@ fn agent_status() {
     
     match load_agent_config() {
         Ok(config) => {
-            println!("{} Agent Status", *helpers::SUCCESS);
+            notice!("{} Agent Status", *helpers::SUCCESS);
             println!("   ID: {}", config.id);
             println!("   Name: {}", config.name);
             println!("   Server: {}", config.server_url);
+            println!("   Token: {}", opm::config::token::display(&config.token));
+            if let Some(proxy) = &config.client.proxy {
+                println!("   Proxy: {}", proxy);
+            }
+            if let Some(ca_cert) = &config.client.ca_cert {
+                println!("   CA Certificate: {}", ca_cert);
+            }
+            if config.client.insecure {
+                println!("   TLS Verification: disabled");
+            }
+            if let Some(time) = config.seen.last_seen {
+                println!("   Server Daemon: {}, checked {} ago", cli::servers::describe(&config.seen), helpers::format_duration(time));
+            }
             println!("   Status: Connected"); // In real implementation, check actual connection status
         }
         Err(_) => {
-            println!("{} No active agent connection", *helpers::WARN);
+            alert!("{} No active agent connection", *helpers::WARN);
         }
     }
 }
@@ -433,7 +1250,7 @@ fn start_agent_daemon() {
     
     // First, ensure the local daemon is running with API enabled
     if !daemon::pid::exists() {
-        println!("{} Starting local OPM daemon with API enabled...", *helpers::SUCCESS);
+        notice!("{} Starting local OPM daemon with API enabled...", *helpers::SUCCESS);
         daemon::restart(&true, &false, false);
         
         // Wait a bit for daemon to initialize
@@ -444,12 +1261,12 @@ fn start_agent_daemon() {
     match unsafe { fork() } {
         Ok(ForkResult::Parent { child: _ }) => {
             // Parent process
-            println!("{} Agent daemon started successfully", *helpers::SUCCESS);
-            println!("{} Agent is now connecting to server and will manage local processes", *helpers::SUCCESS);
-            println!();
-            println!("  View agent logs: tail -f ~/.opm/agent.log");
-            println!("  Check agent status: opm agent status");
-            println!("  Disconnect agent: opm agent disconnect");
+            notice!("{} Agent daemon started successfully", *helpers::SUCCESS);
+            notice!("{} Agent is now connecting to server and will manage local processes", *helpers::SUCCESS);
+            notice!();
+            notice!("  View agent logs: tail -f ~/.opm/agent.log");
+            notice!("  Check agent status: opm agent status");
+            notice!("  Disconnect agent: opm agent disconnect");
         }
         Ok(ForkResult::Child) => {
             // Child process - run the agent
@@ -518,13 +1335,22 @@ fn start_agent_daemon() {
 }
 
 fn main() {
-    let cli = Cli::parse();
+    let mut cli = Cli::parse();
+
+    // The supervisor runs for the lifetime of its process, none of the usual setup applies to it
+    if let Commands::Supervise { state, out, error, command } = &cli.command {
+        std::process::exit(opm::process::supervise::run(state, out, error, command));
+    }
+
     let mut env = env_logger::Builder::new();
     let level = cli.verbose.log_level_filter();
     let informer = update_informer::new(registry::Crates, "opm", env!("CARGO_PKG_VERSION"));
 
+    // Action commands piped into other tools stay quiet, as do parallel invocations
+    opm::output::set_quiet(cli.quiet() || (cli.command.is_action() && !stdout().is_terminal()));
+
     if let Some(version) = informer.check_version().ok().flatten() {
-        println!(
+        notice!(
             "{} New version is available: {version}",
             *opm::helpers::WARN
         );
@@ -533,21 +1359,122 @@ fn main() {
     globals::init();
     env.filter_level(level).init();
 
+    // Refused before anything changes, the command would be left half done without the daemon
+    if cli.command.needs_daemon() && !daemon::pid::alive() && !cli.autostart() {
+        crashln!("{} The daemon is not running and autostart is disabled, run `opm daemon restore` first", *opm::helpers::FAIL);
+    }
+
+    if let Some(entry) = cli.command.audit_entry() {
+        opm::audit::begin(entry);
+    }
+
+    // Audited as given, the command itself sees the processes a pattern matched
+    cli.command.expand_patterns();
+
     match &cli.command {
-        Commands::Import { path } => cli::import::read_hcl(path),
+        Commands::Import { path, background, from_pm2, start, stop_pm2 } => match (from_pm2, path) {
+            (false, Some(path)) => cli::import::read_hcl(path, *background),
+            _ => cli::pm2::import(*start, *stop_pm2),
+        },
+        Commands::ImportStatus { format } => cli::import::status(format),
         Commands::Export { items, path } => cli::import::export_hcl(items, path),
         Commands::Start {
             name,
             args,
             watch,
             max_memory,
+            max_memory_action,
             server,
             reset_env,
             workers,
             port_range,
-        } => cli::start(name, args, watch, max_memory, reset_env, &defaults(server), workers, port_range),
-        Commands::Stop { items, server } => cli::stop(items, &defaults(server)),
-        Commands::Remove { items, server } => cli::remove(items, &defaults(server)),
+            labels,
+            env,
+            startup_grace,
+            monitor_interval,
+            max_restarts,
+            watch_cooldown,
+            url,
+            description,
+            group,
+            alerts,
+            max_log_rate,
+            cron_restart,
+            health_url,
+            health_cmd,
+            health_interval,
+            health_retries,
+            health_timeout,
+            command_file,
+            pidfile,
+            expand_env_templates,
+            umask,
+            ulimit_nofile,
+            ulimit_core,
+            collect_cores,
+            supervised,
+            log_timestamps,
+            shell,
+            shell_args,
+            force_update,
+        } => {
+            let (args, name) = match command_file {
+                // Without --name the process is named after the file
+                Some(path) => (
+                    Args::Script(cli::read_command_file(path)),
+                    name.clone().or_else(|| path.file_stem().map(|stem| stem.to_string_lossy().into_owned())),
+                ),
+                None => (args.clone().unwrap(), name.clone()),
+            };
+
+            let health = health::Changes {
+                probe: health_url.clone().map(Probe::Url).or_else(|| health_cmd.clone().map(Probe::Command)).map(Some),
+                interval: *health_interval,
+                retries: *health_retries,
+                timeout: *health_timeout,
+            }
+            .apply(None)
+            .unwrap_or_else(|err| crashln!("{} {err}", *opm::helpers::FAIL));
+
+            let options = StartOptions {
+                watch: watch.clone(),
+                max_memory: max_memory.unwrap_or_default(),
+                max_memory_action: *max_memory_action,
+                limits: Limits { umask: *umask, nofile: *ulimit_nofile, core: *ulimit_core },
+                shell: Shell { program: shell.clone(), args: (!shell_args.is_empty()).then(|| shell_args.clone()) },
+                collect_cores: *collect_cores,
+                supervised: *supervised || *log_timestamps,
+                labels: labels.iter().cloned().collect(),
+                env: env.iter().cloned().collect(),
+                startup_grace: *startup_grace,
+                monitor_interval_ms: *monitor_interval,
+                max_restarts: *max_restarts,
+                watch_cooldown: *watch_cooldown,
+                url: url.clone(),
+                description: description.clone(),
+                group: group.clone(),
+                alerts: alerts.clone(),
+                max_log_rate: max_log_rate.clone(),
+                restart_cron: cron_restart.clone(),
+                health,
+                pidfile: pidfile.clone(),
+                expand_env: *expand_env_templates,
+                force_update: *force_update,
+            };
+
+            cli::start(&name, &args, &options, reset_env, &defaults(server), workers, port_range)
+        }
+        Commands::Stop {
+            items,
+            server,
+            deadline,
+            signal,
+            labels,
+        } => cli::stop(items, labels, deadline, *signal, &defaults(server)),
+        Commands::Remove { items, keep_logs, purge_logs, server } => {
+            let purge_logs = ternary!(*keep_logs, Some(false), purge_logs.then_some(true));
+            cli::remove(items, purge_logs, &defaults(server))
+        }
         Commands::Restore { server } => {
             // Ensure daemon is running before restore (silent mode)
             // Read config to check if API/WebUI should be enabled
@@ -579,53 +1506,234 @@ fn main() {
             Internal::restore(&defaults(server))
         },
         Commands::Save { server } => Internal::save(&defaults(server)),
-        Commands::Env { item, server } => cli::env(item, &defaults(server)),
+        Commands::Env { command: Some(EnvCommand::Clear { item, server, yes }), .. } => cli::env_clear(item, *yes, &defaults(server)),
+        Commands::Env { item: Some(item), server, .. } => cli::env(item, &defaults(server)),
+        // Clap requires the item when no subcommand is given
+        Commands::Env { .. } => unreachable!(),
         Commands::Details {
             item,
             format,
             server,
-        } => cli::info(item, format, &defaults(server)),
-        Commands::List { format, server } => Internal::list(format, &defaults(server)),
+            snapshot,
+        } => match snapshot {
+            Some(args) => cli::snapshot(item, args, format, &defaults(server)),
+            None => cli::info(item, format, &defaults(server)),
+        },
+        Commands::List { format, server, labels, columns, max_width, all_servers, merged } => match all_servers {
+            true => Internal::list_servers(format, labels, columns, *max_width, *merged),
+            false => Internal::list_matching(format, &defaults(server), labels, columns, *max_width),
+        },
+        Commands::Stats { command } => match command {
+            StatsCommand::Summary { by, format, server } => cli::stats_summary(by, format, &defaults(server)),
+        },
+        Commands::Audit { lines, process, action, format, server } => {
+            let filter = opm::audit::Filter { process: process.clone(), action: action.clone() };
+            cli::audit_log(*lines, &filter, format, &defaults(server))
+        }
+        Commands::Notifications { command } => match command {
+            NotificationsCommand::History { limit, format, server } => cli::notification_history(*limit, format, &defaults(server)),
+        },
+        Commands::Wait { items, target, timeout, any, server, .. } => {
+            let verbose = cli.verbose.is_present() && !cli.quiet();
+            std::process::exit(cli::wait::run(items, *target, *timeout, *any, verbose, &defaults(server)))
+        }
+        Commands::Events { follow, format, event_types, process, lines, wait, server } => {
+            let filter = opm::events::Filter { kinds: event_types.clone(), process: process.clone() };
+            let options = cli::events::Options { format, filter: &filter, lines: *lines, wait: *wait };
+            cli::events::run(*follow, &options, &defaults(server))
+        }
+        Commands::Report { from, to, format, output, by } => {
+            let options = cli::report::Options {
+                from: from.unwrap_or_else(|| Utc::now() - TimeDelta::days(7)),
+                to: to.unwrap_or_else(Utc::now),
+                format,
+                output: output.as_deref(),
+                rollup: *by,
+            };
+            cli::report::run(&options)
+        }
+        Commands::Ui { server } => cli::ui::run(&defaults(server)),
         Commands::Logs {
-            item,
+            items,
             lines,
             server,
             follow,
             filter,
             errors_only,
             stats,
-        } => cli::logs(
-            item,
-            lines,
-            &defaults(server),
-            *follow,
-            filter.as_deref(),
-            *errors_only,
-            *stats,
-        ),
-        Commands::Flush { item, server } => cli::flush(item, &defaults(server)),
+            stats_json,
+            stats_field,
+            labels,
+            output,
+            hours,
+            since,
+            until,
+            timestamps,
+            rotated,
+        } => match output {
+            Some(output) => cli::export_logs(items, *lines, *hours, output, labels, &defaults(server)),
+            None => cli::logs(
+                items,
+                &lines.unwrap_or(ternary!(since.is_none() && until.is_none(), 15, usize::MAX)),
+                &defaults(server),
+                *follow,
+                *errors_only,
+                labels,
+                &LogOptions {
+                    filter: filter.as_deref(),
+                    stats: *stats,
+                    stats_field: stats_json.then_some(stats_field.as_str()),
+                    window: Window { since: *since, until: *until },
+                    timestamps: *timestamps,
+                    rotated: *rotated,
+                },
+            ),
+        },
+        Commands::Flush { item, server, yes } => cli::flush(item, *yes, &defaults(server)),
+        Commands::Cores { item, clean } => cli::cores(item, *clean),
+        Commands::MigrateLogs { symlink, .. } => cli::migrate_logs(ternary!(*symlink, logdir::Mode::Symlink, logdir::Mode::Move)),
+        Commands::Snapshots { command } => match command {
+            SnapshotsCommand::List { format } => cli::snapshots::list(format),
+            SnapshotsCommand::Restore { stamp, dry_run } => cli::snapshots::restore(stamp, *dry_run),
+        },
 
         Commands::Daemon { command } => match command {
             Daemon::Stop => daemon::stop(),
             Daemon::Reset => daemon::reset(),
-            Daemon::Health { format } => daemon::health(format),
-            Daemon::Restore { api, webui } => daemon::restart(api, webui, level.as_str() != "OFF"),
-            Daemon::Setup => daemon::setup(),
+            Daemon::Health { format, check_webui } => daemon::health(format, *check_webui),
+            Daemon::ReloadConfig => daemon::reload_config(),
+            Daemon::Stats { format, reset } => daemon::stats(format, *reset),
+            Daemon::Restore { api, webui, foreground, pid1, daemon_env } => {
+                if *foreground || *pid1 {
+                    daemon::foreground(*pid1);
+                }
+                daemon::set_env(daemon_env.clone());
+                daemon::restart(api, webui, level.as_str() != "OFF")
+            }
+            Daemon::Setup { uninstall, dry_run, yes } => daemon::setup(*uninstall, *dry_run, *yes),
+            Daemon::Token { command } => match command {
+                TokenCommand::Add { name, actions, processes, read_only } => daemon::token::add(name, actions, processes, *read_only),
+                TokenCommand::Remove { name } => daemon::token::remove(name),
+                TokenCommand::List { format } => daemon::token::list(format),
+            },
         },
 
-        Commands::Restart { items, server } => cli::restart(items, &defaults(server)),
-        Commands::Reload { items, server } => cli::reload(items, &defaults(server)),
-        Commands::GetCommand { item, server } => cli::get_command(item, &defaults(server)),
+        Commands::Restart { items, server, labels, if_changed, rolling, delay_ms } => match rolling {
+            true => cli::rolling::run(items, labels, std::time::Duration::from_millis(*delay_ms), *if_changed, &defaults(server)),
+            false => cli::restart(items, labels, *if_changed, &defaults(server)),
+        },
+        Commands::Reload { items, server, if_changed } => cli::reload(items, *if_changed, &defaults(server)),
+        Commands::TriggerReload { item, reason, server } => cli::trigger_reload(item, reason, &defaults(server)),
+        Commands::GetCommand { item, raw, format, show_secrets, server } => cli::get_command(item, *raw, format, *show_secrets, &defaults(server)),
         Commands::Adjust {
-            item,
+            items,
             command,
+            command_file,
             name,
+            labels,
+            unlabel,
+            url,
+            no_url,
+            description,
+            no_description,
+            group,
+            no_group,
+            max_restarts,
+            no_max_restarts,
+            watch_cooldown,
+            no_watch_cooldown,
+            umask,
+            ulimit_nofile,
+            ulimit_core,
+            collect_cores,
+            no_collect_cores,
+            supervised,
+            no_supervised,
+            track_children,
+            no_track_children,
+            shell,
+            shell_args,
+            no_shell,
+            alerts,
+            no_alerts,
+            max_log_rate,
+            no_max_log_rate,
+            max_memory_action,
+            cron_restart,
+            no_cron_restart,
+            health_url,
+            health_cmd,
+            health_interval,
+            health_retries,
+            health_timeout,
+            no_health,
+            env_add,
+            env_remove,
+            restart,
+            dry_run,
             server,
-        } => cli::adjust(item, command, name, &defaults(server)),
+        } => {
+            let command = command_file.as_ref().map(cli::read_command_file).or_else(|| command.clone());
+            let url = ternary!(*no_url, Some(None), url.clone().map(Some));
+            let description = ternary!(*no_description, Some(None), description.clone().map(Some));
+            let group = ternary!(*no_group, Some(None), group.clone().map(Some));
+            let max_restarts = ternary!(*no_max_restarts, Some(None), max_restarts.map(Some));
+            let watch_cooldown = ternary!(*no_watch_cooldown, Some(None), watch_cooldown.map(Some));
+            let limits = Limits { umask: *umask, nofile: *ulimit_nofile, core: *ulimit_core };
+            let collect_cores = ternary!(*no_collect_cores, Some(false), collect_cores.then_some(true));
+            let supervised = ternary!(*no_supervised, Some(false), supervised.then_some(true));
+            let track_children = ternary!(*no_track_children, Some(false), track_children.then_some(true));
+            let shell = match *no_shell {
+                true => Some(Shell::default()),
+                false => (shell.is_some() || !shell_args.is_empty()).then(|| Shell { program: shell.clone(), args: (!shell_args.is_empty()).then(|| shell_args.clone()) }),
+            };
+            let alerts = ternary!(*no_alerts, Some(vec![]), (!alerts.is_empty()).then(|| alerts.clone()));
+            let max_log_rate = ternary!(*no_max_log_rate, Some(None), max_log_rate.clone().map(Some));
+            let restart_cron = ternary!(*no_cron_restart, Some(None), cron_restart.clone().map(Some));
+            let probe = health_url.clone().map(Probe::Url).or_else(|| health_cmd.clone().map(Probe::Command));
+            let health = health::Changes {
+                probe: ternary!(*no_health, Some(None), probe.map(Some)),
+                interval: *health_interval,
+                retries: *health_retries,
+                timeout: *health_timeout,
+            };
+            let changes = cli::adjust::Changes {
+                command,
+                name: name.clone(),
+                labels: labels.clone(),
+                unlabel: unlabel.clone(),
+                url,
+                description,
+                group,
+                max_restarts,
+                watch_cooldown,
+                limits,
+                collect_cores,
+                supervised,
+                track_children,
+                shell,
+                alerts,
+                max_log_rate,
+                max_memory_action: *max_memory_action,
+                restart_cron,
+                health,
+                env: opm::process::environment::Changes { add: env_add.clone(), remove: env_remove.clone() },
+                restart: *restart,
+            };
+            cli::adjust(items, &changes, *dry_run, &defaults(server))
+        }
+
+        Commands::Supervise { .. } => unreachable!(),
 
         Commands::Agent { command } => match command {
-            AgentCommand::Connect { server_url, name, token } => {
-                agent_connect(server_url.clone(), name.clone(), token.clone())
+            AgentCommand::Connect { server_url, name, token, token_env, token_file, proxy, ca_cert, insecure, no_test, force } => {
+                let client = opm::config::structs::ClientOptions {
+                    proxy: proxy.clone(),
+                    ca_cert: ca_cert.clone(),
+                    insecure: *insecure,
+                };
+                let token = opm::config::token::reference(token.clone(), token_env.clone(), token_file.clone());
+                agent_connect(server_url.clone(), name.clone(), token, client, !*no_test, *force)
             }
             AgentCommand::List => agent_list(),
             AgentCommand::Disconnect => agent_disconnect(),
@@ -633,6 +1741,8 @@ fn main() {
         },
     };
 
+    opm::audit::finish();
+
     if !matches!(&cli.command, Commands::Daemon { .. })
         && !matches!(&cli.command, Commands::Save { .. })
         && !matches!(&cli.command, Commands::Env { .. })
@@ -640,11 +1750,242 @@ fn main() {
         && !matches!(&cli.command, Commands::GetCommand { .. })
         && !matches!(&cli.command, Commands::Adjust { .. })
         && !matches!(&cli.command, Commands::Agent { .. })
+        && !matches!(&cli.command, Commands::Events { .. })
+        && !matches!(&cli.command, Commands::Notifications { .. })
+        && !matches!(&cli.command, Commands::Cores { .. })
+        && !matches!(&cli.command, Commands::MigrateLogs { .. })
+        && !daemon::pid::exists()
+        && cli.autostart()
     {
         // When auto-starting daemon, read API/WebUI settings from config
-        if !daemon::pid::exists() {
-            let config = opm::config::read();
-            daemon::restart(&config.daemon.web.api, &config.daemon.web.ui, false);
-        }
+        let config = opm::config::read();
+        daemon::restart(&config.daemon.web.api, &config.daemon.web.ui, false);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Cli {
+        Cli::try_parse_from([&["opm"], args].concat()).unwrap()
+    }
+
+    #[test]
+    fn test_quiet_flag() {
+        assert!(parse(&["stop", "all", "-q"]).quiet());
+        assert!(parse(&["--quiet", "list", "--format", "json"]).quiet());
+        assert!(!parse(&["stop", "all"]).quiet());
+        assert!(!parse(&["-v", "stop", "all"]).quiet());
+
+        assert!(parse(&["stop", "all"]).command.is_action());
+        assert!(!parse(&["list"]).command.is_action());
+    }
+
+    #[test]
+    fn test_shell_args_start_with_hyphens() {
+        let Commands::Start { shell, shell_args, .. } = parse(&["start", "deploy", "--shell", "/bin/sh", "--shell-arg", "-l", "--shell-arg", "-c"]).command else { unreachable!() };
+        assert_eq!((shell.as_deref(), shell_args), (Some("/bin/sh"), vec![string!("-l"), string!("-c")]));
+
+        assert!(Cli::try_parse_from(["opm", "adjust", "deploy", "--shell", "sh"]).is_err());
+        assert!(Cli::try_parse_from(["opm", "adjust", "deploy", "--no-shell", "--shell-arg", "-c"]).is_err());
+    }
+
+    #[test]
+    fn test_list_all_servers_flags() {
+        let Commands::List { all_servers, merged, .. } = parse(&["list", "--all-servers", "--merged"]).command else { unreachable!() };
+        assert!(all_servers && merged);
+
+        assert!(Cli::try_parse_from(["opm", "list", "--merged"]).is_err());
+        assert!(Cli::try_parse_from(["opm", "list", "--all-servers", "-s", "prod"]).is_err());
+    }
+
+    #[test]
+    fn test_import_from_pm2_flags() {
+        let Commands::Import { path, from_pm2, start, stop_pm2, .. } = parse(&["import", "--from-pm2", "--start", "--stop-pm2"]).command else { unreachable!() };
+        assert!(path.is_none() && from_pm2 && start && stop_pm2);
+
+        assert!(Cli::try_parse_from(["opm", "import"]).is_err());
+        assert!(Cli::try_parse_from(["opm", "import", "all.hcl", "--from-pm2"]).is_err());
+        assert!(Cli::try_parse_from(["opm", "import", "--from-pm2", "--stop-pm2"]).is_err());
+        assert!(Cli::try_parse_from(["opm", "import", "all.hcl", "--start"]).is_err());
+    }
+
+    #[test]
+    fn test_stop_signal() {
+        let Commands::Stop { signal, .. } = parse(&["stop", "api", "--signal", "sigint"]).command else { unreachable!() };
+        assert_eq!(signal, Some(Signal::SIGINT));
+        let Commands::Stop { signal, .. } = parse(&["stop", "api"]).command else { unreachable!() };
+        assert_eq!(signal, None);
+
+        let err = Cli::try_parse_from(["opm", "stop", "api", "--signal", "SIGFOO"]).err().unwrap().to_string();
+        assert!(err.contains("supported signals are SIGTERM"), "{err}");
+    }
+
+    #[test]
+    fn test_wait_flags() {
+        let Commands::Wait { target, timeout, any, .. } = parse(&["wait", "api", "--for", "stopped", "--timeout", "5", "--any"]).command else { unreachable!() };
+        assert_eq!((target, timeout, any), (cli::wait::State::Stopped, 5, true));
+        let Commands::Wait { target, timeout, .. } = parse(&["wait", "api"]).command else { unreachable!() };
+        assert_eq!((target, timeout), (cli::wait::State::Online, 60));
+
+        assert!(Cli::try_parse_from(["opm", "wait", "api", "--all", "--any"]).is_err());
+        assert!(Cli::try_parse_from(["opm", "wait", "api", "--for", "healthy"]).is_err());
+    }
+
+    #[test]
+    fn test_rolling_restart_flags() {
+        let Commands::Restart { rolling, delay_ms, .. } = parse(&["restart", "all", "--rolling", "--delay-ms", "500"]).command else { unreachable!() };
+        assert_eq!((rolling, delay_ms), (true, 500));
+        let Commands::Restart { rolling, delay_ms, .. } = parse(&["restart", "api"]).command else { unreachable!() };
+        assert_eq!((rolling, delay_ms), (false, 2000));
+
+        assert!(Cli::try_parse_from(["opm", "restart", "api", "--delay-ms", "500"]).is_err());
+    }
+
+    #[test]
+    fn test_description_flags() {
+        let Commands::Adjust { description, no_description, .. } = parse(&["adjust", "api", "--description", "Billing worker, owned by @dana"]).command else { unreachable!() };
+        assert_eq!((description.as_deref(), no_description), (Some("Billing worker, owned by @dana"), false));
+
+        assert!(Cli::try_parse_from(["opm", "adjust", "api", "--description", "x", "--no-description"]).is_err());
+        assert!(Cli::try_parse_from(["opm", "start", "app.js", "--description", " "]).is_err());
+        assert!(Cli::try_parse_from(["opm", "start", "app.js", "--description", &"x".repeat(1025)]).is_err());
+    }
+
+    #[test]
+    fn test_group_flags() {
+        let Commands::Start { group, .. } = parse(&["start", "app.js", "--group", "api"]).command else { unreachable!() };
+        assert_eq!(group.as_deref(), Some("api"));
+
+        let Commands::Adjust { group, no_group, .. } = parse(&["adjust", "api-1", "--no-group"]).command else { unreachable!() };
+        assert_eq!((group, no_group), (None, true));
+
+        let Commands::Stop { items, .. } = parse(&["stop", "group:api"]).command else { unreachable!() };
+        assert!(matches!(&items.unwrap().items[..], [Item::Group(group)] if group == "api"));
+
+        assert!(Cli::try_parse_from(["opm", "adjust", "api-1", "--group", "api", "--no-group"]).is_err());
+        assert!(Cli::try_parse_from(["opm", "start", "app.js", "--group", "my group"]).is_err());
+    }
+
+    #[test]
+    fn test_max_log_rate_flags() {
+        let Commands::Start { max_log_rate, .. } = parse(&["start", "app.js", "--max-log-rate", "100M/min restart"]).command else { unreachable!() };
+        assert_eq!(max_log_rate.map(|rate| rate.to_string()).as_deref(), Some("100M/min restart"));
+
+        let Commands::Adjust { max_log_rate, no_max_log_rate, .. } = parse(&["adjust", "api", "--no-max-log-rate"]).command else { unreachable!() };
+        assert_eq!((max_log_rate, no_max_log_rate), (None, true));
+
+        assert!(Cli::try_parse_from(["opm", "start", "app.js", "--max-log-rate", "100M"]).is_err());
+        assert!(Cli::try_parse_from(["opm", "adjust", "api", "--max-log-rate", "1G/h", "--no-max-log-rate"]).is_err());
+    }
+
+    #[test]
+    fn test_cron_restart_flags() {
+        let Commands::Start { cron_restart, .. } = parse(&["start", "app.js", "--cron-restart", "0 3 * * *"]).command else { unreachable!() };
+        assert_eq!(cron_restart.map(|schedule| schedule.to_string()).as_deref(), Some("0 3 * * *"));
+
+        let Commands::Adjust { cron_restart, no_cron_restart, .. } = parse(&["adjust", "api", "--no-cron-restart"]).command else { unreachable!() };
+        assert_eq!((cron_restart, no_cron_restart), (None, true));
+
+        let Err(err) = Cli::try_parse_from(["opm", "start", "app.js", "--cron-restart", "0 3 * *"]) else { unreachable!() };
+        assert!(err.to_string().contains("expected 5 fields"));
+        assert!(Cli::try_parse_from(["opm", "adjust", "api", "--cron-restart", "@daily", "--no-cron-restart"]).is_err());
+    }
+
+    #[test]
+    fn test_health_flags() {
+        let Commands::Start { health_url, health_retries, .. } = parse(&["start", "app.js", "--health-url", "http://127.0.0.1:8080/health", "--health-retries", "5"]).command else { unreachable!() };
+        assert_eq!((health_url.as_deref(), health_retries), (Some("http://127.0.0.1:8080/health"), Some(5)));
+
+        // A url and a command are one check too many, the interval needs a check to apply to
+        assert!(Cli::try_parse_from(["opm", "start", "app.js", "--health-url", "http://127.0.0.1:8080/health", "--health-cmd", "true"]).is_err());
+        assert!(Cli::try_parse_from(["opm", "start", "app.js", "--health-interval", "30"]).is_err());
+        assert!(Cli::try_parse_from(["opm", "start", "app.js", "--health-cmd", "true", "--health-interval", "0"]).is_err());
+
+        let Commands::Adjust { health_interval, no_health, .. } = parse(&["adjust", "api", "--health-interval", "30"]).command else { unreachable!() };
+        assert_eq!((health_interval, no_health), (Some(30), false));
+        assert!(Cli::try_parse_from(["opm", "adjust", "api", "--no-health", "--health-retries", "2"]).is_err());
+    }
+
+    #[test]
+    fn test_max_memory_action_flags() {
+        let Commands::Start { max_memory_action, .. } = parse(&["start", "app.js", "--max-memory", "512M", "--max-memory-action", "restart"]).command else { unreachable!() };
+        assert_eq!(max_memory_action, Some(memlimit::Action::Restart));
+
+        // An action without a limit would never be taken
+        assert!(Cli::try_parse_from(["opm", "start", "app.js", "--max-memory-action", "restart"]).is_err());
+        assert!(Cli::try_parse_from(["opm", "start", "app.js", "--max-memory", "512M", "--max-memory-action", "kill"]).is_err());
+
+        let Commands::Adjust { max_memory_action, .. } = parse(&["adjust", "api", "--max-memory-action", "stop"]).command else { unreachable!() };
+        assert_eq!(max_memory_action, Some(memlimit::Action::Stop));
+    }
+
+    #[test]
+    fn test_bulk_adjust_flags() {
+        let cli = parse(&["adjust", "worker-1,worker-2,group:queue", "--supervised", "--dry-run"]);
+        assert!(!cli.command.is_action());
+        assert!(cli.command.audit_entry().is_none());
+
+        let Commands::Adjust { items, dry_run, .. } = cli.command else { unreachable!() };
+        assert_eq!((items.items.len(), dry_run), (3, true));
+
+        let Commands::Adjust { dry_run, .. } = parse(&["adjust", "all", "--max-restarts", "5"]).command else { unreachable!() };
+        assert!(!dry_run);
+
+        assert!(Cli::try_parse_from(["opm", "adjust", "api,worker", "--dry-run", "--restart"]).is_err());
+    }
+
+    #[test]
+    fn test_migrate_logs_flags() {
+        let Commands::MigrateLogs { moved, symlink } = parse(&["migrate-logs", "--symlink"]).command else { unreachable!() };
+        assert_eq!((moved, symlink), (false, true));
+
+        assert!(matches!(parse(&["migrate-logs"]).command, Commands::MigrateLogs { moved: false, symlink: false }));
+        assert!(Cli::try_parse_from(["opm", "migrate-logs", "--move", "--symlink"]).is_err());
+    }
+
+    #[test]
+    fn test_token_flags() {
+        let Commands::Daemon { command: Daemon::Token { command: TokenCommand::Add { name, actions, processes, read_only } } } =
+            parse(&["daemon", "token", "add", "ci", "--actions", "restart,logs", "--processes", "app-*,group:workers"]).command
+        else {
+            unreachable!()
+        };
+
+        assert_eq!((name.as_str(), actions, processes, read_only), ("ci", vec![string!("restart"), string!("logs")], vec![string!("app-*"), string!("group:workers")], false));
+        assert_eq!(parse(&["daemon", "token", "rm", "ci"]).command.audit_entry().unwrap().action, "token_remove");
+    }
+
+    #[test]
+    fn test_no_daemon_flag() {
+        assert!(parse(&["list", "--no-daemon"]).no_daemon);
+        assert!(parse(&["--no-daemon", "logs", "api"]).no_daemon);
+        assert!(!parse(&["list", "--no-daemon"]).autostart());
+
+        assert!(parse(&["restore"]).command.needs_daemon());
+        assert!(parse(&["start", "app.js", "--watch", "src"]).command.needs_daemon());
+        assert!(parse(&["import", "all.hcl", "--background"]).command.needs_daemon());
+        assert!(!parse(&["start", "app.js"]).command.needs_daemon());
+        assert!(!parse(&["list"]).command.needs_daemon());
+    }
+
+    #[test]
+    fn test_audit_entry() {
+        let entry = parse(&["stop", "api,3"]).command.audit_entry().unwrap();
+        assert_eq!((entry.action.as_str(), entry.target.as_str(), entry.server), ("stop", "api,3", None));
+
+        let entry = parse(&["restart", "--label", "team=payments"]).command.audit_entry().unwrap();
+        assert_eq!(entry.target, "label:team=payments");
+
+        let entry = parse(&["start", "node app.js", "--name", "app", "-s", "prod"]).command.audit_entry().unwrap();
+        assert_eq!((entry.target.as_str(), entry.server.as_deref()), ("app", Some("prod")));
+
+        let entry = parse(&["env", "clear", "all", "--yes"]).command.audit_entry().unwrap();
+        assert_eq!((entry.action.as_str(), entry.target.as_str()), ("reset_env", "all"));
+
+        assert!(parse(&["env", "api"]).command.audit_entry().is_none());
+        assert!(parse(&["list"]).command.audit_entry().is_none());
+        assert!(parse(&["audit", "--action", "stop"]).command.audit_entry().is_none());
     }
 }
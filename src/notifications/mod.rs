@@ -1,18 +1,95 @@
+pub mod history;
+pub mod queue;
+
 use crate::config::structs::Notifications;
+use chrono::Utc;
+use history::{Delivery, History, NotificationHistory};
 use notify_rust::{Notification, Urgency};
-use std::sync::Arc;
+use queue::{NotificationQueue, QueueStatus};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
 use std::collections::HashMap;
+use std::time::Duration;
 use tokio::sync::RwLock;
 
+/// How often the background task looks for queued notifications to retry
+const RETRY_TICK_SECS: u64 = 5;
+
 #[derive(Debug, Clone)]
 pub struct NotificationManager {
     config: Arc<RwLock<Option<Notifications>>>,
+    queue: Arc<Mutex<NotificationQueue>>,
+    history: Arc<Mutex<History>>,
 }
 
 impl NotificationManager {
     pub fn new(config: Option<Notifications>) -> Self {
         Self {
             config: Arc::new(RwLock::new(config)),
+            queue: Arc::new(Mutex::new(NotificationQueue::new(None))),
+            history: Arc::new(Mutex::new(History::new(None))),
+        }
+    }
+
+    /// Create a manager whose retry queue survives restarts in a jsonl file
+    pub fn with_queue_file(config: Option<Notifications>, path: PathBuf) -> Self {
+        Self {
+            config: Arc::new(RwLock::new(config)),
+            queue: Arc::new(Mutex::new(NotificationQueue::new(Some(path)))),
+            history: Arc::new(Mutex::new(History::new(None))),
+        }
+    }
+
+    /// Keep the delivery history in a jsonl file as well, the last deliveries are read back from it
+    pub fn with_history_file(self, path: PathBuf) -> Self {
+        Self {
+            history: Arc::new(Mutex::new(History::new(Some(path)))),
+            ..self
+        }
+    }
+
+    pub fn queue_status(&self) -> QueueStatus {
+        self.queue.lock().unwrap().status(Utc::now())
+    }
+
+    /// The last `limit` deliveries and the last one of each configured channel
+    pub async fn history(&self, limit: usize) -> NotificationHistory {
+        let channels = self.config.read().await.as_ref().and_then(|cfg| cfg.channels.clone()).unwrap_or_default();
+        self.history.lock().unwrap().report(limit, &channels)
+    }
+
+    /// Retry queued notifications in the background until the runtime shuts down
+    pub fn spawn_retry_task(&self) -> tokio::task::JoinHandle<()> {
+        let manager = self.clone();
+
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(RETRY_TICK_SECS));
+            loop {
+                interval.tick().await;
+                manager.retry_pending().await;
+            }
+        })
+    }
+
+    /// Attempt every queued notification that is due, rescheduling the ones that fail again
+    pub async fn retry_pending(&self) {
+        self.retry_due(Utc::now()).await
+    }
+
+    async fn retry_due(&self, now: chrono::DateTime<Utc>) {
+        let due = self.queue.lock().unwrap().take_due(now);
+        if due.is_empty() {
+            return;
+        }
+
+        let client = reqwest::Client::new();
+        for item in due {
+            match self.attempt(&client, "retry", None, &item.channel, &item.title, &item.message).await {
+                Ok(_) => log::info!("Delivered queued notification '{}' after {} attempts", item.title, item.attempts + 1),
+                Err(ChannelError::Failed(err) | ChannelError::Invalid(err)) => {
+                    self.queue.lock().unwrap().reschedule(item, err, now)
+                }
+            }
         }
     }
 
@@ -21,7 +98,7 @@ impl NotificationManager {
         *cfg = config;
     }
 
-    pub async fn send(&self, event: NotificationEvent, title: &str, message: &str) {
+    pub async fn send(&self, event: NotificationEvent, process: Option<&str>, title: &str, message: &str) {
         let config = self.config.read().await;
         
         if let Some(cfg) = config.as_ref() {
@@ -38,6 +115,7 @@ impl NotificationManager {
                     NotificationEvent::ProcessStop => events.process_stop,
                     NotificationEvent::ProcessCrash => events.process_crash,
                     NotificationEvent::ProcessRestart => events.process_restart,
+                    NotificationEvent::ProcessAlert => events.process_alert,
                 };
 
                 if !enabled {
@@ -53,7 +131,7 @@ impl NotificationManager {
             // Send to configured external channels
             if let Some(channels) = &cfg.channels {
                 if !channels.is_empty() {
-                    if let Err(e) = self.send_channel_notifications(title, message, channels).await {
+                    if let Err(e) = self.send_channel_notifications(event, process, title, message, channels).await {
                         log::warn!("Failed to send channel notifications: {}", e);
                     }
                 }
@@ -69,7 +147,7 @@ impl NotificationManager {
     ) -> Result<(), Box<dyn std::error::Error>> {
         let urgency = match event {
             NotificationEvent::ProcessCrash => Urgency::Critical,
-            NotificationEvent::AgentDisconnect => Urgency::Normal,
+            NotificationEvent::AgentDisconnect | NotificationEvent::ProcessAlert => Urgency::Normal,
             _ => Urgency::Low,
         };
 
@@ -86,40 +164,29 @@ impl NotificationManager {
 
     async fn send_channel_notifications(
         &self,
+        event: NotificationEvent,
+        process: Option<&str>,
         title: &str,
         message: &str,
         channels: &[String],
     ) -> Result<(), Box<dyn std::error::Error>> {
-        use reqwest::Client;
-        
-        let client = Client::new();
+        let client = reqwest::Client::new();
         let mut errors = Vec::new();
         let mut success_count = 0;
         
         for channel_url in channels {
-            // Parse the shoutrrr URL to determine the service type
-            if let Some((service, rest)) = channel_url.split_once("://") {
-                let result = match service {
-                    "discord" => self.send_discord_webhook(&client, rest, title, message).await,
-                    "slack" => self.send_slack_webhook(&client, rest, title, message).await,
-                    "telegram" => self.send_telegram_message(&client, rest, title, message).await,
-                    _ => {
-                        log::warn!("Unsupported notification service: {}", service);
-                        errors.push(format!("Unsupported service: {}", service));
-                        continue;
-                    }
-                };
-                
-                match result {
-                    Ok(_) => success_count += 1,
-                    Err(e) => {
-                        log::warn!("Failed to send to {}: {}", service, e);
-                        errors.push(format!("{}: {}", service, e));
-                    }
+            match self.attempt(&client, event.name(), process, channel_url, title, message).await {
+                Ok(_) => success_count += 1,
+                Err(ChannelError::Invalid(e)) => {
+                    log::warn!("{}", e);
+                    errors.push(e);
+                }
+                Err(ChannelError::Failed(e)) => {
+                    log::warn!("Failed to send to {}: {}", history::redact(channel_url), e);
+                    // Keep the notification around so it is not lost on a transient failure
+                    self.queue.lock().unwrap().push(channel_url, title, message, e.clone(), Utc::now());
+                    errors.push(e);
                 }
-            } else {
-                log::warn!("Invalid channel URL format: {}", channel_url);
-                errors.push(format!("Invalid URL format: {}", channel_url));
             }
         }
         
@@ -132,6 +199,71 @@ impl NotificationManager {
         }
     }
 
+    /// Send a test notification to `channels`, recorded in the history but never queued for a retry
+    pub async fn send_test(&self, title: &str, message: &str, channels: &[String]) -> Result<(), String> {
+        let client = reqwest::Client::new();
+        let mut errors = Vec::new();
+
+        for channel_url in channels {
+            if let Err(ChannelError::Failed(e) | ChannelError::Invalid(e)) = self.attempt(&client, "test", None, channel_url, title, message).await {
+                log::warn!("Failed to send to {}: {}", history::redact(channel_url), e);
+                errors.push(e);
+            }
+        }
+
+        match errors.len() < channels.len() {
+            true => Ok(()),
+            false if errors.is_empty() => Err("No valid notification channels configured".into()),
+            false => Err(errors.join("; ")),
+        }
+    }
+
+    /// Send to one channel and record the attempt, the error comes back with the channel tokens redacted
+    async fn attempt(
+        &self,
+        client: &reqwest::Client,
+        event: &str,
+        process: Option<&str>,
+        channel_url: &str,
+        title: &str,
+        message: &str,
+    ) -> Result<(), ChannelError> {
+        let result = self.send_to_channel(client, channel_url, title, message).await.map_err(|err| match err {
+            ChannelError::Invalid(e) => ChannelError::Invalid(history::redact_text(&e, channel_url)),
+            ChannelError::Failed(e) => ChannelError::Failed(history::redact_text(&e, channel_url)),
+        });
+
+        let error = match &result {
+            Err(ChannelError::Failed(e) | ChannelError::Invalid(e)) => Err(e.as_str()),
+            Ok(_) => Ok(()),
+        };
+        self.history.lock().unwrap().record(Delivery::new(event, process, title, channel_url, error));
+
+        result
+    }
+
+    async fn send_to_channel(
+        &self,
+        client: &reqwest::Client,
+        channel_url: &str,
+        title: &str,
+        message: &str,
+    ) -> Result<(), ChannelError> {
+        // Parse the shoutrrr URL to determine the service type
+        let Some((service, rest)) = channel_url.split_once("://") else {
+            return Err(ChannelError::Invalid(format!("Invalid URL format: {}", channel_url)));
+        };
+
+        let result = match service {
+            "discord" => self.send_discord_webhook(client, rest, title, message).await,
+            "slack" => self.send_slack_webhook(client, rest, title, message).await,
+            "telegram" => self.send_telegram_message(client, rest, title, message).await,
+            _ => return Err(ChannelError::Invalid(format!("Unsupported service: {}", service))),
+        };
+
+        result.map_err(|e| ChannelError::Failed(format!("{}: {}", service, e)))
+    }
+
     async fn send_discord_webhook(
         &self,
         client: &reqwest::Client,
@@ -259,6 +391,12 @@ impl NotificationManager {
     }
 }
 
+/// Why a single channel send failed; only `Failed` is worth retrying
+enum ChannelError {
+    Invalid(String),
+    Failed(String),
+}
+
 #[derive(Debug, Clone, Copy)]
 pub enum NotificationEvent {
     AgentConnect,
@@ -267,4 +405,133 @@ pub enum NotificationEvent {
     ProcessStop,
     ProcessCrash,
     ProcessRestart,
+    ProcessAlert,
+}
+
+impl NotificationEvent {
+    /// The name of the event in the config and the delivery history
+    pub fn name(&self) -> &'static str {
+        match self {
+            NotificationEvent::AgentConnect => "agent_connect",
+            NotificationEvent::AgentDisconnect => "agent_disconnect",
+            NotificationEvent::ProcessStart => "process_start",
+            NotificationEvent::ProcessStop => "process_stop",
+            NotificationEvent::ProcessCrash => "process_crash",
+            NotificationEvent::ProcessRestart => "process_restart",
+            NotificationEvent::ProcessAlert => "process_alert",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    // Webhook endpoint answering 500 to the first `failures` requests and 204 afterwards
+    async fn flaky_webhook(failures: usize) -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let hits = Arc::new(AtomicUsize::new(0));
+        let counter = hits.clone();
+
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut request = Vec::new();
+                let mut buffer = [0u8; 4096];
+
+                // Read headers and the json body before answering
+                while let Ok(read) = socket.read(&mut buffer).await {
+                    if read == 0 {
+                        break;
+                    }
+                    request.extend_from_slice(&buffer[..read]);
+                    let text = String::from_utf8_lossy(&request);
+                    if let Some(end) = text.find("\r\n\r\n") {
+                        let length = text
+                            .lines()
+                            .find_map(|line| line.to_lowercase().strip_prefix("content-length:").map(|v| v.trim().parse::<usize>().unwrap_or(0)))
+                            .unwrap_or(0);
+                        if request.len() >= end + 4 + length {
+                            break;
+                        }
+                    }
+                }
+
+                let status = match counter.fetch_add(1, Ordering::SeqCst) < failures {
+                    true => "500 Internal Server Error",
+                    false => "204 No Content",
+                };
+                let response = format!("HTTP/1.1 {status}\r\ncontent-length: 0\r\nconnection: close\r\n\r\n");
+                let _ = socket.write_all(response.as_bytes()).await;
+            }
+        });
+
+        (format!("discord://http://{address}/webhook"), hits)
+    }
+
+    #[tokio::test]
+    async fn test_failed_notification_is_queued_and_retried() {
+        let (channel, hits) = flaky_webhook(1).await;
+        let manager = NotificationManager::new(None);
+        let channels = vec![channel];
+
+        assert!(manager.send_channel_notifications(NotificationEvent::ProcessCrash, Some("app"), "crash", "app crashed", &channels).await.is_err());
+        assert_eq!(manager.queue_status().pending, 1);
+
+        // Not due yet, nothing is sent
+        manager.retry_due(Utc::now()).await;
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+
+        manager.retry_due(Utc::now() + chrono::Duration::seconds(60)).await;
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+        assert_eq!(manager.queue_status().pending, 0);
+    }
+
+    #[tokio::test]
+    async fn test_identical_failures_are_deduplicated() {
+        let (channel, _) = flaky_webhook(usize::MAX).await;
+        let manager = NotificationManager::new(None);
+        let channels = vec![channel];
+
+        for _ in 0..3 {
+            let _ = manager.send_channel_notifications(NotificationEvent::ProcessCrash, Some("app"), "crash", "app crashed", &channels).await;
+        }
+        let _ = manager.send_channel_notifications(NotificationEvent::ProcessCrash, Some("worker"), "crash", "worker crashed", &channels).await;
+
+        assert_eq!(manager.queue_status().pending, 2);
+    }
+
+    #[tokio::test]
+    async fn test_deliveries_are_recorded() {
+        let (channel, _) = flaky_webhook(1).await;
+        let manager = NotificationManager::new(Some(Notifications { enabled: true, events: None, channels: Some(vec![channel.clone()]) }));
+        let channels = vec![channel];
+
+        let _ = manager.send_channel_notifications(NotificationEvent::ProcessCrash, Some("app"), "crash", "app crashed", &channels).await;
+        manager.retry_due(Utc::now() + chrono::Duration::seconds(60)).await;
+        assert!(manager.send_test("OPM Test Notification", "hello", &channels).await.is_ok());
+        assert!(manager.send_test("OPM Test Notification", "hello", &["pigeon://coop".to_string()]).await.is_err());
+
+        let report = manager.history(10).await;
+        let deliveries: Vec<(&str, bool)> = report.deliveries.iter().map(|delivery| (delivery.event.as_str(), delivery.success)).collect();
+        assert_eq!(deliveries, [("process_crash", false), ("retry", true), ("test", true), ("test", false)]);
+        assert_eq!(report.deliveries[0].process.as_deref(), Some("app"));
+
+        // Test sends are not queued, only the configured channel is reported
+        assert_eq!(manager.queue_status().pending, 0);
+        assert_eq!(report.channels.len(), 1);
+        assert_eq!(report.channels[0].last.as_ref().map(|last| last.event.as_str()), Some("test"));
+    }
+
+    #[tokio::test]
+    async fn test_unsupported_channel_is_not_queued() {
+        let manager = NotificationManager::new(None);
+        let channels = vec!["pigeon://coop".to_string()];
+
+        assert!(manager.send_channel_notifications(NotificationEvent::ProcessCrash, Some("app"), "crash", "app crashed", &channels).await.is_err());
+        assert_eq!(manager.queue_status().pending, 0);
+    }
 }
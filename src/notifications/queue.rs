@@ -0,0 +1,200 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+};
+use utoipa::ToSchema;
+
+/// First retry delay, doubled after every failed attempt
+pub const RETRY_BASE_SECS: i64 = 5;
+/// Upper bound for the delay between two attempts
+pub const RETRY_MAX_DELAY_SECS: i64 = 300;
+/// Notifications still failing after this long are dropped
+pub const RETRY_MAX_AGE_SECS: i64 = 3600;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueuedNotification {
+    pub channel: String,
+    pub title: String,
+    pub message: String,
+    pub attempts: u32,
+    /// Number of identical notifications folded into this entry
+    #[serde(default)]
+    pub duplicates: u32,
+    pub first_failed: DateTime<Utc>,
+    pub next_attempt: DateTime<Utc>,
+    pub last_error: String,
+}
+
+impl QueuedNotification {
+    fn is_same(&self, channel: &str, title: &str, message: &str) -> bool {
+        self.channel == channel && self.title == title && self.message == message
+    }
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct QueueStatus {
+    pub pending: usize,
+    #[schema(value_type = Option<String>, example = "2000-01-01T01:00:00.000Z")]
+    pub oldest: Option<DateTime<Utc>>,
+    pub oldest_age_secs: Option<i64>,
+}
+
+/// Outbound queue for channel notifications that failed to send
+#[derive(Debug, Default)]
+pub struct NotificationQueue {
+    items: Vec<QueuedNotification>,
+    spill: Option<PathBuf>,
+}
+
+impl NotificationQueue {
+    pub fn new(spill: Option<PathBuf>) -> Self {
+        let items = spill.as_deref().map(load).unwrap_or_default();
+        Self { items, spill }
+    }
+
+    /// Queue a failed send, folding it into an identical pending entry if there is one
+    pub fn push(&mut self, channel: &str, title: &str, message: &str, error: String, now: DateTime<Utc>) {
+        if let Some(existing) = self.items.iter_mut().find(|item| item.is_same(channel, title, message)) {
+            existing.duplicates += 1;
+            existing.last_error = error;
+        } else {
+            self.items.push(QueuedNotification {
+                channel: channel.to_string(),
+                title: title.to_string(),
+                message: message.to_string(),
+                attempts: 1,
+                duplicates: 0,
+                first_failed: now,
+                next_attempt: now + backoff(1),
+                last_error: error,
+            });
+        }
+
+        self.persist();
+    }
+
+    /// Remove and return every entry whose retry time has come, dropping expired ones
+    pub fn take_due(&mut self, now: DateTime<Utc>) -> Vec<QueuedNotification> {
+        let max_age = Duration::seconds(RETRY_MAX_AGE_SECS);
+        let (expired, pending): (Vec<_>, Vec<_>) = self.items.drain(..).partition(|item| now - item.first_failed > max_age);
+
+        for item in &expired {
+            log::warn!(
+                "Dropping notification '{}' for {} after {} attempts: {}",
+                item.title,
+                super::history::redact(&item.channel),
+                item.attempts,
+                item.last_error
+            );
+        }
+
+        let (due, waiting): (Vec<_>, Vec<_>) = pending.into_iter().partition(|item| item.next_attempt <= now);
+        self.items = waiting;
+
+        if !expired.is_empty() || !due.is_empty() {
+            self.persist();
+        }
+
+        due
+    }
+
+    /// Put an entry back after another failed attempt
+    pub fn reschedule(&mut self, mut item: QueuedNotification, error: String, now: DateTime<Utc>) {
+        item.attempts += 1;
+        item.next_attempt = now + backoff(item.attempts);
+        item.last_error = error;
+        self.items.push(item);
+        self.persist();
+    }
+
+    pub fn status(&self, now: DateTime<Utc>) -> QueueStatus {
+        let oldest = self.items.iter().map(|item| item.first_failed).min();
+
+        QueueStatus {
+            pending: self.items.len(),
+            oldest,
+            oldest_age_secs: oldest.map(|oldest| (now - oldest).num_seconds()),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    fn persist(&self) {
+        let Some(path) = &self.spill else { return };
+
+        let write = || -> std::io::Result<()> {
+            let mut file = OpenOptions::new().create(true).write(true).truncate(true).open(path)?;
+            for item in &self.items {
+                if let Ok(line) = serde_json::to_string(item) {
+                    writeln!(file, "{line}")?;
+                }
+            }
+            Ok(())
+        };
+
+        if let Err(err) = write() {
+            log::warn!("Failed to write notification queue to {}: {}", path.display(), err);
+        }
+    }
+}
+
+/// Delay before the given attempt number, doubling up to the cap
+pub fn backoff(attempts: u32) -> Duration {
+    let exponent = attempts.saturating_sub(1).min(16);
+    Duration::seconds((RETRY_BASE_SECS << exponent).min(RETRY_MAX_DELAY_SECS))
+}
+
+fn load(path: &Path) -> Vec<QueuedNotification> {
+    fs::read_to_string(path)
+        .map(|contents| contents.lines().filter_map(|line| serde_json::from_str(line).ok()).collect())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_doubles_up_to_cap() {
+        assert_eq!(backoff(1), Duration::seconds(5));
+        assert_eq!(backoff(2), Duration::seconds(10));
+        assert_eq!(backoff(3), Duration::seconds(20));
+        assert_eq!(backoff(40), Duration::seconds(RETRY_MAX_DELAY_SECS));
+    }
+
+    #[test]
+    fn test_expired_entries_are_dropped() {
+        let now = Utc::now();
+        let mut queue = NotificationQueue::new(None);
+        queue.push("discord://x", "title", "message", "offline".into(), now);
+
+        let due = queue.take_due(now + Duration::seconds(RETRY_MAX_AGE_SECS + 1));
+        assert!(due.is_empty());
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn test_queue_is_restored_from_spill_file() {
+        let path = std::env::temp_dir().join(format!("opm-notify-queue-{}.jsonl", std::process::id()));
+        let now = Utc::now();
+
+        let mut queue = NotificationQueue::new(Some(path.clone()));
+        queue.push("discord://x", "title", "message", "offline".into(), now);
+        queue.push("slack://y", "title", "message", "offline".into(), now);
+
+        let restored = NotificationQueue::new(Some(path.clone()));
+        assert_eq!(restored.len(), 2);
+        assert_eq!(restored.status(now).oldest, Some(now));
+
+        fs::remove_file(&path).ok();
+    }
+}
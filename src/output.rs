@@ -0,0 +1,33 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+/// Quiet mode keeps stdout for the requested `--format` payload only
+pub fn set_quiet(quiet: bool) {
+    QUIET.store(quiet, Ordering::Relaxed);
+}
+
+pub fn quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
+
+/// Print decorative output (markers, banners, tables), dropped in quiet mode
+#[macro_export]
+macro_rules! notice {
+    ($($arg:tt)*) => {
+        if !$crate::output::quiet() {
+            println!($($arg)*)
+        }
+    };
+}
+
+/// Print a warning or error, on stderr in quiet mode so it never mixes with the payload
+#[macro_export]
+macro_rules! alert {
+    ($($arg:tt)*) => {
+        match $crate::output::quiet() {
+            true => eprintln!($($arg)*),
+            false => println!($($arg)*),
+        }
+    };
+}
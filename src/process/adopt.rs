@@ -0,0 +1,211 @@
+use super::is_pid_alive;
+
+use nix::{
+    sys::wait::{WaitPidFlag, WaitStatus, waitpid},
+    unistd::Pid,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+    thread::sleep,
+    time::{Duration, Instant},
+};
+
+/// How long `opm start --pidfile` waits for the service to write its pid
+pub const PIDFILE_TIMEOUT: Duration = Duration::from_secs(10);
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Service that forks into the background, tracked through the pid it writes to `path`
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct Pidfile {
+    pub path: PathBuf,
+    /// Command opm launched, it usually exits once the service has forked
+    #[serde(default)]
+    pub launcher: Option<Launcher>,
+    /// Start time of the adopted pid, tells it apart from a later process reusing the number
+    #[serde(default)]
+    pub start_ticks: Option<u64>,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq)]
+pub struct Launcher {
+    pub pid: i64,
+    /// Exit code, None while it still ran when the pid was adopted or after a signal
+    pub code: Option<i32>,
+}
+
+impl fmt::Display for Launcher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.code {
+            Some(code) => write!(f, "pid {}, exited with code {code}", self.pid),
+            None => write!(f, "pid {}, still running at adoption", self.pid),
+        }
+    }
+}
+
+impl Pidfile {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path, launcher: None, start_ticks: None }
+    }
+
+    /// Whether `pid` is still the process that was adopted
+    pub fn owns(&self, pid: i64) -> bool {
+        self.start_ticks.is_none_or(|ticks| start_ticks(pid).is_none_or(|current| current == ticks))
+    }
+}
+
+/// Start time of a process in clock ticks since boot, None where it cannot be read
+pub fn start_ticks(pid: i64) -> Option<u64> {
+    #[cfg(target_os = "linux")]
+    {
+        let stat = fs::read_to_string(format!("/proc/{pid}/stat")).ok()?;
+        // The command name may contain spaces, fields are counted from its closing parenthesis
+        let fields = &stat[stat.rfind(')')? + 1..];
+        fields.split_whitespace().nth(19)?.parse().ok()
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = pid;
+        None
+    }
+}
+
+/// Pid written in a pidfile
+pub fn read(path: &Path) -> Result<i64, String> {
+    let contents = fs::read_to_string(path).map_err(|err| format!("Cannot read pidfile {}: {err}", path.display()))?;
+
+    match contents.split_whitespace().next().map(str::parse::<i64>) {
+        Some(Ok(pid)) if pid > 0 => Ok(pid),
+        _ => Err(format!("Pidfile {} does not contain a pid", path.display())),
+    }
+}
+
+/// Remove a pidfile left behind by an instance that is gone, before launching a new one.
+/// A pidfile naming a live process means another instance is running and is refused.
+pub fn clear_stale(path: &Path) -> Result<(), String> {
+    if !path.exists() {
+        return Ok(());
+    }
+
+    match read(path) {
+        Ok(pid) if is_pid_alive(pid) => Err(format!(
+            "Pidfile {} names running pid {pid}, stop that instance first",
+            path.display()
+        )),
+        _ => fs::remove_file(path).map_err(|err| format!("Cannot remove stale pidfile {}: {err}", path.display())),
+    }
+}
+
+/// Exit code of the launcher if it has finished, reaping it
+fn launcher_exit(pid: i64) -> Option<Option<i32>> {
+    match waitpid(Pid::from_raw(pid as i32), Some(WaitPidFlag::WNOHANG)) {
+        Ok(WaitStatus::Exited(_, code)) => Some(Some(code)),
+        Ok(WaitStatus::Signaled(..)) => Some(None),
+        Ok(_) => None,
+        // Not our child, e.g. already reaped, only liveness is left to go by
+        Err(_) => (!is_pid_alive(pid)).then_some(None),
+    }
+}
+
+/// Wait for the service started by `launcher` to write a live pid to `path`,
+/// `exited` is the exit code of a launcher that was already reaped
+pub fn adopt(path: &Path, launcher: i64, exited: Option<i32>, timeout: Duration) -> Result<(i64, Pidfile), String> {
+    let deadline = Instant::now() + timeout;
+    let mut exit = exited.map(Some);
+
+    loop {
+        exit = exit.or_else(|| launcher_exit(launcher));
+
+        match read(path) {
+            Ok(pid) if is_pid_alive(pid) => {
+                let pidfile = Pidfile {
+                    path: path.to_path_buf(),
+                    launcher: Some(Launcher { pid: launcher, code: exit.flatten() }),
+                    start_ticks: start_ticks(pid),
+                };
+                return Ok((pid, pidfile));
+            }
+            Ok(pid) if Instant::now() >= deadline => {
+                return Err(format!("Pidfile {} names pid {pid}, which is not running", path.display()));
+            }
+            _ => {}
+        }
+
+        if let Some(Some(code)) = exit
+            && code != 0
+            && !path.exists()
+        {
+            return Err(format!("Launcher exited with code {code} before writing {}", path.display()));
+        }
+
+        if Instant::now() >= deadline {
+            return Err(format!("Pidfile {} was not written within {}s", path.display(), timeout.as_secs()));
+        }
+
+        sleep(POLL_INTERVAL);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+
+    fn pidfile(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("opm-adopt-{name}-{}.pid", std::process::id()))
+    }
+
+    #[test]
+    fn test_read_and_clear_stale() {
+        let path = pidfile("stale");
+
+        fs::write(&path, "not a pid\n").unwrap();
+        assert!(read(&path).unwrap_err().contains("does not contain a pid"));
+
+        fs::write(&path, format!("{}\n", std::process::id())).unwrap();
+        assert_eq!(read(&path), Ok(std::process::id() as i64));
+        assert!(clear_stale(&path).unwrap_err().contains("stop that instance first"));
+
+        fs::write(&path, format!("{}\n", i32::MAX - 1000)).unwrap();
+        assert_eq!(clear_stale(&path), Ok(()));
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_adopt_forked_service() {
+        let path = pidfile("fork");
+        let script = format!("sleep 30 & echo $! > {}", path.display());
+        let mut launcher = Command::new("sh").args(["-c", &script]).spawn().unwrap();
+
+        let (pid, adopted) = adopt(&path, launcher.id() as i64, None, Duration::from_secs(5)).unwrap();
+
+        assert_ne!(pid, launcher.id() as i64);
+        assert_eq!(adopted.launcher.map(|launcher| launcher.pid), Some(launcher.id() as i64));
+        assert!(adopted.owns(pid));
+
+        unsafe { libc::kill(pid as i32, libc::SIGKILL) };
+        // adopt already reaped it, nothing is left to wait for
+        launcher.wait().ok();
+        fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_adopt_failures() {
+        let path = pidfile("missing");
+        let mut launcher = Command::new("sh").args(["-c", "exit 3"]).spawn().unwrap();
+        let err = adopt(&path, launcher.id() as i64, None, Duration::from_secs(5)).unwrap_err();
+        assert!(err.contains("exited with code 3"), "{err}");
+        launcher.wait().ok();
+
+        // Reaped by process_run before adoption, the code it collected is used instead
+        let err = adopt(&path, i32::MAX as i64 - 1000, Some(4), Duration::from_secs(5)).unwrap_err();
+        assert!(err.contains("exited with code 4"), "{err}");
+
+        let mut launcher = Command::new("sh").args(["-c", "exit 0"]).spawn().unwrap();
+        let err = adopt(&path, launcher.id() as i64, None, Duration::from_millis(300)).unwrap_err();
+        assert!(err.contains("was not written"), "{err}");
+        launcher.wait().ok();
+    }
+}
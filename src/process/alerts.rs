@@ -0,0 +1,274 @@
+use super::stats::Sample;
+use crate::helpers;
+
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+
+use std::{collections::HashMap, fmt, str::FromStr};
+
+/// What an alert watches, sampled for the process and its children
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Metric {
+    /// Percent of one core
+    Cpu,
+    /// Resident memory in bytes
+    Memory,
+    /// Open file descriptors
+    Fds,
+    Threads,
+}
+
+impl Metric {
+    /// Current value in a sample, None when it could not be read
+    pub fn value(self, sample: &Sample) -> Option<f64> {
+        match self {
+            Metric::Cpu => sample.running.then_some(sample.cpu),
+            Metric::Memory => sample.running.then_some(sample.memory as f64),
+            Metric::Fds => sample.fds.map(|fds| fds as f64),
+            Metric::Threads => sample.threads.map(|threads| threads as f64),
+        }
+    }
+
+    /// A value of this metric as shown in events and notifications
+    pub fn format(self, value: f64) -> String {
+        match self {
+            Metric::Cpu => format!("{value:.1}%"),
+            Metric::Memory => helpers::format_memory(value as u64),
+            Metric::Fds | Metric::Threads => format!("{value}"),
+        }
+    }
+}
+
+impl FromStr for Metric {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "cpu" => Ok(Metric::Cpu),
+            "mem" | "memory" => Ok(Metric::Memory),
+            "fds" => Ok(Metric::Fds),
+            "threads" => Ok(Metric::Threads),
+            value => Err(format!("Unknown metric '{value}', expected cpu, memory, fds or threads")),
+        }
+    }
+}
+
+impl fmt::Display for Metric {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Metric::Cpu => write!(f, "cpu"),
+            Metric::Memory => write!(f, "memory"),
+            Metric::Fds => write!(f, "fds"),
+            Metric::Threads => write!(f, "threads"),
+        }
+    }
+}
+
+/// `<metric> above <value> [for <duration>]`, e.g. `fds above 900 for 1m`, stored as written
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Alert {
+    pub metric: Metric,
+    pub above: f64,
+    /// Seconds the value has to stay above before the alert fires
+    pub duration: u64,
+}
+
+fn parse_duration(value: &str) -> Result<u64, String> {
+    let (amount, unit) = match value.char_indices().last() {
+        Some((index, unit)) if unit.is_ascii_alphabetic() => (&value[..index], unit),
+        _ => (value, 's'),
+    };
+
+    let amount: u64 = amount.parse().map_err(|_| format!("Invalid duration '{value}', expected e.g. 30s, 5m or 1h"))?;
+    match unit {
+        's' => Ok(amount),
+        'm' => Ok(amount * 60),
+        'h' => Ok(amount * 3600),
+        _ => Err(format!("Invalid duration '{value}', expected e.g. 30s, 5m or 1h")),
+    }
+}
+
+fn format_duration(secs: u64) -> String {
+    match secs {
+        secs if secs > 0 && secs % 3600 == 0 => format!("{}h", secs / 3600),
+        secs if secs > 0 && secs % 60 == 0 => format!("{}m", secs / 60),
+        secs => format!("{secs}s"),
+    }
+}
+
+/// Memory thresholds keep the largest unit that divides them exactly, so they read back the same
+pub(super) fn format_bytes(bytes: u64) -> String {
+    [(1024_u64.pow(3), "G"), (1024 * 1024, "M"), (1024, "K")]
+        .iter()
+        .find(|(size, _)| bytes > 0 && bytes.is_multiple_of(*size))
+        .map_or_else(|| bytes.to_string(), |(size, unit)| format!("{}{unit}", bytes / size))
+}
+
+impl FromStr for Alert {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let words: Vec<&str> = value.split_whitespace().collect();
+
+        let (metric, threshold, duration) = match words.as_slice() {
+            [metric, "above", threshold] => (metric, threshold, None),
+            [metric, "above", threshold, "for", duration] => (metric, threshold, Some(duration)),
+            _ => return Err(format!("Invalid alert '{value}', expected '<metric> above <value> [for <duration>]'")),
+        };
+
+        let metric: Metric = metric.parse()?;
+        let above = match metric {
+            Metric::Memory => helpers::parse_memory(threshold)? as f64,
+            Metric::Cpu => threshold.trim_end_matches('%').parse().map_err(|_| format!("Invalid cpu percent '{threshold}'"))?,
+            Metric::Fds | Metric::Threads => threshold.parse::<u64>().map_err(|_| format!("Invalid {metric} count '{threshold}'"))? as f64,
+        };
+
+        Ok(Alert {
+            metric,
+            above,
+            duration: duration.map(|duration| parse_duration(duration)).transpose()?.unwrap_or(0),
+        })
+    }
+}
+
+impl fmt::Display for Alert {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.metric {
+            Metric::Memory => write!(f, "memory above {}", format_bytes(self.above as u64))?,
+            Metric::Cpu => write!(f, "cpu above {}%", self.above)?,
+            metric => write!(f, "{metric} above {}", self.above)?,
+        }
+
+        match self.duration {
+            0 => Ok(()),
+            duration => write!(f, " for {}", format_duration(duration)),
+        }
+    }
+}
+
+impl TryFrom<String> for Alert {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> { value.parse() }
+}
+
+impl From<Alert> for String {
+    fn from(alert: Alert) -> Self { alert.to_string() }
+}
+
+/// Clap parser for `--alert "fds above 900 for 1m"`
+pub fn parse(value: &str) -> Result<Alert, String> { value.parse() }
+
+/// What changed for an alert at a check
+#[derive(Debug, Clone, PartialEq)]
+pub enum Transition {
+    /// The value stayed above the threshold for the whole duration, with the value now
+    Fired(f64),
+    /// A fired alert is back under its threshold
+    Resolved,
+}
+
+struct Breach {
+    since: DateTime<Utc>,
+    fired: bool,
+}
+
+/// Since when the alerts of each process have been over their threshold
+#[derive(Default)]
+pub struct Tracker {
+    breaches: HashMap<(usize, String), Breach>,
+}
+
+impl Tracker {
+    /// Feed the current value of an alert. An alert fires once per breach, values that
+    /// could not be sampled leave its state as it was.
+    pub fn check(&mut self, id: usize, alert: &Alert, value: Option<f64>, now: DateTime<Utc>) -> Option<Transition> {
+        let value = value?;
+        let key = (id, alert.to_string());
+
+        if value <= alert.above {
+            return match self.breaches.remove(&key) {
+                Some(breach) if breach.fired => Some(Transition::Resolved),
+                _ => None,
+            };
+        }
+
+        let breach = self.breaches.entry(key).or_insert(Breach { since: now, fired: false });
+        let held = now - breach.since >= TimeDelta::seconds(alert.duration as i64);
+
+        match breach.fired || !held {
+            true => None,
+            false => {
+                breach.fired = true;
+                Some(Transition::Fired(value))
+            }
+        }
+    }
+
+    /// Drop the state of a process, when it stopped or was removed
+    pub fn forget(&mut self, id: usize) { self.breaches.retain(|(breach_id, _), _| *breach_id != id); }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn alert(value: &str) -> Alert { value.parse().unwrap() }
+
+    #[test]
+    fn test_parse_alert() {
+        assert_eq!(alert("fds above 900 for 1m"), Alert { metric: Metric::Fds, above: 900.0, duration: 60 });
+        assert_eq!(alert("cpu above 80% for 30s"), Alert { metric: Metric::Cpu, above: 80.0, duration: 30 });
+        assert_eq!(alert("mem above 512M").above, 512.0 * 1024.0 * 1024.0);
+        assert_eq!(alert("threads above 200 for 90").duration, 90);
+
+        assert!("fds over 900".parse::<Alert>().is_err());
+        assert!("sockets above 10".parse::<Alert>().is_err());
+        assert!("fds above 9.5".parse::<Alert>().is_err());
+        assert!("fds above 900 for 1d".parse::<Alert>().is_err());
+    }
+
+    #[test]
+    fn test_alert_round_trip() {
+        for value in ["fds above 900 for 1m", "memory above 512M for 2h", "cpu above 80.5%", "threads above 200 for 90s"] {
+            assert_eq!(alert(value).to_string(), value);
+            assert_eq!(alert(&alert(value).to_string()), alert(value));
+        }
+    }
+
+    #[test]
+    fn test_tracker_fires_once_after_duration() {
+        let mut tracker = Tracker::default();
+        let rule = alert("fds above 900 for 1m");
+        let start = Utc::now();
+        let at = |secs: i64| start + TimeDelta::seconds(secs);
+
+        assert_eq!(tracker.check(0, &rule, Some(950.0), at(0)), None);
+        assert_eq!(tracker.check(0, &rule, Some(950.0), at(30)), None);
+        // An unreadable sample neither resets nor fires
+        assert_eq!(tracker.check(0, &rule, None, at(45)), None);
+        assert_eq!(tracker.check(0, &rule, Some(960.0), at(60)), Some(Transition::Fired(960.0)));
+        assert_eq!(tracker.check(0, &rule, Some(990.0), at(120)), None);
+        assert_eq!(tracker.check(0, &rule, Some(100.0), at(130)), Some(Transition::Resolved));
+
+        // A dip below the threshold starts the duration over
+        assert_eq!(tracker.check(0, &rule, Some(950.0), at(140)), None);
+        assert_eq!(tracker.check(0, &rule, Some(800.0), at(150)), None);
+        assert_eq!(tracker.check(0, &rule, Some(950.0), at(190)), None);
+        assert_eq!(tracker.check(0, &rule, Some(950.0), at(250)), Some(Transition::Fired(950.0)));
+
+        tracker.forget(0);
+        assert_eq!(tracker.check(0, &rule, Some(100.0), at(260)), None);
+    }
+
+    #[test]
+    fn test_metric_value() {
+        let sample = Sample { running: true, cpu: 12.5, memory: 2048, fds: Some(42), threads: None };
+
+        assert_eq!(Metric::Cpu.value(&sample), Some(12.5));
+        assert_eq!(Metric::Fds.value(&sample), Some(42.0));
+        assert_eq!(Metric::Threads.value(&sample), None);
+        assert_eq!(Metric::Memory.value(&Sample::default()), None);
+    }
+}
@@ -0,0 +1,289 @@
+use super::Process;
+use crate::file;
+
+use chrono::{DateTime, Local, NaiveDateTime, TimeDelta, TimeZone, Utc};
+use flate2::{Compression, write::GzEncoder};
+use serde::Serialize;
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write},
+    path::PathBuf,
+};
+
+const MANIFEST: &str = "manifest.json";
+
+/// Which part of each log file ends up in the archive, the whole file when both are unset
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Selection {
+    /// Only the last N lines
+    pub lines: Option<usize>,
+    /// Only lines logged at or after this time
+    pub since: Option<DateTime<Utc>>,
+}
+
+impl Selection {
+    /// Selection for the last `lines` lines and/or the last `hours` hours
+    pub fn new(lines: Option<usize>, hours: Option<u64>) -> Self {
+        let since = hours
+            .and_then(|hours| TimeDelta::try_hours(hours.try_into().ok()?))
+            .and_then(|window| Utc::now().checked_sub_signed(window));
+
+        Self { lines, since }
+    }
+}
+
+#[derive(Serialize)]
+pub struct Manifest {
+    pub created: DateTime<Utc>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: DateTime<Utc>,
+    pub lines: Option<usize>,
+    pub processes: Vec<ManifestProcess>,
+}
+
+#[derive(Serialize)]
+pub struct ManifestProcess {
+    pub id: usize,
+    pub name: String,
+    pub command: String,
+    pub path: PathBuf,
+    pub files: Vec<ManifestFile>,
+}
+
+#[derive(Serialize)]
+pub struct ManifestFile {
+    pub kind: &'static str,
+    /// Path of the file inside the archive
+    pub entry: String,
+    pub source: String,
+    pub bytes: u64,
+    pub modified: Option<DateTime<Utc>>,
+}
+
+/// Byte range of a log file that goes into the archive
+struct Slice {
+    entry: String,
+    source: String,
+    offset: u64,
+    len: u64,
+    modified: Option<DateTime<Utc>>,
+}
+
+/// Write a gzipped tarball with a `manifest.json` and the out/error logs of every process.
+/// Log contents are streamed from disk, only the selected byte ranges are computed up front.
+pub fn write<W: Write>(writer: W, processes: &[Process], selection: Selection) -> io::Result<W> {
+    let created = Utc::now();
+    let mut slices = vec![];
+    let mut manifest = Manifest {
+        created,
+        from: selection.since,
+        to: created,
+        lines: selection.lines,
+        processes: vec![],
+    };
+
+    for process in processes {
+        let logs = process.logs();
+        let dir = format!("{}-{}", process.name.replace(['/', ' '], "_"), process.id);
+        let mut files = vec![];
+
+        for (kind, source) in [("out", logs.out), ("error", logs.error)] {
+            let Some(slice) = select(&source, format!("{dir}/{kind}.log"), selection)? else {
+                continue;
+            };
+
+            files.push(ManifestFile {
+                kind,
+                entry: slice.entry.clone(),
+                source: slice.source.clone(),
+                bytes: slice.len,
+                modified: slice.modified,
+            });
+            slices.push(slice);
+        }
+
+        manifest.processes.push(ManifestProcess {
+            id: process.id,
+            name: process.name.clone(),
+            command: process.script.clone(),
+            path: process.path.clone(),
+            files,
+        });
+    }
+
+    let mut builder = tar::Builder::new(GzEncoder::new(writer, Compression::default()));
+    let contents = serde_json::to_vec_pretty(&manifest).map_err(io::Error::other)?;
+    builder.append_data(&mut header(contents.len() as u64, created), MANIFEST, contents.as_slice())?;
+
+    for slice in slices {
+        let mut file = File::open(&slice.source)?;
+        file.seek(SeekFrom::Start(slice.offset))?;
+
+        let mut header = header(slice.len, slice.modified.unwrap_or(created));
+        builder.append_data(&mut header, &slice.entry, file.take(slice.len))?;
+    }
+
+    builder.into_inner()?.finish()
+}
+
+fn header(size: u64, modified: DateTime<Utc>) -> tar::Header {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(size);
+    header.set_mode(0o644);
+    header.set_mtime(modified.timestamp().max(0) as u64);
+    header
+}
+
+/// Work out the byte range of a log file matching the selection, `None` if the file is missing
+fn select(source: &str, entry: String, selection: Selection) -> io::Result<Option<Slice>> {
+    let Ok(mut file) = File::open(source) else {
+        return Ok(None);
+    };
+
+    let metadata = file.metadata()?;
+    let end = metadata.len();
+    let modified = metadata.modified().ok().map(DateTime::<Utc>::from);
+    let mut offset = 0;
+
+    if let Some(lines) = selection.lines {
+        offset = offset.max(file::tail_offset(&mut file, end, lines)?);
+    }
+
+    if let Some(since) = selection.since {
+        let recent = modified.is_none_or(|modified| modified >= since);
+        offset = offset.max(since_offset(&mut file, end, since, recent)?);
+    }
+
+    Ok(Some(Slice {
+        entry,
+        source: source.to_string(),
+        offset,
+        len: end - offset,
+        modified,
+    }))
+}
+
+/// Offset of the first line stamped at or after `since`. Logs without any timestamps are
+/// either kept whole or dropped depending on whether the file was written to since then.
+fn since_offset(file: &mut File, end: u64, since: DateTime<Utc>, recent: bool) -> io::Result<u64> {
+    file.seek(SeekFrom::Start(0))?;
+
+    let mut reader = BufReader::new(file.take(end));
+    let mut line = vec![];
+    let mut pos = 0;
+    let mut stamped = false;
+
+    loop {
+        line.clear();
+        let read = reader.read_until(b'\n', &mut line)?;
+        if read == 0 {
+            break;
+        }
+
+        if let Some(time) = line_time(&String::from_utf8_lossy(&line)) {
+            if time >= since {
+                return Ok(pos);
+            }
+            stamped = true;
+        }
+
+        pos += read as u64;
+    }
+
+    Ok(match stamped || !recent {
+        true => end,
+        false => 0,
+    })
+}
+
+/// Timestamp at the start of a log line, either RFC 3339 or `YYYY-MM-DD HH:MM:SS` in local time
+pub(crate) fn line_time(line: &str) -> Option<DateTime<Utc>> {
+    let token = line.split_whitespace().next()?.trim_matches(['[', ']']);
+
+    if let Ok(time) = DateTime::parse_from_rfc3339(token) {
+        return Some(time.with_timezone(&Utc));
+    }
+
+    let prefix = line.trim_start_matches('[').get(..19)?;
+    ["%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(prefix, format).ok())
+        .and_then(|time| Local.from_local_datetime(&time).earliest())
+        .map(|time| time.with_timezone(&Utc))
+}
+
+/// Archive file name for a single process, e.g. `api-0-logs.tar.gz`
+pub fn file_name(process: &Process) -> String {
+    format!("{}-{}-logs.tar.gz", process.name.replace(['/', ' ', '"'], "_"), process.id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::GzDecoder;
+    use macros_rs::string;
+    use std::fs;
+
+    fn temp_file(name: &str, contents: &str) -> (PathBuf, File) {
+        let path = std::env::temp_dir().join(format!("opm-archive-{}-{name}", std::process::id()));
+        fs::write(&path, contents).unwrap();
+        let file = File::open(&path).unwrap();
+        (path, file)
+    }
+
+    #[test]
+    fn test_since_offset_uses_line_timestamps() {
+        let contents = "2020-01-01T10:00:00Z old\ncontinued\n[2020-01-01T12:00:00Z] new\nafter\n";
+        let (path, mut file) = temp_file("since", contents);
+        let end = contents.len() as u64;
+        let since = DateTime::parse_from_rfc3339("2020-01-01T11:00:00Z").unwrap().with_timezone(&Utc);
+
+        let offset = since_offset(&mut file, end, since, false).unwrap();
+        assert_eq!(&contents[offset as usize..], "[2020-01-01T12:00:00Z] new\nafter\n");
+
+        let later = since + chrono::Duration::days(1);
+        assert_eq!(since_offset(&mut file, end, later, true).unwrap(), end);
+
+        fs::remove_file(path).ok();
+    }
+
+    #[test]
+    fn test_since_offset_without_timestamps_uses_modified_time() {
+        let contents = "plain\nlines\n";
+        let (path, mut file) = temp_file("plain", contents);
+        let end = contents.len() as u64;
+
+        assert_eq!(since_offset(&mut file, end, Utc::now(), true).unwrap(), 0);
+        assert_eq!(since_offset(&mut file, end, Utc::now(), false).unwrap(), end);
+
+        fs::remove_file(path).ok();
+    }
+
+    #[test]
+    fn test_select_and_pack_slice() {
+        let contents = "a\nb\nc\n";
+        let (path, _) = temp_file("pack", contents);
+        let source = path.to_string_lossy().to_string();
+
+        let selection = Selection { lines: Some(2), since: None };
+        let slice = select(&source, string!("p-0/out.log"), selection).unwrap().unwrap();
+        assert_eq!((slice.offset, slice.len), (2, 4));
+        assert!(select("/nonexistent/opm.log", string!("x"), selection).unwrap().is_none());
+
+        let mut builder = tar::Builder::new(GzEncoder::new(vec![], Compression::default()));
+        let mut file = File::open(&path).unwrap();
+        file.seek(SeekFrom::Start(slice.offset)).unwrap();
+        builder.append_data(&mut header(slice.len, Utc::now()), &slice.entry, file.take(slice.len)).unwrap();
+        let bytes = builder.into_inner().unwrap().finish().unwrap();
+
+        let mut archive = tar::Archive::new(GzDecoder::new(bytes.as_slice()));
+        let mut entry = archive.entries().unwrap().next().unwrap().unwrap();
+        let mut unpacked = String::new();
+        entry.read_to_string(&mut unpacked).unwrap();
+
+        assert_eq!(entry.path().unwrap().to_string_lossy(), "p-0/out.log");
+        assert_eq!(unpacked, "b\nc\n");
+
+        fs::remove_file(path).ok();
+    }
+}
@@ -0,0 +1,76 @@
+//! Where a `Runner` keeps its processes and how it runs them.
+//! `FileBackend` is the dump and real processes, `memory::InMemoryBackend` (feature `test-util`) simulates both.
+
+use super::{ProcessMetadata, ProcessRunResult, Runner, dump, is_pid_alive, process_run, process_stop_with_signal, wait_for_process_termination};
+use nix::sys::signal::Signal;
+use std::{fmt, ops::Deref, path::PathBuf, sync::Arc, time::Duration};
+
+pub trait RunnerBackend: Send + Sync {
+    /// The saved processes, an empty list when nothing was saved yet
+    fn read(&self) -> Runner;
+    fn write(&self, runner: &Runner);
+    fn run(&self, metadata: ProcessMetadata) -> Result<ProcessRunResult, String>;
+    /// Ask `pid` to exit with `signal`, it may take a while, see `wait`
+    fn stop(&self, pid: i64, signal: Signal) -> Result<(), String>;
+    fn alive(&self, pid: i64) -> bool;
+    /// Whether `pid` exited within `timeout`
+    fn wait(&self, pid: i64, timeout: Duration) -> bool;
+}
+
+/// The process dump on disk and processes spawned through the configured shell
+#[derive(Debug, Default, Clone, Copy)]
+pub struct FileBackend;
+
+impl RunnerBackend for FileBackend {
+    fn read(&self) -> Runner { dump::read() }
+
+    fn write(&self, runner: &Runner) { dump::write(runner) }
+
+    fn run(&self, metadata: ProcessMetadata) -> Result<ProcessRunResult, String> { process_run(metadata) }
+
+    fn stop(&self, pid: i64, signal: Signal) -> Result<(), String> { process_stop_with_signal(pid, signal) }
+
+    fn alive(&self, pid: i64) -> bool { is_pid_alive(pid) }
+
+    fn wait(&self, pid: i64, timeout: Duration) -> bool { wait_for_process_termination(pid, timeout) }
+}
+
+/// Real processes like `FileBackend`, kept in a dump at another path than the configured one
+#[derive(Debug, Clone)]
+pub struct DumpFile(pub PathBuf);
+
+impl RunnerBackend for DumpFile {
+    fn read(&self) -> Runner { dump::read_at(&self.0) }
+
+    fn write(&self, runner: &Runner) { dump::write_at(&self.0, runner) }
+
+    fn run(&self, metadata: ProcessMetadata) -> Result<ProcessRunResult, String> { process_run(metadata) }
+
+    fn stop(&self, pid: i64, signal: Signal) -> Result<(), String> { process_stop_with_signal(pid, signal) }
+
+    fn alive(&self, pid: i64) -> bool { is_pid_alive(pid) }
+
+    fn wait(&self, pid: i64, timeout: Duration) -> bool { wait_for_process_termination(pid, timeout) }
+}
+
+/// The backend a `Runner` was made with, shared by its clones
+#[derive(Clone)]
+pub struct Backend(Arc<dyn RunnerBackend>);
+
+impl Backend {
+    pub fn new(backend: Arc<dyn RunnerBackend>) -> Self { Backend(backend) }
+}
+
+impl Default for Backend {
+    fn default() -> Self { Backend(Arc::new(FileBackend)) }
+}
+
+impl Deref for Backend {
+    type Target = dyn RunnerBackend;
+
+    fn deref(&self) -> &Self::Target { &*self.0 }
+}
+
+impl fmt::Debug for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { f.write_str("Backend") }
+}
@@ -0,0 +1,378 @@
+use super::archive::line_time;
+
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeDelta, TimeZone, Utc};
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write},
+};
+
+/// Index files are thinned out to every other checkpoint once they grow past this size
+const MAX_INDEX_BYTES: u64 = 256 * 1024;
+/// Longest encoded checkpoint line, used to read only the tail of an index
+const MAX_LINE_BYTES: u64 = 48;
+
+/// Size of a log file at a point in time, every byte past `offset` was written after `time`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Checkpoint {
+    pub time: DateTime<Utc>,
+    pub offset: u64,
+}
+
+impl Checkpoint {
+    fn encode(&self) -> String {
+        format!("{} {}\n", self.time.timestamp_millis(), self.offset)
+    }
+
+    fn decode(line: &str) -> Option<Self> {
+        let (time, offset) = line.trim().split_once(' ')?;
+
+        Some(Self {
+            time: DateTime::from_timestamp_millis(time.parse().ok()?)?,
+            offset: offset.parse().ok()?,
+        })
+    }
+}
+
+/// Time range for `opm logs --since/--until`, either end may be open
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Window {
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+}
+
+impl Window {
+    pub fn is_empty(&self) -> bool {
+        self.since.is_none() && self.until.is_none()
+    }
+
+    fn contains(&self, time: DateTime<Utc>) -> bool {
+        self.since.is_none_or(|since| time >= since)
+            && self.until.is_none_or(|until| time <= until)
+    }
+
+    /// Human readable range, e.g. `from 2024-01-01 14:30:00 to 2024-01-01 15:00:00`
+    pub fn describe(&self) -> String {
+        let format = |time: DateTime<Utc>| time.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S").to_string();
+
+        match (self.since, self.until) {
+            (Some(since), Some(until)) => format!("from {} to {}", format(since), format(until)),
+            (Some(since), None) => format!("since {}", format(since)),
+            (None, Some(until)) => format!("until {}", format(until)),
+            (None, None) => String::from("all lines"),
+        }
+    }
+}
+
+/// Lines of a log inside a window. Without per-line timestamps the bounds are rounded
+/// out to the surrounding checkpoints, so a few lines outside the window may be included.
+#[derive(Debug, Default)]
+pub struct Slice {
+    pub lines: Vec<String>,
+    pub approximate: bool,
+}
+
+/// Sidecar index of a log file
+pub fn path(log: &str) -> String {
+    format!("{log}.idx")
+}
+
+/// Append a checkpoint if the log grew and the last one is at least `interval` seconds old.
+/// A log that shrank was flushed or replaced, its index starts over.
+pub fn record(log: &str, interval: u64, now: DateTime<Utc>) -> io::Result<()> {
+    let Ok(metadata) = fs::metadata(log) else {
+        return Ok(());
+    };
+
+    let index = path(log);
+    let checkpoint = Checkpoint { time: now, offset: metadata.len() };
+    let interval = TimeDelta::try_seconds(interval.try_into().unwrap_or(i64::MAX)).unwrap_or(TimeDelta::max_value());
+
+    match last(&index)? {
+        Some(last) if checkpoint.offset < last.offset => return fs::write(&index, checkpoint.encode()),
+        Some(last) if checkpoint.offset == last.offset || now - last.time < interval => return Ok(()),
+        _ => {}
+    }
+
+    let mut file = OpenOptions::new().create(true).append(true).open(&index)?;
+    file.write_all(checkpoint.encode().as_bytes())?;
+
+    if file.metadata()?.len() > MAX_INDEX_BYTES {
+        let thinned: String = read(log).iter().step_by(2).map(Checkpoint::encode).collect();
+        fs::write(&index, thinned)?;
+    }
+
+    Ok(())
+}
+
+/// All checkpoints of a log, oldest first
+pub fn read(log: &str) -> Vec<Checkpoint> {
+    match fs::read_to_string(path(log)) {
+        Ok(contents) => contents.lines().filter_map(Checkpoint::decode).collect(),
+        Err(_) => vec![],
+    }
+}
+
+/// Forget the checkpoints of a log, e.g. after it was flushed
+pub fn remove(log: &str) {
+    fs::remove_file(path(log)).ok();
+}
+
+fn last(index: &str) -> io::Result<Option<Checkpoint>> {
+    let Ok(mut file) = File::open(index) else {
+        return Ok(None);
+    };
+
+    let len = file.metadata()?.len();
+    file.seek(SeekFrom::Start(len.saturating_sub(MAX_LINE_BYTES)))?;
+
+    let mut tail = String::new();
+    file.read_to_string(&mut tail)?;
+
+    Ok(tail.lines().last().and_then(Checkpoint::decode))
+}
+
+/// Byte range of a log of `len` bytes that covers the window
+fn bounds(checkpoints: &[Checkpoint], len: u64, window: Window) -> (u64, u64) {
+    // Latest checkpoint taken at or before `since`, everything before it is older
+    let start = window
+        .since
+        .map(|since| checkpoints.partition_point(|checkpoint| checkpoint.time <= since))
+        .and_then(|index| index.checked_sub(1))
+        .map_or(0, |index| checkpoints[index].offset.min(len));
+
+    // Earliest checkpoint taken at or after `until`, everything past it is newer
+    let end = window
+        .until
+        .map(|until| checkpoints.partition_point(|checkpoint| checkpoint.time < until))
+        .and_then(|index| checkpoints.get(index))
+        .map_or(len, |checkpoint| checkpoint.offset.min(len));
+
+    (start, end.max(start))
+}
+
+/// Read the lines of a log inside the window, seeking to the nearest checkpoint instead of
+/// scanning from the start. Lines stamped with a time narrow the range down to the line.
+pub fn select(log: &str, window: Window) -> io::Result<Slice> {
+    let mut file = File::open(log)?;
+    let len = file.metadata()?.len();
+    let (start, end) = bounds(&read(log), len, window);
+
+    // A checkpoint can fall in the middle of a line, which then started before it
+    let mut skip_partial = false;
+    if start > 0 {
+        let mut previous = [0];
+        file.seek(SeekFrom::Start(start - 1))?;
+        file.read_exact(&mut previous)?;
+        skip_partial = previous[0] != b'\n';
+    }
+
+    let mut reader = BufReader::new(file);
+    let mut pos = start;
+    let mut buf = vec![];
+
+    if skip_partial {
+        pos += reader.read_until(b'\n', &mut buf)? as u64;
+    }
+
+    let mut slice = Slice::default();
+    let mut stamped = false;
+    let mut keep = true;
+
+    while pos < end {
+        buf.clear();
+        let read = reader.read_until(b'\n', &mut buf)?;
+        if read == 0 {
+            break;
+        }
+        pos += read as u64;
+
+        let line = String::from_utf8_lossy(&buf).trim_end_matches(['\n', '\r']).to_string();
+
+        // Unstamped lines (stack traces, wrapped output) follow the last stamped line
+        if let Some(time) = line_time(&line) {
+            if window.until.is_some_and(|until| time > until) {
+                break;
+            }
+            stamped = true;
+            keep = window.contains(time);
+        }
+
+        if keep {
+            slice.lines.push(line);
+        }
+    }
+
+    slice.approximate = !stamped && !window.is_empty() && !slice.lines.is_empty();
+    Ok(slice)
+}
+
+/// Parse a `--since`/`--until` value: `HH:MM[:SS]` (today, or yesterday if that is still
+/// ahead), `YYYY-MM-DD [HH:MM[:SS]]`, RFC 3339, or a duration ago such as `30m` or `2h`
+pub fn parse_time(value: &str) -> Result<DateTime<Utc>, String> {
+    parse_time_at(value, Local::now())
+}
+
+fn parse_time_at<Tz: TimeZone>(value: &str, now: DateTime<Tz>) -> Result<DateTime<Utc>, String> {
+    let value = value.trim();
+    let local = |time: NaiveDateTime| {
+        now.timezone()
+            .from_local_datetime(&time)
+            .earliest()
+            .map(|time| time.with_timezone(&Utc))
+            .ok_or_else(|| format!("'{value}' does not exist in the local timezone"))
+    };
+
+    if let Some(unit) = value.chars().last()
+        && let Ok(amount) = value[..value.len() - unit.len_utf8()].parse::<u32>()
+    {
+        let delta = match unit {
+            's' => TimeDelta::try_seconds(amount.into()),
+            'm' => TimeDelta::try_minutes(amount.into()),
+            'h' => TimeDelta::try_hours(amount.into()),
+            'd' => TimeDelta::try_days(amount.into()),
+            _ => None,
+        };
+
+        if let Some(time) = delta.and_then(|delta| now.clone().checked_sub_signed(delta)) {
+            return Ok(time.with_timezone(&Utc));
+        }
+    }
+
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Ok(time.with_timezone(&Utc));
+    }
+
+    if let Some(time) = ["%H:%M:%S", "%H:%M"].iter().find_map(|format| NaiveTime::parse_from_str(value, format).ok()) {
+        let today = now.date_naive();
+        let date = match time > now.time() {
+            true => today.pred_opt().unwrap_or(today),
+            false => today,
+        };
+        return local(date.and_time(time));
+    }
+
+    let formats = ["%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%dT%H:%M"];
+    if let Some(time) = formats.iter().find_map(|format| NaiveDateTime::parse_from_str(value, format).ok()) {
+        return local(time);
+    }
+
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return local(date.and_time(NaiveTime::MIN));
+    }
+
+    Err(format!("invalid time '{value}', expected HH:MM[:SS], YYYY-MM-DD [HH:MM[:SS]], RFC 3339 or a duration like 30m"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn time(value: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(value).unwrap().with_timezone(&Utc)
+    }
+
+    fn temp_log(name: &str, contents: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("opm-checkpoint-{}-{name}.log", std::process::id()));
+        fs::write(&path, contents).unwrap();
+        fs::remove_file(path.with_extension("log.idx")).ok();
+        path
+    }
+
+    fn append(path: &PathBuf, contents: &str) {
+        let mut file = OpenOptions::new().append(true).open(path).unwrap();
+        file.write_all(contents.as_bytes()).unwrap();
+    }
+
+    #[test]
+    fn test_record_respects_interval_and_growth() {
+        let path = temp_log("record", "first\n");
+        let log = path.to_str().unwrap();
+        let start = time("2024-01-01T10:00:00Z");
+
+        record(log, 30, start).unwrap();
+        append(&path, "second\n");
+        record(log, 30, start + TimeDelta::seconds(10)).unwrap();
+        record(log, 30, start + TimeDelta::seconds(40)).unwrap();
+        record(log, 30, start + TimeDelta::seconds(90)).unwrap();
+
+        let expected = vec![Checkpoint { time: start, offset: 6 }, Checkpoint { time: start + TimeDelta::seconds(40), offset: 13 }];
+        assert_eq!(read(log), expected);
+
+        // Flushing the log starts the index over
+        fs::write(&path, "").unwrap();
+        record(log, 30, start + TimeDelta::seconds(100)).unwrap();
+        assert_eq!(read(log), vec![Checkpoint { time: start + TimeDelta::seconds(100), offset: 0 }]);
+
+        remove(log);
+        fs::remove_file(path).ok();
+    }
+
+    #[test]
+    fn test_bounds_round_out_to_checkpoints() {
+        let checkpoints = [
+            Checkpoint { time: time("2024-01-01T10:00:00Z"), offset: 100 },
+            Checkpoint { time: time("2024-01-01T10:00:30Z"), offset: 250 },
+            Checkpoint { time: time("2024-01-01T10:01:00Z"), offset: 400 },
+        ];
+        let window = |since: Option<&str>, until: Option<&str>| Window { since: since.map(time), until: until.map(time) };
+
+        assert_eq!(bounds(&checkpoints, 500, window(Some("2024-01-01T10:00:40Z"), None)), (250, 500));
+        assert_eq!(bounds(&checkpoints, 500, window(None, Some("2024-01-01T10:00:10Z"))), (0, 250));
+        assert_eq!(bounds(&checkpoints, 500, window(Some("2024-01-01T09:00:00Z"), Some("2024-01-01T11:00:00Z"))), (0, 500));
+        assert_eq!(bounds(&checkpoints, 500, window(Some("2024-01-01T10:00:30Z"), Some("2024-01-01T10:00:30Z"))), (250, 250));
+        assert_eq!(bounds(&[], 500, window(Some("2024-01-01T10:00:40Z"), None)), (0, 500));
+    }
+
+    #[test]
+    fn test_select_unstamped_uses_checkpoints() {
+        let path = temp_log("unstamped", "old one\nold two\n");
+        let log = path.to_str().unwrap();
+        let start = time("2024-01-01T10:00:00Z");
+
+        record(log, 30, start).unwrap();
+        append(&path, "new one\nnew two\n");
+        record(log, 30, start + TimeDelta::seconds(60)).unwrap();
+        append(&path, "newest\n");
+
+        let since = select(log, Window { since: Some(start + TimeDelta::seconds(5)), until: None }).unwrap();
+        assert_eq!(since.lines, vec!["new one", "new two", "newest"]);
+        assert!(since.approximate);
+
+        let until = select(log, Window { since: None, until: Some(start + TimeDelta::seconds(5)) }).unwrap();
+        assert_eq!(until.lines, vec!["old one", "old two", "new one", "new two"]);
+
+        remove(log);
+        fs::remove_file(path).ok();
+    }
+
+    #[test]
+    fn test_select_stamped_is_exact() {
+        let contents = "2024-01-01T10:00:00Z boot\n2024-01-01T10:05:00Z request\n  at handler\n2024-01-01T10:10:00Z shutdown\n";
+        let path = temp_log("stamped", contents);
+        let log = path.to_str().unwrap();
+
+        let window = Window { since: Some(time("2024-01-01T10:01:00Z")), until: Some(time("2024-01-01T10:06:00Z")) };
+        let slice = select(log, window).unwrap();
+
+        assert_eq!(slice.lines, vec!["2024-01-01T10:05:00Z request", "  at handler"]);
+        assert!(!slice.approximate);
+
+        fs::remove_file(path).ok();
+    }
+
+    #[test]
+    fn test_parse_time() {
+        let now = time("2024-03-10T12:00:00Z");
+
+        assert_eq!(parse_time_at("30m", now).unwrap(), time("2024-03-10T11:30:00Z"));
+        assert_eq!(parse_time_at("2d", now).unwrap(), time("2024-03-08T12:00:00Z"));
+        assert_eq!(parse_time_at("14:30", now).unwrap(), time("2024-03-09T14:30:00Z"));
+        assert_eq!(parse_time_at("09:15:30", now).unwrap(), time("2024-03-10T09:15:30Z"));
+        assert_eq!(parse_time_at("2024-03-01 08:00", now).unwrap(), time("2024-03-01T08:00:00Z"));
+        assert_eq!(parse_time_at("2024-03-01", now).unwrap(), time("2024-03-01T00:00:00Z"));
+        assert_eq!(parse_time_at("2024-03-01T08:00:00+02:00", now).unwrap(), time("2024-03-01T06:00:00Z"));
+        assert!(parse_time_at("yesterday", now).is_err());
+        assert!(parse_time_at("-5m", now).is_err());
+    }
+}
@@ -0,0 +1,128 @@
+//! How long processes have run, measured on the monotonic clock of the machine.
+//! The wall clock jumps after NTP corrections or a VM resume, `started` is kept for display only.
+
+use chrono::{DateTime, TimeDelta, Utc};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Where Linux keeps an id that changes on every boot, the monotonic clock starts over with it
+const BOOT_ID: &str = "/proc/sys/kernel/random/boot_id";
+
+static BOOT: Lazy<String> = Lazy::new(|| std::fs::read_to_string(BOOT_ID).map(|id| id.trim().to_string()).unwrap_or_default());
+
+/// A moment on the monotonic clock, which only means something during the boot it was taken in
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Mark {
+    /// Empty where the system has no boot id, the mark is then trusted while it is not ahead of the clock
+    #[serde(default)]
+    pub boot: String,
+    /// Milliseconds since boot, sleep and suspend included
+    pub ms: u64,
+}
+
+/// Now on the monotonic clock, None where it cannot be read
+pub fn now() -> Option<Mark> {
+    #[cfg(target_os = "linux")]
+    let clock = libc::CLOCK_BOOTTIME;
+    #[cfg(not(target_os = "linux"))]
+    let clock = libc::CLOCK_MONOTONIC;
+
+    let mut time = libc::timespec { tv_sec: 0, tv_nsec: 0 };
+    if unsafe { libc::clock_gettime(clock, &mut time) } != 0 {
+        return None;
+    }
+
+    Some(Mark {
+        boot: BOOT.clone(),
+        ms: time.tv_sec as u64 * 1000 + time.tv_nsec as u64 / 1_000_000,
+    })
+}
+
+/// Time a process has run, never negative
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Elapsed {
+    pub duration: TimeDelta,
+    /// The wall clock was all there was to go by and it put the start in the future, `duration` is 0 then
+    pub skewed: bool,
+}
+
+impl Elapsed {
+    pub fn millis(&self) -> i64 { self.duration.num_milliseconds() }
+}
+
+/// `5m`, or `0s (clock skew detected)` when the wall clock went back past the start
+impl fmt::Display for Elapsed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.skewed {
+            true => write!(f, "0s (clock skew detected)"),
+            false => write!(f, "{}", crate::helpers::format_seconds(self.duration.num_seconds())),
+        }
+    }
+}
+
+/// Time since a start at `started` on the wall clock and `mark` on the monotonic one.
+/// The mark decides when it was taken in the current boot, otherwise the wall clock does, clamped at zero.
+pub fn elapsed(started: DateTime<Utc>, mark: Option<&Mark>, now: DateTime<Utc>, now_mark: Option<&Mark>) -> Elapsed {
+    if let (Some(mark), Some(current)) = (mark, now_mark)
+        && mark.boot == current.boot
+        && mark.ms <= current.ms
+    {
+        let duration = TimeDelta::milliseconds((current.ms - mark.ms).min(i64::MAX as u64) as i64);
+        return Elapsed { duration, skewed: false };
+    }
+
+    let duration = now - started;
+    match duration < TimeDelta::zero() {
+        true => Elapsed { duration: TimeDelta::zero(), skewed: true },
+        false => Elapsed { duration, skewed: false },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mark(ms: u64) -> Mark { Mark { boot: String::from("3f2a9c1e"), ms } }
+
+    #[test]
+    fn test_monotonic_clock_moves_forward() {
+        let first = now().unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        let second = now().unwrap();
+
+        assert_eq!(first.boot, second.boot);
+        assert!(second.ms >= first.ms + 20);
+    }
+
+    #[test]
+    fn test_wall_clock_jumps_are_ignored_with_a_mark() {
+        let started = Utc::now();
+
+        // The wall clock went back an hour, ten seconds after the start
+        let back = elapsed(started, Some(&mark(5_000)), started - TimeDelta::hours(1), Some(&mark(15_000)));
+        assert_eq!(back, Elapsed { duration: TimeDelta::seconds(10), skewed: false });
+
+        // And forward a day
+        let forward = elapsed(started, Some(&mark(5_000)), started + TimeDelta::days(1), Some(&mark(15_000)));
+        assert_eq!(forward.duration, TimeDelta::seconds(10));
+    }
+
+    #[test]
+    fn test_wall_clock_fallback_is_clamped() {
+        let started = Utc::now();
+
+        // Started before the current boot, or before marks were kept
+        let other_boot = Mark { boot: String::from("other"), ms: 1_000 };
+        let reboot = elapsed(started, Some(&other_boot), started + TimeDelta::minutes(3), Some(&mark(60_000)));
+        assert_eq!(reboot, Elapsed { duration: TimeDelta::minutes(3), skewed: false });
+
+        let back = elapsed(started, None, started - TimeDelta::minutes(5), Some(&mark(60_000)));
+        assert_eq!(back, Elapsed { duration: TimeDelta::zero(), skewed: true });
+        assert_eq!(back.to_string(), "0s (clock skew detected)");
+
+        // A mark ahead of the clock cannot be from this boot either
+        let ahead = elapsed(started, Some(&mark(90_000)), started + TimeDelta::seconds(30), Some(&mark(60_000)));
+        assert_eq!(ahead.to_string(), "30s");
+    }
+}
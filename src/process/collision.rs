@@ -0,0 +1,60 @@
+use super::Process;
+use std::collections::BTreeMap;
+
+/// A process already registered under the name `opm start` or `POST /process/create` was given
+#[derive(Debug, Clone, PartialEq)]
+pub enum Collision {
+    /// Runs the same command, it is started instead of adding a second process with the name
+    Same(usize),
+    /// Runs another command, which is only replaced with `--force-update`
+    Changed { id: usize, stored: String },
+}
+
+/// The process named `name`, and whether it already runs `script` as stored
+pub fn check(list: &BTreeMap<usize, Process>, name: &str, script: &str) -> Option<Collision> {
+    let (id, process) = list.iter().find(|(_, process)| process.name == name)?;
+
+    match process.script == script {
+        true => Some(Collision::Same(*id)),
+        false => Some(Collision::Changed { id: *id, stored: process.script.clone() }),
+    }
+}
+
+/// The stored and the new command line by line, `-` marking the stored one and `+` the new one
+pub fn diff(stored: &str, script: &str) -> String {
+    let removed = stored.lines().map(|line| format!("- {line}"));
+    let added = script.lines().map(|line| format!("+ {line}"));
+    removed.chain(added).collect::<Vec<_>>().join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+        use std::path::PathBuf;
+
+    fn list(processes: &[(&str, &str)]) -> BTreeMap<usize, Process> {
+        processes
+            .iter()
+            .enumerate()
+            .map(|(id, (name, script))| {
+                let process = Process { id, name: name.to_string(), script: script.to_string(), path: PathBuf::from("/srv"), ..Default::default() };
+                (id, process)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_check() {
+        let list = list(&[("web", "node web.js"), ("app", "node v1/index.js")]);
+
+        assert_eq!(check(&list, "app", "node v1/index.js"), Some(Collision::Same(1)));
+        assert_eq!(check(&list, "app", "node v2/index.js"), Some(Collision::Changed { id: 1, stored: String::from("node v1/index.js") }));
+        assert_eq!(check(&list, "worker", "node v2/index.js"), None);
+    }
+
+    #[test]
+    fn test_diff() {
+        assert_eq!(diff("node v1/index.js", "node v2/index.js"), "- node v1/index.js\n+ node v2/index.js");
+        assert_eq!(diff("cd /srv\nnode v1.js", "node v2.js"), "- cd /srv\n- node v1.js\n+ node v2.js");
+    }
+}
@@ -0,0 +1,275 @@
+use super::Process;
+use crate::config::structs::Cores;
+use crate::helpers;
+
+use chrono::{DateTime, Utc};
+use global_placeholders::global;
+use macros_rs::{ternary, then};
+use serde::{Deserialize, Serialize};
+
+use std::{
+    fs::{self, File},
+    io::{self, Read},
+    path::{Path, PathBuf},
+};
+
+/// Where the kernel writes core files, see core(5)
+const CORE_PATTERN: &str = "/proc/sys/kernel/core_pattern";
+
+/// A core file left by a crash of the process
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Core {
+    pub path: PathBuf,
+    pub bytes: u64,
+    pub time: DateTime<Utc>,
+}
+
+impl Core {
+    fn read(path: &Path) -> Option<Core> {
+        let meta = fs::metadata(path).ok()?;
+        let time = meta.modified().ok()?.into();
+        Some(Core { path: path.to_path_buf(), bytes: meta.len(), time })
+    }
+
+    /// Human readable form, e.g. `~/.opm/cores/api/core.1234 (12.5mb)`
+    pub fn describe(&self) -> String { format!("{} ({})", self.path.display(), helpers::format_memory(self.bytes)) }
+}
+
+fn base() -> PathBuf { PathBuf::from(global!("opm.base")).join("cores") }
+
+/// Directory the cores of a process are collected into, `~/.opm/cores/<name>`
+pub fn dir(name: &str) -> PathBuf { base().join(name.replace(['/', ' '], "_")) }
+
+/// Whether the file starts with an ELF header of type ET_CORE, so sources named `core.js` are never taken for one
+pub fn is_core(path: &Path) -> bool {
+    let mut header = [0u8; 18];
+    let read = File::open(path).and_then(|mut file| file.read_exact(&mut header));
+
+    // e_type follows the 16 byte e_ident, in the byte order e_ident[5] names
+    read.is_ok()
+        && header.starts_with(b"\x7fELF")
+        && match header[5] {
+            2 => header[16..18] == [0, 4],
+            _ => header[16..18] == [4, 0],
+        }
+}
+
+/// Directories a crash of the process may have left a core in and the file name prefix it would have.
+/// A piped pattern hands cores to a system service, only the working directory is then worth checking.
+pub fn locations(pattern: &str, cwd: &Path) -> Vec<(PathBuf, String)> {
+    let pattern = pattern.trim();
+    let fallback = (cwd.to_path_buf(), String::from("core"));
+
+    if pattern.is_empty() || pattern.starts_with('|') {
+        return vec![fallback];
+    }
+
+    let path = Path::new(pattern);
+    let dir = match path.parent() {
+        Some(parent) if path.is_absolute() => parent.to_path_buf(),
+        Some(parent) => cwd.join(parent),
+        None => cwd.to_path_buf(),
+    };
+
+    let name = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+    let prefix = name.split('%').next().unwrap_or_default().to_string();
+
+    let mut locations = vec![(dir, ternary!(prefix.is_empty(), String::from("core"), prefix))];
+    then!(!locations.contains(&fallback), locations.push(fallback));
+    locations
+}
+
+fn core_pattern() -> String { fs::read_to_string(CORE_PATTERN).unwrap_or_default() }
+
+/// Core files written in the given locations since `since`
+pub fn find(locations: &[(PathBuf, String)], since: DateTime<Utc>) -> Vec<PathBuf> {
+    let mut found = vec![];
+
+    for (dir, prefix) in locations {
+        let Ok(entries) = fs::read_dir(dir) else { continue };
+
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let named = entry.file_name().to_string_lossy().starts_with(prefix.as_str());
+            let recent = entry.metadata().is_ok_and(|meta| meta.is_file() && meta.modified().is_ok_and(|time| DateTime::<Utc>::from(time) >= since));
+
+            then!(named && recent && is_core(&path), found.push(path));
+        }
+    }
+
+    found
+}
+
+/// Move a core into the collection directory, left where it is when that is not possible
+fn take(path: PathBuf, dir: &Path) -> PathBuf {
+    let Some(name) = path.file_name() else { return path };
+    let target = dir.join(format!("{}-{}", Utc::now().format("%Y%m%dT%H%M%S"), name.to_string_lossy()));
+
+    if fs::create_dir_all(dir).is_err() {
+        return path;
+    }
+
+    // Across filesystems a rename fails, the core is copied instead
+    match fs::rename(&path, &target).or_else(|_| fs::copy(&path, &target).and_then(|_| fs::remove_file(&path))) {
+        Ok(()) => target,
+        Err(err) => {
+            log::warn!("Cannot collect core {}: {err}", path.display());
+            let _ = fs::remove_file(&target);
+            path
+        }
+    }
+}
+
+/// Cores the last crash of the process left since it was started, moved to `dir` and pruned to the retention caps
+pub fn collect(process: &Process, retention: &Cores) -> Vec<Core> {
+    let found = find(&locations(&core_pattern(), &process.path), process.started);
+    if found.is_empty() {
+        return vec![];
+    }
+
+    let dir = dir(&process.name);
+    let collected: Vec<PathBuf> = found.into_iter().map(|path| take(path, &dir)).collect();
+    prune(&base(), retention);
+
+    collected.iter().filter_map(|path| Core::read(path)).collect()
+}
+
+/// Cores kept in a collection directory, newest first
+fn listed(dir: &Path) -> Vec<Core> {
+    let Ok(entries) = fs::read_dir(dir) else { return vec![] };
+    let mut cores: Vec<Core> = entries.flatten().filter_map(|entry| Core::read(&entry.path())).collect();
+    cores.sort_by_key(|core| std::cmp::Reverse(core.time));
+    cores
+}
+
+/// Keep at most `max_count` cores per process and `max_size` in total, deleting the oldest first
+pub fn prune(base: &Path, retention: &Cores) -> Vec<Core> {
+    let max_bytes = retention.max_size.as_deref().map(helpers::parse_memory).and_then(Result::ok);
+    let Ok(entries) = fs::read_dir(base) else { return vec![] };
+
+    let mut kept = vec![];
+    let mut deleted = vec![];
+
+    for entry in entries.flatten() {
+        let mut cores = listed(&entry.path());
+        let excess = cores.split_off(cores.len().min(retention.max_count));
+        kept.extend(cores);
+        deleted.extend(excess);
+    }
+
+    if let Some(max_bytes) = max_bytes {
+        kept.sort_by_key(|core| std::cmp::Reverse(core.time));
+        let mut total = 0;
+
+        for core in kept {
+            total += core.bytes;
+            then!(total > max_bytes, deleted.push(core));
+        }
+    }
+
+    deleted.retain(|core| match fs::remove_file(&core.path) {
+        Ok(()) => true,
+        Err(err) => {
+            log::warn!("Cannot delete core {}: {err}", core.path.display());
+            false
+        }
+    });
+
+    deleted
+}
+
+/// Collected cores of the process and the ones its last crash left elsewhere, newest first
+pub fn available(process: &Process) -> Vec<Core> {
+    let mut cores = listed(&dir(&process.name));
+
+    for core in &process.crash.cores {
+        if !cores.iter().any(|kept| kept.path == core.path) && let Some(core) = Core::read(&core.path) {
+            cores.push(core);
+        }
+    }
+
+    cores.sort_by_key(|core| std::cmp::Reverse(core.time));
+    cores
+}
+
+/// Delete the available cores of the process
+pub fn clean(process: &Process) -> io::Result<Vec<Core>> {
+    let cores = available(process);
+    for core in &cores {
+        fs::remove_file(&core.path)?;
+    }
+
+    Ok(cores)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use macros_rs::string;
+
+    fn elf(kind: u8) -> Vec<u8> {
+        let mut header = b"\x7fELF\x02\x01\x01".to_vec();
+        header.resize(16, 0);
+        header.extend([kind, 0, 0x3e, 0]);
+        header
+    }
+
+    fn temp(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("opm-cores-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_locations() {
+        let cwd = Path::new("/srv/api");
+        assert_eq!(locations("core\n", cwd), vec![(cwd.to_path_buf(), string!("core"))]);
+        assert_eq!(locations("|/usr/lib/systemd/systemd-coredump %P", cwd), vec![(cwd.to_path_buf(), string!("core"))]);
+        assert_eq!(
+            locations("/var/crash/core.%e.%p", cwd),
+            vec![(PathBuf::from("/var/crash"), string!("core.")), (cwd.to_path_buf(), string!("core"))]
+        );
+        assert_eq!(locations("dumps/%e.core", cwd)[0], (cwd.join("dumps"), string!("core")));
+    }
+
+    #[test]
+    fn test_find_only_cores() {
+        let dir = temp("find");
+        fs::write(dir.join("core.1234"), elf(4)).unwrap();
+        fs::write(dir.join("core.js"), "module.exports = {}").unwrap();
+        fs::write(dir.join("core.so"), elf(3)).unwrap();
+        fs::write(dir.join("other.1234"), elf(4)).unwrap();
+
+        let locations = vec![(dir.clone(), string!("core"))];
+        assert_eq!(find(&locations, Utc::now() - chrono::TimeDelta::minutes(1)), vec![dir.join("core.1234")]);
+        // Cores from before the last start belong to an earlier crash
+        assert!(find(&locations, Utc::now() + chrono::TimeDelta::minutes(1)).is_empty());
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_prune() {
+        let base = temp("prune");
+        for (name, count) in [("api", 3), ("worker", 1)] {
+            fs::create_dir_all(base.join(name)).unwrap();
+            for index in 0..count {
+                let path = base.join(name).join(format!("core.{index}"));
+                fs::write(&path, vec![0; 100]).unwrap();
+                let time = std::time::SystemTime::now() - std::time::Duration::from_secs(60 * (10 - index));
+                File::options().write(true).open(&path).unwrap().set_modified(time).unwrap();
+            }
+        }
+
+        let retention = Cores { max_count: 2, max_size: Some(string!("250b")) };
+        let mut deleted: Vec<String> = prune(&base, &retention).iter().map(|core| core.path.strip_prefix(&base).unwrap().display().to_string()).collect();
+        deleted.sort();
+
+        // api keeps its two newest, then the oldest left overall goes to fit 250 bytes
+        assert_eq!(deleted, vec!["api/core.0", "worker/core.0"]);
+        assert_eq!(listed(&base.join("api")).len(), 2);
+
+        fs::remove_dir_all(&base).ok();
+    }
+}
@@ -0,0 +1,122 @@
+//! How often the daemon does its costly work, counted since it started or since the last reset.
+//! Plain atomics, so counting stays cheap enough to leave on in production.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    sync::{
+        RwLock,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+/// Something the daemon does often enough for its cost to add up
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Counter {
+    DumpRead,
+    DumpWrite,
+    ChildrenScan,
+    ProcessTable,
+    CpuSample,
+    Restart,
+    WatchHash,
+}
+
+impl Counter {
+    pub const ALL: [Counter; 7] = [
+        Counter::DumpRead,
+        Counter::DumpWrite,
+        Counter::ChildrenScan,
+        Counter::ProcessTable,
+        Counter::CpuSample,
+        Counter::Restart,
+        Counter::WatchHash,
+    ];
+
+    /// Key in `opm daemon stats --format json`, and the Prometheus counter as `opm_daemon_{name}_total`
+    pub fn name(self) -> &'static str {
+        match self {
+            Counter::DumpRead => "dump_reads",
+            Counter::DumpWrite => "dump_writes",
+            Counter::ChildrenScan => "children_scans",
+            Counter::ProcessTable => "process_table_scans",
+            Counter::CpuSample => "cpu_samples",
+            Counter::Restart => "restarts",
+            Counter::WatchHash => "watch_hashes",
+        }
+    }
+
+    pub fn help(self) -> &'static str {
+        match self {
+            Counter::DumpRead => "Times the process dump was read from disk",
+            Counter::DumpWrite => "Times the process dump was written to disk",
+            Counter::ChildrenScan => "Times /proc was walked for the children of a process",
+            Counter::ProcessTable => "Times the whole process table was enumerated",
+            Counter::CpuSample => "CPU usage samples taken",
+            Counter::Restart => "Process restarts performed",
+            Counter::WatchHash => "Hashes computed of watched paths",
+        }
+    }
+}
+
+static COUNTS: [AtomicU64; Counter::ALL.len()] = [const { AtomicU64::new(0) }; Counter::ALL.len()];
+
+/// When counting started over, None until the first reset so the process start time is used
+static SINCE: RwLock<Option<DateTime<Utc>>> = RwLock::new(None);
+
+/// What the counters held at one moment
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Snapshot {
+    pub since: DateTime<Utc>,
+    pub counts: BTreeMap<String, u64>,
+}
+
+impl Snapshot {
+    pub fn get(&self, counter: Counter) -> u64 { self.counts.get(counter.name()).copied().unwrap_or(0) }
+}
+
+pub fn add(counter: Counter) { COUNTS[counter as usize].fetch_add(1, Ordering::Relaxed); }
+
+/// The counts so far, `started` being when this process began counting
+pub fn snapshot(started: DateTime<Utc>) -> Snapshot {
+    let since = SINCE.read().map(|since| *since).unwrap_or_default().unwrap_or(started);
+    let counts = Counter::ALL.iter().map(|&counter| (counter.name().to_string(), COUNTS[counter as usize].load(Ordering::Relaxed))).collect();
+    Snapshot { since, counts }
+}
+
+/// Start every count over from 0, returning what they held
+pub fn reset(started: DateTime<Utc>) -> Snapshot {
+    let mut since = SINCE.write().unwrap_or_else(|err| err.into_inner());
+    let counts = Counter::ALL.iter().map(|&counter| (counter.name().to_string(), COUNTS[counter as usize].swap(0, Ordering::Relaxed))).collect();
+    let previous = Snapshot { since: since.unwrap_or(started), counts };
+    *since = Some(Utc::now());
+    previous
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counts_and_reset() {
+        let started = Utc::now();
+        // Other tests count too, only what this one adds is certain
+        let before = snapshot(started).get(Counter::WatchHash);
+        add(Counter::WatchHash);
+        add(Counter::WatchHash);
+        assert!(snapshot(started).get(Counter::WatchHash) >= before + 2);
+
+        let previous = reset(started);
+        assert!(previous.get(Counter::WatchHash) >= before + 2);
+        assert_eq!(previous.since, started);
+        assert!(snapshot(started).since >= started);
+    }
+
+    #[test]
+    fn test_names() {
+        let names: std::collections::HashSet<_> = Counter::ALL.iter().map(|counter| counter.name()).collect();
+        assert_eq!(names.len(), Counter::ALL.len());
+        assert_eq!(Counter::ALL.iter().position(|&counter| counter == Counter::WatchHash), Some(Counter::WatchHash as usize));
+    }
+}
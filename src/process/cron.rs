@@ -0,0 +1,242 @@
+//! `--cron-restart`, a restart at the times of a cron expression, e.g. `0 3 * * *` for every night at 3.
+//! Times are in the local time of the daemon like crontab's, the daemon restarts a running process once per time.
+
+use chrono::{DateTime, Datelike, Local, NaiveDate, NaiveDateTime, TimeDelta, TimeZone, Timelike, Utc};
+use macros_rs::ternary;
+use serde::{Deserialize, Serialize};
+use std::{fmt, str::FromStr};
+
+/// Minutes searched for the next time, an expression like `0 0 30 2 *` never matches
+const HORIZON: i64 = 60 * 24 * 366 * 5;
+
+const MONTHS: [&str; 12] = ["jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec"];
+const WEEKDAYS: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+/// The values one field of the expression matches, bit `n` for value `n`
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Field {
+    bits: u64,
+    /// Written as `*`, which matters for the days, see `Schedule::day_matches`
+    any: bool,
+}
+
+impl Field {
+    fn has(&self, value: u32) -> bool { self.bits & (1 << value) != 0 }
+
+    /// One field of `min-max`, a list of `*`, `N`, `N-M` with an optional `/STEP`, or of names from `names`
+    fn parse(value: &str, kind: &str, min: u32, max: u32, names: &[&str]) -> Result<Self, String> {
+        let invalid = |part: &str| format!("Invalid {kind} '{part}' in cron expression, expected {min}-{max}");
+        let number = |part: &str| -> Result<u32, String> {
+            let lower = part.to_ascii_lowercase();
+            let number = match names.iter().position(|name| *name == lower) {
+                Some(index) => index as u32 + min,
+                None => part.parse().map_err(|_| invalid(part))?,
+            };
+            match (min..=max).contains(&number) {
+                true => Ok(number),
+                false => Err(invalid(part)),
+            }
+        };
+
+        let mut bits = 0;
+        for part in value.split(',') {
+            let (range, step) = match part.split_once('/') {
+                Some((range, step)) => (range, step.parse::<u32>().ok().filter(|step| *step > 0).ok_or_else(|| invalid(part))?),
+                None => (part, 1),
+            };
+
+            let (start, end) = match range.split_once('-') {
+                _ if range == "*" => (min, max),
+                Some((start, end)) => (number(start)?, number(end)?),
+                // `5/15` runs from 5 to the end of the field
+                None if part.contains('/') => (number(range)?, max),
+                None => (number(range)?, number(range)?),
+            };
+
+            if start > end {
+                return Err(invalid(part));
+            }
+            bits |= (start..=end).step_by(step as usize).fold(0, |bits, value| bits | 1 << value);
+        }
+
+        Ok(Field { bits, any: value == "*" })
+    }
+}
+
+/// A 5 field cron expression (minute, hour, day of month, month, day of week) or one of `@hourly`, `@daily`,
+/// `@weekly`, `@monthly` and `@yearly`, stored as written
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Schedule {
+    source: String,
+    minutes: Field,
+    hours: Field,
+    days: Field,
+    months: Field,
+    weekdays: Field,
+}
+
+impl Schedule {
+    /// When both days are restricted either one matching is enough, as in crontab
+    fn day_matches(&self, time: &NaiveDateTime) -> bool {
+        let day = self.days.has(time.day());
+        let weekday = self.weekdays.has(time.weekday().num_days_from_sunday());
+
+        match (self.days.any, self.weekdays.any) {
+            (false, false) => day || weekday,
+            _ => day && weekday,
+        }
+    }
+
+    /// The first matching minute after `after`, in the same local time
+    fn next_local(&self, after: NaiveDateTime) -> Option<NaiveDateTime> {
+        let mut time = after.with_second(0)?.with_nanosecond(0)? + TimeDelta::minutes(1);
+        let limit = time + TimeDelta::minutes(HORIZON);
+
+        // Whole months, days and hours are skipped at once, so a yearly time is found in a few hundred steps
+        while time < limit {
+            if !self.months.has(time.month()) {
+                let (year, month) = ternary!(time.month() == 12, (time.year() + 1, 1), (time.year(), time.month() + 1));
+                time = NaiveDate::from_ymd_opt(year, month, 1)?.and_hms_opt(0, 0, 0)?;
+            } else if !self.day_matches(&time) {
+                time = time.date().succ_opt()?.and_hms_opt(0, 0, 0)?;
+            } else if !self.hours.has(time.hour()) {
+                time = time.with_minute(0)? + TimeDelta::hours(1);
+            } else if !self.minutes.has(time.minute()) {
+                time += TimeDelta::minutes(1);
+            } else {
+                return Some(time);
+            }
+        }
+
+        None
+    }
+
+    /// The next time after `after` the schedule is due. A time skipped by a daylight saving change is left out,
+    /// one that happens twice is due the first time.
+    pub fn next(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let mut time = after.with_timezone(&Local).naive_local();
+
+        loop {
+            time = self.next_local(time)?;
+            if let Some(local) = Local.from_local_datetime(&time).earliest() {
+                return Some(local.with_timezone(&Utc));
+            }
+        }
+    }
+
+    /// The time of the schedule that passed since `since`, if one did by `now`
+    pub fn due(&self, since: DateTime<Utc>, now: DateTime<Utc>) -> Option<DateTime<Utc>> { self.next(since).filter(|time| *time <= now) }
+}
+
+impl FromStr for Schedule {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let expanded = match value.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            name if name.starts_with('@') => return Err(format!("Unknown cron macro '{name}', expected @hourly, @daily, @weekly, @monthly or @yearly")),
+            value => value,
+        };
+
+        let fields: Vec<&str> = expanded.split_whitespace().collect();
+        let [minutes, hours, days, months, weekdays] = fields[..] else {
+            return Err(format!("Invalid cron expression '{value}', expected 5 fields (minute hour day month weekday), e.g. \"0 3 * * *\""));
+        };
+
+        let mut weekdays = Field::parse(weekdays, "day of week", 0, 7, &WEEKDAYS)?;
+        // Sunday is both 0 and 7
+        if weekdays.has(7) {
+            weekdays.bits = (weekdays.bits | 1) & !(1 << 7);
+        }
+
+        Ok(Schedule {
+            source: value.trim().to_string(),
+            minutes: Field::parse(minutes, "minute", 0, 59, &[])?,
+            hours: Field::parse(hours, "hour", 0, 23, &[])?,
+            days: Field::parse(days, "day of month", 1, 31, &[])?,
+            months: Field::parse(months, "month", 1, 12, &MONTHS)?,
+            weekdays,
+        })
+    }
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { write!(f, "{}", self.source) }
+}
+
+impl TryFrom<String> for Schedule {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> { value.parse() }
+}
+
+impl From<Schedule> for String {
+    fn from(schedule: Schedule) -> Self { schedule.source }
+}
+
+/// Clap parser for `--cron-restart "0 3 * * *"`
+pub fn parse(value: &str) -> Result<Schedule, String> { value.parse() }
+
+/// The next restart as shown by `opm details`
+pub fn describe(schedule: &Schedule, now: DateTime<Utc>) -> String {
+    match schedule.next(now) {
+        Some(next) => format!("{schedule} (next {})", next.with_timezone(&Local).format("%Y-%m-%d %H:%M %Z")),
+        None => format!("{schedule} (never due)"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(value: &str) -> NaiveDateTime { NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M").unwrap() }
+
+    fn next(expression: &str, after: &str) -> Option<NaiveDateTime> { expression.parse::<Schedule>().unwrap().next_local(at(after)) }
+
+    #[test]
+    fn test_parse() {
+        assert!("0 3 * * *".parse::<Schedule>().is_ok());
+        assert!("*/15 9-17 * jan-jun mon-fri".parse::<Schedule>().is_ok());
+        assert_eq!("@daily".parse::<Schedule>().unwrap().to_string(), "@daily");
+
+        assert!("0 3 * *".parse::<Schedule>().unwrap_err().contains("expected 5 fields"));
+        assert!("60 3 * * *".parse::<Schedule>().unwrap_err().contains("Invalid minute '60'"));
+        assert!("0 3 * * 8".parse::<Schedule>().is_err());
+        assert!("*/0 * * * *".parse::<Schedule>().is_err());
+        assert!("0 5-3 * * *".parse::<Schedule>().is_err());
+        assert!("@reboot".parse::<Schedule>().unwrap_err().contains("Unknown cron macro"));
+    }
+
+    #[test]
+    fn test_next() {
+        assert_eq!(next("0 3 * * *", "2026-10-16 02:59"), Some(at("2026-10-16 03:00")));
+        assert_eq!(next("0 3 * * *", "2026-10-16 03:00"), Some(at("2026-10-17 03:00")));
+        assert_eq!(next("*/20 * * * *", "2026-10-16 10:41"), Some(at("2026-10-16 11:00")));
+        assert_eq!(next("30 4 1 1 *", "2026-10-16 00:00"), Some(at("2027-01-01 04:30")));
+
+        // 2026-10-16 is a friday, 7 is sunday like 0
+        assert_eq!(next("0 0 * * 7", "2026-10-16 12:00"), Some(at("2026-10-18 00:00")));
+        // Both days restricted, either one is enough
+        assert_eq!(next("0 0 20 * mon", "2026-10-16 12:00"), Some(at("2026-10-19 00:00")));
+
+        assert_eq!(next("0 0 30 2 *", "2026-10-16 12:00"), None);
+    }
+
+    #[test]
+    fn test_due_once() {
+        // Every quarter hour, the same times in any time zone
+        let schedule: Schedule = "*/15 * * * *".parse().unwrap();
+        let since = Utc.with_ymd_and_hms(2026, 10, 16, 9, 30, 0).unwrap();
+        let minutes = |minutes: i64| since + TimeDelta::minutes(minutes);
+
+        assert_eq!(schedule.due(since, minutes(10)), None);
+        assert_eq!(schedule.due(since, minutes(20)), Some(minutes(15)));
+        assert_eq!(schedule.due(minutes(15), minutes(20)), None);
+        assert_eq!(schedule.due(minutes(15), minutes(30)), Some(minutes(30)));
+    }
+}
@@ -0,0 +1,44 @@
+//! Free text kept with a process, e.g. what it does and who owns it. Shown by `opm details` and the
+//! WebUI, matched by `/search`, left out of `opm list` so the table stays compact.
+
+pub const MAX_BYTES: usize = 1024;
+
+/// Check a description given by a user, it is stored verbatim
+pub fn parse(value: &str) -> Result<String, String> {
+    if value.trim().is_empty() {
+        return Err(String::from("a description cannot be empty"));
+    }
+
+    if value.len() > MAX_BYTES {
+        return Err(format!("a description can be at most {MAX_BYTES} bytes, this one is {} bytes", value.len()));
+    }
+
+    Ok(value.to_string())
+}
+
+/// Whether `query`, already lowercased, is part of the description
+pub fn matches(description: &Option<String>, query: &str) -> bool { description.as_ref().is_some_and(|description| description.to_lowercase().contains(query)) }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let description = "legacy billing worker, owned by @dana\ndecommission Q3 ";
+        assert_eq!(parse(description).unwrap(), description);
+
+        assert!(parse("  \n").is_err());
+        assert!(parse(&"x".repeat(MAX_BYTES)).is_ok());
+        assert!(parse(&"é".repeat(MAX_BYTES / 2 + 1)).unwrap_err().contains("1026 bytes"));
+    }
+
+    #[test]
+    fn test_matches() {
+        let description = Some(String::from("Legacy billing worker\nowned by @dana"));
+        assert!(matches(&description, "billing"));
+        assert!(matches(&description, "@dana"));
+        assert!(!matches(&description, "search"));
+        assert!(!matches(&None, "billing"));
+    }
+}
@@ -0,0 +1,246 @@
+//! Content addressed storage for the large values of the dump, next to it in `blobs/`.
+//! A value past `THRESHOLD` is written once as `blobs/<hash>`, the dump keeps `\0blob:<hash>` in its place.
+//! Names cannot hold a NUL byte, so a reference is never mistaken for a value set by a user.
+
+use super::replace;
+use crate::process::{Process, Runner};
+
+use std::{
+    collections::BTreeSet,
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+/// Values of this many bytes or more are stored as blobs
+pub const THRESHOLD: usize = 4096;
+
+const PREFIX: &str = "\0blob:";
+
+/// Age a blob nobody refers to is deleted after, a writer may have stored it for a dump not saved yet
+pub const SWEEP_GRACE: Duration = Duration::from_secs(3600);
+
+/// A blob deleted by `Store::sweep`
+#[derive(Debug, PartialEq)]
+pub struct Swept {
+    pub path: PathBuf,
+    pub bytes: u64,
+}
+
+pub struct Store {
+    dir: PathBuf,
+}
+
+/// Values of a process that may be stored as blobs
+fn values(process: &mut Process) -> impl Iterator<Item = &mut String> { process.env.values_mut() }
+
+/// Hash a value is stored under when it is a reference
+pub fn reference(value: &str) -> Option<&str> { value.strip_prefix(PREFIX) }
+
+/// Hashes of the blobs `runner` refers to
+pub fn references(runner: &Runner) -> BTreeSet<String> {
+    runner.list.values().flat_map(|process| process.env.values()).filter_map(|value| reference(value)).map(str::to_string).collect()
+}
+
+fn is_hash(name: &str) -> bool { name.len() == 64 && name.bytes().all(|byte| byte.is_ascii_hexdigit()) }
+
+impl Store {
+    /// The store next to a dump, `~/.opm/blobs` for the default one
+    pub fn beside(dump: &Path) -> Store {
+        let dir = dump.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        Store { dir: dir.join("blobs") }
+    }
+
+    pub fn dir(&self) -> &Path { &self.dir }
+
+    fn path(&self, hash: &str) -> PathBuf { self.dir.join(hash) }
+
+    /// Write a value unless its blob already exists, then only its time is refreshed for `sweep`
+    fn store(&self, value: &str) -> io::Result<String> {
+        let hash = blake3::hash(value.as_bytes()).to_hex().to_string();
+        let path = self.path(&hash);
+
+        match File::options().write(true).open(&path) {
+            Ok(file) => file.set_modified(SystemTime::now())?,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                fs::create_dir_all(&self.dir)?;
+                replace(&path, value.as_bytes())?;
+            }
+            Err(err) => return Err(err),
+        }
+
+        Ok(hash)
+    }
+
+    /// Replace the large values of `runner` with references, returning how many were.
+    /// A value is only replaced once its blob is written, so an error leaves the rest inline.
+    pub fn offload(&self, runner: &mut Runner) -> io::Result<usize> {
+        let mut offloaded = 0;
+
+        for process in runner.list.values_mut() {
+            for value in values(process).filter(|value| value.len() >= THRESHOLD && reference(value).is_none()) {
+                *value = format!("{PREFIX}{}", self.store(value)?);
+                offloaded += 1;
+            }
+        }
+
+        Ok(offloaded)
+    }
+
+    /// Put the stored values back in place of their references, returning the hashes of missing blobs.
+    /// A missing value is left empty rather than failing the whole dump.
+    pub fn materialize(&self, runner: &mut Runner) -> Vec<String> {
+        let mut missing = vec![];
+
+        for process in runner.list.values_mut() {
+            for value in values(process) {
+                let Some(hash) = reference(value).map(str::to_string) else { continue };
+
+                *value = fs::read_to_string(self.path(&hash)).unwrap_or_else(|_| {
+                    missing.push(hash);
+                    String::new()
+                });
+            }
+        }
+
+        missing
+    }
+
+    /// Delete the blobs not in `live` that were last stored `SWEEP_GRACE` before `now`
+    pub fn sweep(&self, live: &BTreeSet<String>, now: SystemTime) -> io::Result<Vec<Swept>> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(err) => return Err(err),
+        };
+        let mut swept = vec![];
+
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let Ok(metadata) = entry.metadata() else { continue };
+            let age = metadata.modified().ok().and_then(|modified| now.duration_since(modified).ok()).unwrap_or_default();
+
+            if is_hash(&name) && !live.contains(&name) && age >= SWEEP_GRACE {
+                fs::remove_file(entry.path())?;
+                swept.push(Swept { path: entry.path(), bytes: metadata.len() });
+            }
+        }
+
+        Ok(swept)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::structs::DumpFormat,
+        process::{StartOptions, dump, id::Id, new_process},
+    };
+    use macros_rs::then;
+    use std::{collections::BTreeMap, time::Instant};
+
+    fn temp(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("opm-blobs-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// `count` processes sharing two certificates of `size` bytes, with a small variable each
+    fn runner(count: usize, size: usize) -> Runner {
+        let certs = ["a", "b"].map(|line| format!("-----BEGIN CERTIFICATE-----\n{}\n-----END CERTIFICATE-----", line.repeat(size)));
+        let mut list = BTreeMap::new();
+
+        for id in 0..count {
+            let mut process = new_process(id, &format!("api-{id}"), "node index.js", PathBuf::from("/srv"), &StartOptions::default());
+            process.env = BTreeMap::from([(String::from("PORT"), format!("{}", 3000 + id)), (String::from("TLS_CERT"), certs[id % 2].clone())]);
+            list.insert(id, process);
+        }
+
+        Runner {
+            id: Id::new(count),
+            list,
+            remote: None,
+            host: None,
+            backend: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let dir = temp("round-trip");
+        let store = Store::beside(&dir.join("process.dump"));
+        let original = runner(6, THRESHOLD);
+
+        for format in [DumpFormat::Ron, DumpFormat::Json] {
+            let mut offloaded = original.clone();
+            assert_eq!(store.offload(&mut offloaded).unwrap(), 6);
+            assert_eq!(references(&offloaded).len(), 2);
+
+            let encoded = dump::encode(&offloaded, format).unwrap();
+            assert!(!encoded.contains("CERTIFICATE") && encoded.contains("3005"));
+
+            let mut decoded = dump::decode(encoded.as_bytes()).unwrap();
+            assert!(store.materialize(&mut decoded).is_empty());
+            assert_eq!(dump::encode(&decoded, format).unwrap(), dump::encode(&original, format).unwrap());
+        }
+
+        // Two certificates for six processes, the small values stay inline
+        assert_eq!(fs::read_dir(store.dir()).unwrap().count(), 2);
+        assert_eq!(original.list[&0].env["PORT"], "3000");
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_missing_blob_and_sweep() {
+        let dir = temp("sweep");
+        let store = Store::beside(&dir.join("process.dump"));
+        let mut runner = runner(2, THRESHOLD);
+        store.offload(&mut runner).unwrap();
+
+        let refs: Vec<String> = references(&runner).into_iter().collect();
+        fs::write(store.dir().join(".stray.tmp.1"), "").unwrap();
+
+        // Only the unreferenced blob past the grace period goes
+        let live = BTreeSet::from([refs[0].clone()]);
+        assert!(store.sweep(&live, SystemTime::now()).unwrap().is_empty());
+        let swept = store.sweep(&live, SystemTime::now() + SWEEP_GRACE).unwrap();
+        assert_eq!(swept.iter().map(|blob| blob.path.clone()).collect::<Vec<_>>(), vec![store.dir().join(&refs[1])]);
+        assert!(swept[0].bytes >= THRESHOLD as u64);
+
+        let missing = store.materialize(&mut runner);
+        assert_eq!(missing, vec![refs[1].clone()]);
+        assert!(runner.list.values().any(|process| process.env["TLS_CERT"].is_empty()));
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_offloaded_saves_are_faster() {
+        let dir = temp("timing");
+        let store = Store::beside(&dir.join("process.dump"));
+        let runner = runner(40, 64 * 1024);
+
+        // Best of a few saves each, the first offload writes the two blobs
+        let time = |offload: bool| {
+            (0..5)
+                .map(|_| {
+                    let started = Instant::now();
+                    let mut saved = runner.clone();
+                    then!(offload, store.offload(&mut saved).unwrap());
+                    replace(&dir.join("process.dump"), dump::encode(&saved, DumpFormat::Ron).unwrap().as_bytes()).unwrap();
+                    started.elapsed()
+                })
+                .min()
+                .unwrap()
+        };
+
+        let (inline, offloaded) = (time(false), time(true));
+        assert!(offloaded < inline, "saved in {offloaded:?} with blobs, {inline:?} without");
+
+        fs::remove_dir_all(&dir).ok();
+    }
+}
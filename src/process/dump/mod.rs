@@ -0,0 +1,323 @@
+pub mod blobs;
+
+use crate::{
+    config::structs::{ClientOptions, DumpFormat},
+    file::{self, Exists},
+    helpers, log,
+    process::{
+        Runner,
+        counters::{self, Counter},
+        host, http,
+        id::Id,
+        snapshots,
+    },
+};
+
+use chrono::Utc;
+use colored::Colorize;
+use global_placeholders::global;
+use macros_rs::{crashln, fmtstr, string};
+use std::{collections::BTreeMap, fs, io, path::{Path, PathBuf}, process};
+
+pub fn from(address: &str, token: Option<&str>, options: &ClientOptions) -> Result<Runner, anyhow::Error> {
+    let client = http::sync::build(options)?;
+    let headers = http::headers(token)?;
+
+    let response = client
+        .get(fmtstr!("{address}/daemon/dump"))
+        .headers(headers)
+        .send()?;
+    let bytes = response.bytes()?;
+
+    decode(&bytes).map_err(|err| anyhow::anyhow!("Cannot parse remote dump: {err}"))
+}
+
+pub fn read() -> Runner { read_at(Path::new(&global!("opm.dump"))) }
+
+/// The dump at `path`, created empty when it does not exist yet
+pub fn read_at(path: &Path) -> Runner {
+    counters::add(Counter::DumpRead);
+    let dump = path.to_string_lossy();
+
+    if !Exists::check(&dump).file() {
+        let runner = Runner {
+            id: Id::new(0),
+            list: BTreeMap::new(),
+            remote: None,
+            host: None,
+            backend: Default::default(),
+        };
+
+        write_at(path, &runner);
+        log!("created dump file");
+        return runner;
+    }
+
+    // Try to read the dump file with error recovery, either format is accepted
+    match file::read_file_with_retry(&dump).and_then(|bytes| decode(&bytes)) {
+        Ok(runner) => materialize(&blobs::Store::beside(path), runner),
+        Err(err) => {
+            // If parsing fails, the dump file is likely corrupted
+            // Log the error and create a fresh dump file
+            log!("[dump::read] Corrupted dump file detected: {err}");
+            
+            // Backup the corrupted file for debugging
+            let backup_path = format!(
+                "{}.corrupted.{}",
+                dump,
+                Utc::now().format("%Y%m%d_%H%M%S")
+            );
+            
+            // Try rename first (fast for same filesystem), fall back to copy+remove for cross-filesystem
+            let backup_result = fs::rename(path, &backup_path)
+                .or_else(|_| {
+                    fs::copy(path, &backup_path)
+                        .and_then(|_| fs::remove_file(path))
+                });
+            
+            if let Err(e) = backup_result {
+                log!("[dump::read] Failed to backup corrupted file: {e}");
+            } else {
+                log!("[dump::read] Backed up corrupted file to: {backup_path}");
+            }
+            
+            // Create a fresh runner with empty state
+            let runner = Runner {
+                id: Id::new(0),
+                list: BTreeMap::new(),
+                remote: None,
+                host: None,
+                backend: Default::default(),
+            };
+            
+            write_at(path, &runner);
+            log!("[dump::read] Created fresh dump file after corruption");
+            
+            runner
+        }
+    }
+}
+
+/// Dump as served to remote clients, always RON regardless of the local format
+pub fn raw() -> Result<Vec<u8>, String> { encode(&read(), DumpFormat::Ron).map(String::into_bytes) }
+
+pub fn write(dump: &Runner) { write_at(Path::new(&global!("opm.dump")), dump) }
+
+pub fn write_at(path: &Path, dump: &Runner) {
+    counters::add(Counter::DumpWrite);
+    // Stamped with the writing machine, so a daemon notices another host saving into a shared home
+    let dump = Runner { host: Some(host::current().to_string()), ..dump.clone() };
+    #[cfg(feature = "dump-blobs")]
+    let dump = offload(&blobs::Store::beside(path), dump);
+
+    let encoded = match encode(&dump, format()) {
+        Ok(contents) => contents,
+        Err(err) => crashln!(
+            "{} Cannot encode dump.\n{}",
+            *helpers::FAIL,
+            err.white()
+        ),
+    };
+
+    if let Err(err) = replace(path, encoded.as_bytes()) {
+        crashln!(
+            "{} Error writing dumpfile.\n{}",
+            *helpers::FAIL,
+            string!(err).white()
+        )
+    }
+}
+
+/// The large values of a dump moved to its blob store, they stay inline when the store cannot be written
+#[cfg(feature = "dump-blobs")]
+fn offload(store: &blobs::Store, mut dump: Runner) -> Runner {
+    if let Err(err) = store.offload(&mut dump) {
+        log!("[dump::write] Cannot store blobs in {}, values stay inline: {err}", store.dir().display());
+    }
+    dump
+}
+
+/// The values of a dump read back from its blob store
+pub fn materialize(store: &blobs::Store, mut dump: Runner) -> Runner {
+    for hash in store.materialize(&mut dump) {
+        log!("[dump::read] Missing blob {hash} in {}, its value is left empty", store.dir().display());
+    }
+    dump
+}
+
+/// Delete the blobs neither the dump nor a snapshot of it refers to, nothing when the dump cannot be read
+pub fn sweep_blobs() -> Result<Vec<blobs::Swept>, String> {
+    let path = PathBuf::from(global!("opm.dump"));
+    let bytes = fs::read(&path).map_err(|err| format!("Cannot read {}: {err}", path.display()))?;
+    let mut live = blobs::references(&decode(&bytes)?);
+
+    // A snapshot that cannot be parsed cannot be restored either
+    for snapshot in snapshots::list(&snapshots::dir()) {
+        if let Ok(runner) = fs::read(&snapshot.path).map_err(|err| string!(err)).and_then(|bytes| decode(&bytes)) {
+            live.extend(blobs::references(&runner));
+        }
+    }
+
+    blobs::Store::beside(&path).sweep(&live, std::time::SystemTime::now()).map_err(|err| string!(err))
+}
+
+/// Write a file aside and rename it into place, so a reader or a crash never leaves half of it
+pub fn replace(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let mut temp = path.as_os_str().to_owned();
+    temp.push(format!(".tmp.{}", process::id()));
+
+    fs::write(&temp, contents)?;
+    fs::rename(&temp, path).inspect_err(|_| {
+        let _ = fs::remove_file(&temp);
+    })
+}
+
+/// Serialize a dump, JSON is pretty-printed with maps in key order so it diffs cleanly
+pub fn encode(dump: &Runner, format: DumpFormat) -> Result<String, String> {
+    match format {
+        DumpFormat::Ron => ron::ser::to_string(dump).map_err(|err| string!(err)),
+        DumpFormat::Json => serde_json::to_string_pretty(dump)
+            .map(|json| json + "\n")
+            .map_err(|err| string!(err)),
+    }
+}
+
+/// Parse a dump in either format, RON dumps start with `(` and JSON dumps with `{`
+pub fn decode(bytes: &[u8]) -> Result<Runner, String> {
+    match bytes.iter().find(|byte| !byte.is_ascii_whitespace()) {
+        Some(b'{') => serde_json::from_slice(bytes).map_err(|err| string!(err)),
+        _ => ron::de::from_bytes(bytes).map_err(|err| string!(err)),
+    }
+}
+
+fn format() -> DumpFormat {
+    match global!("opm.dump.format").as_str() {
+        "json" => DumpFormat::Json,
+        _ => DumpFormat::Ron,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process::{Crash, Labels, Process, Watch};
+    use chrono::{DurationRound, TimeDelta};
+    use std::path::PathBuf;
+
+    fn runner() -> Runner {
+        let process = Process {
+            id: 3,
+            pid: 4242,
+            shell_pid: Some(4241),
+            env: BTreeMap::from([(string!("PORT"), string!("3000")), (string!("NODE_ENV"), string!("production"))]),
+            name: string!("api"),
+            path: PathBuf::from("/srv/api"),
+            script: string!("node index.js"),
+            restarts: 7,
+            running: true,
+            crash: Crash { crashed: true, value: 2, cores: vec![], next_restart: None },
+            watch: Watch {
+                enabled: true,
+                path: string!("src"),
+                hash: string!("abc123"),
+                cooldown_secs: None,
+                last_reload: None,
+                pending: None,
+            },
+            children: vec![4243, 4244],
+            // Timestamps are stored in milliseconds
+            started: Utc::now().duration_trunc(TimeDelta::milliseconds(1)).unwrap(),
+            started_mark: None,
+            errored: None,
+            max_memory: 512 * 1024 * 1024,
+            agent_id: Some(string!("agent-1")),
+            labels: Labels::from([(string!("team"), string!("payments"))]),
+            depends_on: vec![string!("db")],
+            startup_grace: Some(10),
+            last_restart_reason: Some(string!("deploy")),
+            url: Some(string!("https://api.internal/health")),
+            ..Default::default()
+        };
+
+        Runner {
+            id: Id::new(4),
+            remote: None,
+            list: BTreeMap::from([(3, process)]),
+            host: Some(string!("web-1 (3f2a9c1e7d6b)")),
+            backend: Default::default(),
+        }
+    }
+
+    fn assert_round_trip(format: DumpFormat) {
+        let original = runner();
+        let encoded = encode(&original, format).unwrap();
+        let decoded = decode(encoded.as_bytes()).unwrap();
+
+        // Process has no PartialEq, so compare through a second encoding
+        assert_eq!(encode(&decoded, format).unwrap(), encoded);
+
+        let process = decoded.info(3).unwrap();
+        assert_eq!(decoded.id.next(), 4);
+        assert_eq!(decoded.host.as_deref(), Some("web-1 (3f2a9c1e7d6b)"));
+        assert_eq!(process.shell_pid, Some(4241));
+        assert_eq!(process.agent_id.as_deref(), Some("agent-1"));
+        assert_eq!(process.started, original.info(3).unwrap().started);
+        assert_eq!(process.labels.get("team").map(String::as_str), Some("payments"));
+        assert_eq!(process.depends_on, vec![string!("db")]);
+        assert_eq!(process.startup_grace, Some(10));
+        assert_eq!(process.last_restart_reason.as_deref(), Some("deploy"));
+        assert_eq!(process.url.as_deref(), Some("https://api.internal/health"));
+        assert_eq!(process.max_memory, 512 * 1024 * 1024);
+        assert!(process.watch.enabled && process.crash.crashed);
+    }
+
+    #[test]
+    fn test_ron_round_trip() {
+        assert_round_trip(DumpFormat::Ron);
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        assert_round_trip(DumpFormat::Json);
+    }
+
+    #[test]
+    fn test_json_is_pretty_and_ordered() {
+        let runner = runner();
+        let encoded = encode(&runner, DumpFormat::Json).unwrap();
+
+        assert!(encoded.starts_with("{\n"));
+        assert!(encoded.find("\"NODE_ENV\"").unwrap() < encoded.find("\"PORT\"").unwrap());
+        assert_eq!(encode(&runner, DumpFormat::Json).unwrap(), encoded);
+    }
+
+    #[test]
+    fn test_decode_detects_format() {
+        let runner = runner();
+        let ron = encode(&runner, DumpFormat::Ron).unwrap();
+        let json = format!("  \n{}", encode(&runner, DumpFormat::Json).unwrap());
+
+        assert!(ron.starts_with('('));
+        assert_eq!(decode(ron.as_bytes()).unwrap().list.len(), 1);
+        assert_eq!(decode(json.as_bytes()).unwrap().list.len(), 1);
+        assert!(decode(b"{ not json").is_err());
+    }
+
+    #[test]
+    fn test_old_dump_without_optional_fields() {
+        let ron = encode(&runner(), DumpFormat::Ron).unwrap();
+        let ron = ron.replace(",startup_grace:Some(10)", "").replace(",depends_on:[\"db\"]", "");
+        let ron = ron.replace(",last_restart_reason:Some(\"deploy\")", "").replace(",url:Some(\"https://api.internal/health\")", "");
+        let ron = ron.replace(",host:Some(\"web-1 (3f2a9c1e7d6b)\")", "");
+        assert!(!ron.contains("startup_grace") && !ron.contains("depends_on") && !ron.contains("last_restart_reason"));
+        assert!(!ron.contains("url:") && !ron.contains("host:"));
+
+        let decoded = decode(ron.as_bytes()).unwrap();
+
+        assert_eq!(decoded.info(3).unwrap().startup_grace, None);
+        assert!(decoded.info(3).unwrap().depends_on.is_empty());
+        assert_eq!(decoded.info(3).unwrap().last_restart_reason, None);
+        assert_eq!(decoded.info(3).unwrap().url, None);
+        assert_eq!(decoded.host, None);
+    }
+}
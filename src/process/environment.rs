@@ -0,0 +1,191 @@
+//! Edits to the stored environment of a process, `opm adjust --env-add/--env-remove`.
+//! All of them are checked before any is applied, so a process never ends up with half of them.
+
+use super::Env;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashSet, fmt};
+use utoipa::ToSchema;
+
+/// A POSIX environment name, a letter or underscore followed by letters, digits and underscores
+pub fn validate_key(key: &str) -> Result<(), String> {
+    let mut chars = key.chars();
+
+    match chars.next() {
+        None => Err(String::from("environment variable name cannot be empty")),
+        Some(first) if !(first.is_ascii_alphabetic() || first == '_') => Err(format!("environment variable '{key}' must start with a letter or underscore")),
+        _ if !chars.all(|c| c.is_ascii_alphanumeric() || c == '_') => Err(format!("environment variable '{key}' may only contain [A-Za-z0-9_]")),
+        _ => Ok(()),
+    }
+}
+
+/// Parse a `KEY=VALUE` pair for `--env-add`, the value is kept as written
+pub fn parse(pair: &str) -> Result<(String, String), String> {
+    let Some((key, value)) = pair.split_once('=') else {
+        return Err(format!("invalid environment variable '{pair}', expected KEY=VALUE"));
+    };
+
+    validate_key(key)?;
+    if value.contains('\0') {
+        return Err(format!("value of environment variable '{key}' contains a NUL byte"));
+    }
+
+    Ok((key.to_string(), value.to_string()))
+}
+
+/// Variables to set and to remove, applied together
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Changes {
+    #[serde(default)]
+    #[schema(example = json!([["LOG_LEVEL", "debug"]]))]
+    pub add: Vec<(String, String)>,
+    #[serde(default)]
+    #[schema(example = json!(["DEBUG"]))]
+    pub remove: Vec<String>,
+}
+
+/// Body of `POST /process/<id>/env`, changes or a plain map of the variables to set
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum Update {
+    Changes(Changes),
+    #[schema(example = json!({"LOG_LEVEL": "debug"}))]
+    Set(Env),
+}
+
+impl From<Update> for Changes {
+    fn from(update: Update) -> Changes {
+        match update {
+            Update::Changes(changes) => changes,
+            Update::Set(vars) => Changes { add: vars.into_iter().collect(), remove: vec![] },
+        }
+    }
+}
+
+/// What happened to one variable
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, PartialEq)]
+#[serde(tag = "change", rename_all = "snake_case")]
+pub enum Change {
+    Added { key: String, value: String },
+    Changed { key: String, from: String, to: String },
+    Removed { key: String, value: String },
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Change::Added { key, value } => write!(f, "+ {key}={value}"),
+            Change::Changed { key, from, to } => write!(f, "~ {key}={from} -> {to}"),
+            Change::Removed { key, value } => write!(f, "- {key}={value}"),
+        }
+    }
+}
+
+impl Changes {
+    pub fn is_empty(&self) -> bool { self.add.is_empty() && self.remove.is_empty() }
+
+    /// Names, NUL bytes, and a key both added and removed, or added twice with different values
+    pub fn validate(&self) -> Result<(), String> {
+        for (key, value) in &self.add {
+            parse(&format!("{key}={value}"))?;
+        }
+        self.remove.iter().try_for_each(|key| validate_key(key))?;
+
+        let removed: HashSet<&str> = self.remove.iter().map(String::as_str).collect();
+        if let Some((key, _)) = self.add.iter().find(|(key, _)| removed.contains(key.as_str())) {
+            return Err(format!("environment variable '{key}' is both added and removed"));
+        }
+
+        for (index, (key, value)) in self.add.iter().enumerate() {
+            if self.add[..index].iter().any(|(other, earlier)| other == key && earlier != value) {
+                return Err(format!("environment variable '{key}' is added twice with different values"));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Apply to `env` once validated, removing a variable that is not set changes nothing
+    pub fn apply(&self, env: &mut Env) -> Result<Vec<Change>, String> {
+        self.validate()?;
+        let mut changes = vec![];
+
+        for key in &self.remove {
+            if let Some(value) = env.remove(key) {
+                changes.push(Change::Removed { key: key.clone(), value });
+            }
+        }
+
+        for (key, value) in &self.add {
+            match env.insert(key.clone(), value.clone()) {
+                None => changes.push(Change::Added { key: key.clone(), value: value.clone() }),
+                Some(from) if from != *value => changes.push(Change::Changed { key: key.clone(), from, to: value.clone() }),
+                Some(_) => {}
+            }
+        }
+
+        Ok(changes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keys() {
+        assert!(validate_key("DATABASE_URL").is_ok());
+        assert!(validate_key("_private").is_ok());
+        assert!(validate_key("").is_err());
+        assert!(validate_key("1PASSWORD").is_err());
+        assert!(validate_key("MY-VAR").is_err());
+        assert!(validate_key("MY VAR").is_err());
+
+        assert_eq!(parse("URL=postgres://a?b=c"), Ok((String::from("URL"), String::from("postgres://a?b=c"))));
+        assert_eq!(parse("EMPTY="), Ok((String::from("EMPTY"), String::new())));
+        assert!(parse("NO_VALUE").is_err());
+        assert!(parse("=value").is_err());
+    }
+
+    #[test]
+    fn test_conflicts_are_rejected() {
+        let both = Changes { add: vec![(String::from("A"), String::from("1"))], remove: vec![String::from("A")] };
+        assert_eq!(both.validate(), Err(String::from("environment variable 'A' is both added and removed")));
+
+        let twice = Changes { add: vec![(String::from("A"), String::from("1")), (String::from("A"), String::from("2"))], remove: vec![] };
+        assert!(twice.validate().is_err());
+
+        let same = Changes { add: vec![(String::from("A"), String::from("1")), (String::from("A"), String::from("1"))], remove: vec![] };
+        assert!(same.validate().is_ok());
+
+        // Nothing is applied when one change is invalid
+        let mut env = Env::from([(String::from("A"), String::from("0"))]);
+        let invalid = Changes { add: vec![(String::from("B"), String::from("1"))], remove: vec![String::from("bad-key")] };
+        assert!(invalid.apply(&mut env).is_err());
+        assert_eq!(env, Env::from([(String::from("A"), String::from("0"))]));
+    }
+
+    #[test]
+    fn test_update_bodies() {
+        let changes: Update = serde_json::from_str(r#"{"add": [["A", "1"]], "remove": ["B"]}"#).unwrap();
+        assert_eq!(Changes::from(changes), Changes { add: vec![(String::from("A"), String::from("1"))], remove: vec![String::from("B")] });
+
+        // A map is only variables to set, even one named like a field of the changes
+        let set: Update = serde_json::from_str(r#"{"PORT": "8080", "add": "x"}"#).unwrap();
+        let expected = vec![(String::from("PORT"), String::from("8080")), (String::from("add"), String::from("x"))];
+        assert_eq!(Changes::from(set), Changes { add: expected, remove: vec![] });
+    }
+
+    #[test]
+    fn test_apply() {
+        let mut env = Env::from([(String::from("KEEP"), String::from("1")), (String::from("OLD"), String::from("x")), (String::from("LEVEL"), String::from("info"))]);
+        let changes = Changes {
+            add: vec![(String::from("LEVEL"), String::from("debug")), (String::from("NEW"), String::from("y")), (String::from("KEEP"), String::from("1"))],
+            remove: vec![String::from("OLD"), String::from("MISSING")],
+        };
+
+        let applied = changes.apply(&mut env).unwrap();
+        assert_eq!(applied.iter().map(Change::to_string).collect::<Vec<_>>(), ["- OLD=x", "~ LEVEL=info -> debug", "+ NEW=y"]);
+        assert_eq!(env.keys().collect::<Vec<_>>(), ["KEEP", "LEVEL", "NEW"]);
+    }
+}
@@ -0,0 +1,118 @@
+use super::{Env, Process, hash, limits::Limits, load_dotenv, shell::Shell};
+
+use serde::Serialize;
+use std::path::Path;
+
+/// Everything a restart would run the process with, serialized in field order with sorted env keys
+#[derive(Serialize)]
+struct Inputs<'p> {
+    script: &'p str,
+    path: &'p Path,
+    /// Stored environment with the current `.env` of the working directory on top
+    env: Env,
+    max_memory: u64,
+    /// Hash of the watched path, only when watching is enabled
+    watch: Option<String>,
+    /// Left out while unset, so processes without limits keep the fingerprint they had
+    #[serde(skip_serializing_if = "Limits::is_empty")]
+    limits: Limits,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    collect_cores: bool,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    supervised: bool,
+    #[serde(skip_serializing_if = "Shell::is_empty")]
+    shell: Shell,
+}
+
+/// Fingerprint of the inputs that shape a running process, stored at every successful start
+///
+/// Restarting with an equal fingerprint would run the same command, in the same directory, with the
+/// same environment and limits, which is what `opm restart --if-changed` skips.
+pub fn compute(process: &Process) -> String {
+    let mut env = process.env.clone();
+    env.extend(load_dotenv(&process.path));
+
+    let inputs = Inputs {
+        env,
+        script: &process.script,
+        path: &process.path,
+        max_memory: process.max_memory,
+        watch: process.watch.enabled.then(|| hash::create(process.path.join(&process.watch.path))),
+        limits: process.limits(),
+        collect_cores: process.collect_cores,
+        supervised: process.supervised,
+        shell: process.custom_shell(),
+    };
+
+    // Every field serializes, and an Env is a BTreeMap, so the encoding is deterministic
+    let encoded = serde_json::to_vec(&inputs).unwrap_or_default();
+    blake3::hash(&encoded).to_hex().to_string()
+}
+
+/// Whether the process runs with the inputs it would be restarted with
+pub fn unchanged(process: &Process) -> bool { process.fingerprint.as_deref() == Some(&compute(process)) }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use macros_rs::string;
+    use std::{fs, path::PathBuf};
+
+    fn process(path: PathBuf, env: &[(&str, &str)]) -> Process {
+        Process {
+            env: env.iter().map(|(key, value)| (string!(key), string!(value))).collect(),
+            name: string!("app"),
+            path,
+            script: string!("node server.js"),
+            running: true,
+            started: Utc::now(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_fingerprint_ignores_insertion_order() {
+        let dir = std::env::temp_dir();
+        let a = process(dir.clone(), &[("PORT", "80"), ("HOST", "a")]);
+        let b = process(dir, &[("HOST", "a"), ("PORT", "80")]);
+
+        assert_eq!(compute(&a), compute(&b));
+        assert_eq!(compute(&a).len(), 64);
+    }
+
+    #[test]
+    fn test_fingerprint_tracks_inputs() {
+        let dir = std::env::temp_dir().join(format!("opm-fingerprint-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let mut item = process(dir.clone(), &[("PORT", "80")]);
+        assert!(!unchanged(&item));
+
+        item.fingerprint = Some(compute(&item));
+        assert!(unchanged(&item));
+
+        item.max_memory = 1024;
+        assert!(!unchanged(&item));
+        item.max_memory = 0;
+
+        item.umask = Some(0o002);
+        assert!(!unchanged(&item));
+        item.umask = None;
+
+        item.shell_args = Some(vec![string!("-lc")]);
+        assert!(!unchanged(&item));
+        item.shell_args = None;
+
+        // The current .env counts, even though the stored environment is the same
+        fs::write(dir.join(".env"), "PORT=81\n").unwrap();
+        assert!(!unchanged(&item));
+        fs::remove_file(dir.join(".env")).unwrap();
+        assert!(unchanged(&item));
+
+        item.script = string!("node server.js --inspect");
+        assert!(!unchanged(&item));
+
+        fs::remove_dir_all(&dir).ok();
+    }
+}
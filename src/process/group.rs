@@ -0,0 +1,37 @@
+//! Processes managed together, e.g. the workers of one `--workers` start. `group:NAME` stands for every
+//! member on the command line, `opm list` shows the group column once a process has one.
+
+pub const MAX_LEN: usize = 63;
+
+/// Check a group name given by a user, it ends up in `group:NAME` so it cannot hold a comma or a space
+pub fn parse(value: &str) -> Result<String, String> {
+    let name = value.trim();
+
+    if name.is_empty() {
+        return Err(String::from("a group name cannot be empty"));
+    }
+
+    if name.len() > MAX_LEN {
+        return Err(format!("group '{name}' is longer than {MAX_LEN} characters"));
+    }
+
+    if !name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-')) {
+        return Err(format!("group '{name}' may only contain [A-Za-z0-9_.-]"));
+    }
+
+    Ok(name.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(parse(" api.v2_blue-1 ").unwrap(), "api.v2_blue-1");
+        assert!(parse("").is_err());
+        assert!(parse("api,web").unwrap_err().contains("[A-Za-z0-9_.-]"));
+        assert!(parse("my group").is_err());
+        assert!(parse(&"a".repeat(MAX_LEN + 1)).unwrap_err().contains("longer than"));
+    }
+}
@@ -1,8 +1,10 @@
+use crate::process::counters::{self, Counter};
 use macros_rs::crashln;
 use merkle_hash::{Algorithm, MerkleTree, bytes_to_hex};
 use std::path::PathBuf;
 
 pub fn create(path: PathBuf) -> String {
+    counters::add(Counter::WatchHash);
     log::info!("creating hash for {:?}", path);
     let tree = match MerkleTree::builder(&path.to_str().unwrap())
         .algorithm(Algorithm::Blake3)
@@ -0,0 +1,253 @@
+//! `--health-url` and `--health-cmd`, a check the daemon runs on an online process every `interval` seconds.
+//! A process with one is `starting` until a check passed, `unhealthy` while checks fail, and restarted like a
+//! crashed one after `retries` failures in a row, so its restart limit applies.
+
+use chrono::{DateTime, Utc};
+use macros_rs::{string, ternary};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use nix::{
+    sys::signal::{Signal, kill},
+    unistd::Pid,
+};
+
+use std::{
+    fmt,
+    os::unix::process::CommandExt,
+    path::Path,
+    process::{Command, Stdio},
+    thread::sleep,
+    time::{Duration, Instant},
+};
+
+pub const DEFAULT_INTERVAL: u64 = 10;
+pub const DEFAULT_RETRIES: u32 = 3;
+pub const DEFAULT_TIMEOUT: u64 = 5;
+
+/// How often a running command is looked at while it is waited for
+const POLL: Duration = Duration::from_millis(50);
+
+/// What is checked, an answer in the 2xx range or a command exiting with 0 passes
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Probe {
+    Url(String),
+    Command(String),
+}
+
+impl fmt::Display for Probe {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Probe::Url(url) => write!(f, "GET {url}"),
+            Probe::Command(command) => write!(f, "{command}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Check {
+    pub probe: Probe,
+    /// Seconds from one check to the next
+    pub interval: u64,
+    /// Failures in a row before the process is restarted
+    pub retries: u32,
+    /// Seconds a check may take, a slower one fails
+    pub timeout: u64,
+}
+
+impl Check {
+    pub fn new(probe: Probe) -> Self {
+        Check {
+            probe,
+            interval: DEFAULT_INTERVAL,
+            retries: DEFAULT_RETRIES,
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+
+    /// `GET http://127.0.0.1:8080/health every 10s, restart after 3 failures`
+    pub fn describe(&self) -> String { format!("{} every {}s, restart after {} failures", self.probe, self.interval, self.retries) }
+
+    /// Whether a check is due at `now`, the first one once the process is online
+    pub fn due(&self, last: Option<&Status>, now: DateTime<Utc>) -> bool { last.is_none_or(|last| (now - last.at).num_seconds() >= self.interval as i64) }
+
+    /// Run the check, Ok and Err both describe the outcome, e.g. `HTTP 503` or `exit 1`
+    pub fn run(&self, cwd: &Path) -> Result<String, String> {
+        let timeout = Duration::from_secs(self.timeout.max(1));
+
+        match &self.probe {
+            Probe::Url(url) => {
+                let client = reqwest::blocking::Client::builder().timeout(timeout).build().map_err(|err| err.to_string())?;
+                let status = client.get(url).send().map_err(|err| ternary!(err.is_timeout(), format!("timed out after {}s", timeout.as_secs()), err.to_string()))?.status();
+
+                match status.is_success() {
+                    true => Ok(format!("HTTP {}", status.as_u16())),
+                    false => Err(format!("HTTP {}", status.as_u16())),
+                }
+            }
+            Probe::Command(command) => {
+                // A group of its own, so a check that hangs is killed with whatever it started
+                let mut child = Command::new("sh")
+                    .args(["-c", command])
+                    .current_dir(cwd)
+                    .process_group(0)
+                    .stdin(Stdio::null())
+                    .stdout(Stdio::null())
+                    .stderr(Stdio::null())
+                    .spawn()
+                    .map_err(|err| format!("cannot run: {err}"))?;
+
+                let start = Instant::now();
+                loop {
+                    match child.try_wait().map_err(|err| err.to_string())? {
+                        Some(status) if status.success() => return Ok(string!("exit 0")),
+                        Some(status) => return Err(status.code().map_or_else(|| string!("killed by a signal"), |code| format!("exit {code}"))),
+                        None if start.elapsed() >= timeout => {
+                            let _ = kill(Pid::from_raw(-(child.id() as i32)), Signal::SIGKILL);
+                            let _ = child.wait();
+                            return Err(format!("timed out after {}s", timeout.as_secs()));
+                        }
+                        None => sleep(POLL),
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Outcome of the last check of a process
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct Status {
+    #[schema(value_type = String, example = "2000-01-01T01:00:00.000Z")]
+    pub at: DateTime<Utc>,
+    pub healthy: bool,
+    #[schema(example = "HTTP 200")]
+    pub detail: String,
+    /// Checks failed in a row, 0 once one passed
+    pub failures: u32,
+}
+
+impl Status {
+    /// The status after a check with `result`, counting on from `last` of the same run of the process
+    pub fn after(last: Option<&Status>, result: Result<String, String>, now: DateTime<Utc>) -> Self {
+        match result {
+            Ok(detail) => Status { at: now, healthy: true, detail, failures: 0 },
+            Err(detail) => Status {
+                at: now,
+                healthy: false,
+                detail,
+                failures: last.map_or(0, |last| last.failures) + 1,
+            },
+        }
+    }
+
+    /// `healthy (HTTP 200)` or `unhealthy (HTTP 503, 2 failures)`
+    pub fn describe(&self) -> String {
+        match self.healthy {
+            true => format!("healthy ({})", self.detail),
+            false => format!("unhealthy ({}, {} failures)", self.detail, self.failures),
+        }
+    }
+}
+
+/// The health check options of `opm start` and `opm adjust`, None fields are left as they are
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Changes {
+    /// Some(None) removes the check
+    pub probe: Option<Option<Probe>>,
+    pub interval: Option<u64>,
+    pub retries: Option<u32>,
+    pub timeout: Option<u64>,
+}
+
+impl Changes {
+    pub fn is_empty(&self) -> bool { self.probe.is_none() && self.interval.is_none() && self.retries.is_none() && self.timeout.is_none() }
+
+    /// The check of a process after the changes, a new probe keeps the interval, retries and timeout of `current`
+    pub fn apply(&self, current: Option<&Check>) -> Result<Option<Check>, String> {
+        if self.interval == Some(0) || self.retries == Some(0) || self.timeout == Some(0) {
+            return Err(string!("the health check interval, retries and timeout must be at least 1"));
+        }
+
+        let check = match (&self.probe, current) {
+            (Some(None), _) => return Ok(None),
+            (Some(Some(probe)), _) => Check {
+                probe: probe.clone(),
+                ..current.cloned().unwrap_or_else(|| Check::new(probe.clone()))
+            },
+            (None, Some(current)) => current.clone(),
+            (None, None) if self.is_empty() => return Ok(None),
+            (None, None) => return Err(string!("there is no health check to change, add one with --health-url or --health-cmd")),
+        };
+
+        Ok(Some(Check {
+            interval: self.interval.unwrap_or(check.interval),
+            retries: self.retries.unwrap_or(check.retries),
+            timeout: self.timeout.unwrap_or(check.timeout),
+            probe: check.probe,
+        }))
+    }
+}
+
+/// Clap parser for `--health-cmd`
+pub fn parse_command(value: &str) -> Result<String, String> {
+    match value.trim() {
+        "" => Err(String::from("a health check command cannot be empty")),
+        command => Ok(command.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeDelta;
+
+    #[test]
+    fn test_failures_count_in_a_row() {
+        let now = Utc::now();
+        let failed = Status::after(None, Err(string!("HTTP 503")), now);
+        let again = Status::after(Some(&failed), Err(string!("timed out after 5s")), now);
+
+        assert_eq!((failed.failures, again.failures), (1, 2));
+        assert_eq!(again.describe(), "unhealthy (timed out after 5s, 2 failures)");
+        assert_eq!(Status::after(Some(&again), Ok(string!("HTTP 200")), now).failures, 0);
+    }
+
+    #[test]
+    fn test_due() {
+        let check = Check::new(Probe::Url(string!("http://127.0.0.1:8080/health")));
+        let now = Utc::now();
+        let last = Status::after(None, Ok(string!("HTTP 200")), now);
+
+        assert!(check.due(None, now));
+        assert!(!check.due(Some(&last), now + TimeDelta::seconds(9)));
+        assert!(check.due(Some(&last), now + TimeDelta::seconds(10)));
+        assert_eq!(check.describe(), "GET http://127.0.0.1:8080/health every 10s, restart after 3 failures");
+    }
+
+    #[test]
+    fn test_changes() {
+        let url = Probe::Url(string!("http://127.0.0.1:8080/health"));
+        let current = Check { retries: 5, ..Check::new(url.clone()) };
+        let interval = Changes { interval: Some(30), ..Changes::default() };
+
+        let replaced = Changes { probe: Some(Some(Probe::Command(string!("pg_isready")))), ..Changes::default() }.apply(Some(&current)).unwrap().unwrap();
+        assert_eq!((replaced.probe.to_string(), replaced.retries), (string!("pg_isready"), 5));
+
+        assert_eq!(interval.apply(Some(&current)).unwrap().map(|check| (check.interval, check.retries)), Some((30, 5)));
+        assert!(interval.apply(None).unwrap_err().contains("no health check"));
+        assert_eq!(Changes { probe: Some(None), ..Changes::default() }.apply(Some(&current)), Ok(None));
+        assert_eq!(Changes::default().apply(None), Ok(None));
+        assert!(Changes { retries: Some(0), ..Changes::default() }.apply(Some(&current)).unwrap_err().contains("at least 1"));
+    }
+
+    #[test]
+    fn test_command_check() {
+        let check = |command: &str, timeout: u64| Check { timeout, ..Check::new(Probe::Command(command.to_string())) }.run(Path::new("/tmp"));
+
+        assert_eq!(check("true", 5), Ok(string!("exit 0")));
+        assert_eq!(check("exit 3", 5), Err(string!("exit 3")));
+        assert_eq!(check("sleep 5", 1), Err(string!("timed out after 1s")));
+    }
+}
@@ -0,0 +1,47 @@
+//! Which machine wrote to an opm home, so two hosts sharing one `~/.opm` over NFS notice each other
+
+use once_cell::sync::Lazy;
+use std::fs;
+
+/// Where systemd and dbus keep the id of this machine, it survives a hostname change
+const MACHINE_ID: [&str; 2] = ["/etc/machine-id", "/var/lib/dbus/machine-id"];
+
+static CURRENT: Lazy<String> = Lazy::new(|| {
+    let name = hostname::get().map(|name| name.to_string_lossy().into_owned()).unwrap_or_else(|_| String::from("unknown"));
+    let machine = MACHINE_ID.iter().find_map(|path| fs::read_to_string(path).ok()).map(|id| id.trim().to_string());
+
+    identify(&name, machine.as_deref())
+});
+
+/// `hostname (machine-id)`, or the hostname alone where the machine has no id
+pub fn identify(name: &str, machine: Option<&str>) -> String {
+    match machine.filter(|id| !id.is_empty()) {
+        Some(id) => format!("{name} ({})", &id[..id.len().min(12)]),
+        None => name.to_string(),
+    }
+}
+
+/// This machine, as written into the dump and the daemon lock
+pub fn current() -> &'static str { &CURRENT }
+
+/// Whether `host` names another machine, a dump or lock without one was written before hosts were recorded
+pub fn is_foreign(host: Option<&str>) -> bool { host.is_some_and(|host| host != current()) }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_identify() {
+        assert_eq!(identify("web-1", Some("3f2a9c1e7d6b4a0e8c5f1b2d3e4f5a6b\n".trim())), "web-1 (3f2a9c1e7d6b)");
+        assert_eq!(identify("web-1", Some("")), "web-1");
+        assert_eq!(identify("web-1", None), "web-1");
+    }
+
+    #[test]
+    fn test_is_foreign() {
+        assert!(!is_foreign(None));
+        assert!(!is_foreign(Some(current())));
+        assert!(is_foreign(Some("other-host (0123456789ab)")));
+    }
+}
@@ -1,18 +1,65 @@
-use crate::process::Remote;
+use crate::{
+    audit, events,
+    config::{structs::ClientOptions, token},
+    notifications::history::NotificationHistory,
+    process::{Labels, ProcessItem, PurgedLogs, Remote, StartOptions, checkpoint::Window, environment, stats},
+};
+use anyhow::anyhow;
 use macros_rs::{fmtstr, string};
-use reqwest::Client;
+use nix::sys::signal::Signal;
 use reqwest::header::{HeaderMap, HeaderValue};
-use serde::Serialize;
-use std::path::PathBuf;
+use reqwest::{Certificate, Client};
+use serde::{Deserialize, Serialize};
+use std::{fs, path::PathBuf, time::Duration};
+
+/// Log archives can be large, so their download gets more time than a regular request
+const ARCHIVE_TIMEOUT: Duration = Duration::from_secs(600);
 
 #[derive(Serialize)]
 struct ActionBody {
     pub method: String,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub purge_logs: bool,
+    /// With `stop`, the signal to stop the process with
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signal: Option<String>,
+}
+
+/// Answer of the remove action, older daemons leave `logs` out
+#[derive(Deserialize)]
+pub struct RemoveResponse {
+    #[serde(default)]
+    pub logs: Option<PurgedLogs>,
+}
+
+#[derive(Serialize)]
+struct BulkBody<'b> {
+    pub all: bool,
+    pub method: &'b str,
+}
+
+/// Processes a bulk action succeeded and failed on
+#[derive(Deserialize)]
+pub struct BulkResult {
+    pub success: Vec<usize>,
+    pub failed: Vec<usize>,
+}
+
+#[derive(Serialize)]
+struct TriggerBody<'t> {
+    pub reason: &'t str,
+}
+
+#[derive(Deserialize)]
+struct TriggerResponse {
+    pub pid: i64,
 }
 
 pub struct LogResponse {
     pub path: &'static str,
     pub lines: Vec<String>,
+    /// Set when the lines were selected by checkpoints rather than per-line timestamps
+    pub approximate: bool,
 }
 
 #[derive(Serialize)]
@@ -21,41 +68,118 @@ struct CreateBody<'c> {
     pub script: &'c String,
     pub path: PathBuf,
     pub watch: &'c Option<String>,
+    pub labels: &'c Labels,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub expand_env_templates: bool,
+}
+
+// Both reqwest builders expose the same options but share no trait
+macro_rules! configure {
+    ($builder:expr, $options:expr) => {{
+        let mut builder = $builder;
+        let options: &ClientOptions = $options;
+
+        if let Some(proxy) = &options.proxy {
+            let proxy = reqwest::Proxy::all(proxy).map_err(|err| anyhow!("invalid proxy url '{proxy}': {err}"))?;
+            builder = builder.proxy(proxy);
+        }
+
+        if let Some(path) = &options.ca_cert {
+            let pem = fs::read(path).map_err(|err| anyhow!("unable to read ca certificate '{path}': {err}"))?;
+            for cert in Certificate::from_pem_bundle(&pem).map_err(|err| anyhow!("invalid ca certificate '{path}': {err}"))? {
+                builder = builder.add_root_certificate(cert);
+            }
+        }
+
+        if options.insecure {
+            log::warn!("TLS certificate verification is disabled for this client");
+            builder = builder.danger_accept_invalid_certs(true);
+        }
+
+        builder
+    }};
 }
 
 pub mod sync {
+    use super::*;
     use reqwest::blocking::Client;
-    use reqwest::header::{HeaderMap, HeaderValue};
 
     pub use reqwest::blocking::Response;
-    pub fn client(token: &Option<String>) -> (Client, HeaderMap) {
-        let client = Client::new();
-        let mut headers = HeaderMap::new();
 
-        if let Some(token) = token {
-            headers.insert("token", HeaderValue::from_str(&token).unwrap());
-        }
+    pub fn build(options: &ClientOptions) -> Result<Client, anyhow::Error> {
+        Ok(configure!(Client::builder(), options).build()?)
+    }
 
-        return (client, headers);
+    pub fn client(token: &Option<String>, options: &ClientOptions) -> Result<(Client, HeaderMap), anyhow::Error> {
+        Ok((build(options)?, headers(token.as_deref())?))
     }
 }
 
-pub async fn client(token: &Option<String>) -> (Client, HeaderMap) {
-    let client = Client::new();
+pub fn build(options: &ClientOptions) -> Result<Client, anyhow::Error> {
+    Ok(configure!(Client::builder(), options).build()?)
+}
+
+pub async fn client(token: &Option<String>, options: &ClientOptions) -> Result<(Client, HeaderMap), anyhow::Error> {
+    Ok((build(options)?, headers(token.as_deref())?))
+}
+
+/// Request headers carrying the token, `env:` and `file:` references are resolved on every call
+pub fn headers(token: Option<&str>) -> Result<HeaderMap, anyhow::Error> {
     let mut headers = HeaderMap::new();
 
     if let Some(token) = token {
-        headers.insert("token", HeaderValue::from_str(&token).unwrap());
+        let token = token::resolve(token)?;
+        headers.insert("token", HeaderValue::from_str(&token).map_err(|_| anyhow!("Token contains characters not allowed in a header"))?);
     }
 
-    return (client, headers);
+    Ok(headers)
+}
+
+/// Name the stage a request failed at, so proxy/TLS/DNS problems are told apart
+pub fn failure_stage(err: &reqwest::Error, options: &ClientOptions) -> &'static str {
+    let mut chain = err.to_string();
+    let mut source = std::error::Error::source(err);
+
+    while let Some(inner) = source {
+        chain.push_str(&format!(": {inner}"));
+        source = inner.source();
+    }
+
+    let chain = chain.to_lowercase();
+
+    if chain.contains("dns error") || chain.contains("failed to lookup address") {
+        "DNS"
+    } else if chain.contains("certificate") || chain.contains("tls") || chain.contains("handshake") {
+        "TLS"
+    } else if chain.contains("proxy") || (options.proxy.is_some() && err.is_connect()) {
+        "proxy"
+    } else if err.is_timeout() {
+        "timeout"
+    } else if err.is_connect() {
+        "connection"
+    } else {
+        "request"
+    }
+}
+
+/// Format a request error together with the failing stage
+pub fn describe_error(err: &reqwest::Error, options: &ClientOptions) -> String {
+    let mut message = err.to_string();
+    let mut source = std::error::Error::source(err);
+
+    while let Some(inner) = source {
+        message.push_str(&format!(": {inner}"));
+        source = inner.source();
+    }
+
+    format!("{} failure: {message}", failure_stage(err, options))
 }
 
 pub fn info(
-    Remote { address, token, .. }: &Remote,
+    Remote { address, token, options, .. }: &Remote,
     id: usize,
 ) -> Result<sync::Response, anyhow::Error> {
-    let (client, headers) = sync::client(token);
+    let (client, headers) = sync::client(token, options)?;
     Ok(client
         .get(fmtstr!("{address}/process/{id}/info"))
         .headers(headers)
@@ -63,11 +187,11 @@ pub fn info(
 }
 
 pub fn logs(
-    Remote { address, token, .. }: &Remote,
+    Remote { address, token, options, .. }: &Remote,
     id: usize,
     kind: &str,
 ) -> Result<LogResponse, anyhow::Error> {
-    let (client, headers) = sync::client(token);
+    let (client, headers) = sync::client(token, options)?;
     let response = client
         .get(fmtstr!("{address}/process/{id}/logs/{kind}/raw"))
         .headers(headers)
@@ -88,38 +212,109 @@ pub fn logs(
                 .last()
                 .unwrap_or(""),
         )),
+        approximate: false,
     })
 }
 
+/// Fetch the lines of a log inside a time window, the daemon seeks using its checkpoints
+pub fn logs_window(
+    Remote { address, token, options, .. }: &Remote,
+    id: usize,
+    kind: &str,
+    window: &Window,
+) -> Result<LogResponse, anyhow::Error> {
+    let (client, headers) = sync::client(token, options)?;
+    let mut query = vec![];
+
+    if let Some(since) = window.since {
+        query.push(("since", since.to_rfc3339()));
+    }
+
+    if let Some(until) = window.until {
+        query.push(("until", until.to_rfc3339()));
+    }
+
+    let log = client
+        .get(fmtstr!("{address}/process/{id}/logs/{kind}/raw"))
+        .query(&query)
+        .headers(headers)
+        .send()?
+        .error_for_status()?
+        .text()?;
+
+    let mut lines = log.lines();
+    let path = lines.next().and_then(|header| header.strip_prefix("# PATH ")).unwrap_or("");
+    let mut lines = lines.peekable();
+    let approximate = lines.next_if_eq(&"# APPROXIMATE").is_some();
+
+    Ok(LogResponse {
+        path: Box::leak(Box::from(path)),
+        lines: lines.map(|line| line.to_string()).collect(),
+        approximate,
+    })
+}
+
+/// Download the log archive of a process, the body is a gzipped tarball
+pub fn logs_archive(
+    Remote { address, token, options, .. }: &Remote,
+    id: usize,
+    lines: Option<usize>,
+    hours: Option<u64>,
+) -> Result<sync::Response, anyhow::Error> {
+    let (client, headers) = sync::client(token, options)?;
+    let mut query = vec![];
+
+    if let Some(lines) = lines {
+        query.push(("lines", lines.to_string()));
+    }
+
+    if let Some(hours) = hours {
+        query.push(("hours", hours.to_string()));
+    }
+
+    Ok(client
+        .get(fmtstr!("{address}/process/{id}/logs/archive"))
+        .query(&query)
+        .timeout(ARCHIVE_TIMEOUT)
+        .headers(headers)
+        .send()?)
+}
+
 pub fn create(
-    Remote { address, token, .. }: &Remote,
+    Remote { address, token, options, .. }: &Remote,
     name: &String,
     script: &String,
     path: PathBuf,
-    watch: &Option<String>,
+    start: &StartOptions,
 ) -> Result<sync::Response, anyhow::Error> {
-    let (client, headers) = sync::client(token);
+    let (client, headers) = sync::client(token, options)?;
     let content = CreateBody {
         name,
         script,
         path,
-        watch,
+        watch: &start.watch,
+        labels: &start.labels,
+        expand_env_templates: start.expand_env,
     };
 
+    // A name taken by a process with another command is answered with 409
     Ok(client
         .post(fmtstr!("{address}/process/create"))
         .json(&content)
         .headers(headers)
-        .send()?)
+        .send()?
+        .error_for_status()?)
 }
 
 pub fn restart(
-    Remote { address, token, .. }: &Remote,
+    Remote { address, token, options, .. }: &Remote,
     id: usize,
 ) -> Result<sync::Response, anyhow::Error> {
-    let (client, headers) = sync::client(token);
+    let (client, headers) = sync::client(token, options)?;
     let content = ActionBody {
         method: string!("restart"),
+        purge_logs: false,
+        signal: None,
     };
 
     Ok(client
@@ -130,12 +325,14 @@ pub fn restart(
 }
 
 pub fn reload(
-    Remote { address, token, .. }: &Remote,
+    Remote { address, token, options, .. }: &Remote,
     id: usize,
 ) -> Result<sync::Response, anyhow::Error> {
-    let (client, headers) = sync::client(token);
+    let (client, headers) = sync::client(token, options)?;
     let content = ActionBody {
         method: string!("reload"),
+        purge_logs: false,
+        signal: None,
     };
 
     Ok(client
@@ -145,12 +342,180 @@ pub fn reload(
         .send()?)
 }
 
+/// Ask the remote daemon for a watch-style reload, returns the new pid
+pub fn trigger(Remote { address, token, options, .. }: &Remote, id: usize, reason: &str) -> Result<i64, anyhow::Error> {
+    let (client, headers) = sync::client(token, options)?;
+    let response = client
+        .post(fmtstr!("{address}/process/{id}/trigger"))
+        .json(&TriggerBody { reason })
+        .headers(headers)
+        .send()?
+        .error_for_status()?;
+
+    Ok(response.json::<TriggerResponse>()?.pid)
+}
+
+/// Set and remove stored environment variables of a process on the remote daemon
+pub fn adjust_env(Remote { address, token, options, .. }: &Remote, id: usize, changes: &environment::Changes) -> Result<Vec<environment::Change>, anyhow::Error> {
+    let (client, headers) = sync::client(token, options)?;
+    let response = client
+        .post(fmtstr!("{address}/process/{id}/env"))
+        .json(changes)
+        .headers(headers)
+        .send()?
+        .error_for_status()?;
+
+    Ok(response.json::<Vec<environment::Change>>()?)
+}
+
+/// Remove one variable stored on the remote daemon, nothing when it was not set
+pub fn unset_env(Remote { address, token, options, .. }: &Remote, id: usize, key: &str) -> Result<Option<environment::Change>, anyhow::Error> {
+    let (client, headers) = sync::client(token, options)?;
+    let response = client
+        .delete(fmtstr!("{address}/process/{id}/env/{key}"))
+        .headers(headers)
+        .send()?
+        .error_for_status()?;
+
+    Ok(response.json::<Vec<environment::Change>>()?.pop())
+}
+
+/// Set or remove the description of a process, `null` removes it
+pub fn describe(remote: &Remote, id: usize, description: &Option<String>) -> Result<(), anyhow::Error> { patch(remote, id, serde_json::json!({ "description": description })) }
+
+/// Move a process into a group, `null` takes it out
+pub fn group(remote: &Remote, id: usize, group: &Option<String>) -> Result<(), anyhow::Error> { patch(remote, id, serde_json::json!({ "group": group })) }
+
+/// `PATCH /process/<id>` with the fields of `body`
+fn patch(Remote { address, token, options, .. }: &Remote, id: usize, body: serde_json::Value) -> Result<(), anyhow::Error> {
+    let (client, headers) = sync::client(token, options)?;
+    client
+        .patch(fmtstr!("{address}/process/{id}"))
+        .json(&body)
+        .headers(headers)
+        .send()?
+        .error_for_status()?;
+
+    Ok(())
+}
+
+/// Processes of the remote daemon with the usage it sampled
+pub fn list(Remote { address, token, options, .. }: &Remote) -> Result<Vec<ProcessItem>, anyhow::Error> {
+    let (client, headers) = sync::client(token, options)?;
+    let response = client
+        .get(fmtstr!("{address}/list"))
+        .headers(headers)
+        .send()?
+        .error_for_status()?;
+
+    Ok(response.json::<Vec<ProcessItem>>()?)
+}
+
+/// Run an action of the bulk endpoint on every process of the remote daemon
+pub fn bulk_all(Remote { address, token, options, .. }: &Remote, method: &str) -> Result<BulkResult, anyhow::Error> {
+    let (client, headers) = sync::client(token, options)?;
+    let response = client
+        .post(fmtstr!("{address}/process/bulk-action"))
+        .json(&BulkBody { all: true, method })
+        .headers(headers)
+        .send()?
+        .error_for_status()?;
+
+    Ok(response.json::<BulkResult>()?)
+}
+
+/// Resource usage summary computed by the remote daemon
+pub fn stats_summary(Remote { address, token, options, .. }: &Remote, by: &stats::By) -> Result<stats::Summary, anyhow::Error> {
+    let (client, headers) = sync::client(token, options)?;
+    let response = client
+        .get(fmtstr!("{address}/stats/summary"))
+        .query(&[("by", by.to_string())])
+        .headers(headers)
+        .send()?
+        .error_for_status()?;
+
+    Ok(response.json::<stats::Summary>()?)
+}
+
+/// Audit log entries of the remote daemon
+pub fn audit(Remote { address, token, options, .. }: &Remote, lines: usize, filter: &audit::Filter) -> Result<Vec<audit::Entry>, anyhow::Error> {
+    let (client, headers) = sync::client(token, options)?;
+    let mut query = vec![("lines", lines.to_string())];
+
+    query.extend(filter.process.clone().map(|process| ("process", process)));
+    query.extend(filter.action.clone().map(|action| ("action", action)));
+
+    let response = client
+        .get(fmtstr!("{address}/daemon/audit"))
+        .query(&query)
+        .headers(headers)
+        .send()?
+        .error_for_status()?;
+
+    Ok(response.json::<Vec<audit::Entry>>()?)
+}
+
+fn events_query(filter: &events::Filter) -> Vec<(&'static str, String)> {
+    let mut query = vec![];
+
+    if !filter.kinds.is_empty() {
+        query.push(("types", filter.kinds.iter().map(|kind| kind.to_string()).collect::<Vec<_>>().join(",")));
+    }
+
+    query.extend(filter.process.clone().map(|process| ("process", process)));
+    query
+}
+
+/// Process events the remote daemon recorded
+pub fn events(Remote { address, token, options, .. }: &Remote, lines: usize, filter: &events::Filter) -> Result<Vec<events::Event>, anyhow::Error> {
+    let (client, headers) = sync::client(token, options)?;
+    let mut query = events_query(filter);
+    query.push(("lines", lines.to_string()));
+
+    let response = client
+        .get(fmtstr!("{address}/daemon/events"))
+        .query(&query)
+        .headers(headers)
+        .send()?
+        .error_for_status()?;
+
+    Ok(response.json::<Vec<events::Event>>()?)
+}
+
+/// Notification deliveries the remote daemon recorded
+pub fn notification_history(Remote { address, token, options, .. }: &Remote, limit: usize) -> Result<NotificationHistory, anyhow::Error> {
+    let (client, headers) = sync::client(token, options)?;
+
+    let response = client
+        .get(fmtstr!("{address}/daemon/notifications/history"))
+        .query(&[("limit", limit)])
+        .headers(headers)
+        .send()?
+        .error_for_status()?;
+
+    Ok(response.json::<NotificationHistory>()?)
+}
+
+/// Server-sent event stream of the remote daemon's process events, without a timeout as it stays open
+pub fn event_stream(Remote { address, token, options, .. }: &Remote, filter: &events::Filter) -> Result<sync::Response, anyhow::Error> {
+    let client = configure!(reqwest::blocking::Client::builder().timeout(None), options).build()?;
+
+    let response = client
+        .get(fmtstr!("{address}/live/daemon/events"))
+        .query(&events_query(filter))
+        .headers(headers(token.as_deref())?)
+        .send()?
+        .error_for_status()?;
+
+    Ok(response)
+}
+
 pub fn rename(
-    Remote { address, token, .. }: &Remote,
+    Remote { address, token, options, .. }: &Remote,
     id: usize,
     name: String,
 ) -> Result<sync::Response, anyhow::Error> {
-    let (client, headers) = sync::client(token);
+    let (client, headers) = sync::client(token, options)?;
     Ok(client
         .post(fmtstr!("{address}/process/{id}/rename"))
         .body(name)
@@ -160,12 +525,15 @@ pub fn rename(
 
 // merge into one function
 pub fn stop(
-    Remote { address, token, .. }: &Remote,
+    Remote { address, token, options, .. }: &Remote,
     id: usize,
+    signal: Option<Signal>,
 ) -> Result<sync::Response, anyhow::Error> {
-    let (client, headers) = sync::client(token);
+    let (client, headers) = sync::client(token, options)?;
     let content = ActionBody {
         method: string!("stop"),
+        purge_logs: false,
+        signal: signal.map(|signal| signal.as_str().to_string()),
     };
 
     Ok(client
@@ -176,12 +544,15 @@ pub fn stop(
 }
 
 pub fn remove(
-    Remote { address, token, .. }: &Remote,
+    Remote { address, token, options, .. }: &Remote,
     id: usize,
+    purge_logs: bool,
 ) -> Result<sync::Response, anyhow::Error> {
-    let (client, headers) = sync::client(token);
+    let (client, headers) = sync::client(token, options)?;
     let content = ActionBody {
         method: string!("remove"),
+        purge_logs,
+        signal: None,
     };
 
     Ok(client
@@ -192,12 +563,14 @@ pub fn remove(
 }
 
 pub fn flush(
-    Remote { address, token, .. }: &Remote,
+    Remote { address, token, options, .. }: &Remote,
     id: usize,
 ) -> Result<sync::Response, anyhow::Error> {
-    let (client, headers) = sync::client(token);
+    let (client, headers) = sync::client(token, options)?;
     let content = ActionBody {
         method: string!("flush"),
+        purge_logs: false,
+        signal: None,
     };
 
     Ok(client
@@ -208,12 +581,14 @@ pub fn flush(
 }
 
 pub fn clear_env(
-    Remote { address, token, .. }: &Remote,
+    Remote { address, token, options, .. }: &Remote,
     id: usize,
 ) -> Result<sync::Response, anyhow::Error> {
-    let (client, headers) = sync::client(token);
+    let (client, headers) = sync::client(token, options)?;
     let content = ActionBody {
         method: string!("clear_env"),
+        purge_logs: false,
+        signal: None,
     };
 
     Ok(client
@@ -222,3 +597,33 @@ pub fn clear_env(
         .headers(headers)
         .send()?)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_rejects_missing_ca_cert() {
+        let options = ClientOptions {
+            ca_cert: Some("/nonexistent/opm-ca.pem".to_string()),
+            ..Default::default()
+        };
+
+        let err = build(&options).unwrap_err().to_string();
+        assert!(err.contains("unable to read ca certificate"));
+        // No default client that would skip the configured CA
+        assert!(sync::client(&None, &options).is_err());
+    }
+
+    #[test]
+    fn test_build_accepts_proxy_and_insecure() {
+        let options = ClientOptions {
+            proxy: Some("http://127.0.0.1:3128".to_string()),
+            ca_cert: None,
+            insecure: true,
+        };
+
+        assert!(build(&options).is_ok());
+        assert!(sync::build(&options).is_ok());
+    }
+}
@@ -0,0 +1,115 @@
+use super::Labels;
+use macros_rs::string;
+
+pub const MAX_LABELS: usize = 32;
+pub const MAX_KEY_LEN: usize = 63;
+pub const MAX_VALUE_LEN: usize = 255;
+
+/// Prefix added to label keys when exported as prometheus label names
+const METRIC_PREFIX: &str = "label_";
+
+pub fn validate_key(key: &str) -> Result<(), String> {
+    if key.is_empty() {
+        return Err(string!("label key cannot be empty"));
+    }
+
+    if key.len() > MAX_KEY_LEN {
+        return Err(format!("label key '{key}' is longer than {MAX_KEY_LEN} characters"));
+    }
+
+    if !key.chars().all(|c| matches!(c, 'a'..='z' | '0'..='9' | '_' | '.' | '-')) {
+        return Err(format!("label key '{key}' may only contain [a-z0-9_.-]"));
+    }
+
+    Ok(())
+}
+
+pub fn validate_value(key: &str, value: &str) -> Result<(), String> {
+    if value.len() > MAX_VALUE_LEN {
+        return Err(format!("value of label '{key}' is longer than {MAX_VALUE_LEN} characters"));
+    }
+
+    if value.chars().any(char::is_control) {
+        return Err(format!("value of label '{key}' contains control characters"));
+    }
+
+    Ok(())
+}
+
+pub fn validate(labels: &Labels) -> Result<(), String> {
+    if labels.len() > MAX_LABELS {
+        return Err(format!("a process can have at most {MAX_LABELS} labels"));
+    }
+
+    for (key, value) in labels {
+        validate_key(key)?;
+        validate_value(key, value)?;
+    }
+
+    Ok(())
+}
+
+/// Parse a `key=value` pair, used for both `--label` arguments and `?label=` queries
+pub fn parse(pair: &str) -> Result<(String, String), String> {
+    let Some((key, value)) = pair.split_once('=') else {
+        return Err(format!("invalid label '{pair}', expected key=value"));
+    };
+
+    let (key, value) = (key.trim(), value.trim());
+    validate_key(key)?;
+    validate_value(key, value)?;
+
+    Ok((key.to_string(), value.to_string()))
+}
+
+/// Every filter has to match (filters are ANDed)
+pub fn matches(labels: &Labels, filters: &[(String, String)]) -> bool {
+    filters.iter().all(|(key, value)| labels.get(key) == Some(value))
+}
+
+/// Human readable form, e.g. `env=prod, team=payments`
+pub fn describe(labels: &Labels) -> String {
+    labels
+        .iter()
+        .map(|(key, value)| format!("{key}={value}"))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Prometheus label name for a label key (`team.name` becomes `label_team_name`)
+pub fn metric_name(key: &str) -> String {
+    format!("{METRIC_PREFIX}{}", key.replace(['.', '-'], "_"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_label() {
+        assert_eq!(parse("team=payments"), Ok((string!("team"), string!("payments"))));
+        assert_eq!(parse("url=a=b"), Ok((string!("url"), string!("a=b"))));
+        assert!(parse("team").is_err());
+        assert!(parse("Team=payments").is_err());
+        assert!(parse("=payments").is_err());
+        assert!(parse(&format!("{}=x", "k".repeat(MAX_KEY_LEN + 1))).is_err());
+    }
+
+    #[test]
+    fn test_filters_are_anded() {
+        let labels = Labels::from([(string!("env"), string!("prod")), (string!("team"), string!("payments"))]);
+
+        assert!(matches(&labels, &[]));
+        assert!(matches(&labels, &[(string!("env"), string!("prod"))]));
+        assert!(matches(&labels, &[(string!("env"), string!("prod")), (string!("team"), string!("payments"))]));
+        assert!(!matches(&labels, &[(string!("env"), string!("prod")), (string!("team"), string!("search"))]));
+        assert!(!matches(&labels, &[(string!("tier"), string!("frontend"))]));
+    }
+
+    #[test]
+    fn test_metric_name() {
+        assert_eq!(metric_name("team"), "label_team");
+        assert_eq!(metric_name("app.kubernetes-name"), "label_app_kubernetes_name");
+        assert_eq!(metric_name("0tier"), "label_0tier");
+    }
+}
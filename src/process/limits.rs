@@ -0,0 +1,207 @@
+use super::Process;
+use macros_rs::string;
+use serde::{Deserialize, Serialize};
+
+/// Stored for `unlimited`, applied as RLIM_INFINITY
+pub const UNLIMITED: u64 = u64::MAX;
+
+/// Linux refuses more descriptors than `fs.nr_open`, whose default is 2^20
+pub const MAX_NOFILE: u64 = 1 << 20;
+
+/// Umask and resource limits a process is spawned with, unset ones are inherited from the daemon
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Limits {
+    pub umask: Option<u32>,
+    pub nofile: Option<u64>,
+    pub core: Option<u64>,
+}
+
+/// Parse an octal umask such as `0002` or `027`
+pub fn parse_umask(value: &str) -> Result<u32, String> {
+    let digits = value.trim();
+    match u32::from_str_radix(digits, 8) {
+        Ok(mask) if mask <= 0o777 && !digits.is_empty() => Ok(mask),
+        _ => Err(format!("invalid umask '{value}', expected octal digits up to 0777")),
+    }
+}
+
+/// Parse a descriptor limit, at least 1 and at most `MAX_NOFILE`
+pub fn parse_nofile(value: &str) -> Result<u64, String> {
+    match parse_limit(value)? {
+        0 => Err(string!("ulimit_nofile must be at least 1")),
+        limit if limit > MAX_NOFILE && limit != UNLIMITED => Err(format!("ulimit_nofile must be at most {MAX_NOFILE}")),
+        limit => Ok(limit),
+    }
+}
+
+/// Parse a core file size limit in bytes, 0 disables core dumps
+pub fn parse_core(value: &str) -> Result<u64, String> { parse_limit(value) }
+
+fn parse_limit(value: &str) -> Result<u64, String> {
+    match value.trim() {
+        "unlimited" => Ok(UNLIMITED),
+        limit => limit.parse::<u64>().ok().filter(|limit| *limit != UNLIMITED).ok_or_else(|| format!("invalid limit '{value}', expected a number or unlimited")),
+    }
+}
+
+pub fn format_umask(mask: u32) -> String { format!("{mask:04o}") }
+
+pub fn format_limit(limit: u64) -> String {
+    match limit {
+        UNLIMITED => string!("unlimited"),
+        limit => limit.to_string(),
+    }
+}
+
+impl Limits {
+    pub fn is_empty(&self) -> bool { *self == Limits::default() }
+
+    /// Human readable form, e.g. `umask 0002, nofile 65536`
+    pub fn describe(&self) -> String {
+        let mut parts: Vec<String> = self.umask.map(|mask| format!("umask {}", format_umask(mask))).into_iter().collect();
+        parts.extend(self.rlimits().into_iter().flatten().map(|(name, _, limit)| format!("{name} {}", format_limit(limit))));
+
+        match parts.is_empty() {
+            true => string!("none"),
+            false => parts.join(", "),
+        }
+    }
+
+    /// Raise the core limit as far as the hard limit allows, unless one is set explicitly
+    pub fn with_core_dumps(self) -> Limits {
+        Limits {
+            core: self.core.or_else(|| hard_limit(libc::RLIMIT_CORE).ok()),
+            ..self
+        }
+    }
+
+    /// Refuse limits above the hard limits of the calling process, which the child could not raise itself past
+    pub fn check(&self) -> Result<(), String> {
+        for (name, resource, limit) in self.rlimits().into_iter().flatten() {
+            let name = format!("ulimit_{name}");
+            let hard = hard_limit(resource)?;
+            if limit > hard {
+                return Err(format!("{name} {} exceeds the hard limit of {} available to the daemon", format_limit(limit), format_limit(hard)));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Apply the limits to the calling process. Only raw syscalls, so it is safe to call between fork and exec.
+    pub fn apply(&self) -> std::io::Result<()> {
+        if let Some(mask) = self.umask {
+            unsafe { libc::umask(mask as libc::mode_t) };
+        }
+
+        for (_, resource, limit) in self.rlimits().into_iter().flatten() {
+            let mut current = libc::rlimit { rlim_cur: 0, rlim_max: 0 };
+            if unsafe { libc::getrlimit(resource, &mut current) } != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+
+            // Only the soft limit is set, the process may still raise it up to the hard one
+            current.rlim_cur = to_rlim(limit);
+            if unsafe { libc::setrlimit(resource, &current) } != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The resource limits that are set, in an array since `apply` must not allocate
+    fn rlimits(&self) -> [Option<(&'static str, Resource, u64)>; 2] {
+        [
+            self.nofile.map(|limit| ("nofile", libc::RLIMIT_NOFILE, limit)),
+            self.core.map(|limit| ("core", libc::RLIMIT_CORE, limit)),
+        ]
+    }
+}
+
+impl Process {
+    pub fn limits(&self) -> Limits {
+        Limits {
+            umask: self.umask,
+            nofile: self.ulimit_nofile,
+            core: self.ulimit_core,
+        }
+    }
+
+    /// Limits the next spawn runs with, core dumps are enabled for `collect_cores`
+    pub fn spawn_limits(&self) -> Limits {
+        match self.collect_cores {
+            true => self.limits().with_core_dumps(),
+            false => self.limits(),
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+type Resource = libc::__rlimit_resource_t;
+#[cfg(not(target_os = "linux"))]
+type Resource = libc::c_int;
+
+fn to_rlim(limit: u64) -> libc::rlim_t {
+    match limit {
+        UNLIMITED => libc::RLIM_INFINITY,
+        limit => limit,
+    }
+}
+
+fn hard_limit(resource: Resource) -> Result<u64, String> {
+    let mut limit = libc::rlimit { rlim_cur: 0, rlim_max: 0 };
+
+    match unsafe { libc::getrlimit(resource, &mut limit) } {
+        0 if limit.rlim_max == libc::RLIM_INFINITY => Ok(UNLIMITED),
+        0 => Ok(limit.rlim_max),
+        _ => Err(format!("cannot read resource limits: {}", std::io::Error::last_os_error())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_umask() {
+        assert_eq!(parse_umask("0002"), Ok(0o002));
+        assert_eq!(parse_umask("027"), Ok(0o027));
+        assert!(parse_umask("0008").is_err());
+        assert!(parse_umask("1777").is_err());
+        assert!(parse_umask("").is_err());
+        assert_eq!(format_umask(0o002), "0002");
+    }
+
+    #[test]
+    fn test_parse_limits() {
+        assert_eq!(parse_nofile("65536"), Ok(65536));
+        assert_eq!(parse_nofile("unlimited"), Ok(UNLIMITED));
+        assert!(parse_nofile("0").is_err());
+        assert!(parse_nofile("2000000").is_err());
+        assert!(parse_nofile("-1").is_err());
+        assert_eq!(parse_core("0"), Ok(0));
+        assert_eq!(format_limit(UNLIMITED), "unlimited");
+    }
+
+    #[test]
+    fn test_check_against_hard_limit() {
+        let hard = hard_limit(libc::RLIMIT_CORE).unwrap();
+        assert!(Limits { core: Some(0), ..Default::default() }.check().is_ok());
+
+        if hard != UNLIMITED {
+            let err = Limits { core: Some(UNLIMITED), ..Default::default() }.check().unwrap_err();
+            assert!(err.contains("ulimit_core unlimited exceeds the hard limit"));
+        }
+    }
+
+    #[test]
+    fn test_describe() {
+        let limits = Limits { umask: Some(0o002), nofile: Some(4096), core: Some(UNLIMITED) };
+        assert_eq!(limits.describe(), "umask 0002, nofile 4096, core unlimited");
+        assert_eq!(Limits::default().describe(), "none");
+        assert_eq!(limits.with_core_dumps(), limits);
+        assert!(Limits::default().with_core_dumps().core.is_some());
+        assert!(Limits::default().is_empty());
+    }
+}
@@ -0,0 +1,126 @@
+//! Logs left in another directory when `runner.log_path` changes. Each process records the directory it was
+//! started with and is read from there until `opm migrate-logs` moves its files to the current one.
+
+use super::{Process, Runner, checkpoint, rotate};
+
+use std::{
+    fs, io,
+    os::unix,
+    path::{Path, PathBuf},
+};
+
+/// How `opm migrate-logs` carries the files over
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Mode {
+    /// Rename the files, or copy and remove them across filesystems
+    Move,
+    /// Leave the files where they are and link to them from the new directory
+    Symlink,
+}
+
+/// Processes whose logs are in another directory than `current`, with that directory
+pub fn stale<'r>(runner: &'r Runner, current: &str) -> Vec<(usize, &'r str)> {
+    runner
+        .list
+        .iter()
+        .filter_map(|(id, process)| process.log_path.as_deref().filter(|dir| Path::new(dir) != Path::new(current)).map(|dir| (*id, dir)))
+        .collect()
+}
+
+/// A log with its rotated archives and offset index, those that exist
+fn files(log: &str) -> Vec<PathBuf> {
+    let log = Path::new(log);
+    let mut files = vec![log.to_path_buf()];
+    files.extend(rotate::archives(log));
+    files.push(PathBuf::from(checkpoint::path(&log.to_string_lossy())));
+
+    files.into_iter().filter(|file| file.symlink_metadata().is_ok()).collect()
+}
+
+fn relocate(from: &Path, to: &Path, mode: Mode) -> io::Result<()> {
+    match mode {
+        Mode::Symlink => unix::fs::symlink(from, to),
+        Mode::Move => fs::rename(from, to).or_else(|_| {
+            fs::copy(from, to)?;
+            fs::remove_file(from)
+        }),
+    }
+}
+
+/// Carry the log files of `process` over to `dir`, returning where each one went.
+/// Nothing is touched when one of them is already taken in `dir`.
+pub fn migrate(process: &Process, dir: &str, mode: Mode) -> Result<Vec<(PathBuf, PathBuf)>, String> {
+    let logs = process.logs();
+    let moves: Vec<(PathBuf, PathBuf)> = [logs.out, logs.error]
+        .into_iter()
+        .flat_map(|log| files(&log))
+        .filter_map(|file| Some((file.clone(), Path::new(dir).join(file.file_name()?))))
+        .collect();
+
+    if let Some((_, taken)) = moves.iter().find(|(_, target)| target.symlink_metadata().is_ok()) {
+        return Err(format!("{} already exists, move it out of the way first", taken.display()));
+    }
+
+    fs::create_dir_all(dir).map_err(|err| format!("cannot create {dir}: {err}"))?;
+    for (file, target) in &moves {
+        relocate(file, target, mode).map_err(|err| format!("cannot carry {} over to {}: {err}", file.display(), target.display()))?;
+    }
+
+    Ok(moves)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use macros_rs::string;
+
+    fn process(dir: &Path) -> Process {
+        Process {
+            name: string!("api server"),
+            script: string!("node index.js"),
+            path: PathBuf::from("/srv"),
+            log_path: Some(dir.display().to_string()),
+            ..Default::default()
+        }
+    }
+
+    fn dirs(name: &str) -> (PathBuf, PathBuf) {
+        let base = std::env::temp_dir().join(format!("opm-logdir-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&base);
+        let (old, new) = (base.join("old"), base.join("new"));
+        fs::create_dir_all(&old).unwrap();
+
+        fs::write(old.join("api_server-out.log"), "out\n").unwrap();
+        fs::write(old.join("api_server-out.log.1"), "rotated\n").unwrap();
+        fs::write(old.join("api_server-error.log"), "error\n").unwrap();
+        (old, new)
+    }
+
+    #[test]
+    fn test_move() {
+        let (old, new) = dirs("move");
+        let moves = migrate(&process(&old), &new.to_string_lossy(), Mode::Move).unwrap();
+
+        assert_eq!(moves.len(), 3);
+        assert_eq!(fs::read_to_string(new.join("api_server-out.log.1")).unwrap(), "rotated\n");
+        assert!(!old.join("api_server-error.log").exists());
+        let _ = fs::remove_dir_all(old.parent().unwrap());
+    }
+
+    #[test]
+    fn test_symlink_and_taken() {
+        let (old, new) = dirs("symlink");
+        fs::create_dir_all(&new).unwrap();
+        fs::write(new.join("api_server-error.log"), "newer\n").unwrap();
+
+        // A file already in the new directory stops the migration before anything moves
+        assert!(migrate(&process(&old), &new.to_string_lossy(), Mode::Symlink).unwrap_err().contains("already exists"));
+        assert!(!new.join("api_server-out.log").exists());
+
+        fs::remove_file(new.join("api_server-error.log")).unwrap();
+        migrate(&process(&old), &new.to_string_lossy(), Mode::Symlink).unwrap();
+        assert_eq!(fs::read_to_string(new.join("api_server-out.log")).unwrap(), "out\n");
+        assert!(old.join("api_server-out.log").exists());
+        let _ = fs::remove_dir_all(old.parent().unwrap());
+    }
+}
@@ -0,0 +1,261 @@
+//! `--max-log-rate`, a guard against a process writing its logs faster than rotation can keep the disk from filling.
+//! The daemon compares the log sizes at each check and acts once the rate stayed over the limit for `BREACHES` checks.
+
+use super::{Process, alerts};
+use crate::helpers;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use std::{
+    collections::HashMap,
+    fmt, fs,
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+/// Consecutive checks over the limit before the action is taken
+pub const BREACHES: u32 = 3;
+
+/// Windows shorter than this are too noisy to give a rate
+const MIN_WINDOW: Duration = Duration::from_millis(100);
+
+/// What the daemon does once a process logged too fast
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Action {
+    /// Record an event and send a `process_alert` notification
+    #[default]
+    Warn,
+    Restart,
+    Stop,
+}
+
+impl FromStr for Action {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "warn" => Ok(Action::Warn),
+            "restart" => Ok(Action::Restart),
+            "stop" => Ok(Action::Stop),
+            value => Err(format!("Unknown log rate action '{value}', expected warn, restart or stop")),
+        }
+    }
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Action::Warn => write!(f, "warn"),
+            Action::Restart => write!(f, "restart"),
+            Action::Stop => write!(f, "stop"),
+        }
+    }
+}
+
+/// `<size>/<s|min|h> [warn|restart|stop]`, e.g. `100M/min restart`, stored as written
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct MaxLogRate {
+    pub bytes: u64,
+    /// Seconds `bytes` are allowed in
+    pub per: u64,
+    pub action: Action,
+}
+
+impl MaxLogRate {
+    /// The limit in bytes per minute, the unit rates are measured in
+    pub fn per_minute(&self) -> u64 { self.bytes.saturating_mul(60) / self.per }
+}
+
+impl FromStr for MaxLogRate {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid log rate '{value}', expected '<size>/<s|min|h> [warn|restart|stop]', e.g. 100M/min");
+        let words: Vec<&str> = value.split_whitespace().collect();
+
+        let (rate, action) = match words.as_slice() {
+            [rate] => (rate, Action::default()),
+            [rate, action] => (rate, action.parse()?),
+            _ => return Err(invalid()),
+        };
+
+        let (size, unit) = rate.split_once('/').ok_or_else(invalid)?;
+        let per = match unit {
+            "s" | "sec" => 1,
+            "m" | "min" => 60,
+            "h" | "hour" => 3600,
+            _ => return Err(invalid()),
+        };
+
+        match helpers::parse_memory(size)? {
+            0 => Err(String::from("A log rate of 0 would act on any output, use --no-max-log-rate to remove it")),
+            bytes => Ok(MaxLogRate { bytes, per, action }),
+        }
+    }
+}
+
+impl fmt::Display for MaxLogRate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let unit = match self.per {
+            1 => "s",
+            3600 => "h",
+            _ => "min",
+        };
+
+        write!(f, "{}/{unit}", alerts::format_bytes(self.bytes))?;
+        match self.action {
+            Action::Warn => Ok(()),
+            action => write!(f, " {action}"),
+        }
+    }
+}
+
+impl TryFrom<String> for MaxLogRate {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> { value.parse() }
+}
+
+impl From<MaxLogRate> for String {
+    fn from(rate: MaxLogRate) -> Self { rate.to_string() }
+}
+
+/// Clap parser for `--max-log-rate 100M/min`
+pub fn parse(value: &str) -> Result<MaxLogRate, String> { value.parse() }
+
+/// Sizes of the out and error log of a process, 0 for one that does not exist yet
+pub fn sizes(process: &Process) -> [u64; 2] {
+    let logs = process.logs();
+    [&logs.out, &logs.error].map(|log| fs::metadata(log).map_or(0, |meta| meta.len()))
+}
+
+/// Bytes per minute written between two readings `secs` apart. A log that shrank was rotated
+/// or flushed meanwhile and counts as nothing written, rather than as a negative rate.
+pub fn rate(before: [u64; 2], after: [u64; 2], secs: f64) -> u64 {
+    let written: u64 = after.iter().zip(before).map(|(after, before)| after.saturating_sub(before)).sum();
+    match secs > 0.0 {
+        true => (written as f64 * 60.0 / secs) as u64,
+        false => 0,
+    }
+}
+
+/// Run `sample`, which waits out a sampling window, and measure how fast the logs grew meanwhile.
+/// The rate is None when the window was too short to tell.
+pub fn during<T>(process: &Process, sample: impl FnOnce() -> T) -> (T, Option<u64>) {
+    let (before, start) = (sizes(process), Instant::now());
+    let value = sample();
+    let elapsed = start.elapsed();
+
+    (value, (elapsed >= MIN_WINDOW).then(|| rate(before, sizes(process), elapsed.as_secs_f64())))
+}
+
+/// A log rate as shown by `opm details`
+pub fn format(rate: u64) -> String { format!("{}/min", helpers::format_memory(rate)) }
+
+struct Reading {
+    sizes: [u64; 2],
+    at: DateTime<Utc>,
+    over: u32,
+    acted: bool,
+}
+
+/// Log sizes each process had at its last check, and for how many checks it has been over its limit
+#[derive(Default)]
+pub struct Tracker {
+    readings: HashMap<usize, Reading>,
+}
+
+impl Tracker {
+    /// Feed the current log sizes of a process, returning the rate once it was over `limit` for
+    /// `BREACHES` checks in a row. That happens once per breach, the first check only takes a reading.
+    pub fn check(&mut self, id: usize, limit: &MaxLogRate, sizes: [u64; 2], now: DateTime<Utc>) -> Option<u64> {
+        let Some(reading) = self.readings.get_mut(&id) else {
+            self.readings.insert(id, Reading { sizes, at: now, over: 0, acted: false });
+            return None;
+        };
+
+        let secs = (now - reading.at).num_milliseconds() as f64 / 1000.0;
+        let rate = rate(reading.sizes, sizes, secs);
+        (reading.sizes, reading.at) = (sizes, now);
+
+        if rate <= limit.per_minute() {
+            (reading.over, reading.acted) = (0, false);
+            return None;
+        }
+
+        reading.over += 1;
+        match reading.acted || reading.over < BREACHES {
+            true => None,
+            false => {
+                reading.acted = true;
+                Some(rate)
+            }
+        }
+    }
+
+    /// Drop the readings of a process, when it stopped, was restarted or removed
+    pub fn forget(&mut self, id: usize) { self.readings.remove(&id); }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeDelta;
+
+    const MB: u64 = 1024 * 1024;
+
+    fn limit(value: &str) -> MaxLogRate { value.parse().unwrap() }
+
+    #[test]
+    fn test_parse_rate() {
+        assert_eq!(limit("100M/min"), MaxLogRate { bytes: 100 * MB, per: 60, action: Action::Warn });
+        assert_eq!(limit("1G/h stop").action, Action::Stop);
+        assert_eq!(limit("512K/s restart").per_minute(), 512 * 1024 * 60);
+
+        assert!("100M".parse::<MaxLogRate>().is_err());
+        assert!("100M/day".parse::<MaxLogRate>().is_err());
+        assert!("100M/min kill".parse::<MaxLogRate>().unwrap_err().contains("warn, restart or stop"));
+        assert!("0/min".parse::<MaxLogRate>().is_err());
+
+        for value in ["100M/min", "1G/h stop", "512K/s restart"] {
+            assert_eq!(limit(value).to_string(), value);
+        }
+    }
+
+    #[test]
+    fn test_shrinking_logs_count_as_nothing() {
+        assert_eq!(rate([0, 0], [MB, 0], 60.0), MB);
+        // The out log was rotated, only the error log grew
+        assert_eq!(rate([50 * MB, 10], [2, 1034], 30.0), 2048);
+        assert_eq!(rate([MB, MB], [0, 0], 10.0), 0);
+        assert_eq!(rate([0, 0], [MB, 0], 0.0), 0);
+    }
+
+    #[test]
+    fn test_tracker_acts_after_consecutive_breaches() {
+        let mut tracker = Tracker::default();
+        let rule = limit("10M/min");
+        let start = Utc::now();
+        let at = |secs: i64| start + TimeDelta::seconds(secs);
+        let size = |mb: u64| [mb * MB, 0];
+
+        assert_eq!(tracker.check(0, &rule, size(0), at(0)), None);
+        assert_eq!(tracker.check(0, &rule, size(20), at(60)), None);
+        assert_eq!(tracker.check(0, &rule, size(40), at(120)), None);
+        assert_eq!(tracker.check(0, &rule, size(60), at(180)), Some(20 * MB));
+        assert_eq!(tracker.check(0, &rule, size(80), at(240)), None);
+
+        // A quiet check, or a rotation, starts the count over
+        assert_eq!(tracker.check(0, &rule, size(81), at(300)), None);
+        assert_eq!(tracker.check(0, &rule, size(100), at(360)), None);
+        assert_eq!(tracker.check(0, &rule, size(1), at(420)), None);
+        assert_eq!(tracker.check(0, &rule, size(20), at(480)), None);
+        assert_eq!(tracker.check(0, &rule, size(40), at(540)), None);
+        assert_eq!(tracker.check(0, &rule, size(60), at(600)), Some(20 * MB));
+
+        tracker.forget(0);
+        assert_eq!(tracker.check(0, &rule, size(500), at(660)), None);
+    }
+}
@@ -0,0 +1,91 @@
+//! Free space on the filesystem holding the process logs.
+//! A full one keeps processes from starting, they are then errored instead of counted as crashed.
+
+use crate::helpers;
+use serde::{Deserialize, Serialize};
+use std::{ffi::CString, io};
+use utoipa::ToSchema;
+
+/// Reason of a process that could not start because its logs could not be written
+pub const FULL: &str = "log filesystem full";
+
+/// Below this share of free space `opm daemon health` warns
+pub const WARN_PERCENT: f64 = 5.0;
+
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, PartialEq)]
+pub struct Space {
+    #[schema(example = "/root/.opm/logs")]
+    pub path: String,
+    /// Bytes an unprivileged process can still write
+    #[schema(example = 1073741824)]
+    pub free: u64,
+    #[schema(example = 21474836480_u64)]
+    pub total: u64,
+}
+
+impl Space {
+    /// None where `path` does not exist or the filesystem cannot be asked
+    pub fn new(path: &str) -> Option<Space> {
+        let c_path = CString::new(path).ok()?;
+        let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+
+        if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } != 0 {
+            return None;
+        }
+
+        let block = stat.f_frsize as u64;
+        Some(Space {
+            path: path.to_string(),
+            free: stat.f_bavail as u64 * block,
+            total: stat.f_blocks as u64 * block,
+        })
+    }
+
+    pub fn percent_free(&self) -> f64 {
+        match self.total {
+            0 => 100.0,
+            total => self.free as f64 / total as f64 * 100.0,
+        }
+    }
+
+    pub fn full(&self) -> bool { self.free == 0 }
+
+    pub fn low(&self) -> bool { self.percent_free() < WARN_PERCENT }
+
+    /// `1.2gb free of 20gb (6%)` for the health table
+    pub fn describe(&self) -> String { format!("{} free of {} ({:.0}%)", helpers::format_memory(self.free), helpers::format_memory(self.total), self.percent_free()) }
+}
+
+/// Whether `path` is on a filesystem with no space left
+pub fn full(path: &str) -> bool { Space::new(path).is_some_and(|space| space.full()) }
+
+pub fn is_enospc(err: &io::Error) -> bool { err.raw_os_error() == Some(libc::ENOSPC) }
+
+/// Whether a start failed with `FULL`, see `process_run`
+pub fn is_full(error: &str) -> bool { error.starts_with(FULL) }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_space() {
+        let space = Space::new("/").unwrap();
+        assert!(space.total > 0 && space.free <= space.total);
+        assert_eq!(Space::new("/does/not/exist"), None);
+        assert!(!full("/does/not/exist"));
+
+        let space = Space { path: String::from("/logs"), free: 512 * 1024 * 1024, total: 20 * 1024 * 1024 * 1024 };
+        assert!(space.low() && !space.full());
+        assert_eq!(space.describe(), "512mb free of 20gb (2%)");
+        assert!(Space { free: 0, ..space }.full());
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(is_enospc(&io::Error::from_raw_os_error(libc::ENOSPC)));
+        assert!(!is_enospc(&io::Error::from_raw_os_error(libc::EACCES)));
+        assert!(is_full(&format!("{FULL}: cannot open '/logs/api-out.log'")));
+        assert!(!is_full("Failed to open stdout log file"));
+    }
+}
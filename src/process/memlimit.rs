@@ -0,0 +1,51 @@
+//! `--max-memory-action`, what the daemon does with an online process whose memory went over `--max-memory`.
+//! `restart` reloads it the way a watch change does and counts the restart in `memory_restarts`.
+
+use serde::{Deserialize, Serialize};
+use std::{fmt, str::FromStr};
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    /// Stopped for good, it is not counted as a crash
+    #[default]
+    Stop,
+    Restart,
+}
+
+impl FromStr for Action {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim() {
+            "stop" => Ok(Action::Stop),
+            "restart" => Ok(Action::Restart),
+            value => Err(format!("Unknown memory limit action '{value}', expected stop or restart")),
+        }
+    }
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Action::Stop => write!(f, "stop"),
+            Action::Restart => write!(f, "restart"),
+        }
+    }
+}
+
+/// Clap parser for `--max-memory-action restart`
+pub fn parse(value: &str) -> Result<Action, String> { value.parse() }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(parse("restart"), Ok(Action::Restart));
+        assert_eq!(parse(" stop "), Ok(Action::Stop));
+        assert!(parse("kill").unwrap_err().contains("expected stop or restart"));
+        assert_eq!(Action::default().to_string(), "stop");
+    }
+}
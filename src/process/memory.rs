@@ -0,0 +1,222 @@
+//! Processes that only exist in memory, for testing code that starts, stops and restarts them.
+//! Nothing is written to disk and no command is run, see `Runner::with_backend`.
+
+use super::{ProcessMetadata, ProcessRunResult, Runner, backend::RunnerBackend, id::Id};
+use nix::sys::signal::Signal;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
+    sync::{Mutex, MutexGuard},
+    time::Duration,
+};
+
+/// First pid handed out, above any `pid_max` so a signal sent to one by mistake reaches nothing
+pub const FIRST_PID: i64 = 10_000_000;
+
+/// What a simulated process does once it is started
+#[derive(Debug, Clone, PartialEq)]
+pub enum Lifetime {
+    /// Runs until it is stopped, or crashed with `InMemoryBackend::crash`
+    Runs,
+    /// Runs like `Runs` but ignores every signal other than SIGKILL
+    IgnoresTerm,
+    /// Completes right away with this exit code
+    Exits(i32),
+    /// Cannot be started, the start fails with this error
+    Fails(String),
+}
+
+/// A start the backend was asked for, failed ones included with a pid of 0
+#[derive(Debug, Clone, PartialEq)]
+pub struct Spawned {
+    pub pid: i64,
+    pub name: String,
+    /// Shell and arguments the command was run with
+    pub shell: String,
+    pub args: Vec<String>,
+    pub command: String,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    dump: Option<String>,
+    next_pid: i64,
+    lifetimes: HashMap<String, VecDeque<Lifetime>>,
+    alive: HashSet<i64>,
+    stubborn: HashSet<i64>,
+    spawned: Vec<Spawned>,
+    stopped: Vec<i64>,
+    signals: Vec<Signal>,
+}
+
+#[derive(Debug)]
+pub struct InMemoryBackend {
+    state: Mutex<State>,
+}
+
+impl Default for InMemoryBackend {
+    fn default() -> Self { InMemoryBackend::new() }
+}
+
+impl InMemoryBackend {
+    pub fn new() -> Self {
+        InMemoryBackend {
+            state: Mutex::new(State { next_pid: FIRST_PID, ..State::default() }),
+        }
+    }
+
+    /// Hand out pids counting up from `pid`
+    pub fn with_pids(self, pid: i64) -> Self {
+        self.lock().next_pid = pid;
+        self
+    }
+
+    /// The next starts of `name` go through `lifetimes` in order, the last one then stays.
+    /// Processes without a script keep running.
+    pub fn script(&self, name: &str, lifetimes: impl IntoIterator<Item = Lifetime>) {
+        self.lock().lifetimes.insert(name.to_string(), lifetimes.into_iter().collect());
+    }
+
+    /// `pid` dies on its own, as a crashed process would
+    pub fn crash(&self, pid: i64) { self.lock().alive.remove(&pid); }
+
+    pub fn spawned(&self) -> Vec<Spawned> { self.lock().spawned.clone() }
+
+    /// Pids the runner asked to stop, in order
+    pub fn stopped(&self) -> Vec<i64> { self.lock().stopped.clone() }
+
+    /// Signals of the stops in `stopped`, in the same order
+    pub fn signals(&self) -> Vec<Signal> { self.lock().signals.clone() }
+
+    fn lock(&self) -> MutexGuard<'_, State> { self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) }
+}
+
+impl RunnerBackend for InMemoryBackend {
+    fn read(&self) -> Runner {
+        let empty = || Runner {
+            id: Id::new(0),
+            list: BTreeMap::new(),
+            remote: None,
+            host: None,
+            backend: Default::default(),
+        };
+
+        match &self.lock().dump {
+            Some(dump) => serde_json::from_str(dump).unwrap_or_else(|_| empty()),
+            None => empty(),
+        }
+    }
+
+    fn write(&self, runner: &Runner) { self.lock().dump = serde_json::to_string(runner).ok(); }
+
+    fn run(&self, metadata: ProcessMetadata) -> Result<ProcessRunResult, String> {
+        let mut state = self.lock();
+
+        let lifetime = match state.lifetimes.get_mut(&metadata.name) {
+            Some(lifetimes) if lifetimes.len() > 1 => lifetimes.pop_front(),
+            Some(lifetimes) => lifetimes.front().cloned(),
+            None => None,
+        };
+
+        let mut spawned = Spawned { pid: 0, name: metadata.name, shell: metadata.shell, args: metadata.args, command: metadata.command };
+        if let Some(Lifetime::Fails(err)) = lifetime {
+            state.spawned.push(spawned);
+            return Err(err);
+        }
+
+        spawned.pid = state.next_pid;
+        state.next_pid += 1;
+        state.spawned.push(spawned.clone());
+
+        match lifetime {
+            Some(Lifetime::Exits(code)) => Ok(ProcessRunResult { pid: spawned.pid, shell_pid: None, exit_code: Some(code) }),
+            _ => {
+                if lifetime == Some(Lifetime::IgnoresTerm) {
+                    state.stubborn.insert(spawned.pid);
+                }
+                state.alive.insert(spawned.pid);
+                Ok(ProcessRunResult { pid: spawned.pid, shell_pid: None, exit_code: None })
+            }
+        }
+    }
+
+    fn stop(&self, pid: i64, signal: Signal) -> Result<(), String> {
+        let mut state = self.lock();
+        if signal == Signal::SIGKILL || !state.stubborn.contains(&pid) {
+            state.alive.remove(&pid);
+        }
+        state.stopped.push(pid);
+        state.signals.push(signal);
+        Ok(())
+    }
+
+    fn alive(&self, pid: i64) -> bool { self.lock().alive.contains(&pid) }
+
+    /// Answers right away, a simulated process never exits later on its own
+    fn wait(&self, pid: i64, _timeout: Duration) -> bool { !self.alive(pid) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::structs::LogsOnEnospc, process::backend::Backend};
+    use std::sync::Arc;
+
+    fn metadata(name: &str) -> ProcessMetadata {
+        ProcessMetadata {
+            name: name.to_string(),
+            shell: String::from("/bin/sh"),
+            command: String::from("node index.js"),
+            log_path: String::new(),
+            args: vec![],
+            env: vec![],
+            limits: Default::default(),
+            supervised: false,
+            logs_on_enospc: LogsOnEnospc::Fail,
+        }
+    }
+
+    #[test]
+    fn test_scripted_lifetimes() {
+        let backend = InMemoryBackend::new().with_pids(500);
+        backend.script("worker", [Lifetime::Fails(String::from("no such file")), Lifetime::Exits(2)]);
+
+        assert_eq!(backend.run(metadata("worker")).unwrap_err(), "no such file");
+        assert_eq!(backend.run(metadata("worker")).unwrap().exit_code, Some(2));
+        assert_eq!(backend.run(metadata("worker")).unwrap().exit_code, Some(2), "The last lifetime stays");
+        assert!(!backend.alive(500) && !backend.alive(501));
+
+        let api = backend.run(metadata("api")).unwrap();
+        assert_eq!((api.pid, api.exit_code), (502, None));
+        assert!(backend.alive(502));
+
+        let pids: Vec<i64> = backend.spawned().iter().map(|spawned| spawned.pid).collect();
+        assert_eq!(pids, vec![0, 500, 501, 502]);
+    }
+
+    #[test]
+    fn test_crash_and_stop() {
+        let backend = InMemoryBackend::new();
+        let first = backend.run(metadata("api")).unwrap().pid;
+        let second = backend.run(metadata("api")).unwrap().pid;
+
+        backend.crash(first);
+        assert!(!backend.alive(first) && backend.wait(first, Duration::ZERO));
+        assert!(backend.stopped().is_empty(), "A crash is not a stop");
+
+        assert_eq!(backend.stop(second, Signal::SIGINT), Ok(()));
+        assert!(!backend.alive(second));
+        assert_eq!(backend.stopped(), vec![second]);
+        assert_eq!(backend.signals(), vec![Signal::SIGINT]);
+    }
+
+    #[test]
+    fn test_dump_is_kept_in_memory() {
+        let backend = Backend::new(Arc::new(InMemoryBackend::new()));
+        assert!(backend.read().list.is_empty());
+
+        let runner = backend.read();
+        runner.id.next();
+        backend.write(&runner);
+        assert_eq!(backend.read().id.next(), 1);
+    }
+}
@@ -1,23 +1,69 @@
+pub mod adopt;
+pub mod alerts;
+pub mod archive;
+pub mod backend;
+pub mod checkpoint;
+pub mod clock;
+pub mod collision;
+pub mod cores;
+pub mod counters;
+pub mod cron;
+pub mod description;
 pub mod dump;
+pub mod environment;
+pub mod fingerprint;
+pub mod group;
 pub mod hash;
+pub mod health;
+pub mod host;
 pub mod http;
 pub mod id;
+pub mod labels;
+pub mod limits;
+pub mod logdir;
+pub mod lograte;
+pub mod logspace;
+pub mod memlimit;
+#[cfg(any(test, feature = "test-util"))]
+pub mod memory;
+pub mod page;
+pub mod pending;
+pub mod rotate;
+pub mod search;
+pub mod shell;
+pub mod shutdown;
+pub mod signal;
+pub mod snapshots;
+pub mod stats;
+pub mod supervise;
+pub mod template;
 pub mod unix;
 
-use crate::{config, config::structs::Server, file, helpers};
+use self::backend::{Backend, RunnerBackend};
+
+use crate::{
+    alert, audit, config, events,
+    config::structs::{ClientOptions, LogsOnEnospc, Server},
+    file, helpers, notice,
+    notifications::history::{self, History, NotificationHistory},
+};
 
 use std::{
     collections::{BTreeMap, HashSet},
     env,
-    fs::File,
+    fs::OpenOptions,
+    io,
     path::PathBuf,
-    sync::{Arc, Mutex},
+    sync::{Arc, Mutex, PoisonError, RwLock, RwLockReadGuard},
     thread,
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 use nix::{
-    sys::signal::{Signal, kill},
+    sys::{
+        signal::{Signal, kill},
+        wait::{WaitPidFlag, WaitStatus, waitpid},
+    },
     unistd::Pid,
 };
 
@@ -29,16 +75,62 @@ use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 
 // Constants for process termination waiting
-const MAX_TERMINATION_WAIT_ATTEMPTS: u32 = 50;
 const TERMINATION_CHECK_INTERVAL_MS: u64 = 100;
+/// Time SIGKILL gets to take effect once a process outlived `daemon.kill_timeout`
+const KILL_WAIT: Duration = Duration::from_secs(1);
+
+// Grace period in seconds to wait after process start before checking for crashes
+// This prevents false crash detection when shell processes haven't spawned children yet
+// Reduced to 1 second to allow faster detection of immediately-crashing processes
+pub const STARTUP_GRACE_PERIOD_SECS: i64 = 1;
+
+/// Cycles a changed children list may wait before it is saved, see `Process::children_due`
+pub const CHILDREN_SAVE_CYCLES: u32 = 10;
+
+/// Shortest `monitor_interval_ms` accepted, checks any faster cost more cpu than they save
+pub const MIN_MONITOR_INTERVAL_MS: u64 = 100;
+
+/// Check a per-process monitoring interval in milliseconds against `MIN_MONITOR_INTERVAL_MS`
+pub fn validate_monitor_interval(ms: u64) -> Result<u64, String> {
+    match ms >= MIN_MONITOR_INTERVAL_MS {
+        true => Ok(ms),
+        false => Err(format!("Monitor interval {ms}ms is below the minimum of {MIN_MONITOR_INTERVAL_MS}ms")),
+    }
+}
+
+/// Seconds a watch reload holds back further changes of the watched path
+pub const DEFAULT_WATCH_COOLDOWN: u64 = 5;
+
+/// Processes a runner keeps without `runner.max_processes`
+pub const DEFAULT_MAX_PROCESSES: usize = 500;
+/// Share of `runner.max_processes` in percent past which the daemon warns
+pub const MAX_PROCESSES_WARN_PERCENT: usize = 80;
+
+/// `runner.max_processes`, or `DEFAULT_MAX_PROCESSES` when it is not set
+pub fn max_processes(config: &config::structs::Runner) -> usize { config.max_processes.unwrap_or(DEFAULT_MAX_PROCESSES) }
+
+/// Whether `count` processes are past `MAX_PROCESSES_WARN_PERCENT` of `limit`
+pub fn near_max_processes(count: usize, limit: usize) -> bool { count * 100 > limit * MAX_PROCESSES_WARN_PERCENT }
+
+/// Delay before restarting a process after `crashes` crashes in a row, `base_ms` doubled with each one up to `max_ms`
+pub fn restart_backoff(crashes: u64, base_ms: u64, max_ms: u64) -> chrono::Duration {
+    let factor = 1u64 << crashes.min(63);
+    chrono::Duration::milliseconds(base_ms.saturating_mul(factor).min(max_ms).min(i64::MAX as u64) as i64)
+}
+
+/// Parse `--monitor-interval`, a number of milliseconds
+pub fn parse_monitor_interval(value: &str) -> Result<u64, String> {
+    let ms = value.trim().parse::<u64>().map_err(|_| format!("Invalid monitor interval '{value}': expected milliseconds, e.g. 250"))?;
+    validate_monitor_interval(ms)
+}
 
 /// Wait for a process to terminate gracefully
 /// Uses libc::kill(pid, 0) to check if process exists, which is the same approach
 /// as pid::running() but implemented here to avoid circular dependencies.
 /// This is more reliable than trying to create a process handle that could fail
 /// for other reasons (permissions, etc.)
-/// Returns true if process terminated, false if timeout reached
-fn wait_for_process_termination(pid: i64) -> bool {
+/// Returns true if process terminated, false if `timeout` was reached
+fn wait_for_process_termination(pid: i64, timeout: Duration) -> bool {
     // Don't wait for invalid PIDs - they're already "terminated"
     // PID 0 signals all processes in current process group (not a specific process)
     // Negative PIDs signal process groups (not individual processes)
@@ -48,16 +140,46 @@ fn wait_for_process_termination(pid: i64) -> bool {
         return true;
     }
     
-    for _ in 0..MAX_TERMINATION_WAIT_ATTEMPTS {
+    let deadline = Instant::now() + timeout;
+    loop {
         // Check if process is still running using libc::kill with signal 0
         // This returns 0 if the process exists, -1 if it doesn't (or permission denied)
         let process_exists = unsafe { libc::kill(pid as i32, 0) == 0 };
         if !process_exists {
             return true; // Process has terminated (or we don't have permission to check)
         }
+        if Instant::now() >= deadline {
+            return false; // Timeout reached, process is still running
+        }
         thread::sleep(Duration::from_millis(TERMINATION_CHECK_INTERVAL_MS));
     }
-    false // Timeout reached, process is still running
+}
+
+/// Wait for processes that were asked to stop, all of them against the same `kill_timeout` so their
+/// shutdowns overlap. The ones still running then are killed along with their children so they cannot keep
+/// holding ports. False when one even outlived SIGKILL.
+fn await_stop(backend: &Backend, pids: &[i64], kill_timeout: Duration, action: &str) -> bool {
+    let stubborn = still_running(backend, pids, kill_timeout);
+
+    for &pid in &stubborn {
+        log::warn!("Process {pid} did not terminate within timeout during {action}, sending SIGKILL");
+        kill_children(process_find_children(pid), Signal::SIGKILL);
+        if let Err(err) = backend.stop(pid, Signal::SIGKILL) {
+            log::warn!("Failed to kill process {pid} during {action}: {err}");
+        }
+    }
+
+    let survivors = still_running(backend, &stubborn, KILL_WAIT);
+    for pid in &survivors {
+        log::error!("Process {pid} is still running after SIGKILL during {action}");
+    }
+    survivors.is_empty()
+}
+
+/// The pids of `pids` that did not exit before `timeout`, each one waited on until the same deadline
+fn still_running(backend: &Backend, pids: &[i64], timeout: Duration) -> Vec<i64> {
+    let deadline = Instant::now() + timeout;
+    pids.iter().copied().filter(|&pid| !backend.wait(pid, deadline.saturating_duration_since(Instant::now()))).collect()
 }
 
 #[derive(Serialize, Deserialize, ToSchema)]
@@ -80,6 +202,28 @@ pub struct Info {
     pub uptime: String,
     pub command: String,
     pub children: Vec<i64>,
+    /// Defunct children, not counted in cpu and memory
+    #[serde(default)]
+    pub zombies: usize,
+    /// Compare with a fresh `fingerprint::compute` to tell if a restart would change anything
+    #[serde(default)]
+    pub fingerprint: Option<String>,
+    #[serde(default)]
+    pub labels: Labels,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(example = "Legacy billing worker, owned by @dana")]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub last_restart_reason: Option<String>,
+    #[serde(default)]
+    #[schema(example = "https://app.internal/health")]
+    pub url: Option<String>,
+    /// Restart limit of its own, `daemon.restarts` applies when None
+    #[serde(default)]
+    pub max_restarts: Option<u64>,
+    /// Last health check since the process was started, None without one
+    #[serde(default)]
+    pub health: Option<health::Status>,
 }
 
 #[derive(Serialize, Deserialize, ToSchema)]
@@ -88,6 +232,29 @@ pub struct Stats {
     pub start_time: i64,
     pub cpu_percent: Option<f64>,
     pub memory_usage: Option<MemoryInfo>,
+    /// Open file descriptors of the process and its children
+    #[serde(default)]
+    pub fds: Option<u64>,
+    /// Threads of the process and its children
+    #[serde(default)]
+    pub threads: Option<u64>,
+    /// How `cpu_percent` was measured, None when it was not
+    #[serde(default)]
+    pub cpu_sample: Option<CpuSample>,
+    /// Bytes per minute the logs grew by while the cpu was sampled
+    #[serde(default)]
+    pub log_rate: Option<u64>,
+}
+
+/// How a cpu usage was measured. Lists and streams read the average since the process
+/// started, a single process is sampled once over `runner.cpu_sample_ms`
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum CpuSample {
+    /// Average since start, read without waiting
+    Fast,
+    /// Current usage over the sample window
+    Timed,
 }
 
 #[derive(Serialize, Deserialize, ToSchema)]
@@ -124,7 +291,31 @@ pub struct LogInfo {
     pub error: String,
 }
 
-#[derive(Serialize, Deserialize, ToSchema)]
+/// A log file deleted with its process
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, PartialEq)]
+pub struct RemovedLog {
+    #[schema(example = "/home/user/.opm/logs/api-out.log")]
+    pub path: String,
+    #[schema(example = 1048576)]
+    pub bytes: u64,
+}
+
+/// What `opm remove --purge-logs` did to the logs of a process
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, Default, PartialEq)]
+pub struct PurgedLogs {
+    pub removed: Vec<RemovedLog>,
+    /// Logs kept because another process writes to the same file
+    pub shared: Vec<String>,
+    /// Files that could not be deleted, with the reason
+    pub failed: Vec<String>,
+}
+
+impl PurgedLogs {
+    /// Space reclaimed in bytes
+    pub fn bytes(&self) -> u64 { self.removed.iter().map(|log| log.bytes).sum() }
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Debug)]
 pub struct ProcessItem {
     pub pid: i64,
     pub id: usize,
@@ -142,6 +333,14 @@ pub struct ProcessItem {
     pub agent_id: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub agent_name: Option<String>,
+    #[serde(default)]
+    pub labels: Labels,
+    /// See `Process::group`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(example = "api")]
+    pub group: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
 }
 
 #[derive(Clone)]
@@ -151,8 +350,9 @@ pub struct ProcessWrapper {
 }
 
 pub type Env = BTreeMap<String, String>;
+pub type Labels = BTreeMap<String, String>;
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
 pub struct Process {
     pub id: usize,
     pub pid: i64,
@@ -172,26 +372,194 @@ pub struct Process {
     pub children: Vec<i64>,
     #[serde(with = "ts_milliseconds")]
     pub started: DateTime<Utc>,
+    /// The start on the monotonic clock, uptimes and the grace period are measured from it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub started_mark: Option<clock::Mark>,
     /// Maximum memory limit in bytes (0 = no limit)
     #[serde(default)]
     pub max_memory: u64,
+    /// What the daemon does once the process goes over `max_memory`, see `memlimit`
+    #[serde(default)]
+    pub max_memory_action: memlimit::Action,
+    /// Restarts for going over `max_memory`, counted apart from crashes
+    #[serde(default)]
+    pub memory_restarts: u64,
     /// Agent ID that owns this process (None for local processes)
     #[serde(default)]
     pub agent_id: Option<String>,
+    /// Free-form key/value labels used for filtering and metrics
+    #[serde(default)]
+    pub labels: Labels,
+    /// Free text set with `--description`, see `description`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Group it is managed with, set with `--group` or by a `--workers` start, see `group`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+    /// `runner.log_path` when it was last started, its logs stay there until `opm migrate-logs`, see `logdir`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub log_path: Option<String>,
+    /// Names of the processes this one needs, they are stopped after it
+    #[serde(default)]
+    pub depends_on: Vec<String>,
+    /// Seconds after a start the process is reported as starting (None = `STARTUP_GRACE_PERIOD_SECS`)
+    #[serde(default)]
+    pub startup_grace: Option<u64>,
+    /// Reason given by the last triggered reload, e.g. `watch` or `deploy`
+    #[serde(default)]
+    pub last_restart_reason: Option<String>,
+    /// Link to the service's own status page, only shown to users
+    #[serde(default)]
+    pub url: Option<String>,
+    /// Set for services that fork into the background, `pid` is then the one read from the pidfile
+    #[serde(default)]
+    pub pidfile: Option<adopt::Pidfile>,
+    /// Inputs the running instance was started with, see `fingerprint::compute`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fingerprint: Option<String>,
+    /// Expand `{{placeholder}}`s in env values every time the process is spawned
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub expand_env: bool,
+    /// Exit code of a command that completed before it could be tracked, cleared by the next launch
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i32>,
+    /// Why the last start failed without counting as a crash, e.g. `logspace::FULL`, cleared by the next launch
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub errored: Option<String>,
+    /// Octal file mode mask set before every spawn (None = inherited from the daemon)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub umask: Option<u32>,
+    /// Soft RLIMIT_NOFILE set before every spawn, `limits::UNLIMITED` for no limit
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ulimit_nofile: Option<u64>,
+    /// Soft RLIMIT_CORE in bytes set before every spawn, `limits::UNLIMITED` for no limit
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ulimit_core: Option<u64>,
+    /// Allow core dumps and collect the ones crashes leave into `~/.opm/cores/<name>`
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub collect_cores: bool,
+    /// Spawned under an `opm __supervise` process that owns its output and records how it exited
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub supervised: bool,
+    /// Registered by `opm import --background`, the daemon starts it once its dependencies are up
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub pending: bool,
+    /// Thresholds the daemon checks at every interval, see `alerts`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub alerts: Vec<alerts::Alert>,
+    /// Fastest the logs may grow before the daemon acts, see `lograte`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_log_rate: Option<lograte::MaxLogRate>,
+    /// Restart the process at the times of this cron expression, see `cron`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub restart_cron: Option<cron::Schedule>,
+    /// When `restart_cron` last fired or was set, the times before it are not due anymore
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub restart_cron_since: Option<DateTime<Utc>>,
+    /// Check the daemon runs while the process is online, see `health`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub health: Option<health::Check>,
+    /// Outcome of the last check of `health`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub health_status: Option<health::Status>,
+    /// Milliseconds between the daemon's checks of this process (None = `daemon.interval`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub monitor_interval_ms: Option<u64>,
+    /// Off for processes that fork many short-lived children, see `tree`
+    #[serde(default = "default_track_children")]
+    pub track_children: bool,
+    /// Shell the script is spawned with instead of `runner.shell` (None = the configured one)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shell: Option<String>,
+    /// Arguments given to the shell before the script instead of `runner.args`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shell_args: Option<Vec<String>>,
+    /// Crashes the daemon restarts the process after (None = `daemon.restarts`), 0 never restarts it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_restarts: Option<u64>,
+    /// Variables set with `opm start --env` or `opm adjust --env-add`, kept over the environment of whoever restarts it
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub pinned_env: Vec<String>,
 }
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
+fn default_track_children() -> bool { true }
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
 pub struct Crash {
     pub crashed: bool,
     pub value: u64,
+    /// Core files found after the last crash, with `collect_cores`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub cores: Vec<cores::Core>,
+    /// When the daemon restarts the process after its last crash, see `restart_backoff`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_restart: Option<DateTime<Utc>>,
 }
 
-#[derive(Clone, Debug, Deserialize, Serialize, ToSchema)]
+#[derive(Clone, Debug, Default, Deserialize, Serialize, ToSchema)]
 pub struct Watch {
     pub enabled: bool,
     #[schema(example = "/path")]
     pub path: String,
     pub hash: String,
+    /// Seconds after a watch reload in which further changes are coalesced (None = `DEFAULT_WATCH_COOLDOWN`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(example = 5)]
+    pub cooldown_secs: Option<u64>,
+    /// When a change of the watched path last reloaded the process
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<String>, example = "2000-01-01T01:00:00.000Z")]
+    pub last_reload: Option<DateTime<Utc>>,
+    /// Hash of a change held back by the cooldown, reloaded once the path stops changing
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pending: Option<String>,
+}
+
+/// What a check of the watched path does, see `Watch::check`
+#[derive(Debug, PartialEq)]
+pub enum WatchChange {
+    Unchanged,
+    /// Reload the process, it was reloaded for a change since the hash it holds
+    Reload,
+    /// Held back until the cooldown is over and the path stopped changing
+    Held,
+}
+
+impl Watch {
+    pub fn cooldown(&self) -> u64 { self.cooldown_secs.unwrap_or(DEFAULT_WATCH_COOLDOWN) }
+
+    /// Decide what the watched path hashing to `hash` at `now` does. A change reloads right away
+    /// unless it comes within the cooldown of the last watch reload, then it waits for the window
+    /// to close and two checks in a row to find the same hash. A `git pull` landing over several
+    /// checks reloads at its first change and once more when it is done.
+    pub fn check(&mut self, hash: &str, now: DateTime<Utc>) -> WatchChange {
+        if hash == self.hash && self.pending.is_none() {
+            return WatchChange::Unchanged;
+        }
+
+        let cooling = self.last_reload.is_some_and(|last| now < last + chrono::Duration::seconds(self.cooldown() as i64));
+        if cooling {
+            self.pending = Some(hash.to_string());
+            return WatchChange::Held;
+        }
+
+        match self.pending.take() {
+            None => WatchChange::Reload,
+            // Changed back to what is running, nothing to reload
+            Some(pending) if pending == hash && hash == self.hash => WatchChange::Unchanged,
+            Some(pending) if pending == hash => WatchChange::Reload,
+            Some(_) => {
+                self.pending = Some(hash.to_string());
+                WatchChange::Held
+            }
+        }
+    }
+
+    /// Note a watch reload at `now`, the cooldown starts over
+    pub fn reloaded(&mut self, now: DateTime<Utc>) {
+        self.last_reload = Some(now);
+        self.pending = None;
+    }
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -200,12 +568,18 @@ pub struct Runner {
     #[serde(skip, default)]
     pub remote: Option<Remote>,
     pub list: BTreeMap<usize, Process>,
+    /// Machine that last wrote the dump, see `host::current`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
+    #[serde(skip, default)]
+    pub(crate) backend: Backend,
 }
 
 #[derive(Clone, Debug)]
 pub struct Remote {
     address: String,
     token: Option<String>,
+    options: ClientOptions,
     pub config: RemoteConfig,
 }
 
@@ -214,6 +588,11 @@ pub struct RemoteConfig {
     pub shell: String,
     pub args: Vec<String>,
     pub log_path: String,
+    /// Left out by older daemons
+    #[serde(default)]
+    pub version: Option<String>,
+    #[serde(default)]
+    pub role: Option<String>,
 }
 
 pub enum Status {
@@ -230,6 +609,56 @@ impl Status {
     }
 }
 
+/// Settings of a new process given to `opm start`, `opm import` or the create route
+#[derive(Clone, Default)]
+pub struct StartOptions {
+    /// Path watched for changes, relative to the working directory
+    pub watch: Option<String>,
+    /// Memory limit in bytes, 0 for none
+    pub max_memory: u64,
+    /// What the daemon does once the process goes over `max_memory`, stop when None
+    pub max_memory_action: Option<memlimit::Action>,
+    /// Umask and resource limits applied at every spawn
+    pub limits: limits::Limits,
+    /// Shell and its arguments instead of `runner.shell` and `runner.args`
+    pub shell: shell::Shell,
+    /// Keep the core dumps of crashes
+    pub collect_cores: bool,
+    /// Spawn through an opm supervisor that stamps and rotates the logs
+    pub supervised: bool,
+    pub labels: Labels,
+    /// Variables kept over the environment of whoever restarts it
+    pub env: Env,
+    /// Seconds the process is reported as starting
+    pub startup_grace: Option<u64>,
+    /// Milliseconds between the checks of the daemon, `daemon.interval` when unset
+    pub monitor_interval_ms: Option<u64>,
+    /// Crashes the daemon restarts the process after, `daemon.restarts` when unset
+    pub max_restarts: Option<u64>,
+    /// Seconds watch changes are coalesced after a watch reload, `DEFAULT_WATCH_COOLDOWN` when unset
+    pub watch_cooldown: Option<u64>,
+    /// Status page of the process
+    pub url: Option<String>,
+    /// Free text shown by `opm details`, checked with `description::parse`
+    pub description: Option<String>,
+    /// Group the process is managed with, see `group::parse`
+    pub group: Option<String>,
+    /// Thresholds checked at every interval of the daemon
+    pub alerts: Vec<alerts::Alert>,
+    /// Fastest the logs may grow before the daemon acts
+    pub max_log_rate: Option<lograte::MaxLogRate>,
+    /// Restart the process at the times of this cron expression
+    pub restart_cron: Option<cron::Schedule>,
+    /// Check the daemon runs while the process is online
+    pub health: Option<health::Check>,
+    /// Pidfile a self-daemonizing service writes its pid to
+    pub pidfile: Option<PathBuf>,
+    /// Expand env templates at every spawn
+    pub expand_env: bool,
+    /// Replace the command of the process that has the name instead of refusing to start
+    pub force_update: bool,
+}
+
 /// Process metadata
 pub struct ProcessMetadata {
     /// Process name
@@ -244,6 +673,12 @@ pub struct ProcessMetadata {
     pub args: Vec<String>,
     /// Environment variables
     pub env: Vec<String>,
+    /// Umask and resource limits applied right before exec
+    pub limits: limits::Limits,
+    /// Run the command under a supervisor, see `supervise`
+    pub supervised: bool,
+    /// Where output goes when the log filesystem is full
+    pub logs_on_enospc: LogsOnEnospc,
 }
 
 macro_rules! lock {
@@ -255,13 +690,17 @@ macro_rules! lock {
     }};
 }
 
-fn kill_children(children: Vec<i64>) {
+fn kill_children(children: Vec<i64>, signal: Signal) {
     for pid in children {
-        match kill(Pid::from_raw(pid as i32), Signal::SIGTERM) {
+        match kill(Pid::from_raw(pid as i32), signal) {
             Ok(_) => {}
             Err(nix::errno::Errno::ESRCH) => {
                 // Process already terminated
             }
+            Err(nix::errno::Errno::EPERM) => {
+                let name = unix::get_process_name(pid as u32).unwrap_or_else(|_| string!("unknown"));
+                log::warn!("Failed to stop child: {}", permission_denied(pid, &name));
+            }
             Err(err) => {
                 log::error!("Failed to stop pid {}: {err:?}", pid);
             }
@@ -269,6 +708,90 @@ fn kill_children(children: Vec<i64>) {
     }
 }
 
+/// A stopped process as it is first stored, before anything was spawned for it
+fn new_process(id: usize, name: &str, command: &str, path: PathBuf, options: &StartOptions) -> Process {
+    let watch = match &options.watch {
+        Some(watch) => Watch {
+            enabled: true,
+            path: string!(watch),
+            hash: hash::create(file::cwd().join(watch)),
+            cooldown_secs: options.watch_cooldown,
+            last_reload: None,
+            pending: None,
+        },
+        None => Watch {
+            enabled: false,
+            path: string!(""),
+            hash: string!(""),
+            cooldown_secs: options.watch_cooldown,
+            last_reload: None,
+            pending: None,
+        },
+    };
+
+    // Merge .env variables into the stored environment (dotenv takes priority)
+    let mut stored_env: Env = env::vars().collect();
+    // Extend with dotenv variables (this overwrites any existing keys)
+    stored_env.extend(load_dotenv(&path));
+
+    Process {
+        id,
+        pid: 0,
+        shell_pid: None,
+        path,
+        watch,
+        crash: Crash {
+            crashed: false,
+            value: 0,
+            cores: vec![],
+            next_restart: None,
+        },
+        restarts: 0,
+        running: false,
+        children: vec![],
+        name: name.to_string(),
+        started: Utc::now(),
+        started_mark: clock::now(),
+        errored: None,
+        script: command.to_string(),
+        env: stored_env,
+        max_memory: options.max_memory,
+        agent_id: None, // Local processes don't have an agent
+        labels: options.labels.clone(),
+        depends_on: vec![],
+        startup_grace: options.startup_grace,
+        last_restart_reason: None,
+        url: options.url.clone(),
+        pidfile: None,
+        fingerprint: None,
+        expand_env: options.expand_env,
+        exit_code: None,
+        umask: options.limits.umask,
+        ulimit_nofile: options.limits.nofile,
+        ulimit_core: options.limits.core,
+        collect_cores: options.collect_cores,
+        supervised: options.supervised,
+        pending: false,
+        alerts: options.alerts.clone(),
+        max_log_rate: options.max_log_rate.clone(),
+        restart_cron: options.restart_cron.clone(),
+        restart_cron_since: options.restart_cron.as_ref().map(|_| Utc::now()),
+        health: options.health.clone(),
+        health_status: None,
+        max_memory_action: options.max_memory_action.unwrap_or_default(),
+        memory_restarts: 0,
+        monitor_interval_ms: options.monitor_interval_ms,
+        track_children: true,
+        shell: options.shell.program.clone(),
+        shell_args: options.shell.args.clone(),
+        max_restarts: options.max_restarts,
+        pinned_env: vec![],
+        description: options.description.clone(),
+        group: options.group.clone(),
+        log_path: None,
+    }
+}
+
 /// Load environment variables from .env file in the specified directory
 fn load_dotenv(path: &PathBuf) -> BTreeMap<String, String> {
     let env_file = path.join(".env");
@@ -323,8 +846,9 @@ pub fn is_pid_alive(pid: i64) -> bool {
         return false;
     }
     
-    // First check if the PID exists using libc::kill
-    let pid_exists = unsafe { libc::kill(pid as i32, 0) == 0 };
+    // First check if the PID exists using kill with no signal
+    // EPERM means the process exists but belongs to another user, it is still alive
+    let pid_exists = matches!(kill(Pid::from_raw(pid as i32), None), Ok(_) | Err(nix::errno::Errno::EPERM));
     
     if !pid_exists {
         return false;
@@ -343,17 +867,61 @@ pub fn is_pid_alive(pid: i64) -> bool {
     true
 }
 
+/// Check if signalling a process is refused because another user owns it
+pub fn is_signal_denied(pid: i64) -> bool {
+    pid > 0 && kill(Pid::from_raw(pid as i32), None) == Err(nix::errno::Errno::EPERM)
+}
+
+/// Check if a process is owned by another user than the one running opm
+/// Root can signal any process, so nothing is privileged from its point of view
+pub fn is_privileged(pid: i64) -> bool {
+    let euid = unsafe { libc::geteuid() };
+    pid > 0 && euid != 0 && unix::get_process_owner(pid as u32).is_ok_and(|uid| uid != euid)
+}
+
+/// Explain why a process cannot be signalled and how to manage it instead
+pub fn permission_denied(pid: i64, name: &str) -> String {
+    let owner = match unix::get_process_owner(pid as u32) {
+        Ok(uid) => unix::get_user_name(uid).unwrap_or_else(|| format!("uid {uid}")),
+        Err(_) => string!("another user"),
+    };
+
+    format!("process {pid} ({name}) is owned by {owner}; re-run with sudo or manage it from the daemon")
+}
+
 impl Runner {
     pub fn new() -> Self {
         dump::read()
     }
 
+    /// Processes kept and run by `backend` instead of the dump and real processes
+    pub fn with_backend(backend: Arc<dyn RunnerBackend>) -> Self {
+        let backend = Backend::new(backend);
+        Runner { backend: backend.clone(), ..backend.read() }
+    }
+
     pub fn refresh(&self) -> Self {
-        Runner::new()
+        Runner { backend: self.backend.clone(), ..self.backend.read() }
     }
 
-    pub fn connect(name: String, Server { address, token }: Server, verbose: bool) -> Option<Self> {
-        let remote_config = match config::from(&address, token.as_deref()) {
+    pub fn connect(name: String, Server { address, token, client, .. }: Server, verbose: bool) -> Option<Self> {
+        if client.insecure {
+            then!(
+                verbose,
+                alert!(
+                    "{} TLS verification is disabled for remote {name}",
+                    *helpers::WARN
+                )
+            );
+        }
+
+        // Otherwise only the generic connection failure would be shown for a missing secret
+        if let Some(Err(err)) = token.as_deref().map(config::token::resolve) {
+            alert!("{} {err} (remote {name})", *helpers::FAIL);
+            return None;
+        }
+
+        let remote_config = match config::from(&address, token.as_deref(), &client) {
             Ok(config) => config,
             Err(err) => {
                 log::error!("{err}");
@@ -361,17 +929,19 @@ impl Runner {
             }
         };
 
-        if let Ok(dump) = dump::from(&address, token.as_deref()) {
+        if let Ok(dump) = dump::from(&address, token.as_deref(), &client) {
             then!(
                 verbose,
-                println!(
+                notice!(
                     "{} Fetched remote (name={name}, address={address})",
                     *helpers::SUCCESS
                 )
             );
+            config::seen(&name, &remote_config);
             Some(Runner {
                 remote: Some(Remote {
                     token,
+                    options: client,
                     address: string!(address),
                     config: remote_config,
                 }),
@@ -382,16 +952,9 @@ impl Runner {
         }
     }
 
-    pub fn start(
-        &mut self,
-        name: &String,
-        command: &String,
-        path: PathBuf,
-        watch: &Option<String>,
-        max_memory: u64,
-    ) -> &mut Self {
+    pub fn start(&mut self, name: &String, command: &String, path: PathBuf, options: &StartOptions) -> &mut Self {
         if let Some(remote) = &self.remote {
-            if let Err(err) = http::create(remote, name, command, path, watch) {
+            if let Err(err) = http::create(remote, name, command, path, options) {
                 crashln!(
                     "{} Failed to start create {name}\nError: {:#?}",
                     *helpers::FAIL,
@@ -399,25 +962,14 @@ impl Runner {
                 );
             };
         } else {
-            let id = self.id.next();
             let config = config::read().runner;
-            let crash = Crash {
-                crashed: false,
-                value: 0,
-            };
+            if let Err(err) = self.check_capacity(1, max_processes(&config)) {
+                log::error!("Refused to start process '{name}': {err}");
+                alert!("{} {err}", *helpers::FAIL);
+                return self;
+            }
 
-            let watch = match watch {
-                Some(watch) => Watch {
-                    enabled: true,
-                    path: string!(watch),
-                    hash: hash::create(file::cwd().join(watch)),
-                },
-                None => Watch {
-                    enabled: false,
-                    path: string!(""),
-                    hash: string!(""),
-                },
-            };
+            let id = self.id.next();
 
             // Load environment variables from .env file
             let dotenv_vars = load_dotenv(&path);
@@ -429,55 +981,71 @@ impl Runner {
             for (key, value) in &dotenv_vars {
                 process_env.push(format!("{}={}", key, value));
             }
+            // Then the ones given to the start
+            for (key, value) in &options.env {
+                process_env.push(format!("{}={}", key, value));
+            }
             // Then add system environment
             process_env.extend(system_env);
+            then!(options.expand_env, template::expand_env(&mut process_env, id, name));
+            let (program, args) = options.shell.resolve(&config.shell, &config.args);
+            // Stamped lines need the supervisor, which writes the log
+            let supervised = options.supervised || config.log_timestamps;
 
-            let result = match process_run(ProcessMetadata {
-                args: config.args,
+            let result = match self.backend.run(ProcessMetadata {
+                args,
                 name: name.clone(),
-                shell: config.shell,
+                shell: program,
                 command: command.clone(),
-                log_path: config.log_path,
+                log_path: config.log_path.clone(),
                 env: process_env,
+                limits: ternary!(options.collect_cores, options.limits.with_core_dumps(), options.limits),
+                supervised,
+                logs_on_enospc: config.logs_on_enospc,
             }) {
                 Ok(result) => result,
                 Err(err) => {
                     log::error!("Failed to start process '{}': {}", name, err);
-                    println!("{} Failed to start process '{}': {}", *helpers::FAIL, name, err);
+                    alert!("{} Failed to start process '{}': {}", *helpers::FAIL, name, err);
                     return self;
                 }
             };
 
-            // Merge .env variables into the stored environment (dotenv takes priority)
-            let mut stored_env: Env = env::vars().collect();
-            // Extend with dotenv variables (this overwrites any existing keys)
-            stored_env.extend(dotenv_vars);
-
             self.list.insert(
                 id,
                 Process {
-                    id,
                     pid: result.pid,
                     shell_pid: result.shell_pid,
-                    path,
-                    watch,
-                    crash,
-                    restarts: 0,
                     running: true,
-                    children: vec![],
-                    name: name.clone(),
-                    started: Utc::now(),
-                    script: command.clone(),
-                    env: stored_env,
-                    max_memory,
-                    agent_id: None, // Local processes don't have an agent
+                    supervised,
+                    log_path: Some(config.log_path.clone()),
+                    ..new_process(id, name, command, path, options)
                 },
             );
+
+            self.process(id).pin_env(&options.env, &dotenv_vars);
+            let fingerprint = fingerprint::compute(&self.list[&id]);
+            self.process(id).fingerprint = Some(fingerprint);
+
+            if let Some(code) = result.exit_code {
+                self.process(id).completed(code);
+                log::info!("Process '{name}' completed right after starting, exit code {code}");
+                then!(code != 0, alert!("{} Process {name} exited right after starting with code {code}, see its error log", *helpers::WARN));
+            }
         }
 
         return self;
     }
 
+    /// Add a process without spawning it, the daemon starts it on its next cycle
+    pub fn register(&mut self, name: &str, command: &str, path: PathBuf, options: &StartOptions) -> usize {
+        let id = self.id.next();
+        let process = new_process(id, name, command, path, options);
+
+        self.list.insert(id, Process { pending: true, ..process });
+        id
+    }
+
     pub fn restart(&mut self, id: usize, dead: bool, increment_counter: bool) -> &mut Self {
         if let Some(remote) = &self.remote {
             if let Err(err) = http::restart(remote, id) {
@@ -488,8 +1056,21 @@ impl Runner {
                 );
             };
         } else {
+            // A crashed process has no pid left to signal, only live ones can be refused
+            if !dead && let Err(err) = self.signal_permission(id) {
+                log::warn!("Refusing to restart: {err}");
+                alert!("{} Cannot restart: {err}", *helpers::FAIL);
+                return self;
+            }
+
+            counters::add(counters::Counter::Restart);
+            let backend = self.backend.clone();
             let process = self.process(id);
-            let config = config::read().runner;
+            let config = config::read();
+            let kill_timeout = shutdown::kill_timeout(&config.daemon);
+            let config = config.runner;
+            // Started by hand or by the daemon, either way it is no longer waiting
+            process.pending = false;
             let Process {
                 path, script, name, ..
             } = process.clone();
@@ -507,16 +1088,17 @@ impl Runner {
                 process.restarts += 1;
             }
 
-            kill_children(process.children.clone());
-            if let Err(err) = process_stop(process.pid) {
-                log::warn!("Failed to stop process {} during restart: {}", process.pid, err);
-                // Continue with restart even if stop fails - process may already be dead
-            }
+            kill_children(process.tree(), Signal::SIGTERM);
+            // An adopted pid that now belongs to another process is left alone
+            if process.owns_pid() {
+                if let Err(err) = backend.stop(process.pid, Signal::SIGTERM) {
+                    log::warn!("Failed to stop process {} during restart: {}", process.pid, err);
+                    // Continue with restart even if stop fails - process may already be dead
+                }
 
-            // Wait for the process to actually terminate before starting a new one
-            // This prevents conflicts when restarting processes that hold resources (e.g., network connections)
-            if !wait_for_process_termination(process.pid) {
-                log::warn!("Process {} did not terminate within timeout during restart", process.pid);
+                // Wait for the process to actually terminate before starting a new one
+                // This prevents conflicts when restarting processes that hold resources (e.g., network connections)
+                await_stop(&backend, &[process.pid], kill_timeout, "restart");
             }
 
             if let Err(err) = std::env::set_current_dir(&path) {
@@ -540,7 +1122,7 @@ impl Runner {
                 }
                 
                 log::error!("Failed to set working directory {:?} for process {} during restart: {}", path, name, err);
-                println!(
+                alert!(
                     "{} Failed to set working directory {:?}\nError: {:#?}",
                     *helpers::FAIL,
                     path,
@@ -569,16 +1151,30 @@ impl Runner {
             temp_env.extend(stored_env_vec);
             // Finally add system environment
             temp_env.extend(system_env);
+            then!(process.expand_env, template::expand_env(&mut temp_env, id, &name));
 
-            let result = match process_run(ProcessMetadata {
-                args: config.args,
-                name: name.clone(),
-                shell: config.shell,
-                log_path: config.log_path,
-                command: script.to_string(),
-                env: temp_env,
-            }) {
-                Ok(result) => result,
+            let (program, args) = process.invocation(&config.shell, &config.args);
+            let launched = launch(
+                &*backend,
+                ProcessMetadata {
+                    args,
+                    name: name.clone(),
+                    shell: program,
+                    log_path: config.log_path.clone(),
+                    command: script.to_string(),
+                    env: temp_env,
+                    limits: process.spawn_limits(),
+                    supervised: process.supervised,
+                    logs_on_enospc: config.logs_on_enospc,
+                },
+                process.pidfile.as_ref(),
+            );
+
+            let result = match launched {
+                Ok((result, pidfile)) => {
+                    then!(pidfile.is_some(), process.pidfile = pidfile);
+                    result
+                }
                 Err(err) => {
                     // Restore working directory before returning
                     if let Some(ref dir) = original_dir {
@@ -594,13 +1190,16 @@ impl Runner {
                     process.crash.crashed = true;
                     
                     // Increment crash counter for restart failures to count against restart limit
-                    // This prevents infinite retry loops when restart repeatedly fails
-                    if dead {
+                    // This prevents infinite retry loops when restart repeatedly fails.
+                    // A full log filesystem is no fault of the process, the daemon retries once there is space.
+                    if logspace::is_full(&err) {
+                        process.errored = Some(string!(logspace::FULL));
+                    } else if dead {
                         self.handle_restart_failure(id, &name);
                     }
                     
                     log::error!("Failed to restart process '{}' (id={}): {}", name, id, err);
-                    println!("{} Failed to restart process '{}' (id={}): {}", *helpers::FAIL, name, id, err);
+                    alert!("{} Failed to restart process '{}' (id={}): {}", *helpers::FAIL, name, id, err);
                     return self;
                 }
             };
@@ -609,13 +1208,24 @@ impl Runner {
             process.shell_pid = result.shell_pid;
             process.running = true;
             process.children = vec![];
+            process.log_path = Some(config.log_path.clone());
             process.started = Utc::now();
+            process.started_mark = clock::now();
             process.crash.crashed = false;
+            process.crash.next_restart = None;
+            process.exit_code = None;
+            process.errored = None;
+
+            // A failing command restarted by the daemon is left to its crash accounting
+            if let Some(code) = result.exit_code
+                && (!dead || code == 0)
+            {
+                process.completed(code);
+                then!(code != 0, alert!("{} Process {name} exited right after starting with code {code}, see its error log", *helpers::WARN));
+            }
 
-            // Merge .env variables into the stored environment (dotenv takes priority)
-            let mut updated_env: Env = env::vars().collect();
-            updated_env.extend(dotenv_vars);
-            process.env.extend(updated_env);
+            process.refresh_env(dotenv_vars);
+            process.fingerprint = Some(fingerprint::compute(process));
 
             // Don't reset crash counter - keep it to preserve crash history
             // The daemon will reset it automatically after the process runs successfully
@@ -643,13 +1253,29 @@ impl Runner {
                 );
             };
         } else {
+            // Check before the new process is spawned, the old one could never be stopped afterwards
+            if !dead && let Err(err) = self.signal_permission(id) {
+                log::warn!("Refusing to reload: {err}");
+                alert!("{} Cannot reload: {err}", *helpers::FAIL);
+                return self;
+            }
+
+            // Two instances cannot share one pidfile, forking services are restarted instead
+            if self.process(id).pidfile.is_some() {
+                return self.restart(id, dead, increment_counter);
+            }
+
+            let backend = self.backend.clone();
             let process = self.process(id);
-            let config = config::read().runner;
+            let config = config::read();
+            let kill_timeout = shutdown::kill_timeout(&config.daemon);
+            let config = config.runner;
             let Process {
                 path,
                 script,
                 name,
                 env,
+                expand_env,
                 watch: _,
                 max_memory: _,
                 ..
@@ -687,7 +1313,7 @@ impl Runner {
                 }
                 
                 log::error!("Failed to set working directory {:?} for process {} during reload: {}", path, name, err);
-                println!(
+                alert!(
                     "{} Failed to set working directory {:?}\nError: {:#?}",
                     *helpers::FAIL,
                     path,
@@ -715,15 +1341,20 @@ impl Runner {
             temp_env.extend(stored_env_vec);
             // Finally add system environment
             temp_env.extend(system_env);
+            then!(expand_env, template::expand_env(&mut temp_env, id, &name));
 
             // Start new process first
-            let result = match process_run(ProcessMetadata {
-                args: config.args,
+            let (program, args) = process.invocation(&config.shell, &config.args);
+            let result = match backend.run(ProcessMetadata {
+                args,
                 name: name.clone(),
-                shell: config.shell,
-                log_path: config.log_path,
+                shell: program,
+                log_path: config.log_path.clone(),
                 command: script.to_string(),
                 env: temp_env,
+                limits: process.spawn_limits(),
+                supervised: process.supervised,
+                logs_on_enospc: config.logs_on_enospc,
             }) {
                 Ok(result) => result,
                 Err(err) => {
@@ -742,32 +1373,45 @@ impl Runner {
                     
                     // Increment crash counter for reload failures to count against restart limit
                     // This prevents infinite retry loops when reload repeatedly fails
-                    if dead {
+                    if logspace::is_full(&err) {
+                        process.errored = Some(string!(logspace::FULL));
+                    } else if dead {
                         self.handle_restart_failure(id, &name);
                     }
                     
                     log::error!("Failed to reload process '{}' (id={}): {}", name, id, err);
-                    println!("{} Failed to reload process '{}' (id={}): {}", *helpers::FAIL, name, id, err);
+                    alert!("{} Failed to reload process '{}' (id={}): {}", *helpers::FAIL, name, id, err);
                     return self;
                 }
             };
 
             // Store old PID before updating
             let old_pid = process.pid;
-            let old_children = process.children.clone();
+            let old_children = process.tree();
 
             // Update process with new PID
             process.pid = result.pid;
             process.shell_pid = result.shell_pid;
             process.running = true;
             process.children = vec![];
+            process.log_path = Some(config.log_path.clone());
             process.started = Utc::now();
+            process.started_mark = clock::now();
             process.crash.crashed = false;
+            process.crash.next_restart = None;
+            process.exit_code = None;
+            process.errored = None;
+
+            // A failing command reloaded by the daemon is left to its crash accounting
+            if let Some(code) = result.exit_code
+                && (!dead || code == 0)
+            {
+                process.completed(code);
+                then!(code != 0, alert!("{} Process {name} exited right after starting with code {code}, see its error log", *helpers::WARN));
+            }
 
-            // Merge .env variables into the stored environment (dotenv takes priority)
-            let mut updated_env: Env = env::vars().collect();
-            updated_env.extend(dotenv_vars);
-            process.env.extend(updated_env);
+            process.refresh_env(dotenv_vars);
+            process.fingerprint = Some(fingerprint::compute(process));
 
             // Don't reset crash counter - keep it to preserve crash history
             // The daemon will reset it automatically after the process runs successfully
@@ -775,15 +1419,13 @@ impl Runner {
             // process stability over time.
 
             // Now stop the old process after the new one is running
-            kill_children(old_children);
-            if let Err(err) = process_stop(old_pid) {
+            kill_children(old_children, Signal::SIGTERM);
+            if let Err(err) = backend.stop(old_pid, Signal::SIGTERM) {
                 log::warn!("Failed to stop old process during reload: {err}");
             }
 
             // Wait for old process to fully terminate to release any held resources
-            if !wait_for_process_termination(old_pid) {
-                log::warn!("Old process {} did not terminate within timeout during reload", old_pid);
-            }
+            await_stop(&backend, &[old_pid], kill_timeout, "reload");
             
             // Restore the original working directory
             if let Some(dir) = original_dir {
@@ -796,19 +1438,28 @@ impl Runner {
         return self;
     }
 
-    pub fn remove(&mut self, id: usize) {
+    /// Stop and remove a process. With `purge_logs` its logs are deleted too, unless another
+    /// process writes to the same files, and what was deleted is returned.
+    pub fn remove(&mut self, id: usize, purge_logs: bool) -> Option<PurgedLogs> {
         if let Some(remote) = &self.remote {
-            if let Err(err) = http::remove(remote, id) {
-                crashln!(
+            match http::remove(remote, id, purge_logs) {
+                Ok(response) => response.json::<http::RemoveResponse>().ok().and_then(|response| response.logs),
+                Err(err) => crashln!(
                     "{} Failed to stop remove {id}\nError: {:#?}",
                     *helpers::FAIL,
                     err
-                );
-            };
+                ),
+            }
         } else {
-            self.stop(id);
-            self.list.remove(&id);
+            self.stop(id, None);
+            let removed = self.list.remove(&id);
             self.save();
+
+            let shared: HashSet<String> = self.list.values().flat_map(|process| {
+                let logs = process.logs();
+                [logs.out, logs.error]
+            }).collect();
+            removed.filter(|_| purge_logs).map(|process| process.logs().purge(&shared))
         }
     }
 
@@ -838,13 +1489,27 @@ impl Runner {
     }
 
     pub fn save(&self) {
-        then!(self.remote.is_none(), dump::write(&self))
+        then!(self.remote.is_none(), self.backend.write(self))
+    }
+
+    /// Another machine that saved the dump last, when two hosts share one opm home
+    pub fn foreign_host(&self) -> Option<&str> {
+        self.host.as_deref().filter(|host| host::is_foreign(Some(host)))
     }
 
     pub fn count(&mut self) -> usize {
         self.list().count()
     }
 
+    /// Refuse `adding` processes when they would take the runner past `limit`
+    pub fn check_capacity(&self, adding: usize, limit: usize) -> Result<(), String> {
+        let count = self.list.len();
+        match count + adding <= limit {
+            true => Ok(()),
+            false => Err(format!("Cannot add more processes: {count} of at most {limit} are managed (runner.max_processes)")),
+        }
+    }
+
     pub fn is_empty(&self) -> bool {
         self.list.is_empty()
     }
@@ -857,6 +1522,16 @@ impl Runner {
         self.list.get(&id)
     }
 
+    /// Fail with a targeted message when a running local process belongs to another user
+    pub fn signal_permission(&self, id: usize) -> Result<(), String> {
+        match self.info(id) {
+            Some(process) if self.remote.is_none() && process.running && is_signal_denied(process.pid) => {
+                Err(permission_denied(process.pid, &process.name))
+            }
+            _ => Ok(()),
+        }
+    }
+
     pub fn try_info(&self, id: usize) -> &Process {
         self.list
             .get(&id)
@@ -913,12 +1588,44 @@ impl Runner {
                 );
             };
         } else {
-            self.process(id).env = BTreeMap::new();
+            let process = self.process(id);
+            process.env = BTreeMap::new();
+            process.pinned_env = vec![];
         }
 
         return self;
     }
 
+    /// Set and remove stored environment variables in one go, used from the next start.
+    /// Nothing changes when one of them is invalid.
+    pub fn adjust_env(&mut self, id: usize, changes: &environment::Changes) -> Result<Vec<environment::Change>, String> {
+        if let Some(remote) = &self.remote {
+            changes.validate()?;
+            return http::adjust_env(remote, id, changes).map_err(|err| err.to_string());
+        }
+
+        let process = self.process(id);
+        let applied = changes.apply(&mut process.env)?;
+
+        process.pinned_env.retain(|key| !changes.remove.contains(key));
+        for (key, _) in &changes.add {
+            then!(!process.pinned_env.contains(key), process.pinned_env.push(key.clone()));
+        }
+
+        Ok(applied)
+    }
+
+    /// Remove one stored variable, used from the next start. Nothing is returned when it was not set.
+    pub fn unset_env(&mut self, id: usize, key: &str) -> Result<Option<environment::Change>, String> {
+        environment::validate_key(key)?;
+        if let Some(remote) = &self.remote {
+            return http::unset_env(remote, id, key).map_err(|err| err.to_string());
+        }
+
+        let changes = environment::Changes { add: vec![], remove: vec![key.to_string()] };
+        Ok(self.adjust_env(id, &changes)?.pop())
+    }
+
     pub fn set_children(&mut self, id: usize, children: Vec<i64>) -> &mut Self {
         self.process(id).children = children;
         return self;
@@ -937,15 +1644,16 @@ impl Runner {
         
         // Check if we've exceeded max restart limit
         let daemon_config = config::read().daemon;
-        if process.crash.value > daemon_config.restarts {
+        if process.crash.value > process.restart_limit(daemon_config.restarts) {
             process.running = false;
             log::error!("Process {} exceeded max restart attempts due to repeated failures", process_name);
         }
     }
 
-    pub fn stop(&mut self, id: usize) -> &mut Self {
+    /// Stop the process with `signal`, SIGTERM when not given
+    pub fn stop(&mut self, id: usize, signal: Option<Signal>) -> &mut Self {
         if let Some(remote) = &self.remote {
-            if let Err(err) = http::stop(remote, id) {
+            if let Err(err) = http::stop(remote, id, signal) {
                 crashln!(
                     "{} Failed to stop process {id}\nError: {:#?}",
                     *helpers::FAIL,
@@ -953,20 +1661,48 @@ impl Runner {
                 );
             };
         } else {
-            let process_to_stop = self.process(id);
-            let pid_to_check = process_to_stop.pid;
+            self.stop_all(&[id], signal);
+        }
+
+        return self;
+    }
 
-            kill_children(process_to_stop.children.clone());
-            let _ = process_stop(pid_to_check); // Continue even if stopping fails
+    /// Stop local processes with `signal`, SIGTERM when not given. All of them are signalled
+    /// before any is waited on, so stopping many takes about as long as the slowest one.
+    pub fn stop_all(&mut self, ids: &[usize], signal: Option<Signal>) -> &mut Self {
+        let signal = signal.unwrap_or(Signal::SIGTERM);
+        let mut stopping = vec![];
+        let mut pids = vec![];
+
+        for &id in ids {
+            // Leave the state alone, the process keeps running as its owner
+            if let Err(err) = self.signal_permission(id) {
+                log::warn!("Refusing to stop: {err}");
+                alert!("{} Cannot stop: {err}", *helpers::FAIL);
+                continue;
+            }
 
-            // waiting until Process is terminated
-            if !wait_for_process_termination(pid_to_check) {
-                log::warn!("Process {} did not terminate within timeout during stop", pid_to_check);
+            let process = self.process(id);
+            let (pid, owned) = (process.pid, process.owns_pid());
+            kill_children(process.tree(), signal);
+            // An adopted pid that now belongs to another process is left alone
+            if owned {
+                let _ = self.backend.stop(pid, signal); // Continue even if stopping fails
+                pids.push(pid);
             }
+            stopping.push(id);
+        }
+
+        // waiting until the processes are terminated
+        await_stop(&self.backend, &pids, shutdown::kill_timeout(&config::read().daemon), "stop");
 
+        for id in stopping {
             let process = self.process(id);
             process.running = false;
             process.crash.crashed = false;
+            process.crash.next_restart = None;
+            // Stopping a pending process cancels its start
+            process.pending = false;
             // Keep crash.value to preserve crash history - only reset via reset_counters()
             process.children = vec![];
         }
@@ -974,6 +1710,23 @@ impl Runner {
         return self;
     }
 
+    /// End a process that failed its health check, with its children. It is left running, so the
+    /// daemon finds it dead and restarts it like a crashed one, within its restart limit.
+    pub fn kill_unhealthy(&mut self, id: usize) -> &mut Self {
+        let process = self.process(id);
+        let (pid, owned) = (process.pid, process.owns_pid());
+        kill_children(process.tree(), Signal::SIGTERM);
+        process.children = vec![];
+
+        if owned {
+            let _ = self.backend.stop(pid, Signal::SIGTERM);
+            // A deadlocked process ignoring SIGTERM is killed once the stop timeout passed
+            await_stop(&self.backend, &[pid], shutdown::kill_timeout(&config::read().daemon), "health check restart");
+        }
+
+        return self;
+    }
+
     pub fn flush(&mut self, id: usize) -> &mut Self {
         if let Some(remote) = &self.remote {
             if let Err(err) = http::flush(remote, id) {
@@ -1006,62 +1759,246 @@ impl Runner {
         return self;
     }
 
-    pub fn watch(&mut self, id: usize, path: &str, enabled: bool) -> &mut Self {
-        let process = self.process(id);
-        process.watch = Watch {
-            enabled,
-            path: string!(path),
-            hash: ternary!(enabled, hash::create(process.path.join(path)), string!("")),
-        };
+    pub fn set_labels(&mut self, id: usize, labels: Labels) -> &mut Self {
+        self.process(id).labels = labels;
+        return self;
+    }
 
+    pub fn set_depends_on(&mut self, id: usize, depends_on: Vec<String>) -> &mut Self {
+        self.process(id).depends_on = depends_on;
         return self;
     }
 
-    pub fn reset_counters(&mut self, id: usize) -> &mut Self {
+    pub fn set_startup_grace(&mut self, id: usize, startup_grace: Option<u64>) -> &mut Self {
+        self.process(id).startup_grace = startup_grace;
+        return self;
+    }
+
+    pub fn set_track_children(&mut self, id: usize, track_children: bool) -> &mut Self {
         let process = self.process(id);
-        process.restarts = 0;
-        process.crash.value = 0;
-        process.crash.crashed = false;
+        process.track_children = track_children;
+        then!(!track_children, process.children = vec![]);
         return self;
     }
 
-    pub fn find(&self, name: &str, server_name: &String) -> Option<usize> {
-        let mut runner = self.clone();
+    /// Applied at the next restart, an empty `shell` goes back to `runner.shell` and `runner.args`
+    pub fn set_shell(&mut self, id: usize, shell: &shell::Shell) -> &mut Self {
+        let process = self.process(id);
+        process.shell = shell.program.clone();
+        process.shell_args = shell.args.clone();
+        return self;
+    }
 
-        if !matches!(&**server_name, "internal" | "local") {
-            let Some(servers) = config::servers().servers else {
-                crashln!("{} Failed to read servers", *helpers::FAIL)
-            };
+    pub fn set_max_restarts(&mut self, id: usize, max_restarts: Option<u64>) -> &mut Self {
+        self.process(id).max_restarts = max_restarts;
+        return self;
+    }
 
-            if let Some(server) = servers.get(server_name) {
-                runner = match Runner::connect(server_name.clone(), server.get(), false) {
-                    Some(remote) => remote,
-                    None => crashln!(
-                        "{} Failed to connect (name={server_name}, address={})",
-                        *helpers::FAIL,
-                        server.address
-                    ),
-                };
-            } else {
-                crashln!("{} Server '{server_name}' does not exist", *helpers::FAIL)
-            };
-        }
+    /// False keeps a registered process stopped, instead of the daemon starting it
+    pub fn set_pending(&mut self, id: usize, pending: bool) -> &mut Self {
+        self.process(id).pending = pending;
+        return self;
+    }
 
-        runner
-            .list
-            .iter()
-            .find(|(_, p)| p.name == name)
-            .map(|(id, _)| *id)
+    pub fn set_watch_cooldown(&mut self, id: usize, cooldown_secs: Option<u64>) -> &mut Self {
+        self.process(id).watch.cooldown_secs = cooldown_secs;
+        return self;
     }
 
-    /// Helper method to build ProcessItem from Process
-    fn build_process_item(&self, id: usize, item: &Process) -> ProcessItem {
-        let mut memory_usage: Option<MemoryInfo> = None;
-        let mut cpu_percent: Option<f64> = None;
+    pub fn set_monitor_interval(&mut self, id: usize, monitor_interval_ms: Option<u64>) -> &mut Self {
+        self.process(id).monitor_interval_ms = monitor_interval_ms;
+        return self;
+    }
 
-        // Use new_fast() to avoid CPU measurement delays for list view
-        // For shell scripts, try shell_pid first to capture the entire process tree
-        let mut pid_for_monitoring = item.shell_pid.unwrap_or(item.pid);
+    pub fn set_url(&mut self, id: usize, url: Option<String>) -> &mut Self {
+        self.process(id).url = url;
+        return self;
+    }
+
+    /// Set or remove the description of a process, checked with `description::parse`
+    pub fn set_description(&mut self, id: usize, text: Option<String>) -> Result<(), String> {
+        if let Some(text) = &text {
+            description::parse(text)?;
+        }
+
+        if let Some(remote) = &self.remote {
+            return http::describe(remote, id, &text).map_err(|err| err.to_string());
+        }
+
+        self.process(id).description = text;
+        Ok(())
+    }
+
+    /// Move a process into a group or out of it, checked with `group::parse`
+    pub fn set_group(&mut self, id: usize, name: Option<String>) -> Result<(), String> {
+        let name = name.as_deref().map(group::parse).transpose()?;
+
+        if let Some(remote) = &self.remote {
+            return http::group(remote, id, &name).map_err(|err| err.to_string());
+        }
+
+        self.process(id).group = name;
+        Ok(())
+    }
+
+    pub fn set_alerts(&mut self, id: usize, alerts: Vec<alerts::Alert>) -> &mut Self {
+        self.process(id).alerts = alerts;
+        return self;
+    }
+
+    pub fn set_max_log_rate(&mut self, id: usize, max_log_rate: Option<lograte::MaxLogRate>) -> &mut Self {
+        self.process(id).max_log_rate = max_log_rate;
+        return self;
+    }
+
+    /// A new check starts without the outcome of the one it replaces
+    pub fn set_health(&mut self, id: usize, health: Option<health::Check>) -> &mut Self {
+        let process = self.process(id);
+        process.health = health;
+        process.health_status = None;
+        return self;
+    }
+
+    pub fn set_max_memory_action(&mut self, id: usize, action: memlimit::Action) -> &mut Self {
+        self.process(id).max_memory_action = action;
+        return self;
+    }
+
+    /// Restarts at the times of `schedule` are counted from now, one set in the past is not due right away
+    pub fn set_restart_cron(&mut self, id: usize, schedule: Option<cron::Schedule>) -> &mut Self {
+        let process = self.process(id);
+        process.restart_cron_since = schedule.as_ref().map(|_| Utc::now());
+        process.restart_cron = schedule;
+        return self;
+    }
+
+    /// Track the pid a forking service writes to `path` instead of the command that launched it.
+    /// On failure the launcher is stopped and the process left stopped, a restart tries again.
+    pub fn adopt(&mut self, id: usize, path: PathBuf) -> Result<i64, String> {
+        let process = self.process(id);
+        let launcher = process.shell_pid.unwrap_or(process.pid);
+
+        match adopt::adopt(&path, launcher, process.exit_code, adopt::PIDFILE_TIMEOUT) {
+            Ok((pid, pidfile)) => {
+                // A launcher completing right away is what forking services do
+                process.pid = pid;
+                process.shell_pid = None;
+                process.pidfile = Some(pidfile);
+                process.running = true;
+                process.crash.crashed = false;
+                process.exit_code = None;
+                Ok(pid)
+            }
+            Err(err) => {
+                let _ = process_stop(launcher);
+                process.pidfile = Some(adopt::Pidfile::new(path));
+                process.running = false;
+                process.crash.crashed = true;
+                Err(err)
+            }
+        }
+    }
+
+    pub fn watch(&mut self, id: usize, path: &str, enabled: bool) -> &mut Self {
+        let process = self.process(id);
+        process.watch = Watch {
+            enabled,
+            path: string!(path),
+            hash: ternary!(enabled, hash::create(process.path.join(path)), string!("")),
+            last_reload: None,
+            pending: None,
+            ..process.watch.clone()
+        };
+
+        return self;
+    }
+
+    /// Reload a process the same way the watcher does and record why. The watch hash is
+    /// refreshed so the daemon doesn't reload again when it notices the same change.
+    pub fn trigger_reload(&mut self, id: usize, reason: &str) -> Result<i64, String> {
+        if let Some(remote) = &self.remote {
+            return http::trigger(remote, id, reason).map_err(|err| err.to_string());
+        }
+
+        self.reload_for(id, reason, None)
+    }
+
+    /// `trigger_reload` for a change the daemon found, `hash` is what the watched path hashed to.
+    /// It is stored as is, so a large tree is not hashed a second time.
+    pub fn watch_reload(&mut self, id: usize, hash: String) -> Result<i64, String> { self.reload_for(id, "watch", Some(hash)) }
+
+    fn reload_for(&mut self, id: usize, reason: &str, watched: Option<String>) -> Result<i64, String> {
+        self.restart(id, false, true);
+
+        let process = self.process(id);
+        process.last_restart_reason = Some(string!(reason));
+
+        if process.watch.enabled {
+            process.watch.hash = watched.unwrap_or_else(|| hash::create(process.path.join(&process.watch.path)));
+        }
+
+        match process.running {
+            true => Ok(process.pid),
+            false => Err(format!("failed to restart process {id}")),
+        }
+    }
+
+    pub fn reset_counters(&mut self, id: usize) -> &mut Self {
+        let process = self.process(id);
+        process.restarts = 0;
+        process.crash.value = 0;
+        process.crash.crashed = false;
+        process.crash.next_restart = None;
+        process.memory_restarts = 0;
+        return self;
+    }
+
+    pub fn find(&self, name: &str, server_name: &String) -> Option<usize> {
+        let mut runner = self.clone();
+
+        if !matches!(&**server_name, "internal" | "local") {
+            let Some(servers) = config::servers().servers else {
+                crashln!("{} Failed to read servers", *helpers::FAIL)
+            };
+
+            if let Some(server) = servers.get(server_name) {
+                runner = match Runner::connect(server_name.clone(), server.get(), false) {
+                    Some(remote) => remote,
+                    None => crashln!(
+                        "{} Failed to connect (name={server_name}, address={})",
+                        *helpers::FAIL,
+                        server.address
+                    ),
+                };
+            } else {
+                crashln!("{} Server '{server_name}' does not exist", *helpers::FAIL)
+            };
+        }
+
+        runner
+            .list
+            .iter()
+            .find(|(_, p)| p.name == name)
+            .map(|(id, _)| *id)
+    }
+
+    /// Ids of the processes carrying every one of the given labels
+    pub fn find_by_labels(&self, filters: &[(String, String)]) -> Vec<usize> {
+        self.list
+            .iter()
+            .filter(|(_, p)| labels::matches(&p.labels, filters))
+            .map(|(id, _)| *id)
+            .collect()
+    }
+
+    /// Fast cpu and memory of a process including its children, as shown in the list view
+    fn sample_usage(item: &Process) -> (Option<f64>, Option<MemoryInfo>) {
+        let mut memory_usage: Option<MemoryInfo> = None;
+        let mut cpu_percent: Option<f64> = None;
+
+        // Use new_fast() to avoid CPU measurement delays for list view
+        // For shell scripts, try shell_pid first to capture the entire process tree
+        let mut pid_for_monitoring = item.shell_pid.unwrap_or(item.pid);
         let mut process_result = unix::NativeProcess::new_fast(pid_for_monitoring as u32);
 
         // If shell_pid fails (process exited), try the actual script pid
@@ -1073,10 +2010,27 @@ impl Runner {
         if let Ok(process) = process_result
             && let Ok(_mem_info_native) = process.memory_info()
         {
-            cpu_percent = Some(get_process_cpu_usage_with_children_fast(pid_for_monitoring));
-            memory_usage = get_process_memory_with_children(pid_for_monitoring);
+            cpu_percent = Some(item.cpu(&process, pid_for_monitoring));
+            memory_usage = item.memory(pid_for_monitoring);
         }
 
+        (cpu_percent, memory_usage)
+    }
+
+    /// Helper method to build ProcessItem from Process
+    /// Cpu and memory are only sampled with `sample`, they read as 0 otherwise
+    fn build_process_item(&self, id: usize, item: &Process, sample: bool) -> (ProcessItem, page::Measured) {
+        let (cpu_percent, memory_usage) = match sample {
+            true => Runner::sample_usage(item),
+            false => (None, None),
+        };
+
+        let mut measured = page::Measured {
+            cpu: cpu_percent.unwrap_or_default(),
+            memory: memory_usage.as_ref().map_or(0, |usage| usage.rss),
+            uptime: 0,
+        };
+
         let cpu_percent = match cpu_percent {
             Some(percent) => format!("{:.2}%", percent),
             None => string!("0.00%"),
@@ -1087,26 +2041,18 @@ impl Runner {
             None => string!("0b"),
         };
 
-        let process_actually_running = item.running && is_pid_alive(item.pid);
-        
-        let status = if process_actually_running {
-            string!("online")
-        } else if item.running {
-            string!("crashed")
-        } else {
-            match item.crash.crashed {
-                true => string!("crashed"),
-                false => string!("stopped"),
-            }
-        };
+        let process_actually_running = item.running && self.backend.alive(item.pid);
+        let status = item.status(process_actually_running, Utc::now()).to_string();
 
         let uptime = if process_actually_running {
-            helpers::format_duration(item.started)
+            let uptime = item.uptime(Utc::now());
+            measured.uptime = uptime.duration.num_seconds();
+            uptime.to_string()
         } else {
             string!("0s")
         };
 
-        ProcessItem {
+        let process = ProcessItem {
             id,
             status,
             pid: item.pid,
@@ -1119,19 +2065,100 @@ impl Runner {
             uptime,
             agent_id: item.agent_id.clone(),
             agent_name: None,
-        }
+            labels: item.labels.clone(),
+            group: item.group().map(String::from),
+            url: item.url.clone(),
+        };
+
+        (process, measured)
     }
 
     pub fn fetch(&self) -> Vec<ProcessItem> {
         let mut processes: Vec<ProcessItem> = Vec::new();
 
         for (id, item) in self.items() {
-            processes.push(self.build_process_item(id, &item));
+            processes.push(self.build_process_item(id, &item, true).0);
         }
 
         return processes;
     }
 
+    /// Every process in id order with the numbers `page::page` sorts by, sampled only with `sample`
+    pub fn fetch_measured(&self, sample: bool) -> Vec<(ProcessItem, page::Measured)> {
+        self.list.iter().map(|(id, item)| self.build_process_item(*id, item, sample)).collect()
+    }
+
+    /// Processes with their usage, sampled by the daemon this runner talks to
+    pub fn processes(&self) -> Result<Vec<ProcessItem>, String> {
+        if let Some(remote) = &self.remote {
+            return http::list(remote).map_err(|err| err.to_string());
+        }
+
+        Ok(self.fetch())
+    }
+
+    /// Resource usage totals per bucket, sampled the same way as the list view
+    pub fn summary(&self, by: &stats::By) -> Result<stats::Summary, String> {
+        if let Some(remote) = &self.remote {
+            return http::stats_summary(remote, by).map_err(|err| err.to_string());
+        }
+
+        let samples = self.list.values().map(|item| (item, Runner::sample(item)));
+        Ok(stats::summarize(samples, by))
+    }
+
+    /// Current usage of a local process, nothing for one that is not running
+    pub fn sample(item: &Process) -> stats::Sample {
+        let running = item.running && is_pid_alive(item.pid);
+        // A stopped process may have left its pid to something else
+        let (cpu, memory) = match running {
+            true => Runner::sample_usage(item),
+            false => (None, None),
+        };
+
+        // Only read for processes that run, a stopped one may have left its pid to something else
+        let handles = match running {
+            true => item.handles(item.shell_pid.unwrap_or(item.pid)),
+            false => Handles::default(),
+        };
+
+        stats::Sample {
+            running,
+            cpu: cpu.unwrap_or_default(),
+            memory: memory.map_or(0, |memory| memory.rss),
+            fds: handles.fds,
+            threads: handles.threads,
+        }
+    }
+
+    /// Latest audit log entries of the daemon this runner talks to
+    pub fn audit(&self, lines: usize, filter: &audit::Filter) -> Result<Vec<audit::Entry>, String> {
+        if let Some(remote) = &self.remote {
+            return http::audit(remote, lines, filter).map_err(|err| err.to_string());
+        }
+
+        Ok(audit::tail(lines, filter))
+    }
+
+    /// Latest process events recorded by the daemon this runner talks to
+    pub fn events(&self, lines: usize, filter: &events::Filter) -> Result<Vec<events::Event>, String> {
+        if let Some(remote) = &self.remote {
+            return http::events(remote, lines, filter).map_err(|err| err.to_string());
+        }
+
+        Ok(events::tail(lines, filter))
+    }
+
+    /// The last `limit` notification deliveries, read from the history the daemon keeps
+    pub fn notification_history(&self, limit: usize) -> Result<NotificationHistory, String> {
+        if let Some(remote) = &self.remote {
+            return http::notification_history(remote, limit).map_err(|err| err.to_string());
+        }
+
+        let channels = config::read().daemon.notifications.and_then(|notifications| notifications.channels).unwrap_or_default();
+        Ok(History::new(Some(history::path())).report(limit, &channels))
+    }
+
     /// Fetch processes filtered by agent ID
     pub fn fetch_by_agent(&self, agent_id: &str) -> Vec<ProcessItem> {
         let mut processes: Vec<ProcessItem> = Vec::new();
@@ -1139,7 +2166,7 @@ impl Runner {
         for (id, item) in self.items() {
             // Only include processes that belong to the specified agent
             if item.agent_id.as_deref() == Some(agent_id) {
-                processes.push(self.build_process_item(id, &item));
+                processes.push(self.build_process_item(id, &item, true).0);
             }
         }
 
@@ -1148,43 +2175,205 @@ impl Runner {
 }
 
 impl LogInfo {
-    pub fn flush(&self) {
-        if let Err(err) = File::create(&self.out) {
-            log::error!("{err}");
-            crashln!(
-                "{} Failed to purge logs (path={})",
-                *helpers::FAIL,
-                self.error
-            );
+    /// Empty both logs, a log that was never written is already empty and not created
+    pub fn truncate(&self) -> io::Result<()> {
+        for path in [&self.out, &self.error] {
+            match OpenOptions::new().write(true).truncate(true).open(path) {
+                Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(io::Error::new(err.kind(), format!("{path}: {err}"))),
+                _ => checkpoint::remove(path),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Rotated archives of the out log, newest first
+    pub fn out_archives(&self) -> Vec<PathBuf> { rotate::archives(&PathBuf::from(&self.out)) }
+
+    /// Rotated archives of the error log, newest first
+    pub fn error_archives(&self) -> Vec<PathBuf> { rotate::archives(&PathBuf::from(&self.error)) }
+
+    /// Files that belong to a log: the log itself, its checkpoint index and its numbered rotations
+    fn files(log: &str) -> Vec<PathBuf> {
+        let path = PathBuf::from(log);
+        let mut files = vec![path.clone(), PathBuf::from(checkpoint::path(log))];
+        files.extend(rotate::archives(&path));
+        files
+    }
+
+    /// Delete both logs with their indexes and rotations, leaving the logs in `shared` alone
+    pub fn purge(&self, shared: &HashSet<String>) -> PurgedLogs {
+        let mut purged = PurgedLogs::default();
+
+        for log in [&self.out, &self.error] {
+            if shared.contains(log) {
+                purged.shared.push(log.clone());
+                continue;
+            }
+
+            for path in LogInfo::files(log) {
+                let Ok(metadata) = std::fs::metadata(&path) else { continue };
+                match std::fs::remove_file(&path) {
+                    Ok(()) => purged.removed.push(RemovedLog { path: path.display().to_string(), bytes: metadata.len() }),
+                    Err(err) => purged.failed.push(format!("{}: {err}", path.display())),
+                }
+            }
         }
 
-        if let Err(err) = File::create(&self.error) {
+        purged
+    }
+
+    pub fn flush(&self) {
+        if let Err(err) = self.truncate() {
             log::error!("{err}");
-            crashln!(
-                "{} Failed to purge logs (path={})",
-                *helpers::FAIL,
-                self.error
-            );
+            crashln!("{} Failed to purge logs ({err})", *helpers::FAIL);
         }
     }
 }
 
 impl Process {
+    /// Record a command that completed right after it was launched. Nothing is left running, so it is
+    /// stopped rather than restarted, and counts as crashed when the exit code is not 0.
+    pub fn completed(&mut self, code: i32) {
+        self.shell_pid = None;
+        self.running = false;
+        self.children = vec![];
+        self.crash.crashed = code != 0;
+        self.exit_code = Some(code);
+    }
+
+    /// Merge the environment of whoever started it and `.env` into the stored one, `.env` taking priority.
+    /// Pinned variables keep the value they were given unless `.env` sets them.
+    fn refresh_env(&mut self, dotenv: Env) {
+        let mut updated: Env = env::vars().filter(|(key, _)| !self.pinned_env.contains(key)).collect();
+        updated.extend(dotenv);
+        self.env.extend(updated);
+    }
+
+    /// Store `vars` as pinned variables, below `.env` but above the environment of whoever restarts it
+    fn pin_env(&mut self, vars: &Env, dotenv: &Env) {
+        for (key, value) in vars {
+            then!(!dotenv.contains_key(key), self.env.insert(key.clone(), value.clone()));
+            then!(!self.pinned_env.contains(key), self.pinned_env.push(key.clone()));
+        }
+    }
+
+    /// False once the adopted pid of a forking service was reused by another process
+    pub fn owns_pid(&self) -> bool {
+        self.pidfile.as_ref().is_none_or(|pidfile| pidfile.owns(self.pid))
+    }
+
     /// Get a log paths of the process item
+    /// Log files of the process, in the directory it was started with
     pub fn logs(&self) -> LogInfo {
         let name = self.name.replace(" ", "_");
 
-        LogInfo {
-            out: global!("opm.logs.out", name.as_str()),
-            error: global!("opm.logs.error", name.as_str()),
+        match &self.log_path {
+            Some(dir) => LogInfo {
+                out: format!("{dir}/{name}-out.log"),
+                error: format!("{dir}/{name}-error.log"),
+            },
+            // Started before the directory was recorded
+            None => LogInfo {
+                out: global!("opm.logs.out", name.as_str()),
+                error: global!("opm.logs.error", name.as_str()),
+            },
+        }
+    }
+
+    /// Startup grace period of the process in seconds
+    pub fn startup_grace(&self) -> i64 {
+        self.startup_grace.map_or(STARTUP_GRACE_PERIOD_SECS, |secs| secs.min(i64::MAX as u64) as i64)
+    }
+
+    /// Milliseconds between the daemon's checks of the process, `default` being `daemon.interval`
+    pub fn monitor_interval(&self, default: u64) -> u64 {
+        self.monitor_interval_ms.unwrap_or(default)
+    }
+
+    /// Crashes the daemon restarts the process after, `default` being `daemon.restarts`
+    pub fn restart_limit(&self, default: u64) -> u64 { self.max_restarts.unwrap_or(default) }
+
+    /// Where the restart limit comes from, for logs and notifications
+    pub fn restart_limit_source(&self) -> &'static str { ternary!(self.max_restarts.is_some(), "max_restarts", "daemon.restarts") }
+
+    /// Children to stop along with the process, looked up right now where they are not tracked
+    pub fn tree(&self) -> Vec<i64> {
+        match self.track_children {
+            true => self.children.clone(),
+            false => process_find_children(self.shell_pid.unwrap_or(self.pid)),
+        }
+    }
+
+    /// Whether `live` children are worth a dump write, the first ones are saved right away
+    /// and later changes at most every `CHILDREN_SAVE_CYCLES` cycles, counted by `cycles`
+    pub fn children_due(&self, live: &[i64], cycles: u32) -> bool {
+        self.track_children && !live.is_empty() && live != self.children && (self.children.is_empty() || cycles >= CHILDREN_SAVE_CYCLES)
+    }
+
+    /// Cpu usage of `process`, with its children where they are tracked
+    pub fn cpu(&self, process: &unix::NativeProcess, pid: i64) -> f64 {
+        match self.track_children {
+            true => get_process_cpu_usage_with_children_from_process(process, pid),
+            false => process.cpu_percent().unwrap_or(0.0),
+        }
+    }
+
+    /// Current cpu usage of `pid`, with its children where they are tracked, in a single sample window
+    pub fn cpu_timed(&self, pid: i64) -> f64 {
+        match self.track_children {
+            true => get_process_cpu_usage_timed(pid).1,
+            false => unix::get_cpu_percent(pid as u32),
+        }
+    }
+
+    /// Memory of `pid`, with its children where they are tracked
+    pub fn memory(&self, pid: i64) -> Option<MemoryInfo> { ternary!(self.track_children, get_process_memory_with_children(pid), get_process_memory(pid)) }
+
+    /// Open files and threads of `pid`, with its children where they are tracked
+    pub fn handles(&self, pid: i64) -> Handles { ternary!(self.track_children, get_process_handles_with_children(pid), get_process_handles(pid)) }
+
+    /// Time since the process was started, `now` only counts where no monotonic start was recorded
+    pub fn uptime(&self, now: DateTime<Utc>) -> clock::Elapsed { clock::elapsed(self.started, self.started_mark.as_ref(), now, clock::now().as_ref()) }
+
+    /// Whether the process was started less than its grace period before `now`.
+    /// A process with a health check also waits for one to pass before it is online, see `status`.
+    /// A start the wall clock puts in the future says nothing about how long ago it was, it does not hold the grace period open.
+    pub fn is_starting(&self, now: DateTime<Utc>) -> bool {
+        let uptime = self.uptime(now);
+        self.running && !uptime.skewed && uptime.millis() < self.startup_grace().saturating_mul(1000)
+    }
+
+    /// Group of the process, the `group` label for those labeled before groups existed
+    pub fn group(&self) -> Option<&str> { self.group.as_deref().or_else(|| self.labels.get("group").map(String::as_str)) }
+
+    /// Outcome of the last health check since the process was started, older ones were of a previous run
+    pub fn health_status(&self) -> Option<&health::Status> { self.health_status.as_ref().filter(|status| status.at >= self.started) }
+
+    /// Status shown in list and info, `alive` tells whether the pid of the process still exists
+    pub fn status(&self, alive: bool, now: DateTime<Utc>) -> &'static str {
+        if self.pending {
+            "pending"
+        } else if self.running && alive {
+            match (self.is_starting(now), self.health.is_some(), self.health_status()) {
+                (true, ..) | (false, true, None) => "starting",
+                (false, true, Some(status)) if !status.healthy => "unhealthy",
+                _ => "online",
+            }
+        } else if self.errored.is_some() {
+            "errored"
+        } else if self.running || self.crash.crashed {
+            "crashed"
+        } else {
+            "stopped"
         }
     }
 }
 
 impl ProcessWrapper {
     /// Stop the process item
-    pub fn stop(&mut self) {
-        lock!(self.runner).stop(self.id);
+    pub fn stop(&mut self, signal: Option<Signal>) {
+        lock!(self.runner).stop(self.id, signal);
     }
 
     /// Restart the process item
@@ -1229,6 +2418,11 @@ impl ProcessWrapper {
         lock!(self.runner).set_env(self.id, env);
     }
 
+    /// Replace the dependencies of the process item
+    pub fn set_depends_on(&mut self, depends_on: Vec<String>) {
+        lock!(self.runner).set_depends_on(self.id, depends_on);
+    }
+
     /// Clear environment values of the process item
     pub fn clear_env(&mut self) {
         lock!(self.runner).clear_env(self.id);
@@ -1243,15 +2437,19 @@ impl ProcessWrapper {
     pub fn fetch(&self) -> ItemSingle {
         let mut runner = lock!(self.runner);
 
+        let backend = runner.backend.clone();
         let item = runner.process(self.id);
         let config = config::read().runner;
 
         // Check if process actually exists before reporting as online
         // A process marked as running but with a non-existent PID should be shown as crashed
-        let process_actually_running = item.running && is_pid_alive(item.pid);
+        let process_actually_running = item.running && backend.alive(item.pid);
         
         let mut memory_usage: Option<MemoryInfo> = None;
         let mut cpu_percent: Option<f64> = None;
+        let mut log_rate: Option<u64> = None;
+        let mut handles = Handles::default();
+        let mut zombies = 0;
 
         // Only fetch CPU and memory stats if process is actually running
         // Stopped or crashed processes should always show None (which displays as 0)
@@ -1259,41 +2457,33 @@ impl ProcessWrapper {
             // For shell scripts, try shell_pid first to capture the entire process tree
             // If shell_pid process has exited, fall back to the actual script pid
             let mut pid_for_monitoring = item.shell_pid.unwrap_or(item.pid);
-            let mut process_result = unix::NativeProcess::new(pid_for_monitoring as u32);
+            let mut process_result = unix::NativeProcess::new_fast(pid_for_monitoring as u32);
 
             // If shell_pid fails (process exited), try the actual script pid
             if process_result.is_err() && item.shell_pid.is_some() {
                 pid_for_monitoring = item.pid;
-                process_result = unix::NativeProcess::new(pid_for_monitoring as u32);
+                process_result = unix::NativeProcess::new_fast(pid_for_monitoring as u32);
             }
 
             if let Ok(process) = process_result
                 && let Ok(_mem_info_native) = process.memory_info()
             {
-                cpu_percent = Some(get_process_cpu_usage_with_children_from_process(
-                    &process,
-                    pid_for_monitoring,
-                ));
-                memory_usage = get_process_memory_with_children(pid_for_monitoring);
+                let (cpu, rate) = lograte::during(item, || item.cpu_timed(pid_for_monitoring));
+                (cpu_percent, log_rate) = (Some(cpu), rate);
+                memory_usage = item.memory(pid_for_monitoring);
+                handles = item.handles(pid_for_monitoring);
             }
+
+            zombies = process_find_zombies(pid_for_monitoring).len();
         }
         
-        let status = if process_actually_running {
-            string!("online")
-        } else if item.running {
-            // Process is marked as running but PID doesn't exist - it crashed
-            string!("crashed")
-        } else {
-            match item.crash.crashed {
-                true => string!("crashed"),
-                false => string!("stopped"),
-            }
-        };
+        // Marked as running but with a dead PID shows as crashed
+        let status = item.status(process_actually_running, Utc::now()).to_string();
 
         // Only count uptime when the process is actually running
         // Crashed or stopped processes should show "0s" uptime
         let uptime = if process_actually_running {
-            helpers::format_duration(item.started)
+            item.uptime(Utc::now()).to_string()
         } else {
             string!("0s")
         };
@@ -1306,25 +2496,28 @@ impl ProcessWrapper {
                 name: item.name.clone(),
                 path: item.path.clone(),
                 children: item.children.clone(),
+                zombies,
+                fingerprint: item.fingerprint.clone(),
                 uptime,
-                command: format!(
-                    "{} {} '{}'",
-                    config.shell,
-                    config.args.join(" "),
-                    item.script.clone()
-                ),
+                command: item.command_line(&config.shell, &config.args),
+                labels: item.labels.clone(),
+                description: item.description.clone(),
+                last_restart_reason: item.last_restart_reason.clone(),
+                url: item.url.clone(),
+                max_restarts: item.max_restarts,
+                health: item.health_status().cloned(),
             },
             stats: Stats {
                 cpu_percent,
                 memory_usage,
                 restarts: item.restarts,
                 start_time: item.started.timestamp_millis(),
+                fds: handles.fds,
+                threads: handles.threads,
+                cpu_sample: cpu_percent.map(|_| CpuSample::Timed),
+                log_rate,
             },
-            watch: Watch {
-                enabled: item.watch.enabled,
-                hash: item.watch.hash.clone(),
-                path: item.watch.path.clone(),
-            },
+            watch: item.watch.clone(),
             log: Log {
                 out: item.logs().out,
                 error: item.logs().error,
@@ -1400,30 +2593,28 @@ pub fn get_process_cpu_usage_with_children_fast(pid: i64) -> f64 {
 }
 
 /// Get the total CPU usage percentage of the process and its children
-/// Uses timed measurement for parent and fast measurements for children
-/// to avoid cumulative delays while maintaining accurate parent measurement
-pub fn get_process_cpu_usage_with_children(pid: i64) -> f64 {
-    let parent_cpu = get_process_cpu_usage_percentage(pid);
-    let children = process_find_children(pid);
-
-    // Use fast CPU calculation for children to avoid multiple delays
-    // The parent already used a timed measurement, so children should use fast measurements
-    // for consistency and to prevent cumulative delays (N children = N * 100ms)
-    let children_cpu: f64 = children
-        .iter()
-        .map(|&child_pid| get_process_cpu_usage_percentage_fast(child_pid))
-        .sum();
-
-    parent_cpu + children_cpu
+/// The process and every child are sampled together, so it waits a single window however many children there are
+pub fn get_process_cpu_usage_with_children(pid: i64) -> f64 { get_process_cpu_usage_timed(pid).1 }
+
+/// Current CPU usage of the process alone and of the process with its children, from one timed sample
+pub fn get_process_cpu_usage_timed(pid: i64) -> (f64, f64) {
+    let pids: Vec<u32> = std::iter::once(pid).chain(process_find_children(pid)).map(|pid| pid as u32).collect();
+    let samples = unix::get_cpu_percent_batch(&pids, unix::cpu::sample_window());
+    (samples[0], samples.iter().sum())
 }
 
-/// Get the total memory usage of the process and its children
-pub fn get_process_memory_with_children(pid: i64) -> Option<MemoryInfo> {
-    let parent_memory = unix::NativeProcess::new_fast(pid as u32)
+/// Get the memory usage of the process alone
+pub fn get_process_memory(pid: i64) -> Option<MemoryInfo> {
+    unix::NativeProcess::new_fast(pid as u32)
         .ok()?
         .memory_info()
         .ok()
-        .map(MemoryInfo::from)?;
+        .map(MemoryInfo::from)
+}
+
+/// Get the total memory usage of the process and its children
+pub fn get_process_memory_with_children(pid: i64) -> Option<MemoryInfo> {
+    let parent_memory = get_process_memory(pid)?;
 
     let children = process_find_children(pid);
 
@@ -1445,42 +2636,143 @@ pub fn get_process_memory_with_children(pid: i64) -> Option<MemoryInfo> {
     })
 }
 
+/// Open file descriptors and threads of a process tree
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Handles {
+    pub fds: Option<u64>,
+    pub threads: Option<u64>,
+}
+
+/// Count the open file descriptors and threads of a process alone
+pub fn get_process_handles(pid: i64) -> Handles {
+    match pid > 0 {
+        true => Handles { fds: unix::open_fds(pid as u32), threads: unix::thread_count(pid as u32) },
+        false => Handles::default(),
+    }
+}
+
+/// Count the open file descriptors and threads of a process and its children.
+/// Each is None when the process' own cannot be read, children that cannot be read are skipped.
+pub fn get_process_handles_with_children(pid: i64) -> Handles {
+    let Handles { fds, threads } = get_process_handles(pid);
+    if fds.is_none() && threads.is_none() {
+        return Handles::default();
+    }
+
+    let children = process_find_children(pid);
+    let sum = |own: Option<u64>, count: fn(u32) -> Option<u64>| own.map(|own| own + children.iter().filter_map(|&child| count(child as u32)).sum::<u64>());
+
+    Handles {
+        fds: sum(fds, unix::open_fds),
+        threads: sum(threads, unix::thread_count),
+    }
+}
+
 /// Stop the process
-pub fn process_stop(pid: i64) -> Result<(), String> {
+pub fn process_stop(pid: i64) -> Result<(), String> { process_stop_with_signal(pid, Signal::SIGTERM) }
+
+/// Stop the process and its children with `signal`
+pub fn process_stop_with_signal(pid: i64, signal: Signal) -> Result<(), String> {
     // Don't attempt to stop invalid PIDs
     // PID 0 sends signal to all processes in current process group (would kill daemon)
     // Negative PIDs send signal to process groups
     if pid <= 0 {
         return Ok(());
     }
+
+    // Bail out before signalling any children when the process belongs to another user
+    if is_signal_denied(pid) {
+        let name = unix::get_process_name(pid as u32).unwrap_or_else(|_| string!("unknown"));
+        return Err(permission_denied(pid, &name));
+    }
     
     let children = process_find_children(pid);
 
     // Stop child processes first
     for child_pid in children {
-        let _ = kill(Pid::from_raw(child_pid as i32), Signal::SIGTERM);
+        let _ = kill(Pid::from_raw(child_pid as i32), signal);
         // Continue even if stopping child processes fails
     }
 
-    // Stop parent process
-    match kill(Pid::from_raw(pid as i32), Signal::SIGTERM) {
-        Ok(_) => Ok(()),
-        Err(nix::errno::Errno::ESRCH) => {
-            // Process already terminated
-            Ok(())
+    // Stop parent process
+    match kill(Pid::from_raw(pid as i32), signal) {
+        Ok(_) => Ok(()),
+        Err(nix::errno::Errno::ESRCH) => {
+            // Process already terminated
+            Ok(())
+        }
+        Err(err) => Err(format!("Failed to stop process {}: {:?}", pid, err)),
+    }
+}
+
+/// Descendants of a process, defunct ones apart since they use no cpu or memory
+#[derive(Debug, Default, PartialEq)]
+pub struct Children {
+    pub live: Vec<i64>,
+    /// Exited but never waited for by their parent
+    pub zombies: Vec<i64>,
+}
+
+/// Outcome of waiting on a pid the daemon may have spawned
+#[derive(Debug, PartialEq)]
+pub enum Reap {
+    Running,
+    Reaped,
+    /// Someone else's child, or already reaped
+    NotChild,
+}
+
+/// Find the live children of the process
+pub fn process_find_children(parent_pid: i64) -> Vec<i64> { process_classify_children(parent_pid).live }
+
+/// Find the zombie children of the process
+pub fn process_find_zombies(parent_pid: i64) -> Vec<i64> { process_classify_children(parent_pid).zombies }
+
+/// Collect the exit status of a zombie, only works for children of the calling process
+pub fn reap(pid: i64) -> Reap {
+    if pid <= 0 {
+        return Reap::NotChild;
+    }
+
+    match waitpid(Pid::from_raw(pid as i32), Some(WaitPidFlag::WNOHANG)) {
+        Ok(WaitStatus::StillAlive) => Reap::Running,
+        Ok(_) => Reap::Reaped,
+        Err(_) => Reap::NotChild,
+    }
+}
+
+/// Held shared by spawns waiting on their own child, and exclusively by `reap_all`
+static OWN_WAITS: RwLock<()> = RwLock::new(());
+
+/// Keep `reap_all` from taking the exit status of a child the caller is about to wait on
+pub fn own_wait() -> RwLockReadGuard<'static, ()> { OWN_WAITS.read().unwrap_or_else(PoisonError::into_inner) }
+
+/// Reap every exited child of the calling process, orphans it was handed as PID 1 included, returning their pids.
+/// Spawns holding `own_wait` are waited for first, so they still get the status of their own child.
+pub fn reap_all() -> Vec<i64> {
+    let _exclusive = OWN_WAITS.write().unwrap_or_else(PoisonError::into_inner);
+    let mut reaped = vec![];
+
+    loop {
+        match waitpid(Pid::from_raw(-1), Some(WaitPidFlag::WNOHANG)) {
+            Ok(WaitStatus::StillAlive) | Err(_) => break,
+            Ok(status) => reaped.extend(status.pid().map(|pid| pid.as_raw() as i64)),
         }
-        Err(err) => Err(format!("Failed to stop process {}: {:?}", pid, err)),
     }
+
+    reaped
 }
 
-/// Find the children of the process
-pub fn process_find_children(parent_pid: i64) -> Vec<i64> {
+/// Find the descendants of the process, a zombie has no children of its own to follow
+pub fn process_classify_children(parent_pid: i64) -> Children {
     let mut children = Vec::new();
+    let mut zombies = Vec::new();
     let mut to_check = vec![parent_pid];
     let mut checked = HashSet::new();
 
     #[cfg(target_os = "linux")]
     {
+        counters::add(counters::Counter::ChildrenScan);
         while let Some(pid) = to_check.pop() {
             if checked.contains(&pid) {
                 continue;
@@ -1494,8 +2786,12 @@ pub fn process_find_children(parent_pid: i64) -> Vec<i64> {
 
             for child_pid_str in contents.split_whitespace() {
                 if let Ok(child_pid) = child_pid_str.parse::<i64>() {
-                    children.push(child_pid);
-                    to_check.push(child_pid); // Check grandchildren
+                    if unix::is_process_zombie(child_pid as i32) {
+                        zombies.push(child_pid);
+                    } else {
+                        children.push(child_pid);
+                        to_check.push(child_pid); // Check grandchildren
+                    }
                 }
             }
         }
@@ -1522,9 +2818,15 @@ pub fn process_find_children(parent_pid: i64) -> Vec<i64> {
                 {
                     for &child in direct_children {
                         if !checked.contains(&child) {
-                            children.push(child);
-                            to_check.push(child);
                             checked.insert(child);
+
+                            match unix::is_process_zombie(child as i32) {
+                                true => zombies.push(child),
+                                false => {
+                                    children.push(child);
+                                    to_check.push(child);
+                                }
+                            }
                         }
                     }
                 }
@@ -1535,7 +2837,7 @@ pub fn process_find_children(parent_pid: i64) -> Vec<i64> {
         }
     }
 
-    children
+    Children { live: children, zombies }
 }
 
 /// Result of running a process
@@ -1543,47 +2845,91 @@ pub fn process_find_children(parent_pid: i64) -> Vec<i64> {
 pub struct ProcessRunResult {
     pub pid: i64,
     pub shell_pid: Option<i64>,
+    /// Set when the command completed before it could be tracked, with its exit code
+    /// (128 + the signal when it was killed). `pid` then no longer exists.
+    pub exit_code: Option<i32>,
 }
 
-/// Run the process
-pub fn process_run(metadata: ProcessMetadata) -> Result<ProcessRunResult, String> {
-    use std::fs::OpenOptions;
-    use std::process::{Command, Stdio};
+/// Run a process, for a forking service the pid it writes to its pidfile is returned instead
+fn launch(backend: &dyn RunnerBackend, metadata: ProcessMetadata, pidfile: Option<&adopt::Pidfile>) -> Result<(ProcessRunResult, Option<adopt::Pidfile>), String> {
+    let Some(pidfile) = pidfile else {
+        return backend.run(metadata).map(|result| (result, None));
+    };
+
+    adopt::clear_stale(&pidfile.path)?;
+    let result = backend.run(metadata)?;
+    let launcher = result.shell_pid.unwrap_or(result.pid);
+
+    match adopt::adopt(&pidfile.path, launcher, result.exit_code, adopt::PIDFILE_TIMEOUT) {
+        Ok((pid, adopted)) => Ok((ProcessRunResult { pid, shell_pid: None, exit_code: None }, Some(adopted))),
+        Err(err) => {
+            let _ = process_stop(launcher);
+            Err(err)
+        }
+    }
+}
 
+/// Descriptors handed to children: stdin, stdout and stderr.
+/// Passed listeners would be dup'ed right above them and counted here.
+const INHERITED_FDS: std::os::fd::RawFd = 3;
+
+/// The log paths of a process and their files, both /dev/null when `full` and `runner.logs_on_enospc = "devnull"`
+fn open_logs(metadata: &ProcessMetadata, full: bool) -> Result<(String, String, std::fs::File, std::fs::File), String> {
     let log_base = format!("{}/{}", metadata.log_path, metadata.name.replace(' ', "_"));
     let stdout_path = format!("{}-out.log", log_base);
     let stderr_path = format!("{}-error.log", log_base);
 
-    // Create log files
-    let stdout_file = OpenOptions::new()
-        .create(true)
-        .append(true)
-        .open(&stdout_path)
-        .map_err(|err| {
-            format!(
-                "Failed to open stdout log file '{}': {}. \
-                Check that the directory exists and you have write permissions.",
-                stdout_path, err
-            )
-        })?;
-
-    let stderr_file = OpenOptions::new()
-        .create(true)
-        .append(true)
-        .open(&stderr_path)
-        .map_err(|err| {
-            format!(
-                "Failed to open stderr log file '{}': {}. \
-                Check that the directory exists and you have write permissions.",
-                stderr_path, err
-            )
-        })?;
+    let open = |kind: &str, path: &str| match full {
+        true => Err((format!("{kind} log file '{path}'"), io::Error::from_raw_os_error(libc::ENOSPC))),
+        false => OpenOptions::new().create(true).append(true).open(path).map_err(|err| (format!("{kind} log file '{path}'"), err)),
+    };
+
+    match open("stdout", &stdout_path).and_then(|stdout| Ok((stdout, open("stderr", &stderr_path)?))) {
+        Ok((stdout, stderr)) => Ok((stdout_path, stderr_path, stdout, stderr)),
+        Err((_, err)) if logspace::is_enospc(&err) && metadata.logs_on_enospc == LogsOnEnospc::Devnull => {
+            log::warn!("{} in {}, output of '{}' goes to /dev/null", logspace::FULL, metadata.log_path, metadata.name);
+
+            let devnull = || OpenOptions::new().write(true).open("/dev/null").map_err(|err| format!("Failed to open /dev/null: {err}"));
+            Ok((string!("/dev/null"), string!("/dev/null"), devnull()?, devnull()?))
+        }
+        Err((file, err)) if logspace::is_enospc(&err) => Err(format!("{}: cannot open {file}, set runner.logs_on_enospc = \"devnull\" to start without logs", logspace::FULL)),
+        Err((file, err)) => Err(format!(
+            "Failed to open {}: {}. \
+            Check that the directory exists and you have write permissions.",
+            file, err
+        )),
+    }
+}
+
+/// Run the process
+pub fn process_run(metadata: ProcessMetadata) -> Result<ProcessRunResult, String> {
+    use std::os::unix::process::CommandExt;
+    use std::process::{Command, Stdio};
+
+    // Checked here rather than left to the child, where failing to apply them would only show as a crash
+    metadata.limits.check()?;
+
+    // Appending to a log that exists works on a full filesystem, everything written to it would be lost
+    let (stdout_path, stderr_path, stdout_file, stderr_file) = open_logs(&metadata, logspace::full(&metadata.log_path))?;
+
+    // A supervised command writes through its supervisor, which opens the logs itself
+    let state = metadata.supervised.then(|| supervise::State::path(&metadata.name));
+    let mut cmd = match &state {
+        Some(state) => {
+            let mut command: Vec<&str> = vec![&metadata.shell];
+            command.extend(metadata.args.iter().map(String::as_str));
+            command.push(&metadata.command);
+            supervise::command(state, &stdout_path, &stderr_path, &command)?
+        }
+        None => {
+            let mut cmd = Command::new(&metadata.shell);
+            cmd.args(&metadata.args).arg(&metadata.command).stdout(Stdio::from(stdout_file)).stderr(Stdio::from(stderr_file));
+            cmd
+        }
+    };
 
     // Execute process
-    let mut cmd = Command::new(&metadata.shell);
-    cmd.args(&metadata.args)
-        .arg(&metadata.command)
-        .envs(metadata.env.iter().map(|env_var| {
+    cmd.envs(metadata.env.iter().map(|env_var| {
             let parts: Vec<&str> = env_var.splitn(2, '=').collect();
             if parts.len() == 2 {
                 (parts[0], parts[1])
@@ -1591,11 +2937,24 @@ pub fn process_run(metadata: ProcessMetadata) -> Result<ProcessRunResult, String
                 (env_var.as_str(), "")
             }
         }))
-        .stdout(Stdio::from(stdout_file))
-        .stderr(Stdio::from(stderr_file))
         .stdin(Stdio::null());
 
-    let child = cmd.spawn().map_err(|err| {
+    // Anything else open in the daemon (API listener, a dump being saved, other logs,
+    // descriptors it inherited itself) must not outlive it in the child. Marked close-on-exec
+    // rather than closed so std still reports exec failures through its own pipe.
+    let fd_limit = unix::fd_limit();
+    let limits = metadata.limits;
+    unsafe {
+        cmd.pre_exec(move || {
+            unix::set_cloexec_from(INHERITED_FDS, fd_limit);
+            limits.apply()
+        });
+    }
+
+    // Until its status is read below, the child is not for a PID 1 daemon to reap
+    let _own_wait = own_wait();
+    let launched = Utc::now();
+    let mut child = cmd.spawn().map_err(|err| {
         // Provide more helpful error messages based on error kind
         match err.kind() {
             std::io::ErrorKind::NotFound => format!(
@@ -1622,15 +2981,34 @@ pub fn process_run(metadata: ProcessMetadata) -> Result<ProcessRunResult, String
         }
     })?;
 
+    if let Some(state) = &state {
+        return supervise::report(&mut child, state, launched);
+    }
+
     let shell_pid = child.id() as i64;
     let actual_pid = unix::get_actual_child_pid(shell_pid);
 
+    // A shell that is gone and left nothing running means the command already completed,
+    // its pid would only ever be reported as a crash. Reaping it here also keeps it from lingering as a zombie.
+    if let Ok(Some(status)) = child.try_wait()
+        && (actual_pid == shell_pid || !is_pid_alive(actual_pid))
+    {
+        use std::os::unix::process::ExitStatusExt;
+
+        return Ok(ProcessRunResult {
+            pid: shell_pid,
+            shell_pid: None,
+            exit_code: Some(status.code().unwrap_or_else(|| 128 + status.signal().unwrap_or(0))),
+        });
+    }
+
     // If shell and actual PIDs differ, store the shell PID for CPU monitoring
     let shell_pid_opt = (shell_pid != actual_pid).then_some(shell_pid);
 
     Ok(ProcessRunResult {
         pid: actual_pid,
         shell_pid: shell_pid_opt,
+        exit_code: None,
     })
 }
 
@@ -1646,9 +3024,17 @@ mod tests {
             id: id::Id::new(1),
             list: BTreeMap::new(),
             remote: None,
+            host: None,
+            backend: Default::default(),
         }
     }
 
+    /// The details read the log paths of a process, which the binary sets up at startup
+    fn setup_log_paths() {
+        global_placeholders::init!("opm.logs.out", "/tmp/{}-out.log");
+        global_placeholders::init!("opm.logs.error", "/tmp/{}-error.log");
+    }
+
     // Use a PID value that's unlikely to exist in the test environment
     const UNLIKELY_PID: i64 = i32::MAX as i64 - 1000;
 
@@ -1660,25 +3046,12 @@ mod tests {
         let process = Process {
             id,
             pid: 12345,
-            shell_pid: None,
-            env: BTreeMap::new(),
             name: "test_process".to_string(),
             path: PathBuf::from("/tmp"),
             script: "echo 'hello world'".to_string(),
-            restarts: 0,
             running: true,
-            crash: Crash {
-                crashed: false,
-                value: 0,
-            },
-            watch: Watch {
-                enabled: false,
-                path: String::new(),
-                hash: String::new(),
-            },
-            children: vec![],
             started: Utc::now(),
-            max_memory: 0,
+            ..Default::default()
         };
 
         runner.list.insert(id, process);
@@ -1710,25 +3083,12 @@ mod tests {
         let process = Process {
             id,
             pid: 12345,
-            shell_pid: None,
-            env: BTreeMap::new(),
             name: "test_process".to_string(),
             path: PathBuf::from("/tmp"),
             script: "echo 'hello world'".to_string(),
-            restarts: 0,
             running: true,
-            crash: Crash {
-                crashed: false,
-                value: 0,
-            },
-            watch: Watch {
-                enabled: false,
-                path: String::new(),
-                hash: String::new(),
-            },
-            children: vec![],
             started: Utc::now(),
-            max_memory: 0,
+            ..Default::default()
         };
 
         runner.list.insert(id, process);
@@ -1740,6 +3100,85 @@ mod tests {
         assert_eq!(runner.info(id).unwrap().children, children);
     }
 
+    #[test]
+    fn test_truncate_logs() {
+        let dir = std::env::temp_dir().join(format!("opm-truncate-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let out = dir.join("app-out.log");
+        let logs = LogInfo {
+            out: out.display().to_string(),
+            error: dir.join("app-error.log").display().to_string(),
+        };
+
+        // Never written logs count as flushed and are not created
+        std::fs::write(&out, "line\n").unwrap();
+        assert!(logs.truncate().is_ok());
+        assert_eq!(std::fs::read_to_string(&out).unwrap(), "");
+        assert!(!dir.join("app-error.log").exists());
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_purge_logs() {
+        let dir = std::env::temp_dir().join(format!("opm-purge-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let path = |name: &str| dir.join(name).display().to_string();
+        for name in ["app-out.log", "app-out.log.idx", "app-out.log.1", "app-out.log.2.gz", "app-error.log", "app-out.log.bak", "app-outer.log"] {
+            std::fs::write(dir.join(name), "line\n").unwrap();
+        }
+
+        let logs = LogInfo { out: path("app-out.log"), error: path("app-error.log") };
+        let purged = logs.purge(&HashSet::from([path("app-error.log")]));
+
+        let mut removed: Vec<String> = purged.removed.iter().map(|log| log.path.clone()).collect();
+        removed.sort();
+        assert_eq!(removed, ["app-out.log", "app-out.log.1", "app-out.log.2.gz", "app-out.log.idx"].map(path));
+        assert_eq!(purged.bytes(), 20);
+
+        // Shared logs and files that only look alike stay
+        assert_eq!(purged.shared, vec![path("app-error.log")]);
+        assert!(purged.failed.is_empty());
+        assert!(["app-error.log", "app-out.log.bak", "app-outer.log"].iter().all(|name| dir.join(name).exists()));
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_zombie_children() {
+        // `exec` leaves a parent that never waits, the backgrounded child stays defunct
+        let mut parent = std::process::Command::new("sh").args(["-c", "sleep 0.1 & exec sleep 5"]).spawn().unwrap();
+        let pid = parent.id() as i64;
+
+        let mut children = Children::default();
+        for _ in 0..50 {
+            children = process_classify_children(pid);
+            if !children.zombies.is_empty() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(20));
+        }
+
+        assert_eq!(children.zombies.len(), 1);
+        assert!(children.live.is_empty());
+        assert!(process_find_children(pid).is_empty());
+        // Not a child of the test, only its own parent can collect it
+        assert_eq!(reap(children.zombies[0]), Reap::NotChild);
+
+        parent.kill().ok();
+        while !unix::is_process_zombie(pid as i32) {
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        assert_eq!(reap(pid), Reap::Reaped);
+        assert!(!is_pid_alive(pid));
+        assert_eq!(reap(pid), Reap::NotChild);
+        // Already reaped above, nothing is left to wait for
+        parent.wait().ok();
+    }
+
     #[test]
     fn test_cpu_usage_measurement() {
         // Test with current process (should return valid percentage)
@@ -1769,6 +3208,9 @@ mod tests {
             log_path: "/tmp".to_string(),
             args: vec!["-c".to_string()],
             env: vec!["TEST_ENV=test_value".to_string()],
+            limits: Default::default(),
+            supervised: false,
+            logs_on_enospc: LogsOnEnospc::Fail,
         };
 
         match process_run(metadata) {
@@ -1795,8 +3237,6 @@ mod tests {
         let process = Process {
             id,
             pid: 12345,
-            shell_pid: None,
-            env: BTreeMap::new(),
             name: "test_process".to_string(),
             path: PathBuf::from("/tmp"),
             script: "echo 'hello world'".to_string(),
@@ -1805,18 +3245,15 @@ mod tests {
             crash: Crash {
                 crashed: true, // Set to crashed
                 value: 3,      // Set to non-zero crash count
+                cores: vec![],
+                next_restart: None,
             },
-            watch: Watch {
-                enabled: false,
-                path: String::new(),
-                hash: String::new(),
-            },
-            children: vec![],
             started: Utc::now(),
-            max_memory: 0,
+            ..Default::default()
         };
 
         runner.list.insert(id, process);
+        runner.process(id).memory_restarts = 2;
 
         // Verify initial values
         assert_eq!(runner.info(id).unwrap().restarts, 5);
@@ -1830,6 +3267,7 @@ mod tests {
         assert_eq!(runner.info(id).unwrap().restarts, 0);
         assert_eq!(runner.info(id).unwrap().crash.value, 0);
         assert_eq!(runner.info(id).unwrap().crash.crashed, false);
+        assert_eq!(runner.info(id).unwrap().memory_restarts, 0);
     }
 
     #[test]
@@ -1896,6 +3334,9 @@ mod tests {
             log_path: "/tmp".to_string(),
             args: vec!["-c".to_string()],
             env: vec![],
+            limits: Default::default(),
+            supervised: false,
+            logs_on_enospc: LogsOnEnospc::Fail,
         };
 
         let result = process_run(metadata);
@@ -1921,6 +3362,9 @@ mod tests {
             log_path: "/nonexistent/directory/that/does/not/exist".to_string(),
             args: vec!["-c".to_string()],
             env: vec![],
+            limits: Default::default(),
+            supervised: false,
+            logs_on_enospc: LogsOnEnospc::Fail,
         };
 
         let result = process_run(metadata);
@@ -1934,6 +3378,179 @@ mod tests {
         );
     }
 
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_child_only_inherits_its_logs() {
+        use std::{fs, os::fd::AsRawFd};
+
+        // Stand-ins for the API listener and a dump being saved, both inheritable
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let dump = fs::File::open("/dev/null").unwrap();
+        for fd in [listener.as_raw_fd(), dump.as_raw_fd()] {
+            unsafe { libc::fcntl(fd, libc::F_SETFD, 0) };
+        }
+
+        let log_path = std::env::temp_dir().join(format!("opm-fd-test-{}", std::process::id()));
+        std::fs::create_dir_all(&log_path).unwrap();
+
+        let result = process_run(ProcessMetadata {
+            name: "fd_test".to_string(),
+            shell: "/bin/sh".to_string(),
+            command: "sleep 5".to_string(),
+            log_path: log_path.display().to_string(),
+            args: vec!["-c".to_string()],
+            env: vec![],
+            limits: Default::default(),
+            supervised: false,
+            logs_on_enospc: LogsOnEnospc::Fail,
+        })
+        .unwrap();
+
+        let mut fds: Vec<(String, String)> = fs::read_dir(format!("/proc/{}/fd", result.pid))
+            .unwrap()
+            .flatten()
+            .map(|entry| {
+                let target = fs::read_link(entry.path()).unwrap_or_default();
+                (entry.file_name().to_string_lossy().into_owned(), target.display().to_string())
+            })
+            .collect();
+        fds.sort();
+
+        let _ = process_stop(result.pid);
+        let _ = fs::remove_dir_all(&log_path);
+
+        assert_eq!(fds.iter().map(|(fd, _)| fd.as_str()).collect::<Vec<_>>(), vec!["0", "1", "2"], "{fds:?}");
+        assert!(fds[1].1.ends_with("fd_test-out.log"));
+        assert!(fds[2].1.ends_with("fd_test-error.log"));
+    }
+
+    #[test]
+    fn test_spawn_limits() {
+        let log_path = std::env::temp_dir().join(format!("opm-limits-test-{}", std::process::id()));
+        std::fs::create_dir_all(&log_path).unwrap();
+
+        let metadata = |limits| ProcessMetadata {
+            name: "limits_test".to_string(),
+            shell: "/bin/sh".to_string(),
+            command: "umask; ulimit -n".to_string(),
+            log_path: log_path.display().to_string(),
+            args: vec!["-c".to_string()],
+            env: vec![],
+            limits,
+            supervised: false,
+            logs_on_enospc: LogsOnEnospc::Fail,
+        };
+
+        let result = process_run(metadata(limits::Limits { umask: Some(0o027), nofile: Some(256), core: None })).unwrap();
+        assert_eq!(result.exit_code, Some(0));
+
+        let output = std::fs::read_to_string(log_path.join("limits_test-out.log")).unwrap();
+        assert_eq!(output.lines().collect::<Vec<_>>(), vec!["0027", "256"]);
+
+        // Above the hard limit nothing is spawned at all
+        let err = process_run(metadata(limits::Limits { core: Some(limits::UNLIMITED), ..Default::default() }));
+        let hard_core = unsafe {
+            let mut limit = libc::rlimit { rlim_cur: 0, rlim_max: 0 };
+            libc::getrlimit(libc::RLIMIT_CORE, &mut limit);
+            limit.rlim_max
+        };
+        if hard_core != libc::RLIM_INFINITY {
+            assert!(err.unwrap_err().contains("ulimit_core"));
+        }
+
+        let _ = std::fs::remove_dir_all(&log_path);
+    }
+
+    fn run_sh(command: &str) -> ProcessRunResult {
+        process_run(ProcessMetadata {
+            name: "quick_exit_test".to_string(),
+            shell: "/bin/sh".to_string(),
+            command: command.to_string(),
+            log_path: std::env::temp_dir().display().to_string(),
+            args: vec!["-c".to_string()],
+            env: vec![],
+            limits: Default::default(),
+            supervised: false,
+            logs_on_enospc: LogsOnEnospc::Fail,
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_logs_on_a_full_filesystem() {
+        let log_path = std::env::temp_dir().join(format!("opm-full-logs-{}", std::process::id()));
+        std::fs::create_dir_all(&log_path).unwrap();
+
+        let mut metadata = ProcessMetadata {
+            name: "full disk".to_string(),
+            shell: "/bin/sh".to_string(),
+            command: "true".to_string(),
+            log_path: log_path.display().to_string(),
+            args: vec!["-c".to_string()],
+            env: vec![],
+            limits: Default::default(),
+            supervised: false,
+            logs_on_enospc: LogsOnEnospc::Fail,
+        };
+
+        let err = open_logs(&metadata, true).unwrap_err();
+        assert!(logspace::is_full(&err), "{err}");
+        assert!(err.contains("full_disk-out.log"));
+
+        metadata.logs_on_enospc = LogsOnEnospc::Devnull;
+        let (stdout, stderr, ..) = open_logs(&metadata, true).unwrap();
+        assert_eq!((stdout.as_str(), stderr.as_str()), ("/dev/null", "/dev/null"));
+        assert!(!log_path.join("full_disk-out.log").exists());
+
+        let (stdout, ..) = open_logs(&metadata, false).unwrap();
+        assert_eq!(stdout, format!("{}/full_disk-out.log", log_path.display()));
+
+        let _ = std::fs::remove_dir_all(&log_path);
+    }
+
+    #[test]
+    fn test_command_completing_right_away() {
+        for (command, code, status) in [("true", 0, "stopped"), ("exit 3", 3, "crashed")] {
+            let result = run_sh(command);
+
+            assert_eq!(result.exit_code, Some(code), "{command}");
+            assert_eq!(result.shell_pid, None, "{command}");
+            // Reaped by process_run, not even a zombie is left
+            assert!(!is_pid_alive(result.pid) && !unix::is_process_zombie(result.pid as i32), "{command}");
+
+            let mut process = Process {
+                pid: result.pid,
+                shell_pid: result.shell_pid,
+                name: "quick_exit_test".to_string(),
+                path: PathBuf::from("/tmp"),
+                script: command.to_string(),
+                running: true,
+                started: Utc::now(),
+                ..Default::default()
+            };
+            process.completed(code);
+
+            assert!(!process.running, "{command}");
+            assert_eq!(process.exit_code, Some(code));
+            assert_eq!(process.status(false, Utc::now()), status, "{command}");
+        }
+    }
+
+    #[test]
+    fn test_long_running_command_is_tracked() {
+        let result = run_sh("sleep 10");
+
+        assert_eq!(result.exit_code, None);
+        assert!(is_pid_alive(result.pid));
+        // The shell either exec'd sleep or is kept apart from its pid for cpu monitoring
+        assert!(result.shell_pid.is_none_or(|shell_pid| shell_pid != result.pid));
+
+        let _ = process_stop(result.pid);
+        if let Some(shell_pid) = result.shell_pid {
+            let _ = process_stop(shell_pid);
+        }
+    }
+
     #[test]
     fn test_error_handling_graceful_failure() {
         // Test that runner doesn't panic when restart fails
@@ -1944,78 +3561,308 @@ mod tests {
         let process = Process {
             id,
             pid: UNLIKELY_PID,
-            shell_pid: None,
-            env: BTreeMap::new(),
             name: "test_process".to_string(),
             path: PathBuf::from("/tmp"),
             script: "echo 'hello'".to_string(),
-            restarts: 0,
             running: false, // Start with not running
-            crash: Crash {
-                crashed: false,
-                value: 0,
-            },
-            watch: Watch {
-                enabled: false,
-                path: String::new(),
-                hash: String::new(),
-            },
-            children: vec![],
             started: Utc::now(),
-            max_memory: 0,
+            ..Default::default()
+        };
+
+        runner.list.insert(id, process);
+
+        // Verify the process exists
+        assert!(runner.exists(id), "Process should exist in runner");
+        
+        // Verify process state
+        let process = runner.info(id).unwrap();
+        assert_eq!(process.running, false, "Process should start as not running");
+        assert_eq!(process.crash.crashed, false, "Process should start as not crashed");
+    }
+
+    #[test]
+    fn test_status_detection_with_dead_pid() {
+        // Test that processes marked as running but with dead PIDs show as crashed
+        let mut runner = setup_test_runner();
+        let id = runner.id.next();
+        
+        let process = Process {
+            id,
+            pid: UNLIKELY_PID,
+            name: "test_process".to_string(),
+            path: PathBuf::from("/tmp"),
+            script: "echo 'hello'".to_string(),
+            running: true, // Marked as running
+            started: Utc::now(),
+            ..Default::default()
+        };
+
+        runner.list.insert(id, process);
+
+        // Fetch the process list and check status
+        let processes = runner.fetch();
+        assert_eq!(processes.len(), 1, "Should have one process");
+        
+        // The process is marked as running but the PID doesn't exist
+        // So status should be "crashed", not "online"
+        assert_eq!(processes[0].status, "crashed", 
+            "Process with dead PID should show as crashed, not online");
+    }
+
+    #[test]
+    fn test_monitor_interval() {
+        assert_eq!(parse_monitor_interval("250"), Ok(250));
+        assert_eq!(parse_monitor_interval(" 100 "), Ok(100));
+        assert_eq!(parse_monitor_interval("99"), Err(String::from("Monitor interval 99ms is below the minimum of 100ms")));
+        assert!(parse_monitor_interval("1s").unwrap_err().contains("expected milliseconds"));
+
+        let mut runner = setup_test_runner();
+        let id = runner.id.next();
+        runner.list.insert(id, new_process(id, "api", "node api.js", PathBuf::from("/tmp"), &StartOptions::default()));
+        assert_eq!(runner.info(id).unwrap().monitor_interval(1000), 1000);
+
+        runner.set_monitor_interval(id, Some(250));
+        assert_eq!(runner.info(id).unwrap().monitor_interval(1000), 250);
+    }
+
+    #[test]
+    fn test_restart_limit() {
+        let mut runner = setup_test_runner();
+        let id = runner.id.next();
+        runner.list.insert(id, new_process(id, "db", "postgres", PathBuf::from("/tmp"), &StartOptions::default()));
+        setup_log_paths();
+        assert_eq!(runner.info(id).unwrap().restart_limit(10), 10);
+        assert_eq!(runner.info(id).unwrap().restart_limit_source(), "daemon.restarts");
+
+        runner.set_max_restarts(id, Some(0));
+        assert_eq!(runner.info(id).unwrap().restart_limit(10), 0);
+        assert_eq!(runner.info(id).unwrap().restart_limit_source(), "max_restarts");
+        assert_eq!(runner.clone().get(id).fetch().info.max_restarts, Some(0));
+
+        runner.set_max_restarts(id, None);
+        assert_eq!(runner.info(id).unwrap().restart_limit(10), 10);
+        assert_eq!(runner.clone().get(id).fetch().info.max_restarts, None);
+    }
+
+    #[test]
+    fn test_children_due() {
+        let mut process = new_process(1, "ci", "./runner.sh", PathBuf::from("/tmp"), &StartOptions::default());
+
+        // The first children are saved right away, stop needs them
+        assert!(process.children_due(&[101, 102], 1));
+        assert!(!process.children_due(&[], 1));
+
+        process.children = vec![101, 102];
+        assert!(!process.children_due(&[101, 102], CHILDREN_SAVE_CYCLES));
+        assert!(!process.children_due(&[101, 103], 1), "Later changes wait");
+        assert!(process.children_due(&[101, 103], CHILDREN_SAVE_CYCLES));
+        assert!(!process.children_due(&[], CHILDREN_SAVE_CYCLES), "An empty list is not saved, as before");
+
+        process.track_children = false;
+        assert!(!process.children_due(&[101, 103], CHILDREN_SAVE_CYCLES));
+    }
+
+    #[test]
+    fn test_untracked_children_are_found_at_stop() {
+        let mut child = std::process::Command::new("sh").args(["-c", "sleep 30 & sleep 30 & wait"]).spawn().unwrap();
+        let pid = child.id() as i64;
+        thread::sleep(Duration::from_millis(200));
+
+        let mut runner = setup_test_runner();
+        let id = runner.id.next();
+        runner.list.insert(id, Process { pid, running: true, ..new_process(id, "ci", "./runner.sh", PathBuf::from("/tmp"), &StartOptions::default()) });
+        runner.set_children(id, vec![pid + 100_000]);
+
+        runner.set_track_children(id, false);
+        let process = runner.info(id).unwrap();
+        assert!(process.children.is_empty(), "Stale children are dropped");
+
+        // Looked up when stopped, the stored list is never consulted
+        let tree = process.tree();
+        assert_eq!(tree.len(), 2);
+        assert_eq!(tree, process_find_children(pid));
+
+        // Only the parent is measured
+        assert_eq!(process.handles(pid), get_process_handles(pid));
+        assert!(get_process_handles_with_children(pid).threads > process.handles(pid).threads);
+
+        kill_children(tree, Signal::SIGTERM);
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+
+    #[test]
+    fn test_details_sample_cpu_once() {
+        let mut child = std::process::Command::new("sh").args(["-c", "sleep 30 & sleep 30 & sleep 30 & sleep 30 & wait"]).spawn().unwrap();
+        let pid = child.id() as i64;
+        thread::sleep(Duration::from_millis(200));
+        assert_eq!(process_find_children(pid).len(), 4);
+
+        let mut runner = setup_test_runner();
+        let id = runner.id.next();
+        runner.list.insert(id, Process { pid, running: true, track_children: true, ..new_process(id, "ci", "./runner.sh", PathBuf::from("/tmp"), &StartOptions::default()) });
+        setup_log_paths();
+
+        // However many children there are, the details wait a single sample window
+        unix::cpu::WAITS.with(|waits| waits.set(0));
+        let details = runner.get(id).fetch();
+        assert_eq!(unix::cpu::WAITS.with(|waits| waits.get()), 1);
+        assert_eq!(details.stats.cpu_sample, Some(CpuSample::Timed));
+
+        unix::cpu::WAITS.with(|waits| waits.set(0));
+        let (own, with_children) = get_process_cpu_usage_timed(pid);
+        assert_eq!(unix::cpu::WAITS.with(|waits| waits.get()), 1);
+        assert!(with_children >= own);
+
+        kill_children(process_find_children(pid), Signal::SIGTERM);
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+
+    /// A parent forking short-lived children the whole time, as a build server or cron-like process would
+    #[test]
+    fn test_forking_parent_bounds_children_writes() {
+        let mut child = std::process::Command::new("sh").args(["-c", "while :; do sleep 0.01 & sleep 0.005; done"]).spawn().unwrap();
+        let pid = child.id() as i64;
+        thread::sleep(Duration::from_millis(100));
+
+        let cycles = 50;
+        let mut process = new_process(1, "ci", "./runner.sh", PathBuf::from("/tmp"), &StartOptions::default());
+        let (mut writes, mut since, mut slowest) = (0, 0, Duration::ZERO);
+
+        // What the daemon does for the process each cycle
+        for _ in 0..cycles {
+            let started = std::time::Instant::now();
+            let live = process_classify_children(pid).live;
+            since += 1;
+
+            if process.children_due(&live, since) {
+                process.children = live;
+                writes += 1;
+                since = 0;
+            }
+
+            slowest = slowest.max(started.elapsed());
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        let _ = child.kill();
+        let _ = child.wait();
+
+        assert!(writes <= cycles / CHILDREN_SAVE_CYCLES + 1, "{writes} dump writes in {cycles} cycles");
+        assert!(slowest < Duration::from_millis(500), "a cycle took {slowest:?}");
+
+        process.track_children = false;
+        assert!(!process.children_due(&[pid + 1], CHILDREN_SAVE_CYCLES));
+    }
+
+    #[test]
+    fn test_starting_status_during_grace_period() {
+        // The test process itself provides a live PID
+        let mut runner = setup_test_runner();
+        let id = runner.id.next();
+        let started = Utc::now();
+
+        let process = Process {
+            id,
+            pid: std::process::id() as i64,
+            name: "test_process".to_string(),
+            path: PathBuf::from("/tmp"),
+            script: "echo 'hello'".to_string(),
+            running: true,
+            started,
+            startup_grace: Some(30),
+            ..Default::default()
         };
 
-        runner.list.insert(id, process);
+        runner.list.insert(id, process);
+        assert_eq!(runner.fetch()[0].status, "starting", "Just started process should show as starting");
+
+        let process = runner.process(id);
+        assert_eq!(process.status(true, started + chrono::Duration::seconds(29)), "starting");
+        assert_eq!(process.status(true, started + chrono::Duration::seconds(30)), "online");
+        assert_eq!(process.status(false, started), "crashed", "Dead PID is a crash even while starting");
+
+        // Without a per-process value the default grace period applies
+        process.startup_grace = None;
+        assert_eq!(process.status(true, started), "starting");
+        assert_eq!(process.status(true, started + chrono::Duration::seconds(STARTUP_GRACE_PERIOD_SECS)), "online");
+
+        process.started = Utc::now() - chrono::Duration::seconds(60);
+        assert_eq!(runner.fetch()[0].status, "online", "Process past its grace period should show as online");
+    }
+
+    #[test]
+    fn test_health_status() {
+        let mut process = new_process(0, "api", "node api.js", PathBuf::from("/tmp"), &StartOptions::default());
+        let started = Utc::now() - chrono::Duration::seconds(60);
+        (process.running, process.started, process.started_mark) = (true, started, None);
+        process.health = Some(health::Check::new(health::Probe::Url(string!("http://127.0.0.1:8080/health"))));
+
+        assert_eq!(process.status(true, Utc::now()), "starting", "Online waits for a check to pass");
+
+        process.health_status = Some(health::Status::after(None, Err(string!("HTTP 503")), Utc::now()));
+        assert_eq!(process.status(true, Utc::now()), "unhealthy");
+
+        process.health_status = Some(health::Status::after(None, Ok(string!("HTTP 200")), Utc::now()));
+        assert_eq!(process.status(true, Utc::now()), "online");
 
-        // Verify the process exists
-        assert!(runner.exists(id), "Process should exist in runner");
-        
-        // Verify process state
-        let process = runner.info(id).unwrap();
-        assert_eq!(process.running, false, "Process should start as not running");
-        assert_eq!(process.crash.crashed, false, "Process should start as not crashed");
+        // A result from before the last start is of a previous run
+        process.started = Utc::now() + chrono::Duration::seconds(1);
+        assert_eq!(process.health_status(), None);
     }
 
     #[test]
-    fn test_status_detection_with_dead_pid() {
-        // Test that processes marked as running but with dead PIDs show as crashed
+    fn test_errored_status() {
+        let mut process = new_process(0, "errored", "sleep 60", PathBuf::from("/tmp"), &StartOptions::default());
+
+        // Waiting for space after its restart failed, the daemon keeps it running and crashed
+        process.running = true;
+        process.crash.crashed = true;
+        process.errored = Some(string!(logspace::FULL));
+        assert_eq!(process.status(false, Utc::now()), "errored");
+        assert_eq!(process.status(true, Utc::now()), "starting", "A live process is not errored");
+
+        process.errored = None;
+        assert_eq!(process.status(false, Utc::now()), "crashed");
+    }
+
+    #[test]
+    fn test_grace_period_survives_wall_clock_jumps() {
         let mut runner = setup_test_runner();
         let id = runner.id.next();
-        
-        let process = Process {
+        let now = clock::now().unwrap();
+
+        let mut process = Process {
             id,
-            pid: UNLIKELY_PID,
-            shell_pid: None,
-            env: BTreeMap::new(),
+            pid: std::process::id() as i64,
             name: "test_process".to_string(),
             path: PathBuf::from("/tmp"),
             script: "echo 'hello'".to_string(),
-            restarts: 0,
-            running: true, // Marked as running
-            crash: Crash {
-                crashed: false,
-                value: 0,
-            },
-            watch: Watch {
-                enabled: false,
-                path: String::new(),
-                hash: String::new(),
-            },
-            children: vec![],
+            running: true,
             started: Utc::now(),
-            max_memory: 0,
+            started_mark: Some(clock::Mark { ms: now.ms.saturating_sub(40_000), ..now.clone() }),
+            startup_grace: Some(30),
+            ..Default::default()
         };
 
-        runner.list.insert(id, process);
+        // Started 40s ago, then the wall clock went back a minute
+        let behind = Utc::now() - chrono::Duration::seconds(60);
+        assert_eq!(process.status(true, behind), "online", "A clock going back must not hold the grace period open");
+        assert!(process.uptime(behind).duration >= chrono::Duration::seconds(40));
 
-        // Fetch the process list and check status
-        let processes = runner.fetch();
-        assert_eq!(processes.len(), 1, "Should have one process");
-        
-        // The process is marked as running but the PID doesn't exist
-        // So status should be "crashed", not "online"
-        assert_eq!(processes[0].status, "crashed", 
-            "Process with dead PID should show as crashed, not online");
+        process.started_mark = Some(clock::Mark { ms: now.ms.saturating_sub(5_000), ..now.clone() });
+        assert_eq!(process.status(true, behind), "starting");
+
+        // Without a mark the start is in the future, shown as skew and not as starting
+        process.started_mark = None;
+        let uptime = process.uptime(behind);
+        assert!(uptime.skewed);
+        assert_eq!(uptime.to_string(), "0s (clock skew detected)");
+        assert_eq!(process.status(true, behind), "online");
+
+        runner.list.insert(id, process);
+        assert_eq!(runner.fetch()[0].uptime, "0s");
     }
 
     #[test]
@@ -2030,25 +3877,12 @@ mod tests {
         let process = Process {
             id,
             pid: UNLIKELY_PID,
-            shell_pid: None,
-            env: BTreeMap::new(),
             name: "test_crashed_process".to_string(),
             path: PathBuf::from("/tmp"),
             script: "echo 'hello'".to_string(),
-            restarts: 0,
             running: true, // Marked as running but PID doesn't exist
-            crash: Crash {
-                crashed: false,
-                value: 0,
-            },
-            watch: Watch {
-                enabled: false,
-                path: String::new(),
-                hash: String::new(),
-            },
-            children: vec![],
             started: past_time, // Started 5 minutes ago
-            max_memory: 0,
+            ..Default::default()
         };
 
         runner.list.insert(id, process);
@@ -2078,25 +3912,12 @@ mod tests {
         let process = Process {
             id,
             pid: UNLIKELY_PID,
-            shell_pid: None,
-            env: BTreeMap::new(),
             name: "test_stopped_process".to_string(),
             path: PathBuf::from("/tmp"),
             script: "echo 'hello'".to_string(),
-            restarts: 0,
             running: false, // Explicitly stopped
-            crash: Crash {
-                crashed: false,
-                value: 0,
-            },
-            watch: Watch {
-                enabled: false,
-                path: String::new(),
-                hash: String::new(),
-            },
-            children: vec![],
             started: past_time, // Started 10 minutes ago
-            max_memory: 0,
+            ..Default::default()
         };
 
         runner.list.insert(id, process);
@@ -2125,25 +3946,12 @@ mod tests {
         let process = Process {
             id,
             pid: 12345,
-            shell_pid: None,
-            env: BTreeMap::new(),
             name: "test_process".to_string(),
             path: PathBuf::from("/tmp"),
             script: "echo 'hello'".to_string(),
-            restarts: 0,
             running: true,
-            crash: Crash {
-                crashed: false,
-                value: 0,
-            },
-            watch: Watch {
-                enabled: false,
-                path: String::new(),
-                hash: String::new(),
-            },
-            children: vec![],
             started: Utc::now(),
-            max_memory: 0,
+            ..Default::default()
         };
 
         runner.list.insert(id, process);
@@ -2173,8 +3981,6 @@ mod tests {
         let mut process = Process {
             id,
             pid: UNLIKELY_PID,
-            shell_pid: None,
-            env: BTreeMap::new(),
             name: "test_process_9_crashes".to_string(),
             path: PathBuf::from("/tmp"),
             script: "echo 'test'".to_string(),
@@ -2183,15 +3989,11 @@ mod tests {
             crash: Crash {
                 crashed: false,
                 value: 9,
+                cores: vec![],
+                next_restart: None,
             },
-            watch: Watch {
-                enabled: false,
-                path: String::new(),
-                hash: String::new(),
-            },
-            children: vec![],
             started: Utc::now(),
-            max_memory: 0,
+            ..Default::default()
         };
         
         runner.list.insert(id, process.clone());
@@ -2218,185 +4020,202 @@ mod tests {
             "crash.value=11 should be > max_restarts=10, preventing restart");
     }
 
+    /// Runner on an `InMemoryBackend` with `api` started, nothing is spawned and the dump is left alone
+    fn memory_runner() -> (Runner, Arc<memory::InMemoryBackend>, usize) {
+        let backend = Arc::new(memory::InMemoryBackend::new());
+        let mut runner = Runner::with_backend(backend.clone());
+
+        // The current directory, a restart changes into the path of the process
+        runner.start(&string!("api"), &string!("node index.js"), file::cwd(), &StartOptions::default());
+        let id = *runner.list.keys().next().unwrap();
+        (runner, backend, id)
+    }
+
     #[test]
     fn test_restart_counter_not_incremented_on_start_command() {
-        // Test that 'opm start' command (dead=false, increment_counter=false) does NOT increment
-        // This ensures the counter doesn't increase when user starts an existing process
-        let mut runner = setup_test_runner();
-        let id = runner.id.next();
-        
-        let process = Process {
-            id,
-            pid: 12345,
-            shell_pid: None,
-            env: BTreeMap::new(),
-            name: "test_process".to_string(),
-            path: PathBuf::from("/tmp"),
-            script: "echo 'test'".to_string(),
-            restarts: 5, // Start with 5 restarts
-            running: true,
-            crash: Crash {
-                crashed: false,
-                value: 0,
-            },
-            watch: Watch {
-                enabled: false,
-                path: String::new(),
-                hash: String::new(),
-            },
-            children: vec![],
-            started: Utc::now(),
-            max_memory: 0,
-        };
-        
-        runner.list.insert(id, process);
-        
-        // Verify initial state
-        assert_eq!(runner.info(id).unwrap().restarts, 5, "Should start with 5 restarts");
-        
-        // Start command (dead=false, increment_counter=false) should NOT increment
-        // So the counter should remain at 5
-        assert_eq!(runner.info(id).unwrap().restarts, 5, 
-            "Start command should NOT increment counter");
+        let (mut runner, backend, id) = memory_runner();
+        let first = runner.info(id).unwrap().pid;
+
+        assert_eq!(first, memory::FIRST_PID);
+        assert_eq!(runner.info(id).unwrap().restarts, 0, "Start command should NOT increment counter");
+
+        // `opm start` on an existing process restarts it without counting
+        runner.restart(id, false, false);
+        let process = runner.info(id).unwrap();
+
+        assert_eq!(process.restarts, 0);
+        assert_eq!(process.pid, first + 1);
+        assert_eq!(backend.stopped(), vec![first]);
+        assert!(backend.alive(process.pid) && !backend.alive(first));
+    }
+
+    #[test]
+    fn test_shell_override_applies_at_restart_and_reload() {
+        let (mut runner, backend, id) = memory_runner();
+        let config = config::read().runner;
+        let fish = shell::Shell { program: Some(string!("/usr/bin/fish")), args: Some(vec![string!("-l"), string!("-c")]) };
+
+        runner.set_shell(id, &fish).restart(id, false, true);
+        runner.reload(id, false, true);
+        runner.set_shell(id, &shell::Shell::default()).restart(id, false, true);
+
+        let spawned: Vec<(String, Vec<String>)> = backend.spawned().into_iter().map(|spawned| (spawned.shell, spawned.args)).collect();
+        let configured = (config.shell.clone(), config.args.clone());
+        let custom = (string!("/usr/bin/fish"), vec![string!("-l"), string!("-c")]);
+        assert_eq!(spawned, vec![configured.clone(), custom.clone(), custom, configured]);
+
+        // The override is what details and get-command show
+        runner.set_shell(id, &fish);
+        assert_eq!(runner.info(id).unwrap().command_line(&config.shell, &config.args), "/usr/bin/fish -l -c 'node index.js'");
+    }
+
+    #[test]
+    fn test_start_env_is_kept_over_the_restarting_env() {
+        let backend = Arc::new(memory::InMemoryBackend::new());
+        let mut runner = Runner::with_backend(backend);
+        let env = Env::from([(string!("PATH"), string!("/opt/api/bin")), (string!("OPM_TEST_PORT"), string!("8080"))]);
+
+        runner.start(&string!("api"), &string!("node index.js"), file::cwd(), &StartOptions { env, ..Default::default() });
+        let id = *runner.list.keys().next().unwrap();
+        assert_eq!(runner.info(id).unwrap().pinned_env, vec![string!("OPM_TEST_PORT"), string!("PATH")]);
+
+        // The PATH of this test does not replace the one given at start
+        runner.restart(id, false, true);
+        runner.reload(id, true, true);
+        let process = runner.info(id).unwrap();
+        assert_eq!((process.env["PATH"].as_str(), process.env["OPM_TEST_PORT"].as_str()), ("/opt/api/bin", "8080"));
+
+        // Removed, the variable is no longer pinned and follows the restarting env again
+        let changes = environment::Changes { add: vec![], remove: vec![string!("PATH")] };
+        runner.adjust_env(id, &changes).unwrap();
+        runner.restart(id, false, true);
+        let process = runner.info(id).unwrap();
+        assert_eq!(process.pinned_env, vec![string!("OPM_TEST_PORT")]);
+        assert_eq!(process.env.get("PATH"), env::var("PATH").ok().as_ref());
     }
 
     #[test]
     fn test_restart_counter_increments_on_restart_command() {
-        // Test that 'opm restart' command (dead=false, increment_counter=true) DOES increment
-        // This ensures the counter tracks manual restart commands
-        let mut runner = setup_test_runner();
-        let id = runner.id.next();
-        
-        let process = Process {
-            id,
-            pid: 12345,
-            shell_pid: None,
-            env: BTreeMap::new(),
-            name: "test_process".to_string(),
-            path: PathBuf::from("/tmp"),
-            script: "echo 'test'".to_string(),
-            restarts: 5, // Start with 5 restarts
-            running: true,
-            crash: Crash {
-                crashed: false,
-                value: 0,
-            },
-            watch: Watch {
-                enabled: false,
-                path: String::new(),
-                hash: String::new(),
-            },
-            children: vec![],
-            started: Utc::now(),
-            max_memory: 0,
-        };
-        
-        runner.list.insert(id, process);
-        
-        // Verify initial state
-        assert_eq!(runner.info(id).unwrap().restarts, 5, "Should start with 5 restarts");
-        
-        // Simulate restart command (increment_counter=true)
-        let proc = runner.process(id);
-        proc.restarts += 1;
-        
-        // Verify the counter incremented
-        assert_eq!(runner.info(id).unwrap().restarts, 6, 
-            "Restart command should increment counter from 5 to 6");
+        let (mut runner, backend, id) = memory_runner();
+
+        runner.restart(id, false, true);
+        runner.restart(id, false, true);
+
+        let process = runner.info(id).unwrap();
+        assert_eq!(process.restarts, 2, "Restart command should increment counter");
+        assert_eq!(process.crash.value, 0);
+        assert_eq!(backend.spawned().len(), 3);
+        assert_eq!(backend.stopped(), vec![memory::FIRST_PID, memory::FIRST_PID + 1]);
     }
 
     #[test]
     fn test_restart_counter_increments_on_crash_restart() {
-        // Test that crash restarts (dead=true) DO increment the restart counter
-        // This ensures automatic daemon restarts are tracked
-        let mut runner = setup_test_runner();
-        let id = runner.id.next();
-        
-        let process = Process {
-            id,
-            pid: UNLIKELY_PID,
-            shell_pid: None,
-            env: BTreeMap::new(),
-            name: "test_crashed_process".to_string(),
-            path: PathBuf::from("/tmp"),
-            script: "echo 'test'".to_string(),
-            restarts: 2, // Start with 2 restarts already
-            running: false,
-            crash: Crash {
-                crashed: true,
-                value: 1, // One crash
-            },
-            watch: Watch {
-                enabled: false,
-                path: String::new(),
-                hash: String::new(),
-            },
-            children: vec![],
-            started: Utc::now(),
-            max_memory: 0,
-        };
-        
-        runner.list.insert(id, process);
-        
-        // Verify initial state
-        assert_eq!(runner.info(id).unwrap().restarts, 2, "Should start with 2 restarts");
-        assert_eq!(runner.info(id).unwrap().crash.value, 1, "Should have 1 crash");
-        
-        // Simulate what the daemon does when it detects a crash and restarts (dead=true)
-        let proc = runner.process(id);
-        proc.restarts += 1; // This is conditional on dead=true in the actual code
-        
-        // Verify the counter incremented
-        assert_eq!(runner.info(id).unwrap().restarts, 3, 
-            "Crash restart should increment counter from 2 to 3");
-        
-        // The crash.value would be managed separately by the daemon
-        // and is reset to 0 on successful restart (not tested here)
+        let (mut runner, backend, id) = memory_runner();
+        backend.script("api", [memory::Lifetime::Fails(string!("exec failed")), memory::Lifetime::Runs]);
+
+        // The daemon sees the process gone and restarts it, the first attempt cannot start
+        backend.crash(memory::FIRST_PID);
+        assert_eq!(runner.fetch()[0].status, "crashed");
+
+        runner.restart(id, true, false);
+        let process = runner.info(id).unwrap();
+        assert_eq!(process.restarts, 1, "Crash restart should increment counter");
+        assert_eq!(process.crash.value, 1, "A failed restart counts as a crash");
+        assert!(process.running && process.crash.crashed, "The daemon keeps retrying");
+
+        runner.restart(id, true, false);
+        let process = runner.info(id).unwrap();
+        assert_eq!(process.restarts, 2);
+        assert_eq!(process.pid, memory::FIRST_PID + 1, "The failed attempt took no pid");
+        assert!(!process.crash.crashed && backend.alive(process.pid));
     }
 
     #[test]
     fn test_reload_counter_increments_on_reload_command() {
-        // Test that 'opm reload' command (dead=false, increment_counter=true) DOES increment
-        // Reload is similar to restart but with zero-downtime (starts new before stopping old)
-        let mut runner = setup_test_runner();
-        let id = runner.id.next();
-        
-        let process = Process {
-            id,
-            pid: 12345,
-            shell_pid: None,
-            env: BTreeMap::new(),
-            name: "test_process".to_string(),
-            path: PathBuf::from("/tmp"),
-            script: "echo 'test'".to_string(),
-            restarts: 5, // Start with 5 restarts
-            running: true,
-            crash: Crash {
-                crashed: false,
-                value: 0,
-            },
-            watch: Watch {
-                enabled: false,
-                path: String::new(),
-                hash: String::new(),
-            },
-            children: vec![],
-            started: Utc::now(),
-            max_memory: 0,
-        };
-        
-        runner.list.insert(id, process);
-        
-        // Verify initial state
-        assert_eq!(runner.info(id).unwrap().restarts, 5, "Should start with 5 restarts");
-        
-        // Simulate reload command (increment_counter=true)
-        let proc = runner.process(id);
-        proc.restarts += 1;
-        
-        // Verify the counter incremented
-        assert_eq!(runner.info(id).unwrap().restarts, 6, 
-            "Reload command should increment counter from 5 to 6");
+        let (mut runner, backend, id) = memory_runner();
+
+        runner.reload(id, false, true);
+        let process = runner.info(id).unwrap();
+        assert_eq!(process.restarts, 1, "Reload command should increment counter");
+        assert_eq!(process.pid, memory::FIRST_PID + 1);
+        assert_eq!(backend.stopped(), vec![memory::FIRST_PID], "The old process is stopped once the new one runs");
+
+        runner.stop(id, None);
+        assert!(!runner.info(id).unwrap().running);
+        assert_eq!(backend.stopped(), vec![memory::FIRST_PID, memory::FIRST_PID + 1]);
+        assert_eq!(runner.fetch()[0].status, "stopped");
+
+        // Saved through the backend, the dump on disk is never touched
+        runner.save();
+        assert_eq!(runner.refresh().info(id).unwrap().restarts, 1);
+    }
+
+    #[test]
+    fn test_stop_with_signal() {
+        let (mut runner, backend, id) = memory_runner();
+
+        runner.stop(id, Some(Signal::SIGQUIT));
+        assert!(!runner.info(id).unwrap().running);
+        assert_eq!(backend.signals(), vec![Signal::SIGQUIT]);
+
+        runner.restart(id, false, false);
+        runner.stop(id, None);
+        assert_eq!(backend.signals().last(), Some(&Signal::SIGTERM), "SIGTERM unless another signal is given");
+    }
+
+    #[test]
+    fn test_stop_all_signals_before_waiting() {
+        let (mut runner, backend, first) = memory_runner();
+        backend.script("api", [memory::Lifetime::IgnoresTerm]);
+        runner.restart(first, false, false);
+        runner.start(&string!("api"), &string!("node index.js"), file::cwd(), &StartOptions::default());
+        let second = *runner.list.keys().last().unwrap();
+        let before = backend.signals().len();
+
+        runner.stop_all(&[first, second], None);
+        assert_eq!(backend.signals()[before..], [Signal::SIGTERM, Signal::SIGTERM, Signal::SIGKILL, Signal::SIGKILL]);
+        assert!([first, second].iter().all(|id| !runner.info(*id).unwrap().running));
+    }
+
+    #[test]
+    fn test_restart_backoff() {
+        let delays: Vec<i64> = (1..=5).map(|crashes| restart_backoff(crashes, 500, 10_000).num_milliseconds()).collect();
+        assert_eq!(delays, [1000, 2000, 4000, 8000, 10_000]);
+        assert_eq!(restart_backoff(200, 500, 10_000).num_milliseconds(), 10_000, "No overflow past the cap");
+        assert_eq!(restart_backoff(3, 0, 10_000), chrono::Duration::zero());
+    }
+
+    #[test]
+    fn test_check_capacity() {
+        let (runner, _, _) = memory_runner();
+
+        assert_eq!(runner.check_capacity(1, 2), Ok(()));
+        assert_eq!(runner.check_capacity(1, 1), Err(string!("Cannot add more processes: 1 of at most 1 are managed (runner.max_processes)")));
+        assert!(runner.check_capacity(2, 2).is_err(), "Adding several counts all of them");
+
+        assert!(!near_max_processes(400, DEFAULT_MAX_PROCESSES));
+        assert!(near_max_processes(401, DEFAULT_MAX_PROCESSES));
+    }
+
+    #[test]
+    fn test_stop_escalates_to_sigkill() {
+        let (mut runner, backend, id) = memory_runner();
+        backend.script("api", [memory::Lifetime::IgnoresTerm]);
+
+        runner.restart(id, false, false);
+        let stubborn = runner.info(id).unwrap().pid;
+        let before = backend.signals().len();
+
+        runner.stop(id, None);
+        assert!(!backend.alive(stubborn));
+        assert_eq!(backend.signals()[before..], [Signal::SIGTERM, Signal::SIGKILL]);
+
+        // A restart gets rid of it the same way before starting the next one
+        runner.restart(id, false, false);
+        let next = runner.info(id).unwrap().pid;
+        runner.restart(id, false, false);
+        assert!(!backend.alive(next) && backend.alive(runner.info(id).unwrap().pid));
+        assert_eq!(backend.signals().last(), Some(&Signal::SIGKILL));
     }
 
     #[test]
@@ -2411,25 +4230,12 @@ mod tests {
         let process = Process {
             id,
             pid: UNLIKELY_PID, // Invalid PID - restore will fail
-            shell_pid: None,
-            env: BTreeMap::new(),
             name: "test_restore_process".to_string(),
             path: PathBuf::from("/tmp"),
             script: "echo 'test'".to_string(),
-            restarts: 0,
             running: true, // Was running before restore
-            crash: Crash {
-                crashed: false,
-                value: 0,
-            },
-            watch: Watch {
-                enabled: false,
-                path: String::new(),
-                hash: String::new(),
-            },
-            children: vec![],
             started: Utc::now(),
-            max_memory: 0,
+            ..Default::default()
         };
         
         runner.list.insert(id, process);
@@ -2475,6 +4281,21 @@ mod tests {
             "Negative PID should not be detected as alive");
     }
 
+    #[test]
+    fn test_own_process_is_not_privileged() {
+        let current_pid = std::process::id() as i64;
+
+        // The test binary owns itself, so it can always signal itself
+        assert!(!is_signal_denied(current_pid));
+        assert!(!is_privileged(current_pid));
+        assert!(!is_signal_denied(UNLIKELY_PID) && !is_privileged(UNLIKELY_PID));
+
+        let message = permission_denied(current_pid, "api");
+        assert!(message.starts_with(&format!("process {current_pid} (api) is owned by ")));
+        assert!(message.ends_with("; re-run with sudo or manage it from the daemon"));
+        assert!(permission_denied(UNLIKELY_PID, "api").contains("is owned by another user"));
+    }
+
     #[test]
     fn test_wait_for_process_termination_with_invalid_pids() {
         use std::time::Instant;
@@ -2483,7 +4304,7 @@ mod tests {
         // Previously, this would cause a 5-second delay because libc::kill(0, 0)
         // checks the entire process group instead of a specific process
         let start = Instant::now();
-        let result = wait_for_process_termination(0);
+        let result = wait_for_process_termination(0, Duration::from_secs(5));
         let duration = start.elapsed();
         
         assert!(result, "wait_for_process_termination should return true for PID 0");
@@ -2492,7 +4313,7 @@ mod tests {
         
         // Test with negative PID
         let start = Instant::now();
-        let result = wait_for_process_termination(-1);
+        let result = wait_for_process_termination(-1, Duration::from_secs(5));
         let duration = start.elapsed();
         
         assert!(result, "wait_for_process_termination should return true for negative PID");
@@ -2501,7 +4322,7 @@ mod tests {
         
         // Test with unlikely PID (should also return quickly since process doesn't exist)
         let start = Instant::now();
-        let result = wait_for_process_termination(UNLIKELY_PID);
+        let result = wait_for_process_termination(UNLIKELY_PID, Duration::from_secs(5));
         let duration = start.elapsed();
         
         assert!(result, 
@@ -2524,25 +4345,18 @@ mod tests {
         let process = Process {
             id,
             pid: 0, // Dead process
-            shell_pid: None,
-            env: BTreeMap::new(),
             name: "test_restart_failure".to_string(),
             path: PathBuf::from("/nonexistent/directory/that/does/not/exist"),
             script: "echo 'test'".to_string(),
-            restarts: 0,
             running: true,
             crash: Crash {
                 crashed: true, // Already marked as crashed, so restart will be attempted
                 value: 1, // First crash detected
+                cores: vec![],
+                next_restart: None,
             },
-            watch: Watch {
-                enabled: false,
-                path: String::new(),
-                hash: String::new(),
-            },
-            children: vec![],
             started: Utc::now(),
-            max_memory: 0,
+            ..Default::default()
         };
         
         runner.list.insert(id, process);
@@ -2580,4 +4394,112 @@ mod tests {
         assert_eq!(process.crash.crashed, true, 
             "Process should still be marked as crashed");
     }
+
+    #[test]
+    fn test_trigger_reload_records_reason() {
+        let mut runner = setup_test_runner();
+        let id = runner.id.next();
+
+        let process = Process {
+            id,
+            name: "test_trigger".to_string(),
+            path: PathBuf::from("/nonexistent/directory/that/does/not/exist"),
+            script: "echo 'test'".to_string(),
+            running: true,
+            started: Utc::now(),
+            ..Default::default()
+        };
+
+        runner.list.insert(id, process);
+
+        // The working directory is missing, so the restart itself fails
+        assert!(runner.trigger_reload(id, "deploy").is_err());
+
+        let process = runner.info(id).unwrap();
+        assert_eq!(process.last_restart_reason.as_deref(), Some("deploy"));
+        assert_eq!(process.restarts, 1);
+        assert!(!process.running);
+    }
+
+    #[test]
+    #[ignore = "it requires actual process execution"]
+    fn test_trigger_reload_returns_new_pid_and_refreshes_watch_hash() {
+        let mut runner = setup_test_runner();
+        let id = runner.id.next();
+        let dir = std::env::temp_dir().join(format!("opm-trigger-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("app.js"), "v1").unwrap();
+
+        let process = Process {
+            id,
+            name: "test_trigger_success".to_string(),
+            path: dir.clone(),
+            script: "sleep 30".to_string(),
+            watch: Watch {
+                enabled: true,
+                path: "app.js".to_string(),
+                hash: hash::create(dir.join("app.js")),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        runner.list.insert(id, process);
+
+        // The deploy changed the watched file, so the daemon would reload on its own without the refresh
+        std::fs::write(dir.join("app.js"), "v2").unwrap();
+        let pid = runner.trigger_reload(id, "deploy").unwrap();
+
+        let process = runner.info(id).unwrap().clone();
+        let hash = hash::create(dir.join("app.js"));
+        runner.stop(id, None);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(pid > 0);
+        assert_eq!(process.pid, pid);
+        assert!(process.running);
+        assert_eq!(process.last_restart_reason.as_deref(), Some("deploy"));
+        assert_eq!(process.watch.hash, hash);
+    }
+
+    #[test]
+    fn test_watch_cooldown() {
+        let mut watch = Watch {
+            enabled: true,
+            path: string!("src"),
+            hash: string!("a"),
+            cooldown_secs: Some(10),
+            last_reload: None,
+            pending: None,
+        };
+        let start = Utc::now();
+        let at = |secs: i64| start + chrono::Duration::seconds(secs);
+
+        assert_eq!(watch.check("a", at(0)), WatchChange::Unchanged);
+        assert_eq!(watch.check("b", at(0)), WatchChange::Reload);
+        watch.hash = string!("b");
+        watch.reloaded(at(0));
+
+        // Changes inside the cooldown wait for it to end
+        assert_eq!(watch.check("c", at(3)), WatchChange::Held);
+        assert_eq!(watch.check("d", at(6)), WatchChange::Held);
+        assert_eq!(watch.pending.as_deref(), Some("d"));
+
+        // Past it, still changing, then settled
+        assert_eq!(watch.check("e", at(11)), WatchChange::Held);
+        assert_eq!(watch.check("e", at(12)), WatchChange::Reload);
+        watch.hash = string!("e");
+        watch.reloaded(at(12));
+        assert_eq!(watch.pending, None);
+
+        // Changed and back again, nothing to reload
+        assert_eq!(watch.check("f", at(13)), WatchChange::Held);
+        assert_eq!(watch.check("e", at(14)), WatchChange::Held);
+        assert_eq!(watch.check("e", at(30)), WatchChange::Unchanged);
+        assert_eq!(watch.pending, None);
+
+        watch.cooldown_secs = Some(0);
+        watch.reloaded(at(30));
+        assert_eq!(watch.check("g", at(30)), WatchChange::Reload);
+    }
 }
@@ -0,0 +1,224 @@
+//! `/list` a page at a time with only the fields asked for, for deployments with hundreds of processes
+
+use super::ProcessItem;
+
+use macros_rs::ternary;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::cmp::Ordering;
+use utoipa::ToSchema;
+
+/// Fields of a listed process, named as in its JSON
+pub const FIELDS: [&str; 15] = [
+    "id",
+    "pid",
+    "name",
+    "status",
+    "cpu",
+    "mem",
+    "restarts",
+    "uptime",
+    "watch_path",
+    "start_time",
+    "agent_id",
+    "agent_name",
+    "labels",
+    "group",
+    "url",
+];
+
+/// What a page can be sorted by, a leading `-` sorts descending
+pub const SORT: [&str; 7] = ["id", "name", "status", "cpu", "mem", "restarts", "uptime"];
+
+/// Fields that need the process to be sampled, the costly part of listing it
+const SAMPLED: [&str; 2] = ["cpu", "mem"];
+
+/// A listed process in numbers, sorting by the formatted fields would put `9mb` after `10mb`
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Measured {
+    pub cpu: f64,
+    pub memory: u64,
+    /// Seconds running, 0 for a process that is not
+    pub uptime: i64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Query {
+    pub offset: usize,
+    pub limit: Option<usize>,
+    /// Every field when None
+    pub fields: Option<Vec<String>>,
+    pub sort: String,
+    pub descending: bool,
+}
+
+impl Query {
+    /// None without any parameter, `/list` then answers the plain array it always did
+    pub fn parse(offset: Option<usize>, limit: Option<usize>, fields: Option<&str>, sort: Option<&str>) -> Result<Option<Query>, String> {
+        if offset.is_none() && limit.is_none() && fields.is_none() && sort.is_none() {
+            return Ok(None);
+        }
+
+        let fields = fields.map(parse_fields).transpose()?;
+        let sort = sort.map(str::trim).unwrap_or("id");
+        let (descending, key) = match sort.strip_prefix('-') {
+            Some(key) => (true, key),
+            None => (false, sort),
+        };
+
+        if !SORT.contains(&key) {
+            return Err(format!("unknown sort '{key}', available: {}", SORT.join(",")));
+        }
+
+        Ok(Some(Query {
+            offset: offset.unwrap_or(0),
+            limit,
+            fields,
+            sort: key.to_string(),
+            descending,
+        }))
+    }
+
+    /// Whether cpu and memory have to be sampled, to be shown or sorted by
+    pub fn samples(&self) -> bool {
+        SAMPLED.contains(&self.sort.as_str()) || self.fields.as_ref().is_none_or(|fields| fields.iter().any(|field| SAMPLED.contains(&field.as_str())))
+    }
+}
+
+fn parse_fields(list: &str) -> Result<Vec<String>, String> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|field| !field.is_empty())
+        .map(|field| match FIELDS.contains(&field) {
+            true => Ok(field.to_string()),
+            false => Err(format!("unknown field '{field}', available: {}", FIELDS.join(","))),
+        })
+        .collect()
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, PartialEq)]
+pub struct Page {
+    /// Processes matching the filters over all pages
+    #[schema(example = 523)]
+    pub total: usize,
+    /// Offset of the next page, None on the last one
+    #[schema(example = 100)]
+    pub next_offset: Option<usize>,
+    /// Processes with the requested fields only
+    #[schema(value_type = Vec<Object>)]
+    pub processes: Vec<Map<String, Value>>,
+}
+
+/// Sort `items` as `query` asks, ties and the default stay in id order, then cut out its page
+pub fn page(mut items: Vec<(ProcessItem, Measured)>, query: &Query) -> Page {
+    items.sort_by(|(a, a_measured), (b, b_measured)| {
+        let order = match query.sort.as_str() {
+            "name" => a.name.cmp(&b.name),
+            "status" => a.status.cmp(&b.status),
+            "cpu" => a_measured.cpu.total_cmp(&b_measured.cpu),
+            "mem" => a_measured.memory.cmp(&b_measured.memory),
+            "restarts" => a.restarts.cmp(&b.restarts),
+            "uptime" => a_measured.uptime.cmp(&b_measured.uptime),
+            _ => Ordering::Equal,
+        };
+        ternary!(query.descending, order.reverse(), order).then(a.id.cmp(&b.id))
+    });
+
+    let total = items.len();
+    let end = query.limit.map_or(total, |limit| query.offset.saturating_add(limit).min(total));
+
+    Page {
+        total,
+        next_offset: (end < total).then_some(end),
+        processes: items.iter().take(end).skip(query.offset).map(|(item, _)| mask(item, query.fields.as_deref())).collect(),
+    }
+}
+
+/// The JSON of `item` with only `fields`, fields that are None are left out as in the full listing
+fn mask(item: &ProcessItem, fields: Option<&[String]>) -> Map<String, Value> {
+    let Ok(Value::Object(mut object)) = serde_json::to_value(item) else { return Map::new() };
+
+    if let Some(fields) = fields {
+        object.retain(|key, _| fields.contains(key));
+    }
+    object
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn item(id: usize, name: &str, cpu: f64, memory: u64) -> (ProcessItem, Measured) {
+        let process = ProcessItem {
+            pid: 100 + id as i64,
+            id,
+            cpu: format!("{cpu:.2}%"),
+            mem: format!("{memory}b"),
+            name: name.to_string(),
+            restarts: 0,
+            status: String::from("online"),
+            uptime: String::from("1m"),
+            watch_path: String::new(),
+            start_time: Utc::now(),
+            agent_id: None,
+            agent_name: None,
+            labels: Default::default(),
+            group: None,
+            url: None,
+        };
+        (process, Measured { cpu, memory, uptime: 60 })
+    }
+
+    fn items() -> Vec<(ProcessItem, Measured)> {
+        vec![item(0, "worker", 9.0, 900), item(1, "api", 10.0, 10_000), item(2, "cron", 0.5, 50), item(3, "web", 10.0, 20)]
+    }
+
+    fn query(offset: Option<usize>, limit: Option<usize>, fields: Option<&str>, sort: Option<&str>) -> Query { Query::parse(offset, limit, fields, sort).unwrap().unwrap() }
+
+    fn ids(page: &Page) -> Vec<u64> { page.processes.iter().map(|process| process["id"].as_u64().unwrap()).collect() }
+
+    #[test]
+    fn test_no_parameters_keep_the_plain_listing() {
+        assert_eq!(Query::parse(None, None, None, None), Ok(None));
+    }
+
+    #[test]
+    fn test_pages_follow_the_id_order() {
+        let first = page(items(), &query(None, Some(3), None, None));
+        assert_eq!((ids(&first), first.total, first.next_offset), (vec![0, 1, 2], 4, Some(3)));
+
+        let last = page(items(), &query(first.next_offset, Some(3), None, None));
+        assert_eq!((ids(&last), last.next_offset), (vec![3], None));
+
+        let past = page(items(), &query(Some(10), Some(3), None, None));
+        assert!(past.processes.is_empty() && past.next_offset.is_none());
+    }
+
+    #[test]
+    fn test_sort_by_numbers_not_text() {
+        assert_eq!(ids(&page(items(), &query(None, None, None, Some("mem")))), vec![3, 2, 0, 1]);
+        // Equal cpu stays in id order either way
+        assert_eq!(ids(&page(items(), &query(None, None, None, Some("-cpu")))), vec![1, 3, 0, 2]);
+        assert_eq!(ids(&page(items(), &query(None, None, None, Some("name")))), vec![1, 2, 3, 0]);
+    }
+
+    #[test]
+    fn test_field_mask() {
+        let query = query(None, Some(1), Some("id, name,status"), None);
+        assert!(!query.samples());
+
+        let page = page(items(), &query);
+        assert_eq!(page.processes[0].keys().collect::<Vec<_>>(), vec!["id", "name", "status"]);
+
+        assert!(Query::parse(None, None, Some("id,cpu"), None).unwrap().unwrap().samples());
+        assert!(Query::parse(None, None, Some("id"), Some("-mem")).unwrap().unwrap().samples());
+        assert!(Query::parse(Some(0), None, None, None).unwrap().unwrap().samples());
+    }
+
+    #[test]
+    fn test_unknown_fields_and_sorts() {
+        assert_eq!(Query::parse(None, None, Some("id,secret"), None).unwrap_err().split(',').next(), Some("unknown field 'secret'"));
+        assert!(Query::parse(None, None, None, Some("-pid")).unwrap_err().starts_with("unknown sort 'pid'"));
+    }
+}
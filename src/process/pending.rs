@@ -0,0 +1,124 @@
+use super::Process;
+
+use chrono::{DateTime, Utc};
+use global_placeholders::global;
+use serde::{Deserialize, Serialize};
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs, io,
+};
+
+/// Processes the daemon lets start at once while it works through pending ones
+pub const MAX_STARTING: usize = 4;
+
+/// The last `opm import --background`, kept in `~/.opm/import.json` for `opm import-status`
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Import {
+    pub file: String,
+    pub time: DateTime<Utc>,
+    pub ids: Vec<usize>,
+}
+
+impl Import {
+    fn path() -> String { format!("{}import.json", global!("opm.base")) }
+
+    pub fn write(&self) -> io::Result<()> { fs::write(Import::path(), serde_json::to_vec_pretty(self)?) }
+
+    /// None when no background import was made yet
+    pub fn read() -> io::Result<Option<Import>> {
+        match fs::read(Import::path()) {
+            Ok(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+/// Where an imported process is, `None` when it was removed since
+pub fn state(process: Option<&Process>) -> &'static str {
+    match process {
+        None => "removed",
+        Some(process) if process.pending => "pending",
+        Some(process) if process.running => "started",
+        Some(process) if process.crash.crashed => "failed",
+        Some(_) => "stopped",
+    }
+}
+
+/// Pending processes to start now, in id order. A process waits while anything it depends on is
+/// still pending or starting, and no more than `MAX_STARTING` processes are starting at a time.
+pub fn ready(list: &BTreeMap<usize, Process>, now: DateTime<Utc>) -> Vec<usize> {
+    let starting = list.values().filter(|p| p.is_starting(now)).count();
+    let waiting: HashSet<&str> = list.values().filter(|p| p.pending || p.is_starting(now)).map(|p| p.name.as_str()).collect();
+    let pending: Vec<(&usize, &Process)> = list.iter().filter(|(_, p)| p.pending).collect();
+
+    let mut ready: Vec<usize> = pending
+        .iter()
+        .filter(|(_, p)| !p.depends_on.iter().any(|name| waiting.contains(name.as_str())))
+        .map(|(id, _)| **id)
+        .collect();
+
+    // Nothing is left to wait for, pending processes depending on each other are started together
+    if ready.is_empty() && starting == 0 && !pending.is_empty() {
+        ready = pending.iter().map(|(id, _)| **id).collect();
+        log::warn!("Dependency cycle between pending processes {ready:?}, starting them together");
+    }
+
+    ready.truncate(MAX_STARTING.saturating_sub(starting));
+    ready
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn process(id: usize, name: &str, depends_on: &[&str], pending: bool) -> (usize, Process) {
+        (
+            id,
+            Process {
+                id,
+                name: name.to_string(),
+                path: PathBuf::from("/"),
+                script: "true".to_string(),
+                depends_on: depends_on.iter().map(|d| d.to_string()).collect(),
+                started: Utc::now() - chrono::Duration::hours(1),
+                started_mark: None,
+                errored: None,
+                pending,
+                ..Default::default()
+            },
+        )
+    }
+
+    #[test]
+    fn test_ready_respects_dependencies() {
+        let mut list: BTreeMap<usize, Process> = [process(0, "db", &[], true), process(1, "api", &["db"], true), process(2, "web", &["api"], true)].into();
+        assert_eq!(ready(&list, Utc::now()), vec![0]);
+
+        // db is starting, api still waits for it
+        let db = list.get_mut(&0).unwrap();
+        db.pending = false;
+        db.running = true;
+        db.started = Utc::now();
+        assert!(ready(&list, Utc::now()).is_empty());
+
+        assert_eq!(ready(&list, Utc::now() + chrono::Duration::minutes(5)), vec![1]);
+        assert_eq!(state(list.get(&1)), "pending");
+        assert_eq!(state(list.get(&0)), "started");
+        assert_eq!(state(None), "removed");
+    }
+
+    #[test]
+    fn test_ready_limits_concurrency() {
+        let list: BTreeMap<usize, Process> = (0..10).map(|id| process(id, &format!("worker-{id}"), &[], true)).collect();
+        assert_eq!(ready(&list, Utc::now()), (0..MAX_STARTING).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_ready_breaks_cycles() {
+        let list: BTreeMap<usize, Process> = [process(0, "a", &["b"], true), process(1, "b", &["a"], true), process(2, "c", &[], false)].into();
+        assert_eq!(ready(&list, Utc::now()), vec![0, 1]);
+    }
+}
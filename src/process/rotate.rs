@@ -0,0 +1,210 @@
+//! Size based rotation of process logs, see `runner.log_max_size` and `runner.log_keep`.
+//! A rotated log becomes `api-out.log.1` and older archives shift up by one, `.2.gz` once housekeeping compressed it.
+
+use super::checkpoint;
+use crate::{config::structs::Runner, helpers};
+use flate2::read::GzDecoder;
+
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, BufReader, Read},
+    path::{Path, PathBuf},
+};
+
+/// Archives kept per log without `runner.log_keep`
+pub const DEFAULT_KEEP: usize = 5;
+
+/// Size a log is rotated past and how many archives of it are kept
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Policy {
+    pub max_bytes: u64,
+    pub keep: usize,
+}
+
+impl Policy {
+    /// None when `runner.log_max_size` is not set, logs then grow until `opm flush`
+    pub fn new(runner: &Runner) -> Result<Option<Policy>, String> {
+        let Some(size) = runner.log_max_size.as_deref() else { return Ok(None) };
+        let max_bytes = helpers::parse_memory(size).map_err(|err| format!("runner.log_max_size: {err}"))?;
+
+        Ok(Some(Policy {
+            max_bytes,
+            keep: runner.log_keep.unwrap_or(DEFAULT_KEEP),
+        }))
+    }
+}
+
+/// How the process keeps writing once its log is rotated
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Mode {
+    /// Move the log away and create a new one, the writer has to reopen it like the supervisor does on SIGHUP
+    Rename,
+    /// Copy the log to `.1` and empty it, for a process holding it open in append mode.
+    /// Lines written between the copy and the truncation are lost.
+    Copy,
+}
+
+/// Number of an archive of `log`, 2 for both `api-out.log.2` and `api-out.log.2.gz`
+fn index(log: &Path, archive: &Path) -> Option<usize> {
+    let name = log.file_name()?.to_str()?;
+    let rest = archive.file_name()?.to_str()?.strip_prefix(name)?.strip_prefix('.')?;
+    rest.strip_suffix(".gz").unwrap_or(rest).parse().ok()
+}
+
+fn numbered(log: &Path, index: usize, compressed: bool) -> PathBuf {
+    PathBuf::from(format!("{}.{index}{}", log.display(), if compressed { ".gz" } else { "" }))
+}
+
+/// Archives of a log, newest first
+pub fn archives(log: &Path) -> Vec<PathBuf> {
+    let dir = log.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let Ok(entries) = fs::read_dir(dir) else { return vec![] };
+
+    let mut archives: Vec<(usize, PathBuf)> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter_map(|path| Some((index(log, &path)?, path)))
+        .collect();
+
+    archives.sort();
+    archives.into_iter().map(|(_, path)| path).collect()
+}
+
+/// Make room for a new `.1`, every archive moves up by one and the ones past `keep` are deleted
+fn shift(log: &Path, keep: usize) -> io::Result<()> {
+    // Oldest first, so no archive is moved over the next one
+    for archive in archives(log).into_iter().rev() {
+        let Some(index) = index(log, &archive) else { continue };
+
+        match index >= keep {
+            true => fs::remove_file(&archive)?,
+            false => fs::rename(&archive, numbered(log, index + 1, archive.to_string_lossy().ends_with(".gz")))?,
+        }
+    }
+
+    Ok(())
+}
+
+/// Move `log` to `.1` behind its older archives, keeping `keep` of them
+pub fn archive(log: &Path, keep: usize) -> io::Result<()> {
+    shift(log, keep)?;
+
+    match keep {
+        0 => fs::remove_file(log),
+        _ => fs::rename(log, numbered(log, 1, false)),
+    }
+}
+
+/// Rotate `log` once it grew past the policy, false when it is still small enough or does not exist
+pub fn rotate(log: &Path, policy: Policy, mode: Mode) -> io::Result<bool> {
+    match fs::metadata(log) {
+        Ok(metadata) if metadata.len() > policy.max_bytes => {}
+        Ok(_) => return Ok(false),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(false),
+        Err(err) => return Err(err),
+    }
+
+    match mode {
+        Mode::Rename => archive(log, policy.keep)?,
+        Mode::Copy => {
+            shift(log, policy.keep)?;
+            if policy.keep > 0 {
+                fs::copy(log, numbered(log, 1, false))?;
+            }
+        }
+    }
+
+    // Recreated for the next writer, or emptied under the process still writing to it
+    OpenOptions::new().create(true).write(true).truncate(true).open(log)?;
+    checkpoint::remove(&log.display().to_string());
+
+    Ok(true)
+}
+
+fn read_lines(archive: &Path) -> Vec<String> {
+    let Ok(file) = File::open(archive) else { return vec![] };
+    let reader: Box<dyn Read> = match archive.to_string_lossy().ends_with(".gz") {
+        true => Box::new(GzDecoder::new(file)),
+        false => Box::new(file),
+    };
+
+    BufReader::new(reader).lines().map_while(Result::ok).collect()
+}
+
+/// Last `count` lines of the archives of `log`, oldest first, for a tail the log alone cannot fill
+pub fn tail(log: &Path, count: usize) -> Vec<String> {
+    let mut lines = vec![];
+
+    for archive in archives(log) {
+        if lines.len() >= count {
+            break;
+        }
+
+        let mut archived = read_lines(&archive);
+        let kept = archived.split_off(archived.len().saturating_sub(count - lines.len()));
+        lines.splice(0..0, kept);
+    }
+
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::{Compression, write::GzEncoder};
+    use std::io::Write;
+
+    fn temp(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("opm-rotate-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_rotate_keeps_archives() {
+        let dir = temp("keep");
+        let log = dir.join("api-out.log");
+        let policy = Policy { max_bytes: 10, keep: 2 };
+
+        fs::write(&log, "short\n").unwrap();
+        assert!(!rotate(&log, policy, Mode::Copy).unwrap());
+        assert!(!rotate(&dir.join("missing.log"), policy, Mode::Copy).unwrap());
+
+        for (round, mode) in [(1, Mode::Copy), (2, Mode::Rename), (3, Mode::Copy)] {
+            fs::write(&log, format!("round {round} past the limit\n")).unwrap();
+            assert!(rotate(&log, policy, mode).unwrap());
+            assert_eq!(fs::metadata(&log).unwrap().len(), 0);
+        }
+
+        assert_eq!(archives(&log), vec![dir.join("api-out.log.1"), dir.join("api-out.log.2")]);
+        assert_eq!(fs::read_to_string(dir.join("api-out.log.1")).unwrap(), "round 3 past the limit\n");
+        assert_eq!(fs::read_to_string(dir.join("api-out.log.2")).unwrap(), "round 2 past the limit\n");
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_compressed_archives() {
+        let dir = temp("gz");
+        let log = dir.join("api-out.log");
+
+        let mut encoder = GzEncoder::new(File::create(dir.join("api-out.log.2.gz")).unwrap(), Compression::default());
+        encoder.write_all(b"one\ntwo\n").unwrap();
+        encoder.finish().unwrap();
+        fs::write(dir.join("api-out.log.1"), "three\nfour\n").unwrap();
+        fs::write(dir.join("api-out.log.idx"), "").unwrap();
+        fs::write(dir.join("api-err.log.1"), "other\n").unwrap();
+
+        assert_eq!(archives(&log), vec![dir.join("api-out.log.1"), dir.join("api-out.log.2.gz")]);
+        assert_eq!(tail(&log, 3), vec!["two", "three", "four"]);
+        assert_eq!(tail(&log, 10), vec!["one", "two", "three", "four"]);
+
+        fs::write(&log, "five\n").unwrap();
+        archive(&log, 5).unwrap();
+        assert!(dir.join("api-out.log.3.gz").exists() && dir.join("api-out.log.2").exists());
+        assert_eq!(tail(&log, 1), vec!["five"]);
+
+        fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,133 @@
+use super::{Process, description, is_pid_alive};
+
+use chrono::Utc;
+use macros_rs::ternary;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+pub const DEFAULT_LIMIT: usize = 10;
+pub const MAX_LIMIT: usize = 100;
+
+/// Lightweight search result, only what is needed to jump to a process
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, PartialEq)]
+pub struct SearchMatch {
+    #[schema(example = 0)]
+    pub id: usize,
+    #[schema(example = "api")]
+    pub name: String,
+    #[schema(example = "online")]
+    pub status: String,
+}
+
+/// How well a process matches `query` (already lowercased), lower ranks first.
+/// Exact names beat prefixes, which beat substrings of the name, then label and description matches.
+fn rank(process: &Process, query: &str) -> Option<(u8, usize)> {
+    let name = process.name.to_lowercase();
+
+    if name == query {
+        return Some((0, 0));
+    }
+
+    if let Some(position) = name.find(query) {
+        return Some((ternary!(position == 0, 1, 2), position));
+    }
+
+    if process.labels.iter().any(|(key, value)| format!("{key}={value}").to_lowercase().contains(query)) {
+        return Some((3, 0));
+    }
+
+    description::matches(&process.description, query).then_some((4, 0))
+}
+
+/// Case-insensitive name lookup, an empty query lists the first processes by id.
+/// Only the pid of each returned process is checked, nothing is sampled.
+pub fn search<'p>(processes: impl IntoIterator<Item = &'p Process>, query: &str, limit: usize) -> Vec<SearchMatch> {
+    let query = query.trim().to_lowercase();
+    let mut ranked: Vec<_> = processes
+        .into_iter()
+        .filter_map(|process| match query.is_empty() {
+            true => Some(((0, 0, 0, process.id), process)),
+            false => rank(process, &query).map(|(rank, position)| ((rank, position, process.name.len(), process.id), process)),
+        })
+        .collect();
+
+    ranked.sort_by_key(|(key, _)| *key);
+
+    let now = Utc::now();
+    ranked
+        .into_iter()
+        .take(limit.clamp(1, MAX_LIMIT))
+        .map(|(_, process)| SearchMatch {
+            id: process.id,
+            name: process.name.clone(),
+            status: process.status(process.running && is_pid_alive(process.pid), now).to_string(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process::Labels;
+    use macros_rs::string;
+    use std::path::PathBuf;
+
+    fn process(id: usize, name: &str, labels: &[(&str, &str)]) -> Process {
+        Process {
+            id,
+            name: string!(name),
+            path: PathBuf::from("/tmp"),
+            script: string!("sleep 1"),
+            started: Utc::now(),
+            labels: labels.iter().map(|(key, value)| (string!(*key), string!(*value))).collect::<Labels>(),
+            ..Default::default()
+        }
+    }
+
+    fn names(matches: &[SearchMatch]) -> Vec<&str> {
+        matches.iter().map(|found| found.name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_exact_then_prefix_then_substring() {
+        let processes = [
+            process(0, "worker-api", &[]),
+            process(1, "api-gateway", &[]),
+            process(2, "API", &[]),
+            process(3, "billing", &[("team", "api")]),
+            process(4, "api", &[]),
+            process(5, "scheduler", &[]),
+        ];
+
+        let found = search(&processes, "Api", 10);
+        assert_eq!(names(&found), vec!["API", "api", "api-gateway", "worker-api", "billing"]);
+        assert_eq!(found[0], SearchMatch { id: 2, name: string!("API"), status: string!("stopped") });
+    }
+
+    #[test]
+    fn test_description_ranks_last() {
+        let mut processes = [process(0, "worker-7", &[]), process(1, "dana-tools", &[]), process(2, "cron", &[])];
+        processes[0].description = Some(string!("Legacy billing worker, owned by @Dana"));
+
+        assert_eq!(names(&search(&processes, "dana", 10)), vec!["dana-tools", "worker-7"]);
+        assert_eq!(names(&search(&processes, "billing", 10)), vec!["worker-7"]);
+    }
+
+    #[test]
+    fn test_substring_prefers_earlier_and_shorter() {
+        let processes = [process(0, "my-long-cache", &[]), process(1, "xcache", &[]), process(2, "my-cache", &[])];
+
+        assert_eq!(names(&search(&processes, "cache", 10)), vec!["xcache", "my-cache", "my-long-cache"]);
+    }
+
+    #[test]
+    fn test_limit_and_empty_query() {
+        let processes: Vec<Process> = (0..30).map(|id| process(id, &format!("job-{id}"), &[])).collect();
+
+        assert_eq!(search(&processes, "job", 5).len(), 5);
+        assert_eq!(search(&processes, "job", 0).len(), 1);
+        assert_eq!(search(&processes, "job", 1000).len(), 30);
+        assert_eq!(search(&processes, "", 3).iter().map(|found| found.id).collect::<Vec<_>>(), vec![0, 1, 2]);
+        assert!(search(&processes, "nothing", 10).is_empty());
+    }
+}
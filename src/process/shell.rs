@@ -0,0 +1,104 @@
+use super::Process;
+use crate::helpers;
+use serde::{Deserialize, Serialize};
+use std::{fs, os::unix::fs::PermissionsExt, path::Path};
+
+/// Shell a process is spawned with, unset parts come from `runner.shell` and `runner.args`
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Shell {
+    pub program: Option<String>,
+    pub args: Option<Vec<String>>,
+}
+
+/// Check a shell is an absolute path to an executable file, returned as written
+pub fn parse(path: &str) -> Result<String, String> {
+    let path = path.trim();
+
+    if !Path::new(path).is_absolute() {
+        return Err(format!("shell '{path}' must be an absolute path"));
+    }
+
+    match fs::metadata(path) {
+        Ok(metadata) if !metadata.is_file() => Err(format!("shell '{path}' is not a file")),
+        Ok(metadata) if metadata.permissions().mode() & 0o111 == 0 => Err(format!("shell '{path}' is not executable")),
+        Ok(_) => Ok(path.to_string()),
+        Err(err) => Err(format!("shell '{path}' cannot be used: {err}")),
+    }
+}
+
+impl Shell {
+    pub fn is_empty(&self) -> bool { *self == Shell::default() }
+
+    /// The program and arguments, `shell` and `args` of the runner config filling in the unset ones
+    pub fn resolve(&self, shell: &str, args: &[String]) -> (String, Vec<String>) {
+        (
+            self.program.clone().unwrap_or_else(|| shell.to_string()),
+            self.args.clone().unwrap_or_else(|| args.to_vec()),
+        )
+    }
+
+    /// Human readable form, e.g. `/usr/bin/fish -l -c`, or `runner.shell` when nothing is set
+    pub fn describe(&self) -> String {
+        match (&self.program, &self.args) {
+            (None, None) => "runner.shell".to_string(),
+            (program, args) => {
+                let program = program.as_deref().unwrap_or("runner.shell");
+                let args = args.as_ref().map_or_else(|| "runner.args".to_string(), |args| args.join(" "));
+                format!("{program} {args}").trim_end().to_string()
+            }
+        }
+    }
+}
+
+impl Process {
+    pub fn custom_shell(&self) -> Shell {
+        Shell {
+            program: self.shell.clone(),
+            args: self.shell_args.clone(),
+        }
+    }
+
+    /// Program and arguments the next spawn runs the script with
+    pub fn invocation(&self, shell: &str, args: &[String]) -> (String, Vec<String>) { self.custom_shell().resolve(shell, args) }
+
+    /// The command line a spawn runs, as `opm details` and `opm get-command` show it
+    pub fn command_line(&self, shell: &str, args: &[String]) -> String {
+        let (shell, args) = self.invocation(shell, args);
+        helpers::shell_command(&shell, &args, &self.script)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(parse("/bin/sh"), Ok("/bin/sh".to_string()));
+        assert!(parse("sh").unwrap_err().contains("absolute"));
+        assert!(parse("/bin").unwrap_err().contains("not a file"));
+        assert!(parse("/etc/hostname-that-does-not-exist").is_err());
+
+        let file = std::env::temp_dir().join(format!("opm-shell-test-{}", std::process::id()));
+        fs::write(&file, "").unwrap();
+        assert!(parse(&file.display().to_string()).unwrap_err().contains("not executable"));
+        let _ = fs::remove_file(&file);
+    }
+
+    #[test]
+    fn test_resolve() {
+        let args = vec!["-c".to_string()];
+
+        assert_eq!(Shell::default().resolve("/bin/bash", &args), ("/bin/bash".to_string(), args.clone()));
+
+        let fish = Shell { program: Some("/usr/bin/fish".to_string()), args: Some(vec!["-l".to_string(), "-c".to_string()]) };
+        assert_eq!(fish.resolve("/bin/bash", &args), ("/usr/bin/fish".to_string(), vec!["-l".to_string(), "-c".to_string()]));
+        assert_eq!(fish.describe(), "/usr/bin/fish -l -c");
+
+        // A shell without its own arguments keeps the configured ones
+        let zsh = Shell { program: Some("/bin/zsh".to_string()), args: None };
+        assert_eq!(zsh.resolve("/bin/bash", &args), ("/bin/zsh".to_string(), args));
+        assert_eq!(zsh.describe(), "/bin/zsh runner.args");
+        assert_eq!(Shell::default().describe(), "runner.shell");
+    }
+}
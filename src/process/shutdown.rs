@@ -0,0 +1,233 @@
+use super::{Process, Runner, is_pid_alive, kill_children, process_find_children, process_stop_with_signal};
+use crate::config::structs::Daemon;
+
+use nix::{
+    sys::signal::{Signal, kill},
+    unistd::Pid,
+};
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Time a process gets to exit after SIGTERM before it is killed, unless `daemon.kill_timeout` says otherwise
+pub const KILL_TIMEOUT: Duration = Duration::from_secs(5);
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Outcome of one ordering level, reported once all of its processes are down
+pub struct LevelSummary {
+    /// 1-based index of the level
+    pub level: usize,
+    pub levels: usize,
+    /// Processes that exited after SIGTERM
+    pub stopped: Vec<String>,
+    /// Processes that had to be killed (kill timeout or global deadline)
+    pub killed: Vec<String>,
+    pub elapsed: Duration,
+}
+
+/// `daemon.kill_timeout` of `daemon`, or `KILL_TIMEOUT` when it is not set
+pub fn kill_timeout(daemon: &Daemon) -> Duration { daemon.kill_timeout.map_or(KILL_TIMEOUT, Duration::from_millis) }
+
+/// Group processes into stop levels, a process is always stopped before the ones it depends on.
+/// Without any dependency metadata every process gets its own level, newest first.
+pub fn plan(list: &BTreeMap<usize, Process>, ids: &[usize]) -> Vec<Vec<usize>> {
+    let targets: BTreeMap<usize, &Process> = ids.iter().filter_map(|id| list.get(id).map(|p| (*id, p))).collect();
+
+    if targets.values().all(|p| p.depends_on.is_empty()) {
+        let mut order: Vec<(&usize, &&Process)> = targets.iter().collect();
+        order.sort_by(|(a_id, a), (b_id, b)| b.started.cmp(&a.started).then(b_id.cmp(a_id)));
+        return order.into_iter().map(|(id, _)| vec![*id]).collect();
+    }
+
+    let mut remaining = targets;
+    let mut levels = vec![];
+
+    while !remaining.is_empty() {
+        // Names something still running depends on have to wait for a later level
+        let needed: HashSet<&str> = remaining.values().flat_map(|p| p.depends_on.iter().map(String::as_str)).collect();
+        let level: Vec<usize> = remaining.iter().filter(|(_, p)| !needed.contains(p.name.as_str())).map(|(id, _)| *id).collect();
+
+        if level.is_empty() {
+            log::warn!("Dependency cycle between processes {:?}, stopping them together", remaining.keys().collect::<Vec<_>>());
+            levels.push(remaining.keys().copied().collect());
+            break;
+        }
+
+        level.iter().for_each(|id| {
+            remaining.remove(id);
+        });
+        levels.push(level);
+    }
+
+    levels
+}
+
+/// Stop the given processes level by level. Processes of a level are signalled together with `signal` and
+/// killed once `kill_timeout` passes, once `deadline` passes everything left is killed right away.
+pub fn run(runner: &mut Runner, ids: &[usize], deadline: Option<Duration>, kill_timeout: Duration, signal: Signal, mut progress: impl FnMut(&LevelSummary)) {
+    let started = Instant::now();
+    let deadline = deadline.map(|deadline| started + deadline);
+    let levels = plan(&runner.list, ids);
+
+    for (index, level) in levels.iter().enumerate() {
+        let level_started = Instant::now();
+        let expired = deadline.is_some_and(|deadline| level_started >= deadline);
+        let mut pending: Vec<usize> = vec![];
+        let mut killed: Vec<usize> = vec![];
+
+        for id in level {
+            let process = runner.process(*id);
+
+            if expired {
+                force_kill(process);
+                killed.push(*id);
+            } else {
+                kill_children(process.tree(), signal);
+                if let Err(err) = process_stop_with_signal(process.pid, signal) {
+                    log::warn!("{err}");
+                }
+                pending.push(*id);
+            }
+        }
+
+        let mut timeout = level_started + kill_timeout;
+        if let Some(deadline) = deadline {
+            timeout = timeout.min(deadline);
+        }
+
+        loop {
+            pending.retain(|id| runner.info(*id).is_some_and(|p| is_pid_alive(p.pid)));
+            if pending.is_empty() || Instant::now() >= timeout {
+                break;
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+
+        for id in pending {
+            force_kill(runner.process(id));
+            killed.push(id);
+        }
+
+        let mut summary = LevelSummary {
+            level: index + 1,
+            levels: levels.len(),
+            stopped: vec![],
+            killed: vec![],
+            elapsed: level_started.elapsed(),
+        };
+
+        for id in level {
+            let process = runner.process(*id);
+            process.running = false;
+            process.crash.crashed = false;
+            process.children = vec![];
+
+            match killed.contains(id) {
+                true => summary.killed.push(process.name.clone()),
+                false => summary.stopped.push(process.name.clone()),
+            }
+        }
+
+        progress(&summary);
+    }
+}
+
+fn force_kill(process: &Process) {
+    if process.pid <= 0 {
+        return;
+    }
+
+    let mut pids: Vec<i64> = process.children.clone();
+    pids.extend(process_find_children(process.pid));
+    pids.push(process.pid);
+
+    for pid in pids {
+        match kill(Pid::from_raw(pid as i32), Signal::SIGKILL) {
+            Ok(_) | Err(nix::errno::Errno::ESRCH) => {}
+            Err(err) => log::error!("Failed to kill pid {pid}: {err:?}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration as ChronoDuration, Utc};
+    use std::path::PathBuf;
+    use macros_rs::string;
+
+    fn process(id: usize, name: &str, depends_on: &[&str], age_secs: i64) -> (usize, Process) {
+        let process = Process {
+            id,
+            name: name.to_string(),
+            path: PathBuf::from("/tmp"),
+            running: true,
+            started: Utc::now() - ChronoDuration::seconds(age_secs),
+            depends_on: depends_on.iter().map(|d| d.to_string()).collect(),
+            ..Default::default()
+        };
+
+        (id, process)
+    }
+
+    #[test]
+    fn test_plan_without_dependencies_uses_reverse_start_order() {
+        let list = BTreeMap::from([process(0, "db", &[], 30), process(1, "api", &[], 20), process(2, "web", &[], 10)]);
+        assert_eq!(plan(&list, &[0, 1, 2]), vec![vec![2], vec![1], vec![0]]);
+    }
+
+    #[test]
+    fn test_plan_stops_dependents_first() {
+        let list = BTreeMap::from([
+            process(0, "db", &[], 0),
+            process(1, "cache", &[], 0),
+            process(2, "api", &["db", "cache"], 0),
+            process(3, "worker", &["db"], 0),
+            process(4, "web", &["api"], 0),
+        ]);
+
+        assert_eq!(plan(&list, &[0, 1, 2, 3, 4]), vec![vec![3, 4], vec![2], vec![0, 1]]);
+        // Dependencies outside of the stopped set are ignored
+        assert_eq!(plan(&list, &[2, 3]), vec![vec![2, 3]]);
+    }
+
+    #[test]
+    fn test_plan_groups_cycles_together() {
+        let list = BTreeMap::from([process(0, "a", &["b"], 0), process(1, "b", &["a"], 0), process(2, "c", &["a"], 0)]);
+        assert_eq!(plan(&list, &[0, 1, 2]), vec![vec![2], vec![0, 1]]);
+    }
+
+    #[test]
+    fn test_run_kills_processes_ignoring_sigterm_at_deadline() {
+        let mut polite = std::process::Command::new("sleep").arg("30").spawn().unwrap();
+        let mut stubborn = std::process::Command::new("sh").args(["-c", "trap '' TERM; sleep 30"]).spawn().unwrap();
+        // Give the shell time to install its trap
+        thread::sleep(Duration::from_millis(200));
+
+        let mut runner = Runner {
+            id: crate::process::id::Id::new(0),
+            remote: None,
+            list: BTreeMap::from([process(0, "polite", &[], 0), process(1, "stubborn", &["polite"], 0)]),
+            host: None,
+            backend: Default::default(),
+        };
+        runner.process(0).pid = polite.id() as i64;
+        runner.process(1).pid = stubborn.id() as i64;
+
+        let mut summaries = vec![];
+        let started = Instant::now();
+        run(&mut runner, &[0, 1], Some(Duration::from_secs(1)), KILL_TIMEOUT, Signal::SIGTERM, |summary| {
+            summaries.push((summary.stopped.clone(), summary.killed.clone()))
+        });
+
+        assert!(started.elapsed() < KILL_TIMEOUT);
+        assert_eq!(summaries, vec![(vec![], vec![string!("stubborn")]), (vec![], vec![string!("polite")])]);
+        assert!(runner.list.values().all(|p| !p.running));
+
+        let _ = polite.wait();
+        let _ = stubborn.wait();
+    }
+}
@@ -0,0 +1,47 @@
+//! Signals a process can be stopped with, for services that shut down gracefully on something else than SIGTERM
+
+use nix::sys::signal::Signal;
+
+/// What `--signal` takes, SIGTERM being the default
+pub const SUPPORTED: [Signal; 8] = [
+    Signal::SIGTERM,
+    Signal::SIGINT,
+    Signal::SIGQUIT,
+    Signal::SIGKILL,
+    Signal::SIGHUP,
+    Signal::SIGUSR1,
+    Signal::SIGUSR2,
+    Signal::SIGWINCH,
+];
+
+/// Parse a signal name in any case, with or without its `SIG` prefix
+pub fn parse(name: &str) -> Result<Signal, String> {
+    let upper = name.trim().to_ascii_uppercase();
+    let full = match upper.starts_with("SIG") {
+        true => upper,
+        false => format!("SIG{upper}"),
+    };
+
+    SUPPORTED.into_iter().find(|signal| signal.as_str() == full).ok_or_else(|| {
+        let names: Vec<&str> = SUPPORTED.iter().map(|signal| signal.as_str()).collect();
+        format!("unknown signal '{name}', supported signals are {}", names.join(", "))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(parse("SIGINT"), Ok(Signal::SIGINT));
+        assert_eq!(parse("sigquit"), Ok(Signal::SIGQUIT));
+        assert_eq!(parse("kill"), Ok(Signal::SIGKILL));
+        assert_eq!(parse(" Term "), Ok(Signal::SIGTERM));
+
+        let err = parse("SIGFOO").unwrap_err();
+        assert!(err.contains("unknown signal 'SIGFOO'") && err.contains("SIGTERM, SIGINT"), "{err}");
+        assert!(parse("SIGSEGV").is_err());
+        assert!(parse("").is_err());
+    }
+}
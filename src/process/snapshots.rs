@@ -0,0 +1,324 @@
+//! Copies of the dump the daemon takes on a schedule, `opm snapshots` rolls the process list back to one.
+//! These are unrelated to the per-process `opm details --snapshot`, which only share the directory.
+
+use super::{Process, Runner, dump, id::Id};
+
+use chrono::{DateTime, NaiveDateTime, TimeDelta, Utc};
+use global_placeholders::global;
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use std::{
+    collections::BTreeSet,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    sync::atomic::Ordering,
+};
+
+const PREFIX: &str = "dump-";
+/// Time in the file names, in UTC so they sort in the order they were taken
+const STAMP: &str = "%Y%m%dT%H%M%SZ";
+
+/// Dump fields that describe the running instance rather than how the process is defined
+const RUNTIME: [&str; 12] = ["id", "pid", "shell_pid", "restarts", "running", "crash", "children", "started", "last_restart_reason", "fingerprint", "exit_code", "pending"];
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Snapshot {
+    /// Timestamp in the file name, what `opm snapshots restore` takes
+    pub stamp: String,
+    pub time: DateTime<Utc>,
+    pub bytes: u64,
+    #[serde(skip)]
+    pub path: PathBuf,
+}
+
+impl Snapshot {
+    fn read(path: &Path) -> Option<Snapshot> {
+        let stamp = path.file_name()?.to_str()?.strip_prefix(PREFIX)?;
+        let time = NaiveDateTime::parse_from_str(stamp, STAMP).ok()?.and_utc();
+
+        Some(Snapshot {
+            stamp: stamp.to_string(),
+            time,
+            bytes: fs::metadata(path).ok()?.len(),
+            path: path.to_path_buf(),
+        })
+    }
+
+    /// The process list the snapshot holds, in either dump format, with its values read from the blobs of the dump
+    pub fn load(&self) -> Result<Runner, String> {
+        let bytes = fs::read(&self.path).map_err(|err| format!("Cannot read {}: {err}", self.path.display()))?;
+        let runner = dump::decode(&bytes).map_err(|err| format!("Cannot parse {}: {err}", self.path.display()))?;
+        Ok(dump::materialize(&dump::blobs::Store::beside(Path::new(&global!("opm.dump"))), runner))
+    }
+}
+
+pub fn dir() -> PathBuf { PathBuf::from(global!("opm.base")).join("snapshots") }
+
+/// Snapshots in `dir`, oldest first
+pub fn list(dir: &Path) -> Vec<Snapshot> {
+    let Ok(entries) = fs::read_dir(dir) else { return vec![] };
+
+    let mut snapshots: Vec<Snapshot> = entries.flatten().filter_map(|entry| Snapshot::read(&entry.path())).collect();
+    snapshots.sort_by_key(|snapshot| snapshot.time);
+    snapshots
+}
+
+/// Whether there is no snapshot yet or the newest is `interval` seconds old
+pub fn due(dir: &Path, interval: u64, now: DateTime<Utc>) -> bool {
+    list(dir).last().is_none_or(|newest| now - newest.time >= TimeDelta::seconds(interval as i64))
+}
+
+/// Copy `dump` into `dir` unless the newest snapshot holds the same bytes, then delete all but the
+/// `keep` newest. The new snapshot is always kept.
+pub fn take(dir: &Path, dump: &[u8], now: DateTime<Utc>, keep: usize) -> io::Result<Option<Snapshot>> {
+    if list(dir).last().is_some_and(|newest| fs::read(&newest.path).is_ok_and(|bytes| bytes == dump)) {
+        return Ok(None);
+    }
+
+    fs::create_dir_all(dir)?;
+    let path = dir.join(format!("{PREFIX}{}", now.format(STAMP)));
+    dump::replace(&path, dump)?;
+
+    for old in list(dir).iter().rev().skip(keep.max(1)) {
+        fs::remove_file(&old.path)?;
+    }
+
+    Ok(Snapshot::read(&path))
+}
+
+/// The snapshot with this timestamp, or the only one whose timestamp starts with it (`20261015T09`)
+pub fn find(dir: &Path, stamp: &str) -> Result<Snapshot, String> {
+    let stamp = stamp.strip_prefix(PREFIX).unwrap_or(stamp);
+    let matches: Vec<Snapshot> = list(dir).into_iter().filter(|snapshot| snapshot.stamp.starts_with(stamp)).collect();
+
+    match matches.as_slice() {
+        [] => Err(format!("No snapshot matches '{stamp}', see `opm snapshots list`")),
+        [snapshot] => Ok(snapshot.clone()),
+        many => match many.iter().find(|snapshot| snapshot.stamp == stamp) {
+            Some(snapshot) => Ok(snapshot.clone()),
+            None => Err(format!("'{stamp}' matches {} snapshots, give more of the timestamp", many.len())),
+        },
+    }
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Kind {
+    /// Only in the snapshot, added back stopped
+    Created,
+    /// Only in the current list, dropped from it
+    Removed,
+    /// In both with another definition, replaced by the snapshot's and stopped
+    Changed,
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Kind::Created => write!(f, "created"),
+            Kind::Removed => write!(f, "removed"),
+            Kind::Changed => write!(f, "changed"),
+        }
+    }
+}
+
+/// What restoring a snapshot does to one process
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Change {
+    pub kind: Kind,
+    pub id: usize,
+    pub name: String,
+    /// Dump fields that differ, for changed processes
+    pub fields: Vec<String>,
+    /// Whether the current process is marked running, restoring never stops one
+    pub running: bool,
+}
+
+/// How a process is defined, without what its running instance left in the dump
+fn definition(process: &Process) -> Map<String, Value> {
+    let Ok(Value::Object(mut fields)) = serde_json::to_value(process) else { return Map::new() };
+
+    for key in RUNTIME {
+        fields.remove(key);
+    }
+    if let Some(Value::Object(watch)) = fields.get_mut("watch") {
+        watch.remove("hash");
+    }
+
+    fields
+}
+
+/// What restoring `snapshot` over `current` changes, by process id. Ids of removed processes are
+/// handed out again, so an id that holds another name is the removal of one process and the creation of another.
+pub fn diff(current: &Runner, snapshot: &Runner) -> Vec<Change> {
+    let ids: BTreeSet<usize> = current.list.keys().chain(snapshot.list.keys()).copied().collect();
+    let change = |kind, id, process: &Process, fields| Change { kind, id, name: process.name.clone(), fields, running: process.running };
+    let created = |id, process: &Process| Change { running: false, ..change(Kind::Created, id, process, vec![]) };
+
+    ids.into_iter()
+        .flat_map(|id| match (current.list.get(&id), snapshot.list.get(&id)) {
+            (None, Some(then)) => vec![created(id, then)],
+            (Some(now), None) => vec![change(Kind::Removed, id, now, vec![])],
+            (Some(now), Some(then)) if now.name != then.name => vec![change(Kind::Removed, id, now, vec![]), created(id, then)],
+            (Some(now), Some(then)) => {
+                let (now_fields, then_fields) = (definition(now), definition(then));
+                let keys: BTreeSet<&String> = now_fields.keys().chain(then_fields.keys()).collect();
+                let fields: Vec<String> = keys.into_iter().filter(|key| now_fields.get(*key) != then_fields.get(*key)).cloned().collect();
+
+                match fields.is_empty() {
+                    true => vec![],
+                    false => vec![change(Kind::Changed, id, now, fields)],
+                }
+            }
+            (None, None) => vec![],
+        })
+        .collect()
+}
+
+/// Changes that would have to stop a running process, restore refuses them
+pub fn blocked(changes: &[Change]) -> Vec<&Change> { changes.iter().filter(|change| change.running && change.kind != Kind::Created).collect() }
+
+/// Apply `changes` from `snapshot` to `current`. Created and changed processes come back stopped,
+/// processes the snapshot does not change are left as they are.
+pub fn apply(current: &mut Runner, mut snapshot: Runner, changes: &[Change]) {
+    for change in changes {
+        if change.kind == Kind::Removed {
+            current.list.remove(&change.id);
+            continue;
+        }
+
+        let Some(mut process) = snapshot.list.remove(&change.id) else { continue };
+        process.pid = 0;
+        process.shell_pid = None;
+        process.running = false;
+        process.children.clear();
+        process.crash.crashed = false;
+        process.fingerprint = None;
+        process.exit_code = None;
+        process.pending = false;
+        current.list.insert(change.id, process);
+    }
+
+    // Ids handed out since the snapshot was taken are not given out again
+    let next = current.id.counter.load(Ordering::SeqCst).max(snapshot.id.counter.load(Ordering::SeqCst));
+    current.id = Id::new(next);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use macros_rs::ternary;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("opm-snapshots-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn at(stamp: &str) -> DateTime<Utc> { NaiveDateTime::parse_from_str(stamp, STAMP).unwrap().and_utc() }
+
+    fn process(id: usize, name: &str, script: &str, running: bool) -> Process {
+        Process {
+            id,
+            pid: ternary!(running, 4000 + id as i64, 0),
+            name: name.to_string(),
+            path: PathBuf::from("/srv"),
+            script: script.to_string(),
+            running,
+            started: Utc::now(),
+            ..Default::default()
+        }
+    }
+
+    fn runner(next: usize, processes: Vec<Process>) -> Runner {
+        Runner {
+            id: Id::new(next),
+            remote: None,
+            list: processes.into_iter().map(|process| (process.id, process)).collect(),
+            host: None,
+            backend: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_take_skips_unchanged_and_keeps_newest() {
+        let dir = temp_dir("take");
+
+        let first = take(&dir, b"(one)", at("20261014T090000Z"), 2).unwrap().unwrap();
+        assert_eq!(first.stamp, "20261014T090000Z");
+        assert_eq!(take(&dir, b"(one)", at("20261015T090000Z"), 2).unwrap(), None);
+
+        take(&dir, b"(two)", at("20261015T090000Z"), 2).unwrap();
+        take(&dir, b"(three)", at("20261016T090000Z"), 2).unwrap();
+
+        let stamps: Vec<String> = list(&dir).into_iter().map(|snapshot| snapshot.stamp).collect();
+        assert_eq!(stamps, vec!["20261015T090000Z", "20261016T090000Z"]);
+        assert_eq!(fs::read(dir.join("dump-20261016T090000Z")).unwrap(), b"(three)");
+        // No temporary files are left next to the snapshots
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+
+        assert!(!due(&dir, 86400, at("20261016T230000Z")));
+        assert!(due(&dir, 86400, at("20261017T090000Z")));
+        assert!(due(&temp_dir("none"), 86400, at("20261017T090000Z")));
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_find_by_prefix() {
+        let dir = temp_dir("find");
+        fs::create_dir_all(dir.join("api")).unwrap();
+        for stamp in ["20261015T090000Z", "20261015T210000Z", "20261016T090000Z"] {
+            take(&dir, stamp.as_bytes(), at(stamp), 10).unwrap();
+        }
+
+        assert_eq!(find(&dir, "20261016").unwrap().stamp, "20261016T090000Z");
+        assert_eq!(find(&dir, "dump-20261015T21").unwrap().stamp, "20261015T210000Z");
+        assert_eq!(find(&dir, "20261015").unwrap_err(), "'20261015' matches 2 snapshots, give more of the timestamp");
+        assert!(find(&dir, "2025").is_err());
+        // Directories of `opm details --snapshot` are not dump snapshots
+        assert_eq!(list(&dir).len(), 3);
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_diff_ignores_runtime_fields() {
+        let mut restarted = process(0, "api", "node api.js", true);
+        restarted.restarts = 4;
+        restarted.pid = 9999;
+        restarted.watch.hash = String::from("abc");
+
+        let current = runner(5, vec![restarted, process(1, "worker", "node worker.js --fast", false), process(3, "cron", "node cron.js", true), process(4, "queue", "node queue.js", false)]);
+        let snapshot = runner(5, vec![process(0, "api", "node api.js", false), process(1, "worker", "node worker.js", true), process(2, "mailer", "node mail.js", true), process(4, "billing", "node billing.js", false)]);
+
+        let changes = diff(&current, &snapshot);
+        let summary: Vec<(Kind, usize, &str)> = changes.iter().map(|change| (change.kind, change.id, change.name.as_str())).collect();
+        assert_eq!(
+            summary,
+            vec![(Kind::Changed, 1, "worker"), (Kind::Created, 2, "mailer"), (Kind::Removed, 3, "cron"), (Kind::Removed, 4, "queue"), (Kind::Created, 4, "billing")]
+        );
+        assert_eq!(changes[0].fields, vec!["script"]);
+
+        // Only the running cron would have to be stopped
+        let blocked: Vec<usize> = blocked(&changes).iter().map(|change| change.id).collect();
+        assert_eq!(blocked, vec![3]);
+    }
+
+    #[test]
+    fn test_apply_restores_stopped() {
+        let mut current = runner(5, vec![process(0, "api", "node api.js", true), process(1, "worker", "node worker.js --fast", false), process(3, "cron", "node cron.js", false)]);
+        let snapshot = runner(3, vec![process(0, "api", "node api.js", false), process(1, "worker", "node worker.js", false), process(2, "mailer", "node mail.js", true)]);
+
+        let changes = diff(&current, &snapshot);
+        apply(&mut current, snapshot, &changes);
+
+        assert_eq!(current.list.keys().copied().collect::<Vec<_>>(), vec![0, 1, 2]);
+        // The unchanged api keeps running, the rest come back stopped
+        assert!(current.list[&0].running && current.list[&0].pid == 4000);
+        assert_eq!(current.list[&1].script, "node worker.js");
+        assert!(!current.list[&2].running && current.list[&2].pid == 0);
+        assert_eq!(current.id.next(), 5);
+    }
+}
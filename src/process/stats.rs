@@ -0,0 +1,590 @@
+use super::Process;
+
+use chrono::{DateTime, Utc};
+use flate2::read::GzDecoder;
+use global_placeholders::global;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Write},
+    path::Path,
+    str::FromStr,
+};
+
+/// Bucket for processes without the label, or without a name prefix
+pub const NONE: &str = "(none)";
+
+/// The sample file moves to `metrics.jsonl.<time>` once it grows past this size, housekeeping prunes the archives
+pub const MAX_BYTES: u64 = 10 * 1024 * 1024;
+
+/// How processes are bucketed in a summary
+#[derive(Debug, Clone, PartialEq)]
+pub enum By {
+    /// Group of the process, see `Process::group`
+    Group,
+    Label(String),
+    /// Part of the name before the first `-`, `_` or `.`
+    NamePrefix,
+}
+
+impl FromStr for By {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "group" => Ok(By::Group),
+            "name-prefix" => Ok(By::NamePrefix),
+            value => match value.strip_prefix("label:") {
+                Some(key) if !key.is_empty() => Ok(By::Label(key.to_string())),
+                _ => Err(format!("Invalid grouping '{value}', expected group, label:<key> or name-prefix")),
+            },
+        }
+    }
+}
+
+impl fmt::Display for By {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            By::Group => write!(f, "group"),
+            By::Label(key) => write!(f, "label:{key}"),
+            By::NamePrefix => write!(f, "name-prefix"),
+        }
+    }
+}
+
+/// Resource usage sampled for one process
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Sample {
+    pub running: bool,
+    pub cpu: f64,
+    /// Resident memory of the process and its children in bytes
+    pub memory: u64,
+    /// Open file descriptors of the process and its children, None when they could not be read
+    pub fds: Option<u64>,
+    /// Threads of the process and its children, None when they could not be read
+    pub threads: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, Default, PartialEq)]
+pub struct Bucket {
+    #[schema(example = "payments")]
+    pub name: String,
+    #[schema(example = 12)]
+    pub processes: usize,
+    #[schema(example = 11)]
+    pub running: usize,
+    /// Sum of the cpu percentages, may exceed 100 on several cores
+    #[schema(example = 37.5)]
+    pub cpu: f64,
+    /// Resident memory in bytes, children included
+    #[schema(example = 1073741824)]
+    pub memory: u64,
+    #[schema(example = 4)]
+    pub restarts: u64,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone)]
+pub struct Summary {
+    #[schema(example = "label:team")]
+    pub by: String,
+    /// Largest memory users first
+    pub buckets: Vec<Bucket>,
+    pub total: Bucket,
+}
+
+impl Bucket {
+    fn add(&mut self, process: &Process, sample: &Sample) {
+        self.processes += 1;
+        self.running += usize::from(sample.running);
+        self.cpu += sample.cpu;
+        self.memory += sample.memory;
+        self.restarts += process.restarts;
+    }
+}
+
+fn bucket_name(process: &Process, by: &By) -> String {
+    let name = match by {
+        By::Group => process.group(),
+        By::Label(key) => process.labels.get(key).map(String::as_str),
+        By::NamePrefix => process.name.split(['-', '_', '.']).next(),
+    };
+
+    match name {
+        Some(name) if !name.is_empty() => name.to_string(),
+        _ => NONE.to_string(),
+    }
+}
+
+pub fn summarize<'p>(samples: impl IntoIterator<Item = (&'p Process, Sample)>, by: &By) -> Summary {
+    let mut buckets: BTreeMap<String, Bucket> = BTreeMap::new();
+    let mut total = Bucket { name: String::from("total"), ..Bucket::default() };
+
+    for (process, sample) in samples {
+        let name = bucket_name(process, by);
+        let bucket = buckets.entry(name.clone()).or_insert_with(|| Bucket { name, ..Bucket::default() });
+
+        bucket.add(process, &sample);
+        total.add(process, &sample);
+    }
+
+    let mut buckets: Vec<Bucket> = buckets.into_values().collect();
+    buckets.sort_by(|a, b| b.memory.cmp(&a.memory).then_with(|| a.name.cmp(&b.name)));
+
+    Summary { by: by.to_string(), buckets, total }
+}
+
+/// One process as the daemon sampled it, stored as a line of `~/.opm/metrics.jsonl`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Record {
+    pub time: DateTime<Utc>,
+    pub id: usize,
+    pub name: String,
+    /// Group of the process, see `Process::group`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+    pub running: bool,
+    pub cpu: f64,
+    pub memory: u64,
+    /// Restart counter of the process at that time
+    pub restarts: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fds: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub threads: Option<u64>,
+}
+
+impl Record {
+    pub fn new(time: DateTime<Utc>, process: &Process, sample: &Sample) -> Self {
+        Record {
+            time,
+            id: process.id,
+            name: process.name.clone(),
+            group: process.group().map(String::from),
+            running: sample.running,
+            cpu: sample.cpu,
+            memory: sample.memory,
+            restarts: process.restarts,
+            fds: sample.fds,
+            threads: sample.threads,
+        }
+    }
+}
+
+/// What the rows of a report are per hour
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Rollup {
+    Process,
+    /// Group of the process, summed over its processes at each sample
+    Group,
+}
+
+impl FromStr for Rollup {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "process" => Ok(Rollup::Process),
+            "group" => Ok(Rollup::Group),
+            value => Err(format!("Invalid rollup '{value}', expected process or group")),
+        }
+    }
+}
+
+/// Usage of one process or group over one hour
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Row {
+    /// Start of the hour
+    pub hour: DateTime<Utc>,
+    /// Process name, or group with `Rollup::Group`
+    pub name: String,
+    pub samples: usize,
+    pub cpu_avg: f64,
+    pub cpu_max: f64,
+    pub rss_avg: u64,
+    pub rss_max: u64,
+    /// Restarts counted since the previous sample of each process
+    pub restarts: u64,
+    /// Share of the samples the process was running, from 0 to 1
+    pub uptime_ratio: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Report {
+    pub rows: Vec<Row>,
+    /// First and last sample inside the requested range, none without any
+    pub covered: Option<(DateTime<Utc>, DateTime<Utc>)>,
+}
+
+impl Row {
+    /// Columns of the csv export, in field order
+    pub const COLUMNS: [&str; 9] = ["hour", "name", "samples", "cpu_avg", "cpu_max", "rss_avg", "rss_max", "restarts", "uptime_ratio"];
+
+    pub fn fields(&self) -> Vec<String> {
+        vec![
+            self.hour.to_rfc3339(),
+            self.name.clone(),
+            self.samples.to_string(),
+            format!("{:.2}", self.cpu_avg),
+            format!("{:.2}", self.cpu_max),
+            self.rss_avg.to_string(),
+            self.rss_max.to_string(),
+            self.restarts.to_string(),
+            format!("{:.4}", self.uptime_ratio),
+        ]
+    }
+}
+
+/// Sum of the records of one sample time that land in the same row
+#[derive(Default)]
+struct Point {
+    cpu: f64,
+    memory: u64,
+    restarts: u64,
+    running: usize,
+    processes: usize,
+}
+
+#[derive(Default)]
+struct Hour {
+    samples: usize,
+    cpu: f64,
+    cpu_max: f64,
+    memory: u64,
+    memory_max: u64,
+    restarts: u64,
+    running: usize,
+    processes: usize,
+}
+
+fn start_of_hour(time: DateTime<Utc>) -> DateTime<Utc> {
+    let seconds = time.timestamp();
+    DateTime::from_timestamp(seconds - seconds.rem_euclid(3600), 0).unwrap_or(time)
+}
+
+fn round(value: f64, digits: i32) -> f64 {
+    let scale = 10f64.powi(digits);
+    (value * scale).round() / scale
+}
+
+/// Hourly usage of the records between `from` and `to`, earlier records only count towards restarts
+pub fn report(mut records: Vec<Record>, from: DateTime<Utc>, to: DateTime<Utc>, rollup: Rollup) -> Report {
+    records.sort_by_key(|record| record.time);
+
+    let mut last_restarts: HashMap<usize, u64> = HashMap::new();
+    let mut points: BTreeMap<(DateTime<Utc>, String), Point> = BTreeMap::new();
+    let mut covered: Option<(DateTime<Utc>, DateTime<Utc>)> = None;
+
+    for record in records {
+        // A counter that went down was reset by removing and adding the process again
+        let restarts = last_restarts.insert(record.id, record.restarts).map_or(0, |last| record.restarts.saturating_sub(last));
+
+        if record.time < from || record.time >= to {
+            continue;
+        }
+
+        covered = Some(covered.map_or((record.time, record.time), |(first, _)| (first, record.time)));
+
+        let key = match rollup {
+            Rollup::Process => record.name,
+            Rollup::Group => record.group.filter(|group| !group.is_empty()).unwrap_or_else(|| NONE.to_string()),
+        };
+
+        let point = points.entry((record.time, key)).or_default();
+        point.cpu += record.cpu;
+        point.memory += record.memory;
+        point.restarts += restarts;
+        point.running += usize::from(record.running);
+        point.processes += 1;
+    }
+
+    let mut hours: BTreeMap<(DateTime<Utc>, String), Hour> = BTreeMap::new();
+
+    for ((time, key), point) in points {
+        let hour = hours.entry((start_of_hour(time), key)).or_default();
+        hour.samples += 1;
+        hour.cpu += point.cpu;
+        hour.cpu_max = hour.cpu_max.max(point.cpu);
+        hour.memory += point.memory;
+        hour.memory_max = hour.memory_max.max(point.memory);
+        hour.restarts += point.restarts;
+        hour.running += point.running;
+        hour.processes += point.processes;
+    }
+
+    let rows = hours
+        .into_iter()
+        .map(|((hour, name), totals)| Row {
+            hour,
+            name,
+            samples: totals.samples,
+            cpu_avg: round(totals.cpu / totals.samples as f64, 2),
+            cpu_max: round(totals.cpu_max, 2),
+            rss_avg: totals.memory / totals.samples as u64,
+            rss_max: totals.memory_max,
+            restarts: totals.restarts,
+            uptime_ratio: round(totals.running as f64 / totals.processes as f64, 4),
+        })
+        .collect();
+
+    Report { rows, covered }
+}
+
+/// Add one round of samples to the sample file, archiving it once it is large
+pub fn append(path: &str, records: &[Record]) -> io::Result<()> {
+    let mut lines = String::new();
+    for record in records {
+        lines.push_str(&serde_json::to_string(record)?);
+        lines.push('\n');
+    }
+
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    file.write_all(lines.as_bytes())?;
+
+    if file.metadata()?.len() > MAX_BYTES {
+        fs::rename(path, format!("{path}.{}", Utc::now().format("%Y%m%dT%H%M%S")))?;
+    }
+
+    Ok(())
+}
+
+/// Write samples to the daemon's sample file. Failing to write them never fails the daemon.
+pub fn record(records: &[Record]) {
+    if let Err(err) = append(&global!("opm.metrics"), records) {
+        log::warn!("Cannot write process samples: {err}");
+    }
+}
+
+/// Records of the sample file at `path` and its archives, gzipped ones included
+///
+/// Archives last written before `since` cannot hold anything newer and are not read.
+pub fn read(path: &Path, since: DateTime<Utc>) -> Vec<Record> {
+    let (Some(dir), Some(name)) = (path.parent(), path.file_name().and_then(|name| name.to_str())) else {
+        return vec![];
+    };
+
+    let prefix = format!("{name}.");
+    let Ok(entries) = fs::read_dir(dir) else { return vec![] };
+    let mut records = vec![];
+
+    for entry in entries.flatten() {
+        let file = entry.file_name().to_string_lossy().into_owned();
+        if file != name && !file.starts_with(&prefix) {
+            continue;
+        }
+
+        let modified = entry.metadata().and_then(|meta| meta.modified()).map(DateTime::<Utc>::from);
+        if file != name && modified.is_ok_and(|modified| modified < since) {
+            continue;
+        }
+
+        let mut contents = String::new();
+        let read = File::open(entry.path()).and_then(|handle| {
+            let mut reader: Box<dyn Read> = match file.ends_with(".gz") {
+                true => Box::new(GzDecoder::new(handle)),
+                false => Box::new(handle),
+            };
+            reader.read_to_string(&mut contents)
+        });
+
+        if read.is_ok() {
+            records.extend(contents.lines().filter_map(|line| serde_json::from_str::<Record>(line).ok()));
+        }
+    }
+
+    records
+}
+
+/// Records of the daemon's sample file
+pub fn history(since: DateTime<Utc>) -> Vec<Record> { read(Path::new(&global!("opm.metrics")), since) }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use macros_rs::string;
+    use std::path::PathBuf;
+
+    fn process(name: &str, team: Option<&str>, restarts: u64) -> Process {
+        Process {
+            name: string!(name),
+            path: PathBuf::from("/tmp"),
+            script: string!("sleep 1"),
+            restarts,
+            running: true,
+            started: Utc::now(),
+            labels: team.map(|team| (string!("team"), string!(team))).into_iter().collect(),
+            ..Default::default()
+        }
+    }
+
+    fn sample(memory: u64, cpu: f64) -> Sample {
+        Sample { running: memory > 0, cpu, memory, ..Sample::default() }
+    }
+
+    #[test]
+    fn test_parse_by() {
+        assert_eq!("label:team".parse(), Ok(By::Label(string!("team"))));
+        assert_eq!("group".parse(), Ok(By::Group));
+        assert_eq!("name-prefix".parse(), Ok(By::NamePrefix));
+        assert!("label:".parse::<By>().is_err());
+        assert!("team".parse::<By>().is_err());
+    }
+
+    #[test]
+    fn test_summarize_by_label() {
+        let processes = [
+            process("payments-api", Some("payments"), 1),
+            process("payments-worker", Some("payments"), 2),
+            process("search", Some("search"), 0),
+            process("cron", None, 5),
+        ];
+        let samples = [sample(300, 1.5), sample(200, 2.0), sample(1000, 0.5), sample(0, 0.0)];
+
+        let summary = summarize(processes.iter().zip(samples), &By::Label(string!("team")));
+        let names: Vec<&str> = summary.buckets.iter().map(|bucket| bucket.name.as_str()).collect();
+
+        assert_eq!(summary.by, "label:team");
+        assert_eq!(names, vec!["search", "payments", NONE]);
+        assert_eq!(
+            summary.buckets[1],
+            Bucket { name: string!("payments"), processes: 2, running: 2, cpu: 3.5, memory: 500, restarts: 3 }
+        );
+        assert_eq!((summary.total.processes, summary.total.running), (4, 3));
+        assert_eq!((summary.total.memory, summary.total.restarts), (1500, 8));
+    }
+
+    #[test]
+    fn test_summarize_by_name_prefix() {
+        let processes = [process("api-1", None, 0), process("api_2", None, 0), process("worker", None, 0), process("-x", None, 0)];
+        let summary = summarize(processes.iter().map(|process| (process, sample(1, 0.0))), &By::NamePrefix);
+        let names: Vec<(&str, usize)> = summary.buckets.iter().map(|bucket| (bucket.name.as_str(), bucket.processes)).collect();
+
+        assert_eq!(names, vec![("api", 2), (NONE, 1), ("worker", 1)]);
+    }
+
+    #[test]
+    fn test_summarize_by_group() {
+        let mut worker = process("api-worker-1", None, 0);
+        worker.group = Some(string!("api"));
+        // Labeled before groups existed
+        let mut labeled = process("api-legacy", None, 0);
+        labeled.labels.insert(string!("group"), string!("api"));
+
+        let processes = [worker, labeled, process("cron", None, 0)];
+        let summary = summarize(processes.iter().map(|process| (process, sample(1, 0.0))), &By::Group);
+        let names: Vec<(&str, usize)> = summary.buckets.iter().map(|bucket| (bucket.name.as_str(), bucket.processes)).collect();
+
+        assert_eq!(summary.by, "group");
+        assert_eq!(names, vec![("api", 2), (NONE, 1)]);
+    }
+
+    fn time(value: &str) -> DateTime<Utc> { DateTime::parse_from_rfc3339(value).unwrap().with_timezone(&Utc) }
+
+    fn record(at: &str, id: usize, group: Option<&str>, running: bool, cpu: f64, memory: u64, restarts: u64) -> Record {
+        Record {
+            time: time(at),
+            id,
+            name: format!("app-{id}"),
+            group: group.map(String::from),
+            running,
+            cpu,
+            memory,
+            restarts,
+            fds: None,
+            threads: None,
+        }
+    }
+
+    #[test]
+    fn test_report_per_process() {
+        let records = vec![
+            // Before the range, only the restart counter is used
+            record("2024-01-01T09:59:00Z", 0, None, true, 90.0, 900, 2),
+            record("2024-01-01T10:00:00Z", 0, None, true, 10.0, 100, 2),
+            record("2024-01-01T10:30:00Z", 0, None, false, 0.0, 0, 3),
+            record("2024-01-01T10:45:00Z", 0, None, true, 20.0, 300, 5),
+            record("2024-01-01T11:15:00Z", 0, None, true, 5.0, 200, 5),
+        ];
+
+        let report = report(records, time("2024-01-01T10:00:00Z"), time("2024-01-02T00:00:00Z"), Rollup::Process);
+
+        assert_eq!(report.covered, Some((time("2024-01-01T10:00:00Z"), time("2024-01-01T11:15:00Z"))));
+        assert_eq!(
+            report.rows[0],
+            Row {
+                hour: time("2024-01-01T10:00:00Z"),
+                name: string!("app-0"),
+                samples: 3,
+                cpu_avg: 10.0,
+                cpu_max: 20.0,
+                rss_avg: 133,
+                rss_max: 300,
+                restarts: 3,
+                uptime_ratio: 0.6667,
+            }
+        );
+        assert_eq!((report.rows[1].hour, report.rows[1].restarts, report.rows[1].uptime_ratio), (time("2024-01-01T11:00:00Z"), 0, 1.0));
+        assert_eq!(report.rows[1].fields()[3], "5.00");
+    }
+
+    #[test]
+    fn test_report_by_group() {
+        let records = vec![
+            record("2024-01-01T10:00:00Z", 0, Some("web"), true, 10.0, 100, 0),
+            record("2024-01-01T10:00:00Z", 1, Some("web"), true, 30.0, 300, 0),
+            record("2024-01-01T10:00:00Z", 2, None, true, 1.0, 10, 0),
+            record("2024-01-01T10:01:00Z", 0, Some("web"), true, 10.0, 100, 0),
+            record("2024-01-01T10:01:00Z", 1, Some("web"), false, 0.0, 0, 1),
+        ];
+
+        let report = report(records, time("2024-01-01T00:00:00Z"), time("2024-01-02T00:00:00Z"), Rollup::Group);
+        let names: Vec<&str> = report.rows.iter().map(|row| row.name.as_str()).collect();
+        assert_eq!(names, vec![NONE, "web"]);
+
+        // Processes of a group add up per sample, the hour averages those sums
+        let web = &report.rows[1];
+        assert_eq!((web.samples, web.cpu_avg, web.cpu_max), (2, 25.0, 40.0));
+        assert_eq!((web.rss_avg, web.rss_max, web.restarts, web.uptime_ratio), (250, 400, 1, 0.75));
+
+        assert_eq!("group".parse(), Ok(Rollup::Group));
+        assert!("team".parse::<Rollup>().is_err());
+    }
+
+    #[test]
+    fn test_report_without_samples_in_range() {
+        let records = vec![record("2024-01-01T10:00:00Z", 0, None, true, 1.0, 1, 0)];
+        let report = report(records, time("2024-02-01T00:00:00Z"), time("2024-02-02T00:00:00Z"), Rollup::Process);
+        assert_eq!(report, Report { rows: vec![], covered: None });
+    }
+
+    #[test]
+    fn test_read_current_and_archived_samples() {
+        let dir = std::env::temp_dir().join(format!("opm-metrics-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("metrics.jsonl");
+
+        append(path.to_str().unwrap(), &[record("2024-01-01T10:00:00Z", 0, None, true, 1.0, 1, 0)]).unwrap();
+        fs::rename(&path, dir.join("metrics.jsonl.20240101T100000")).unwrap();
+
+        let line = format!("{}\n", serde_json::to_string(&record("2024-01-01T09:00:00Z", 1, None, true, 1.0, 1, 0)).unwrap());
+        let mut encoder = flate2::write::GzEncoder::new(File::create(dir.join("metrics.jsonl.20240101T090000.gz")).unwrap(), flate2::Compression::default());
+        encoder.write_all(line.as_bytes()).unwrap();
+        encoder.finish().unwrap();
+
+        append(path.to_str().unwrap(), &[record("2024-01-01T11:00:00Z", 2, None, true, 1.0, 1, 0)]).unwrap();
+        fs::write(dir.join("other.jsonl"), &line).unwrap();
+
+        let mut ids: Vec<usize> = read(&path, time("2024-01-01T00:00:00Z")).into_iter().map(|record| record.id).collect();
+        ids.sort();
+        assert_eq!(ids, vec![0, 1, 2]);
+
+        // Archives written before the start of a report are skipped
+        assert_eq!(read(&path, Utc::now() + chrono::TimeDelta::hours(1)).len(), 1);
+
+        fs::remove_dir_all(&dir).ok();
+    }
+}
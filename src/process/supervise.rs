@@ -0,0 +1,349 @@
+use super::{Process, ProcessRunResult, is_pid_alive, rotate, unix};
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use global_placeholders::global;
+use serde::{Deserialize, Serialize};
+
+use nix::unistd::{ForkResult, fork, setsid};
+
+use std::{
+    env,
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, BufReader, Read, Write},
+    os::unix::process::ExitStatusExt,
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+    sync::atomic::{AtomicI32, AtomicUsize, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Hidden subcommand opm is re-executed with to supervise a command
+pub const SUBCOMMAND: &str = "__supervise";
+
+/// Logs move to `.1` once they grow past this size
+pub const MAX_LOG_BYTES: u64 = 50 * 1024 * 1024;
+
+/// Rotated logs kept per stream, `.1` being the newest
+const KEEP_ROTATED: usize = 5;
+
+/// Time a spawn waits for the supervisor to report the command it started
+const REPORT_TIMEOUT: Duration = Duration::from_secs(5);
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Time the output left in the pipes gets to reach the logs once the command exited
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Pid of the supervised command, for the signal handlers
+static CHILD: AtomicI32 = AtomicI32::new(0);
+/// Bumped by SIGHUP, writers reopen their log when it changed
+static REOPEN: AtomicUsize = AtomicUsize::new(0);
+
+/// What the supervisor of a process last recorded, `~/.opm/supervised/<name>.json`
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct State {
+    pub supervisor: i64,
+    /// Pid of the shell the command runs in
+    pub pid: i64,
+    pub started: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exited: Option<DateTime<Utc>>,
+    /// Exit code of the command, 128 + the signal when it was killed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i32>,
+}
+
+impl State {
+    pub fn path(name: &str) -> PathBuf { PathBuf::from(global!("opm.base")).join("supervised").join(format!("{}.json", name.replace(['/', ' '], "_"))) }
+
+    pub fn read(path: &Path) -> Option<State> { serde_json::from_slice(&fs::read(path).ok()?).ok() }
+
+    /// Replace the state file in one step, a reader never sees half of it
+    fn write(&self, path: &Path) -> io::Result<()> {
+        let temp = path.with_extension(format!("json.{}", self.supervisor));
+        fs::write(&temp, serde_json::to_vec(self)?)?;
+        fs::rename(&temp, path)
+    }
+
+    /// The command is running as long as its supervisor did not record an exit and is still there itself
+    pub fn alive(&self) -> bool { self.exited.is_none() && is_pid_alive(self.supervisor) }
+}
+
+impl Process {
+    /// State of the supervisor that started the current instance, None for unsupervised
+    /// processes and when the state file belongs to another instance with the same name
+    pub fn supervisor(&self) -> Option<State> {
+        if !self.supervised {
+            return None;
+        }
+
+        State::read(&State::path(&self.name)).filter(|state| state.pid == self.shell_pid.unwrap_or(self.pid))
+    }
+}
+
+/// Program the supervisor is run from, `OPM_SUPERVISOR` points tests at the built binary
+fn program() -> io::Result<PathBuf> {
+    match env::var_os("OPM_SUPERVISOR") {
+        Some(program) => Ok(PathBuf::from(program)),
+        None => env::current_exe(),
+    }
+}
+
+/// The supervisor invocation running `command` with its output going to the given logs
+pub fn command(state: &Path, out: &str, error: &str, command: &[&str]) -> Result<Command, String> {
+    let program = program().map_err(|err| format!("Cannot find the opm binary to supervise with: {err}"))?;
+
+    if let Some(dir) = state.parent() {
+        fs::create_dir_all(dir).map_err(|err| format!("Cannot create {}: {err}", dir.display()))?;
+    }
+
+    // A state file left by an earlier instance would be taken for the new one
+    let _ = fs::remove_file(state);
+
+    let mut cmd = Command::new(program);
+    cmd.arg(SUBCOMMAND)
+        .arg("--state")
+        .arg(state)
+        .arg("--out")
+        .arg(out)
+        .arg("--error")
+        .arg(error)
+        .arg("--")
+        .args(command)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+
+    Ok(cmd)
+}
+
+/// Wait for the launcher to detach and the supervisor to report the command it started
+pub fn report(child: &mut Child, state: &Path, launched: DateTime<Utc>) -> Result<ProcessRunResult, String> {
+    match child.wait() {
+        Ok(status) if status.success() => {}
+        Ok(status) => return Err(format!("Supervisor failed to start ({status})")),
+        Err(err) => return Err(format!("Supervisor failed to start: {err}")),
+    }
+
+    let deadline = Instant::now() + REPORT_TIMEOUT;
+    let state = loop {
+        match State::read(state) {
+            Some(state) if state.started >= launched => break state,
+            _ if Instant::now() >= deadline => {
+                return Err(format!("Supervisor did not report the command within {}s", REPORT_TIMEOUT.as_secs()));
+            }
+            _ => thread::sleep(POLL_INTERVAL),
+        }
+    };
+
+    if let Some(code) = state.exit_code {
+        return Ok(ProcessRunResult { pid: state.pid, shell_pid: None, exit_code: Some(code) });
+    }
+
+    let actual_pid = unix::get_actual_child_pid(state.pid);
+    Ok(ProcessRunResult {
+        pid: actual_pid,
+        shell_pid: (state.pid != actual_pid).then_some(state.pid),
+        exit_code: None,
+    })
+}
+
+/// A log the supervisor writes to, every line prefixed with the time it was read
+struct Log {
+    path: PathBuf,
+    file: File,
+    generation: usize,
+}
+
+impl Log {
+    fn open(path: &Path) -> io::Result<Log> {
+        Ok(Log {
+            path: path.to_path_buf(),
+            file: OpenOptions::new().create(true).append(true).open(path)?,
+            generation: REOPEN.load(Ordering::Acquire),
+        })
+    }
+
+    fn write(&mut self, line: &[u8]) -> io::Result<()> {
+        // Moved away by logrotate and signalled with SIGHUP, or grown past the limit
+        if REOPEN.load(Ordering::Acquire) != self.generation {
+            *self = Log::open(&self.path)?;
+        } else if self.file.metadata()?.len() >= MAX_LOG_BYTES {
+            let _ = rotate::archive(&self.path, KEEP_ROTATED);
+            *self = Log::open(&self.path)?;
+        }
+
+        let stamp = Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true);
+        let mut entry = Vec::with_capacity(stamp.len() + line.len() + 2);
+        entry.extend_from_slice(stamp.as_bytes());
+        entry.push(b' ');
+        entry.extend_from_slice(line);
+        entry.push(b'\n');
+
+        self.file.write_all(&entry)
+    }
+}
+
+/// Time the supervisor stamped a log line with and the line as the command wrote it
+pub fn split_stamp(line: &str) -> Option<(DateTime<Utc>, &str)> {
+    let (stamp, rest) = line.split_once(' ')?;
+    let time = DateTime::parse_from_rfc3339(stamp).ok()?;
+    Some((time.with_timezone(&Utc), rest))
+}
+
+/// Copy the lines of a pipe into the log until the command closes it
+fn pipe(source: impl Read, mut log: Log) {
+    let mut reader = BufReader::new(source);
+    let mut line = vec![];
+
+    loop {
+        line.clear();
+        match reader.read_until(b'\n', &mut line) {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {
+                let text = line.strip_suffix(b"\n").unwrap_or(&line);
+                if log.write(text.strip_suffix(b"\r").unwrap_or(text)).is_err() {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+extern "C" fn forward_signal(signal: libc::c_int) {
+    let child = CHILD.load(Ordering::Acquire);
+    if child > 0 {
+        unsafe { libc::kill(child, signal) };
+    }
+}
+
+extern "C" fn reopen_logs(_: libc::c_int) { REOPEN.fetch_add(1, Ordering::AcqRel); }
+
+/// `opm __supervise`: detach from the caller, run the command with its output piped into
+/// timestamped logs and record in `state` how it ended. Returns the exit code for the caller.
+pub fn run(state_path: &Path, out: &Path, error: &Path, command: &[String]) -> i32 {
+    // The launcher exits right away, the spawner is neither kept waiting nor left with a zombie
+    match unsafe { fork() } {
+        Ok(ForkResult::Parent { .. }) => return 0,
+        Ok(ForkResult::Child) => {}
+        Err(_) => return 1,
+    }
+
+    // A session of its own, so signals meant for the daemon or a terminal never reach it
+    let _ = setsid();
+
+    let (Ok(out), Ok(mut error)) = (Log::open(out), Log::open(error)) else { return 1 };
+    let Some((program, args)) = command.split_first() else { return 1 };
+    let supervisor = std::process::id() as i64;
+
+    let mut child = match Command::new(program).args(args).stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::piped()).spawn() {
+        Ok(child) => child,
+        Err(err) => {
+            let _ = error.write(format!("[opm] cannot run {program}: {err}").as_bytes());
+            let now = Utc::now();
+            let state = State { supervisor, pid: supervisor, started: now, exited: Some(now), exit_code: Some(127) };
+            let _ = state.write(state_path);
+            return 127;
+        }
+    };
+
+    CHILD.store(child.id() as i32, Ordering::Release);
+    unsafe {
+        libc::signal(libc::SIGTERM, forward_signal as *const () as usize);
+        libc::signal(libc::SIGINT, forward_signal as *const () as usize);
+        libc::signal(libc::SIGHUP, reopen_logs as *const () as usize);
+    }
+
+    let mut state = State { supervisor, pid: child.id() as i64, started: Utc::now(), exited: None, exit_code: None };
+    let _ = state.write(state_path);
+
+    let readers = [
+        child.stdout.take().map(|stdout| thread::spawn(move || pipe(stdout, out))),
+        child.stderr.take().map(|stderr| thread::spawn(move || pipe(stderr, error))),
+    ];
+
+    let code = match child.wait() {
+        Ok(status) => status.code().unwrap_or_else(|| 128 + status.signal().unwrap_or(0)),
+        Err(_) => 1,
+    };
+
+    // Background children of the command may keep the pipes open, their output is not waited for
+    let deadline = Instant::now() + DRAIN_TIMEOUT;
+    while readers.iter().flatten().any(|reader| !reader.is_finished()) && Instant::now() < deadline {
+        thread::sleep(POLL_INTERVAL);
+    }
+
+    // A restart may already have handed the state file to the next supervisor
+    if State::read(state_path).is_some_and(|current| current.supervisor == supervisor) {
+        state.exited = Some(Utc::now());
+        state.exit_code = Some(code);
+        let _ = state.write(state_path);
+    }
+
+    code
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("opm-supervise-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_log_rotation() {
+        let dir = temp("rotate");
+        let path = dir.join("api-out.log");
+
+        fs::write(&path, vec![b'x'; MAX_LOG_BYTES as usize]).unwrap();
+        fs::write(dir.join("api-out.log.1"), "older").unwrap();
+
+        let mut log = Log::open(&path).unwrap();
+        log.write(b"after rotation").unwrap();
+
+        assert_eq!(fs::read_to_string(dir.join("api-out.log.2")).unwrap(), "older");
+        assert_eq!(fs::metadata(dir.join("api-out.log.1")).unwrap().len(), MAX_LOG_BYTES);
+
+        let line = fs::read_to_string(&path).unwrap();
+        assert!(line.ends_with(" after rotation\n"), "{line}");
+        assert!(crate::process::archive::line_time(&line).is_some(), "lines are stamped for --since/--until");
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_long_lines_are_not_split() {
+        let dir = temp("long");
+        let path = dir.join("api-out.log");
+        let long = "x".repeat(1024 * 1024);
+
+        pipe(format!("{long}\nshort\r\n\n").as_bytes(), Log::open(&path).unwrap());
+
+        let contents = fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = contents.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(split_stamp(lines[0]).unwrap().1, long);
+        assert_eq!(split_stamp(lines[1]).unwrap().1, "short");
+        assert_eq!(split_stamp(lines[2]).unwrap().1, "");
+        assert!(split_stamp("plain line").is_none());
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_state_alive() {
+        let state = State { supervisor: std::process::id() as i64, pid: 1, started: Utc::now(), exited: None, exit_code: None };
+        assert!(state.alive());
+        assert!(!State { exited: Some(Utc::now()), exit_code: Some(0), ..state.clone() }.alive());
+
+        let dir = temp("state");
+        let path = dir.join("api.json");
+        state.write(&path).unwrap();
+        assert_eq!(State::read(&path), Some(state));
+
+        fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,117 @@
+use std::borrow::Cow;
+
+/// Process identity placeholders in env values are expanded against
+pub struct Context<'c> {
+    pub id: usize,
+    pub name: &'c str,
+    /// `PORT` of the environment being expanded
+    pub port: Option<&'c str>,
+}
+
+impl<'c> Context<'c> {
+    /// Context of a spawn environment, its last `PORT` entry wins like it does for the child
+    pub fn of(id: usize, name: &'c str, env: &'c [String]) -> Self {
+        let port = env.iter().rev().find_map(|entry| entry.strip_prefix("PORT="));
+        Context { id, name, port }
+    }
+
+    fn value(&self, key: &str) -> Option<String> {
+        match key {
+            "hostname" => hostname::get().ok().map(|name| name.to_string_lossy().into_owned()),
+            "opm.process_name" => Some(self.name.to_string()),
+            "opm.process_id" => Some(self.id.to_string()),
+            "opm.worker_index" => worker_index(self.name).map(|index| index.to_string()),
+            "port" => self.port.map(str::to_string),
+            _ => None,
+        }
+    }
+}
+
+/// Zero-based index of a process started with `--workers`, named `<name>-worker-<n>`
+fn worker_index(name: &str) -> Option<usize> {
+    let (prefix, number) = name.rsplit_once("worker-")?;
+    let number = number.parse::<usize>().ok().filter(|number| *number > 0)?;
+    (prefix.is_empty() || prefix.ends_with('-')).then(|| number - 1)
+}
+
+/// Expand the `{{placeholder}}`s of a value, returning the ones left as is because they have no value
+pub fn expand<'v>(value: &'v str, context: &Context) -> (Cow<'v, str>, Vec<String>) {
+    if !value.contains("{{") {
+        return (Cow::Borrowed(value), vec![]);
+    }
+
+    let (mut out, mut unknown, mut rest) = (String::with_capacity(value.len()), vec![], value);
+
+    while let Some(start) = rest.find("{{") {
+        let Some(end) = rest[start + 2..].find("}}") else { break };
+        let key = rest[start + 2..start + 2 + end].trim();
+
+        out.push_str(&rest[..start]);
+        match context.value(key) {
+            Some(value) => out.push_str(&value),
+            None => {
+                out.push_str(&rest[start..start + end + 4]);
+                unknown.push(key.to_string());
+            }
+        }
+        rest = &rest[start + end + 4..];
+    }
+
+    out.push_str(rest);
+    (Cow::Owned(out), unknown)
+}
+
+/// Expand every `KEY=value` entry of a spawn environment in place
+pub fn expand_env(env: &mut [String], id: usize, name: &str) {
+    let snapshot = env.to_vec();
+    let context = Context::of(id, name, &snapshot);
+
+    for entry in env.iter_mut() {
+        let Some((key, value)) = entry.split_once('=') else { continue };
+        let (expanded, unknown) = expand(value, &context);
+
+        for placeholder in unknown {
+            log::warn!("Placeholder {{{{{placeholder}}}}} in {key} of process {name} has no value, left as is");
+        }
+
+        if let Cow::Owned(expanded) = expanded {
+            *entry = format!("{key}={expanded}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand_placeholders() {
+        let context = Context { id: 4, name: "api-worker-2", port: Some("8080") };
+        let (value, unknown) = expand("http://{{ hostname }}:{{port}}/{{opm.process_name}}?id={{opm.process_id}}&w={{opm.worker_index}}", &context);
+
+        let host = hostname::get().unwrap().to_string_lossy().into_owned();
+        assert_eq!(value, format!("http://{host}:8080/api-worker-2?id=4&w=1"));
+        assert!(unknown.is_empty());
+    }
+
+    #[test]
+    fn test_unknown_placeholders_are_kept() {
+        let context = Context { id: 0, name: "api", port: None };
+        let (value, unknown) = expand("{{port}}-{{ user }}-{{unclosed", &context);
+
+        assert_eq!(value, "{{port}}-{{ user }}-{{unclosed");
+        assert_eq!(unknown, ["port", "user"]);
+        assert!(matches!(expand("plain", &context).0, Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn test_expand_env_uses_last_port() {
+        let mut env = vec![String::from("PORT=1"), String::from("URL=:{{port}}"), String::from("PORT=2")];
+        expand_env(&mut env, 0, "web");
+
+        assert_eq!(env[1], "URL=:2");
+        assert_eq!(worker_index("worker-1"), Some(0));
+        assert_eq!(worker_index("coworker-1"), None);
+        assert_eq!(worker_index("api"), None);
+    }
+}
@@ -1,3 +1,48 @@
+use crate::process::counters::{self, Counter};
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+
+/// Bounds of `runner.cpu_sample_ms`
+pub const MIN_SAMPLE_MS: u64 = 20;
+pub const MAX_SAMPLE_MS: u64 = 1000;
+
+/// Window of a timed sample in milliseconds, 0 until read from the config
+static SAMPLE_MS: AtomicU64 = AtomicU64::new(0);
+
+#[cfg(test)]
+thread_local! {
+    /// Times the current thread waited for a timed sample
+    pub(crate) static WAITS: std::cell::Cell<u64> = const { std::cell::Cell::new(0) };
+}
+
+/// Set the window of timed samples, clamped to its bounds. None restores the default
+pub fn set_sample_window(ms: Option<u64>) {
+    let ms = ms.unwrap_or(super::PROCESS_OPERATION_DELAY_MS).clamp(MIN_SAMPLE_MS, MAX_SAMPLE_MS);
+    SAMPLE_MS.store(ms, Ordering::Relaxed);
+}
+
+/// Window of timed samples, `runner.cpu_sample_ms` of the config unless set otherwise
+pub fn sample_window() -> Duration {
+    if SAMPLE_MS.load(Ordering::Relaxed) == 0 {
+        set_sample_window(crate::config::read().runner.cpu_sample_ms);
+    }
+    Duration::from_millis(SAMPLE_MS.load(Ordering::Relaxed))
+}
+
+fn wait(window: Duration) {
+    #[cfg(test)]
+    WAITS.with(|waits| waits.set(waits.get() + 1));
+    std::thread::sleep(window);
+}
+
+/// Current cpu usage of a process, measured over the configured window
+pub fn get_cpu_percent(pid: u32) -> f64 { get_cpu_percent_over(pid, sample_window()) }
+
+/// Current cpu usage of a process, measured over `window`
+pub fn get_cpu_percent_over(pid: u32, window: Duration) -> f64 { get_cpu_percent_batch(&[pid], window)[0] }
+
 /// Get the effective number of CPUs, taking into account container CPU quotas.
 /// In containerized environments (Docker, Kubernetes, etc.), this returns the CPU quota
 /// instead of the host's CPU count. Falls back to host CPU count if not in a container.
@@ -78,69 +123,70 @@ pub fn get_effective_cpu_count() -> f64 {
     num_cpus::get() as f64
 }
 
+/// Process and system cpu time in seconds, read from /proc
 #[cfg(target_os = "linux")]
-pub fn get_cpu_percent(pid: u32) -> f64 {
+fn cpu_times(pid: u32) -> Option<(f64, f64)> {
     use std::fs;
-    use std::thread;
-    use std::time::{Duration, Instant};
-
-    // Take two measurements to calculate CPU usage rate
-    let get_cpu_time = |pid: u32| -> Option<(f64, f64)> {
-        let stat_path = format!("/proc/{}/stat", pid);
-        if let Ok(stat_content) = fs::read_to_string(&stat_path) {
-            let parts: Vec<&str> = stat_content.split_whitespace().collect();
-            if parts.len() > 16 {
-                let utime = parts[13].parse::<u64>().ok()? as f64;
-                let stime = parts[14].parse::<u64>().ok()? as f64;
-                let total_process_time = (utime + stime) / 100.0; // Convert clock ticks to seconds
-
-                // Get system CPU time
-                if let Ok(stat_content) = fs::read_to_string("/proc/stat") {
-                    if let Some(cpu_line) = stat_content.lines().next() {
-                        let cpu_parts: Vec<&str> = cpu_line.split_whitespace().collect();
-                        if cpu_parts.len() > 7 {
-                            let user: u64 = cpu_parts[1].parse().ok()?;
-                            let nice: u64 = cpu_parts[2].parse().ok()?;
-                            let system: u64 = cpu_parts[3].parse().ok()?;
-                            let idle: u64 = cpu_parts[4].parse().ok()?;
-                            let iowait: u64 = cpu_parts[5].parse().ok()?;
-                            let irq: u64 = cpu_parts[6].parse().ok()?;
-                            let softirq: u64 = cpu_parts[7].parse().ok()?;
-
-                            let total_system_time =
-                                (user + nice + system + idle + iowait + irq + softirq) as f64
-                                    / 100.0;
-                            return Some((total_process_time, total_system_time));
-                        }
-                    }
-                }
-            }
-        }
 
-        None
-    };
+    let stat_content = fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    let parts = super::process_info::stat_fields(&stat_content);
+    if parts.len() <= 16 {
+        return None;
+    }
+
+    let utime = parts[13].parse::<u64>().ok()? as f64;
+    let stime = parts[14].parse::<u64>().ok()? as f64;
+    let total_process_time = (utime + stime) / 100.0; // Convert clock ticks to seconds
+
+    // Get system CPU time
+    let stat_content = fs::read_to_string("/proc/stat").ok()?;
+    let cpu_parts: Vec<&str> = stat_content.lines().next()?.split_whitespace().collect();
+    if cpu_parts.len() <= 7 {
+        return None;
+    }
+
+    let mut total_system_time = 0;
+    for part in &cpu_parts[1..8] {
+        total_system_time += part.parse::<u64>().ok()?;
+    }
+    Some((total_process_time, total_system_time as f64 / 100.0))
+}
 
-    if let Some((start_process, start_system)) = get_cpu_time(pid) {
-        let start_time = Instant::now();
-        thread::sleep(Duration::from_millis(super::PROCESS_OPERATION_DELAY_MS));
+/// Current cpu usage of every one of `pids`, in order, all measured over the same `window`.
+/// However many pids there are, the calling thread waits once.
+#[cfg(target_os = "linux")]
+pub fn get_cpu_percent_batch(pids: &[u32], window: Duration) -> Vec<f64> {
+    pids.iter().for_each(|_| counters::add(Counter::CpuSample));
+
+    let start: Vec<Option<(f64, f64)>> = pids.iter().map(|&pid| cpu_times(pid)).collect();
+    if start.iter().all(Option::is_none) {
+        return vec![0.0; pids.len()];
+    }
+
+    let start_time = Instant::now();
+    wait(window);
+    let elapsed = start_time.elapsed().as_secs_f64();
+    let cpu_cores = get_effective_cpu_count();
+
+    pids.iter()
+        .zip(start)
+        .map(|(&pid, start)| {
+            let (Some((start_process, start_system)), Some((end_process, end_system))) = (start, cpu_times(pid)) else {
+                return 0.0;
+            };
 
-        if let Some((end_process, end_system)) = get_cpu_time(pid) {
-            let elapsed = start_time.elapsed().as_secs_f64();
             let process_diff = end_process - start_process;
             let system_diff = end_system - start_system;
 
             if system_diff > 0.0 && elapsed > 0.0 {
-                let cpu_cores = get_effective_cpu_count();
                 let available_cpu_time = elapsed * cpu_cores;
-                let cpu_percent = (process_diff / available_cpu_time) * 100.0;
                 // Clamp to 100% - a process can use at most 100% of available CPU
                 // In containers with CPU quota, this means 100% of the quota
-                return cpu_percent.min(100.0);
+                return ((process_diff / available_cpu_time) * 100.0).min(100.0);
             }
-        }
-    }
-
-    0.0
+            0.0
+        })
+        .collect()
 }
 
 /// Get approximate CPU percentage without delay-based sampling
@@ -151,6 +197,8 @@ pub fn get_cpu_percent_fast(pid: u32) -> f64 {
     use std::fs;
     use std::sync::OnceLock;
 
+    counters::add(Counter::CpuSample);
+
     // Cache the effective number of CPUs (respects container limits)
     static EFFECTIVE_CPUS: OnceLock<f64> = OnceLock::new();
     let num_cpus = *EFFECTIVE_CPUS.get_or_init(|| get_effective_cpu_count());
@@ -172,7 +220,7 @@ pub fn get_cpu_percent_fast(pid: u32) -> f64 {
 
     let stat_path = format!("/proc/{}/stat", pid);
     if let Ok(stat_content) = fs::read_to_string(&stat_path) {
-        let parts: Vec<&str> = stat_content.split_whitespace().collect();
+        let parts = super::process_info::stat_fields(&stat_content);
         // Indices from /proc/[pid]/stat format (see `man 5 proc`):
         // [13] = utime (CPU time in user mode)
         // [14] = stime (CPU time in kernel mode)
@@ -217,6 +265,7 @@ pub fn get_cpu_percent_fast(pid: u32) -> f64 {
 
 #[cfg(target_os = "macos")]
 pub fn get_cpu_percent_fast(pid: u32) -> f64 {
+    counters::add(Counter::CpuSample);
     // For macOS, we'll use ps command as a fast approximation
     if let Some(percent) = get_cpu_percent_ps(pid) {
         return percent;
@@ -224,26 +273,37 @@ pub fn get_cpu_percent_fast(pid: u32) -> f64 {
     0.0
 }
 
+/// Current cpu usage of every one of `pids`, in order, all measured over the same `window`.
+/// However many pids there are, the calling thread waits once. Processes the task info of which
+/// cannot be read fall back to the average `ps` reports.
 #[cfg(target_os = "macos")]
-pub fn get_cpu_percent(pid: u32) -> f64 {
-    // Try mach task info first
-    if let Some(percent) = get_cpu_percent_mach(pid) {
-        return percent;
-    }
+pub fn get_cpu_percent_batch(pids: &[u32], window: Duration) -> Vec<f64> {
+    pids.iter().for_each(|_| counters::add(Counter::CpuSample));
 
-    // Fallback to ps command
-    if let Some(percent) = get_cpu_percent_ps(pid) {
-        return percent;
+    let start: Vec<Option<f64>> = pids.iter().map(|&pid| mach_cpu_time(pid)).collect();
+    let start_time = Instant::now();
+    if start.iter().any(Option::is_some) {
+        wait(window);
     }
+    let elapsed_real = start_time.elapsed().as_secs_f64();
+    let cpu_cores = num_cpus::get() as f64;
 
-    0.0
+    pids.iter()
+        .zip(start)
+        .map(|(&pid, start)| match (start, mach_cpu_time(pid)) {
+            (Some(start), Some(end)) if elapsed_real > 0.0 => {
+                let available_cpu_time = elapsed_real * cpu_cores;
+                ((end - start) / available_cpu_time * 100.0 * cpu_cores).min(100.0)
+            }
+            _ => get_cpu_percent_ps(pid).unwrap_or(0.0),
+        })
+        .collect()
 }
 
+/// User and system time of a process in seconds, from its mach task info
 #[cfg(target_os = "macos")]
-fn get_cpu_percent_mach(pid: u32) -> Option<f64> {
+fn mach_cpu_time(pid: u32) -> Option<f64> {
     use std::mem;
-    use std::thread;
-    use std::time::{Duration, Instant};
 
     #[repr(C)]
     struct TaskBasicInfo {
@@ -286,7 +346,6 @@ fn get_cpu_percent_mach(pid: u32) -> Option<f64> {
         return None;
     }
 
-    // Get first measurement
     let mut info: TaskBasicInfo = unsafe { mem::zeroed() };
     let mut count = TASK_BASIC_INFO_COUNT;
     if unsafe {
@@ -301,40 +360,7 @@ fn get_cpu_percent_mach(pid: u32) -> Option<f64> {
         return None;
     }
 
-    let start_time = Instant::now();
-    let start_cpu_time = time_to_seconds(&info.user_time) + time_to_seconds(&info.system_time);
-
-    // Wait for measurement interval
-    thread::sleep(Duration::from_millis(super::PROCESS_OPERATION_DELAY_MS));
-
-    // Get second measurement
-    let mut info2: TaskBasicInfo = unsafe { mem::zeroed() };
-    let mut count2 = TASK_BASIC_INFO_COUNT;
-    if unsafe {
-        task_info(
-            task,
-            TASK_BASIC_INFO,
-            &mut info2 as *mut _ as *mut libc::c_void,
-            &mut count2,
-        )
-    } != 0
-    {
-        return None;
-    }
-
-    let elapsed_real = start_time.elapsed().as_secs_f64();
-    if elapsed_real <= 0.0 {
-        return None;
-    }
-
-    let end_cpu_time = time_to_seconds(&info2.user_time) + time_to_seconds(&info2.system_time);
-    let cpu_time_used = end_cpu_time - start_cpu_time;
-
-    let cpu_cores = num_cpus::get() as f64;
-    let available_cpu_time = elapsed_real * cpu_cores;
-    let cpu_percent = (cpu_time_used / available_cpu_time) * 100.0 * cpu_cores;
-
-    Some(cpu_percent.min(100.0))
+    Some(time_to_seconds(&info.user_time) + time_to_seconds(&info.system_time))
 }
 
 #[cfg(target_os = "macos")]
@@ -347,3 +373,27 @@ fn get_cpu_percent_ps(pid: u32) -> Option<f64> {
     let cpu_str = String::from_utf8(output.stdout).ok()?;
     cpu_str.trim().parse::<f64>().ok()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sample_window_bounds() {
+        set_sample_window(Some(5));
+        assert_eq!(sample_window(), Duration::from_millis(MIN_SAMPLE_MS));
+        set_sample_window(Some(60_000));
+        assert_eq!(sample_window(), Duration::from_millis(MAX_SAMPLE_MS));
+        set_sample_window(None);
+        assert_eq!(sample_window(), Duration::from_millis(super::super::PROCESS_OPERATION_DELAY_MS));
+    }
+
+    #[test]
+    fn test_batch_waits_once() {
+        let pid = std::process::id();
+        WAITS.with(|waits| waits.set(0));
+        let samples = get_cpu_percent_batch(&[pid, pid, pid], Duration::from_millis(MIN_SAMPLE_MS));
+        assert_eq!(samples.len(), 3);
+        assert_eq!(WAITS.with(|waits| waits.get()), 1);
+    }
+}
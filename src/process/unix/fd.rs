@@ -0,0 +1,90 @@
+use std::os::fd::RawFd;
+
+/// Number of descriptor slots worth checking, from the soft RLIMIT_NOFILE.
+/// Read before forking since the loop in `set_cloexec_from` has to stay async-signal-safe.
+pub fn fd_limit() -> RawFd {
+    let mut limit = libc::rlimit { rlim_cur: 0, rlim_max: 0 };
+
+    match unsafe { libc::getrlimit(libc::RLIMIT_NOFILE, &mut limit) } {
+        0 if limit.rlim_cur != libc::RLIM_INFINITY => limit.rlim_cur.min(RawFd::MAX as libc::rlim_t) as RawFd,
+        _ => 65536,
+    }
+}
+
+/// Mark every descriptor from `first` up to `limit` close-on-exec.
+/// Only raw syscalls and no allocation, so it is safe to call between fork and exec.
+pub fn set_cloexec_from(first: RawFd, limit: RawFd) {
+    #[cfg(target_os = "linux")]
+    if unsafe { libc::syscall(libc::SYS_close_range, first as libc::c_uint, libc::c_uint::MAX, libc::CLOSE_RANGE_CLOEXEC) } == 0 {
+        return;
+    }
+
+    // Kernels before 5.11 and macOS, unused slots fail with EBADF
+    for fd in first..limit {
+        unsafe { libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) };
+    }
+}
+
+/// Open file descriptors of a process, None when they cannot be read (another user, already gone)
+pub fn open_fds(pid: u32) -> Option<u64> {
+    #[cfg(target_os = "linux")]
+    {
+        std::fs::read_dir(format!("/proc/{pid}/fd")).ok().map(|entries| entries.count() as u64)
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        const PROC_PIDLISTFDS: i32 = 1;
+        // struct proc_fdinfo { int32_t proc_fd; uint32_t proc_fdtype; }
+        const PROC_FDINFO_SIZE: i32 = 8;
+
+        unsafe extern "C" {
+            fn proc_pidinfo(pid: libc::c_int, flavor: libc::c_int, arg: u64, buffer: *mut libc::c_void, buffersize: libc::c_int) -> libc::c_int;
+        }
+
+        // Without a buffer the call returns the size the list would take
+        match unsafe { proc_pidinfo(pid as i32, PROC_PIDLISTFDS, 0, std::ptr::null_mut(), 0) } {
+            size if size > 0 => Some((size / PROC_FDINFO_SIZE) as u64),
+            _ => None,
+        }
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+    {
+        let _ = pid;
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{fs::File, os::fd::AsRawFd};
+
+    fn cloexec(fd: RawFd) -> bool {
+        unsafe { libc::fcntl(fd, libc::F_GETFD) & libc::FD_CLOEXEC != 0 }
+    }
+
+    #[test]
+    fn test_set_cloexec_from() {
+        let file = File::open("/dev/null").unwrap();
+        let fd = file.as_raw_fd();
+
+        unsafe { libc::fcntl(fd, libc::F_SETFD, 0) };
+        assert!(!cloexec(fd));
+
+        set_cloexec_from(fd, fd_limit());
+        assert!(cloexec(fd));
+        assert!(fd_limit() > fd);
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_open_fds() {
+        let files: Vec<File> = (0..3).map(|_| File::open("/dev/null").unwrap()).collect();
+
+        // Other tests open and close files concurrently, only a lower bound holds
+        assert!(open_fds(std::process::id()).unwrap() >= files.len() as u64);
+        assert_eq!(open_fds(u32::MAX), None);
+    }
+}
@@ -3,14 +3,16 @@ use std::time::{Duration, SystemTime};
 
 pub mod cpu;
 pub mod env;
+pub mod fd;
 pub mod memory;
 pub mod process_info;
 pub mod process_list;
 
-pub use cpu::{get_cpu_percent, get_cpu_percent_fast, get_effective_cpu_count};
+pub use cpu::{get_cpu_percent, get_cpu_percent_batch, get_cpu_percent_fast, get_cpu_percent_over, get_effective_cpu_count};
 pub use env::{Vars, env};
+pub use fd::{fd_limit, open_fds, set_cloexec_from};
 pub use memory::{NativeMemoryInfo, get_memory_info};
-pub use process_info::{get_parent_pid, get_process_name, get_process_start_time, is_process_zombie};
+pub use process_info::{get_parent_pid, get_process_name, get_process_owner, get_process_start_time, get_user_name, is_process_zombie, thread_count};
 pub use process_list::native_processes;
 
 pub const PROCESS_OPERATION_DELAY_MS: u64 = 100;
@@ -26,11 +28,15 @@ pub struct NativeProcess {
 }
 
 impl NativeProcess {
-    pub fn new(pid: u32) -> Result<Self, String> {
+    /// Create a new NativeProcess, sampling its cpu usage over the configured window
+    pub fn new(pid: u32) -> Result<Self, String> { Self::timed(pid, cpu::sample_window()) }
+
+    /// Create a new NativeProcess, sampling its current cpu usage over `window`
+    pub fn timed(pid: u32, window: Duration) -> Result<Self, String> {
         let ppid = get_parent_pid(pid as i32)?.map(|p| p as u32);
         let name = get_process_name(pid)?;
         let memory_info = get_memory_info(pid).ok();
-        let cpu_percent = get_cpu_percent(pid);
+        let cpu_percent = get_cpu_percent_over(pid, window);
         let create_time = get_process_start_time(pid)?;
 
         Ok(NativeProcess {
@@ -82,63 +88,61 @@ impl NativeProcess {
     }
 }
 
-#[cfg(target_os = "linux")]
+/// Lookups of the command a shell spawned, spread over `PROCESS_OPERATION_DELAY_MS`
+const CHILD_LOOKUP_ATTEMPTS: u64 = 5;
+
+/// Pid of the command a shell runs, the shell's own pid when it exec'd the command or already exited
+///
+/// The shell may not have forked yet when it is first looked at, so the lookup is retried a few times.
+/// Children that already exited are skipped, and so is the rest of the lookup once the shell itself
+/// exited, as it will not spawn anything anymore.
 pub fn get_actual_child_pid(shell_pid: i64) -> i64 {
-    thread::sleep(Duration::from_millis(PROCESS_OPERATION_DELAY_MS));
-
-    let proc_path = format!("/proc/{}/task/{}/children", shell_pid, shell_pid);
-    if let Ok(contents) = std::fs::read_to_string(&proc_path)
-        && let Some(child_pid_str) = contents.split_whitespace().next()
-        && let Ok(child_pid) = child_pid_str.parse::<i64>()
-    {
-        return child_pid;
+    for _ in 0..CHILD_LOOKUP_ATTEMPTS {
+        thread::sleep(Duration::from_millis(PROCESS_OPERATION_DELAY_MS / CHILD_LOOKUP_ATTEMPTS));
+
+        if let Some(child) = find_child(shell_pid) {
+            return child;
+        }
+
+        if !super::is_pid_alive(shell_pid) {
+            break;
+        }
     }
 
-    let pid = if let Ok(processes) = native_processes() {
-        processes
-            .iter()
-            .find(|process| {
-                if let Ok(Some(ppid)) = process.ppid() {
-                    ppid as i64 == shell_pid
-                } else {
-                    false
-                }
+    shell_pid
+}
+
+#[cfg(target_os = "linux")]
+fn find_child(shell_pid: i64) -> Option<i64> {
+    let children: Vec<i64> = match std::fs::read_to_string(format!("/proc/{shell_pid}/task/{shell_pid}/children")) {
+        Ok(contents) => contents.split_whitespace().filter_map(|pid| pid.parse().ok()).collect(),
+        // Kernels built without CONFIG_PROC_CHILDREN, every process has to be looked at
+        Err(_) => native_processes()
+            .map(|processes| {
+                processes
+                    .iter()
+                    .filter(|process| matches!(process.ppid(), Ok(Some(ppid)) if ppid as i64 == shell_pid))
+                    .map(|process| process.pid() as i64)
+                    .collect()
             })
-            .map_or(shell_pid, |p| p.pid() as i64)
-    } else {
-        shell_pid
+            .unwrap_or_default(),
     };
 
-    pid
+    children.into_iter().find(|pid| !is_process_zombie(*pid as i32))
 }
 
 #[cfg(target_os = "macos")]
-pub fn get_actual_child_pid(shell_pid: i64) -> i64 {
-    // Wait for shell to spawn the actual command
-    thread::sleep(Duration::from_millis(PROCESS_OPERATION_DELAY_MS));
+fn find_child(shell_pid: i64) -> Option<i64> {
+    let live = |pid: i64| !is_process_zombie(pid as i32);
 
-    // Find children by iterating processes
     if let Ok(processes) = native_processes() {
-        for process in processes {
-            let ppid = match process.ppid() {
-                Ok(Some(ppid)) => ppid as i64,
-                _ => continue,
-            };
-
-            if ppid == shell_pid {
-                return process.pid() as i64;
-            }
-        }
+        return processes
+            .iter()
+            .filter(|process| matches!(process.ppid(), Ok(Some(ppid)) if ppid as i64 == shell_pid))
+            .map(|process| process.pid() as i64)
+            .find(|pid| live(*pid));
     }
 
     // Fallback: try using sysctl or other macOS specific methods
-    for test_pid in 1..32768 {
-        if let Ok(Some(ppid)) = get_parent_pid(test_pid) {
-            if ppid as i64 == shell_pid {
-                return test_pid as i64;
-            }
-        }
-    }
-
-    shell_pid
+    (1..32768).filter(|pid| matches!(get_parent_pid(*pid), Ok(Some(ppid)) if ppid as i64 == shell_pid)).map(i64::from).find(|pid| live(*pid))
 }
@@ -2,6 +2,18 @@ use std::time::SystemTime;
 #[cfg(target_os = "linux")]
 use std::time::{Duration, UNIX_EPOCH};
 
+/// Fields of `/proc/<pid>/stat` numbered as in `man 5 proc` from 0.
+/// `comm` may hold spaces, such as the name the daemon gives itself, so it is cut out at its parentheses.
+pub fn stat_fields(content: &str) -> Vec<&str> {
+    let (Some(open), Some(close)) = (content.find('('), content.rfind(')')) else {
+        return content.split_whitespace().collect();
+    };
+
+    let mut fields = vec![content[..open].trim(), &content[open..=close]];
+    fields.extend(content[close + 1..].split_whitespace());
+    fields
+}
+
 pub fn get_process_name(pid: u32) -> Result<String, String> {
     #[cfg(target_os = "macos")]
     {
@@ -83,7 +95,7 @@ pub fn get_process_start_time(_pid: u32) -> Result<SystemTime, String> {
         let stat_content = fs::read_to_string(&stat_path)
             .map_err(|e| format!("Failed to read process stat: {}", e))?;
 
-        let parts: Vec<&str> = stat_content.split_whitespace().collect();
+        let parts = stat_fields(&stat_content);
         if parts.len() > 21 {
             if let Ok(start_time) = parts[21].parse::<u64>() {
                 // Convert from clock ticks to seconds (simplified)
@@ -175,7 +187,7 @@ pub fn get_parent_pid(pid: i32) -> Result<Option<i32>, String> {
 
     // Parse /proc/pid/stat format
     // The format is: pid (comm) state ppid ...
-    let parts: Vec<&str> = stat_content.split_whitespace().collect();
+    let parts = stat_fields(&stat_content);
     if parts.len() < 4 {
         return Err(format!("Invalid stat format for PID {}", pid));
     }
@@ -287,10 +299,172 @@ pub fn is_process_zombie(pid: i32) -> bool {
     }
 }
 
+/// Threads of a process, None when it cannot be read
+pub fn thread_count(pid: u32) -> Option<u64> {
+    #[cfg(target_os = "linux")]
+    {
+        let status = std::fs::read_to_string(format!("/proc/{pid}/status")).ok()?;
+        status.lines().find_map(|line| line.strip_prefix("Threads:")).and_then(|count| count.trim().parse().ok())
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        use std::mem;
+
+        const PROC_PIDTASKINFO: i32 = 4;
+
+        #[repr(C)]
+        struct ProcTaskInfo {
+            pti_virtual_size: u64,
+            pti_resident_size: u64,
+            pti_total_user: u64,
+            pti_total_system: u64,
+            pti_threads_user: u64,
+            pti_threads_system: u64,
+            pti_policy: i32,
+            pti_faults: i32,
+            pti_pageins: i32,
+            pti_cow_faults: i32,
+            pti_messages_sent: i32,
+            pti_messages_received: i32,
+            pti_syscalls_mach: i32,
+            pti_syscalls_unix: i32,
+            pti_csw: i32,
+            pti_threadnum: i32,
+            pti_numrunning: i32,
+            pti_priority: i32,
+        }
+
+        unsafe extern "C" {
+            fn proc_pidinfo(pid: libc::c_int, flavor: libc::c_int, arg: u64, buffer: *mut libc::c_void, buffersize: libc::c_int) -> libc::c_int;
+        }
+
+        let mut info: ProcTaskInfo = unsafe { mem::zeroed() };
+        let size = mem::size_of::<ProcTaskInfo>() as i32;
+
+        match unsafe { proc_pidinfo(pid as i32, PROC_PIDTASKINFO, 0, &mut info as *mut _ as *mut libc::c_void, size) } {
+            result if result == size => Some(info.pti_threadnum.max(0) as u64),
+            _ => None,
+        }
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+    {
+        let _ = pid;
+        None
+    }
+}
+
+/// Get the uid owning a process, which may differ from the user running opm
+pub fn get_process_owner(pid: u32) -> Result<u32, String> {
+    #[cfg(target_os = "linux")]
+    {
+        use std::os::unix::fs::MetadataExt;
+
+        // /proc/PID is owned by the effective uid of the process and readable by everyone
+        std::fs::metadata(format!("/proc/{}", pid))
+            .map(|metadata| metadata.uid())
+            .map_err(|e| format!("Failed to read owner of PID {}: {}", pid, e))
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        use std::mem;
+
+        const PROC_PIDTBSDINFO: i32 = 3;
+
+        #[repr(C)]
+        struct ProcBsdInfo {
+            pbi_flags: u32,
+            pbi_status: u32,
+            pbi_xstatus: u32,
+            pbi_pid: u32,
+            pbi_ppid: u32,
+            pbi_uid: u32,
+            pbi_gid: u32,
+            pbi_ruid: u32,
+            pbi_rgid: u32,
+            pbi_svuid: u32,
+            pbi_svgid: u32,
+            rfu_1: u32,
+            pbi_comm: [libc::c_char; 16],
+            pbi_name: [libc::c_char; 32],
+            pbi_nfiles: u32,
+            pbi_pgid: u32,
+            pbi_pjobc: u32,
+            e_tdev: u32,
+            e_tpgid: u32,
+            pbi_nice: i32,
+            pbi_start_tvsec: u64,
+            pbi_start_tvusec: u64,
+        }
+
+        unsafe extern "C" {
+            fn proc_pidinfo(
+                pid: libc::c_int,
+                flavor: libc::c_int,
+                arg: u64,
+                buffer: *mut libc::c_void,
+                buffersize: libc::c_int,
+            ) -> libc::c_int;
+        }
+
+        let mut proc_info: ProcBsdInfo = unsafe { mem::zeroed() };
+        let result = unsafe {
+            proc_pidinfo(
+                pid as i32,
+                PROC_PIDTBSDINFO,
+                0,
+                &mut proc_info as *mut _ as *mut libc::c_void,
+                mem::size_of::<ProcBsdInfo>() as i32,
+            )
+        };
+
+        if result <= 0 {
+            return Err(format!("Failed to get process info for PID {}", pid));
+        }
+
+        Ok(proc_info.pbi_uid)
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+    {
+        Err(format!("Process owner lookup is not supported for PID {}", pid))
+    }
+}
+
+/// Resolve a uid to its login name through the system user database
+pub fn get_user_name(uid: u32) -> Option<String> {
+    use std::ffi::CStr;
+
+    let mut passwd: libc::passwd = unsafe { std::mem::zeroed() };
+    let mut result: *mut libc::passwd = std::ptr::null_mut();
+    let mut buffer = vec![0 as libc::c_char; 4096];
+
+    // getpwuid_r is the reentrant variant, the daemon may resolve owners from several threads
+    let status = unsafe { libc::getpwuid_r(uid, &mut passwd, buffer.as_mut_ptr(), buffer.len(), &mut result) };
+
+    if status != 0 || result.is_null() {
+        return None;
+    }
+
+    unsafe { CStr::from_ptr(passwd.pw_name) }.to_str().ok().map(String::from)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_stat_fields_keep_comm_with_spaces() {
+        let stat = "4242 (OPM Restart Handler) S 1 4242 4242 0 -1 4194624 120 0 0 0 7 3 0 0 20 0 4 0 1500";
+
+        let fields = stat_fields(stat);
+        assert_eq!(fields[1], "(OPM Restart Handler)");
+        assert_eq!((fields[2], fields[3], fields[13], fields[14], fields[21]), ("S", "1", "7", "3", "1500"));
+        assert_eq!(stat_fields("1 (init) S 0")[3], "0");
+    }
+
     #[test]
     fn test_get_parent_pid_current_process() {
         let current_pid = std::process::id() as i32;
@@ -345,4 +519,30 @@ mod tests {
         assert!(!is_process_zombie(1), 
             "Init process should not be detected as zombie");
     }
+
+    #[test]
+    fn test_get_process_owner_current_process() {
+        let owner = get_process_owner(std::process::id());
+        assert_eq!(owner, Ok(unsafe { libc::geteuid() }));
+        assert!(get_process_owner(i32::MAX as u32).is_err());
+    }
+
+    #[test]
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    fn test_thread_count() {
+        let (sender, receiver) = std::sync::mpsc::channel::<()>();
+        let thread = std::thread::spawn(move || receiver.recv());
+        // At least the main thread and the one blocked above
+        assert!(thread_count(std::process::id()).unwrap() >= 2);
+
+        drop(sender);
+        let _ = thread.join();
+        assert_eq!(thread_count(i32::MAX as u32), None);
+    }
+
+    #[test]
+    fn test_get_user_name_root() {
+        // uid 0 is root on every unix system with a user database
+        assert_eq!(get_user_name(0).as_deref(), Some("root"));
+    }
 }
@@ -1,7 +1,9 @@
 use super::NativeProcess;
+use crate::process::counters::{self, Counter};
 
 /// Get all running processes
 pub fn native_processes() -> Result<Vec<NativeProcess>, String> {
+    counters::add(Counter::ProcessTable);
     #[cfg(target_os = "macos")]
     {
         use std::mem;
@@ -73,7 +75,7 @@ pub fn native_processes() -> Result<Vec<NativeProcess>, String> {
 
         for kinfo in processes_buf {
             let pid = kinfo.kp_proc.p_pid as u32;
-            if let Ok(process) = NativeProcess::new(pid) {
+            if let Ok(process) = NativeProcess::new_fast(pid) {
                 result_processes.push(process);
             }
         }
@@ -94,7 +96,7 @@ pub fn native_processes() -> Result<Vec<NativeProcess>, String> {
             if let Ok(entry) = entry
                 && let Ok(file_name) = entry.file_name().into_string()
                 && let Ok(pid) = file_name.parse::<u32>()
-                && let Ok(process) = NativeProcess::new(pid)
+                && let Ok(process) = NativeProcess::new_fast(pid)
             {
                 processes.push(process);
             }
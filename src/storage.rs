@@ -0,0 +1,375 @@
+use crate::config::{self, structs::Limits, structs::Retention};
+use flate2::{Compression, write::GzEncoder};
+use global_placeholders::global;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use std::{
+    fmt,
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+/// Files written to more recently than this may still be read or rotated, housekeeping leaves them alone
+pub const RECENT: Duration = Duration::from_secs(10 * 60);
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Kind of history kept on disk, each with its own retention limits
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Class {
+    Events,
+    Metrics,
+    Logs,
+    Audit,
+}
+
+impl Class {
+    pub const ALL: [Class; 4] = [Class::Events, Class::Metrics, Class::Logs, Class::Audit];
+
+    fn dir(&self) -> PathBuf {
+        match self {
+            Class::Logs => PathBuf::from(config::read().runner.log_path),
+            _ => PathBuf::from(global!("opm.base")),
+        }
+    }
+
+    /// Whether a file of the directory belongs to the class, and if so whether it is still written to.
+    /// Live files are counted in the usage but never compressed or deleted.
+    fn classify(&self, name: &str) -> Option<bool> {
+        let live = match self {
+            Class::Events => "events.jsonl",
+            Class::Metrics => "metrics.jsonl",
+            Class::Audit => "audit.jsonl",
+            Class::Logs if name.ends_with(".log") => return Some(true),
+            // Rotations are numbered, `api-out.log.1` or `api-out.log.2.gz`, unlike the `.log.idx` checkpoints
+            Class::Logs => return name.split_once(".log.").filter(|(_, rest)| rest.starts_with(|c: char| c.is_ascii_digit())).map(|_| false),
+        };
+
+        match name.strip_prefix(live) {
+            Some("") => Some(true),
+            Some(rest) if rest.starts_with('.') => Some(false),
+            _ => None,
+        }
+    }
+
+    fn limits<'r>(&self, retention: &'r Retention) -> &'r Limits {
+        match self {
+            Class::Events => &retention.events,
+            Class::Metrics => &retention.metrics,
+            Class::Logs => &retention.logs,
+            Class::Audit => &retention.audit,
+        }
+    }
+}
+
+impl fmt::Display for Class {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Class::Events => write!(f, "events"),
+            Class::Metrics => write!(f, "metrics"),
+            Class::Logs => write!(f, "logs"),
+            Class::Audit => write!(f, "audit"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub path: PathBuf,
+    pub bytes: u64,
+    pub modified: SystemTime,
+    pub live: bool,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, PartialEq)]
+pub struct Usage {
+    pub class: Class,
+    #[schema(example = "/home/user/.opm/logs")]
+    pub path: String,
+    #[schema(example = 12)]
+    pub files: usize,
+    /// Rotated or compressed files, the ones retention limits apply to
+    #[schema(example = 10)]
+    pub archived: usize,
+    #[schema(example = 73400320)]
+    pub bytes: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    Deleted,
+    Compressed,
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Action::Deleted => write!(f, "deleted"),
+            Action::Compressed => write!(f, "compressed"),
+        }
+    }
+}
+
+/// What a housekeeping pass did to one file
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pruned {
+    pub class: Class,
+    pub path: PathBuf,
+    pub action: Action,
+    /// Bytes freed on disk
+    pub bytes: u64,
+    pub reason: &'static str,
+}
+
+/// Files of a class in `dir`, oldest first. Housekeeping temp files start with a dot and are skipped.
+pub fn scan(class: Class, dir: &Path) -> Vec<Entry> {
+    let Ok(entries) = fs::read_dir(dir) else { return vec![] };
+
+    let mut files: Vec<Entry> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok().filter(|name| !name.starts_with('.'))?;
+            let live = class.classify(&name)?;
+            let metadata = entry.metadata().ok().filter(|metadata| metadata.is_file())?;
+
+            Some(Entry {
+                path: entry.path(),
+                bytes: metadata.len(),
+                modified: metadata.modified().ok()?,
+                live,
+            })
+        })
+        .collect();
+
+    files.sort_by_key(|file| file.modified);
+    files
+}
+
+pub fn usage() -> Vec<Usage> {
+    Class::ALL
+        .iter()
+        .map(|class| {
+            let dir = class.dir();
+            let files = scan(*class, &dir);
+
+            Usage {
+                class: *class,
+                path: dir.display().to_string(),
+                files: files.len(),
+                archived: files.iter().filter(|file| !file.live).count(),
+                bytes: files.iter().map(|file| file.bytes).sum(),
+            }
+        })
+        .collect()
+}
+
+/// Path next to `path` with a leading dot, which `scan` never picks up
+fn aside(path: &Path, suffix: &str) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!(".{name}{suffix}"))
+}
+
+/// Renamed out of the way before removal, so a reader that opened it keeps its data
+fn delete(path: &Path) -> io::Result<()> {
+    let doomed = aside(path, ".prune");
+    fs::rename(path, &doomed)?;
+    fs::remove_file(doomed)
+}
+
+/// Replace `path` with `path.gz`, keeping its modification time so its age still counts.
+/// Returns the compressed size.
+fn compress(path: &Path, modified: SystemTime) -> io::Result<u64> {
+    let temp = aside(path, ".gz.tmp");
+    let target = PathBuf::from(format!("{}.gz", path.display()));
+
+    let result = (|| {
+        let mut encoder = GzEncoder::new(File::create(&temp)?, Compression::default());
+        io::copy(&mut File::open(path)?, &mut encoder)?;
+
+        let file = encoder.finish()?;
+        file.set_modified(modified)?;
+        file.metadata().map(|metadata| metadata.len())
+    })();
+
+    match result {
+        Ok(bytes) => {
+            fs::rename(&temp, target)?;
+            delete(path)?;
+            Ok(bytes)
+        }
+        Err(err) => {
+            fs::remove_file(&temp).ok();
+            Err(err)
+        }
+    }
+}
+
+/// Apply `limits` to the files of a class, oldest first: archived files past `max_days` are deleted,
+/// the rest compressed when asked to, then more are deleted until the class fits in `max_size`.
+/// Failures are returned next to what was done, one bad file does not stop the pass.
+pub fn enforce(class: Class, files: Vec<Entry>, limits: &Limits, now: SystemTime) -> (Vec<Pruned>, Vec<String>) {
+    let mut pruned = vec![];
+    let mut errors = vec![];
+
+    let max_size = match limits.max_size.as_deref().map(crate::helpers::parse_memory) {
+        Some(Err(err)) => {
+            errors.push(format!("retention.{class}.max_size: {err}"));
+            None
+        }
+        Some(Ok(bytes)) => Some(bytes),
+        None => None,
+    };
+
+    let age = |file: &Entry| now.duration_since(file.modified).unwrap_or_default();
+    let mut total: u64 = files.iter().map(|file| file.bytes).sum();
+    let mut kept = vec![];
+
+    for file in files.into_iter().filter(|file| !file.live && age(file) >= RECENT) {
+        let path = file.path.clone();
+
+        if limits.max_days.is_some_and(|days| age(&file) > Duration::from_secs(days.saturating_mul(DAY.as_secs()))) {
+            match delete(&path) {
+                Ok(()) => {
+                    total -= file.bytes;
+                    pruned.push(Pruned { class, path, action: Action::Deleted, bytes: file.bytes, reason: "max_days" });
+                }
+                Err(err) => errors.push(format!("Cannot delete {}: {err}", path.display())),
+            }
+            continue;
+        }
+
+        if limits.compress && path.extension().is_none_or(|ext| ext != "gz") {
+            match compress(&path, file.modified) {
+                Ok(bytes) => {
+                    total = total - file.bytes + bytes;
+                    let freed = file.bytes.saturating_sub(bytes);
+                    pruned.push(Pruned { class, path: path.clone(), action: Action::Compressed, bytes: freed, reason: "compress" });
+                    kept.push(Entry { path: PathBuf::from(format!("{}.gz", path.display())), bytes, ..file });
+                }
+                Err(err) => {
+                    errors.push(format!("Cannot compress {}: {err}", path.display()));
+                    kept.push(file);
+                }
+            }
+            continue;
+        }
+
+        kept.push(file);
+    }
+
+    if let Some(max_size) = max_size {
+        for file in kept {
+            if total <= max_size {
+                break;
+            }
+
+            match delete(&file.path) {
+                Ok(()) => {
+                    total -= file.bytes;
+                    pruned.push(Pruned { class, path: file.path, action: Action::Deleted, bytes: file.bytes, reason: "max_size" });
+                }
+                Err(err) => errors.push(format!("Cannot delete {}: {err}", file.path.display())),
+            }
+        }
+    }
+
+    (pruned, errors)
+}
+
+/// One housekeeping pass over every class
+pub fn housekeep(retention: &Retention) -> (Vec<Pruned>, Vec<String>) {
+    let now = SystemTime::now();
+    let mut pruned = vec![];
+    let mut errors = vec![];
+
+    for class in Class::ALL {
+        let (done, failed) = enforce(class, scan(class, &class.dir()), class.limits(retention), now);
+        pruned.extend(done);
+        errors.extend(failed);
+    }
+
+    (pruned, errors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    fn dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("opm-storage-{name}-{}", std::process::id()));
+        fs::remove_dir_all(&dir).ok();
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn write(dir: &Path, name: &str, bytes: usize, days_old: u32) {
+        let file = File::create(dir.join(name)).unwrap();
+        io::Write::write_all(&mut &file, "x".repeat(bytes).as_bytes()).unwrap();
+        file.set_modified(SystemTime::now() - DAY * days_old).unwrap();
+    }
+
+    fn names(dir: &Path, class: Class) -> Vec<String> {
+        scan(class, dir).iter().map(|file| file.path.file_name().unwrap().to_string_lossy().to_string()).collect()
+    }
+
+    #[test]
+    fn test_classify() {
+        assert_eq!(Class::Audit.classify("audit.jsonl"), Some(true));
+        assert_eq!(Class::Audit.classify("audit.jsonl.1.gz"), Some(false));
+        assert_eq!(Class::Audit.classify("audit.jsonl-old"), None);
+        assert_eq!(Class::Logs.classify("api-out.log"), Some(true));
+        assert_eq!(Class::Logs.classify("api-out.log.2.gz"), Some(false));
+        assert_eq!(Class::Logs.classify("api-out.log.idx"), None);
+        assert_eq!(Class::Events.classify("audit.jsonl"), None);
+    }
+
+    #[test]
+    fn test_max_days_and_size() {
+        let dir = dir("limits");
+        write(&dir, "api-out.log", 100, 30);
+        write(&dir, "api-out.log.3", 100, 20);
+        write(&dir, "api-out.log.2", 100, 5);
+        write(&dir, "api-out.log.1", 100, 2);
+        write(&dir, "web-out.log.1", 100, 0);
+        write(&dir, ".web-out.log.2.gz.tmp", 100, 40);
+
+        let limits = Limits { max_days: Some(10), max_size: Some(String::from("250B")), compress: false };
+        let (pruned, errors) = enforce(Class::Logs, scan(Class::Logs, &dir), &limits, SystemTime::now());
+        let reasons: Vec<(&str, &str)> = pruned.iter().map(|p| (p.path.file_name().unwrap().to_str().unwrap(), p.reason)).collect();
+
+        assert!(errors.is_empty(), "{errors:?}");
+        assert_eq!(reasons, vec![("api-out.log.3", "max_days"), ("api-out.log.2", "max_size"), ("api-out.log.1", "max_size")]);
+        // The live log is never touched and the fresh one is skipped even though the class is over its size
+        assert_eq!(names(&dir, Class::Logs), vec!["api-out.log", "web-out.log.1"]);
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_compress() {
+        let dir = dir("compress");
+        write(&dir, "audit.jsonl", 10, 0);
+        write(&dir, "audit.jsonl.1", 4096, 3);
+
+        let limits = Limits { compress: true, ..Limits::default() };
+        let (pruned, errors) = enforce(Class::Audit, scan(Class::Audit, &dir), &limits, SystemTime::now());
+
+        assert!(errors.is_empty(), "{errors:?}");
+        assert_eq!(pruned[0].action, Action::Compressed);
+        assert_eq!(names(&dir, Class::Audit), vec!["audit.jsonl.1.gz", "audit.jsonl"]);
+
+        let mut contents = String::new();
+        flate2::read::GzDecoder::new(File::open(dir.join("audit.jsonl.1.gz")).unwrap()).read_to_string(&mut contents).unwrap();
+        assert_eq!(contents.len(), 4096);
+
+        // Already compressed files are left as they are on the next pass
+        let (pruned, _) = enforce(Class::Audit, scan(Class::Audit, &dir), &limits, SystemTime::now());
+        assert!(pruned.is_empty());
+
+        fs::remove_dir_all(&dir).ok();
+    }
+}
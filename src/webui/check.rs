@@ -0,0 +1,195 @@
+//! Self-check of the embedded WebUI, so a stale or missing build is reported instead of serving blank pages
+
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeSet, error::Error};
+use tera::{Context, Tera};
+use utoipa::ToSchema;
+
+/// Attributes the built pages link assets from
+const ATTRIBUTES: [&str; 5] = ["href", "src", "content", "component-url", "renderer-url"];
+
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, PartialEq)]
+#[schema(as = WebuiProblem)]
+pub struct Problem {
+    /// Template name or asset path
+    #[schema(example = "/assets/index.3f2a1c.js")]
+    pub item: String,
+    #[schema(example = "not embedded, linked from dashboard")]
+    pub detail: String,
+}
+
+/// Outcome of `opm daemon health --check-webui`, also logged at API startup and served by `/daemon/webui-status`
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, PartialEq)]
+#[schema(as = WebuiReport)]
+pub struct Report {
+    /// Every page renders and every asset it links to is embedded
+    pub ok: bool,
+    /// Release builds embed the built WebUI, debug builds only placeholder pages
+    pub built: bool,
+    #[schema(example = 6)]
+    pub templates: usize,
+    /// Distinct assets the pages link to
+    #[schema(example = 14)]
+    pub assets: usize,
+    pub unrenderable: Vec<Problem>,
+    pub missing: Vec<Problem>,
+}
+
+/// What the routes put in the context of the pages, with placeholder values
+fn context() -> Context {
+    let mut ctx = Context::new();
+    ctx.insert("base_path", "");
+    ctx.insert("build_version", env!("CARGO_PKG_VERSION"));
+    ctx.insert("process_id", &0);
+    ctx.insert("server_name", "local");
+    ctx
+}
+
+/// Paths of the embedded files a page links to, `/assets/index.3f2a1c.js` or `/static/style.css`
+pub fn references(html: &str) -> BTreeSet<String> {
+    let openings: Vec<String> = ATTRIBUTES.iter().map(|attr| format!("{attr}=\"")).collect();
+
+    openings
+        .iter()
+        .flat_map(|opening| html.split(opening.as_str()).skip(1))
+        .filter_map(|rest| rest.split('"').next())
+        .map(|link| link.split(['?', '#']).next().unwrap_or(link))
+        .filter(|link| link.starts_with("/assets/") || link.starts_with("/static/"))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Tera errors keep the cause, e.g. the missing variable, in their sources
+fn describe(err: &tera::Error) -> String {
+    let mut message = err.to_string();
+    let mut source = err.source();
+
+    while let Some(cause) = source {
+        message = format!("{message}: {cause}");
+        source = cause.source();
+    }
+
+    message
+}
+
+/// Render every template and look up the assets the pages link to with `embedded`
+pub fn check_with(tera: &Tera, built: bool, embedded: impl Fn(&str) -> bool) -> Report {
+    let mut names: Vec<&str> = tera.get_template_names().collect();
+    names.sort();
+
+    let (mut unrenderable, mut missing) = (vec![], vec![]);
+    let mut linked = BTreeSet::new();
+
+    for name in &names {
+        let html = match tera.render(name, &context()) {
+            Ok(html) => html,
+            Err(err) => {
+                unrenderable.push(Problem { item: name.to_string(), detail: describe(&err) });
+                continue;
+            }
+        };
+
+        for path in references(&html) {
+            if linked.insert(path.clone()) && !embedded(&path) {
+                missing.push(Problem { item: path, detail: format!("not embedded, linked from {name}") });
+            }
+        }
+    }
+
+    Report {
+        ok: built && unrenderable.is_empty() && missing.is_empty(),
+        built,
+        templates: names.len(),
+        assets: linked.len(),
+        unrenderable,
+        missing,
+    }
+}
+
+/// Check the pages of `tera` against the files built into this binary
+pub fn check(tera: &Tera) -> Report {
+    check_with(tera, cfg!(not(debug_assertions)), |path| match (path.strip_prefix("/assets/"), path.strip_prefix("/static/")) {
+        (Some(name), _) => super::asset(name).is_some(),
+        (_, Some(name)) => super::static_file(name).is_some(),
+        _ => false,
+    })
+}
+
+impl Report {
+    /// One line per problem, for the daemon log and the health output
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = vec![];
+        if !self.built {
+            lines.push(String::from("this is a debug build, it serves placeholder pages instead of the WebUI"));
+        }
+
+        lines.extend(self.unrenderable.iter().map(|problem| format!("template {} does not render: {}", problem.item, problem.detail)));
+        lines.extend(self.missing.iter().map(|problem| format!("asset {} is {}", problem.item, problem.detail)));
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAGE: &str = r#"<html><head>
+<link rel="icon" type="image/svg+xml" href="{{base_path | safe}}/assets/favicon.svg" />
+<link rel="stylesheet" href="{{base_path | safe}}/assets/index.3f2a1c.css?v=2">
+<meta property="og:image" content="{{base_path | safe}}/assets/banner.png" />
+</head><body>
+<astro-island component-url="{{base_path | safe}}/assets/login.9b1e.js" renderer-url="{{base_path | safe}}/assets/client.77aa.js"></astro-island>
+<a href="{{base_path | safe}}/servers">Servers</a> <a href="https://github.com/">GitHub</a>
+</body></html>"#;
+
+    fn tera(templates: &[(&str, &str)]) -> Tera {
+        let mut tera = Tera::default();
+        tera.add_raw_templates(templates.to_vec()).unwrap();
+        tera
+    }
+
+    #[test]
+    fn test_references() {
+        let html = tera(&[("login", PAGE)]).render("login", &context()).unwrap();
+
+        assert_eq!(
+            references(&html).into_iter().collect::<Vec<_>>(),
+            vec!["/assets/banner.png", "/assets/client.77aa.js", "/assets/favicon.svg", "/assets/index.3f2a1c.css", "/assets/login.9b1e.js"]
+        );
+    }
+
+    #[test]
+    fn test_check_reports_missing_assets_once() {
+        let view = "<script src=\"{{base_path}}/assets/login.9b1e.js\"></script> {{ process_id }}";
+        let tera = tera(&[("login", PAGE), ("view", view)]);
+
+        let report = check_with(&tera, true, |path| path != "/assets/login.9b1e.js" && path != "/assets/banner.png");
+        assert!(!report.ok);
+        assert_eq!((report.templates, report.assets), (2, 5));
+        assert_eq!(report.missing.iter().map(|problem| problem.item.as_str()).collect::<Vec<_>>(), vec!["/assets/banner.png", "/assets/login.9b1e.js"]);
+        assert_eq!(report.missing[0].detail, "not embedded, linked from login");
+
+        assert!(check_with(&tera, true, |_| true).ok);
+        assert!(!check_with(&tera, false, |_| true).ok);
+    }
+
+    #[test]
+    fn test_check_reports_unrenderable_templates() {
+        let tera = tera(&[("status", "{{ server_name }} {{ uptime }}"), ("servers", "{{ base_path }}")]);
+
+        let report = check_with(&tera, true, |_| true);
+        assert_eq!(report.unrenderable.len(), 1);
+        assert_eq!(report.unrenderable[0].item, "status");
+        assert!(report.unrenderable[0].detail.contains("uptime"), "{}", report.unrenderable[0].detail);
+        assert_eq!(report.lines(), vec![format!("template status does not render: {}", report.unrenderable[0].detail)]);
+    }
+
+    #[test]
+    fn test_placeholder_templates_render() {
+        let report = check(&super::super::templates());
+
+        assert_eq!(report.templates, 6);
+        assert!(report.unrenderable.is_empty());
+        assert_eq!(report.built, cfg!(not(debug_assertions)));
+    }
+}
@@ -1,9 +1,11 @@
+use include_dir::{include_dir, Dir, File};
 use opm::config;
 use tera::Tera;
 
-pub fn create_templates() -> (Tera, String) {
+pub fn create_templates() -> (Tera, String) { (templates(), config::read().get_path()) }
+
+pub fn templates() -> Tera {
     let mut tera = Tera::default();
-    let path = config::read().get_path();
 
     #[cfg(not(debug_assertions))]
     {
@@ -32,7 +34,29 @@ pub fn create_templates() -> (Tera, String) {
         .unwrap();
     }
 
-    return (tera, path.trim_end_matches('/').to_string());
+    return tera;
+}
+
+/// A file of the built `dist/assets`, served under `/assets`. Debug builds embed none.
+pub fn asset(name: &str) -> Option<&'static File<'static>> {
+    #[cfg(not(debug_assertions))]
+    {
+        static DIR: Dir = include_dir!("src/webui/dist/assets");
+        DIR.get_file(name)
+    }
+
+    #[cfg(debug_assertions)]
+    {
+        let _ = name;
+        None
+    }
+}
+
+/// A file of `src/daemon/static`, served under `/static`
+pub fn static_file(name: &str) -> Option<&'static File<'static>> {
+    static DIR: Dir = include_dir!("src/daemon/static");
+    DIR.get_file(name)
 }
 
 pub mod assets;
+pub mod check;
@@ -1,9 +1,9 @@
 import ky from 'ky';
-import { $settings } from '@/store';
 
 export { SSE } from 'sse.js';
 
-export const headers = { token: $settings.get().token };
+// The session cookie set by the login page authenticates every request
+export const headers = {};
 
 // Retry configuration for API requests
 const retryConfig = {
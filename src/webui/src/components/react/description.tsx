@@ -0,0 +1,112 @@
+import { api } from '@/api';
+import { useEffect, useState, useRef, forwardRef, useImperativeHandle } from 'react';
+import { CheckIcon, XMarkIcon } from '@heroicons/react/20/solid';
+
+// Same cap as the daemon, checked there too
+const MAX_BYTES = 1024;
+
+interface DescriptionProps {
+	base: string;
+	server: string;
+	process_id: number;
+	callback: () => void;
+	old: string | null;
+	onSuccess?: (msg: string) => void;
+	onError?: (msg: string) => void;
+}
+
+const Description = forwardRef((props: DescriptionProps, ref) => {
+	const [isEditing, setIsEditing] = useState(false);
+	const [formData, setFormData] = useState('');
+	const inputRef = useRef<HTMLTextAreaElement>(null);
+	const size = new TextEncoder().encode(formData).length;
+
+	useImperativeHandle(ref, () => ({
+		triggerEdit: () => setIsEditing(true)
+	}));
+
+	const handleSave = async () => {
+		const url =
+			props.server !== 'local' ? `${props.base}/remote/${props.server}/process/${props.process_id}` : `${props.base}/process/${props.process_id}`;
+
+		try {
+			// An empty description removes it
+			await api.patch(url, { json: { description: formData.trim() ? formData : null } });
+			setIsEditing(false);
+			props.callback();
+			props.onSuccess?.('Description saved');
+		} catch (err) {
+			props.onError?.(`Failed to save the description: ${err instanceof Error ? err.message : 'Unknown error'}`);
+		}
+	};
+
+	const handleCancel = () => {
+		setFormData(props.old ?? '');
+		setIsEditing(false);
+	};
+
+	const handleKeyDown = (e: React.KeyboardEvent) => {
+		if (e.key === 'Enter' && (e.ctrlKey || e.metaKey)) {
+			e.preventDefault();
+			handleSave();
+		} else if (e.key === 'Escape') {
+			handleCancel();
+		}
+	};
+
+	useEffect(() => {
+		setFormData(props.old ?? '');
+	}, [props.old]);
+
+	useEffect(() => {
+		if (isEditing && inputRef.current) {
+			inputRef.current.focus();
+		}
+	}, [isEditing]);
+
+	if (!isEditing) {
+		return props.old ? <p className="text-sm leading-6 text-zinc-300 whitespace-pre-wrap break-words">{props.old}</p> : null;
+	}
+
+	return (
+		<div className="flex items-start gap-2 mt-1" onClick={(e) => e.stopPropagation()}>
+			<div className="flex-1">
+				<textarea
+					ref={inputRef}
+					rows={3}
+					value={formData}
+					placeholder="What this process does, who owns it..."
+					onChange={(e) => setFormData(e.target.value)}
+					onKeyDown={handleKeyDown}
+					className="w-full px-2 py-1 bg-zinc-800 border border-zinc-600 rounded text-zinc-100 text-sm focus:outline-none focus:ring-2 focus:ring-blue-500 focus:border-blue-500"
+				/>
+				<span className={`text-xs ${size > MAX_BYTES ? 'text-red-400' : 'text-zinc-500'}`}>
+					{size}/{MAX_BYTES} bytes, ctrl+enter to save
+				</span>
+			</div>
+			<button
+				disabled={size > MAX_BYTES}
+				onClick={(e) => {
+					e.stopPropagation();
+					handleSave();
+				}}
+				className="disabled:opacity-50 p-1.5 bg-green-600 hover:bg-green-700 rounded text-white transition-colors shadow-sm hover:shadow-md"
+				title="Save">
+				<CheckIcon className="h-5 w-5" />
+			</button>
+			<button
+				onClick={(e) => {
+					e.stopPropagation();
+					handleCancel();
+				}}
+				className="p-1.5 bg-red-600 hover:bg-red-700 rounded text-white transition-colors shadow-sm hover:shadow-md"
+				title="Cancel">
+				<XMarkIcon className="h-5 w-5" />
+			</button>
+		</div>
+	);
+});
+
+Description.displayName = 'Description';
+
+export default Description;
@@ -23,6 +23,7 @@ type ProcessItem = {
 	watch: string;
 	agent_id?: string;
 	agent_name?: string;
+	url?: string;
 };
 
 const Index = (props: { base: string }) => {
@@ -38,8 +39,10 @@ const Index = (props: { base: string }) => {
 
 	const badge = {
 		online: 'bg-emerald-400',
+		starting: 'bg-cyan-400',
 		stopped: 'bg-red-500',
-		crashed: 'bg-amber-400'
+		crashed: 'bg-amber-400',
+		errored: 'bg-rose-500'
 	};
 
 	async function fetch() {
@@ -71,7 +74,7 @@ const Index = (props: { base: string }) => {
 	}
 
 	const isRemote = (item: ProcessItem): boolean => item.server !== 'local';
-	const isRunning = (status: string): boolean => !['stopped', 'crashed'].includes(status);
+	const isRunning = (status: string): boolean => !['stopped', 'crashed', 'errored'].includes(status);
 	const action = async (item: ProcessItem, name: string) => {
 		const endpoint = item.server === 'local' 
 			? `${props.base}/process/${item.id}/action`
@@ -261,6 +264,12 @@ const Index = (props: { base: string }) => {
 									className="transition inline-flex items-center justify-center space-x-1.5 border focus:outline-none focus:ring-0 focus:ring-offset-0 focus:z-10 shrink-0 border-amber-700 hover:border-amber-600 bg-amber-600 text-white hover:bg-amber-700 px-3 py-2 text-sm font-semibold rounded-lg">
 									Stop
 								</button>
+								<button
+									type="button"
+									onClick={() => bulkAction('flush')}
+									className="transition inline-flex items-center justify-center space-x-1.5 border focus:outline-none focus:ring-0 focus:ring-offset-0 focus:z-10 shrink-0 border-zinc-700 hover:border-zinc-600 bg-zinc-800 text-zinc-50 hover:bg-zinc-700 px-3 py-2 text-sm font-semibold rounded-lg">
+									Flush Logs
+								</button>
 								<button
 									type="button"
 									onClick={() => bulkAction('delete')}
@@ -326,8 +335,10 @@ const Index = (props: { base: string }) => {
 							className="w-full sm:w-auto px-4 py-2.5 bg-zinc-900/50 border border-zinc-700/50 rounded-lg text-zinc-200 focus:outline-none focus:ring-2 focus:ring-blue-500/50 focus:border-blue-500 transition-all">
 							<option value="all">All Status</option>
 							<option value="online">Online</option>
+							<option value="starting">Starting</option>
 							<option value="stopped">Stopped</option>
 							<option value="crashed">Crashed</option>
+							<option value="errored">Errored</option>
 						</select>
 					</div>
 					<div className="sm:w-auto w-full">
@@ -387,6 +398,15 @@ const Index = (props: { base: string }) => {
 												{item.agent_name}
 											</span>
 										)}
+										{item.url && (
+											<a
+												href={item.url}
+												target="_blank"
+												rel="noopener noreferrer"
+												className="text-xs font-medium text-sky-400 hover:text-sky-300 truncate">
+												Status page
+											</a>
+										)}
 									</div>
 								</div>
 								<span className="relative flex h-2.5 w-2.5 -mt-3.5">
@@ -511,7 +531,7 @@ const Index = (props: { base: string }) => {
 									</Transition>
 								</Menu>
 							</div>
-							<a href={isRemote(item) ? `./view/${item.id}?server=${item.server}` : `./view/${item.id}`} className="block transition-colors duration-200 hover:bg-zinc-900/20">
+							<a href={isRemote(item) ? `${props.base}/view/${item.id}?server=${item.server}` : `${props.base}/view/${item.id}`} className="block transition-colors duration-200 hover:bg-zinc-900/20">
 								<dl className="-my-3 divide-y divide-zinc-800/30 px-6 py-4 text-sm leading-6">
 									<div className="flex justify-between gap-x-2 py-2 transition-colors hover:text-zinc-300">
 										<dt className="text-zinc-600 font-medium">cpu usage</dt>
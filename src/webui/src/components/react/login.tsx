@@ -36,13 +36,17 @@ const Login = (props: { base: string }) => {
 
 	const handleSubmit = (event: any) => {
 		event.preventDefault();
-		$settings.setKey('token', token);
+		// Tokens kept by earlier versions, the session cookie replaces them
+		$settings.setKey('token', undefined);
 
-		fetch(props.base + '/daemon/metrics', {
-			headers: { token }
+		fetch(props.base + '/login', {
+			method: 'POST',
+			headers: { 'Content-Type': 'application/json' },
+			credentials: 'same-origin',
+			body: JSON.stringify({ token })
 		}).then((response) => {
 			if (response.status === 200) {
-				window.location.href = props.base;
+				window.location.href = props.base + '/';
 			} else {
 				setLoginFailed(true);
 				setTimeout(() => {
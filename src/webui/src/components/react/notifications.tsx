@@ -13,6 +13,39 @@ const TOGGLE_PIN_CLASSES = "inline-block h-5 w-5 sm:h-7 sm:w-7 rounded-full bg-w
 const getToggleClasses = (enabled: boolean) => `${TOGGLE_BASE_CLASSES} ${enabled ? 'bg-blue-600' : 'bg-zinc-700'}`;
 const getTogglePinClasses = (enabled: boolean) => TOGGLE_PIN_CLASSES;
 
+// One attempt to send to a channel, tokens in the channel and error are redacted by the daemon
+type Delivery = {
+	time: string;
+	event: string;
+	process?: string;
+	title: string;
+	channel: string;
+	success: boolean;
+	error?: string;
+};
+
+type NotificationHistory = {
+	channels: { channel: string; last: Delivery | null }[];
+	deliveries: Delivery[];
+};
+
+const DeliveryStatus = ({ delivery }: { delivery: Delivery | null | undefined }) => {
+	if (!delivery) {
+		return <span className="text-xs text-zinc-500">Never sent</span>;
+	}
+
+	const time = new Date(delivery.time).toLocaleString();
+	return delivery.success ? (
+		<span className="text-xs text-emerald-400" title={time}>
+			Delivered {time}
+		</span>
+	) : (
+		<span className="text-xs text-red-400" title={delivery.error}>
+			Failed {time}
+		</span>
+	);
+};
+
 const NotificationSettings = (props: { base: string }) => {
 	const { toasts, closeToast, success, error } = useToast();
 	const [loading, setLoading] = useState(true);
@@ -26,16 +59,31 @@ const NotificationSettings = (props: { base: string }) => {
 			process_start: false,
 			process_stop: false,
 			process_crash: false,
-			process_restart: false
+			process_restart: false,
+			process_alert: false
 		},
 		channels: [] as string[]
 	});
 	const [newChannel, setNewChannel] = useState('');
+	const [deliveries, setDeliveries] = useState<Delivery[]>([]);
+	// Last delivery of each saved channel, keyed by the channel as configured
+	const [lastDelivery, setLastDelivery] = useState<Record<string, Delivery | null>>({});
+
+	async function fetchHistory(channels: string[]) {
+		try {
+			const history: NotificationHistory = await api.get(`${props.base}/daemon/notifications/history?limit=20`).json();
+			setDeliveries([...history.deliveries].reverse());
+			setLastDelivery(Object.fromEntries(channels.map((channel, index) => [channel, history.channels[index]?.last ?? null])));
+		} catch (error) {
+			console.error('Failed to fetch notification history:', error);
+		}
+	}
 
 	async function fetchSettings() {
 		try {
-			const response = await api.get(`${props.base}/daemon/config/notifications`).json();
+			const response: any = await api.get(`${props.base}/daemon/config/notifications`).json();
 			setSettings(response || settings);
+			await fetchHistory(response?.channels || []);
 		} catch (error) {
 			console.error('Failed to fetch notification settings:', error);
 		} finally {
@@ -71,6 +119,7 @@ const NotificationSettings = (props: { base: string }) => {
 			error('Failed to send test notification: ' + (err as Error).message);
 		} finally {
 			setTesting(false);
+			fetchHistory(settings.channels);
 		}
 	}
 
@@ -220,8 +269,9 @@ const NotificationSettings = (props: { base: string }) => {
 						<div className="space-y-2">
 							{settings.channels.map((channel, index) => (
 								<div key={index} className="flex items-center justify-between bg-zinc-800 border border-zinc-700 rounded-lg p-3">
-									<div className="flex-1 font-mono text-sm text-zinc-300 truncate mr-4">
-										{channel}
+									<div className="flex-1 min-w-0 mr-4">
+										<div className="font-mono text-sm text-zinc-300 truncate">{channel}</div>
+										<DeliveryStatus delivery={lastDelivery[channel]} />
 									</div>
 									<button
 										onClick={() => removeChannel(index)}
@@ -247,6 +297,35 @@ const NotificationSettings = (props: { base: string }) => {
 						</div>
 					</div>
 				</div>
+
+				{/* Recent Deliveries */}
+				<div className="bg-zinc-900 border border-zinc-800 rounded-lg p-6">
+					<h3 className="text-lg font-semibold text-zinc-200 mb-2">Recent Deliveries</h3>
+					<p className="text-sm text-zinc-400 mb-4">The last notifications sent to each channel, newest first</p>
+
+					{deliveries.length === 0 ? (
+						<div className="text-center py-4 text-zinc-400 text-sm">No notifications sent yet</div>
+					) : (
+						<div className="space-y-2">
+							{deliveries.map((delivery, index) => (
+								<div key={index} className="bg-zinc-800 border border-zinc-700 rounded-lg p-3">
+									<div className="flex items-center justify-between gap-4">
+										<div className="text-sm text-zinc-200 truncate">
+											{delivery.title}
+											<span className="ml-2 text-xs text-zinc-500">
+												{delivery.event}
+												{delivery.process && ` · ${delivery.process}`}
+											</span>
+										</div>
+										<DeliveryStatus delivery={delivery} />
+									</div>
+									<div className="font-mono text-xs text-zinc-400 truncate mt-1">{delivery.channel}</div>
+									{delivery.error && <div className="text-xs text-red-400 mt-1 break-words">{delivery.error}</div>}
+								</div>
+							))}
+						</div>
+					)}
+				</div>
 			</div>
 		</Fragment>
 	);
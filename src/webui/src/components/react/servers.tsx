@@ -17,11 +17,11 @@ const Index = (props: { base: string }) => {
 		offline: 'bg-red-500/10 text-red-500'
 	};
 
-	// Auto-detect server host from current URL
+	// Auto-detect server host from current URL, including the base path the daemon is served under
 	useEffect(() => {
 		const hostname = window.location.hostname;
 		const port = window.location.port || '9876';
-		setServerHost(`${hostname}:${port}`);
+		setServerHost(`${hostname}:${port}${props.base}`);
 	}, []);
 
 	async function fetchAgents() {
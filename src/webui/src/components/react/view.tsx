@@ -3,6 +3,7 @@ import { Switch } from '@headlessui/react';
 import { matchSorter } from 'match-sorter';
 import Loader from '@/components/react/loader';
 import InlineRename from '@/components/react/inline-rename';
+import Description from '@/components/react/description';
 import { useEffect, useState, useRef, Fragment } from 'react';
 import { classNames, isRunning, formatMemory, startDuration } from '@/helpers';
 import { EllipsisVerticalIcon, CheckIcon, ChevronUpDownIcon } from '@heroicons/react/20/solid';
@@ -241,11 +242,14 @@ const View = (props: { id: string; base: string }) => {
 	const [live, setLive] = useState<SSE | null>(null);
 	const [liveReload, setLiveReload] = useState(false);
 	const renameRef = useRef<{ triggerEdit: () => void }>(null);
+	const descriptionRef = useRef<{ triggerEdit: () => void }>(null);
 
 	const badge = {
 		online: 'bg-emerald-400/10 text-emerald-400',
+		starting: 'bg-cyan-400/10 text-cyan-400',
 		stopped: 'bg-red-500/10 text-red-500',
-		crashed: 'bg-amber-400/10 text-amber-400'
+		crashed: 'bg-amber-400/10 text-amber-400',
+		errored: 'bg-rose-500/10 text-rose-500'
 	};
 
 	const server = new URLSearchParams(window.location.search).get('server') ?? 'local';
@@ -307,6 +311,21 @@ const View = (props: { id: string; base: string }) => {
 		}
 	};
 
+	const downloadLogs = async (id: number) => {
+		try {
+			const blob = await api.get(`${props.base}/process/${id}/logs/archive`, { timeout: false }).blob();
+			const url = URL.createObjectURL(blob);
+			const link = document.createElement('a');
+
+			link.href = url;
+			link.download = `${item.info.name.replace(/[\/ "]/g, '_')}-${id}-logs.tar.gz`;
+			link.click();
+			URL.revokeObjectURL(url);
+		} catch (err) {
+			error(`Failed to download logs: ${(err as Error).message}`);
+		}
+	};
+
 	if (!loaded) {
 		return <Loader />;
 	} else {
@@ -358,7 +377,26 @@ const View = (props: { id: string; base: string }) => {
 								</div>
 							)}
 						</div>
-						<p className="text-xs leading-6 text-zinc-400">{item.info.command}</p>
+						<Description
+							ref={descriptionRef}
+							base={props.base}
+							server={server}
+							process_id={parseInt(props.id)}
+							callback={openConnection}
+							old={item.info.description ?? null}
+							onSuccess={success}
+							onError={error}
+						/>
+						<p className="text-xs leading-6 text-zinc-400 whitespace-pre-wrap break-all">{item.info.command}</p>
+						{item.info.url && (
+							<a
+								href={item.info.url}
+								target="_blank"
+								rel="noopener noreferrer"
+								className="text-xs leading-6 text-sky-400 hover:text-sky-300 break-all">
+								{item.info.url}
+							</a>
+						)}
 					</div>
 					<div className="flex lg:ml-4 mt-0">
 						<span>
@@ -448,6 +486,23 @@ const View = (props: { id: string; base: string }) => {
 													</button>
 												)}
 											</MenuItem>
+											<MenuItem>
+												{({ focus, close }) => (
+													<button
+														onClick={(e) => {
+															e.preventDefault();
+															e.stopPropagation();
+															descriptionRef.current?.triggerEdit();
+															close();
+														}}
+														className={classNames(
+															focus ? 'bg-zinc-800/80 text-zinc-50' : 'text-zinc-200',
+															'rounded-md block p-2 w-full text-left cursor-pointer'
+														)}>
+														Edit Description
+													</button>
+												)}
+											</MenuItem>
 											<MenuItem>
 												{({ _ }) => (
 													<a
@@ -460,6 +515,20 @@ const View = (props: { id: string; base: string }) => {
 													</a>
 												)}
 											</MenuItem>
+											{server == 'local' && (
+												<MenuItem>
+													{({ focus }) => (
+														<a
+															onClick={() => downloadLogs(props.id)}
+															className={classNames(
+																focus ? 'bg-zinc-800/80 text-zinc-50' : 'text-zinc-200',
+																'rounded-md block p-2 w-full text-left cursor-pointer'
+															)}>
+															Download Logs
+														</a>
+													)}
+												</MenuItem>
+											)}
 										</div>
 										<div className="p-1.5">
 											<MenuItem>
@@ -2,7 +2,7 @@ import { useState } from 'react';
 
 export const classNames = (...classes: Array<any>) => classes.filter(Boolean).join(' ');
 
-export const isRunning = (status: string): boolean => (status == 'stopped' ? false : status == 'crashed' ? false : true);
+export const isRunning = (status: string): boolean => !['stopped', 'crashed', 'errored'].includes(status);
 
 export const formatMemory = (bytes: number): [number, string] => {
 	const units = ['b', 'kb', 'mb', 'gb'];
@@ -0,0 +1,86 @@
+//! A process forking short-lived children the whole time, watched by a real daemon
+
+mod common;
+
+use common::{Daemon, Home};
+
+use std::{
+    fs,
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
+
+/// Forks a child every few milliseconds, each one gone a moment later, and one that stays.
+/// Wrapped in its own shell, opm watches the first process its shell starts.
+const FORKING: &str = "sh -c 'sleep 60 & while :; do sleep 0.01 & sleep 0.005; done'";
+
+impl Daemon {
+    fn modified(&self) -> Option<SystemTime> { fs::metadata(self.home.0.join(".opm/process.dump")).and_then(|meta| meta.modified()).ok() }
+
+    /// Times the dump was written during `window`
+    fn dump_writes(&self, window: Duration) -> usize {
+        let (deadline, mut last, mut writes) = (Instant::now() + window, self.modified(), 0);
+
+        while Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(5));
+            let modified = self.modified();
+            if modified != last {
+                (last, writes) = (modified, writes + 1);
+            }
+        }
+        writes
+    }
+
+    /// Pids of the long-lived child of `FORKING`, started with this home
+    fn sleepers(&self) -> Vec<u32> {
+        let home = format!("HOME={}", self.home.0.display());
+        let read = |pid: u32, file: &str| fs::read(format!("/proc/{pid}/{file}")).unwrap_or_default();
+
+        fs::read_dir("/proc")
+            .unwrap()
+            .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse::<u32>().ok())
+            .filter(|&pid| read(pid, "cmdline") == b"sleep\x0060\0")
+            .filter(|&pid| read(pid, "environ").split(|&byte| byte == 0).any(|var| var == home.as_bytes()))
+            .collect()
+    }
+
+    /// Seconds of cpu the daemon used so far
+    fn cpu_seconds(&self) -> f64 {
+        let pid = fs::read_to_string(self.home.0.join(".opm/daemon.pid")).unwrap();
+        let stat = fs::read_to_string(format!("/proc/{}/stat", pid.trim())).unwrap();
+        let fields: Vec<&str> = stat.rsplit_once(')').unwrap().1.split_whitespace().collect();
+        let ticks: u64 = fields[11].parse::<u64>().unwrap() + fields[12].parse::<u64>().unwrap();
+        ticks as f64 / unsafe { libc::sysconf(libc::_SC_CLK_TCK) } as f64
+    }
+}
+
+#[test]
+fn test_forking_parent_keeps_dump_writes_bounded() {
+    let daemon = Daemon::start(Home::new("children", "forking"));
+    daemon.opm(&["start", FORKING, "--name", "ci", "--monitor-interval", "100"]);
+    thread::sleep(Duration::from_secs(1));
+
+    // About 30 cycles, the children change in every one of them
+    let cpu = daemon.cpu_seconds();
+    let tracked = daemon.dump_writes(Duration::from_secs(3));
+    assert!(tracked <= 6, "{tracked} dump writes in 3s with children tracked");
+
+    daemon.opm(&["adjust", "ci", "--no-track-children"]);
+    thread::sleep(Duration::from_millis(300));
+
+    let untracked = daemon.dump_writes(Duration::from_secs(3));
+    assert!(untracked <= 1, "{untracked} dump writes in 3s without children tracked");
+
+    // The daemon keeps up with its cycles instead of spending them walking the tree
+    let used = daemon.cpu_seconds() - cpu;
+    assert!(used < 3.0, "the daemon used {used:.2}s of cpu in 6s");
+
+    let info = String::from_utf8_lossy(&daemon.opm(&["info", "ci"]).stdout).to_string();
+    assert!(info.contains("not tracked"), "{info}");
+
+    // Stop still takes the children down, looked up right then
+    assert_eq!(daemon.sleepers().len(), 1);
+    daemon.opm(&["stop", "ci"]);
+    thread::sleep(Duration::from_millis(300));
+    assert_eq!(daemon.sleepers(), Vec::<u32>::new());
+}
@@ -0,0 +1,196 @@
+//! Helpers shared by the integration tests, each test file uses only some of them
+#![allow(dead_code)]
+
+use std::{
+    fs,
+    io::{Read, Write},
+    net::{TcpListener, TcpStream},
+    os::unix::net::UnixStream,
+    path::PathBuf,
+    process::{Child, Command, ExitStatus, Output, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// An opm home of its own, so tests never touch the processes of the user running them
+pub struct Home(pub PathBuf);
+
+impl Home {
+    /// An empty home for the test `name` of `suite`
+    pub fn new(suite: &str, name: &str) -> Home {
+        let path = std::env::temp_dir().join(format!("opm-{suite}-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        Home(path)
+    }
+
+    /// The opm binary with this home
+    pub fn command(&self) -> Command {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_opm"));
+        command.env("HOME", &self.0).stdin(Stdio::null());
+        command
+    }
+
+    pub fn run(&self, args: &[&str]) -> Output { self.command().arg("--no-daemon").args(args).output().unwrap() }
+
+    pub fn opm(&self, args: &[&str]) -> Output {
+        let output = self.run(args);
+        assert!(output.status.success(), "opm {args:?}: {}", String::from_utf8_lossy(&output.stderr));
+        output
+    }
+
+    pub fn details(&self, name: &str) -> serde_json::Value { serde_json::from_slice(&self.opm(&["details", name, "--format", "json"]).stdout).unwrap() }
+}
+
+impl Drop for Home {
+    fn drop(&mut self) {
+        let _ = self.command().args(["--no-daemon", "stop", "all"]).output();
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// A daemon with an opm home of its own, stopped again when the test ends
+pub struct Daemon {
+    pub home: Home,
+    /// Port of the API, 0 when the daemon serves none
+    pub port: u16,
+    /// What runs a foreground daemon, none once the daemon went to the background
+    child: Option<Child>,
+    /// Run through a launcher like unshare, whose pid namespace goes away with it
+    launched: bool,
+}
+
+impl Daemon {
+    /// A daemon gone to the background through `opm daemon start`
+    pub fn start(home: Home) -> Daemon {
+        // The daemon keeps inherited pipes open, so its output is discarded rather than captured
+        let status = home.command().args(["daemon", "start"]).stdout(Stdio::null()).stderr(Stdio::null()).status().unwrap();
+        assert!(status.success(), "opm daemon start failed");
+        Daemon { home, port: 0, child: None, launched: false }.ready()
+    }
+
+    /// A daemon in the foreground, run through `launcher` and its arguments when there is one,
+    /// which keeps the processes of the daemon to itself like unshare
+    pub fn foreground(home: Home, launcher: &[&str]) -> Daemon { Daemon::spawn(home, 0, launcher) }
+
+    /// A foreground daemon serving the API on a free port, behind `token` when there is one.
+    /// `daemon` and `web` are more lines of those tables of config.toml
+    pub fn serve(home: Home, token: Option<&str>, daemon: &str, web: &str) -> Daemon {
+        let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let config = format!(
+            "default = \"local\"\n\n[runner]\nshell = \"/bin/sh\"\nargs = [\"-c\"]\nnode = \"node\"\nlog_path = \"{}\"\n\n\
+             [daemon]\nrestarts = 10\ninterval = 1000\nkind = \"default\"\n{daemon}\n\
+             [daemon.web]\napi = true\nui = false\naddress = \"127.0.0.1\"\nport = {port}\n{web}\n\
+             [daemon.web.secure]\nenabled = {}\ntoken = \"{}\"\n",
+            home.0.join(".opm/logs").display(),
+            token.is_some(),
+            token.unwrap_or_default()
+        );
+        fs::create_dir_all(home.0.join(".opm")).unwrap();
+        fs::write(home.0.join(".opm/config.toml"), config).unwrap();
+
+        Daemon::spawn(home, port, &[])
+    }
+
+    fn spawn(home: Home, port: u16, launcher: &[&str]) -> Daemon {
+        let mut command = match launcher {
+            [] => Command::new(env!("CARGO_BIN_EXE_opm")),
+            [program, args @ ..] => {
+                let mut command = Command::new(program);
+                command.args(args).arg(env!("CARGO_BIN_EXE_opm"));
+                command
+            }
+        };
+
+        let child = command
+            .args(["daemon", "start", "--foreground"])
+            .env("HOME", &home.0)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+
+        Daemon { home, port, child: Some(child), launched: !launcher.is_empty() }.ready()
+    }
+
+    /// Waits for the command socket, and for the API when the daemon serves one
+    fn ready(self) -> Daemon {
+        let deadline = Instant::now() + Duration::from_secs(20);
+        while UnixStream::connect(self.home.0.join(".opm/daemon.sock")).is_err() || self.port != 0 && TcpStream::connect(("127.0.0.1", self.port)).is_err() {
+            assert!(Instant::now() < deadline, "timed out waiting for the daemon");
+            thread::sleep(Duration::from_millis(50));
+        }
+        self
+    }
+
+    pub fn opm(&self, args: &[&str]) -> Output { self.home.opm(args) }
+
+    /// Pid of what runs a foreground daemon
+    pub fn pid(&self) -> u32 { self.child.as_ref().expect("the daemon runs in the background").id() }
+
+    /// Exit status of a foreground daemon once it exited
+    pub fn try_wait(&mut self) -> Option<ExitStatus> { self.child.as_mut().expect("the daemon runs in the background").try_wait().unwrap() }
+}
+
+impl Drop for Daemon {
+    fn drop(&mut self) {
+        match &mut self.child {
+            Some(child) => {
+                let _ = child.kill();
+                let _ = child.wait();
+                // Pids of another namespace name other processes out here, so the home must not stop them
+                if self.launched {
+                    let _ = fs::remove_file(self.home.0.join(".opm/process.dump"));
+                }
+            }
+            None => {
+                let _ = self.home.command().args(["daemon", "stop"]).stdout(Stdio::null()).stderr(Stdio::null()).status();
+            }
+        }
+    }
+}
+
+/// Status, headers and body of an answer of the API
+pub struct Answer {
+    pub status: u16,
+    pub head: String,
+    pub body: String,
+}
+
+impl Answer {
+    /// Values of every header called `name`
+    pub fn headers(&self, name: &str) -> Vec<String> {
+        self.head
+            .lines()
+            .filter_map(|line| line.split_once(':'))
+            .filter(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.trim().to_string())
+            .collect()
+    }
+}
+
+/// Sends a request with `headers` to the API on `port`, closing the connection after the answer
+pub fn request(port: u16, method: &str, path: &str, headers: &[(&str, &str)], body: &str) -> Answer {
+    let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+    let mut request = format!("{method} {path} HTTP/1.1\r\nHost: 127.0.0.1\r\nConnection: close\r\nContent-Length: {}\r\n", body.len());
+    if !body.is_empty() {
+        request.push_str("Content-Type: application/json\r\n");
+    }
+    for (name, value) in headers {
+        request.push_str(&format!("{name}: {value}\r\n"));
+    }
+    request.push_str("\r\n");
+    request.push_str(body);
+    stream.write_all(request.as_bytes()).unwrap();
+
+    let mut answer = String::new();
+    stream.read_to_string(&mut answer).unwrap();
+    let (head, body) = answer.split_once("\r\n\r\n").unwrap_or((&answer, ""));
+
+    Answer {
+        status: head.split_whitespace().nth(1).unwrap().parse().unwrap(),
+        head: head.to_string(),
+        body: body.to_string(),
+    }
+}
@@ -0,0 +1,165 @@
+//! Drives the command socket of a real daemon started from the opm binary
+
+mod common;
+
+use common::{Daemon, Home};
+use serde_json::{Value, json};
+
+use std::{
+    fs,
+    io::{BufRead, BufReader, Write},
+    os::unix::{fs::PermissionsExt, net::UnixStream},
+    path::PathBuf,
+    thread,
+    time::{Duration, Instant},
+};
+
+impl Daemon {
+    fn socket(&self) -> PathBuf { self.home.0.join(".opm/daemon.sock") }
+
+    fn connect(&self) -> Connection {
+        let stream = UnixStream::connect(self.socket()).unwrap();
+        stream.set_read_timeout(Some(Duration::from_secs(30))).unwrap();
+        Connection { reader: BufReader::new(stream.try_clone().unwrap()), writer: stream }
+    }
+}
+
+/// A daemon whose config.toml ends with `config`
+fn start(name: &str, config: &str) -> Daemon {
+    let home = Home::new("ipc", name);
+    if !config.is_empty() {
+        // Any command writes the default config first
+        home.opm(&["list"]);
+        let file = home.0.join(".opm/config.toml");
+        fs::write(&file, fs::read_to_string(&file).unwrap() + config).unwrap();
+    }
+    Daemon::start(home)
+}
+
+struct Connection {
+    reader: BufReader<UnixStream>,
+    writer: UnixStream,
+}
+
+impl Connection {
+    /// Send one request line, None when the daemon hung up instead of answering
+    fn send(&mut self, request: Value) -> Option<Value> {
+        writeln!(self.writer, "{request}").ok()?;
+        let mut answer = String::new();
+        match self.reader.read_line(&mut answer).unwrap_or(0) {
+            0 => None,
+            _ => Some(serde_json::from_str(&answer).unwrap()),
+        }
+    }
+}
+
+#[test]
+fn test_daemon_socket() {
+    let daemon = start("socket", "");
+    assert_eq!(fs::metadata(daemon.socket()).unwrap().permissions().mode() & 0o777, 0o600);
+
+    daemon.opm(&["start", "sleep 300", "--name", "sleeper"]);
+
+    let mut conn = daemon.connect();
+    let hello = conn.send(json!({ "type": "hello", "version": 1 })).unwrap();
+    assert_eq!(hello["ok"], true);
+    assert_eq!(hello["data"]["version"], 1);
+
+    let list = conn.send(json!({ "type": "list" })).unwrap();
+    assert_eq!(list["data"][0]["name"], "sleeper");
+
+    let stop = conn.send(json!({ "type": "action", "id": 0, "method": "stop" })).unwrap();
+    assert_eq!(stop["data"], json!({ "done": true, "action": "stop" }));
+
+    let info = conn.send(json!({ "type": "info", "id": 0 })).unwrap();
+    assert_eq!(info["data"]["info"]["status"], "stopped");
+
+    let missing = conn.send(json!({ "type": "action", "id": 42, "method": "stop" })).unwrap();
+    assert_eq!(missing, json!({ "ok": false, "error": "Process was not found" }));
+
+    let health = conn.send(json!({ "type": "health" })).unwrap();
+    assert_eq!(health["data"]["pid"], hello["data"]["pid"]);
+    assert_eq!(health["data"]["process_count"], 1);
+
+    let audit = fs::read_to_string(daemon.home.0.join(".opm/audit.jsonl")).unwrap();
+    assert!(audit.lines().any(|line| line.contains(r#""action":"stop""#) && line.contains(r#""via":"ipc""#)), "{audit}");
+
+    // The CLI reports the daemon through its socket
+    let report: Value = serde_json::from_slice(&daemon.opm(&["daemon", "health", "--format", "json"]).stdout).unwrap();
+    assert_eq!(report["status"], "online");
+    assert!(report["socket"].as_str().unwrap().ends_with("(answering)"), "{report}");
+}
+
+#[test]
+fn test_daemon_socket_version() {
+    let daemon = start("version", "");
+
+    let mut conn = daemon.connect();
+    let answer = conn.send(json!({ "type": "hello", "version": 99 })).unwrap();
+    assert_eq!(answer["ok"], false);
+    assert_eq!(conn.send(json!({ "type": "list" })), None);
+
+    let mut conn = daemon.connect();
+    assert_eq!(conn.send(json!({ "type": "list" })).unwrap()["error"], "Send hello with the protocol version first");
+}
+
+#[test]
+fn test_actions_queue_behind_running_one() {
+    let daemon = start("queue", "");
+    // Ignoring SIGTERM keeps the stop busy until the daemon gives up waiting and kills it
+    daemon.opm(&["start", "trap '' TERM; sleep 300", "--name", "stubborn"]);
+
+    let mut stopping = daemon.connect();
+    stopping.send(json!({ "type": "hello", "version": 1 })).unwrap();
+    let stop = thread::spawn(move || stopping.send(json!({ "type": "action", "id": 0, "method": "stop" })).unwrap());
+    thread::sleep(Duration::from_millis(500));
+
+    let mut conn = daemon.connect();
+    conn.send(json!({ "type": "hello", "version": 1 })).unwrap();
+
+    let restart = conn.send(json!({ "type": "action", "id": 0, "method": "restart" })).unwrap();
+    assert_eq!(restart["data"], json!({ "done": true, "action": "restart", "queued": true, "position": 1 }));
+    // A second restart right behind the first one is the same
+    let again = conn.send(json!({ "type": "action", "id": 0, "method": "restart" })).unwrap();
+    assert_eq!(again["data"]["position"], 1);
+
+    assert_eq!(conn.send(json!({ "type": "queue", "id": 0 })).unwrap()["data"], json!(["restart"]));
+    let details: Value = serde_json::from_slice(&daemon.opm(&["details", "stubborn", "--format", "json"]).stdout).unwrap();
+    assert_eq!(details["queued"], json!(["restart"]));
+
+    assert_eq!(stop.join().unwrap()["data"], json!({ "done": true, "action": "stop" }));
+
+    // The restart runs once the stop is done
+    let deadline = Instant::now() + Duration::from_secs(15);
+    loop {
+        let info = conn.send(json!({ "type": "info", "id": 0 })).unwrap();
+        let pending = conn.send(json!({ "type": "queue", "id": 0 })).unwrap();
+        if info["data"]["info"]["status"] != "stopped" && pending["data"] == json!([]) {
+            break;
+        }
+        assert!(Instant::now() < deadline, "the queued restart did not run: {info}");
+        thread::sleep(Duration::from_millis(100));
+    }
+}
+
+#[test]
+fn test_tiny_self_limits_trim_caches() {
+    let daemon = start("limits", "\n[daemon.self_limits]\nmax_rss = \"1K\"\n");
+    let log = daemon.home.0.join(".opm/daemon.log");
+
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while !fs::read_to_string(&log).unwrap_or_default().contains("trimmed its caches") {
+        assert!(Instant::now() < deadline, "the daemon did not trim: {}", fs::read_to_string(&log).unwrap_or_default());
+        thread::sleep(Duration::from_millis(100));
+    }
+    assert!(fs::read_to_string(&log).unwrap().contains("over its memory limit"));
+
+    let mut conn = daemon.connect();
+    conn.send(json!({ "type": "hello", "version": 1 })).unwrap();
+    let health = conn.send(json!({ "type": "health" })).unwrap();
+    assert_eq!(health["data"]["limits"]["max_rss"], 1024, "{health}");
+    assert!(health["data"]["limits"]["rss"].as_u64().unwrap() > 1024, "{health}");
+
+    let report: Value = serde_json::from_slice(&daemon.opm(&["daemon", "health", "--format", "json"]).stdout).unwrap();
+    assert_eq!(report["limits"]["max_rss"], 1024, "{report}");
+}
@@ -0,0 +1,47 @@
+//! Machine readable output of `opm list`, through the opm binary
+
+mod common;
+
+use common::Home;
+
+impl Home {
+    fn stdout(&self, args: &[&str]) -> String { String::from_utf8(self.opm(args).stdout).unwrap() }
+}
+
+#[test]
+fn test_list_json() {
+    let home = Home::new("list", "json");
+    assert_eq!(home.stdout(&["list", "--format", "json"]).trim(), "[]");
+
+    home.opm(&["start", "sleep 300", "--name", "api", "--label", "tier=web"]);
+    home.opm(&["start", "sleep 300", "--name", "worker"]);
+
+    // Nothing but the array on stdout, so it can be piped into jq
+    let processes: Vec<serde_json::Value> = serde_json::from_str(&home.stdout(&["list", "--format", "json"])).unwrap();
+    assert_eq!(processes.len(), 2);
+    for key in ["id", "pid", "cpu", "mem", "restarts", "uptime", "watch_path", "start_time", "status"] {
+        assert!(processes[0].get(key).is_some(), "{key} missing from {}", processes[0]);
+    }
+    assert_eq!(processes[0]["name"], "api");
+    assert!(processes[0]["pid"].as_i64().unwrap() > 0);
+
+    let web: Vec<serde_json::Value> = serde_json::from_str(&home.stdout(&["list", "--format", "json", "--label", "tier=web"])).unwrap();
+    assert_eq!(web.len(), 1);
+    assert_eq!(web[0]["name"], "api");
+}
+
+#[test]
+fn test_list_minimal() {
+    let home = Home::new("list", "minimal");
+    // Online right away, not starting for a second
+    home.opm(&["start", "sleep 300", "--name", "api", "--startup-grace", "0"]);
+    home.opm(&["start", "sleep 300", "--name", "worker", "--startup-grace", "0"]);
+
+    let text = home.stdout(&["list", "--format", "minimal", "--columns", "cpu,mem"]);
+    let lines: Vec<Vec<&str>> = text.lines().map(|line| line.split('\t').collect()).collect();
+
+    assert_eq!(lines.len(), 2, "{text}");
+    assert_eq!(lines[0][..3], ["0", "api", "online"]);
+    assert_eq!(lines[1][..3], ["1", "worker", "online"]);
+    assert!(lines[0][3].parse::<u32>().unwrap() > 0);
+}
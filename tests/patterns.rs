@@ -0,0 +1,51 @@
+//! Processes targeted by name patterns like `api-*`, through the opm binary
+
+mod common;
+
+use common::Home;
+
+impl Home {
+    /// Everything a failing command printed
+    fn failure(&self, args: &[&str]) -> String {
+        let output = self.run(args);
+        assert!(!output.status.success(), "opm {args:?} succeeded");
+        format!("{}{}", String::from_utf8_lossy(&output.stdout), String::from_utf8_lossy(&output.stderr))
+    }
+
+    fn pid(&self, name: &str) -> serde_json::Value { self.details(name)["pid"].clone() }
+}
+
+#[test]
+fn test_restart_pattern() {
+    let home = Home::new("patterns", "restart");
+    for name in ["api-users", "api-orders", "worker"] {
+        home.opm(&["start", "sleep 300", "--name", name]);
+    }
+    let pids: Vec<_> = ["api-users", "api-orders", "worker"].iter().map(|name| home.pid(name)).collect();
+
+    home.opm(&["restart", "api-*"]);
+
+    assert_ne!(home.pid("api-users"), pids[0]);
+    assert_ne!(home.pid("api-orders"), pids[1]);
+    assert_eq!(home.pid("worker"), pids[2]);
+
+    let text = home.failure(&["restart", "db-*"]);
+    assert!(text.contains("No processes match pattern 'db-*'"), "{text}");
+}
+
+#[test]
+fn test_single_process_pattern() {
+    let home = Home::new("patterns", "single");
+    for name in ["api-users", "api-orders", "lit*eral"] {
+        home.opm(&["start", "sleep 300", "--name", name]);
+    }
+
+    // A pattern matching a single process works where one is expected
+    assert_eq!(home.pid("api-u*"), home.pid("api-users"));
+
+    let text = home.failure(&["details", "api-*"]);
+    assert!(text.contains("matches 2 processes") && text.contains("api-users (0)") && text.contains("api-orders (1)"), "{text}");
+
+    // Quoted, the name is taken as written
+    assert_eq!(home.pid("name:'lit*eral'"), home.pid("lit*"));
+}
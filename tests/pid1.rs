@@ -0,0 +1,94 @@
+//! The daemon as pid 1 of a pid namespace of its own, the way it runs as the entrypoint of a container
+
+mod common;
+
+use common::{Daemon, Home};
+
+use std::{
+    fs,
+    process::{Command, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Leaves an orphan for pid 1 to take over, and keeps running itself
+const ORPHANING: &str = "(sleep 1000 &); exec sleep 999";
+
+/// Runs what follows as pid 1 of a new pid namespace, killed along with unshare
+const UNSHARE: [&str; 7] = ["unshare", "--user", "--map-root-user", "--pid", "--fork", "--mount-proc", "--kill-child"];
+
+impl Daemon {
+    /// Pid of the daemon outside its namespace, the child unshare forked
+    fn daemon(&self) -> u32 { wait_for(|| children(self.pid()).first().copied()) }
+
+    fn log(&self) -> String { fs::read_to_string(self.home.0.join(".opm/daemon.log")).unwrap_or_default() }
+}
+
+/// A foreground daemon under unshare, None where unprivileged user namespaces are not available
+fn start(name: &str) -> Option<Daemon> {
+    let available = Command::new(UNSHARE[0]).args(&UNSHARE[1..]).arg("true").stdout(Stdio::null()).stderr(Stdio::null()).status();
+    if !available.is_ok_and(|status| status.success()) {
+        return None;
+    }
+
+    Some(Daemon::foreground(Home::new("pid1", name), &UNSHARE))
+}
+
+/// State letter and parent of a process
+fn stat(pid: u32) -> Option<(char, u32)> {
+    let stat = fs::read_to_string(format!("/proc/{pid}/stat")).ok()?;
+    let mut fields = stat.rsplit_once(')')?.1.split_whitespace();
+    Some((fields.next()?.chars().next()?, fields.next()?.parse().ok()?))
+}
+
+fn children(parent: u32) -> Vec<u32> {
+    fs::read_dir("/proc")
+        .unwrap()
+        .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse::<u32>().ok())
+        .filter(|&pid| stat(pid).is_some_and(|(_, ppid)| ppid == parent))
+        .collect()
+}
+
+fn wait_for<T>(mut found: impl FnMut() -> Option<T>) -> T {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    loop {
+        if let Some(value) = found() {
+            return value;
+        }
+        assert!(Instant::now() < deadline, "timed out");
+        thread::sleep(Duration::from_millis(50));
+    }
+}
+
+#[test]
+fn test_pid1_reaps_orphans_and_stops_processes() {
+    let Some(mut init) = start("reap") else {
+        eprintln!("skipped, unprivileged user namespaces are not available");
+        return;
+    };
+
+    // Started by the daemon, so inside its namespace
+    let file = init.home.0.join("orphans.hcl");
+    fs::write(&file, format!("process \"orphans\" {{\n  script = \"{ORPHANING}\"\n}}\n")).unwrap();
+    init.opm(&["import", file.to_str().unwrap(), "--background"]);
+
+    let daemon = init.daemon();
+    let orphan = wait_for(|| children(daemon).into_iter().find(|pid| fs::read(format!("/proc/{pid}/cmdline")).unwrap_or_default() == b"sleep\x001000\0"));
+
+    // Killed from outside, the orphan is reaped by the daemon instead of staying defunct
+    assert_eq!(unsafe { libc::kill(orphan as i32, libc::SIGKILL) }, 0);
+    thread::sleep(Duration::from_secs(1));
+
+    let zombies: Vec<u32> = children(daemon).into_iter().filter(|&pid| stat(pid).is_some_and(|(state, _)| state == 'Z')).collect();
+    assert_eq!(zombies, Vec::<u32>::new());
+    assert!(init.log().contains("reaped exited child"), "{}", init.log());
+
+    // SIGTERM stops the process before the daemon exits, rather than the kernel killing it after
+    assert_eq!(unsafe { libc::kill(daemon as i32, libc::SIGTERM) }, 0);
+    let status = wait_for(|| init.try_wait());
+    assert!(status.success(), "{status}");
+
+    let log = init.log();
+    assert!(log.contains("stopping processes before exiting"), "{log}");
+    assert!(log.contains("stopped=orphans"), "{log}");
+}
@@ -0,0 +1,140 @@
+//! The REST verbs of a process against the API of a foreground daemon, behind its token
+
+mod common;
+
+use common::{Answer, Daemon, Home};
+
+const TOKEN: &str = "process-routes-token";
+
+impl Daemon {
+    fn request(&self, method: &str, path: &str, token: Option<&str>, body: &str) -> Answer {
+        let headers: Vec<_> = token.map(|token| ("token", token)).into_iter().collect();
+        common::request(self.port, method, path, &headers, body)
+    }
+
+    /// Status of a request sent with the token
+    fn status(&self, method: &str, path: &str) -> u16 { self.request(method, path, Some(TOKEN), "").status }
+
+    fn create(&self, name: &str) {
+        let body = format!("{{\"name\":\"{name}\",\"script\":\"sleep 60\",\"path\":\"{}\"}}", self.home.0.display());
+        assert_eq!(self.request("POST", "/process/create", Some(TOKEN), &body).status, 200);
+    }
+}
+
+/// A daemon serving the API with the token
+fn serve(name: &str) -> Daemon { Daemon::serve(Home::new("routes", name), Some(TOKEN), "", "") }
+
+#[test]
+fn test_verbs_need_the_token() {
+    let daemon = serve("auth");
+    daemon.create("guarded");
+
+    for (method, path) in [("DELETE", "/process/0"), ("POST", "/process/0/stop"), ("POST", "/process/0/restart"), ("POST", "/process/0/reload")] {
+        assert_eq!(daemon.request(method, path, None, "").status, 401, "{method} {path}");
+        assert_eq!(daemon.request(method, path, Some("wrong"), "").status, 401, "{method} {path}");
+    }
+
+    // Nothing was removed without the token
+    assert_eq!(daemon.status("GET", "/process/0/info"), 200);
+}
+
+#[test]
+fn test_verbs_on_missing_process() {
+    let daemon = serve("missing");
+
+    for (method, path) in [("DELETE", "/process/7"), ("POST", "/process/7/stop"), ("POST", "/process/7/restart"), ("POST", "/process/7/reload")] {
+        assert_eq!(daemon.status(method, path), 404, "{method} {path}");
+    }
+}
+
+#[test]
+fn test_verbs_run_the_actions() {
+    let daemon = serve("verbs");
+    daemon.create("verbs");
+
+    let restart = daemon.request("POST", "/process/0/restart", Some(TOKEN), "");
+    assert_eq!(restart.status, 200);
+    assert!(restart.body.contains("\"action\":\"restart\""), "{}", restart.body);
+
+    assert_eq!(daemon.status("POST", "/process/0/reload"), 200);
+    assert_eq!(daemon.status("POST", "/process/0/stop?signal=SIGFOO"), 400);
+
+    let stop = daemon.request("POST", "/process/0/stop?signal=SIGINT", Some(TOKEN), "");
+    assert_eq!(stop.status, 200);
+    assert!(stop.body.contains("\"action\":\"stop\""), "{}", stop.body);
+
+    // A second delete finds nothing left to remove
+    let remove = daemon.request("DELETE", "/process/0?purge_logs=true", Some(TOKEN), "");
+    assert_eq!(remove.status, 200);
+    assert!(remove.body.contains("\"action\":\"remove\""), "{}", remove.body);
+    assert_eq!(daemon.status("DELETE", "/process/0"), 404);
+    assert_eq!(daemon.status("GET", "/process/0/info"), 404);
+}
+
+#[test]
+fn test_env_map_and_unset() {
+    let daemon = serve("env");
+    daemon.create("env");
+
+    let set = daemon.request("POST", "/process/0/env", Some(TOKEN), "{\"LOG_LEVEL\":\"debug\",\"PORT\":\"3000\"}");
+    assert_eq!(set.status, 200);
+    assert!(set.body.contains("LOG_LEVEL") && set.body.contains("PORT"), "{}", set.body);
+
+    let unset = daemon.request("DELETE", "/process/0/env/LOG_LEVEL", Some(TOKEN), "");
+    assert_eq!(unset.status, 200);
+    assert!(unset.body.contains("LOG_LEVEL"), "{}", unset.body);
+
+    // Gone from the stored env, and removing it again changes nothing
+    let env = daemon.request("GET", "/process/0/env", Some(TOKEN), "");
+    assert!(!env.body.contains("LOG_LEVEL") && env.body.contains("PORT"), "{}", env.body);
+    assert_eq!(daemon.request("DELETE", "/process/0/env/LOG_LEVEL", Some(TOKEN), "").body, "[]");
+
+    assert_eq!(daemon.status("DELETE", "/process/0/env/1BAD"), 400);
+    assert_eq!(daemon.request("POST", "/process/0/env", Some(TOKEN), "{\"1BAD\":\"x\"}").status, 400);
+    assert_eq!(daemon.status("DELETE", "/process/7/env/PORT"), 404);
+    assert_eq!(daemon.request("DELETE", "/process/0/env/PORT", None, "").status, 401);
+}
+
+#[test]
+fn test_patch_description() {
+    let daemon = serve("patch");
+    daemon.create("billing");
+
+    let body = "{\"description\":\"Legacy billing worker\\nowned by @dana\"}";
+    assert_eq!(daemon.request("PATCH", "/process/0", Some(TOKEN), body).status, 200);
+    let info = daemon.request("GET", "/process/0/info", Some(TOKEN), "");
+    assert!(info.body.contains("\"description\":\"Legacy billing worker\\nowned by @dana\""), "{}", info.body);
+
+    let search = daemon.request("GET", "/search?q=dana", Some(TOKEN), "");
+    assert!(search.body.contains("\"name\":\"billing\""), "{}", search.body);
+
+    // Too long or unknown fields change nothing, null removes it
+    let long = format!("{{\"description\":\"{}\"}}", "x".repeat(1025));
+    assert_eq!(daemon.request("PATCH", "/process/0", Some(TOKEN), &long).status, 400);
+    assert_eq!(daemon.request("PATCH", "/process/0", Some(TOKEN), "{\"descr\":\"x\"}").status, 422);
+    assert!(daemon.request("GET", "/process/0/info", Some(TOKEN), "").body.contains("@dana"));
+
+    assert_eq!(daemon.request("PATCH", "/process/0", Some(TOKEN), "{\"description\":null}").status, 200);
+    assert!(!daemon.request("GET", "/process/0/info", Some(TOKEN), "").body.contains("description"));
+
+    assert_eq!(daemon.request("PATCH", "/process/7", Some(TOKEN), "{}").status, 404);
+    assert_eq!(daemon.request("PATCH", "/process/0", None, "{}").status, 401);
+}
+
+#[test]
+fn test_patch_group() {
+    let daemon = serve("group");
+    daemon.create("mailer");
+
+    assert_eq!(daemon.request("PATCH", "/process/0", Some(TOKEN), "{\"group\":\"api\"}").status, 200);
+    let list = daemon.request("GET", "/list", Some(TOKEN), "");
+    assert!(list.body.contains("\"group\":\"api\""), "{}", list.body);
+
+    // An invalid group fails the whole patch
+    let patch = "{\"description\":\"Sends mail\",\"group\":\"api web\"}";
+    assert_eq!(daemon.request("PATCH", "/process/0", Some(TOKEN), patch).status, 400);
+    assert!(!daemon.request("GET", "/process/0/info", Some(TOKEN), "").body.contains("Sends mail"));
+
+    assert_eq!(daemon.request("PATCH", "/process/0", Some(TOKEN), "{\"group\":null}").status, 200);
+    assert!(!daemon.request("GET", "/list", Some(TOKEN), "").body.contains("\"group\""));
+}
@@ -0,0 +1,63 @@
+//! WebUI logins against the API of a foreground daemon, the token traded for a session cookie
+
+mod common;
+
+use common::{Answer, Daemon, Home};
+
+use std::{thread, time::Duration};
+
+const TOKEN: &str = "session-test-token";
+
+impl Daemon {
+    fn login(&self, token: &str) -> Answer { common::request(self.port, "POST", "/login", &[], &format!("{{\"token\":\"{token}\"}}")) }
+
+    /// Status of an authenticated route sent with `headers`
+    fn storage(&self, headers: &[(&str, &str)]) -> u16 { common::request(self.port, "GET", "/daemon/storage", headers, "").status }
+}
+
+/// A daemon serving the API with the token, whose sessions last `session_ttl` seconds
+fn serve(name: &str, session_ttl: u64) -> Daemon { Daemon::serve(Home::new("session", name), Some(TOKEN), "", &format!("session_ttl = {session_ttl}\n")) }
+
+/// `opm_session=<id>` out of a `Set-Cookie` header
+fn session(answer: &Answer) -> String {
+    let cookie = answer.headers("set-cookie").into_iter().find(|cookie| cookie.starts_with("opm_session=")).expect("no session cookie");
+    cookie.split(';').next().unwrap().to_string()
+}
+
+#[test]
+fn test_header_and_cookie_auth() {
+    let daemon = serve("auth", 3600);
+
+    assert_eq!(daemon.storage(&[]), 401);
+    assert_eq!(daemon.storage(&[("token", TOKEN)]), 200);
+    assert_eq!(daemon.storage(&[("token", "wrong")]), 401);
+
+    let refused = daemon.login("wrong");
+    assert_eq!(refused.status, 401);
+    assert!(refused.headers("set-cookie").is_empty());
+
+    let login = daemon.login(TOKEN);
+    assert_eq!(login.status, 200);
+    let cookie = login.headers("set-cookie").into_iter().find(|cookie| cookie.starts_with("opm_session=")).unwrap();
+    assert!(cookie.contains("HttpOnly") && cookie.contains("SameSite=Strict"), "{cookie}");
+
+    let session = session(&login);
+    assert_eq!(daemon.storage(&[("Cookie", &session)]), 200);
+    assert_eq!(daemon.storage(&[("Cookie", "opm_session=forged")]), 401);
+
+    // Logging out ends the session on the daemon, not only the cookie in the browser
+    assert_eq!(common::request(daemon.port, "POST", "/logout", &[("Cookie", &session)], "").status, 204);
+    assert_eq!(daemon.storage(&[("Cookie", &session)]), 401);
+    assert_eq!(daemon.storage(&[("token", TOKEN)]), 200);
+}
+
+#[test]
+fn test_session_expiry() {
+    let daemon = serve("expiry", 1);
+
+    let session = session(&daemon.login(TOKEN));
+    assert_eq!(daemon.storage(&[("Cookie", &session)]), 200);
+
+    thread::sleep(Duration::from_millis(1500));
+    assert_eq!(daemon.storage(&[("Cookie", &session)]), 401);
+}
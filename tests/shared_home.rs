@@ -0,0 +1,72 @@
+//! An opm home another host uses too, as two machines sharing `~/.opm` over NFS would leave it
+
+mod common;
+
+use chrono::{TimeDelta, Utc};
+use common::Home;
+use serde_json::json;
+
+use std::{fs, path::PathBuf, process::Output};
+
+const OTHER: &str = "other-host (0123456789ab)";
+
+impl Home {
+    fn file(&self, name: &str) -> PathBuf { self.0.join(".opm").join(name) }
+
+    /// A daemon of another host, refreshed `age` ago
+    fn lock(&self, age: TimeDelta) {
+        fs::write(self.file("daemon.pid"), "4242").unwrap();
+        fs::write(self.file("daemon.lock"), json!({"host": OTHER, "pid": 4242, "refreshed": Utc::now() - age}).to_string()).unwrap();
+    }
+
+    fn shared(&self) -> Vec<String> {
+        let health: serde_json::Value = serde_json::from_slice(&self.opm(&["daemon", "health", "--format", "json"]).stdout).unwrap();
+        serde_json::from_value(health["shared"].clone()).unwrap()
+    }
+}
+
+fn text(output: &Output) -> String { format!("{}{}", String::from_utf8_lossy(&output.stdout), String::from_utf8_lossy(&output.stderr)) }
+
+#[test]
+fn test_live_lock_of_other_host_refuses_daemon() {
+    let home = Home::new("shared", "live");
+    home.opm(&["list"]);
+    home.lock(TimeDelta::seconds(5));
+
+    let start = home.command().args(["daemon", "start"]).output().unwrap();
+    assert!(!start.status.success() && text(&start).contains(OTHER), "{}", text(&start));
+
+    let stop = home.command().args(["daemon", "stop"]).output().unwrap();
+    assert!(!stop.status.success() && text(&stop).contains(OTHER), "{}", text(&stop));
+
+    // Pid 4242 is not running here, which must not pass for a stale pid file
+    assert!(home.file("daemon.pid").exists());
+    assert_eq!(home.shared().len(), 1);
+    assert!(home.shared()[0].contains(&format!("is held by {OTHER}")), "{:?}", home.shared());
+}
+
+#[test]
+fn test_dump_saved_by_other_host_is_reported() {
+    let home = Home::new("shared", "dump");
+    home.opm(&["start", "sleep 300", "--name", "app"]);
+    assert!(home.shared().is_empty());
+
+    let dump = fs::read_to_string(home.file("process.dump")).unwrap();
+    let (start, end) = (dump.find("host:Some(\"").unwrap() + 11, dump.rfind("\")").unwrap());
+    fs::write(home.file("process.dump"), format!("{}{OTHER}{}", &dump[..start], &dump[end..])).unwrap();
+
+    assert_eq!(home.shared(), vec![format!("the dump was last saved by {OTHER}")]);
+
+    // Saving on this host hands the dump back
+    home.opm(&["restart", "app"]);
+    assert!(home.shared().is_empty());
+}
+
+#[test]
+fn test_stale_lock_of_other_host_is_only_reported() {
+    let home = Home::new("shared", "stale");
+    home.opm(&["list"]);
+    home.lock(TimeDelta::minutes(10));
+
+    assert!(home.shared()[0].contains(&format!("was left by {OTHER}")), "{:?}", home.shared());
+}
@@ -0,0 +1,71 @@
+//! The API of a foreground daemon keeps answering while a process takes its time to stop
+
+mod common;
+
+use common::{Daemon, Home};
+
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+/// How long the daemon waits on a SIGTERM before sending SIGKILL
+const KILL_TIMEOUT: Duration = Duration::from_secs(3);
+
+impl Daemon {
+    /// Add a process that ignores SIGTERM, so stopping it lasts the whole kill timeout
+    fn create_stubborn(&self, name: &str) {
+        let body = format!(
+            "{{\"name\":\"{name}\",\"script\":\"trap '' TERM; exec sleep 60\",\"path\":\"{}\"}}",
+            self.home.0.display()
+        );
+        assert_eq!(status(self.port, "POST", "/process/create", &body), 200);
+    }
+}
+
+/// A daemon serving the API without a token
+fn serve(name: &str) -> Daemon { Daemon::serve(Home::new("slow", name), None, &format!("kill_timeout = {}\n", KILL_TIMEOUT.as_millis()), "") }
+
+/// Status of the answer
+fn status(port: u16, method: &str, path: &str, body: &str) -> u16 { common::request(port, method, path, &[], body).status }
+
+/// Time `GET /list` takes while `action` runs against the daemon, and how long the action itself took
+fn list_during(daemon: &Daemon, method: &str, path: &str, body: &'static str) -> (Duration, Duration) {
+    let (port, method, path) = (daemon.port, method.to_string(), path.to_string());
+    let action = thread::spawn(move || {
+        let started = Instant::now();
+        assert_eq!(status(port, &method, &path, body), 200);
+        started.elapsed()
+    });
+
+    // Let the stop get to waiting on the process
+    thread::sleep(Duration::from_millis(500));
+    let started = Instant::now();
+    assert_eq!(status(daemon.port, "GET", "/list", ""), 200);
+    let list = started.elapsed();
+
+    (list, action.join().unwrap())
+}
+
+#[test]
+fn test_list_answers_during_slow_stop() {
+    let daemon = serve("stop");
+    daemon.create_stubborn("stubborn");
+
+    let (list, stop) = list_during(&daemon, "POST", "/process/0/action", "{\"method\":\"stop\"}");
+    assert!(stop >= KILL_TIMEOUT, "the stop waited {stop:?}");
+    assert!(list < Duration::from_secs(1), "listing took {list:?} behind a stop");
+}
+
+#[test]
+fn test_bulk_stop_waits_once() {
+    let daemon = serve("bulk");
+    for name in ["first", "second", "third"] {
+        daemon.create_stubborn(name);
+    }
+
+    let (list, stop) = list_during(&daemon, "POST", "/process/bulk-action", "{\"method\":\"stop\",\"all\":true}");
+    assert!(stop >= KILL_TIMEOUT, "the stop waited {stop:?}");
+    assert!(stop < KILL_TIMEOUT * 2, "the processes were waited on one after another, {stop:?}");
+    assert!(list < Duration::from_secs(1), "listing took {list:?} behind a stop");
+}
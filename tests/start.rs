@@ -0,0 +1,54 @@
+//! `opm start --name` with a name that is already taken, through the opm binary
+
+mod common;
+
+use common::Home;
+
+impl Home {
+    fn list(&self) -> Vec<serde_json::Value> { serde_json::from_slice(&self.opm(&["list", "--format", "json"]).stdout).unwrap() }
+}
+
+#[test]
+fn test_start_same_command_restarts() {
+    let home = Home::new("start", "same");
+    home.opm(&["start", "sleep 300", "--name", "app"]);
+    let pid = home.details("app")["pid"].clone();
+
+    home.opm(&["start", "sleep 300", "--name", "app"]);
+
+    assert_eq!(home.list().len(), 1);
+    assert_ne!(home.details("app")["pid"], pid);
+}
+
+#[test]
+fn test_start_other_command_fails() {
+    let home = Home::new("start", "differs");
+    home.opm(&["start", "sleep 300", "--name", "app"]);
+    let pid = home.details("app")["pid"].clone();
+
+    let output = home.run(&["start", "sleep 301", "--name", "app"]);
+    let text = format!("{}{}", String::from_utf8_lossy(&output.stdout), String::from_utf8_lossy(&output.stderr));
+
+    assert!(!output.status.success(), "{text}");
+    assert!(text.contains("- sleep 300") && text.contains("+ sleep 301") && text.contains("--force-update"), "{text}");
+
+    // The process keeps running its command, untouched
+    let details = home.details("app");
+    assert_eq!(details["pid"], pid);
+    assert!(details["command"].as_str().unwrap().contains("sleep 300"), "{details}");
+    assert_eq!(home.list().len(), 1);
+}
+
+#[test]
+fn test_start_force_update_replaces_command() {
+    let home = Home::new("start", "force");
+    home.opm(&["start", "sleep 300", "--name", "app"]);
+    let pid = home.details("app")["pid"].clone();
+
+    home.opm(&["start", "sleep 301", "--name", "app", "--force-update"]);
+
+    let details = home.details("app");
+    assert!(details["command"].as_str().unwrap().contains("sleep 301"), "{details}");
+    assert_ne!(details["pid"], pid);
+    assert_eq!(home.list().len(), 1);
+}
@@ -0,0 +1,85 @@
+//! Spawns real commands through the opm binary, with and without `--supervised`
+
+mod common;
+
+use common::Home;
+
+use std::{
+    fs,
+    path::Path,
+    thread,
+    time::{Duration, Instant},
+};
+
+impl Home {
+    fn log(&self, name: &str, kind: &str) -> String { fs::read_to_string(self.0.join(format!(".opm/logs/{name}-{kind}.log"))).unwrap_or_default() }
+
+    fn state(&self, name: &str) -> Option<serde_json::Value> {
+        let state = fs::read(self.0.join(format!(".opm/supervised/{name}.json"))).ok()?;
+        serde_json::from_slice(&state).ok()
+    }
+}
+
+fn wait_for(what: &str, mut check: impl FnMut() -> bool) {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while !check() {
+        assert!(Instant::now() < deadline, "timed out waiting for {what}");
+        thread::sleep(Duration::from_millis(50));
+    }
+}
+
+/// Every line starts with an RFC 3339 timestamp followed by a space
+fn timestamped(log: &str) -> bool {
+    !log.is_empty() && log.lines().all(|line| line.split_once(' ').is_some_and(|(time, _)| chrono::DateTime::parse_from_rfc3339(time).is_ok()))
+}
+
+fn alive(pid: i64) -> bool { Path::new(&format!("/proc/{pid}")).exists() }
+
+#[test]
+fn test_supervised_process() {
+    let home = Home::new("supervise", "running");
+    home.opm(&["start", "echo hello; echo oops >&2; sleep 30", "--name", "app", "--supervised"]);
+
+    wait_for("both logs", || !home.log("app", "out").is_empty() && !home.log("app", "error").is_empty());
+    assert!(timestamped(&home.log("app", "out")) && home.log("app", "out").ends_with(" hello\n"));
+    assert!(timestamped(&home.log("app", "error")) && home.log("app", "error").ends_with(" oops\n"));
+
+    let state = home.state("app").unwrap();
+    let supervisor = state["supervisor"].as_i64().unwrap();
+    assert!(alive(supervisor));
+    assert!(state["exit_code"].is_null());
+
+    let details = home.details("app");
+    assert_eq!(details["supervised"], true);
+    assert!(details["status"] == "starting" || details["status"] == "online");
+
+    // Stopping the command ends its supervisor too, after it recorded how the command exited
+    home.opm(&["stop", "app"]);
+    wait_for("the supervisor to exit", || !alive(supervisor));
+    assert!(home.state("app").unwrap()["exit_code"].is_i64());
+}
+
+#[test]
+fn test_supervised_exit_code() {
+    let home = Home::new("supervise", "exit");
+    home.opm(&["start", "echo bye; exit 3", "--name", "app", "--supervised"]);
+
+    wait_for("the exit to be recorded", || home.state("app").is_some_and(|state| state["exit_code"] == 3));
+    assert!(home.state("app").unwrap()["exited"].is_string());
+    assert!(timestamped(&home.log("app", "out")));
+}
+
+#[test]
+fn test_unsupervised_process() {
+    let home = Home::new("supervise", "plain");
+    home.opm(&["start", "echo hello; sleep 30", "--name", "app"]);
+
+    wait_for("the log", || !home.log("app", "out").is_empty());
+    // Output goes to the log as written, and no supervisor keeps state for it
+    assert_eq!(home.log("app", "out"), "hello\n");
+    assert!(home.state("app").is_none());
+
+    let details = home.details("app");
+    assert_eq!(details["supervised"], false);
+    assert!(details["status"] == "starting" || details["status"] == "online");
+}